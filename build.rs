@@ -32,7 +32,7 @@ fn main() {
 //
 // This file is automatically generated by build.rs from metadata.schema.json.
 
-#![allow(unused)]
+#![allow(unused, clippy::all)]
 "#;
     let rust_file_contents = format!("{}{}", documentation, rust_code);
 
@@ -0,0 +1,14 @@
+//! Feeds arbitrary bytes to the metadata parser, which must reject malformed
+//! metadata with an error rather than panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Metadata files are read as UTF-8, so other input never reaches the
+    // parser.
+    if let Ok(contents) = std::str::from_utf8(data) {
+        let _ = c_rust_program_pairs::parse_str(contents);
+    }
+});
@@ -3,7 +3,26 @@
 //! This module defines the data structures used to parse command line
 //! arguments when running the program.
 
-use clap::{Parser, Subcommand};
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Args, CommandFactory, Parser, Subcommand, error::ErrorKind};
+
+use crate::corpus::{
+    DeleteScope,
+    analyze::TranslationOrigin,
+    build::CBuildMode,
+    config::DownloadConfig,
+    downloader::{self, ConflictPolicy, DownloadOptions, GitBackend, Layout, LinkMode},
+    export::{ExportFormat, Granularity},
+    list::ListSort,
+    metadata::MetadataKind,
+    report::ErrorFormat,
+    schema::{ExtensionFilter, Language},
+    show::ShowLayout,
+    site::ReportFormat,
+    split::Materialize,
+    transform::Transform,
+};
 
 /// This struct represents the top-level CLI entry point for the tool.
 #[derive(Parser)]
@@ -11,17 +30,913 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// How to print the error that ends a command on stderr.
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    pub error_format: ErrorFormat,
+
+    /// Print the results of `download`, `demo`, `list`, `stats`, `lint`,
+    /// `delete`, `build`, `cache`, `doctor`, and `audit` as JSON on stdout,
+    /// and errors as JSON on stderr; other commands reject it.
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Corpus root, holding `metadata/`, to run in instead of the current
+    /// directory; overrides `root` in `corpus.toml`.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Number of threads used for parallel work; overrides `jobs` in
+    /// `corpus.toml`.
+    #[arg(long, global = true, value_name = "N")]
+    pub jobs: Option<usize>,
+
+    /// Metadata schema to validate against instead of the one built into the
+    /// binary, such as an edited `metadata/metadata.schema.json`.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub schema: Option<PathBuf>,
+
+    /// Directory holding the metadata, with `project/`, `individual/`, and
+    /// `demo/` within it, to read instead of the corpus root's `metadata/`.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub metadata_dir: Option<PathBuf>,
 }
 
 /// This struct represents the different commands available.
 #[derive(Subcommand)]
 pub enum Commands {
     /// Downloads a subset of the corpus; used for demonstration.
-    Demo,
+    Demo(DownloadArgs),
 
     /// Downloads all C-Rust program pairs.
-    Download,
+    Download(DownloadArgs),
+
+    /// Watches the metadata and downloads the pairs that are added or
+    /// edited, until interrupted.
+    Watch(DownloadArgs),
+
+    /// Browses the program pairs in an interactive terminal interface and
+    /// downloads the selected ones.
+    Tui(DownloadArgs),
+
+    /// Serves the corpus over HTTP, so that others can list program pairs,
+    /// fetch their metadata and archives, and trigger downloads.
+    Serve(ServeArgs),
+
+    /// Delete the `program_pairs` and `repository_clones` directories, or
+    /// only the parts selected by the flags.
+    Delete(DeleteArgs),
+
+    /// Manages the repository clone cache in `repository_clones/`.
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Validates metadata files against the schema and reports every
+    /// violation.
+    Lint {
+        /// Metadata files or directories to check; defaults to every
+        /// metadata directory.
+        paths: Vec<PathBuf>,
+
+        /// Also check that the C and Rust programs of each downloaded pair
+        /// implement the same tool, flagging Rust repositories that look
+        /// like wrappers, never mention the program, or lack its options.
+        #[arg(long)]
+        semantic: bool,
+    },
+
+    /// Lists the program pairs in the metadata.
+    List {
+        /// How to order the program pairs.
+        #[arg(long, value_enum, default_value_t = ListSort::Name)]
+        sort: ListSort,
+
+        /// Only list the pairs with this tag; may be repeated or
+        /// comma-separated to list pairs with any of them.
+        #[arg(long, value_name = "TAG", value_delimiter = ',')]
+        tag: Vec<String>,
+    },
+
+    /// Searches program names and descriptions, and optionally the sources of
+    /// downloaded pairs, for text.
+    Search {
+        /// Text to search for, ignoring case.
+        query: String,
+
+        /// Also search the sources of the pairs in `program_pairs/`.
+        #[arg(long)]
+        code: bool,
+    },
+
+    /// Queries GitHub and GitLab for repository information, such as stars,
+    /// and stores it in `metadata/enriched/`.
+    Enrich,
+
+    /// Checks that every repository, source, and documentation URL in the
+    /// metadata can be reached, and reports dead links and renamed or
+    /// archived repositories.
+    Audit,
+    /// Compares the commit each downloaded pair was copied from with the
+    /// current head of its upstream repository, and lists the pairs whose
+    /// upstream has moved and how many commits behind they are.
+    Outdated,
+
+    /// Searches GitHub for Rust rewrites of C tools and writes them as draft
+    /// metadata entries to review.
+    Discover {
+        /// Names of the C tools to search rewrites of, such as `grep`; may be
+        /// repeated or comma-separated.  Defaults to the C programs in the
+        /// metadata.
+        #[arg(long, value_name = "TOOL", value_delimiter = ',')]
+        seed: Vec<String>,
+
+        /// File to write the draft metadata entries to.
+        #[arg(long, short, default_value = "metadata/drafts/discovered.json")]
+        output: PathBuf,
+
+        /// Maximum number of repositories to consider for each tool.
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        limit: usize,
+    },
+
+    /// Helps write metadata files.
+    Metadata {
+        #[command(subcommand)]
+        command: MetadataCommands,
+    },
+
+    /// Creates a skeleton metadata file with example values to replace.
+    New {
+        /// Whether the file describes a project with several programs or
+        /// individual programs.
+        #[arg(long, value_enum)]
+        kind: MetadataKind,
+
+        /// Name of the project or program; the file is named after it.
+        name: String,
+    },
+
+    /// Renames a program pair in the metadata, the skip list, and
+    /// `program_pairs/`, along with its manifest.
+    Rename {
+        /// Current name of the program pair.
+        old: String,
+
+        /// New name of the program pair.
+        new: String,
+    },
+
+    /// Reports lines of code, comments, and blank lines, or approximate token
+    /// counts, of downloaded pairs.
+    Stats {
+        /// Also report counts for every source file.
+        #[arg(long)]
+        detailed: bool,
+
+        /// Report approximate token counts instead of line counts.
+        #[arg(long)]
+        tokens: bool,
+
+        /// List the pairs whose C and Rust sources together exceed this many
+        /// tokens, such as a model's context window.
+        #[arg(long, value_name = "N", requires = "tokens")]
+        max_tokens: Option<usize>,
+
+        /// Also report the totals of the pairs with each tag, as recorded in
+        /// their manifests when they were downloaded.
+        #[arg(long)]
+        by_tag: bool,
+
+        /// Also report the `unsafe` blocks, `unsafe fn`s, raw pointer types,
+        /// and `extern "C"` items of every Rust program.
+        #[arg(long = "unsafe")]
+        unsafe_code: bool,
+
+        /// Also report the number, average length, and approximate
+        /// cyclomatic complexity of the functions of every C program.
+        #[arg(long)]
+        complexity: bool,
+
+        /// Also report how much disk space identical files of downloaded
+        /// pairs take up, and how much `--link store` saves.
+        #[arg(long)]
+        dedup: bool,
+    },
+
+    /// Pairs the C and Rust functions of a downloaded program pair by name.
+    Diff {
+        /// Name of the program pair to compare.
+        pair: String,
+    },
+
+    /// Prints a source file of a downloaded program pair next to the
+    /// corresponding file of the pair's other program, with syntax
+    /// highlighting.
+    Show {
+        /// Name of the program pair to show.
+        pair: String,
+
+        /// Path or name of a C or Rust file of the pair; defaults to the C
+        /// file defining `main`.
+        file: Option<String>,
+
+        /// How to lay out the two files.
+        #[arg(long, value_enum, default_value_t = ShowLayout::SideBySide)]
+        layout: ShowLayout,
+
+        /// Width of the output in columns; defaults to the terminal's width.
+        #[arg(long, value_name = "COLUMNS")]
+        width: Option<usize>,
+    },
+
+    /// Lists the functions of a downloaded program pair, to help write its
+    /// `function_mappings`.
+    Functions {
+        /// Name of the program pair.
+        pair: String,
+
+        /// Print the C and Rust functions whose names match as
+        /// `function_mappings` to paste into the pair's metadata.
+        #[arg(long)]
+        suggest: bool,
+    },
+
+    /// Partitions the downloaded program pairs into training, validation,
+    /// and test sets, recorded in `program_pairs/splits/`.
+    Split {
+        /// Fraction of pairs in the training set.
+        #[arg(long, default_value_t = 0.8)]
+        train: f64,
+
+        /// Fraction of pairs in the test set; the remaining pairs form the
+        /// validation set.
+        #[arg(long, default_value_t = 0.2)]
+        test: f64,
+
+        /// Seed determining which pairs go into which set.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// Also create a directory per split holding copies of, or links
+        /// to, its pairs.
+        #[arg(long, value_enum)]
+        materialize: Option<Materialize>,
+    },
+
+    /// Exports the downloaded program pairs, with their metadata and source
+    /// text, as a dataset for machine learning.
+    Export {
+        /// The file format of the dataset.
+        #[arg(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+        format: ExportFormat,
+
+        /// Whether each record describes a program pair or a source file;
+        /// `alignment` records always describe a program pair.
+        #[arg(long, value_enum, default_value_t = Granularity::Pair)]
+        granularity: Granularity,
+
+        /// Only export the pairs whose Rust program has one of these
+        /// translation origins, as recorded by `analyze
+        /// --translation-origin`; may be repeated.
+        #[arg(long, value_enum)]
+        origin: Vec<TranslationOrigin>,
+
+        /// File to write the dataset to; defaults to stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Checks the environment, such as write permissions, proxy settings,
+    /// and connectivity, and suggests fixes for the problems found.
+    Doctor,
+
+    /// Creates the metadata directories and schema of a corpus root in the
+    /// current directory, along with the demo metadata.
+    Init,
+
+    /// Loads the metadata, provenance, and statistics of every program pair
+    /// into a SQLite database, replacing it if it exists.
+    #[cfg(feature = "index")]
+    Index {
+        /// The database file to write.
+        #[arg(long, default_value = "corpus.db")]
+        db: PathBuf,
+    },
+
+    /// Renders the downloaded program pairs as a report, such as a static
+    /// HTML site to publish on GitHub Pages.
+    Report {
+        /// The output format of the report.
+        #[arg(long, value_enum, default_value_t = ReportFormat::Html)]
+        format: ReportFormat,
+
+        /// Directory to write the report to.
+        #[arg(long, short, default_value = "site")]
+        output: PathBuf,
+    },
+
+    /// Analyzes downloaded program pairs and records the results in
+    /// `program_pairs/analysis.json`.
+    Analyze {
+        /// Name of the program pair to analyze; defaults to every pair.
+        pair: Option<String>,
+
+        /// Classify whether each Rust program was machine-translated from C,
+        /// such as by c2rust, or written by hand.
+        #[arg(long, required_unless_present = "classify")]
+        translation_origin: bool,
+
+        /// Classify each pair's domain, such as compression or networking,
+        /// from keywords in its description and sources, and tag the pair
+        /// with it.
+        #[arg(long)]
+        classify: bool,
+    },
+
+    /// Advances the commits repositories are locked to in `corpus.lock`.
+    Update {
+        /// Fetch the latest commit of each locked repository's default
+        /// branch and lock it, so the next download checks it out.
+        #[arg(long, required = true)]
+        refresh_lock: bool,
+
+        /// URLs of the repositories to refresh; defaults to every locked
+        /// repository.
+        repositories: Vec<String>,
+    },
+
+    /// Records the metadata of every program pair and the commits the
+    /// downloaded pairs were copied from in `releases/corpus-v<version>.json`.
+    Snapshot {
+        /// The version of the release, such as `1.2`.
+        version: String,
+    },
+
+    /// Prints release notes listing the program pairs added, removed, and
+    /// changed between two snapshots.
+    Changelog {
+        /// The earlier snapshot, as a version or a path.
+        from: String,
+
+        /// The later snapshot, as a version or a path.
+        to: String,
+
+        /// File to write the release notes to; defaults to stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Commits the downloaded program pairs, with their manifests, as a
+    /// snapshot in `publish.git/` and pushes it to a git remote.
+    Publish {
+        /// URL of the git repository to push the snapshot to.
+        #[arg(long, value_name = "URL")]
+        remote: String,
+
+        /// The snapshot's commit message; defaults to the number of program
+        /// pairs it holds.
+        #[arg(long, short)]
+        message: Option<String>,
+
+        /// Proxy to connect to the remote through; by default, the proxy
+        /// downloads use.
+        #[arg(long, value_name = "URL")]
+        proxy: Option<String>,
+
+        /// File or directory of CA certificates used to verify the remote;
+        /// by default, the CA bundle downloads use.
+        #[arg(long, value_name = "PATH")]
+        ca_bundle: Option<PathBuf>,
+    },
+
+    /// Attempts to compile downloaded program pairs and records the results.
+    Build {
+        /// Name of the program pair to build.
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        pair: Option<String>,
+
+        /// Build every downloaded program pair.
+        #[arg(long)]
+        all: bool,
+
+        /// How to compile the C program.
+        #[arg(long, value_enum, default_value_t = CBuildMode::PerFile)]
+        c_mode: CBuildMode,
+
+        /// The C compiler used to compile each file.
+        #[arg(long, default_value = "cc")]
+        cc: String,
+    },
+}
+
+impl Cli {
+    /// Rejects `--json` for the commands that only print text, rather than
+    /// letting them ignore it.
+    ///
+    /// # Returns
+    ///
+    /// The arguments, or a [`clap::Error`] if `--json` was given to such a
+    /// command.
+    pub fn check_json(self) -> Result<Self, clap::Error> {
+        if self.json && !self.command.as_ref().is_none_or(Commands::prints_json) {
+            return Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "--json is only supported by `download`, `demo`, `list`, `stats`, `lint`, \
+                 `delete`, `build`, `cache`, `doctor`, and `audit`",
+            ));
+        }
+        Ok(self)
+    }
+}
+
+impl Commands {
+    /// Whether the command prints its results as JSON with `--json`.
+    pub fn prints_json(&self) -> bool {
+        match self {
+            Commands::Demo(_)
+            | Commands::Download(_)
+            | Commands::Delete(_)
+            | Commands::Lint { .. }
+            | Commands::List { .. }
+            | Commands::Stats { .. }
+            | Commands::Build { .. }
+            | Commands::Cache { .. }
+            | Commands::Doctor
+            | Commands::Audit => true,
+            #[cfg(feature = "index")]
+            Commands::Index { .. } => false,
+            Commands::Watch(_)
+            | Commands::Tui(_)
+            | Commands::Serve(_)
+            | Commands::Search { .. }
+            | Commands::Enrich
+            | Commands::Outdated
+            | Commands::Discover { .. }
+            | Commands::Metadata { .. }
+            | Commands::New { .. }
+            | Commands::Rename { .. }
+            | Commands::Diff { .. }
+            | Commands::Show { .. }
+            | Commands::Functions { .. }
+            | Commands::Split { .. }
+            | Commands::Export { .. }
+            | Commands::Init
+            | Commands::Report { .. }
+            | Commands::Analyze { .. }
+            | Commands::Update { .. }
+            | Commands::Snapshot { .. }
+            | Commands::Changelog { .. }
+            | Commands::Publish { .. } => false,
+        }
+    }
+
+    /// Whether the command reads the metadata directories, so it can only
+    /// run in a corpus root.
+    pub fn reads_metadata(&self) -> bool {
+        match self {
+            Commands::Download(_)
+            | Commands::Watch(_)
+            | Commands::Tui(_)
+            | Commands::Serve(_)
+            | Commands::List { .. }
+            | Commands::Search { .. }
+            | Commands::Enrich
+            | Commands::Audit
+            | Commands::New { .. }
+            | Commands::Rename { .. }
+            | Commands::Export { .. }
+            | Commands::Report { .. }
+            | Commands::Analyze { .. }
+            | Commands::Snapshot { .. } => true,
+            #[cfg(feature = "index")]
+            Commands::Index { .. } => true,
+            // Explicit paths may lie outside the corpus root.
+            Commands::Lint { paths, .. } => paths.is_empty(),
+            // Clones are only kept or reported by whether the metadata still
+            // references them when asked to.
+            Commands::Delete(args) => args.unused,
+            Commands::Cache { command } => matches!(command, CacheCommands::Size),
+            // The demo writes its built-in metadata if it is missing, and
+            // discovery skips the metadata it cannot read.
+            Commands::Demo(_)
+            | Commands::Init
+            | Commands::Doctor
+            | Commands::Discover { .. }
+            | Commands::Outdated
+            | Commands::Metadata { .. }
+            | Commands::Stats { .. }
+            | Commands::Diff { .. }
+            | Commands::Show { .. }
+            | Commands::Functions { .. }
+            | Commands::Split { .. }
+            | Commands::Update { .. }
+            | Commands::Changelog { .. }
+            | Commands::Publish { .. }
+            | Commands::Build { .. } => false,
+        }
+    }
+}
+
+/// This enum represents the subcommands of the `cache` command.
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// Reports the disk usage of each cached clone.
+    Size,
+
+    /// Removes least recently used clones until the cache fits in a size.
+    Prune {
+        /// The maximum cache size, such as `10G` or `500MiB`.
+        #[arg(long)]
+        max_size: String,
+    },
+
+    /// Removes the cached clone of a repository.
+    Clear {
+        /// The repository's URL, its path in `repository_clones/`, or its name.
+        repository: String,
+    },
+
+    /// Checks that every clone has a resolvable HEAD and a populated working
+    /// directory.
+    Fsck {
+        /// Remove unhealthy clones, so that the next download clones them
+        /// again.
+        #[arg(long)]
+        remove: bool,
+    },
+}
+
+/// This enum represents the subcommands of the `metadata` command.
+#[derive(Subcommand)]
+pub enum MetadataCommands {
+    /// Looks up a crate on crates.io and prints the `rust_program` section of
+    /// a pair entry for it.
+    Rust {
+        /// Name of the Rust rewrite on crates.io.
+        crate_name: String,
+    },
+
+    /// Discovers a program's sources in a repository, from the module tree
+    /// of a Rust crate or the `Makefile.am` files of a C project, and prints
+    /// them as `source_paths` or writes them to a metadata file.
+    Discover {
+        /// Name of the program's executable or binary.
+        program_name: String,
+
+        /// A local checkout, or the URL of a repository to clone into
+        /// `repository_clones/`.
+        #[arg(long, value_name = "PATH|URL")]
+        repo: String,
+
+        /// Directory of the Rust crate within the repository, holding its
+        /// `Cargo.toml`, such as a member of a workspace.
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        crate_dir: PathBuf,
+
+        /// Keep only the sources of a C program defining functions reachable
+        /// from its `main`, and the headers they include.
+        #[arg(long)]
+        prune: bool,
+
+        /// Write the sources to the pair named after the program in this
+        /// metadata file, instead of printing them.
+        #[arg(long, value_name = "METADATA_FILE")]
+        write: Option<PathBuf>,
+    },
+}
+
+/// Options shared by the commands that download program pairs.  Each
+/// overrides its default from the `download` section of `corpus.toml`.
+#[derive(Args)]
+pub struct DownloadArgs {
+    /// Forbid network access and only use repositories already cached in
+    /// `repository_clones/`; other pairs are skipped.  `--offline=false`
+    /// overrides `offline` in `corpus.toml`.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub offline: Option<bool>,
+
+    /// Number of commits of history to clone, overriding the metadata's
+    /// `clone_depth`; 0 clones the full history.
+    #[arg(long, value_name = "N", conflicts_with = "full_history")]
+    pub clone_depth: Option<u64>,
+
+    /// Clone the full history of every repository, deepening cached shallow
+    /// clones; the same as `--clone-depth 0`.
+    #[arg(long)]
+    pub full_history: bool,
+
+    /// Proxy to clone through, such as `http://proxy:8080`; by default, a
+    /// proxy is detected from `HTTP_PROXY` and `HTTPS_PROXY`.
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// File or directory of CA certificates used to verify HTTPS servers.
+    #[arg(long, value_name = "PATH")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Ignore the user's global and system git configuration while
+    /// downloading, such as URL rewrites that break clones;
+    /// `--isolate-git-config=false` overrides `corpus.toml`.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub isolate_git_config: Option<bool>,
+
+    /// Also copy files with this extension from `source_paths` directories;
+    /// may be repeated or comma-separated.
+    #[arg(long, value_name = "EXT", value_delimiter = ',')]
+    pub include_extension: Vec<String>,
+
+    /// Never copy files with this extension from `source_paths` directories;
+    /// may be repeated or comma-separated.
+    #[arg(long, value_name = "EXT", value_delimiter = ',')]
+    pub exclude_extension: Vec<String>,
+
+    /// Also copy the build files closest to each program's sources: the
+    /// `Cargo.toml` of Rust programs, and the `Makefile` and `configure.ac`
+    /// of C programs; `--include-build-files=false` overrides `corpus.toml`.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub include_build_files: Option<bool>,
+
+    /// What clones repositories: the built-in libgit2 (the default), or the
+    /// system `git` binary, which supports credential helpers.
+    #[arg(long, value_enum)]
+    pub git_backend: Option<GitBackend>,
+
+    /// Abort the clone of a repository, and count its pair as failed, if it
+    /// takes longer than this many seconds.
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Maximum transfer rate of clones per second, such as `2M` or `500KiB`.
+    #[arg(long, value_name = "RATE", value_parser = downloader::parse_bandwidth)]
+    pub max_bandwidth: Option<u64>,
+
+    /// Shell command to run in each program pair's directory after it is
+    /// downloaded, with the pair's name in `PROGRAM_NAME`; the pair counts as
+    /// failed if the command fails.
+    #[arg(long, value_name = "COMMAND")]
+    pub hook: Option<String>,
+
+    /// Link files from `repository_clones/` into `program_pairs/` instead of
+    /// copying them (hard links unless `symbolic` is given, or `store` to
+    /// hard link identical files to one copy in `store/objects/`); files are
+    /// copied where links fail, such as across filesystems.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "KIND",
+        num_args = 0..=1,
+        default_missing_value = "hard"
+    )]
+    pub link: Option<LinkMode>,
+
+    /// Place every program's files the same way, overriding the metadata's
+    /// `output_directory` and `flatten`: `flat` in `c-program/` and
+    /// `rust-program/`, `mirrored` keeping the subdirectories of
+    /// `source_paths` directories, or `by-language` in `c/` and `rust/`.
+    #[arg(long, value_enum, value_name = "PRESET")]
+    pub layout: Option<Layout>,
+
+    /// Download even if the repositories to clone are estimated to need more
+    /// disk space than is available; `--skip-space-check=false` overrides
+    /// `corpus.toml`.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub skip_space_check: Option<bool>,
+
+    /// Download every program pair again, even those whose metadata, locked
+    /// commits, and options are unchanged since they were downloaded.
+    #[arg(long)]
+    pub force: bool,
+
+    /// What to do with files changed since their pair was downloaded when
+    /// the pair is downloaded again: `overwrite` them (the default), which
+    /// also needs `--force`, `skip` them, fail the pair with `error`, or
+    /// `backup` them with an `.orig` suffix before overwriting them.
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// Only download the program pairs with this tag, such as `compression`;
+    /// may be repeated or comma-separated to select pairs with any of them.
+    #[arg(long, value_name = "TAG", value_delimiter = ',')]
+    pub tag: Vec<String>,
+
+    /// Only download the program pairs whose C and Rust programs are both
+    /// written in these languages, such as `c,rust` to leave out pairs
+    /// translated from C++ or to Go or Zig; may be repeated or
+    /// comma-separated.
+    #[arg(long, value_enum, value_name = "LANGUAGE", value_delimiter = ',')]
+    pub languages: Vec<Language>,
+
+    /// Abort before downloading anything if any metadata file fails to parse
+    /// or validate, instead of skipping it and reporting it at the end;
+    /// `--strict=false` overrides `corpus.toml`.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub strict: Option<bool>,
+
+    /// Rewrite every placed C and Rust source file with these transforms,
+    /// in order, and record them in the file's provenance; may be repeated
+    /// or comma-separated.
+    #[arg(long, value_enum, value_name = "TRANSFORM", value_delimiter = ',')]
+    pub transform: Vec<Transform>,
+
+    /// Copy binary files, such as object files and images, found in
+    /// `source_paths` directories instead of skipping and reporting them;
+    /// `--allow-binary=false` overrides `corpus.toml`.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub allow_binary: Option<bool>,
+
+    /// Download the program pairs in a shuffled order instead of sorted by
+    /// name, such as to download a random sample of the corpus;
+    /// `--shuffle=false` overrides `corpus.toml`.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub shuffle: Option<bool>,
+
+    /// Seed to shuffle the program pairs with, so that the same order can be
+    /// repeated; a seed is picked and printed if none is given.
+    #[arg(long, value_name = "SEED", requires = "shuffle")]
+    pub seed: Option<u64>,
+
+    /// Only download the program pairs of this profile, defined in
+    /// `metadata/profiles.json`, or the built-in `full` (the default) or
+    /// `demo`.
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Fetch the metadata from this git repository's `metadata/` directory
+    /// into `metadata/` before downloading.
+    #[arg(long, value_name = "URL")]
+    pub metadata_repo: Option<String>,
+
+    /// Directory of the `--metadata-repo` repository to copy the metadata
+    /// from instead of its `metadata/`, such as `.` for its root.
+    #[arg(long, value_name = "PATH", requires = "metadata_repo")]
+    pub subdirectory: Option<PathBuf>,
+}
+
+impl DownloadArgs {
+    /// Converts the command-line arguments into [`DownloadOptions`].
+    ///
+    /// # Arguments
+    ///
+    /// - `config` - Defaults from `corpus.toml` for the options not given on
+    ///   the command line, including flags given as `=false`.  Extensions
+    ///   given on the command line are added to the configured ones.
+    pub fn options(&self, config: &DownloadConfig) -> DownloadOptions {
+        let defaults = config.options();
+        DownloadOptions {
+            root: defaults.root,
+            offline: self.offline.unwrap_or(defaults.offline),
+            clone_depth: if self.full_history {
+                Some(0)
+            } else {
+                self.clone_depth.or(defaults.clone_depth)
+            },
+            proxy: self.proxy.clone().or(defaults.proxy),
+            ca_bundle: self.ca_bundle.clone().or(defaults.ca_bundle),
+            isolate_git_config: self
+                .isolate_git_config
+                .unwrap_or(defaults.isolate_git_config),
+            extensions: defaults.extensions.extend(&ExtensionFilter::new(
+                &self.include_extension,
+                &self.exclude_extension,
+            )),
+            include_build_files: self
+                .include_build_files
+                .unwrap_or(defaults.include_build_files),
+            git_backend: self.git_backend.unwrap_or(defaults.git_backend),
+            timeout: self.timeout.map(Duration::from_secs).or(defaults.timeout),
+            max_bandwidth: self.max_bandwidth.or(defaults.max_bandwidth),
+            hook: self.hook.clone().or(defaults.hook),
+            link: self.link.or(defaults.link),
+            layout: self.layout.or(defaults.layout),
+            skip_space_check: self.skip_space_check.unwrap_or(defaults.skip_space_check),
+            force: self.force,
+            on_conflict: self.on_conflict.unwrap_or(defaults.on_conflict),
+            tags: if self.tag.is_empty() {
+                defaults.tags
+            } else {
+                self.tag.clone()
+            },
+            languages: if self.languages.is_empty() {
+                defaults.languages
+            } else {
+                self.languages.clone()
+            },
+            strict: self.strict.unwrap_or(defaults.strict),
+            transforms: if self.transform.is_empty() {
+                defaults.transforms
+            } else {
+                self.transform.clone()
+            },
+            allow_binary: self.allow_binary.unwrap_or(defaults.allow_binary),
+            shuffle: match self.shuffle {
+                Some(true) => Some(
+                    self.seed
+                        .or(config.seed)
+                        .unwrap_or_else(downloader::random_seed),
+                ),
+                Some(false) => None,
+                None => defaults.shuffle,
+            },
+            profile: self.profile.clone().or(defaults.profile),
+            metadata_repository: self.metadata_repo.clone().or(defaults.metadata_repository),
+            metadata_subdirectory: self.subdirectory.clone().or(defaults.metadata_subdirectory),
+        }
+    }
+}
+
+/// Options of the `delete` command, selecting what to delete.
+#[derive(Args)]
+pub struct DeleteArgs {
+    /// Only delete downloaded program pairs in `program_pairs/`.
+    #[arg(long, conflicts_with_all = ["pair", "language"])]
+    pub pairs: bool,
+
+    /// Only delete cached clones in `repository_clones/`.
+    #[arg(long)]
+    pub clones: bool,
+
+    /// Only delete this downloaded program pair.
+    #[arg(long, value_name = "NAME")]
+    pub pair: Option<String>,
+
+    /// Only delete the C or Rust half of the downloaded program pairs.
+    #[arg(long, value_enum)]
+    pub language: Option<Language>,
+
+    /// Only delete cached clones that no metadata file references.
+    #[arg(long, conflicts_with_all = ["pairs", "clones", "pair", "language", "yes"])]
+    pub unused: bool,
+
+    /// Delete without asking for confirmation.
+    #[arg(long, short)]
+    pub yes: bool,
+}
+
+/// Options of the `serve` command.
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to listen on; use `0.0.0.0:8080` to accept connections from
+    /// other machines.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub address: String,
+
+    /// Options of the downloads triggered over HTTP.
+    #[command(flatten)]
+    pub download: DownloadArgs,
+}
 
-    /// Delete the `program_pairs` and `repository_clones` directories.
-    Delete,
+impl DeleteArgs {
+    /// Converts the command-line arguments into a [`DeleteScope`].
+    pub fn scope(&self) -> DeleteScope {
+        DeleteScope {
+            pairs: self.pairs,
+            clones: self.clones,
+            pair: self.pair.clone(),
+            language: self.language.clone(),
+        }
+    }
 }
@@ -2,14 +2,95 @@
 //!
 //! This module transforms schema files into strongly-typed Rust structs.
 
+pub mod analysis;
+pub mod analyze;
+pub mod audit;
+mod automake;
+pub mod build;
+mod build_files;
+pub mod cache;
+mod call_graph;
+pub mod config;
+mod conflict;
 mod delete;
+pub mod diff;
+pub mod discover;
+pub mod doctor;
 pub mod downloader;
+pub mod effects;
+pub mod enrich;
 pub mod errors;
+pub mod export;
+pub mod functions;
+mod git_cli;
+mod git_options;
+pub mod github;
+mod hook;
+#[cfg(feature = "index")]
+pub mod index;
+pub mod init;
+pub mod license;
+pub mod lint;
+pub mod list;
+pub mod lock;
+pub mod manifest;
+pub mod metadata;
 mod metadata_structs;
+mod metrics;
+pub mod outdated;
+pub mod output;
 pub mod parser;
+mod patch;
+pub mod plan;
+pub mod profile;
+pub mod progress;
+pub mod provenance;
+pub mod publish;
+pub mod release;
+mod remote_metadata;
+pub mod rename;
+pub mod report;
+mod rust_modules;
 pub mod schema;
+pub mod search;
+mod semantic;
+pub mod serve;
+pub mod show;
+pub mod site;
+pub mod skiplist;
+mod space;
+pub mod split;
+pub mod stats;
+mod store;
+mod timings;
+pub mod transform;
+pub mod tui;
 mod utils;
+pub mod watch;
+pub mod writer;
 
-pub use delete::delete;
-pub use downloader::download_program_pairs;
-pub use parser::parse;
+pub use analyze::analyze;
+pub use audit::audit;
+pub use build::build;
+pub use delete::{DeleteScope, delete, delete_unused};
+pub use diff::diff;
+pub use discover::discover;
+pub use doctor::doctor;
+pub use downloader::{download_program_pairs, download_program_pairs_with_events};
+pub use enrich::enrich;
+pub use export::export;
+#[cfg(feature = "index")]
+pub use index::index;
+pub use init::init;
+pub use lint::lint;
+pub use list::list;
+pub use outdated::outdated;
+pub use publish::publish;
+pub use rename::rename;
+pub use search::search;
+pub use serve::serve;
+pub use show::show;
+pub use split::split;
+pub use stats::stats;
+pub use tui::tui;
+pub use watch::watch;
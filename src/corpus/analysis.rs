@@ -0,0 +1,11 @@
+//! # Source Analysis
+//!
+//! This module holds the lightweight analyses of program sources that other
+//! commands build on.  The [`ast`] module extracts the functions of C and
+//! Rust source files from their syntax trees, for `diff`, `functions`, and
+//! library users.  The `complexity` analysis counts the functions of C
+//! programs, their length, and their approximate cyclomatic complexity, as
+//! reported by `stats --complexity`.
+
+pub mod ast;
+pub(crate) mod complexity;
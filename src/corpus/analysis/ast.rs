@@ -0,0 +1,233 @@
+//! # Syntax Tree Function Extraction
+//!
+//! This module extracts the function definitions of C and Rust source files
+//! from syntax trees built with tree-sitter, whose grammars recover from
+//! errors, so that a file that does not parse still yields the functions
+//! around the error.  The function lists power `diff`, `functions`, and the
+//! complexity statistics, and library users get them as [`Function`]s.
+//!
+//! C is parsed before preprocessing, so functions in every branch of an
+//! `#if` are found, and definitions whose headers are built by macros may
+//! be missed.  Only C and Rust have grammars, so no functions are
+//! extracted from C++, Go, or Zig.
+
+use std::{fs, path::Path};
+
+use serde::Serialize;
+use tree_sitter::{Node, Parser};
+
+use crate::corpus::schema::Language;
+
+/// A function definition found in a source file.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct Function {
+    /// Name of the function.
+    pub name: String,
+    /// The function's signature with whitespace collapsed.
+    pub signature: String,
+    /// 1-based line number of the function's name.
+    pub line: usize,
+    /// 1-based line number of the end of the function's body, or of its
+    /// signature if it has no body.
+    pub end_line: usize,
+}
+
+/// Extracts the function definitions of a source file.
+///
+/// Files that cannot be read yield no functions.
+///
+/// # Arguments
+///
+/// - `path` - The source file.
+/// - `language` - The language the source file is written in.
+///
+/// # Returns
+///
+/// The functions defined in the file, in order of appearance.
+pub fn extract_file(path: &Path, language: &Language) -> Vec<Function> {
+    match fs::read(path) {
+        Ok(bytes) => extract(&String::from_utf8_lossy(&bytes), language),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Extracts the function definitions from some source code.
+///
+/// Functions are found at any depth, including methods and nested Rust
+/// functions.  Rust trait methods without a default body are included.
+///
+/// # Arguments
+///
+/// - `source` - The source code.
+/// - `language` - The language the source code is written in.
+///
+/// # Returns
+///
+/// The functions defined in `source`, in order of appearance.
+pub fn extract(source: &str, language: &Language) -> Vec<Function> {
+    let grammar = match language {
+        Language::C => tree_sitter_c::LANGUAGE,
+        Language::Rust => tree_sitter_rust::LANGUAGE,
+        Language::Cpp | Language::Go | Language::Zig => return Vec::new(),
+    };
+    let mut parser = Parser::new();
+    parser
+        .set_language(&grammar.into())
+        .expect("Unreachable because the grammars are built for this tree-sitter version");
+    let Some(tree) = parser.parse(source, None) else {
+        return Vec::new();
+    };
+
+    let mut functions = Vec::new();
+    let mut cursor = tree.walk();
+    'walk: loop {
+        if let Some(function) = function(cursor.node(), source, language) {
+            functions.push(function);
+        }
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+    functions
+}
+
+/// Reads a function definition from a syntax tree node.
+///
+/// # Arguments
+///
+/// - `node` - The node, which need not be a function definition.
+/// - `source` - The source code the node was parsed from.
+/// - `language` - The language of `source`.
+///
+/// # Returns
+///
+/// The [`Function`], or `None` if `node` does not define a named function.
+fn function(node: Node, source: &str, language: &Language) -> Option<Function> {
+    let name = match (language, node.kind()) {
+        (Language::C, "function_definition") => c_name(node.child_by_field_name("declarator")?)?,
+        (Language::Rust, "function_item" | "function_signature_item") => {
+            node.child_by_field_name("name")?
+        }
+        _ => return None,
+    };
+    let body = node.child_by_field_name("body");
+    let signature_end = body.map_or(node.end_byte(), |body| body.start_byte());
+    let signature = &source[node.start_byte()..signature_end];
+    Some(Function {
+        name: source[name.byte_range()].to_string(),
+        signature: signature
+            .trim_end_matches(';')
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+        line: name.start_position().row + 1,
+        end_line: node.end_position().row + 1,
+    })
+}
+
+/// Finds the name in the declarator of a C function definition, which may
+/// be wrapped in pointers and parentheses, as in `char *(*handler(int))(void)`.
+///
+/// # Arguments
+///
+/// - `declarator` - The definition's declarator.
+///
+/// # Returns
+///
+/// The identifier naming the function, or `None` if the declarator is not a
+/// function declarator, such as when the parser recovered from an error.
+fn c_name(mut declarator: Node) -> Option<Node> {
+    let mut is_function = false;
+    loop {
+        match declarator.kind() {
+            "identifier" => return is_function.then_some(declarator),
+            "function_declarator" => is_function = true,
+            "pointer_declarator" | "parenthesized_declarator" | "attributed_declarator" => {}
+            _ => return None,
+        }
+        declarator = declarator
+            .child_by_field_name("declarator")
+            .or_else(|| declarator.named_child(0))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that C function definitions are found while declarations,
+    /// initializers, and macros are ignored.
+    #[test]
+    fn test_extract_c() {
+        let source = r#"#include <stdio.h>
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+
+static int helper(int x);
+static int table[] = { 1, 2 };
+
+/* usage() { */
+static void __attribute__ ((noreturn))
+usage (int status)
+{
+    if (status) { puts("main() {"); }
+}
+
+int main(int argc, char **argv) {
+    return helper(argc);
+}
+
+static char *(*handler(int signal))(void) { return 0; }
+"#;
+        let functions = extract(source, &Language::C);
+        let names: Vec<_> = functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(vec!["usage", "main", "handler"], names);
+        assert_eq!(9, functions[0].line);
+        assert_eq!(12, functions[0].end_line);
+        assert_eq!(16, functions[1].end_line);
+        assert_eq!(
+            "static void __attribute__ ((noreturn)) usage (int status)",
+            functions[0].signature
+        );
+    }
+
+    /// Tests that Rust functions, including methods and trait methods
+    /// without a body, are found while function pointer types and comments
+    /// are ignored.
+    #[test]
+    fn test_extract_rust() {
+        let source = r##"// fn commented() {}
+pub fn uumain(args: &[String]) -> i32 {
+    let callback: fn(i32) -> i32 = double;
+    let label = "fn fake() {}";
+    let raw = r#"fn raw() { "quoted" }"#;
+    callback(args.len() as i32)
+}
+
+impl Parser {
+    pub(crate) fn parse<'a>(&self, input: &'a str) -> &'a str { input }
+}
+
+trait Visit {
+    fn visit(&mut self);
+}
+"##;
+        let functions = extract(source, &Language::Rust);
+        let names: Vec<_> = functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(vec!["uumain", "parse", "visit"], names);
+        assert_eq!(2, functions[0].line);
+        assert_eq!(7, functions[0].end_line);
+        assert_eq!(10, functions[1].end_line);
+        assert_eq!(
+            "pub(crate) fn parse<'a>(&self, input: &'a str) -> &'a str",
+            functions[1].signature
+        );
+        assert_eq!("fn visit(&mut self)", functions[2].signature);
+        assert_eq!(14, functions[2].end_line);
+    }
+}
@@ -0,0 +1,176 @@
+//! # C Complexity Metrics
+//!
+//! This module measures how complex the functions of C source files are:
+//! how many there are, how long they are, and their approximate cyclomatic
+//! complexity.  Functions are found by [`ast::extract`], and complexity is
+//! counted from the decision points on the lines of each function's body, so
+//! the numbers are approximate but cheap to compute.
+
+use std::{fmt, ops::AddAssign};
+
+use serde::Serialize;
+
+use crate::corpus::{analysis::ast, functions, schema::Language};
+
+/// Keywords that each add a path through a function.
+const DECISION_KEYWORDS: [&str; 4] = ["if", "for", "while", "case"];
+
+/// Operators that each add a path through a function, by short-circuiting or
+/// choosing between two values.
+const DECISION_OPERATORS: [&str; 3] = ["&&", "||", "?"];
+
+/// Complexity counts for the functions of one or more C source files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Complexity {
+    /// Function definitions.
+    pub functions: usize,
+    /// Lines spanned by the function definitions, from the name to the
+    /// closing brace.
+    pub function_lines: usize,
+    /// Cyclomatic complexity summed over the functions.
+    pub total_complexity: usize,
+    /// Cyclomatic complexity of the most complex function.
+    pub max_complexity: usize,
+}
+
+impl AddAssign for Complexity {
+    fn add_assign(&mut self, other: Self) {
+        self.functions += other.functions;
+        self.function_lines += other.function_lines;
+        self.total_complexity += other.total_complexity;
+        self.max_complexity = self.max_complexity.max(other.max_complexity);
+    }
+}
+
+impl Complexity {
+    /// Average number of lines per function, or 0 without functions.
+    pub fn average_length(&self) -> f64 {
+        average(self.function_lines, self.functions)
+    }
+
+    /// Average cyclomatic complexity per function, or 0 without functions.
+    pub fn average_complexity(&self) -> f64 {
+        average(self.total_complexity, self.functions)
+    }
+
+    /// Buckets the program by the complexity of its most complex function.
+    pub fn difficulty(&self) -> Difficulty {
+        match self.max_complexity {
+            0..=10 => Difficulty::Simple,
+            11..=20 => Difficulty::Moderate,
+            _ => Difficulty::Complex,
+        }
+    }
+}
+
+/// How difficult a program is to translate, judged by the cyclomatic
+/// complexity of its most complex function with the usual thresholds of 10
+/// and 20.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    /// Every function has a complexity of at most 10.
+    Simple,
+    /// The most complex function has a complexity of 11 to 20.
+    Moderate,
+    /// Some function has a complexity above 20.
+    Complex,
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Difficulty::Simple => "simple",
+            Difficulty::Moderate => "moderate",
+            Difficulty::Complex => "complex",
+        })
+    }
+}
+
+/// Measures the complexity of the functions in some C source code.
+///
+/// The cyclomatic complexity of a function is 1 plus its decision points:
+/// `if`, `for`, `while`, and `case` keywords, and `&&`, `||`, and `?`
+/// operators.  Comments, literals, and preprocessor directives are ignored.
+///
+/// # Arguments
+///
+/// - `source` - The C source code.
+///
+/// # Returns
+///
+/// The [`Complexity`] of the functions defined in `source`.
+pub fn measure(source: &str) -> Complexity {
+    let code = functions::strip_comments_and_literals(source, &Language::C);
+    let lines: Vec<&str> = code.lines().collect();
+
+    let mut complexity = Complexity::default();
+    for function in ast::extract(source, &Language::C) {
+        let body = lines
+            .get(function.line - 1..function.end_line.min(lines.len()))
+            .unwrap_or_default();
+        let function_complexity = 1 + body.iter().map(|line| decisions(line)).sum::<usize>();
+        complexity += Complexity {
+            functions: 1,
+            function_lines: function.end_line - function.line + 1,
+            total_complexity: function_complexity,
+            max_complexity: function_complexity,
+        };
+    }
+    complexity
+}
+
+/// Counts the decision points on a line of C code.
+fn decisions(line: &str) -> usize {
+    let keywords = line
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| DECISION_KEYWORDS.contains(word))
+        .count();
+    let operators: usize = DECISION_OPERATORS
+        .iter()
+        .map(|operator| line.matches(operator).count())
+        .sum();
+    keywords + operators
+}
+
+/// Divides a total by a count, or returns 0 if the count is 0.
+fn average(total: usize, count: usize) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        total as f64 / count as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that decision points are counted per function, ignoring those
+    /// in comments and strings.
+    #[test]
+    fn test_measure() {
+        let source = r#"
+int sign(int x) {
+    // if while for
+    if (x > 0 && x < 100) {
+        return 1;
+    } else if (x < 0) {
+        return -1;
+    }
+    return 0;
+}
+
+void greet(void) {
+    puts("if (a || b) ?");
+}
+"#;
+        let complexity = measure(source);
+        assert_eq!(2, complexity.functions);
+        assert_eq!(12, complexity.function_lines);
+        assert_eq!(5, complexity.total_complexity);
+        assert_eq!(4, complexity.max_complexity);
+        assert_eq!(6.0, complexity.average_length());
+        assert_eq!(Difficulty::Simple, complexity.difficulty());
+    }
+}
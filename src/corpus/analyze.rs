@@ -0,0 +1,761 @@
+//! # Corpus Analysis
+//!
+//! This module runs analyses over the downloaded program pairs and records
+//! their results per pair in `program_pairs/analysis.json`, so the corpus
+//! can be filtered by them.  The translation origin analysis tells Rust
+//! programs machine-translated from C, such as by c2rust, apart from
+//! idiomatic hand-written ones, from signals in their sources:
+//!
+//! - c2rust markers, such as `#[c2rust::src_loc]` attributes, the
+//!   `mutable_transmutes` lint c2rust allows, and `current_block` variables
+//!   emulating `goto`;
+//! - the share of functions defined as `unsafe extern "C"`;
+//! - how often the `libc` crate's types and functions are used.
+//!
+//! The domain classification labels each pair with the kind of program it
+//! is, such as compression or networking, from keywords in its description
+//! and in the identifiers of its sources.  The label is added to the pair's
+//! tags, so `list --tag` and `download --tag` can select pairs by domain.
+//!
+//! The [`unsafety`] analysis measures the unsafe code of Rust programs, and
+//! is reported by `stats --unsafe`.
+
+pub mod unsafety;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    ops::AddAssign,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{
+        effects::{FileSystem, Storage},
+        errors::AnalyzeError,
+        functions,
+        manifest::{self, Manifest},
+        parser,
+        schema::{Language, ProgramPair},
+        stats::loc,
+        utils,
+    },
+    paths::{
+        ANALYSIS_FILE, DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY,
+        PROGRAM_PAIRS_DIRECTORY, PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// Identifiers that only appear in code generated by c2rust.
+const C2RUST_MARKERS: [&str; 3] = ["c2rust", "mutable_transmutes", "current_block"];
+
+/// Share of functions defined as `unsafe extern "C"` from which a program
+/// counts as machine-translated, if it also uses `libc` often.
+const MACHINE_EXTERN_SHARE: f64 = 0.5;
+
+/// Uses of `libc::` per 100 lines of code from which a program counts as
+/// machine-translated, if it also defines many `unsafe extern "C"`
+/// functions.
+const MACHINE_LIBC_DENSITY: f64 = 2.0;
+
+/// Share of functions defined as `unsafe extern "C"` up to which a program
+/// counts as hand-written, if it also rarely uses `libc`.
+const HAND_WRITTEN_EXTERN_SHARE: f64 = 0.1;
+
+/// Uses of `libc::` per 100 lines of code below which a program counts as
+/// hand-written, if it also defines few `unsafe extern "C"` functions.
+const HAND_WRITTEN_LIBC_DENSITY: f64 = 1.0;
+
+/// Keywords of each domain.  Keywords of up to three letters must match a
+/// whole word; longer ones may be part of one, so `crypt` matches `decrypt`.
+const DOMAIN_KEYWORDS: [(Domain, &[&str]); 5] = [
+    (
+        Domain::Compression,
+        &[
+            "compress", "deflate", "inflate", "zlib", "gzip", "bzip", "huffman", "lzma", "lzw",
+            "zstd", "archive", "unzip",
+        ],
+    ),
+    (
+        Domain::Networking,
+        &[
+            "socket", "tcp", "udp", "http", "dns", "network", "hostname", "packet", "ipv4", "ipv6",
+            "url", "proxy",
+        ],
+    ),
+    (
+        Domain::TextProcessing,
+        &[
+            "regex", "grep", "pattern", "utf8", "unicode", "tokenize", "lexer", "sed", "awk", "wc",
+            "csv", "markdown",
+        ],
+    ),
+    (
+        Domain::Crypto,
+        &[
+            "crypt", "cipher", "md5", "sha1", "sha256", "sha512", "blake", "hmac", "aes", "rsa",
+            "checksum", "digest",
+        ],
+    ),
+    (
+        Domain::SystemUtility,
+        &[
+            "process", "signal", "syscall", "inode", "readdir", "mkdir", "chdir", "chmod", "chown",
+            "kill", "pid", "uid",
+        ],
+    ),
+];
+
+/// How many identifier keywords a keyword in the description counts as, since
+/// the description says what the program is for.
+const DESCRIPTION_WEIGHT: usize = 3;
+
+/// The analyses recorded for one program pair.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PairAnalysis {
+    /// How the pair's Rust program was written, if analyzed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation_origin: Option<OriginReport>,
+    /// The pair's domain, if classified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<DomainReport>,
+}
+
+/// The domain of a program pair, with the keywords it was judged from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainReport {
+    /// The pair's domain.
+    pub domain: Domain,
+    /// The keywords of the domain found in the pair's description and
+    /// sources.
+    pub keywords: Vec<String>,
+}
+
+/// The kind of program a pair is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Domain {
+    /// Compressors, decompressors, and archivers.
+    Compression,
+    /// Network clients, servers, and tools.
+    Networking,
+    /// Searching, transforming, and comparing text.
+    TextProcessing,
+    /// Encryption, hashing, and checksums.
+    Crypto,
+    /// Tools managing files, processes, and the system; also the domain of
+    /// pairs no keyword points elsewhere.
+    SystemUtility,
+}
+
+impl Domain {
+    /// Names the domain as it is recorded and tagged.
+    pub fn label(self) -> &'static str {
+        match self {
+            Domain::Compression => "compression",
+            Domain::Networking => "networking",
+            Domain::TextProcessing => "text-processing",
+            Domain::Crypto => "crypto",
+            Domain::SystemUtility => "system-utility",
+        }
+    }
+}
+
+/// Whether a tag names a domain, so it may have been added by
+/// `analyze --classify` rather than written in the metadata.
+pub fn is_domain_label(tag: &str) -> bool {
+    DOMAIN_KEYWORDS
+        .iter()
+        .any(|(domain, _)| domain.label() == tag)
+}
+
+/// How a Rust program was written, with the signals it was judged from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OriginReport {
+    /// The classification of the program.
+    pub origin: TranslationOrigin,
+    /// The signals found in the program's sources.
+    pub signals: OriginSignals,
+}
+
+/// How a Rust program was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationOrigin {
+    /// Machine-translated from C, such as by c2rust, and left largely as
+    /// generated.
+    MachineTranslated,
+    /// Between the two, such as c2rust output that was partly rewritten.
+    Mixed,
+    /// Idiomatic Rust written by hand.
+    HandWritten,
+    /// The program has no Rust code to judge.
+    Unknown,
+}
+
+/// Signals of machine translation found in Rust sources.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OriginSignals {
+    /// Lines of code.
+    pub code_lines: usize,
+    /// Occurrences of identifiers that only c2rust generates.
+    pub c2rust_markers: usize,
+    /// Functions defined with a body.
+    pub functions: usize,
+    /// Functions defined as `unsafe extern "C"`.
+    pub unsafe_extern_functions: usize,
+    /// Uses of items of the `libc` crate.
+    pub libc_uses: usize,
+    /// Raw pointer types, `*mut` and `*const`.
+    pub raw_pointers: usize,
+}
+
+impl AddAssign for OriginSignals {
+    fn add_assign(&mut self, other: Self) {
+        self.code_lines += other.code_lines;
+        self.c2rust_markers += other.c2rust_markers;
+        self.functions += other.functions;
+        self.unsafe_extern_functions += other.unsafe_extern_functions;
+        self.libc_uses += other.libc_uses;
+        self.raw_pointers += other.raw_pointers;
+    }
+}
+
+impl OriginSignals {
+    /// Share of the functions defined as `unsafe extern "C"`.
+    fn extern_share(&self) -> f64 {
+        if self.functions == 0 {
+            0.0
+        } else {
+            self.unsafe_extern_functions as f64 / self.functions as f64
+        }
+    }
+
+    /// Uses of `libc::` per 100 lines of code.
+    fn libc_density(&self) -> f64 {
+        if self.code_lines == 0 {
+            0.0
+        } else {
+            self.libc_uses as f64 * 100.0 / self.code_lines as f64
+        }
+    }
+
+    /// Classifies the program the signals were found in.
+    ///
+    /// # Returns
+    ///
+    /// [`TranslationOrigin::MachineTranslated`] if there are c2rust markers,
+    /// or if most functions are `unsafe extern "C"` and `libc` is used
+    /// often; [`TranslationOrigin::HandWritten`] if few functions are and
+    /// `libc` is rarely used; [`TranslationOrigin::Mixed`] otherwise.
+    pub fn classify(&self) -> TranslationOrigin {
+        if self.code_lines == 0 {
+            TranslationOrigin::Unknown
+        } else if self.c2rust_markers > 0
+            || (self.extern_share() >= MACHINE_EXTERN_SHARE
+                && self.libc_density() >= MACHINE_LIBC_DENSITY)
+        {
+            TranslationOrigin::MachineTranslated
+        } else if self.extern_share() <= HAND_WRITTEN_EXTERN_SHARE
+            && self.libc_density() < HAND_WRITTEN_LIBC_DENSITY
+        {
+            TranslationOrigin::HandWritten
+        } else {
+            TranslationOrigin::Mixed
+        }
+    }
+}
+
+/// Analyzes one or all downloaded program pairs, prints a summary, and
+/// records the results in `program_pairs/analysis.json`.
+///
+/// # Arguments
+///
+/// - `program_name` - The pair to analyze, or `None` to analyze every pair.
+/// - `translation_origin` - Whether to classify how each pair's Rust program
+///   was written.
+/// - `classify` - Whether to classify each pair's domain and add it to the
+///   tags in its manifest.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`AnalyzeError`] on failure.
+pub fn analyze(
+    program_name: Option<&str>,
+    translation_origin: bool,
+    classify: bool,
+) -> Result<(), AnalyzeError> {
+    let pairs_directory = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    let pair_directories = match program_name {
+        Some(program_name) => {
+            let pair_directory = pairs_directory.join(program_name);
+            if !pair_directory.is_dir() {
+                return Err(AnalyzeError::PairNotFound {
+                    program_name: program_name.to_string(),
+                });
+            }
+            vec![pair_directory]
+        }
+        None => {
+            utils::list_program_pairs(pairs_directory).map_err(|error| AnalyzeError::IoRead {
+                path: pairs_directory.to_path_buf(),
+                error,
+            })?
+        }
+    };
+    let pair_name = |pair_directory: &Path| {
+        pair_directory
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+
+    let mut results = load_results()?;
+    if translation_origin {
+        println!(
+            "{:<24} {:<20} {:>8} {:>12} {:>12}",
+            "Pair", "Origin", "Markers", "Extern fns", "libc/100"
+        );
+        for pair_directory in &pair_directories {
+            let name = pair_name(pair_directory);
+            let signals = program_signals(&manifest::program_directory(
+                pair_directory,
+                &Language::Rust,
+            ))?;
+            let origin = signals.classify();
+            println!(
+                "{:<24} {:<20} {:>8} {:>11.0}% {:>12.1}",
+                name,
+                origin_name(origin),
+                signals.c2rust_markers,
+                signals.extern_share() * 100.0,
+                signals.libc_density()
+            );
+            results.entry(name).or_default().translation_origin =
+                Some(OriginReport { origin, signals });
+        }
+    }
+    if classify {
+        let descriptions = metadata_descriptions();
+        println!("{:<24} {:<16} Keywords", "Pair", "Domain");
+        for pair_directory in &pair_directories {
+            let name = pair_name(pair_directory);
+            let mut identifiers = BTreeSet::new();
+            for language in [Language::C, Language::Rust] {
+                let directory = manifest::program_directory(pair_directory, &language);
+                identifiers.extend(program_identifiers(&directory, &language)?);
+            }
+            let description = descriptions.get(&name).map_or("", String::as_str);
+            let report = classify_domain(description, &identifiers);
+            println!(
+                "{:<24} {:<16} {}",
+                name,
+                report.domain.label(),
+                report.keywords.join(", ")
+            );
+            tag_manifest(pair_directory, report.domain)?;
+            results.entry(name).or_default().domain = Some(report);
+        }
+    }
+
+    save_results(&results)
+}
+
+/// Adds the domain recorded by `analyze --classify` to a pair's tags, so
+/// that the pair can be selected by it.
+///
+/// # Arguments
+///
+/// - `pair` - The program pair, as parsed from its metadata.
+/// - `analyses` - The recorded analyses of each pair by name.
+pub fn add_domain_tag(pair: &mut ProgramPair, analyses: &BTreeMap<String, PairAnalysis>) {
+    let Some(report) = analyses
+        .get(&pair.program_name)
+        .and_then(|analysis| analysis.domain.as_ref())
+    else {
+        return;
+    };
+    let label = report.domain.label().to_string();
+    if !pair.tags.contains(&label) {
+        pair.tags.push(label);
+        pair.tags.sort();
+    }
+}
+
+/// Collects the description of every program pair in the metadata.
+///
+/// Metadata files that fail to parse are reported and skipped.
+fn metadata_descriptions() -> BTreeMap<String, String> {
+    let mut descriptions = BTreeMap::new();
+    for directory in [
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+        DEMO_METADATA_DIRECTORY,
+    ] {
+        let Ok(metadata_files) = parser::parse_directory(Path::new(directory)) else {
+            continue;
+        };
+        for (path, metadata) in metadata_files {
+            match metadata {
+                Ok(metadata) => descriptions.extend(
+                    metadata
+                        .pairs
+                        .into_iter()
+                        .map(|pair| (pair.program_name, pair.program_description)),
+                ),
+                Err(error) => eprintln!("Failed to parse '{}': {}", path.display(), error),
+            }
+        }
+    }
+    descriptions
+}
+
+/// Collects the identifiers of every source file of a program in a language,
+/// split into lowercase words.
+///
+/// # Arguments
+///
+/// - `directory` - A `c-program/` or `rust-program/` directory.
+/// - `language` - The language of the files to read.
+///
+/// # Returns
+///
+/// The words of the identifiers, or [`AnalyzeError`] if a file cannot be
+/// read.
+fn program_identifiers(
+    directory: &Path,
+    language: &Language,
+) -> Result<BTreeSet<String>, AnalyzeError> {
+    let mut identifiers = BTreeSet::new();
+    for path in utils::list_files(directory) {
+        if Language::from_path(&path).as_ref() != Some(language) {
+            continue;
+        }
+        let bytes = fs::read(&path).map_err(|error| AnalyzeError::IoRead {
+            path: path.clone(),
+            error,
+        })?;
+        let source = String::from_utf8_lossy(&bytes);
+        let code = functions::strip_comments_and_literals(&source, language);
+        identifiers.extend(words(&code));
+    }
+    Ok(identifiers)
+}
+
+/// Classifies the domain of a program pair from keywords.
+///
+/// Each keyword of a domain found in the description scores
+/// [`DESCRIPTION_WEIGHT`], and each found in the identifiers scores one.
+///
+/// # Arguments
+///
+/// - `description` - The pair's description.
+/// - `identifiers` - The words of the identifiers in the pair's sources.
+///
+/// # Returns
+///
+/// The [`DomainReport`] of the highest-scoring domain, the earliest on a
+/// tie, or [`Domain::SystemUtility`] if no keyword is found.
+pub fn classify_domain(description: &str, identifiers: &BTreeSet<String>) -> DomainReport {
+    let description = words(description);
+    let mut best = DomainReport {
+        domain: Domain::SystemUtility,
+        keywords: Vec::new(),
+    };
+    let mut best_score = 0;
+    for (domain, keywords) in DOMAIN_KEYWORDS {
+        let found_in = |words: &BTreeSet<String>, keyword: &str| {
+            words.iter().any(|word| {
+                if keyword.len() <= 3 {
+                    word == keyword
+                } else {
+                    word.contains(keyword)
+                }
+            })
+        };
+        let mut score = 0;
+        let mut found = Vec::new();
+        for keyword in keywords {
+            let in_description = found_in(&description, keyword);
+            let in_identifiers = found_in(identifiers, keyword);
+            score += usize::from(in_description) * DESCRIPTION_WEIGHT + usize::from(in_identifiers);
+            if in_description || in_identifiers {
+                found.push(keyword.to_string());
+            }
+        }
+        if score > best_score {
+            best_score = score;
+            best = DomainReport {
+                domain,
+                keywords: found,
+            };
+        }
+    }
+    best
+}
+
+/// Splits text into lowercase words, also splitting identifiers at
+/// underscores and `camelCase` boundaries.
+fn words(text: &str) -> BTreeSet<String> {
+    let mut words = BTreeSet::new();
+    for token in text.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut word = String::new();
+        let mut after_lowercase = false;
+        for c in token.chars() {
+            if c.is_ascii_uppercase() && after_lowercase {
+                words.insert(std::mem::take(&mut word));
+            }
+            after_lowercase = c.is_ascii_lowercase() || c.is_ascii_digit();
+            word.push(c.to_ascii_lowercase());
+        }
+        if !word.is_empty() {
+            words.insert(word);
+        }
+    }
+    words
+}
+
+/// Replaces the domain among the tags in a pair's manifest, if the pair has
+/// one.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The pair's directory in `program_pairs/`.
+/// - `domain` - The pair's domain.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`AnalyzeError::Manifest`] on failure.
+fn tag_manifest(pair_directory: &Path, domain: Domain) -> Result<(), AnalyzeError> {
+    let Some(mut manifest) = Manifest::load(pair_directory) else {
+        return Ok(());
+    };
+    manifest.tags.retain(|tag| !is_domain_label(tag));
+    manifest.tags.push(domain.label().to_string());
+    manifest.tags.sort();
+    manifest
+        .save(pair_directory)
+        .map_err(|error| AnalyzeError::Manifest { error })
+}
+
+/// Collects the signals of machine translation from every Rust file of a
+/// program.
+///
+/// # Arguments
+///
+/// - `directory` - A `rust-program/` directory.
+///
+/// # Returns
+///
+/// The signals summed over the program's Rust files, or [`AnalyzeError`] if
+/// a file cannot be read.
+fn program_signals(directory: &Path) -> Result<OriginSignals, AnalyzeError> {
+    let mut signals = OriginSignals::default();
+    for path in utils::list_files(directory) {
+        if Language::from_path(&path) != Some(Language::Rust) {
+            continue;
+        }
+        // Files that are not valid UTF-8 are read lossily.
+        let bytes = fs::read(&path).map_err(|error| AnalyzeError::IoRead {
+            path: path.clone(),
+            error,
+        })?;
+        signals += origin_signals(&String::from_utf8_lossy(&bytes));
+    }
+    Ok(signals)
+}
+
+/// Collects the signals of machine translation from Rust source code.
+///
+/// Comments and literals are ignored, so a comment mentioning c2rust is not
+/// taken for a marker.
+///
+/// # Arguments
+///
+/// - `source` - The Rust source code.
+///
+/// # Returns
+///
+/// The [`OriginSignals`] found in `source`.
+pub fn origin_signals(source: &str) -> OriginSignals {
+    let code = functions::strip_comments_and_literals(source, &Language::Rust);
+    let words: Vec<(usize, &str)> = code
+        .match_indices(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .scan(0, |start, (index, separator)| {
+            let word = (*start, &code[*start..index]);
+            *start = index + separator.len();
+            Some(word)
+        })
+        .filter(|(_, word)| !word.is_empty())
+        .collect();
+
+    let mut signals = OriginSignals {
+        code_lines: loc::count_lines(source, &Language::Rust).code,
+        ..OriginSignals::default()
+    };
+    for (position, &(offset, word)) in words.iter().enumerate() {
+        if C2RUST_MARKERS.iter().any(|marker| word.starts_with(marker)) {
+            signals.c2rust_markers += 1;
+        }
+        match word {
+            "libc" if code[offset + word.len()..].starts_with("::") => signals.libc_uses += 1,
+            "mut" | "const" if code[..offset].ends_with('*') => signals.raw_pointers += 1,
+            "fn" => {
+                // Only count definitions, not declarations in `extern` blocks
+                // or trait methods without a body.
+                let is_definition = code[offset..]
+                    .find(['{', ';'])
+                    .is_some_and(|end| code[offset + end..].starts_with('{'));
+                if !is_definition {
+                    continue;
+                }
+                signals.functions += 1;
+                // The qualifiers directly before `fn`, such as `pub unsafe
+                // extern`; the ABI string was blanked with the literals.
+                let qualifiers: Vec<&str> = words[..position]
+                    .iter()
+                    .rev()
+                    .take(3)
+                    .map(|&(_, word)| word)
+                    .take_while(|word| matches!(*word, "pub" | "unsafe" | "extern" | "crate"))
+                    .collect();
+                if qualifiers.contains(&"unsafe") && qualifiers.contains(&"extern") {
+                    signals.unsafe_extern_functions += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    signals
+}
+
+/// Names a translation origin as it is recorded.
+fn origin_name(origin: TranslationOrigin) -> &'static str {
+    match origin {
+        TranslationOrigin::MachineTranslated => "machine_translated",
+        TranslationOrigin::Mixed => "mixed",
+        TranslationOrigin::HandWritten => "hand_written",
+        TranslationOrigin::Unknown => "unknown",
+    }
+}
+
+/// Loads previously recorded analyses, if any.
+///
+/// # Returns
+///
+/// The analysis of each pair by name, or [`AnalyzeError`] if the file cannot
+/// be read.
+pub fn load_results() -> Result<BTreeMap<String, PairAnalysis>, AnalyzeError> {
+    load_results_from(&FileSystem::default())
+}
+
+/// Loads previously recorded analyses from a [`Storage`], if any.
+///
+/// # Arguments
+///
+/// - `storage` - The storage holding `program_pairs/analysis.json`.
+///
+/// # Returns
+///
+/// The analysis of each pair by name, or [`AnalyzeError`] if the file cannot
+/// be read.
+pub fn load_results_from(
+    storage: &dyn Storage,
+) -> Result<BTreeMap<String, PairAnalysis>, AnalyzeError> {
+    let path = Path::new(ANALYSIS_FILE);
+    if !storage.exists(path) {
+        return Ok(BTreeMap::new());
+    }
+    let contents = storage
+        .read_to_string(path)
+        .map_err(|error| AnalyzeError::IoRead {
+            path: path.to_path_buf(),
+            error,
+        })?;
+    serde_json::from_str(&contents).map_err(|error| AnalyzeError::Serialize { error })
+}
+
+/// Writes analyses to `program_pairs/analysis.json`.
+fn save_results(results: &BTreeMap<String, PairAnalysis>) -> Result<(), AnalyzeError> {
+    let path = PathBuf::from(ANALYSIS_FILE);
+    let contents =
+        serde_json::to_string_pretty(results).map_err(|error| AnalyzeError::Serialize { error })?;
+    fs::write(&path, contents).map_err(|error| AnalyzeError::IoWrite { path, error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that code with c2rust's attributes, `unsafe extern "C"`
+    /// functions, `libc` types, and raw pointers is classified as machine
+    /// translated.
+    #[test]
+    fn test_c2rust_output_is_machine_translated() {
+        let source = r#"
+#![allow(dead_code, mutable_transmutes, non_camel_case_types)]
+extern "C" {
+    fn puts(s: *const libc::c_char) -> libc::c_int;
+}
+#[no_mangle]
+pub unsafe extern "C" fn greet(mut name: *mut libc::c_char) -> libc::c_int {
+    let mut current_block_3: u64;
+    return puts(name);
+}
+"#;
+        let signals = origin_signals(source);
+        assert_eq!(1, signals.functions);
+        assert_eq!(1, signals.unsafe_extern_functions);
+        assert_eq!(2, signals.c2rust_markers);
+        assert_eq!(4, signals.libc_uses);
+        assert_eq!(2, signals.raw_pointers);
+        assert_eq!(TranslationOrigin::MachineTranslated, signals.classify());
+    }
+
+    /// Tests that idiomatic code is classified as hand-written even when a
+    /// comment or string mentions c2rust or `libc`, and that code without
+    /// functions is unknown.
+    #[test]
+    fn test_idiomatic_code_is_hand_written() {
+        let source = r#"
+// Unlike the c2rust version, this uses iterators.
+fn main() {
+    let names = ["a", "b"];
+    for name in names.iter() {
+        println!("libc::puts {name}");
+    }
+}
+"#;
+        let signals = origin_signals(source);
+        assert_eq!(1, signals.functions);
+        assert_eq!(0, signals.c2rust_markers);
+        assert_eq!(0, signals.libc_uses);
+        assert_eq!(TranslationOrigin::HandWritten, signals.classify());
+        assert_eq!(
+            TranslationOrigin::Unknown,
+            OriginSignals::default().classify()
+        );
+    }
+
+    /// Tests that a pair's domain is the one whose keywords its description
+    /// and identifiers mention most, weighting the description higher.
+    #[test]
+    fn test_classify_domain() {
+        let identifiers = words("deflate_block inflateInit z_stream crc32");
+        let report = classify_domain("A fast gzip implementation", &identifiers);
+        assert_eq!(Domain::Compression, report.domain);
+        assert_eq!(vec!["deflate", "inflate", "gzip"], report.keywords);
+
+        // A keyword in the description outweighs one in the identifiers.
+        let identifiers = words("open_socket read_line");
+        let report = classify_domain("Search files for a regex pattern", &identifiers);
+        assert_eq!(Domain::TextProcessing, report.domain);
+
+        // Short keywords must match whole words, so `used` is not `sed`.
+        let report = classify_domain("Print the files used", &words("context"));
+        assert_eq!(Domain::SystemUtility, report.domain);
+        assert!(report.keywords.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! # Unsafe Code Metrics
+//!
+//! This module counts how much of a Rust source file relies on `unsafe`:
+//! `unsafe` blocks, `unsafe fn`s, raw pointer types, and `extern "C"` items.
+//! Files are parsed with `syn`, so the counts ignore comments and string
+//! literals, but code inside macro invocations, such as `println!`, is not
+//! seen.
+
+use std::ops::AddAssign;
+
+use serde::Serialize;
+use syn::{
+    Abi, ExprUnsafe, ForeignItem, ImplItemFn, ItemFn, ItemForeignMod, Signature, TraitItemFn,
+    TypePtr,
+    visit::{self, Visit},
+};
+
+/// Counts of unsafe code in one or more Rust source files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UnsafeCounts {
+    /// `unsafe { ... }` blocks.
+    pub unsafe_blocks: usize,
+    /// Functions and methods declared `unsafe fn`, with or without a body.
+    pub unsafe_fns: usize,
+    /// Raw pointer types, `*const T` and `*mut T`, wherever they appear, such
+    /// as in signatures and casts.
+    pub raw_pointers: usize,
+    /// Functions defined with the C ABI, and items declared in
+    /// `extern "C" { ... }` blocks.
+    pub extern_c_items: usize,
+    /// Files that could not be parsed, and so were not counted.
+    pub unparsed_files: usize,
+}
+
+impl AddAssign for UnsafeCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.unsafe_blocks += other.unsafe_blocks;
+        self.unsafe_fns += other.unsafe_fns;
+        self.raw_pointers += other.raw_pointers;
+        self.extern_c_items += other.extern_c_items;
+        self.unparsed_files += other.unparsed_files;
+    }
+}
+
+impl<'ast> Visit<'ast> for UnsafeCounts {
+    fn visit_expr_unsafe(&mut self, block: &'ast ExprUnsafe) {
+        self.unsafe_blocks += 1;
+        visit::visit_expr_unsafe(self, block);
+    }
+
+    fn visit_signature(&mut self, signature: &'ast Signature) {
+        if signature.unsafety.is_some() {
+            self.unsafe_fns += 1;
+        }
+        visit::visit_signature(self, signature);
+    }
+
+    fn visit_type_ptr(&mut self, pointer: &'ast TypePtr) {
+        self.raw_pointers += 1;
+        visit::visit_type_ptr(self, pointer);
+    }
+
+    fn visit_item_fn(&mut self, function: &'ast ItemFn) {
+        if is_c_abi(function.sig.abi.as_ref()) {
+            self.extern_c_items += 1;
+        }
+        visit::visit_item_fn(self, function);
+    }
+
+    fn visit_impl_item_fn(&mut self, function: &'ast ImplItemFn) {
+        if is_c_abi(function.sig.abi.as_ref()) {
+            self.extern_c_items += 1;
+        }
+        visit::visit_impl_item_fn(self, function);
+    }
+
+    fn visit_trait_item_fn(&mut self, function: &'ast TraitItemFn) {
+        if is_c_abi(function.sig.abi.as_ref()) {
+            self.extern_c_items += 1;
+        }
+        visit::visit_trait_item_fn(self, function);
+    }
+
+    fn visit_item_foreign_mod(&mut self, block: &'ast ItemForeignMod) {
+        if is_c_abi(Some(&block.abi)) {
+            self.extern_c_items += block
+                .items
+                .iter()
+                .filter(|item| !matches!(item, ForeignItem::Macro(_) | ForeignItem::Verbatim(_)))
+                .count();
+        }
+        visit::visit_item_foreign_mod(self, block);
+    }
+}
+
+/// Returns whether an ABI is the C ABI, which `extern` without a name
+/// defaults to.
+fn is_c_abi(abi: Option<&Abi>) -> bool {
+    abi.is_some_and(|abi| {
+        abi.name
+            .as_ref()
+            .is_none_or(|name| matches!(name.value().as_str(), "C" | "C-unwind"))
+    })
+}
+
+/// Counts the unsafe code in a Rust source file.
+///
+/// # Arguments
+///
+/// - `source` - The Rust source code.
+///
+/// # Returns
+///
+/// The [`UnsafeCounts`] of `source`, with `unparsed_files` set to 1 and
+/// nothing else counted if it is not valid Rust.
+pub fn count_unsafe(source: &str) -> UnsafeCounts {
+    let mut counts = UnsafeCounts::default();
+    match syn::parse_file(source) {
+        Ok(file) => counts.visit_file(&file),
+        Err(_) => counts.unparsed_files = 1,
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that each kind of unsafe code is counted, outside of comments and
+    /// strings.
+    #[test]
+    fn test_count_unsafe() {
+        let source = r#"
+// unsafe { *const u8 }
+extern "C" {
+    fn strlen(s: *const u8) -> usize;
+    static errno: i32;
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn length(s: *mut u8) -> usize {
+    let text = "unsafe { }";
+    unsafe { strlen(s as *const u8) + text.len() }
+}
+
+extern "Rust" fn other() {}
+
+struct Buffer;
+
+impl Buffer {
+    unsafe fn get(&self) -> u8 {
+        unsafe { 0 }
+    }
+}
+"#;
+        assert_eq!(
+            UnsafeCounts {
+                unsafe_blocks: 2,
+                unsafe_fns: 2,
+                raw_pointers: 3,
+                extern_c_items: 3,
+                unparsed_files: 0,
+            },
+            count_unsafe(source)
+        );
+    }
+
+    /// Tests that a file that is not valid Rust is reported as unparsed.
+    #[test]
+    fn test_count_unsafe_unparsed() {
+        assert_eq!(
+            UnsafeCounts {
+                unparsed_files: 1,
+                ..UnsafeCounts::default()
+            },
+            count_unsafe("fn main( {")
+        );
+    }
+}
@@ -0,0 +1,438 @@
+//! # Upstream Audit
+//!
+//! This module checks that the links in the metadata still lead somewhere,
+//! so that metadata rot is caught before downloads start failing.  Every git
+//! repository is listed as `git ls-remote` does, which also shows whether a
+//! `branch` still exists; repositories on GitHub and GitLab are looked up in
+//! their host's API to find renamed and archived ones.  Documentation pages
+//! and the URLs of tarball and file sources are requested over HTTP.  With
+//! `--json`, the problems found are printed as JSON.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path,
+    time::Duration,
+};
+
+use git2::{Direction, ProxyOptions, Remote};
+use rayon::prelude::*;
+use reqwest::{
+    StatusCode, Url,
+    blocking::{Client, RequestBuilder},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{
+        config::AuthConfig,
+        errors::AuditError,
+        git_options::{self, GitOptions},
+        github::GitHubClient,
+        output, parser,
+        schema::SourceType,
+        utils,
+    },
+    paths::{DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROJECT_METADATA_DIRECTORY},
+};
+
+/// How long listing a repository waits for its server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a link in the metadata points to, which decides how it is checked.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum LinkKind {
+    /// A git repository, with the branch downloaded from it, if any.
+    Repository {
+        /// The branch, or `None` for the default branch.
+        branch: Option<String>,
+    },
+    /// The URL of a tarball or file source.
+    Source,
+    /// A documentation page.
+    Documentation,
+}
+
+/// A problem found with a link.
+#[derive(Debug, Serialize)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+enum Problem {
+    /// The link cannot be reached.
+    Dead {
+        /// Why the link is considered dead.
+        reason: String,
+    },
+    /// The repository has moved to another URL.
+    Renamed {
+        /// The repository's current URL.
+        new_url: String,
+    },
+    /// The repository is archived, so it no longer changes.
+    Archived,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Dead { reason } => write!(formatter, "dead ({reason})"),
+            Problem::Renamed { new_url } => write!(formatter, "renamed to '{new_url}'"),
+            Problem::Archived => write!(formatter, "archived"),
+        }
+    }
+}
+
+/// Every problem found by [`audit`], as printed with `--json`.
+#[derive(Debug, Serialize)]
+struct AuditReport<'a> {
+    /// Number of repositories and tarball and file sources checked.
+    checked_repositories: usize,
+    /// Number of documentation links checked.
+    checked_documentation_links: usize,
+    /// The problems found, by link.
+    problems: Vec<LinkProblem<'a>>,
+}
+
+/// A problem found with a link, as printed with `--json`.
+#[derive(Debug, Serialize)]
+struct LinkProblem<'a> {
+    /// The link, with `@<branch>` for a branch of a repository.
+    url: String,
+    /// The problem, tagged by its kind.
+    #[serde(flatten)]
+    problem: Problem,
+    /// The program pairs using the link.
+    pairs: Vec<&'a str>,
+}
+
+/// A GitHub repository, as returned by `GET /repos/{owner}/{repo}`, which
+/// follows renames.
+#[derive(Deserialize)]
+struct GitHubRepository {
+    full_name: String,
+    html_url: String,
+    archived: bool,
+}
+
+/// A GitLab project, as returned by `GET /projects/{id}`.
+#[derive(Deserialize)]
+struct GitLabProject {
+    path_with_namespace: String,
+    web_url: String,
+    archived: bool,
+}
+
+/// Checks every repository, source, and documentation URL in the metadata
+/// and reports the dead, renamed, and archived ones.
+///
+/// Metadata files that fail to parse are reported and skipped.
+///
+/// # Arguments
+///
+/// - `auth` - The API tokens to look up GitHub and GitLab repositories with.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if no problems are found, [`AuditError::Problems`] if
+/// any are, and another [`AuditError`] on failure.
+pub fn audit(auth: &AuthConfig) -> Result<(), AuditError> {
+    let links = metadata_links();
+    let client = utils::http_client().map_err(|error| AuditError::Client { error })?;
+    let github = GitHubClient::new(auth).map_err(|error| AuditError::GitHub { error })?;
+
+    // Keep an unreachable server from stalling the audit, unless an
+    // application using the library already set libgit2's options.
+    let _ = git_options::apply(&GitOptions {
+        connect_timeout: Some(CONNECT_TIMEOUT),
+        ..GitOptions::default()
+    });
+    let results: Vec<Vec<Problem>> = links
+        .par_iter()
+        .map(|((url, kind), _)| check_link(&client, &github, url, kind, auth))
+        .collect();
+
+    let (mut dead, mut renamed, mut archived) = (0, 0, 0);
+    let mut problems = Vec::new();
+    for (((url, kind), pairs), link_problems) in links.iter().zip(results) {
+        for problem in link_problems {
+            match problem {
+                Problem::Dead { .. } => dead += 1,
+                Problem::Renamed { .. } => renamed += 1,
+                Problem::Archived => archived += 1,
+            }
+            let url = match kind {
+                LinkKind::Repository {
+                    branch: Some(branch),
+                } => format!("{url}@{branch}"),
+                _ => url.clone(),
+            };
+            let pairs: Vec<&str> = pairs.iter().map(String::as_str).collect();
+            if !output::json() {
+                println!("'{url}' is {problem}; used by {}", pairs.join(", "));
+            }
+            problems.push(LinkProblem {
+                url,
+                problem,
+                pairs,
+            });
+        }
+    }
+
+    let repositories = links
+        .keys()
+        .filter(|(_, kind)| *kind != LinkKind::Documentation)
+        .count();
+    if output::json() {
+        output::print_json(&AuditReport {
+            checked_repositories: repositories,
+            checked_documentation_links: links.len() - repositories,
+            problems,
+        });
+    } else {
+        println!(
+            "Checked {repositories} repositories and {} documentation links: {dead} dead, \
+             {renamed} renamed, {archived} archived",
+            links.len() - repositories
+        );
+    }
+    if dead + renamed + archived > 0 {
+        return Err(AuditError::Problems {
+            dead,
+            renamed,
+            archived,
+        });
+    }
+    Ok(())
+}
+
+/// Collects the links of every program in the metadata.
+///
+/// # Returns
+///
+/// A map from each link, as written in the metadata, and what it points to,
+/// to the names of the program pairs using it.
+fn metadata_links() -> BTreeMap<(String, LinkKind), BTreeSet<String>> {
+    let mut links: BTreeMap<(String, LinkKind), BTreeSet<String>> = BTreeMap::new();
+    for directory in [
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+        DEMO_METADATA_DIRECTORY,
+    ] {
+        let metadata_files = match parser::parse_directory(Path::new(directory)) {
+            Ok(metadata_files) => metadata_files,
+            Err(error) => {
+                eprintln!("Failed to read '{directory}': {error}");
+                continue;
+            }
+        };
+        for (path, metadata) in metadata_files {
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(error) => {
+                    eprintln!("Failed to parse '{}': {}", path.display(), error);
+                    continue;
+                }
+            };
+            for pair in &metadata.pairs {
+                for program in pair.programs() {
+                    let kind = match program.source_type {
+                        SourceType::Git => LinkKind::Repository {
+                            branch: program.branch.clone(),
+                        },
+                        SourceType::Tarball | SourceType::HttpFile => LinkKind::Source,
+                    };
+                    for link in [
+                        (program.source_url(&program.repository_url), kind),
+                        (
+                            program.documentation_url.as_str().to_string(),
+                            LinkKind::Documentation,
+                        ),
+                    ] {
+                        links
+                            .entry(link)
+                            .or_default()
+                            .insert(pair.program_name.clone());
+                    }
+                }
+            }
+        }
+    }
+    links
+}
+
+/// Checks one link.
+///
+/// # Arguments
+///
+/// - `client` - The HTTP client used for requests other than to GitHub.
+/// - `github` - The GitHub API client.
+/// - `url` - The link.
+/// - `kind` - What the link points to.
+/// - `auth` - The API tokens to look up GitLab repositories with.
+///
+/// # Returns
+///
+/// The problems found, if any.
+fn check_link(
+    client: &Client,
+    github: &GitHubClient,
+    url: &str,
+    kind: &LinkKind,
+    auth: &AuthConfig,
+) -> Vec<Problem> {
+    let LinkKind::Repository { branch } = kind else {
+        return check_url(client, url).into_iter().collect();
+    };
+    if let Err(reason) = list_references(url, branch.as_deref()) {
+        return vec![Problem::Dead { reason }];
+    }
+
+    let mut problems = Vec::new();
+    match look_up_repository(client, github, url, auth) {
+        Ok(Some((current_url, archived))) => {
+            if let Some(new_url) = current_url {
+                problems.push(Problem::Renamed { new_url });
+            }
+            if archived {
+                problems.push(Problem::Archived);
+            }
+        }
+        Ok(None) => {}
+        Err(error) => {
+            eprintln!("Skipped the rename and archive checks of '{url}': {error}");
+        }
+    }
+    problems
+}
+
+/// Lists the references of a repository, as `git ls-remote` does, without
+/// touching any clone of it.
+///
+/// # Arguments
+///
+/// - `url` - The repository's URL.
+/// - `branch` - A branch that must exist, or `None`.
+///
+/// # Returns
+///
+/// SHA of the head of the branch, or of the default branch if `branch` is
+/// `None`, `None` if the repository has no default branch, such as when it
+/// is empty, or why the repository or branch cannot be found.
+pub fn list_references(url: &str, branch: Option<&str>) -> Result<Option<String>, String> {
+    let mut proxy_options = ProxyOptions::new();
+    proxy_options.auto();
+    let mut remote = Remote::create_detached(url).map_err(|error| error.message().to_string())?;
+    remote
+        .connect_auth(Direction::Fetch, None, Some(proxy_options))
+        .map_err(|error| error.message().to_string())?;
+    let references = remote.list().map_err(|error| error.message().to_string())?;
+    let reference = branch.map_or_else(
+        || "HEAD".to_string(),
+        |branch| format!("refs/heads/{branch}"),
+    );
+    let head = references
+        .iter()
+        .find(|head| head.name() == reference)
+        .map(|head| head.oid().to_string());
+    match (head, branch) {
+        (None, Some(branch)) => Err(format!("branch '{branch}' not found")),
+        (head, _) => Ok(head),
+    }
+}
+
+/// Requests a documentation page or source URL.
+///
+/// Servers that do not support `HEAD` requests are sent a `GET` request, and
+/// `file://` URLs are looked up on disk.
+///
+/// # Returns
+///
+/// [`Problem::Dead`] if the request fails or returns an error status.
+fn check_url(client: &Client, url: &str) -> Option<Problem> {
+    if let Ok(parsed) = Url::parse(url)
+        && parsed.scheme() == "file"
+    {
+        let exists = parsed.to_file_path().is_ok_and(|path| path.exists());
+        return (!exists).then(|| Problem::Dead {
+            reason: "no such file".to_string(),
+        });
+    }
+
+    let status = |request: RequestBuilder| request.send().map(|response| response.status());
+    let result = match status(client.head(url)) {
+        Ok(StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED) => status(client.get(url)),
+        result => result,
+    };
+    match result {
+        Ok(status) if status.is_client_error() || status.is_server_error() => Some(Problem::Dead {
+            reason: status.to_string(),
+        }),
+        Ok(_) => None,
+        Err(error) => Some(Problem::Dead {
+            reason: error.to_string(),
+        }),
+    }
+}
+
+/// Looks up a repository in the API of its host, if it is hosted on GitHub
+/// or GitLab.
+///
+/// # Arguments
+///
+/// - `client` - The HTTP client used for GitLab requests.
+/// - `github` - The GitHub API client.
+/// - `url` - The repository's URL.
+/// - `auth` - The API tokens to authenticate with GitLab.
+///
+/// # Returns
+///
+/// The repository's current URL if it was renamed, and whether it is
+/// archived; `None` if the repository is hosted elsewhere; or why the
+/// request failed.
+fn look_up_repository(
+    client: &Client,
+    github: &GitHubClient,
+    url: &str,
+    auth: &AuthConfig,
+) -> Result<Option<(Option<String>, bool)>, String> {
+    let normalized = utils::normalize_repository_url(url);
+    let Some((host, path)) = normalized.split_once('/') else {
+        return Ok(None);
+    };
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Ok(None);
+    }
+
+    let (current_path, current_url, archived) = if host == "github.com" {
+        let repository: GitHubRepository = github
+            .get(&format!("/repos/{path}"), &[])
+            .map_err(|error| error.to_string())?;
+        (
+            repository.full_name,
+            repository.html_url,
+            repository.archived,
+        )
+    } else if host.contains("gitlab") {
+        let mut request = client.get(format!(
+            "https://{host}/api/v4/projects/{}",
+            path.replace('/', "%2F")
+        ));
+        if let Some(token) = auth.gitlab_token() {
+            request = request.header("PRIVATE-TOKEN", token);
+        }
+        let project: GitLabProject = request
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .map_err(|error| error.to_string())?;
+        (
+            project.path_with_namespace,
+            project.web_url,
+            project.archived,
+        )
+    } else {
+        return Ok(None);
+    };
+    // Hosts treat repository paths case-insensitively.
+    let renamed = !current_path.eq_ignore_ascii_case(path);
+    Ok(Some((renamed.then_some(current_url), archived)))
+}
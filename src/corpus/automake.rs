@@ -0,0 +1,317 @@
+//! # Automake Sources
+//!
+//! This module finds the C sources of a program in a repository built with
+//! Automake, by reading the `_SOURCES` variables of its `Makefile.am` and
+//! included `.mk` files.
+//!
+//! Automake names a program's variables after its canonicalized path, so a
+//! program listed as `bin_PROGRAMS = src/cmp` has its sources in
+//! `src_cmp_SOURCES` rather than `cmp_SOURCES`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use crate::corpus::{errors::MetadataError, utils};
+
+/// How deeply variable references such as `$(common_sources)` are expanded,
+/// so variables that refer to themselves cannot loop forever.
+const MAX_EXPANSION_DEPTH: usize = 8;
+
+/// Extensions of the files kept from a program's sources.
+const SOURCE_EXTENSIONS: [&str; 2] = ["c", "h"];
+
+/// Finds the C sources of a program in a local checkout of an Automake
+/// repository.
+///
+/// # Arguments
+///
+/// - `repository` - The root of the checkout.
+/// - `program` - Name of the program's executable, such as `cmp`.
+///
+/// # Returns
+///
+/// The sources that exist in the checkout as `/`-separated paths relative to
+/// its root, or [`MetadataError`] if the program is not found.
+pub fn find_sources(repository: &Path, program: &str) -> Result<Vec<String>, MetadataError> {
+    let read_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |error| MetadataError::IoRead { path, error }
+    };
+    let root = repository.canonicalize().map_err(read_error(repository))?;
+
+    let makefiles = WalkDir::new(&root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let path = entry.path();
+            entry.file_type().is_file()
+                && (entry.file_name() == "Makefile.am"
+                    || path.extension().is_some_and(|extension| extension == "mk"))
+        });
+    for makefile in makefiles {
+        let contents = fs::read_to_string(makefile.path()).map_err(read_error(makefile.path()))?;
+        let Some(sources) = program_sources(&parse_variables(&contents), program) else {
+            continue;
+        };
+
+        // Sources are relative to the Makefile, or to the top-level
+        // Makefile.am for `.mk` files included from it.
+        let directory = makefile.path().parent().unwrap_or(&root);
+        let mut paths: Vec<String> = sources
+            .iter()
+            .filter(|source| {
+                Path::new(source)
+                    .extension()
+                    .is_some_and(|extension| SOURCE_EXTENSIONS.iter().any(|e| extension == *e))
+            })
+            .filter_map(|source| {
+                let source = utils::platform_path(source);
+                [directory.join(&source), root.join(&source)]
+                    .into_iter()
+                    .find_map(|path| path.canonicalize().ok())
+            })
+            .filter_map(|path| relative_slash_path(&path, &root))
+            .collect();
+        // A source may be listed again anywhere, such as directly and
+        // through a shared variable; the first listing is kept.
+        let mut seen = HashSet::new();
+        paths.retain(|path| seen.insert(path.clone()));
+        return Ok(paths);
+    }
+
+    Err(MetadataError::ProgramNotFound {
+        program: program.to_string(),
+        repository: repository.to_path_buf(),
+    })
+}
+
+/// Converts a name into the form Automake uses in variable names, replacing
+/// every character other than letters, digits, and `@` with `_`.
+///
+/// # Arguments
+///
+/// - `name` - A program path as listed in a `_PROGRAMS` variable.
+///
+/// # Returns
+///
+/// The canonicalized name, such as `src_cmp` for `src/cmp`.
+fn canonicalize(name: &str) -> String {
+    name.chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() || character == '@' {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Parses the variable assignments of a Makefile, joining continued lines
+/// and appending `+=` assignments to earlier ones.
+///
+/// # Arguments
+///
+/// - `contents` - The contents of a Makefile.
+///
+/// # Returns
+///
+/// The words assigned to each variable.
+fn parse_variables(contents: &str) -> HashMap<String, Vec<String>> {
+    let mut variables: HashMap<String, Vec<String>> = HashMap::new();
+    let joined = contents.replace("\\\r\n", " ").replace("\\\n", " ");
+    for line in joined.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        if line.starts_with('\t') {
+            continue;
+        }
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (name, append) = match name.strip_suffix('+') {
+            Some(name) => (name, true),
+            None => (name.trim_end_matches([':', '?']), false),
+        };
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            continue;
+        }
+
+        let words = value.split_whitespace().map(str::to_string);
+        let variable = variables.entry(name.to_string()).or_default();
+        if !append {
+            variable.clear();
+        }
+        variable.extend(words);
+    }
+    variables
+}
+
+/// Finds the sources of a program among a Makefile's variables.
+///
+/// The program is looked up in every `_PROGRAMS` variable by the last
+/// component of its path, and its sources are read from the `_SOURCES`
+/// variable named after the canonicalized path.  Failing that, any
+/// `_SOURCES` variable ending in the canonicalized program name is used, such
+/// as for programs listed through a substitution, and a listed program
+/// without one defaults to a single `.c` file, as in Automake.
+///
+/// # Arguments
+///
+/// - `variables` - The variables of a Makefile.
+/// - `program` - Name of the program's executable.
+///
+/// # Returns
+///
+/// The program's sources as written in the Makefile, or `None` if it does
+/// not build the program.
+fn program_sources(variables: &HashMap<String, Vec<String>>, program: &str) -> Option<Vec<String>> {
+    let mut listed: Vec<&String> = variables
+        .iter()
+        .filter(|(name, _)| name.ends_with("_PROGRAMS"))
+        .flat_map(|(_, programs)| programs)
+        .filter(|entry| {
+            let entry = entry.trim_end_matches("$(EXEEXT)");
+            entry.rsplit('/').next() == Some(program)
+        })
+        .collect();
+    listed.sort();
+
+    let canonical_program = canonicalize(program);
+    let suffix = format!("_{canonical_program}_SOURCES");
+    let mut names: Vec<String> = listed
+        .iter()
+        .map(|entry| {
+            format!(
+                "{}_SOURCES",
+                canonicalize(entry.trim_end_matches("$(EXEEXT)"))
+            )
+        })
+        .collect();
+    names.push(format!("{canonical_program}_SOURCES"));
+    let mut prefixed: Vec<&String> = variables
+        .keys()
+        .filter(|name| {
+            name.ends_with(&suffix) && !name.starts_with("EXTRA_") && !name.starts_with("nodist_")
+        })
+        .collect();
+    prefixed.sort();
+    names.extend(prefixed.into_iter().cloned());
+
+    if let Some(sources) = names.iter().find_map(|name| variables.get(name)) {
+        return Some(expand(variables, sources, 0));
+    }
+    listed
+        .first()
+        .map(|entry| vec![format!("{}.c", entry.trim_end_matches("$(EXEEXT)"))])
+}
+
+/// Replaces references to other variables, such as `$(common_sources)`, with
+/// their words; references to undefined variables are dropped.
+fn expand(variables: &HashMap<String, Vec<String>>, words: &[String], depth: usize) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for word in words {
+        let reference = word
+            .strip_prefix("$(")
+            .and_then(|word| word.strip_suffix(')'))
+            .or_else(|| {
+                word.strip_prefix("${")
+                    .and_then(|word| word.strip_suffix('}'))
+            });
+        match reference {
+            Some(name) if depth < MAX_EXPANSION_DEPTH => {
+                if let Some(words) = variables.get(name) {
+                    expanded.extend(expand(variables, words, depth + 1));
+                }
+            }
+            Some(_) => {}
+            None if word.contains('$') || word.contains('@') => {}
+            None => expanded.push(word.clone()),
+        }
+    }
+    expanded
+}
+
+/// Converts a path within the repository into a `/`-separated relative path.
+fn relative_slash_path(path: &Path, root: &Path) -> Option<String> {
+    let relative: PathBuf = path.strip_prefix(root).ok()?.to_path_buf();
+    utils::slash_path(&relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that program paths are canonicalized like Automake does.
+    #[test]
+    fn test_canonicalize() {
+        assert_eq!("src_cmp", canonicalize("src/cmp"));
+        assert_eq!("lib_foo_bar_a", canonicalize("lib/foo.bar.a"));
+        assert_eq!("diff3", canonicalize("diff3"));
+    }
+
+    /// Tests that sources are found through the canonicalized program path,
+    /// prefixed variables, continued lines, appends, and variable references.
+    #[test]
+    fn test_program_sources() {
+        let variables = parse_variables(
+            "bin_PROGRAMS = src/cmp src/diff$(EXEEXT)\n\
+             common = src/system.c \\\n\
+             \tsrc/system.h\n\
+             src_cmp_SOURCES = src/cmp.c $(common) # comment\n\
+             src_cmp_SOURCES += src/cmp-extra.c\n\
+             bin_PROGRAMS += $(sdiff_prog)\n\
+             EXTRA_src_sdiff_SOURCES = src/sdiff-old.c\n\
+             src_sdiff_SOURCES = src/sdiff.c\n",
+        );
+
+        assert_eq!(
+            Some(vec![
+                "src/cmp.c".to_string(),
+                "src/system.c".to_string(),
+                "src/system.h".to_string(),
+                "src/cmp-extra.c".to_string(),
+            ]),
+            program_sources(&variables, "cmp")
+        );
+        assert_eq!(
+            Some(vec!["src/diff.c".to_string()]),
+            program_sources(&variables, "diff")
+        );
+        assert_eq!(
+            Some(vec!["src/sdiff.c".to_string()]),
+            program_sources(&variables, "sdiff")
+        );
+        assert_eq!(None, program_sources(&variables, "grep"));
+    }
+
+    /// Tests that a source listed more than once, even apart, is found once.
+    #[test]
+    fn test_find_sources_dedup() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        fs::create_dir(root.join("src")).unwrap();
+        for name in ["cmp.c", "system.c", "system.h"] {
+            fs::write(root.join("src").join(name), "").unwrap();
+        }
+        fs::write(
+            root.join("Makefile.am"),
+            "common = src/system.c src/system.h\n\
+             src_cmp_SOURCES = src/system.c src/cmp.c $(common) src/cmp.c\n\
+             bin_PROGRAMS = src/cmp\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec!["src/system.c", "src/cmp.c", "src/system.h"],
+            find_sources(root, "cmp").unwrap()
+        );
+    }
+}
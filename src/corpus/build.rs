@@ -0,0 +1,250 @@
+//! # Build Verification
+//!
+//! This module attempts to compile the C and Rust programs of downloaded
+//! program pairs.  Metadata often lists only a subset of a project's sources,
+//! so a downloaded pair does not necessarily compile; the results of each
+//! attempt are recorded in `program_pairs/build-results.json`, and printed
+//! as JSON with `--json`.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{errors::BuildError, manifest, output, schema::Language, utils},
+    paths::{BUILD_RESULTS_FILE, PROGRAM_PAIRS_DIRECTORY},
+};
+
+/// Number of trailing compiler output lines kept for a failed build.
+const LOG_LINES: usize = 20;
+
+/// How the C program of a pair is compiled.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum CBuildMode {
+    /// Compile each `.c` file separately with `<cc> -c`.
+    PerFile,
+    /// Run `make` in the C program's directory.
+    Make,
+}
+
+/// Options controlling how program pairs are built.
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    /// How the C program is compiled.
+    pub c_mode: CBuildMode,
+    /// The C compiler used in [`CBuildMode::PerFile`] mode.
+    pub compiler: String,
+}
+
+/// The outcome of compiling one program.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildOutcome {
+    /// Whether the program compiled.
+    pub compiled: bool,
+    /// The last lines of compiler output if compilation failed.
+    pub log: String,
+}
+
+/// The outcome of compiling both programs of a pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildResult {
+    /// The outcome for the C program.
+    pub c_program: BuildOutcome,
+    /// The outcome for the Rust program.
+    pub rust_program: BuildOutcome,
+}
+
+/// The pairs [`build`] built, as printed with `--json`.
+#[derive(Debug, Serialize)]
+struct BuildReport {
+    /// The outcome of each pair built, by name.
+    pairs: BTreeMap<String, BuildResult>,
+}
+
+/// Builds one or all downloaded program pairs, prints a summary, and
+/// records the results in `program_pairs/build-results.json`.
+///
+/// # Arguments
+///
+/// - `program_name` - The pair to build, or `None` to build every pair.
+/// - `options` - How to compile the programs.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`BuildError`] on failure.  A program
+/// that fails to compile is not an error.
+pub fn build(program_name: Option<&str>, options: &BuildOptions) -> Result<(), BuildError> {
+    let pairs_directory = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    let pair_directories = match program_name {
+        Some(program_name) => {
+            let pair_directory = pairs_directory.join(program_name);
+            if !pair_directory.is_dir() {
+                return Err(BuildError::PairNotFound {
+                    program_name: program_name.to_string(),
+                });
+            }
+            vec![pair_directory]
+        }
+        None => utils::list_program_pairs(pairs_directory).map_err(|error| BuildError::IoRead {
+            path: pairs_directory.to_path_buf(),
+            error,
+        })?,
+    };
+
+    let mut results = load_results()?;
+    let mut built = BTreeMap::new();
+    for pair_directory in pair_directories {
+        let name = pair_directory
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let result = BuildResult {
+            c_program: build_c(
+                &manifest::program_directory(&pair_directory, &Language::C),
+                options,
+            ),
+            rust_program: build_rust(&manifest::program_directory(
+                &pair_directory,
+                &Language::Rust,
+            )),
+        };
+        if !output::json() {
+            println!(
+                "{:<24} C: {:<8} Rust: {}",
+                name,
+                status(&result.c_program),
+                status(&result.rust_program)
+            );
+        }
+        results.insert(name.clone(), result.clone());
+        built.insert(name, result);
+    }
+
+    save_results(&results)?;
+    if output::json() {
+        output::print_json(&BuildReport { pairs: built });
+    }
+    Ok(())
+}
+
+/// Compiles the C program in `directory` according to `options`.
+fn build_c(directory: &Path, options: &BuildOptions) -> BuildOutcome {
+    match options.c_mode {
+        CBuildMode::Make => run(Command::new("make").current_dir(directory)),
+        CBuildMode::PerFile => {
+            let sources: Vec<PathBuf> = utils::list_files(directory)
+                .into_iter()
+                .filter(|path| path.extension().is_some_and(|extension| extension == "c"))
+                .collect();
+            if sources.is_empty() {
+                return failure("No C source files found".to_string());
+            }
+
+            let object = env::temp_dir().join(format!("c-rust-build-{}.o", std::process::id()));
+            let mut log = String::new();
+            for source in &sources {
+                let outcome = run(Command::new(&options.compiler)
+                    .arg("-c")
+                    .arg(source)
+                    .arg("-o")
+                    .arg(&object)
+                    .arg("-I")
+                    .arg(directory));
+                if !outcome.compiled {
+                    log.push_str(&outcome.log);
+                    log.push('\n');
+                }
+            }
+            let _ = fs::remove_file(&object);
+
+            BuildOutcome {
+                compiled: log.is_empty(),
+                log: tail(&log),
+            }
+        }
+    }
+}
+
+/// Runs `cargo build` for the Rust program in `directory`.
+///
+/// The Rust program can only be built if its `Cargo.toml` was downloaded
+/// along with its sources.
+fn build_rust(directory: &Path) -> BuildOutcome {
+    if !directory.join("Cargo.toml").is_file() {
+        return failure("No Cargo.toml found".to_string());
+    }
+    run(Command::new("cargo")
+        .args(["build", "--quiet"])
+        .current_dir(directory))
+}
+
+/// Runs a compiler command and captures its outcome.
+fn run(command: &mut Command) -> BuildOutcome {
+    match command.output() {
+        Ok(output) => BuildOutcome {
+            compiled: output.status.success(),
+            log: if output.status.success() {
+                String::new()
+            } else {
+                tail(&format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                ))
+            },
+        },
+        Err(error) => failure(format!(
+            "Failed to run '{}': {}",
+            command.get_program().to_string_lossy(),
+            error
+        )),
+    }
+}
+
+/// Creates a failed [`BuildOutcome`] with the given log.
+fn failure(log: String) -> BuildOutcome {
+    BuildOutcome {
+        compiled: false,
+        log,
+    }
+}
+
+/// Keeps only the last [`LOG_LINES`] lines of compiler output.
+fn tail(log: &str) -> String {
+    let lines: Vec<&str> = log.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(LOG_LINES)..].join("\n")
+}
+
+/// Describes an outcome for the summary table.
+fn status(outcome: &BuildOutcome) -> &'static str {
+    if outcome.compiled { "ok" } else { "FAILED" }
+}
+
+/// Loads previously recorded build results, if any.
+fn load_results() -> Result<BTreeMap<String, BuildResult>, BuildError> {
+    let path = Path::new(BUILD_RESULTS_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let contents = fs::read_to_string(path).map_err(|error| BuildError::IoRead {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    serde_json::from_str(&contents).map_err(|error| BuildError::Serialize { error })
+}
+
+/// Writes build results to `program_pairs/build-results.json`.
+fn save_results(results: &BTreeMap<String, BuildResult>) -> Result<(), BuildError> {
+    let path = Path::new(BUILD_RESULTS_FILE);
+    let contents =
+        serde_json::to_string_pretty(results).map_err(|error| BuildError::Serialize { error })?;
+    fs::write(path, contents).map_err(|error| BuildError::IoWrite {
+        path: path.to_path_buf(),
+        error,
+    })
+}
@@ -0,0 +1,117 @@
+//! # Build Files
+//!
+//! This module finds the files needed to build a program that live next to,
+//! rather than among, its sources, such as the `Cargo.toml` of a Rust crate
+//! or the `Makefile` and `configure.ac` of a C project.  Metadata usually
+//! lists only a program's sources, so without them `build` cannot compile
+//! the downloaded program.
+
+use std::path::{Path, PathBuf};
+
+use crate::corpus::schema::{Language, RelativeSourcePath};
+
+/// Names of the build files detected for a language.
+fn build_file_names(language: &Language) -> &'static [&'static str] {
+    match language {
+        Language::C => &["Makefile", "configure.ac"],
+        Language::Cpp => &["Makefile", "CMakeLists.txt", "configure.ac"],
+        Language::Rust => &["Cargo.toml"],
+        Language::Go => &["go.mod"],
+        Language::Zig => &["build.zig", "build.zig.zon"],
+    }
+}
+
+/// Finds the build files closest to a program's sources.
+///
+/// For each source path, the directory it names, or the directory holding
+/// it if it is a file, is searched first and then each of its parents up to
+/// the repository root; the closest file of each name is taken.
+///
+/// # Arguments
+///
+/// - `repository_directory` - The root of the program's repository.
+/// - `language` - The program's language, which decides the file names.
+/// - `source_paths` - The program's `source_paths`.
+///
+/// # Returns
+///
+/// The paths of the build files, sorted and without duplicates.
+pub fn find_build_files(
+    repository_directory: &Path,
+    language: &Language,
+    source_paths: &[RelativeSourcePath],
+) -> Vec<PathBuf> {
+    let mut build_files = Vec::new();
+    for source_path in source_paths {
+        let source = repository_directory.join(source_path.to_path());
+        let mut directory = if source.is_dir() {
+            Some(source.as_path())
+        } else {
+            source.parent()
+        };
+        let mut names: Vec<&str> = build_file_names(language).to_vec();
+        while let Some(current) = directory
+            .filter(|current| current.starts_with(repository_directory) && !names.is_empty())
+        {
+            names.retain(|name| {
+                let path = current.join(name);
+                let found = path.is_file();
+                if found {
+                    build_files.push(path);
+                }
+                !found
+            });
+            directory = current.parent();
+        }
+    }
+    build_files.sort();
+    build_files.dedup();
+    build_files
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Tests that the closest build file of each name is found, searching
+    /// up from each source path.
+    #[test]
+    fn test_find_build_files() {
+        let repository = tempfile::tempdir().unwrap();
+        let root = repository.path();
+        for file in [
+            "Cargo.toml",
+            "Makefile",
+            "configure.ac",
+            "src/uu/ls/Cargo.toml",
+            "src/uu/ls/src/ls.rs",
+            "lib/Makefile",
+            "lib/util.c",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let paths = |paths: &[&str]| -> Vec<RelativeSourcePath> {
+            paths
+                .iter()
+                .map(|path| RelativeSourcePath::new(path.to_string()).unwrap())
+                .collect()
+        };
+
+        assert_eq!(
+            vec![root.join("src/uu/ls/Cargo.toml")],
+            find_build_files(root, &Language::Rust, &paths(&["src/uu/ls/src"]))
+        );
+        assert_eq!(
+            vec![root.join("configure.ac"), root.join("lib/Makefile")],
+            find_build_files(root, &Language::C, &paths(&["lib/util.c"]))
+        );
+        assert_eq!(
+            vec![root.join("Makefile"), root.join("configure.ac")],
+            find_build_files(root, &Language::C, &paths(&["missing/file.c"]))
+        );
+    }
+}
@@ -0,0 +1,1051 @@
+//! # Repository Clone Cache
+//!
+//! This module manages `repository_clones/`, the local cache of git
+//! repositories that program pairs are copied from.  Clones are keyed by
+//! their normalized repository URL, so a repository hosting both C and Rust
+//! programs is only cloned once.  Tarball and file sources are cached the
+//! same way, and are managed like clones.
+//!
+//! The last time each clone was used by a download is recorded in
+//! `repository_clones/cache-index.json`, so that the cache can be pruned
+//! in least-recently-used order.  The index also records the clones of
+//! metadata repositories given with `--metadata-repo`, which no metadata
+//! file references but are still in use.
+//!
+//! A clone can break, such as when a clone is interrupted or objects are
+//! deleted.  Downloads check each clone's health before using it and clone
+//! broken ones again, and `cache fsck` checks every clone.
+//!
+//! The `cache` commands print their results as JSON with `--json`.
+//!
+//! Downloads running at the same time, in one process or several, take a
+//! [`CacheLock`] on each clone they use, so that one never fetches into or
+//! checks out a clone while another is cloning it or copying from it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, Write},
+    iter,
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git2::{ObjectType, Repository, TreeWalkMode, TreeWalkResult, build::CheckoutBuilder};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+    corpus::{errors::CacheError, output, parser, utils},
+    paths::{
+        CACHE_INDEX_FILE, DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY, REPOSITORY_CLONES_DIRECTORY,
+    },
+};
+
+/// Suffix of the lock file beside each locked clone and the cache index.
+const LOCK_SUFFIX: &str = ".lock";
+
+/// Name of the file in a cached tarball or file source that records the
+/// source's SHA-256, so that a source whose checksum changes in the metadata
+/// is fetched again.  It also marks the directory as a cache entry, as
+/// `.git` marks a clone.
+pub const SOURCE_CHECKSUM_FILE: &str = ".source-sha256";
+
+/// Bookkeeping about cached clones, stored in
+/// `repository_clones/cache-index.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    /// Unix timestamp of the last use of each clone, keyed by the clone's
+    /// path relative to `repository_clones/`.
+    last_used: BTreeMap<String, u64>,
+    /// The clones of metadata repositories, keyed as in `last_used`.
+    #[serde(default)]
+    metadata_repositories: BTreeSet<String>,
+}
+
+/// The cached clones, as printed by [`size`] with `--json`.
+#[derive(Debug, Serialize)]
+struct SizeReport {
+    /// Every cached clone, sorted by path.
+    clones: Vec<SizedClone>,
+    /// Disk usage of the whole cache in bytes.
+    total_bytes: u64,
+}
+
+/// A cached clone, as printed by [`size`] with `--json`.
+#[derive(Debug, Serialize)]
+struct SizedClone {
+    /// The clone's directory.
+    path: PathBuf,
+    /// Disk usage of the clone in bytes.
+    bytes: u64,
+    /// Number of programs in the metadata referencing the clone.
+    references: usize,
+    /// Unix timestamp of the last download that used the clone, if known.
+    last_used: Option<u64>,
+}
+
+/// What [`prune`] or [`clear`] removed, as printed with `--json`.
+#[derive(Debug, Default, Serialize)]
+struct RemoveReport {
+    /// The removed clones with their disk usage in bytes.
+    removed: Vec<RemovedClone>,
+    /// The clones kept because a download was using them.
+    skipped: Vec<PathBuf>,
+    /// Disk usage of the cache afterwards in bytes.
+    total_bytes: u64,
+}
+
+/// A clone removed from the cache, as printed with `--json`.
+#[derive(Debug, Serialize)]
+struct RemovedClone {
+    /// The clone's directory.
+    path: PathBuf,
+    /// Disk usage of the clone before it was removed, in bytes.
+    bytes: u64,
+}
+
+/// The health of the cached clones, as printed by [`fsck`] with `--json`.
+#[derive(Debug, Default, Serialize)]
+struct FsckReport {
+    /// Number of clones checked.
+    checked: usize,
+    /// The unhealthy clones.
+    unhealthy: Vec<UnhealthyClone>,
+}
+
+/// An unhealthy clone, as printed by [`fsck`] with `--json`.
+#[derive(Debug, Serialize)]
+struct UnhealthyClone {
+    /// The clone's directory.
+    path: PathBuf,
+    /// Why the clone cannot be used.
+    problem: String,
+    /// Whether the clone was removed.
+    removed: bool,
+}
+
+/// Information about one cached clone.
+#[derive(Debug)]
+pub struct CloneInfo {
+    /// The clone's directory.
+    pub path: PathBuf,
+    /// Disk usage of the clone in bytes.
+    pub size: u64,
+    /// Unix timestamp of the last download that used the clone, if known.
+    pub last_used: Option<u64>,
+}
+
+/// Why a cached clone cannot be used.
+#[derive(Debug)]
+pub enum CloneProblem {
+    /// The directory cannot be opened as a git repository.
+    Unreadable(git2::Error),
+    /// The repository has no working directory, or its working directory is
+    /// somewhere else.
+    NoWorkdir,
+    /// `HEAD` does not resolve to a commit.
+    UnresolvableHead(git2::Error),
+    /// The trees of the `HEAD` commit cannot be read.
+    UnreadableTree(git2::Error),
+    /// This many files of the `HEAD` commit are missing from the object
+    /// database.
+    MissingObjects(usize),
+    /// This many files of the `HEAD` commit are missing from the working
+    /// directory.
+    MissingFiles(usize),
+}
+
+impl fmt::Display for CloneProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloneProblem::Unreadable(error) => {
+                write!(f, "not a readable git repository: {}", error.message())
+            }
+            CloneProblem::NoWorkdir => write!(f, "the working directory is missing"),
+            CloneProblem::UnresolvableHead(error) => {
+                write!(f, "HEAD does not resolve to a commit: {}", error.message())
+            }
+            CloneProblem::UnreadableTree(error) => {
+                write!(f, "the tree of HEAD cannot be read: {}", error.message())
+            }
+            CloneProblem::MissingObjects(count) => {
+                write!(f, "{count} objects of HEAD are missing")
+            }
+            CloneProblem::MissingFiles(count) => {
+                write!(
+                    f,
+                    "{count} files of HEAD are missing from the working directory"
+                )
+            }
+        }
+    }
+}
+
+/// Prints the disk usage of every cached clone, along with how many
+/// programs reference it and when it was last used.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`CacheError`] on failure.
+pub fn size() -> Result<(), CacheError> {
+    let clones = clone_info(Path::new(""))?;
+    let reference_counts = reference_counts()?;
+    let references = |clone: &CloneInfo| reference_counts.get(&clone.path).copied().unwrap_or(0);
+    let total_bytes = clones.iter().map(|clone| clone.size).sum();
+
+    if output::json() {
+        let clones = clones
+            .iter()
+            .map(|clone| SizedClone {
+                path: clone.path.clone(),
+                bytes: clone.size,
+                references: references(clone),
+                last_used: clone.last_used,
+            })
+            .collect();
+        output::print_json(&SizeReport {
+            clones,
+            total_bytes,
+        });
+        return Ok(());
+    }
+
+    let now = unix_time();
+    println!(
+        "{:>10} {:>10} {:>10}  Clone",
+        "Size", "References", "Last used"
+    );
+    for clone in &clones {
+        let last_used = match clone.last_used {
+            Some(last_used) => format_age(now.saturating_sub(last_used)),
+            None => "never".to_string(),
+        };
+        println!(
+            "{:>10} {:>10} {:>10}  {}",
+            utils::format_size(clone.size),
+            references(clone),
+            last_used,
+            clone.path.display()
+        );
+    }
+    println!(
+        "{:>10} in {} clones",
+        utils::format_size(total_bytes),
+        clones.len()
+    );
+    Ok(())
+}
+
+/// Removes least recently used clones until the cache fits within a size.
+/// Clones that a download is using are skipped.
+///
+/// # Arguments
+///
+/// - `max_size` - The maximum cache size, such as "10G".
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`CacheError`] on failure.
+pub fn prune(max_size: &str) -> Result<(), CacheError> {
+    let max_size = utils::parse_size(max_size).ok_or_else(|| CacheError::InvalidSize {
+        size: max_size.to_string(),
+    })?;
+
+    let mut clones = clone_info(Path::new(""))?;
+    clones.sort_by_key(|clone| clone.last_used.unwrap_or(0));
+    let mut report = RemoveReport {
+        total_bytes: clones.iter().map(|clone| clone.size).sum(),
+        ..RemoveReport::default()
+    };
+
+    let json = output::json();
+    for clone in clones {
+        if report.total_bytes <= max_size {
+            break;
+        }
+        if !try_remove_clone(Path::new(""), &clone.path)? {
+            if !json {
+                println!(
+                    "Skipping '{}', which a download is using",
+                    clone.path.display()
+                );
+            }
+            report.skipped.push(clone.path);
+            continue;
+        }
+        if !json {
+            println!(
+                "Removed '{}' ({})",
+                clone.path.display(),
+                utils::format_size(clone.size)
+            );
+        }
+        report.total_bytes -= clone.size;
+        report.removed.push(RemovedClone {
+            path: clone.path,
+            bytes: clone.size,
+        });
+    }
+
+    if json {
+        output::print_json(&report);
+    } else {
+        println!(
+            "Cache size is now {}",
+            utils::format_size(report.total_bytes)
+        );
+    }
+    Ok(())
+}
+
+/// Removes the cached clone of one repository.
+///
+/// # Arguments
+///
+/// - `repository` - The repository's URL, its path within
+///   `repository_clones/`, or its name.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`CacheError`] if no single clone
+/// matches `repository`, a download is using it, or it cannot be removed.
+pub fn clear(repository: &str) -> Result<(), CacheError> {
+    let root = Path::new("");
+    let by_url = clone_directory(root, repository);
+    let candidates: Vec<PathBuf> = if is_cache_entry(&by_url) {
+        vec![by_url]
+    } else {
+        list_clones(root)
+            .into_iter()
+            .filter(|clone| {
+                let key = index_key(root, clone);
+                key == repository
+                    || key.ends_with(&format!("/{repository}"))
+                    || clone.file_name().is_some_and(|name| name == repository)
+            })
+            .collect()
+    };
+
+    match candidates.as_slice() {
+        [] => Err(CacheError::CloneNotFound {
+            repository: repository.to_string(),
+        }),
+        [clone] => {
+            let bytes = utils::directory_size(clone);
+            if !try_remove_clone(root, clone)? {
+                return Err(CacheError::CloneInUse {
+                    path: clone.clone(),
+                });
+            }
+            if output::json() {
+                let total_bytes = clone_info(root)?.iter().map(|clone| clone.size).sum();
+                output::print_json(&RemoveReport {
+                    removed: vec![RemovedClone {
+                        path: clone.clone(),
+                        bytes,
+                    }],
+                    skipped: Vec::new(),
+                    total_bytes,
+                });
+            } else {
+                println!("Removed '{}'", clone.display());
+            }
+            Ok(())
+        }
+        _ => Err(CacheError::AmbiguousRepository {
+            repository: repository.to_string(),
+            candidates: candidates
+                .iter()
+                .map(|clone| index_key(root, clone))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }),
+    }
+}
+
+/// Checks the health of every cached clone.
+///
+/// # Arguments
+///
+/// - `remove` - Whether to remove unhealthy clones, so that the next
+///   download clones them again; clones that a download is using are
+///   skipped.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if every clone is healthy or unhealthy clones were
+/// removed, and [`CacheError`] otherwise.
+pub fn fsck(remove: bool) -> Result<(), CacheError> {
+    let clones = list_clones(Path::new(""));
+    let json = output::json();
+    let mut report = FsckReport {
+        checked: clones.len(),
+        ..FsckReport::default()
+    };
+    for clone in &clones {
+        // Tarball and file sources are checked against their checksum when
+        // they are used instead.
+        if !clone.join(".git").exists() {
+            continue;
+        }
+        let Some(problem) = diagnose(clone) else {
+            continue;
+        };
+        if !json {
+            println!("'{}': {}", clone.display(), problem);
+        }
+        let removed = remove && try_remove_clone(Path::new(""), clone)?;
+        if remove && !json {
+            if removed {
+                println!("Removed '{}'", clone.display());
+            } else {
+                println!("Skipping '{}', which a download is using", clone.display());
+            }
+        }
+        report.unhealthy.push(UnhealthyClone {
+            path: clone.clone(),
+            problem: problem.to_string(),
+            removed,
+        });
+    }
+
+    let unhealthy = report.unhealthy.len();
+    if json {
+        output::print_json(&report);
+    } else {
+        println!("{} of {} clones are unhealthy", unhealthy, clones.len());
+    }
+    if unhealthy > 0 && !remove {
+        return Err(CacheError::UnhealthyClones { count: unhealthy });
+    }
+    Ok(())
+}
+
+/// Checks whether a cached clone can be copied from: it must open as a
+/// repository with its own working directory, and every file of its `HEAD`
+/// commit must be in both its object database and its working directory.
+///
+/// # Arguments
+///
+/// - `clone` - The clone's directory within `repository_clones/`.
+///
+/// # Returns
+///
+/// The [`CloneProblem`] found, or `None` if the clone is healthy.
+pub fn diagnose(clone: &Path) -> Option<CloneProblem> {
+    let repository = match Repository::open(clone) {
+        Ok(repository) => repository,
+        Err(error) => return Some(CloneProblem::Unreadable(error)),
+    };
+    let workdir = repository
+        .workdir()
+        .and_then(|workdir| workdir.canonicalize().ok());
+    if workdir.is_none() || workdir != clone.canonicalize().ok() {
+        return Some(CloneProblem::NoWorkdir);
+    }
+    let tree = match repository.head().and_then(|head| head.peel_to_tree()) {
+        Ok(tree) => tree,
+        Err(error) => return Some(CloneProblem::UnresolvableHead(error)),
+    };
+    let odb = match repository.odb() {
+        Ok(odb) => odb,
+        Err(error) => return Some(CloneProblem::Unreadable(error)),
+    };
+
+    // Submodules are commits in the tree, and are checked out separately.
+    let mut missing_objects = 0;
+    let mut missing_files = 0;
+    let walked = tree.walk(TreeWalkMode::PreOrder, |directory, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            if !odb.exists(entry.id()) {
+                missing_objects += 1;
+            }
+            let path = clone
+                .join(directory)
+                .join(String::from_utf8_lossy(entry.name_bytes()).as_ref());
+            if fs::symlink_metadata(path).is_err() {
+                missing_files += 1;
+            }
+        }
+        TreeWalkResult::Ok
+    });
+    if let Err(error) = walked {
+        Some(CloneProblem::UnreadableTree(error))
+    } else if missing_objects > 0 {
+        Some(CloneProblem::MissingObjects(missing_objects))
+    } else if missing_files > 0 {
+        Some(CloneProblem::MissingFiles(missing_files))
+    } else {
+        None
+    }
+}
+
+/// An exclusive advisory lock on a cached clone or the cache index.
+///
+/// The lock is held on a file beside the locked path, such as
+/// `repository_clones/github.com/uutils/coreutils.lock`, which holds the
+/// process ID of its holder.  The lock is released when dropped, emptying
+/// the file, or by the operating system when its holder exits, so a lock
+/// never outlives its holder.  A lock whose file still holds a process ID
+/// when it is taken was left by a holder that exited without releasing it,
+/// such as a download that was killed; see [`CacheLock::interrupted`].
+#[derive(Debug)]
+pub struct CacheLock {
+    /// The open lock file, which holds the lock until it is closed.
+    file: File,
+    /// The lock file's path.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    path: PathBuf,
+    /// Whether the previous holder exited without releasing the lock.
+    interrupted: bool,
+}
+
+impl CacheLock {
+    /// Takes the lock on a path in `repository_clones/`, or on the staging
+    /// directory of a program pair, waiting for another download, in this
+    /// process or another, to release it first.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The clone, file, or directory to lock, which need not exist
+    ///   yet.
+    /// - `on_wait` - Called before waiting for the lock, with the process ID
+    ///   of its holder if it is known.
+    ///
+    /// # Returns
+    ///
+    /// The [`CacheLock`], or [`CacheError::IoWrite`] if the lock file cannot
+    /// be created or locked.
+    pub fn acquire(path: &Path, on_wait: impl FnOnce(Option<u32>)) -> Result<Self, CacheError> {
+        Self::take(path, Some(on_wait)).map(|lock| {
+            lock.expect("Unreachable because a lock that is waited for is always taken")
+        })
+    }
+
+    /// Takes the lock on a path in `repository_clones/` only if no other
+    /// download, in this process or another, holds it.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The clone, file, or directory to lock, which need not exist
+    ///   yet.
+    ///
+    /// # Returns
+    ///
+    /// The [`CacheLock`], `None` if another download holds it, or
+    /// [`CacheError::IoWrite`] if the lock file cannot be created or locked.
+    pub fn try_acquire(path: &Path) -> Result<Option<Self>, CacheError> {
+        Self::take(path, None::<fn(Option<u32>)>)
+    }
+
+    /// Takes the lock on a path, waiting for its holder to release it only
+    /// if `on_wait` is given.
+    ///
+    /// # Returns
+    ///
+    /// The [`CacheLock`], `None` if it is held and `on_wait` is `None`, or
+    /// [`CacheError::IoWrite`] if the lock file cannot be created or locked.
+    fn take(
+        path: &Path,
+        on_wait: Option<impl FnOnce(Option<u32>)>,
+    ) -> Result<Option<Self>, CacheError> {
+        let lock_path = PathBuf::from(format!("{}{LOCK_SUFFIX}", path.display()));
+        let lock_error = |error| CacheError::IoWrite {
+            path: lock_path.clone(),
+            error,
+        };
+        let wait = on_wait.is_some();
+        let mut on_wait = on_wait;
+        let mut file = loop {
+            if let Some(parent) = lock_path.parent() {
+                fs::create_dir_all(parent).map_err(lock_error)?;
+            }
+            let mut file = match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&lock_path)
+            {
+                Ok(file) => file,
+                // Its directory was removed by the holder of another lock
+                // in it, which removed its own lock once done.
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(lock_error(error)),
+            };
+            if file.try_lock().is_err() {
+                if !wait {
+                    return Ok(None);
+                }
+                if let Some(on_wait) = on_wait.take() {
+                    on_wait(holder(&mut file));
+                }
+                file.lock().map_err(lock_error)?;
+            }
+            // A holder that removed the lock file released the lock on a
+            // file no other download can open any more, so the lock is
+            // taken again on the file now at its path.
+            if is_lock_file(&file, &lock_path) {
+                break file;
+            }
+        };
+
+        let interrupted = holder(&mut file).is_some();
+        file.set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| write!(file, "{}", process::id()))
+            .map_err(lock_error)?;
+        Ok(Some(Self {
+            file,
+            path: lock_path,
+            interrupted,
+        }))
+    }
+
+    /// Returns whether the previous holder of the lock exited without
+    /// releasing it, so that the locked clone may have been left half
+    /// updated.
+    pub fn interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// Releases the lock and removes its lock file, such as once the
+    /// directory it locks is gone.  Downloads waiting for the lock then
+    /// take it on a new lock file.
+    pub fn remove(self) {
+        // The file is removed while the lock is held, so no download takes
+        // the lock on it afterwards without finding it removed.  Windows
+        // cannot remove a file other downloads hold open, so it is kept.
+        #[cfg(unix)]
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Whether an open lock file is still the file at its path.
+///
+/// # Arguments
+///
+/// - `file` - The open lock file.
+/// - `lock_path` - The path it was opened at.
+///
+/// # Returns
+///
+/// `false` if the file was removed from its path since it was opened, or
+/// replaced by another; always `true` where lock files are never removed.
+#[cfg(unix)]
+fn is_lock_file(file: &File, lock_path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(lock_path)) {
+        (Ok(open), Ok(current)) => open.dev() == current.dev() && open.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+/// Whether an open lock file is still the file at its path.
+///
+/// # Returns
+///
+/// Always `true`, since lock files are only removed on Unix.
+#[cfg(not(unix))]
+fn is_lock_file(_file: &File, _lock_path: &Path) -> bool {
+    true
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        // An empty lock file marks the lock as released cleanly; closing the
+        // file releases the lock itself.
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// Reads the process ID of the holder of a lock from its lock file.
+///
+/// # Arguments
+///
+/// - `file` - The open lock file.
+///
+/// # Returns
+///
+/// The process ID, or `None` if the file is empty or cannot be read.
+fn holder(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+/// Restores the working directory of a clone to its `HEAD` commit, such as
+/// after a download was killed while checking out another commit.  A clone
+/// that cannot be restored is removed, so that it is cloned again.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `clone` - The clone's directory within `repository_clones/`, which need
+///   not exist.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`CacheError`] if the clone cannot be
+/// removed.
+pub fn restore_clone(root: &Path, clone: &Path) -> Result<(), CacheError> {
+    if !clone.exists() {
+        return Ok(());
+    }
+    let restored = Repository::open(clone)
+        .and_then(|repository| repository.checkout_head(Some(CheckoutBuilder::new().force())));
+    if restored.is_err() {
+        remove_clone(root, clone)?;
+    }
+    Ok(())
+}
+
+/// Returns the directory in which a repository is cached.
+///
+/// # Example
+///
+/// "https://github.com/eza-community/eza.git" is cached in
+/// `repository_clones/github.com/eza-community/eza`.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `repository_url` - The repository's URL.
+///
+/// # Returns
+///
+/// The path of the cached clone, which may not exist yet.
+pub fn clone_directory(root: &Path, repository_url: &str) -> PathBuf {
+    let key = utils::normalize_repository_url(repository_url).replace(':', "_");
+    let mut path = root.join(REPOSITORY_CLONES_DIRECTORY);
+    for segment in key.split('/') {
+        if !matches!(segment, "" | "." | "..") {
+            path.push(segment);
+        }
+    }
+    path
+}
+
+/// Lists the repositories cached in `repository_clones/`.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+///
+/// # Returns
+///
+/// The directories of all cached clones and tarball and file sources,
+/// sorted by path.
+pub fn list_clones(root: &Path) -> Vec<PathBuf> {
+    let mut clones = Vec::new();
+    let mut walker = WalkDir::new(root.join(REPOSITORY_CLONES_DIRECTORY))
+        .min_depth(1)
+        .into_iter();
+    while let Some(Ok(entry)) = walker.next() {
+        if entry.file_type().is_dir() && is_cache_entry(entry.path()) {
+            clones.push(entry.into_path());
+            walker.skip_current_dir();
+        }
+    }
+    clones.sort();
+    clones
+}
+
+/// Checks whether a directory in `repository_clones/` is a cache entry: a
+/// clone, or a downloaded tarball or file source.
+///
+/// # Arguments
+///
+/// - `directory` - The directory, which need not exist.
+///
+/// # Returns
+///
+/// `true` if the directory holds `.git` or [`SOURCE_CHECKSUM_FILE`].
+fn is_cache_entry(directory: &Path) -> bool {
+    directory.join(".git").exists() || directory.join(SOURCE_CHECKSUM_FILE).is_file()
+}
+
+/// Removes a cached clone, along with any parent directories left empty.
+/// The caller holds the clone's [`CacheLock`], such as a download removing
+/// a broken clone before cloning it again.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `clone` - The clone's directory within `repository_clones/`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`CacheError`] on failure.
+pub fn remove_clone(root: &Path, clone: &Path) -> Result<(), CacheError> {
+    fs::remove_dir_all(clone).map_err(|error| CacheError::IoDelete {
+        path: clone.to_path_buf(),
+        error,
+    })?;
+    forget_clone(root, clone)
+}
+
+/// Removes a cached clone like [`remove_clone`], unless a download is using
+/// it: the clone's [`CacheLock`] is only taken if no download holds it, so
+/// that a clone is never removed while it is cloned, fetched, or copied
+/// from.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `clone` - The clone's directory within `repository_clones/`.
+///
+/// # Returns
+///
+/// Whether the clone was removed, `false` if a download holds its lock, or
+/// [`CacheError`] on failure.
+pub fn try_remove_clone(root: &Path, clone: &Path) -> Result<bool, CacheError> {
+    let Some(lock) = CacheLock::try_acquire(clone)? else {
+        return Ok(false);
+    };
+    fs::remove_dir_all(clone).map_err(|error| CacheError::IoDelete {
+        path: clone.to_path_buf(),
+        error,
+    })?;
+    // The lock file is removed too, so its directory can be removed if empty.
+    lock.remove();
+    forget_clone(root, clone)?;
+    Ok(true)
+}
+
+/// Removes the parent directories a removed clone left empty, and its entry
+/// in the cache index.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `clone` - The removed clone's directory within `repository_clones/`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`CacheError`] on failure.
+fn forget_clone(root: &Path, clone: &Path) -> Result<(), CacheError> {
+    // `remove_dir` only succeeds on empty directories.
+    let clones = root.join(REPOSITORY_CLONES_DIRECTORY);
+    for parent in clone.ancestors().skip(1) {
+        if parent == clones || !parent.starts_with(&clones) || fs::remove_dir(parent).is_err() {
+            break;
+        }
+    }
+
+    let _lock = CacheLock::acquire(&root.join(CACHE_INDEX_FILE), |_| {})?;
+    let mut index = load_index(root)?;
+    let key = index_key(root, clone);
+    let removed = index.last_used.remove(&key).is_some();
+    if index.metadata_repositories.remove(&key) || removed {
+        save_index(root, &index)?;
+    }
+    Ok(())
+}
+
+/// Records that a cached clone was just used.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `clone` - The clone's directory within `repository_clones/`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`CacheError`] on failure.
+pub fn record_use(root: &Path, clone: &Path) -> Result<(), CacheError> {
+    let _lock = CacheLock::acquire(&root.join(CACHE_INDEX_FILE), |_| {})?;
+    let mut index = load_index(root)?;
+    index.last_used.insert(index_key(root, clone), unix_time());
+    save_index(root, &index)
+}
+
+/// Records that a cached clone holds a metadata repository, so that it
+/// counts as referenced although no metadata file names it.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `clone` - The clone's directory within `repository_clones/`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`CacheError`] on failure.
+pub fn record_metadata_repository(root: &Path, clone: &Path) -> Result<(), CacheError> {
+    let _lock = CacheLock::acquire(&root.join(CACHE_INDEX_FILE), |_| {})?;
+    let mut index = load_index(root)?;
+    if index.metadata_repositories.insert(index_key(root, clone)) {
+        save_index(root, &index)?;
+    }
+    Ok(())
+}
+
+/// Gathers the size and last use of every cached clone.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+///
+/// # Returns
+///
+/// A [`CloneInfo`] for each clone sorted by path, or [`CacheError`] on
+/// failure.
+pub fn clone_info(root: &Path) -> Result<Vec<CloneInfo>, CacheError> {
+    let index = load_index(root)?;
+    Ok(list_clones(root)
+        .into_iter()
+        .map(|path| CloneInfo {
+            size: utils::directory_size(&path),
+            last_used: index.last_used.get(&index_key(root, &path)).copied(),
+            path,
+        })
+        .collect())
+}
+
+/// Counts how many programs in the metadata reference each cached clone.
+///
+/// Every metadata file is parsed; a file that fails to parse is an error
+/// because the clones it references would otherwise look unused.  The
+/// clone of each metadata repository counts as one reference.
+///
+/// # Returns
+///
+/// A map from clone directory to the number of programs referencing it, or
+/// a [`CacheError`] on failure.
+pub fn reference_counts() -> Result<HashMap<PathBuf, usize>, CacheError> {
+    let mut counts = HashMap::new();
+    let root = Path::new("");
+    for key in load_index(root)?.metadata_repositories {
+        let clone = key
+            .split('/')
+            .fold(root.join(REPOSITORY_CLONES_DIRECTORY), |path, segment| {
+                path.join(segment)
+            });
+        *counts.entry(clone).or_insert(0) += 1;
+    }
+    for directory in [
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+        DEMO_METADATA_DIRECTORY,
+    ] {
+        // Metadata synced from a repository may lack some directories.
+        if !Path::new(directory).is_dir() {
+            continue;
+        }
+        let metadata_files =
+            parser::parse_directory(Path::new(directory)).map_err(|error| CacheError::Parse {
+                path: PathBuf::from(directory),
+                error,
+            })?;
+        for (path, metadata) in metadata_files {
+            let metadata = metadata.map_err(|error| CacheError::Parse { path, error })?;
+            for pair in &metadata.pairs {
+                for program in pair.programs() {
+                    // A clone of a mirror is used in place of the repository
+                    // URL, so it is referenced too.
+                    for url in iter::once(&program.repository_url).chain(&program.mirror_urls) {
+                        let source_url = program.source_url(url);
+                        *counts
+                            .entry(clone_directory(Path::new(""), &source_url))
+                            .or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Returns the key of a clone in the cache index: its path relative to
+/// `repository_clones/`, separated by `/`.
+fn index_key(root: &Path, clone: &Path) -> String {
+    clone
+        .strip_prefix(root.join(REPOSITORY_CLONES_DIRECTORY))
+        .unwrap_or(clone)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Loads the cache index of a corpus root, or an empty index if none has
+/// been written yet.
+fn load_index(root: &Path) -> Result<CacheIndex, CacheError> {
+    let path = &root.join(CACHE_INDEX_FILE);
+    if !path.exists() {
+        return Ok(CacheIndex::default());
+    }
+    let contents = fs::read_to_string(path).map_err(|error| CacheError::IoRead {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    serde_json::from_str(&contents).map_err(|error| CacheError::Serialize { error })
+}
+
+/// Writes the cache index to `repository_clones/cache-index.json` under a
+/// corpus root.
+fn save_index(root: &Path, index: &CacheIndex) -> Result<(), CacheError> {
+    let path = &root.join(CACHE_INDEX_FILE);
+    let contents =
+        serde_json::to_string_pretty(index).map_err(|error| CacheError::Serialize { error })?;
+    let clones = root.join(REPOSITORY_CLONES_DIRECTORY);
+    fs::create_dir_all(&clones).map_err(|error| CacheError::IoWrite {
+        path: clones.clone(),
+        error,
+    })?;
+    fs::write(path, contents).map_err(|error| CacheError::IoWrite {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+/// Returns the current Unix time in seconds.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Formats a duration in seconds as a short age such as "3d ago".
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that a clone is only removed once no download holds its lock,
+    /// and that its lock file and empty parents are removed with it.
+    #[test]
+    fn test_try_remove_clone() {
+        let root = tempfile::tempdir().unwrap();
+        let clone = clone_directory(root.path(), "https://example.com/owner/repository.git");
+        fs::create_dir_all(clone.join(".git")).unwrap();
+
+        let lock = CacheLock::acquire(&clone, |_| {}).unwrap();
+        assert!(!try_remove_clone(root.path(), &clone).unwrap());
+        assert!(clone.is_dir());
+
+        drop(lock);
+        assert!(try_remove_clone(root.path(), &clone).unwrap());
+        assert!(!clone.exists());
+        assert!(
+            !root
+                .path()
+                .join(REPOSITORY_CLONES_DIRECTORY)
+                .join("example.com")
+                .exists()
+        );
+    }
+}
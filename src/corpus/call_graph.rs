@@ -0,0 +1,231 @@
+//! # C Call Graph
+//!
+//! This module prunes the C sources found for a program, such as through its
+//! `Makefile.am`, to those it actually uses, since monolithic repositories
+//! often list shared sources that most of their programs never call into.
+//! Starting from `main`, every function whose name is mentioned in the body
+//! of a reachable function is reachable too, whether it is called or passed
+//! as a function pointer.  A file is kept if it defines a reachable function,
+//! and the names mentioned outside the functions of a kept file, such as in
+//! tables of function pointers, are reachable as well.  Headers are kept if a
+//! kept file includes them.
+//!
+//! Functions are found by [`ast::extract`], and calls are matched by name
+//! alone, so a static function is confused with functions of the same name
+//! in other files; pruning keeps too much rather than too little.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::Path,
+};
+
+use crate::corpus::{analysis::ast, errors::MetadataError, functions, schema::Language, utils};
+
+/// Name of the function a program starts in.
+const ENTRY_POINT: &str = "main";
+
+/// The functions and references of a C source file.
+#[derive(Debug, Default)]
+struct SourceFile {
+    /// The functions defined in the file, with the identifiers their
+    /// definitions mention.
+    functions: Vec<(String, BTreeSet<String>)>,
+    /// The identifiers mentioned outside the file's functions.
+    top_level: BTreeSet<String>,
+    /// Names of the files the file includes with `#include "..."`.
+    includes: BTreeSet<String>,
+}
+
+/// Drops the sources of a program that are not reachable from its `main`.
+///
+/// # Arguments
+///
+/// - `repository` - The root of the checkout.
+/// - `sources` - The program's sources as `/`-separated paths relative to
+///   `repository`.
+///
+/// # Returns
+///
+/// The reachable sources, in the order given, or every source if none
+/// defines `main`, or [`MetadataError::IoRead`] if a source cannot be read.
+pub fn prune(repository: &Path, sources: Vec<String>) -> Result<Vec<String>, MetadataError> {
+    let mut files = Vec::new();
+    for source in &sources {
+        let path = repository.join(utils::platform_path(source));
+        let bytes = fs::read(&path).map_err(|error| MetadataError::IoRead { path, error })?;
+        files.push(analyze(&String::from_utf8_lossy(&bytes)));
+    }
+
+    let Some(reachable) = reachable_files(&sources, &files) else {
+        eprintln!("No source defines {ENTRY_POINT}; keeping every source");
+        return Ok(sources);
+    };
+    eprintln!(
+        "Pruned {} of {} sources unreachable from {ENTRY_POINT}",
+        sources.len() - reachable.len(),
+        sources.len()
+    );
+    Ok(sources
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| reachable.contains(index))
+        .map(|(_, source)| source)
+        .collect())
+}
+
+/// Finds the functions and references of a C source file.
+///
+/// # Arguments
+///
+/// - `source` - The contents of the file.
+///
+/// # Returns
+///
+/// The [`SourceFile`].
+fn analyze(source: &str) -> SourceFile {
+    let code = functions::strip_comments_and_literals(source, &Language::C);
+    let lines: Vec<&str> = code.lines().collect();
+    let mut in_function = vec![false; lines.len()];
+    let mut file = SourceFile::default();
+    for function in ast::extract(source, &Language::C) {
+        let start = function.line.saturating_sub(1).min(lines.len());
+        let end = function.end_line.clamp(start, lines.len());
+        in_function[start..end].fill(true);
+        let mentioned = lines[start..end]
+            .iter()
+            .flat_map(|line| identifiers(line))
+            .filter(|identifier| *identifier != function.name)
+            .map(str::to_string)
+            .collect();
+        file.functions.push((function.name, mentioned));
+    }
+    file.top_level = lines
+        .iter()
+        .zip(&in_function)
+        .filter(|(_, in_function)| !**in_function)
+        .flat_map(|(line, _)| identifiers(line))
+        .map(str::to_string)
+        .collect();
+
+    // Literals are blanked in `code`, so includes are read from the source.
+    file.includes = source
+        .lines()
+        .filter_map(|line| {
+            let directive = line.trim_start().strip_prefix('#')?.trim_start();
+            let (_, name) = directive.strip_prefix("include")?.split_once('"')?;
+            let (name, _) = name.split_once('"')?;
+            Some(name.rsplit('/').next().unwrap_or(name).to_string())
+        })
+        .collect();
+    file
+}
+
+/// Lists the identifiers in a line of code.
+fn identifiers(line: &str) -> impl Iterator<Item = &str> {
+    line.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|word| word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'))
+}
+
+/// Finds the files reachable from `main`.
+///
+/// # Arguments
+///
+/// - `paths` - The `/`-separated path of each file.
+/// - `files` - The functions and references of each file.
+///
+/// # Returns
+///
+/// The indices of the reachable files, or `None` if no file defines `main`.
+fn reachable_files(paths: &[String], files: &[SourceFile]) -> Option<BTreeSet<usize>> {
+    let mut definitions: HashMap<&str, Vec<(usize, &BTreeSet<String>)>> = HashMap::new();
+    for (index, file) in files.iter().enumerate() {
+        for (name, mentioned) in &file.functions {
+            definitions
+                .entry(name.as_str())
+                .or_default()
+                .push((index, mentioned));
+        }
+    }
+    if !definitions.contains_key(ENTRY_POINT) {
+        return None;
+    }
+
+    let mut reachable = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![ENTRY_POINT];
+    while let Some(name) = pending.pop() {
+        if !visited.insert(name) {
+            continue;
+        }
+        for (index, mentioned) in definitions.get(name).into_iter().flatten() {
+            pending.extend(mentioned.iter().map(String::as_str));
+            if reachable.insert(*index) {
+                pending.extend(files[*index].top_level.iter().map(String::as_str));
+            }
+        }
+    }
+
+    // Keep the files that kept files include, and those they include.
+    let file_name = |path: &String| path.rsplit('/').next().unwrap_or(path).to_string();
+    let mut pending: Vec<usize> = reachable.iter().copied().collect();
+    while let Some(index) = pending.pop() {
+        for (included, path) in paths.iter().enumerate() {
+            if files[index].includes.contains(&file_name(path)) && reachable.insert(included) {
+                pending.push(included);
+            }
+        }
+    }
+    Some(reachable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that only the files reachable from `main` through calls,
+    /// function pointers, and includes are kept.
+    #[test]
+    fn test_reachable_files() {
+        let sources = [
+            (
+                "src/cmp.c",
+                "#include \"system.h\"\n\
+                 static int compare(void) { return 0; }\n\
+                 int main(void) {\n    parse_options();\n    return compare();\n}\n",
+            ),
+            (
+                "src/options.c",
+                "#include \"options.h\"\n\
+                 static struct handler handlers[] = { { \"help\", usage } };\n\
+                 void parse_options(void) { dispatch(handlers); }\n",
+            ),
+            ("src/usage.c", "void usage(void) { /* main() */ }\n"),
+            ("src/diff.c", "int diff(void) { return compare(); }\n"),
+            ("src/system.h", "#include \"config.h\"\n"),
+            ("src/config.h", "#define VERSION 1\n"),
+            ("src/options.h", "void parse_options(void);\n"),
+            ("src/unused.h", "void diff(void);\n"),
+        ];
+        let paths: Vec<String> = sources.iter().map(|(path, _)| path.to_string()).collect();
+        let files: Vec<SourceFile> = sources.iter().map(|(_, source)| analyze(source)).collect();
+
+        let reachable: Vec<&str> = reachable_files(&paths, &files)
+            .unwrap()
+            .into_iter()
+            .map(|index| sources[index].0)
+            .collect();
+        assert_eq!(
+            vec![
+                "src/cmp.c",
+                "src/options.c",
+                "src/usage.c",
+                "src/system.h",
+                "src/config.h",
+                "src/options.h",
+            ],
+            reachable
+        );
+        assert!(reachable_files(&paths[3..], &files[3..]).is_none());
+    }
+}
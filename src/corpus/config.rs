@@ -0,0 +1,360 @@
+//! # Configuration File
+//!
+//! This module loads `corpus.toml`, which sets defaults for options that
+//! would otherwise be repeated on every invocation.  The file is looked up in
+//! the current directory, or the corpus root given with `--root`, then in
+//! `c-rust-program-pairs/` under the user's configuration directory, such as
+//! `~/.config` on Linux; only the first file found is used.  Command-line
+//! flags override the file, and flags that turn an option on, such as
+//! `--strict`, turn it off again when given as `--strict=false`.
+//!
+//! ```toml
+//! root = "~/src/c-rust-program-pairs"
+//! jobs = 4
+//!
+//! [download]
+//! clone_depth = 10
+//! proxy = "http://proxy:8080"
+//! max_bandwidth = "2M"
+//! include_extensions = ["inc"]
+//!
+//! [auth]
+//! github_token = "ghp_..."
+//! ```
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::corpus::{
+    downloader::{self, ConflictPolicy, DownloadOptions, GitBackend, Layout, LinkMode},
+    errors::ConfigError,
+    schema::{ExtensionFilter, Language},
+    transform::Transform,
+};
+
+/// Name of the configuration file.
+const CONFIG_FILE: &str = "corpus.toml";
+
+/// Directory holding the configuration file within the user's configuration
+/// directory.
+const CONFIG_DIRECTORY: &str = "c-rust-program-pairs";
+
+/// The contents of `corpus.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The corpus root, holding `metadata/`, that commands run in; relative
+    /// to the configuration file's directory, and `~` is the home directory.
+    pub root: Option<PathBuf>,
+    /// Number of threads used for parallel work, such as parsing metadata
+    /// and copying files.
+    pub jobs: Option<usize>,
+    /// Defaults for the options of the commands that download program pairs.
+    pub download: DownloadConfig,
+    /// Credentials for the APIs queried by `enrich`.
+    pub auth: AuthConfig,
+}
+
+/// Defaults for the options of the commands that download program pairs,
+/// named after their command-line flags.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadConfig {
+    /// Only use repositories already cached in `repository_clones/`.
+    pub offline: bool,
+    /// Number of commits of history to clone, where 0 clones the full
+    /// history.
+    pub clone_depth: Option<u64>,
+    /// Clone the full history of every repository.
+    pub full_history: bool,
+    /// Proxy to clone through.
+    pub proxy: Option<String>,
+    /// File or directory of CA certificates used to verify HTTPS servers.
+    pub ca_bundle: Option<PathBuf>,
+    /// Ignore the user's global and system git configuration.
+    pub isolate_git_config: bool,
+    /// Extensions also copied from `source_paths` directories.
+    pub include_extensions: Vec<String>,
+    /// Extensions never copied from `source_paths` directories.
+    pub exclude_extensions: Vec<String>,
+    /// Also copy the build files closest to each program's sources.
+    pub include_build_files: bool,
+    /// What clones repositories.
+    pub git_backend: Option<GitBackend>,
+    /// Seconds the clone of one repository may take.
+    pub timeout: Option<u64>,
+    /// Maximum transfer rate of clones, such as `2M`.
+    pub max_bandwidth: Option<String>,
+    /// Shell command run in each program pair's directory after download.
+    pub hook: Option<String>,
+    /// How to link files into `program_pairs/` instead of copying them.
+    pub link: Option<LinkMode>,
+    /// Where program files are placed within their pairs' directories.
+    pub layout: Option<Layout>,
+    /// Download even if the disk space looks insufficient.
+    pub skip_space_check: bool,
+    /// What happens to files changed since their pair was downloaded.
+    pub on_conflict: Option<ConflictPolicy>,
+    /// Only download the program pairs with at least one of these tags.
+    pub tags: Vec<String>,
+    /// Only download the program pairs written in these languages.
+    pub languages: Vec<Language>,
+    /// Abort if any metadata file fails to parse.
+    pub strict: bool,
+    /// Transforms run over every placed source file, in order.
+    pub transforms: Vec<Transform>,
+    /// Copy binary files found in `source_paths` directories.
+    pub allow_binary: bool,
+    /// Download the program pairs in a shuffled order.
+    pub shuffle: bool,
+    /// Seed to shuffle the program pairs with.
+    pub seed: Option<u64>,
+    /// Name of the profile whose pairs are downloaded.
+    pub profile: Option<String>,
+    /// URL of a git repository the metadata is fetched from.
+    pub metadata_repo: Option<String>,
+    /// Directory of the metadata repository the metadata is copied from.
+    pub metadata_subdirectory: Option<PathBuf>,
+}
+
+/// Credentials for the APIs queried by `enrich`.  The `GITHUB_TOKEN` and
+/// `GITLAB_TOKEN` environment variables take precedence over these.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Token for the GitHub API.
+    pub github_token: Option<String>,
+    /// Token for the GitLab API.
+    pub gitlab_token: Option<String>,
+}
+
+impl Config {
+    /// Changes into the corpus root given on the command line, if any, and
+    /// loads the first `corpus.toml` found from there, or the default
+    /// configuration if there is none.
+    ///
+    /// # Arguments
+    ///
+    /// - `root` - The corpus root given on the command line, which overrides
+    ///   the configured root.
+    ///
+    /// # Returns
+    ///
+    /// The [`Config`] on success, with `root` resolved against the file's
+    /// directory, or unset if given on the command line, and [`ConfigError`]
+    /// on failure.
+    pub fn load(root: Option<&Path>) -> Result<Self, ConfigError> {
+        if let Some(root) = root {
+            change_root(root)?;
+        }
+        let candidates = [Some(PathBuf::from(CONFIG_FILE)), user_config_file()];
+        let Some(path) = candidates.into_iter().flatten().find(|path| path.is_file()) else {
+            return Ok(Config::default());
+        };
+        let mut config = Self::read(&path)?;
+        if root.is_some() {
+            config.root = None;
+        }
+        Ok(config)
+    }
+
+    /// Reads and validates a configuration file.
+    ///
+    /// # Returns
+    ///
+    /// The [`Config`] on success, with `root` resolved against the file's
+    /// directory, and [`ConfigError`] on failure.
+    fn read(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|error| ConfigError::IoRead {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let mut config: Config =
+            toml::from_str(&contents).map_err(|error| ConfigError::Deserialize {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        if let Some(max_bandwidth) = &config.download.max_bandwidth {
+            downloader::parse_bandwidth(max_bandwidth).map_err(|message| {
+                ConfigError::InvalidValue {
+                    path: path.to_path_buf(),
+                    key: "download.max_bandwidth",
+                    message,
+                }
+            })?;
+        }
+        config.root = config.root.map(|root| {
+            let root = expand_home(&root);
+            path.parent().unwrap_or(Path::new("")).join(root)
+        });
+        Ok(config)
+    }
+
+    /// Applies the settings that affect the whole process: changes into the
+    /// configured corpus root and sizes the thread pool.
+    ///
+    /// # Arguments
+    ///
+    /// - `jobs` - The number of threads given on the command line, which
+    ///   overrides the configured number.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success and [`ConfigError`] on failure.
+    pub fn apply(&self, jobs: Option<usize>) -> Result<(), ConfigError> {
+        if let Some(root) = &self.root {
+            change_root(root)?;
+        }
+        if let Some(jobs) = jobs.or(self.jobs) {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build_global()
+                .map_err(|error| ConfigError::ThreadPool { error })?;
+        }
+        Ok(())
+    }
+}
+
+/// Changes into a corpus root.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`ConfigError::Root`] on failure.
+fn change_root(root: &Path) -> Result<(), ConfigError> {
+    env::set_current_dir(root).map_err(|error| ConfigError::Root {
+        path: root.to_path_buf(),
+        error,
+    })
+}
+
+impl DownloadConfig {
+    /// Converts the configured defaults into [`DownloadOptions`], to which
+    /// command-line flags are then applied.
+    pub fn options(&self) -> DownloadOptions {
+        DownloadOptions {
+            // Commands run in the corpus root, once `--root` changed into it.
+            root: PathBuf::new(),
+            offline: self.offline,
+            clone_depth: if self.full_history {
+                Some(0)
+            } else {
+                self.clone_depth
+            },
+            proxy: self.proxy.clone(),
+            ca_bundle: self.ca_bundle.clone(),
+            isolate_git_config: self.isolate_git_config,
+            extensions: ExtensionFilter::new(&self.include_extensions, &self.exclude_extensions),
+            include_build_files: self.include_build_files,
+            git_backend: self.git_backend.unwrap_or_default(),
+            timeout: self.timeout.map(Duration::from_secs),
+            // Validated when the configuration is loaded.
+            max_bandwidth: self
+                .max_bandwidth
+                .as_deref()
+                .and_then(|rate| downloader::parse_bandwidth(rate).ok()),
+            hook: self.hook.clone(),
+            link: self.link,
+            layout: self.layout,
+            skip_space_check: self.skip_space_check,
+            force: false,
+            on_conflict: self.on_conflict.unwrap_or_default(),
+            tags: self.tags.clone(),
+            languages: self.languages.clone(),
+            strict: self.strict,
+            transforms: self.transforms.clone(),
+            allow_binary: self.allow_binary,
+            shuffle: self
+                .shuffle
+                .then(|| self.seed.unwrap_or_else(downloader::random_seed)),
+            profile: self.profile.clone(),
+            metadata_repository: self.metadata_repo.clone(),
+            metadata_subdirectory: self.metadata_subdirectory.clone(),
+        }
+    }
+}
+
+impl AuthConfig {
+    /// The token for the GitHub API, from `GITHUB_TOKEN` or the configuration.
+    pub fn github_token(&self) -> Option<String> {
+        env::var("GITHUB_TOKEN")
+            .ok()
+            .or_else(|| self.github_token.clone())
+    }
+
+    /// The token for the GitLab API, from `GITLAB_TOKEN` or the configuration.
+    pub fn gitlab_token(&self) -> Option<String> {
+        env::var("GITLAB_TOKEN")
+            .ok()
+            .or_else(|| self.gitlab_token.clone())
+    }
+}
+
+/// The configuration file in the user's configuration directory, if the
+/// platform has one.
+fn user_config_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join(CONFIG_DIRECTORY).join(CONFIG_FILE))
+}
+
+/// Expands a leading `~` in a path to the user's home directory.
+///
+/// # Arguments
+///
+/// - `path` - The path to expand.
+///
+/// # Returns
+///
+/// The expanded path, or `path` itself if it does not start with `~` or the
+/// home directory is unknown.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the `[download]` table sets the download options, with
+    /// `full_history` taking precedence over `clone_depth`.
+    #[test]
+    fn test_download_config_options() {
+        let config: Config = toml::from_str(
+            r#"
+            jobs = 2
+
+            [download]
+            full_history = true
+            clone_depth = 5
+            git_backend = "cli"
+            link = "symbolic"
+            layout = "by-language"
+            on_conflict = "backup"
+            max_bandwidth = "2M"
+            include_extensions = [".inc"]
+            "#,
+        )
+        .unwrap();
+        let options = config.download.options();
+        assert_eq!(Some(2), config.jobs);
+        assert_eq!(Some(0), options.clone_depth);
+        assert_eq!(GitBackend::Cli, options.git_backend);
+        assert_eq!(Some(LinkMode::Symbolic), options.link);
+        assert_eq!(Some(Layout::ByLanguage), options.layout);
+        assert_eq!(ConflictPolicy::Backup, options.on_conflict);
+        assert_eq!(Some(2 * 1024 * 1024), options.max_bandwidth);
+        assert_eq!(vec!["inc"], options.extensions.include);
+    }
+
+    /// Tests that misspelled keys are rejected rather than ignored.
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[download]\nclone-depth = 5").is_err());
+    }
+}
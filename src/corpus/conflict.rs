@@ -0,0 +1,196 @@
+//! # Download Conflicts
+//!
+//! This module finds the files of a program pair that were changed since the
+//! pair was downloaded, by comparing them with the SHA-256 recorded in its
+//! provenance, so that downloading the pair again does not silently overwrite
+//! them.  What happens to them instead is decided by the download's
+//! [`ConflictPolicy`].
+
+use std::{fs, io, path::Path};
+
+use crate::corpus::{
+    downloader::ConflictPolicy, errors::DownloaderError, progress::DownloadProgress,
+    provenance::Provenance, store, utils,
+};
+
+/// Suffix of the copies [`ConflictPolicy::Backup`] keeps of changed files.
+const BACKUP_SUFFIX: &str = ".orig";
+
+/// A file changed since its program pair was downloaded.
+pub struct LocalChange {
+    /// Path of the file within the program pair's directory, separated by
+    /// `/`, such as `c-program/ls.c`.
+    pub path: String,
+    /// The file's changed content.
+    content: Vec<u8>,
+}
+
+/// Finds the files of a downloaded program pair whose content differs from
+/// the content they were placed with.
+///
+/// Files without a recorded hash, such as those of a pair downloaded before
+/// hashes were recorded, files that were deleted, and symbolic links into
+/// the clone cache, whose content is the clone's, are never changed.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The program pair's directory.
+///
+/// # Returns
+///
+/// The changed files with their content, [`DownloaderError::PathEscape`] if
+/// the provenance names a file outside of the pair's directory, which
+/// [`resolve`] would then remove or write next to, or
+/// [`DownloaderError::IoRead`] if a changed file cannot be read.
+pub fn find_local_changes(pair_directory: &Path) -> Result<Vec<LocalChange>, DownloaderError> {
+    let Some(provenance) = Provenance::load(pair_directory) else {
+        return Ok(Vec::new());
+    };
+    let mut changes = Vec::new();
+    for file in provenance.files {
+        let Some(sha256) = &file.sha256 else {
+            continue;
+        };
+        let path = pair_directory.join(&file.path);
+        let is_file = fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_file());
+        if !is_file {
+            continue;
+        }
+        let path = utils::ensure_within(&path, pair_directory)?;
+        if store::hash_file(&path).is_ok_and(|hash| hash == *sha256) {
+            continue;
+        }
+        let content = fs::read(&path).map_err(|error| DownloaderError::IoRead { path, error })?;
+        changes.push(LocalChange {
+            path: file.path,
+            content,
+        });
+    }
+    Ok(changes)
+}
+
+/// Decides whether a program pair may be downloaded over its changed files.
+///
+/// # Arguments
+///
+/// - `changes` - The pair's changed files.
+/// - `policy` - What happens to changed files.
+/// - `force` - Whether `--force` was given, which lets
+///   [`ConflictPolicy::Overwrite`] overwrite them.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the pair may be downloaded, or
+/// [`DownloaderError::LocalChanges`] listing the changed files if not.
+pub fn check(
+    changes: &[LocalChange],
+    policy: ConflictPolicy,
+    force: bool,
+) -> Result<(), DownloaderError> {
+    let hint = match policy {
+        _ if changes.is_empty() => return Ok(()),
+        ConflictPolicy::Overwrite if !force => {
+            "pass --force to overwrite them, or --on-conflict skip or backup to keep them"
+        }
+        ConflictPolicy::Error => "pass --on-conflict skip or backup to keep them",
+        ConflictPolicy::Overwrite | ConflictPolicy::Skip | ConflictPolicy::Backup => {
+            return Ok(());
+        }
+    };
+    Err(DownloaderError::LocalChanges {
+        paths: changes
+            .iter()
+            .map(|change| change.path.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        hint,
+    })
+}
+
+/// Applies the conflict policy to the changed files of a program pair once
+/// the pair was placed again in its staging directory, before the staged
+/// pair replaces its previous download.
+///
+/// [`ConflictPolicy::Skip`] writes each changed file back in place of the
+/// downloaded one, and [`ConflictPolicy::Backup`] writes it next to the
+/// downloaded one with an `.orig` suffix; the other policies leave the
+/// downloaded files.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The directory the pair was placed in again.
+/// - `changes` - The pair's changed files, found before its files were
+///   placed again.
+/// - `policy` - What happens to changed files.
+/// - `progress` - Reports what happened to each changed file.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::IoWrite`] if a changed
+/// file cannot be written back.
+pub fn resolve(
+    pair_directory: &Path,
+    changes: Vec<LocalChange>,
+    policy: ConflictPolicy,
+    progress: &DownloadProgress,
+) -> Result<(), DownloaderError> {
+    for change in changes {
+        let path = pair_directory.join(&change.path);
+        match policy {
+            ConflictPolicy::Skip => {
+                // The downloaded file may be a link into the clone cache or
+                // the store, which must not be written through.
+                remove_file(&path)?;
+                write_file(&path, &change.content)?;
+                progress.println(format!("Kept changed file '{}'", change.path));
+            }
+            ConflictPolicy::Backup => {
+                let backup = format!("{}{BACKUP_SUFFIX}", change.path);
+                let backup_path = pair_directory.join(&backup);
+                remove_file(&backup_path)?;
+                write_file(&backup_path, &change.content)?;
+                progress.println(format!(
+                    "Overwrote changed file '{}', keeping it as '{backup}'",
+                    change.path
+                ));
+            }
+            ConflictPolicy::Overwrite | ConflictPolicy::Error => {
+                progress.println(format!("Overwrote changed file '{}'", change.path));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes a file if it exists.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::IoWrite`] if the file
+/// exists but cannot be removed.
+fn remove_file(path: &Path) -> Result<(), DownloaderError> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(DownloaderError::IoWrite {
+            path: path.to_path_buf(),
+            error,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Writes a file, creating its directory if the pair's layout changed and
+/// removed it.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::IoWrite`] on failure.
+fn write_file(path: &Path, content: &[u8]) -> Result<(), DownloaderError> {
+    let to_error = |error| DownloaderError::IoWrite {
+        path: path.to_path_buf(),
+        error,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(to_error)?;
+    }
+    fs::write(path, content).map_err(to_error)
+}
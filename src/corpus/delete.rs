@@ -1,23 +1,253 @@
 //! # Delete Operations
 //!
 //! This module provides functionality for cleaning up downloaded program pairs
-//! and repository clones.
+//! and repository clones.  With `--json`, what was deleted is printed as a
+//! JSON object, and the confirmation prompt goes to stderr.
 
-use std::{fs, io::Error, path::Path};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 
-use crate::paths::{PROGRAM_PAIRS_DIRECTORY, REPOSITORY_CLONES_DIRECTORY};
+use serde::Serialize;
 
-/// Removes all downloaded program-pairs and repository clones.
+use crate::{
+    corpus::{
+        cache,
+        errors::{CacheError, DeleteError},
+        manifest, output,
+        schema::Language,
+        utils,
+    },
+    paths::{PROGRAM_PAIRS_DIRECTORY, REPOSITORY_CLONES_DIRECTORY},
+};
+
+/// Which parts of the corpus [`delete`] removes.
 ///
-/// This deletes the directories specified by
-/// [`PROGRAM_PAIRS_DIRECTORY`] and [`REPOSITORY_CLONES_DIRECTORY`],
-/// along with all their contents, if they exist.
-pub fn delete() -> Result<(), Error> {
-    if Path::new(PROGRAM_PAIRS_DIRECTORY).exists() {
-        fs::remove_dir_all(PROGRAM_PAIRS_DIRECTORY)?;
-    };
-    if Path::new(REPOSITORY_CLONES_DIRECTORY).exists() {
-        fs::remove_dir_all(REPOSITORY_CLONES_DIRECTORY)?;
-    };
+/// An empty scope removes every downloaded program pair and repository clone.
+#[derive(Debug, Default)]
+pub struct DeleteScope {
+    /// Remove every downloaded program pair.
+    pub pairs: bool,
+    /// Remove every cached repository clone.
+    pub clones: bool,
+    /// Only remove this downloaded program pair.
+    pub pair: Option<String>,
+    /// Only remove this language's half of the downloaded program pairs.
+    pub language: Option<Language>,
+}
+
+/// What [`delete`] and [`delete_unused`] removed, as printed with `--json`.
+#[derive(Debug, Default, Serialize)]
+struct DeleteResult {
+    /// The removed directories with their disk usage in bytes.
+    deleted: Vec<DeletedDirectory>,
+    /// The unused clones kept because a download was using them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    skipped: Vec<PathBuf>,
+    /// Whether the user declined to delete the selected directories.
+    aborted: bool,
+}
+
+/// A directory removed by [`delete`] or [`delete_unused`].
+#[derive(Debug, Serialize)]
+struct DeletedDirectory {
+    /// Path of the directory.
+    path: PathBuf,
+    /// Disk usage of the directory before it was removed, in bytes.
+    bytes: u64,
+}
+
+impl DeleteScope {
+    /// Whether the scope selects nothing, i.e. everything is removed.
+    fn is_empty(&self) -> bool {
+        !self.pairs && !self.clones && self.pair.is_none() && self.language.is_none()
+    }
+}
+
+/// Removes downloaded program pairs and repository clones.
+///
+/// The directories selected by `scope` are listed along with their disk
+/// usage, and are only removed once the user confirms.
+///
+/// # Arguments
+///
+/// - `scope` - Which directories to remove.
+/// - `assume_yes` - Remove the directories without asking for confirmation.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, including when the user declines, and
+/// [`DeleteError`] on failure.
+pub fn delete(scope: &DeleteScope, assume_yes: bool) -> Result<(), DeleteError> {
+    let json = output::json();
+    let targets = targets(scope)?;
+    let mut result = DeleteResult::default();
+    if targets.is_empty() {
+        if json {
+            output::print_json(&result);
+        } else {
+            println!("Nothing to delete");
+        }
+        return Ok(());
+    }
+
+    let sizes: Vec<u64> = targets
+        .iter()
+        .map(|target| utils::directory_size(target))
+        .collect();
+    if !json {
+        println!("The following will be deleted:");
+        for (target, size) in targets.iter().zip(&sizes) {
+            println!("{:>10}  {}", utils::format_size(*size), target.display());
+        }
+        println!("{:>10}  in total", utils::format_size(sizes.iter().sum()));
+    }
+
+    if !assume_yes && !confirm("Delete these directories?")? {
+        if json {
+            result.aborted = true;
+            output::print_json(&result);
+        } else {
+            println!("Aborted");
+        }
+        return Ok(());
+    }
+
+    for (target, bytes) in targets.into_iter().zip(sizes) {
+        fs::remove_dir_all(&target).map_err(|error| DeleteError::IoDelete {
+            path: target.clone(),
+            error,
+        })?;
+        result.deleted.push(DeletedDirectory {
+            path: target,
+            bytes,
+        });
+    }
+    if json {
+        output::print_json(&result);
+    }
+    Ok(())
+}
+
+/// Removes cached repository clones that no metadata file references.
+///
+/// Clones left behind by metadata edits, or stored under an outdated cache
+/// layout, are pruned; clones referenced by at least one program, and
+/// clones a download is using, are kept.
+pub fn delete_unused() -> Result<(), CacheError> {
+    let reference_counts = cache::reference_counts()?;
+    let mut result = DeleteResult::default();
+    let root = Path::new("");
+    for clone in cache::list_clones(root) {
+        if reference_counts.get(&clone).is_none_or(|&count| count == 0) {
+            let bytes = utils::directory_size(&clone);
+            if !cache::try_remove_clone(root, &clone)? {
+                if !output::json() {
+                    println!(
+                        "Skipping unused clone '{}', which a download is using",
+                        clone.display()
+                    );
+                }
+                result.skipped.push(clone);
+                continue;
+            }
+            if !output::json() {
+                println!("Removed unused clone '{}'", clone.display());
+            }
+            result.deleted.push(DeletedDirectory { path: clone, bytes });
+        }
+    }
+    if output::json() {
+        output::print_json(&result);
+    }
     Ok(())
 }
+
+/// Lists the existing directories that a scope selects.
+///
+/// # Arguments
+///
+/// - `scope` - Which directories to select.
+///
+/// # Returns
+///
+/// The directories to remove, or [`DeleteError::PairNotFound`] if
+/// `scope.pair` has not been downloaded.
+fn targets(scope: &DeleteScope) -> Result<Vec<PathBuf>, DeleteError> {
+    let program_pairs = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    let repository_clones = Path::new(REPOSITORY_CLONES_DIRECTORY);
+    let mut targets = Vec::new();
+
+    if scope.is_empty() || scope.pairs {
+        targets.push(program_pairs.to_path_buf());
+    } else if scope.pair.is_some() || scope.language.is_some() {
+        let pairs = if program_pairs.exists() {
+            utils::list_program_pairs(program_pairs).map_err(|error| DeleteError::IoRead {
+                path: program_pairs.to_path_buf(),
+                error,
+            })?
+        } else {
+            Vec::new()
+        };
+
+        // Match names against the downloaded pairs, rather than joining them
+        // onto `program_pairs/`, so a name cannot point elsewhere.
+        let pairs: Vec<PathBuf> = match &scope.pair {
+            Some(program_name) => {
+                let pair = pairs
+                    .into_iter()
+                    .find(|pair| {
+                        pair.file_name()
+                            .is_some_and(|name| name == program_name.as_str())
+                    })
+                    .ok_or_else(|| DeleteError::PairNotFound {
+                        program_name: program_name.clone(),
+                    })?;
+                vec![pair]
+            }
+            None => pairs,
+        };
+
+        targets.extend(pairs.into_iter().map(|pair| match &scope.language {
+            Some(language) => manifest::program_directory(&pair, language),
+            None => pair,
+        }));
+    }
+
+    if scope.is_empty() || scope.clones {
+        targets.push(repository_clones.to_path_buf());
+    }
+
+    targets.retain(|target| target.exists());
+    Ok(targets)
+}
+
+/// Asks the user a yes-or-no question on the terminal, on stderr with
+/// `--json` so that stdout only holds the result.
+///
+/// # Arguments
+///
+/// - `question` - The question to print.
+///
+/// # Returns
+///
+/// Whether the user answered yes; anything else, including end of input, is
+/// taken as no.
+fn confirm(question: &str) -> Result<bool, DeleteError> {
+    let flushed = if output::json() {
+        eprint!("{question} [y/N] ");
+        io::stderr().flush()
+    } else {
+        print!("{question} [y/N] ");
+        io::stdout().flush()
+    };
+    flushed.map_err(|error| DeleteError::Prompt { error })?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|error| DeleteError::Prompt { error })?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,450 @@
+//! # Cross-Language Diff
+//!
+//! This module compares the C and Rust programs of a downloaded pair at the
+//! function level.  C functions are matched with Rust functions by name, and
+//! functions without a counterpart are listed for each language.  The
+//! functions of a pair can also be listed, or printed as `function_mappings`
+//! to paste into the pair's metadata.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    corpus::{
+        analysis::ast::{self, Function},
+        errors::DiffError,
+        manifest,
+        provenance::Provenance,
+        schema::{FunctionLocation, FunctionMapping, Language, RelativeSourcePath},
+        utils,
+    },
+    paths::PROGRAM_PAIRS_DIRECTORY,
+};
+
+/// A function together with the file it was found in.
+#[derive(Debug, Clone, Serialize)]
+pub struct LocatedFunction {
+    /// Path of the file relative to its program directory.
+    pub file: String,
+    /// The function definition.
+    pub function: Function,
+}
+
+/// The result of comparing the functions of a C program and a Rust program.
+#[derive(Debug, Serialize)]
+pub struct DiffReport {
+    /// Pairs of C and Rust functions with matching names.
+    pub matched: Vec<(LocatedFunction, LocatedFunction)>,
+    /// C functions without a Rust counterpart.
+    pub unmatched_c: Vec<LocatedFunction>,
+    /// Rust functions without a C counterpart.
+    pub unmatched_rust: Vec<LocatedFunction>,
+}
+
+/// Prints a function-level comparison of a downloaded program pair.
+///
+/// # Arguments
+///
+/// - `program_name` - Name of the program pair in `program_pairs/`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`DiffError`] on failure.
+pub fn diff(program_name: &str) -> Result<(), DiffError> {
+    let pair_directory = pair_directory(program_name)?;
+    let c_functions = collect_functions(&pair_directory, &Language::C);
+    let rust_functions = collect_functions(&pair_directory, &Language::Rust);
+    let report = match_functions(c_functions, rust_functions);
+
+    println!("Matched functions ({}):", report.matched.len());
+    for (c_function, rust_function) in &report.matched {
+        println!(
+            "  {:<32} {}:{} <-> {}:{}",
+            c_function.function.name,
+            c_function.file,
+            c_function.function.line,
+            rust_function.file,
+            rust_function.function.line
+        );
+    }
+    print_unmatched("C", &report.unmatched_c);
+    print_unmatched("Rust", &report.unmatched_rust);
+
+    let total_c = report.matched.len() + report.unmatched_c.len();
+    if total_c > 0 {
+        println!(
+            "\n{} of {} C functions ({:.1}%) have a Rust counterpart.",
+            report.matched.len(),
+            total_c,
+            100.0 * report.matched.len() as f64 / total_c as f64
+        );
+    }
+
+    Ok(())
+}
+
+/// Prints the functions of a downloaded program pair, or, with `suggest`,
+/// the C and Rust functions whose names match as `function_mappings` to
+/// paste into the pair's metadata.
+///
+/// Files are named by their path in the repository, as recorded in the
+/// pair's provenance file, so they can be used in metadata.
+///
+/// # Arguments
+///
+/// - `program_name` - Name of the program pair in `program_pairs/`.
+/// - `suggest` - Whether to print mappings rather than every function.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`DiffError`] on failure.
+pub fn functions(program_name: &str, suggest: bool) -> Result<(), DiffError> {
+    let pair_directory = pair_directory(program_name)?;
+    let provenance = Provenance::load(&pair_directory).unwrap_or_default();
+    let c_directory = manifest::program_directory_name(&pair_directory, &Language::C);
+    let rust_directory = manifest::program_directory_name(&pair_directory, &Language::Rust);
+    let repository_path = |located: &LocatedFunction, language: &Language| {
+        let program_directory = if language.is_source() {
+            &c_directory
+        } else {
+            &rust_directory
+        };
+        utils::slash_path(Path::new(&located.file))
+            .and_then(|file| {
+                let path = format!("{program_directory}/{file}");
+                provenance.source_path(&path).map(str::to_string)
+            })
+            .unwrap_or_else(|| located.file.clone())
+    };
+
+    let c_functions = collect_functions(&pair_directory, &Language::C);
+    let rust_functions = collect_functions(&pair_directory, &Language::Rust);
+    if !suggest {
+        let programs = [
+            ("C", Language::C, c_functions),
+            ("Rust", Language::Rust, rust_functions),
+        ];
+        for (label, language, functions) in programs {
+            println!("{} functions ({}):", label, functions.len());
+            for located in &functions {
+                println!(
+                    "  {:<32} {}:{}-{}",
+                    located.function.name,
+                    repository_path(located, &language),
+                    located.function.line,
+                    located.function.end_line
+                );
+            }
+            println!();
+        }
+        return Ok(());
+    }
+
+    let location = |located: &LocatedFunction, language: &Language| {
+        Some(FunctionLocation {
+            file: RelativeSourcePath::new(repository_path(located, language)).ok()?,
+            function: located.function.name.clone(),
+            start_line: Some(located.function.line as u64),
+            end_line: Some(located.function.end_line as u64),
+        })
+    };
+    let total_c = c_functions.len();
+    let report = match_functions(c_functions, rust_functions);
+    let mappings: Vec<FunctionMapping> = report
+        .matched
+        .iter()
+        .filter_map(|(c_function, rust_function)| {
+            Some(FunctionMapping {
+                c: location(c_function, &Language::C)?,
+                rust: location(rust_function, &Language::Rust)?,
+            })
+        })
+        .collect();
+
+    // Details that have no place in the metadata go to stderr, so stdout can
+    // be redirected or piped straight into an editor.
+    eprintln!(
+        "{} of {} C functions matched by name; review each mapping before committing it",
+        mappings.len(),
+        total_c
+    );
+    let json =
+        utils::to_metadata_json(&mappings).map_err(|error| DiffError::Serialize { error })?;
+    println!("\"function_mappings\": {json}");
+    Ok(())
+}
+
+/// Returns the directory of a downloaded program pair.
+///
+/// # Arguments
+///
+/// - `program_name` - Name of the program pair in `program_pairs/`.
+///
+/// # Returns
+///
+/// The pair's directory, or [`DiffError::PairNotFound`] if it has not been
+/// downloaded.
+fn pair_directory(program_name: &str) -> Result<PathBuf, DiffError> {
+    let pair_directory = Path::new(PROGRAM_PAIRS_DIRECTORY).join(program_name);
+    if !pair_directory.is_dir() {
+        return Err(DiffError::PairNotFound {
+            program_name: program_name.to_string(),
+        });
+    }
+    Ok(pair_directory)
+}
+
+/// Extracts the functions of every source file in one half of a pair.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The program pair's directory.
+/// - `language` - Which program of the pair to read.
+///
+/// # Returns
+///
+/// The functions found, ordered by file and then by line.
+pub fn collect_functions(pair_directory: &Path, language: &Language) -> Vec<LocatedFunction> {
+    let program_directory = manifest::program_directory(pair_directory, language);
+    let mut located = Vec::new();
+    for path in utils::list_files(&program_directory) {
+        if Language::from_path(&path).as_ref() != Some(language) {
+            continue;
+        }
+        let file = path
+            .strip_prefix(&program_directory)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        for function in ast::extract_file(&path, language) {
+            located.push(LocatedFunction {
+                file: file.clone(),
+                function,
+            });
+        }
+    }
+    located
+}
+
+/// Matches C functions with Rust functions by name.
+///
+/// Names are first matched exactly, then case-insensitively ignoring
+/// underscores, so that `parse_args` matches `parseArgs`.  Each function is
+/// matched at most once.
+///
+/// # Arguments
+///
+/// - `c_functions` - Functions of the C program.
+/// - `rust_functions` - Functions of the Rust program.
+///
+/// # Returns
+///
+/// A [`DiffReport`] of matched and unmatched functions.
+pub fn match_functions(
+    c_functions: Vec<LocatedFunction>,
+    rust_functions: Vec<LocatedFunction>,
+) -> DiffReport {
+    let normalizers: [fn(&str) -> String; 2] = [
+        |name| name.to_string(),
+        |name| name.replace('_', "").to_lowercase(),
+    ];
+
+    let mut matched = Vec::new();
+    let mut unmatched_c = c_functions;
+    let mut unmatched_rust = rust_functions;
+
+    for normalize in normalizers {
+        let mut rust_by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, function) in unmatched_rust.iter().enumerate().rev() {
+            rust_by_name
+                .entry(normalize(&function.function.name))
+                .or_default()
+                .push(index);
+        }
+
+        let mut taken = vec![false; unmatched_rust.len()];
+        let mut remaining_c = Vec::new();
+        for c_function in unmatched_c {
+            let candidate = rust_by_name
+                .get_mut(&normalize(&c_function.function.name))
+                .and_then(|indices| indices.pop());
+            match candidate {
+                Some(index) => {
+                    taken[index] = true;
+                    matched.push((c_function, unmatched_rust[index].clone()));
+                }
+                None => remaining_c.push(c_function),
+            }
+        }
+
+        unmatched_c = remaining_c;
+        unmatched_rust = unmatched_rust
+            .into_iter()
+            .zip(taken)
+            .filter_map(|(function, taken)| (!taken).then_some(function))
+            .collect();
+    }
+
+    DiffReport {
+        matched,
+        unmatched_c,
+        unmatched_rust,
+    }
+}
+
+/// Prints the functions of one language that have no counterpart.
+fn print_unmatched(language: &str, functions: &[LocatedFunction]) {
+    println!("\nUnmatched {} functions ({}):", language, functions.len());
+    for located in functions {
+        println!(
+            "  {:<32} {}:{}",
+            located.function.name, located.file, located.function.line
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Builds a function found at a line of a file.
+    fn located(file: &str, name: &str, line: usize) -> LocatedFunction {
+        LocatedFunction {
+            file: file.to_string(),
+            function: Function {
+                name: name.to_string(),
+                signature: format!("{name}()"),
+                line,
+                end_line: line + 1,
+            },
+        }
+    }
+
+    /// Returns the names of the C and Rust functions of each match.
+    fn matched_names(report: &DiffReport) -> Vec<(&str, &str)> {
+        report
+            .matched
+            .iter()
+            .map(|(c_function, rust_function)| {
+                (
+                    c_function.function.name.as_str(),
+                    rust_function.function.name.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the names of functions.
+    fn names(functions: &[LocatedFunction]) -> Vec<&str> {
+        functions
+            .iter()
+            .map(|located| located.function.name.as_str())
+            .collect()
+    }
+
+    /// Tests that functions with equal names are matched, and functions
+    /// without a counterpart are listed for their language.
+    #[test]
+    fn test_match_functions() {
+        let report = match_functions(
+            vec![located("main.c", "main", 1), located("main.c", "usage", 9)],
+            vec![
+                located("src/main.rs", "main", 3),
+                located("src/cli.rs", "helper", 1),
+            ],
+        );
+        assert_eq!(vec![("main", "main")], matched_names(&report));
+        assert_eq!(vec!["usage"], names(&report.unmatched_c));
+        assert_eq!(vec!["helper"], names(&report.unmatched_rust));
+    }
+
+    /// Tests that names are matched ignoring case and underscores, but only
+    /// once exact matches are taken.
+    #[test]
+    fn test_match_functions_normalizes_names() {
+        let report = match_functions(
+            vec![
+                located("args.c", "parse_args", 1),
+                located("args.c", "Print_Usage", 20),
+            ],
+            vec![
+                located("src/args.rs", "parseArgs", 1),
+                located("src/args.rs", "parse_args", 10),
+                located("src/args.rs", "print_usage", 30),
+            ],
+        );
+        assert_eq!(
+            vec![("parse_args", "parse_args"), ("Print_Usage", "print_usage")],
+            matched_names(&report)
+        );
+        assert!(report.unmatched_c.is_empty());
+        assert_eq!(vec!["parseArgs"], names(&report.unmatched_rust));
+    }
+
+    /// Tests that each function is matched at most once, in order of
+    /// appearance.
+    #[test]
+    fn test_match_functions_matches_once() {
+        let report = match_functions(
+            vec![located("a.c", "run", 1), located("b.c", "run", 1)],
+            vec![located("src/main.rs", "run", 5)],
+        );
+        assert_eq!(1, report.matched.len());
+        assert_eq!("a.c", report.matched[0].0.file);
+        assert_eq!(vec!["run"], names(&report.unmatched_c));
+        assert_eq!("b.c", report.unmatched_c[0].file);
+        assert!(report.unmatched_rust.is_empty());
+    }
+
+    /// Tests that the functions of one half of a pair are collected from its
+    /// source files only, with paths relative to the program's directory.
+    #[test]
+    fn test_collect_functions() {
+        let pair_directory = tempfile::tempdir().unwrap();
+        let c_directory = pair_directory.path().join("c-program");
+        fs::create_dir_all(c_directory.join("lib")).unwrap();
+        fs::write(
+            c_directory.join("main.c"),
+            "int main(void) {\n    return 0;\n}\n",
+        )
+        .unwrap();
+        fs::write(c_directory.join("lib/util.c"), "void util(void) {}\n").unwrap();
+        fs::write(c_directory.join("README"), "int readme(void) {}\n").unwrap();
+        let rust_directory = pair_directory.path().join("rust-program/src");
+        fs::create_dir_all(&rust_directory).unwrap();
+        fs::write(
+            rust_directory.join("main.rs"),
+            "fn main() {}\n\nfn parse_args() {}\n",
+        )
+        .unwrap();
+
+        let c_functions = collect_functions(pair_directory.path(), &Language::C);
+        let mut c_files: Vec<(String, &str)> = c_functions
+            .iter()
+            .map(|located| {
+                (
+                    located.file.replace('\\', "/"),
+                    located.function.name.as_str(),
+                )
+            })
+            .collect();
+        c_files.sort();
+        assert_eq!(
+            vec![
+                ("lib/util.c".to_string(), "util"),
+                ("main.c".to_string(), "main"),
+            ],
+            c_files
+        );
+        let rust_functions = collect_functions(pair_directory.path(), &Language::Rust);
+        assert_eq!(vec!["main", "parse_args"], names(&rust_functions));
+        assert_eq!(3, rust_functions[1].function.line);
+        assert!(collect_functions(&pair_directory.path().join("missing"), &Language::C).is_empty());
+    }
+}
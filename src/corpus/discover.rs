@@ -0,0 +1,444 @@
+//! # Pair Discovery
+//!
+//! This module searches GitHub for Rust repositories describing themselves as
+//! rewrites or ports of C tools, either the C programs already in the
+//! metadata or a seed list of tool names, and writes the candidates as draft
+//! metadata entries to review.  A candidate's C program is copied from the
+//! metadata when the tool is already in the corpus, and left as example
+//! values to replace otherwise.  Drafts already in the file are kept, and
+//! candidates they already hold are not added again.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::Path,
+};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    corpus::{
+        config::AuthConfig, errors::DiscoverError, github::GitHubClient, parser, schema::Program,
+        utils,
+    },
+    paths::{DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROJECT_METADATA_DIRECTORY},
+};
+
+/// Phrases with which a repository's description introduces the tool it
+/// rewrites.
+const REWRITE_PHRASES: [&str; 6] = [
+    "rewrite of",
+    "rewritten",
+    "port of",
+    "ported",
+    "reimplementation of",
+    "clone of",
+];
+
+/// Comment at the top of the drafts file.
+const DRAFT_COMMENT: &str = "Candidates found by `discover`; check each one, fill in the \
+                             example values, and move the pairs you keep into metadata/.";
+
+/// A page of results of `GET /search/repositories`.
+#[derive(Deserialize)]
+struct SearchResults {
+    items: Vec<GitHubRepository>,
+}
+
+/// A repository found by a GitHub search.
+#[derive(Deserialize)]
+struct GitHubRepository {
+    /// The repository's `owner/name`.
+    full_name: String,
+    html_url: String,
+    description: Option<String>,
+    default_branch: Option<String>,
+    fork: bool,
+    archived: bool,
+}
+
+/// Searches GitHub for Rust rewrites of C tools and writes them as draft
+/// metadata entries.
+///
+/// Repositories already in the metadata or the drafts file, forks, and
+/// archived repositories are left out.
+///
+/// # Arguments
+///
+/// - `seeds` - Names of the C tools to search rewrites of; the C programs of
+///   the pairs in the metadata are searched if it is empty.
+/// - `output` - The file to add the draft entries to.
+/// - `limit` - Maximum number of repositories to consider for each tool.
+/// - `auth` - The API token to authenticate with.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`DiscoverError`] on failure, including
+/// [`DiscoverError::Incomplete`] if the search for any tool failed.
+pub fn discover(
+    seeds: &[String],
+    output: &Path,
+    limit: usize,
+    auth: &AuthConfig,
+) -> Result<(), DiscoverError> {
+    let KnownPairs {
+        c_programs,
+        mut names,
+        mut repositories,
+    } = KnownPairs::load();
+    // The drafts are read first, so a file that is not a drafts file is
+    // refused before any search.
+    let drafts = load_drafts(output)?;
+    for draft in &drafts {
+        if let Some(name) = draft["program_name"].as_str() {
+            names.insert(name.to_string());
+        }
+        if let Some(url) = draft["rust_program"]["repository_url"].as_str() {
+            repositories.insert(utils::normalize_repository_url(url));
+        }
+    }
+    let tools: Vec<String> = if seeds.is_empty() {
+        c_programs.keys().cloned().collect()
+    } else {
+        seeds.to_vec()
+    };
+    let github = GitHubClient::new(auth).map_err(|error| DiscoverError::GitHub { error })?;
+
+    let mut candidates = Vec::new();
+    let mut failed = 0;
+    for tool in &tools {
+        let found = match search(&github, tool, limit) {
+            Ok(found) => found,
+            Err(error) => {
+                eprintln!("Failed to search rewrites of '{tool}': {error}");
+                failed += 1;
+                continue;
+            }
+        };
+        for repository in found {
+            let description = repository.description.as_deref().unwrap_or_default();
+            if repository.fork
+                || repository.archived
+                || !describes_rewrite(description, tool)
+                || !repositories.insert(utils::normalize_repository_url(&repository.html_url))
+            {
+                continue;
+            }
+            let name = candidate_name(&repository.full_name, &names);
+            names.insert(name.clone());
+            println!(
+                "Found '{}' for '{tool}': {description}",
+                repository.html_url
+            );
+            candidates.push(draft_pair(&name, tool, &repository, c_programs.get(tool)));
+        }
+    }
+
+    println!(
+        "Found {} new candidates for {} tools",
+        candidates.len(),
+        tools.len()
+    );
+    if !candidates.is_empty() {
+        let added = candidates.len();
+        write_drafts(output, drafts.into_iter().chain(candidates).collect())?;
+        println!("Added {added} drafts to '{}'", output.display());
+    }
+    if failed > 0 {
+        return Err(DiscoverError::Incomplete {
+            failed,
+            total: tools.len(),
+        });
+    }
+    Ok(())
+}
+
+/// What the metadata already holds.
+struct KnownPairs {
+    /// The C program of each pair, by the name of the C tool.
+    c_programs: BTreeMap<String, Program>,
+    /// Names of the pairs.
+    names: BTreeSet<String>,
+    /// Normalized URLs of the repositories of every program.
+    repositories: BTreeSet<String>,
+}
+
+impl KnownPairs {
+    /// Collects the pairs in the metadata.
+    ///
+    /// Metadata files that fail to parse are reported and skipped.
+    fn load() -> KnownPairs {
+        let mut known = KnownPairs {
+            c_programs: BTreeMap::new(),
+            names: BTreeSet::new(),
+            repositories: BTreeSet::new(),
+        };
+        for directory in [
+            PROJECT_METADATA_DIRECTORY,
+            INDIVIDUAL_METADATA_DIRECTORY,
+            DEMO_METADATA_DIRECTORY,
+        ] {
+            let metadata_files = match parser::parse_directory(Path::new(directory)) {
+                Ok(metadata_files) => metadata_files,
+                Err(error) => {
+                    eprintln!("Failed to read '{directory}': {error}");
+                    continue;
+                }
+            };
+            for (path, metadata) in metadata_files {
+                let metadata = match metadata {
+                    Ok(metadata) => metadata,
+                    Err(error) => {
+                        eprintln!("Failed to parse '{}': {}", path.display(), error);
+                        continue;
+                    }
+                };
+                for pair in metadata.pairs {
+                    for program in pair.programs() {
+                        known.repositories.insert(utils::normalize_repository_url(
+                            program.repository_url.as_str(),
+                        ));
+                    }
+                    known
+                        .c_programs
+                        .entry(tool_name(&pair.program_name, &pair.c_program))
+                        .or_insert(pair.c_program);
+                    known.names.insert(pair.program_name);
+                }
+            }
+        }
+        known
+    }
+}
+
+/// Names the C tool of a pair after its first `.c` source file, such as `ls`
+/// for `src/ls.c`, since pairs are named after their Rust programs.
+///
+/// # Arguments
+///
+/// - `pair_name` - Name of the pair, used if no source file ends in `.c`.
+/// - `c_program` - The pair's C program.
+fn tool_name(pair_name: &str, c_program: &Program) -> String {
+    c_program
+        .source_paths
+        .iter()
+        .find_map(|path| {
+            let file_name = path.as_str().rsplit('/').next()?;
+            file_name.strip_suffix(".c")
+        })
+        .unwrap_or(pair_name)
+        .to_string()
+}
+
+/// Searches GitHub for Rust repositories whose description mentions a tool,
+/// most starred first.
+///
+/// # Arguments
+///
+/// - `github` - The GitHub API client.
+/// - `tool` - Name of the C tool.
+/// - `limit` - Maximum number of repositories to return.
+///
+/// # Returns
+///
+/// The repositories found, or [`DiscoverError::GitHub`] if the search fails.
+fn search(
+    github: &GitHubClient,
+    tool: &str,
+    limit: usize,
+) -> Result<Vec<GitHubRepository>, DiscoverError> {
+    let query = format!("{tool} in:description language:Rust");
+    let per_page = limit.clamp(1, 100).to_string();
+    let results: SearchResults = github
+        .get(
+            "/search/repositories",
+            &[
+                ("q", query.as_str()),
+                ("sort", "stars"),
+                ("per_page", per_page.as_str()),
+            ],
+        )
+        .map_err(|error| DiscoverError::GitHub { error })?;
+    Ok(results.items)
+}
+
+/// Checks whether a repository's description presents it as a rewrite or
+/// port of a tool, such as "A port of grep to Rust" or "ls, rewritten in
+/// Rust".
+///
+/// # Arguments
+///
+/// - `description` - The repository's description.
+/// - `tool` - Name of the C tool.
+///
+/// # Returns
+///
+/// Whether the description contains one of [`REWRITE_PHRASES`] and the
+/// tool's name as a whole word.
+fn describes_rewrite(description: &str, tool: &str) -> bool {
+    let description = description.to_lowercase();
+    let tool = tool.to_lowercase();
+    let mentions_tool = description.match_indices(&tool).any(|(start, _)| {
+        let before = description[..start].chars().next_back();
+        let after = description[start + tool.len()..].chars().next();
+        [before, after]
+            .iter()
+            .all(|character| !character.is_some_and(char::is_alphanumeric))
+    });
+    mentions_tool
+        && REWRITE_PHRASES
+            .iter()
+            .any(|phrase| description.contains(phrase))
+}
+
+/// Names a candidate pair after its repository, prefixed with the
+/// repository's owner if a pair already has the repository's name.
+///
+/// # Arguments
+///
+/// - `full_name` - The repository's `owner/name`.
+/// - `names` - Names of the existing and proposed pairs.
+fn candidate_name(full_name: &str, names: &BTreeSet<String>) -> String {
+    let (owner, name) = full_name.split_once('/').unwrap_or(("", full_name));
+    let mut candidate = name.to_string();
+    if names.contains(&candidate) {
+        candidate = format!("{owner}-{name}");
+    }
+    let mut suffix = 2;
+    let base = candidate.clone();
+    while names.contains(&candidate) {
+        candidate = format!("{base}-{suffix}");
+        suffix += 1;
+    }
+    candidate
+}
+
+/// Builds the draft metadata entry of a candidate pair.
+///
+/// # Arguments
+///
+/// - `name` - Name of the candidate pair.
+/// - `tool` - Name of the C tool it rewrites.
+/// - `repository` - The Rust repository.
+/// - `c_program` - The tool's C program from the metadata, if it is in the
+///   corpus.
+///
+/// # Returns
+///
+/// The entry, as an element of a metadata file's `pairs`.
+fn draft_pair(
+    name: &str,
+    tool: &str,
+    repository: &GitHubRepository,
+    c_program: Option<&Program>,
+) -> Value {
+    let c_program = match c_program {
+        Some(program) => json!({
+            "documentation_url": program.documentation_url.as_str(),
+            "repository_url": program.repository_url.as_str(),
+            "source_paths": program
+                .source_paths
+                .iter()
+                .map(|path| path.as_str())
+                .collect::<Vec<_>>(),
+        }),
+        None => json!({
+            "documentation_url": format!("https://www.gnu.org/software/{tool}/"),
+            "repository_url": format!("https://github.com/OWNER/{tool}"),
+            "source_paths": [format!("src/{tool}.c")],
+        }),
+    };
+    let branch = repository.default_branch.as_deref().unwrap_or("main");
+    json!({
+        "program_name": name,
+        "program_description": repository.description.as_deref().unwrap_or_default(),
+        "translation_tools": ["manual"],
+        "feature_relationship": "rust_equivalent_to_c",
+        "c_program": c_program,
+        "rust_program": {
+            "documentation_url": format!("{}/blob/{branch}/README.md", repository.html_url),
+            "repository_url": repository.html_url,
+            "source_paths": ["src"],
+        },
+    })
+}
+
+/// Loads the draft entries of an earlier `discover` run.
+///
+/// # Returns
+///
+/// The entries, none if the file does not exist, or a [`DiscoverError`] if
+/// it cannot be read or is not a drafts file.
+fn load_drafts(output: &Path) -> Result<Vec<Value>, DiscoverError> {
+    let contents = match fs::read_to_string(output) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => {
+            return Err(DiscoverError::IoRead {
+                path: output.to_path_buf(),
+                error,
+            });
+        }
+    };
+    let not_drafts = |message: String| DiscoverError::NotDrafts {
+        path: output.to_path_buf(),
+        message,
+    };
+    let mut drafts: Value =
+        serde_json::from_str(&contents).map_err(|error| not_drafts(error.to_string()))?;
+    match drafts.get_mut("pairs").map(Value::take) {
+        Some(Value::Array(pairs)) => Ok(pairs),
+        _ => Err(not_drafts("it has no `pairs` list".to_string())),
+    }
+}
+
+/// Writes draft metadata entries to a file, creating its directory.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`DiscoverError`] on failure.
+fn write_drafts(output: &Path, pairs: Vec<Value>) -> Result<(), DiscoverError> {
+    let drafts = json!({ "$comment": DRAFT_COMMENT, "pairs": pairs });
+    let contents =
+        utils::to_metadata_json(&drafts).map_err(|error| DiscoverError::Serialize { error })?;
+    if let Some(parent) = output
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).map_err(|error| DiscoverError::IoWrite {
+            path: parent.to_path_buf(),
+            error,
+        })?;
+    }
+    fs::write(output, contents + "\n").map_err(|error| DiscoverError::IoWrite {
+        path: output.to_path_buf(),
+        error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that only descriptions presenting a rewrite of the tool match.
+    #[test]
+    fn test_describes_rewrite() {
+        assert!(describes_rewrite("A port of GNU grep to Rust", "grep"));
+        assert!(describes_rewrite("ls, rewritten in Rust", "ls"));
+        assert!(describes_rewrite("Rust reimplementation of `cat`", "cat"));
+        assert!(!describes_rewrite("A rewrite of ripgrep's parser", "grep"));
+        assert!(!describes_rewrite("Fast grep, written in Rust", "grep"));
+        assert!(!describes_rewrite("A port of lsof", "ls"));
+    }
+
+    /// Tests that candidate names avoid the names of existing pairs.
+    #[test]
+    fn test_candidate_name() {
+        let mut names = BTreeSet::from(["grep".to_string()]);
+        assert_eq!("sd", candidate_name("chmln/sd", &names));
+        assert_eq!("alice-grep", candidate_name("alice/grep", &names));
+        names.insert("alice-grep".to_string());
+        assert_eq!("alice-grep-2", candidate_name("alice/grep", &names));
+    }
+}
@@ -0,0 +1,420 @@
+//! # Environment Diagnosis
+//!
+//! This module implements `doctor`, which checks the environment the
+//! downloader runs in: that it is run from the root of the repository with
+//! its metadata and schema, that the corpus directories are writable, that
+//! libgit2 supports HTTPS, which proxy is configured, and that a repository
+//! can be reached.  Every failed check comes with a suggested fix, since most
+//! failures of new users are caused by their environment rather than the
+//! metadata.  With `--json`, the findings are printed as JSON.
+
+use std::{env, fs, path::Path, process, time::Duration};
+
+use git2::{Config, Direction, ProxyOptions, Remote, Version};
+use reqwest::Url;
+use serde::Serialize;
+
+use crate::{
+    corpus::{
+        errors::DoctorError,
+        git_cli,
+        git_options::{self, GitOptions},
+        output, parser,
+    },
+    paths::{
+        self, DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, METADATA_SCHEMA_FILE,
+        PROGRAM_PAIRS_DIRECTORY, PROJECT_METADATA_DIRECTORY, REPOSITORY_CLONES_DIRECTORY,
+    },
+};
+
+/// How long the connectivity check waits for a server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Environment variables that configure a proxy, in the order libgit2 and
+/// reqwest consult them.
+const PROXY_VARIABLES: [&str; 4] = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"];
+
+/// The outcome of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    /// The check passed.
+    Ok,
+    /// The check found something that may cause problems for some uses.
+    Warning,
+    /// The check failed, so downloads will likely fail.
+    Failed,
+}
+
+/// The result of checking one part of the environment.
+#[derive(Debug, Serialize)]
+struct Finding {
+    /// Name of the check.
+    check: &'static str,
+    /// The outcome of the check.
+    status: Status,
+    /// What the check found.
+    detail: String,
+    /// How to fix a warning or failure.
+    remedy: Option<String>,
+}
+
+impl Finding {
+    /// Creates a passed check.
+    fn ok(check: &'static str, detail: impl Into<String>) -> Self {
+        Finding {
+            check,
+            status: Status::Ok,
+            detail: detail.into(),
+            remedy: None,
+        }
+    }
+
+    /// Creates a check with a warning or failure and its fix.
+    fn problem(
+        check: &'static str,
+        status: Status,
+        detail: impl Into<String>,
+        remedy: impl Into<String>,
+    ) -> Self {
+        Finding {
+            check,
+            status,
+            detail: detail.into(),
+            remedy: Some(remedy.into()),
+        }
+    }
+}
+
+/// The findings of [`doctor`], as printed with `--json`.
+#[derive(Debug, Serialize)]
+struct DoctorReport<'a> {
+    /// The result of each check, in the order they ran.
+    findings: &'a [Finding],
+}
+
+/// Checks the environment and prints each finding, with a suggested fix for
+/// every warning and failure.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if no check failed, even with warnings, and
+/// [`DoctorError::Failed`] otherwise.
+pub fn doctor() -> Result<(), DoctorError> {
+    let findings = [
+        check_metadata(),
+        check_schema(),
+        check_writable(),
+        check_libgit2(),
+        check_git_binary(),
+        check_proxy(),
+        check_connectivity(),
+    ];
+
+    let failed = findings
+        .iter()
+        .filter(|finding| finding.status == Status::Failed)
+        .count();
+    if output::json() {
+        output::print_json(&DoctorReport {
+            findings: &findings,
+        });
+    } else {
+        print_findings(&findings, failed);
+    }
+    if failed > 0 {
+        return Err(DoctorError::Failed {
+            failed,
+            total: findings.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Prints each finding, with the suggested fix of every warning and
+/// failure.
+///
+/// # Arguments
+///
+/// - `findings` - The findings of every check.
+/// - `failed` - How many of the checks failed.
+fn print_findings(findings: &[Finding], failed: usize) {
+    for finding in findings {
+        let label = match finding.status {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Failed => "failed",
+        };
+        println!("[{label}] {}: {}", finding.check, finding.detail);
+        if let Some(remedy) = &finding.remedy {
+            println!("    fix: {remedy}");
+        }
+    }
+    if failed == 0 {
+        println!("No problems found");
+    }
+}
+
+/// Checks that the metadata directories exist, which they do when the
+/// program is run from the root of the repository.
+fn check_metadata() -> Finding {
+    const CHECK: &str = "Metadata";
+    let directories = [
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+        DEMO_METADATA_DIRECTORY,
+    ]
+    .map(paths::metadata_path);
+    let missing: Vec<String> = directories
+        .iter()
+        .filter(|directory| !directory.is_dir())
+        .map(|directory| directory.display().to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Finding::problem(
+            CHECK,
+            Status::Failed,
+            format!("'{}' not found", missing.join("', '")),
+            "Run the command from the root of the c-rust-program-pairs repository, \
+             or pass --metadata-dir",
+        );
+    }
+
+    let files: usize = directories
+        .iter()
+        .map(|directory| {
+            fs::read_dir(directory).map_or(0, |entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+                    .count()
+            })
+        })
+        .sum();
+    Finding::ok(CHECK, format!("{files} metadata files"))
+}
+
+/// Checks the metadata schema: that the schema given with `--schema` is
+/// valid JSON, or otherwise that `metadata/metadata.schema.json` matches the
+/// schema built into the binary.
+fn check_schema() -> Finding {
+    const CHECK: &str = "Schema";
+    if let Some(path) = parser::schema_file() {
+        let remedy = "Pass a valid schema to `--schema`, or omit it to use the built-in schema";
+        return match fs::read_to_string(path) {
+            Ok(contents) => match serde_json::from_str::<serde_json::Value>(&contents) {
+                Ok(_) => Finding::ok(CHECK, format!("'{}' is valid JSON", path.display())),
+                Err(error) => Finding::problem(
+                    CHECK,
+                    Status::Failed,
+                    format!("'{}' is not valid JSON: {}", path.display(), error),
+                    remedy,
+                ),
+            },
+            Err(error) => Finding::problem(
+                CHECK,
+                Status::Failed,
+                format!("Failed to read '{}': {}", path.display(), error),
+                remedy,
+            ),
+        };
+    }
+    // The file is only read when building, so an edited schema takes effect
+    // once the binary is rebuilt.
+    match fs::read_to_string(paths::metadata_path(METADATA_SCHEMA_FILE)) {
+        Ok(contents) if contents != parser::BUILT_IN_SCHEMA => Finding::problem(
+            CHECK,
+            Status::Warning,
+            format!("'{METADATA_SCHEMA_FILE}' differs from the schema built into the binary"),
+            format!("Rebuild, or pass `--schema {METADATA_SCHEMA_FILE}` to validate against it"),
+        ),
+        _ => Finding::ok(CHECK, "Using the schema built into the binary"),
+    }
+}
+
+/// Checks that files can be created in the corpus root and in the corpus
+/// directories that already exist.
+fn check_writable() -> Finding {
+    const CHECK: &str = "Write permissions";
+    let directories = [".", PROGRAM_PAIRS_DIRECTORY, REPOSITORY_CLONES_DIRECTORY];
+    for directory in directories {
+        let directory = Path::new(directory);
+        if !directory.is_dir() {
+            continue;
+        }
+        let probe = directory.join(format!(".doctor-{}", process::id()));
+        if let Err(error) = fs::write(&probe, b"").and_then(|()| fs::remove_file(&probe)) {
+            return Finding::problem(
+                CHECK,
+                Status::Failed,
+                format!("Cannot create files in '{}': {error}", directory.display()),
+                "Fix the directory's permissions, such as with `chmod u+w`, or run the \
+                 command as its owner",
+            );
+        }
+    }
+    Finding::ok(CHECK, "The corpus directories are writable")
+}
+
+/// Checks that libgit2 was built with HTTPS support, without which no
+/// repository in the metadata can be cloned.
+fn check_libgit2() -> Finding {
+    const CHECK: &str = "libgit2";
+    let version = Version::get();
+    let (major, minor, patch) = version.libgit2_version();
+    let mut features = Vec::new();
+    if version.https() {
+        features.push("HTTPS");
+    }
+    if version.ssh() {
+        features.push("SSH");
+    }
+    let detail = if features.is_empty() {
+        format!("Version {major}.{minor}.{patch} without HTTPS or SSH support")
+    } else {
+        format!(
+            "Version {major}.{minor}.{patch} with {} support",
+            features.join(" and ")
+        )
+    };
+
+    if version.https() {
+        Finding::ok(CHECK, detail)
+    } else {
+        Finding::problem(
+            CHECK,
+            Status::Failed,
+            detail,
+            "Install the SSL development library, such as with \
+             `sudo apt-get install -y pkg-config libssl-dev`, then run `cargo clean` and \
+             rebuild",
+        )
+    }
+}
+
+/// Checks whether the system `git` binary, used by `--git-backend cli`, is
+/// installed.
+fn check_git_binary() -> Finding {
+    const CHECK: &str = "git";
+    if git_cli::is_available() {
+        Finding::ok(CHECK, "Installed, so `--git-backend cli` can be used")
+    } else {
+        Finding::problem(
+            CHECK,
+            Status::Warning,
+            "Not installed; `--git-backend cli` falls back to libgit2",
+            "Install git to use credential helpers and other features libgit2 lacks",
+        )
+    }
+}
+
+/// Checks that the configured proxy, if any, is a valid URL.  Passwords in
+/// proxy URLs are not printed.
+fn check_proxy() -> Finding {
+    const CHECK: &str = "Proxy";
+    let mut proxies: Vec<(String, String)> = PROXY_VARIABLES
+        .into_iter()
+        .filter_map(|variable| {
+            env::var(variable)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(|value| (format!("${variable}"), value))
+        })
+        .collect();
+    if let Ok(proxy) = Config::open_default().and_then(|config| config.get_string("http.proxy"))
+        && !proxy.is_empty()
+    {
+        proxies.push(("git config http.proxy".to_string(), proxy));
+    }
+    if proxies.is_empty() {
+        return Finding::ok(CHECK, "None configured; connecting directly");
+    }
+
+    let mut shown = Vec::new();
+    for (source, proxy) in &proxies {
+        // A proxy without a scheme is taken to use HTTP.
+        let parsed = if proxy.contains("://") {
+            Url::parse(proxy)
+        } else {
+            Url::parse(&format!("http://{proxy}"))
+        };
+        let mut url = match parsed {
+            Ok(url) if url.host_str().is_some() => url,
+            _ => {
+                return Finding::problem(
+                    CHECK,
+                    Status::Failed,
+                    format!("{source} is not a valid proxy URL"),
+                    format!("Set {source} to a URL such as `http://proxy:8080`, or unset it"),
+                );
+            }
+        };
+        if url.password().is_some() {
+            let _ = url.set_password(Some("***"));
+        }
+        shown.push(format!("{source} = {url}"));
+    }
+    Finding::ok(CHECK, shown.join(", "))
+}
+
+/// Checks that a repository from the metadata can be reached through the
+/// configured proxy, by listing its references as `git ls-remote` does.
+fn check_connectivity() -> Finding {
+    const CHECK: &str = "Connectivity";
+    let Some(url) = first_repository_url() else {
+        return Finding::problem(
+            CHECK,
+            Status::Warning,
+            "Skipped because the metadata lists no repository",
+            "Fix the metadata problems reported above or by `cargo run lint`",
+        );
+    };
+
+    // Keep an unreachable server from stalling the check; downloads set
+    // their own timeouts.
+    let _ = git_options::apply(&GitOptions {
+        connect_timeout: Some(CONNECT_TIMEOUT),
+        ..GitOptions::default()
+    });
+    let mut proxy_options = ProxyOptions::new();
+    proxy_options.auto();
+    let result = Remote::create_detached(url.as_str()).and_then(|mut remote| {
+        remote.connect_auth(Direction::Fetch, None, Some(proxy_options))?;
+        let references = remote.list()?.len();
+        Ok(references)
+    });
+    match result {
+        Ok(references) => Finding::ok(CHECK, format!("Listed {references} references of '{url}'")),
+        Err(error) if error.class() == git2::ErrorClass::Ssl => Finding::problem(
+            CHECK,
+            Status::Failed,
+            format!("TLS error reaching '{url}': {}", error.message()),
+            "If your network intercepts TLS, pass its CA certificates to downloads with \
+             `--ca-bundle`",
+        ),
+        Err(error) => Finding::problem(
+            CHECK,
+            Status::Failed,
+            format!("Failed to reach '{url}': {}", error.message()),
+            "Check your network connection and proxy settings; pass a proxy to downloads \
+             with `--proxy`, or download cached repositories with `--offline`",
+        ),
+    }
+}
+
+/// Finds the repository URL of the first program pair in the metadata,
+/// preferring the demo pairs.
+fn first_repository_url() -> Option<String> {
+    [
+        DEMO_METADATA_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+    ]
+    .into_iter()
+    .filter_map(|directory| parser::parse_directory(Path::new(directory)).ok())
+    .flatten()
+    .filter_map(|(_, metadata)| metadata.ok())
+    .flat_map(|metadata| metadata.pairs)
+    .map(|pair| pair.rust_program.repository_url.to_string())
+    .next()
+}
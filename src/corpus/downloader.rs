@@ -6,387 +6,2757 @@
 //! downloads all program pairs from the
 //! repository URLs provided in the metadata.
 
+#[cfg(feature = "async")]
+pub mod async_downloader;
+
 use std::{
+    collections::{BTreeMap, HashSet},
     fs,
-    path::{Path, PathBuf},
+    io::{self, Read},
+    iter,
+    path::{Component, Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use git2::{build::RepoBuilder, opts, ConfigLevel, FetchOptions, RemoteCallbacks, Repository};
-use indicatif::{ProgressBar, ProgressStyle};
+use flate2::read::GzDecoder;
+
+use git2::{
+    FetchOptions, Oid, ProxyOptions, RemoteCallbacks, Repository, SubmoduleUpdateOptions,
+    build::{CheckoutBuilder, RepoBuilder},
+};
+use reqwest::{Certificate, Proxy, Url, blocking::Client};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tar::Archive;
+use walkdir::WalkDir;
 
 use crate::{
     corpus::{
-        self,
-        errors::DownloaderError,
-        schema::{Language, Metadata, ProgramPair},
-        utils,
+        analyze, build_files,
+        cache::{self, CacheLock, SOURCE_CHECKSUM_FILE},
+        conflict::{self, LocalChange},
+        effects::{FileSystem, GitFetcher},
+        errors::{DownloaderError, ParserError},
+        git_cli,
+        git_options::{self, GitOptions},
+        hook, license,
+        lock::{self, CorpusLock},
+        manifest::{MANIFEST_FILE, Manifest, Materialization, ProgramManifest},
+        output, patch, plan,
+        progress::{CloneProgress, DownloadProgress, PairOutcome},
+        provenance::{FileProvenance, PROVENANCE_FILE, Provenance},
+        schema::{
+            ExtensionFilter, Language, Metadata, Program, ProgramPair, RepoUrl, SourceType,
+            variant_directory,
+        },
+        skiplist::SkipList,
+        space, store, timings,
+        transform::{self, Transform},
+        utils::{self, PlacedFile},
     },
     paths::{
-        DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY,
-        PROJECT_METADATA_DIRECTORY, REPOSITORY_CLONES_DIRECTORY,
+        self, DEMO_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY, STAGING_DIRECTORY,
+        STORE_OBJECTS_DIRECTORY,
     },
 };
 
-/// Reads all metadata files in `metadata/` and downloads all program pairs.
+pub use crate::corpus::progress::DownloadEvent;
+
+/// Options controlling how program pairs are downloaded.
 ///
-/// A progress bar tracks the number of metadata files processed.
+/// New options may be added in minor releases, so the options are created
+/// with [`DownloadOptions::default`] and then changed.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct DownloadOptions {
+    /// The corpus root, holding `metadata/`, against which every path of the
+    /// download is resolved; when empty, the current directory.
+    pub root: PathBuf,
+    /// Forbid network access; only repositories already cached in
+    /// `repository_clones/` are used and other pairs are skipped.
+    pub offline: bool,
+    /// Number of commits of history to clone, where 0 clones the full
+    /// history.  Overrides the `clone_depth` of every repository in the
+    /// metadata; when `None`, the metadata's depth or a depth of 1 is used.
+    pub clone_depth: Option<u64>,
+    /// URL of the proxy to clone through, such as `http://proxy:8080`.  When
+    /// `None`, a proxy is detected from the environment.
+    pub proxy: Option<String>,
+    /// A file or directory of CA certificates used to verify HTTPS servers,
+    /// for networks that intercept TLS with their own certificate authority.
+    pub ca_bundle: Option<PathBuf>,
+    /// Ignore the user's global, XDG, and system git configuration while
+    /// downloading, so settings such as URL rewrites cannot break clones.
+    /// libgit2 reads its configuration search paths process-wide, so they
+    /// stay isolated for the rest of the process.
+    pub isolate_git_config: bool,
+    /// Extensions copied from, or kept out of, `source_paths` directories in
+    /// addition to each program's own filter.
+    pub extensions: ExtensionFilter,
+    /// Also copy the build files closest to each program's sources, such as
+    /// its `Cargo.toml` or `Makefile`, in addition to its `build_files`.
+    pub include_build_files: bool,
+    /// What clones and fetches repositories.
+    pub git_backend: GitBackend,
+    /// How long the clone of one repository, including its submodules, may
+    /// take before it is aborted and its pair counted as failed.
+    pub timeout: Option<Duration>,
+    /// Maximum transfer rate of clones in bytes per second.
+    pub max_bandwidth: Option<u64>,
+    /// A shell command run in each program pair's directory after the pair
+    /// is downloaded; the pair fails if the command fails.
+    pub hook: Option<String>,
+    /// Link files from `repository_clones/` into `program_pairs/` instead of
+    /// copying them; files that cannot be linked are copied.
+    pub link: Option<LinkMode>,
+    /// Where the files of every program are placed within its pair's
+    /// directory; when `None`, the metadata's `output_directory` and
+    /// `flatten` fields or their defaults are used.
+    pub layout: Option<Layout>,
+    /// Download even if the repositories to clone are estimated to need more
+    /// disk space than is available.
+    pub skip_space_check: bool,
+    /// Download every program pair again, even those that are up to date,
+    /// and let [`ConflictPolicy::Overwrite`] overwrite files changed since
+    /// their pair was downloaded.
+    pub force: bool,
+    /// What happens to files changed since their pair was downloaded when
+    /// the pair is downloaded again.
+    pub on_conflict: ConflictPolicy,
+    /// Only download the program pairs with at least one of these tags; all
+    /// pairs are downloaded if it is empty.
+    pub tags: Vec<String>,
+    /// Only download the program pairs whose programs are both written in
+    /// these languages; all pairs are downloaded if it is empty.
+    pub languages: Vec<Language>,
+    /// Abort before downloading anything if any metadata file fails to parse,
+    /// instead of skipping the file and reporting it once the rest of the
+    /// pairs are downloaded.
+    pub strict: bool,
+    /// Transforms run over every placed C and Rust source file, in order.
+    pub transforms: Vec<Transform>,
+    /// Copy binary files found in `source_paths` directories instead of
+    /// skipping them.
+    pub allow_binary: bool,
+    /// Download the program pairs in an order shuffled with this seed, such
+    /// as to sample the corpus, instead of sorted by name.
+    pub shuffle: Option<u64>,
+    /// Name of the profile, in `metadata/profiles.json` or built in, whose
+    /// pairs are downloaded; when `None`, the `full` profile is.
+    pub profile: Option<String>,
+    /// URL of a git repository holding the metadata, which is cloned or
+    /// fetched and copied into `metadata/` before the metadata is parsed.
+    pub metadata_repository: Option<String>,
+    /// Directory of the metadata repository whose files are copied into
+    /// `metadata/`; when `None`, its `metadata/` directory.
+    pub metadata_subdirectory: Option<PathBuf>,
+}
+
+/// The metadata parsed from metadata files, along with the paths of the files
+/// that failed to parse and their errors.
+pub type ParsedMetadata = (Vec<Metadata>, Vec<(PathBuf, ParserError)>);
+
+/// Decides whether a [`Downloader`] downloads a program pair.
+type PairFilter = Box<dyn Fn(&ProgramPair) -> bool + Send + Sync>;
+
+/// How files are linked from `repository_clones/` into `program_pairs/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+    /// Hard links, which keep working if the clone is deleted, but share
+    /// edits with the clone's working directory.
+    Hard,
+    /// Symbolic links to the files' absolute paths, which break if the clone
+    /// is deleted.
+    Symbolic,
+    /// Hard links to a copy of each file's content in `store/objects/`, so
+    /// that identical files of different pairs share their disk space.
+    Store,
+}
+
+impl From<LinkMode> for Materialization {
+    fn from(link: LinkMode) -> Self {
+        match link {
+            LinkMode::Hard => Materialization::Hardlink,
+            LinkMode::Symbolic => Materialization::Symlink,
+            LinkMode::Store => Materialization::Store,
+        }
+    }
+}
+
+/// Presets for where the files of a program are placed within its pair's
+/// directory, for tools that expect the same layout for every pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// `c-program/` and `rust-program/`, with the files of `source_paths`
+    /// directories flattened into them.
+    Flat,
+    /// `c-program/` and `rust-program/`, with the files of `source_paths`
+    /// directories keeping their subdirectories.
+    Mirrored,
+    /// `c/` and `rust/`, with the files of `source_paths` directories
+    /// flattened into them.
+    ByLanguage,
+}
+
+/// What happens to the files of a program pair that were changed since it
+/// was downloaded, found by their SHA-256 in its provenance, when the pair is
+/// downloaded again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Overwrite them with the downloaded files, but only with `--force`;
+    /// without it, the pair fails before anything is overwritten.
+    #[default]
+    Overwrite,
+    /// Keep them in place of the downloaded files.
+    Skip,
+    /// Fail the pair before anything is overwritten.
+    Error,
+    /// Overwrite them, keeping their changed content next to them with an
+    /// `.orig` suffix.
+    Backup,
+}
+
+/// What clones and fetches repositories.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackend {
+    /// The libgit2 library built into this program.
+    #[default]
+    Libgit2,
+    /// The system `git` binary, which supports credential helpers and other
+    /// features libgit2 lacks; libgit2 is used if git is not installed.
+    Cli,
+}
+
+/// Parses a transfer rate such as `2M` or `500KiB/s` into bytes per second.
 ///
 /// # Arguments
 ///
-/// - `demo` - True if a demo is being run, in which case the function downloads
-///            only the program pairs specified `metadata/demo/`.
+/// - `rate` - The rate to parse, with binary units as in `cache prune`.
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` on success, or a [`DownloaderError`] if any step fails.
-pub fn download_program_pairs(demo: bool) -> Result<(), DownloaderError> {
-    // Temporarily override the user's global and system Git configuration.
-    // This is to ensure reliability when using the clone operation to
-    // download repositories.
-    unsafe {
-        opts::set_search_path(ConfigLevel::Global, "/dev/null").unwrap();
-        opts::set_search_path(ConfigLevel::System, "/dev/null").unwrap();
+/// The rate in bytes per second, or a message explaining why `rate` is
+/// malformed.
+pub fn parse_bandwidth(rate: &str) -> Result<u64, String> {
+    let size = rate.trim();
+    utils::parse_size(size.strip_suffix("/s").unwrap_or(size))
+        .ok_or_else(|| format!("expected a rate such as 2M or 500KiB, not '{rate}'"))
+}
+
+/// Enforces the timeout and bandwidth limit of a repository's clone.
+///
+/// libgit2 calls [`TransferLimits::check`] from its transfer progress
+/// callback, which aborts the transfer when it returns `false`.
+struct TransferLimits {
+    /// When the clone started.
+    started: Instant,
+    /// How long the clone may take.
+    timeout: Option<Duration>,
+    /// Maximum transfer rate in bytes per second.
+    max_bandwidth: Option<u64>,
+}
+
+impl TransferLimits {
+    /// Starts timing a clone with the limits of the download options.
+    fn new(options: &DownloadOptions) -> Self {
+        TransferLimits {
+            started: Instant::now(),
+            timeout: options.timeout,
+            max_bandwidth: options.max_bandwidth,
+        }
     }
 
-    let directories = if demo {
-        vec![PathBuf::from(DEMO_METADATA_DIRECTORY)]
-    } else {
-        vec![
-            PathBuf::from(PROJECT_METADATA_DIRECTORY),
-            PathBuf::from(INDIVIDUAL_METADATA_DIRECTORY),
-        ]
-    };
+    /// The instant the clone times out at, if it has a timeout.
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| self.started + timeout)
+    }
+
+    /// Checks a transfer against the limits, sleeping as long as needed to
+    /// keep it under the bandwidth limit.
+    ///
+    /// Transfers restart the byte count, so the rate is measured from the
+    /// start of the clone rather than of each transfer, which may briefly
+    /// exceed the limit after an earlier, slower transfer.
+    ///
+    /// # Arguments
+    ///
+    /// - `received_bytes` - Bytes received so far by the current transfer.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the clone has timed out and the transfer must be aborted.
+    fn check(&self, received_bytes: usize) -> bool {
+        if let Some(max_bandwidth) = self.max_bandwidth.filter(|&rate| rate > 0) {
+            let expected = Duration::from_secs_f64(received_bytes as f64 / max_bandwidth as f64);
+            let mut pause = expected.saturating_sub(self.started.elapsed());
+            if let Some(deadline) = self.deadline() {
+                pause = pause.min(deadline.saturating_duration_since(Instant::now()));
+            }
+            thread::sleep(pause);
+        }
 
-    // Count total metadata files in the directories being processed.
-    let mut total_files = 0;
-    for directory in &directories {
-        total_files += utils::count_files(&directory)?;
+        !self.timed_out()
     }
 
-    // Create a progress bar to track the number of metadata files that have
-    // been processed.
-    let progress_bar = ProgressBar::new(total_files as u64);
-    progress_bar.set_style(
-        ProgressStyle::default_bar()
-            .template("{bar:40.white/white} {pos}/{len} {msg}")
-            .map_err(|error| DownloaderError::ProgressBar(error.to_string()))?
-            .progress_chars("##-"),
-    );
-    progress_bar.set_message(format!("Processing metadata files..."));
+    /// Whether the clone has run past its timeout.
+    fn timed_out(&self) -> bool {
+        self.deadline()
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
 
-    for directory in &directories {
-        download_from_metadata_directory(&directory, &progress_bar)?;
+    /// Replaces the error of a clone that failed after its deadline, such as
+    /// one aborted by [`TransferLimits::check`], with
+    /// [`DownloaderError::TimedOut`].
+    fn or_timed_out(&self, repository_url: &str, error: DownloaderError) -> DownloaderError {
+        match self.timeout {
+            Some(timeout) if self.timed_out() => DownloaderError::TimedOut {
+                repository_url: repository_url.to_string(),
+                timeout,
+            },
+            _ => error,
+        }
     }
+}
 
-    progress_bar.finish_with_message("Downloaded all program pairs!");
-    Ok(())
+/// A cloned repository, or a fetched tarball or file, that a program's files
+/// are copied from.
+struct Checkout<'a> {
+    /// The URL that was cloned: the repository URL or one of its mirrors.
+    url: &'a RepoUrl,
+    /// The clone's working directory.
+    directory: PathBuf,
+    /// SHA of the checked-out commit, or the SHA-256 of a tarball or file.
+    commit: String,
+    /// Keeps other downloads from changing the clone until the program's
+    /// files are copied from it.
+    _lock: CacheLock,
 }
 
-/// Download program pairs in the given metadata files.
+/// Depth of clones whose depth is set neither by the metadata nor by
+/// [`DownloadOptions::clone_depth`]: only the latest commit, to save time and
+/// space.
+const DEFAULT_CLONE_DEPTH: u64 = 1;
+
+/// The first bytes of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Reads all metadata files in `metadata/` and downloads all program pairs.
 ///
-/// The program iterates through each metadata JSON file, then parses and
-/// downloads the program pairs.
+/// Every metadata file is parsed first, so that an overall progress bar can
+/// track the number of program pairs processed.
 ///
 /// # Arguments
 ///
-/// - `directory` - The directory containing the metadata JSON files,
-///   typically `metadata/individual/` or `metadata/projects/`.
-/// - `progress_bar` - Update each time a metadata file is processed.
+/// - `demo` - True if a demo is being run, in which case the function downloads
+///   only the program pairs specified `metadata/demo/`.
+/// - `options` - Options controlling the download.
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` on success, or a [`DownloaderError`] if directory reading
-/// fails.
-pub fn download_from_metadata_directory(
-    directory: &Path,
-    progress_bar: &ProgressBar,
+/// Returns `Ok(())` on success, or a [`DownloaderError`] if any step fails.
+pub fn download_program_pairs(
+    demo: bool,
+    options: &DownloadOptions,
 ) -> Result<(), DownloaderError> {
-    let metadata_files = directory
-        .read_dir()
-        .map_err(|error| DownloaderError::IoRead {
-            path: directory.to_path_buf(),
-            error,
-        })?;
+    download_metadata_pairs(demo, options, None, None)
+}
 
-    for metadata_file in metadata_files {
-        let metadata_file = metadata_file.map_err(|error| DownloaderError::IoRead {
-            path: directory.to_path_buf(),
-            error,
-        })?;
+/// Reads all metadata files in `metadata/` and downloads all program pairs,
+/// sending the download's progress as [`DownloadEvent`]s instead of drawing
+/// progress bars, for frontends that embed this crate and render the
+/// progress themselves.
+///
+/// The download runs on the calling thread, so the events are typically
+/// received on another thread while it runs.
+///
+/// # Arguments
+///
+/// - `demo` - True to download only the program pairs in `metadata/demo/`.
+/// - `options` - Options controlling the download.
+/// - `events` - Receives the progress of the download.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`DownloaderError`] if any step fails.
+pub fn download_program_pairs_with_events(
+    demo: bool,
+    options: &DownloadOptions,
+    events: Sender<DownloadEvent>,
+) -> Result<(), DownloaderError> {
+    download_metadata_pairs(demo, options, Some(events), None)
+}
 
-        // Parse the contents of `metadata_file`.
-        match corpus::parse(&metadata_file.path()) {
-            // Download the program pairs listed in the metadata file.
-            Ok(metadata) => download_from_metadata_file(&metadata, progress_bar),
-
-            // If there is an error parsing the current file,
-            // display an error and move on to the next file.
-            Err(error) => eprintln!(
-                "Failed to parse '{}': {}",
-                metadata_file.path().display(),
-                error
-            ),
-        }
+/// Downloads program pairs into a corpus, for research tools that depend on
+/// this crate instead of running the binary.
+///
+/// A downloader is configured step by step and then run with
+/// [`Downloader::download`], which downloads the pairs of the `full` profile
+/// unless another is chosen:
+///
+/// ```no_run
+/// use c_rust_program_pairs::Downloader;
+///
+/// Downloader::new()
+///     .root("corpus")
+///     .jobs(4)
+///     .filter(|pair| pair.tags.iter().any(|tag| tag == "coreutils"))
+///     .download()?;
+/// # Ok::<(), c_rust_program_pairs::DownloaderError>(())
+/// ```
+#[derive(Default)]
+pub struct Downloader {
+    /// The corpus root, holding `metadata/`, that the download runs in.
+    root: Option<PathBuf>,
+    /// Number of threads used to parse metadata and copy files.
+    jobs: Option<usize>,
+    /// Decides which of the profile's program pairs are downloaded.
+    filter: Option<PairFilter>,
+    /// Options controlling the download.
+    options: DownloadOptions,
+    /// Receives the progress of the download instead of progress bars.
+    events: Option<Sender<DownloadEvent>>,
+}
+
+impl Downloader {
+    /// Creates a downloader with the default options, which runs in the
+    /// current directory and downloads every pair of the `full` profile.
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    Ok(())
+    /// Sets the corpus root, holding `metadata/`, that the download reads
+    /// and writes in, instead of the current directory.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Sets the number of threads used to parse metadata and copy files;
+    /// by default, one per CPU.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// Downloads only the program pairs for which `filter` returns `true`,
+    /// among those the profile and tags select.
+    pub fn filter(mut self, filter: impl Fn(&ProgramPair) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Sets the options controlling the download, replacing any profile set
+    /// before.
+    pub fn options(mut self, options: DownloadOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Downloads the pairs of a profile, in `metadata/profiles.json` or
+    /// built in, instead of the `full` profile.
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.options.profile = Some(profile.into());
+        self
+    }
+
+    /// Sends the progress of the download as [`DownloadEvent`]s instead of
+    /// drawing progress bars.
+    pub fn events(mut self, events: Sender<DownloadEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Reads the metadata files of the corpus and downloads the selected
+    /// program pairs.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or a [`DownloaderError`] if any step
+    /// fails, including [`DownloaderError::Incomplete`] if some pairs fail
+    /// to download.
+    pub fn download(&self) -> Result<(), DownloaderError> {
+        let mut options = self.options.clone();
+        if let Some(root) = &self.root {
+            fs::read_dir(root).map_err(|error| DownloaderError::Root {
+                path: root.clone(),
+                error,
+            })?;
+            options.root.clone_from(root);
+        }
+        let download =
+            || download_metadata_pairs(false, &options, self.events.clone(), self.filter.as_ref());
+        match self.jobs {
+            Some(jobs) => rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()
+                .map_err(|error| DownloaderError::ThreadPool { error })?
+                .install(download),
+            None => download(),
+        }
+    }
 }
 
-/// Downloads all program pairs in a given Metadata object.
+/// Plans a download from the metadata on disk, fetching the metadata
+/// repository with git, and reports the demo metadata written and the
+/// metadata files that failed to parse.
 ///
-/// The program continues, rather than halts, if it fails to download
-/// a program pair.
+/// # Arguments
 ///
-/// Increments the progress bar each time a metadata file is finished
-/// processing.
+/// - `demo` - True to plan only the program pairs in `metadata/demo/`.
+/// - `options` - Options controlling the download.
+/// - `storage` - The files of the corpus root.
 ///
-/// # Arguments
+/// # Returns
 ///
-/// - `metadata` - The program pairs to download.
-/// - `progress_bar` - Is updated each time a metadata file is processed.
-fn download_from_metadata_file(metadata: &Metadata, progress_bar: &ProgressBar) {
-    for pair in metadata.pairs.iter() {
-        if let Err(error) = download_program_pair(pair) {
-            eprintln!("Failed to download '{}': {}", pair.program_name, error)
-        };
+/// The [`ParsedMetadata`] of the pairs to download, or a [`DownloaderError`]
+/// if planning fails.
+pub(crate) fn plan_download(
+    demo: bool,
+    options: &DownloadOptions,
+    storage: &FileSystem,
+) -> Result<ParsedMetadata, DownloaderError> {
+    let plan = plan::parse_pairs(demo, options, storage, &GitFetcher)?;
+    if plan.wrote_demo {
+        eprintln!(
+            "Wrote the built-in demo metadata to '{}'",
+            paths::metadata_path(DEMO_METADATA_DIRECTORY).display()
+        );
     }
-    progress_bar.inc(1);
+    for (path, error) in &plan.invalid_files {
+        eprintln!("Failed to parse '{}': {}", path.display(), error);
+    }
+    Ok((plan.metadata, plan.invalid_files))
 }
 
-/// Downloads a C-Rust program pair.
+/// Reads all metadata files in `metadata/` and downloads all program pairs,
+/// drawing progress bars or sending [`DownloadEvent`]s.
 ///
-/// Checks if the C and Rust repositories exist, and clone them if they don't.
-/// Copy the C source files to program_pairs/<program_name>/c-program.
-/// Copy the Rust source files to program_pairs/<program_name>/rust-program.
+/// # Arguments
 ///
-/// # Side Effects
+/// - `demo` - True to download only the program pairs in `metadata/demo/`.
+/// - `options` - Options controlling the download.
+/// - `events` - Receives the progress of the download, or `None` to draw
+///   progress bars.
+/// - `filter` - Decides which of the parsed program pairs are downloaded, or
+///   `None` to download all of them.
 ///
-/// - Creates destination directories for program pairs at
-///   `program_pairs/<program-name>/`.
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`DownloaderError`] if any step fails.
+fn download_metadata_pairs(
+    demo: bool,
+    options: &DownloadOptions,
+    events: Option<Sender<DownloadEvent>>,
+    filter: Option<&PairFilter>,
+) -> Result<(), DownloaderError> {
+    configure_git(options)?;
+
+    let storage = FileSystem::new(&options.root);
+    let (mut metadata, invalid_files) = plan_download(demo, options, &storage)?;
+    if let Some(filter) = filter {
+        for metadata in &mut metadata {
+            metadata.pairs.retain(|pair| filter(pair));
+        }
+    }
+    let skiplist =
+        SkipList::load_from(&storage).map_err(|error| DownloaderError::SkipList { error })?;
+    space::check(
+        metadata.iter().flat_map(|metadata| &metadata.pairs),
+        &skiplist,
+        options,
+    )?;
+    let lock = CorpusLock::load(&options.root).map_err(|error| DownloaderError::Lock { error })?;
+    let total_pairs: usize = metadata.iter().map(|metadata| metadata.pairs.len()).sum();
+    let progress = match events {
+        Some(events) => DownloadProgress::with_events(total_pairs as u64, events)?,
+        None => DownloadProgress::new(total_pairs as u64)?,
+    };
+    let pairs: Vec<ProgramPair> = metadata
+        .into_iter()
+        .flat_map(|metadata| metadata.pairs)
+        .collect();
+    let failed_pairs = download_all(&pairs, &skiplist, &progress, options, &lock);
+    finish(
+        &options.root,
+        &progress,
+        &lock,
+        failed_pairs,
+        invalid_files,
+        total_pairs,
+    )
+}
+
+/// The outcome of a download, as printed with `--json`.
+#[derive(Debug, Serialize)]
+struct DownloadResult<'a> {
+    /// The number of program pairs processed.
+    total_pairs: usize,
+    /// How each processed pair ended, sorted by name.
+    pairs: Vec<PairOutcome>,
+    /// The metadata files that failed to parse.
+    invalid_files: Vec<InvalidFile<'a>>,
+}
+
+/// A metadata file that failed to parse, as printed with `--json`.
+#[derive(Debug, Serialize)]
+struct InvalidFile<'a> {
+    /// Path of the file.
+    path: &'a Path,
+    /// Why the file failed to parse.
+    message: String,
+}
+
+/// Saves the lock file and the run metrics, and reports the outcome of a
+/// download, also as JSON on stdout with `--json`.
 ///
 /// # Arguments
 ///
-/// - `pair` - A program pair.
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `progress` - Displays the download's progress.
+/// - `lock` - The commits repositories are locked to.
+/// - `failed_pairs` - The names of the program pairs that failed to
+///   download.
+/// - `invalid_files` - The metadata files that failed to parse and their
+///   errors.
+/// - `total_pairs` - The number of program pairs processed.
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` on success, or a [`DownloaderError`] on failure.
-fn download_program_pair(pair: &ProgramPair) -> Result<(), DownloaderError> {
-    let program_name = &pair.program_name;
-    let base_program_path = Path::new(PROGRAM_PAIRS_DIRECTORY).join(program_name);
-    let c_program_path = base_program_path.join("c-program");
-    let rust_program_path = base_program_path.join("rust-program");
-
-    // Create the destination directories for the C and Rust source files.
-    fs::create_dir_all(&c_program_path).map_err(|source| DownloaderError::IoCreate {
-        path: c_program_path.clone(),
-        error: source,
-    })?;
-    fs::create_dir_all(&rust_program_path).map_err(|source| DownloaderError::IoCreate {
-        path: rust_program_path.clone(),
-        error: source,
-    })?;
+/// Returns `Ok(())` if every pair was downloaded, or a [`DownloaderError`]
+/// if the lock file cannot be saved or some pairs or metadata files failed.
+fn finish(
+    root: &Path,
+    progress: &DownloadProgress,
+    lock: &CorpusLock,
+    mut failed_pairs: Vec<String>,
+    invalid_files: Vec<(PathBuf, ParserError)>,
+    total_pairs: usize,
+) -> Result<(), DownloaderError> {
+    lock.save(root)
+        .map_err(|error| DownloaderError::Lock { error })?;
+    // Pairs may have been downloaded in a shuffled order.
+    failed_pairs.sort();
+    // Metrics only track the tool's own performance, so failing to record
+    // them should not fail the download.
+    if let Err(error) = progress.metrics().save(root, &progress.outcomes()) {
+        progress.println(format!("Failed to record run metrics: {error}"));
+    }
 
-    download_files(
-        program_name,
-        Language::C,
-        &c_program_path,
-        &pair.c_program.repository_url,
-        &pair.c_program.source_paths,
-    )?;
-    download_files(
-        program_name,
-        Language::Rust,
-        &rust_program_path,
-        &pair.rust_program.repository_url,
-        &pair.rust_program.source_paths,
-    )?;
+    if output::json() {
+        output::print_json(&DownloadResult {
+            total_pairs,
+            pairs: progress.outcomes(),
+            invalid_files: invalid_files
+                .iter()
+                .map(|(path, error)| InvalidFile {
+                    path,
+                    message: error.to_string(),
+                })
+                .collect(),
+        });
+    }
 
-    Ok(())
+    if failed_pairs.is_empty() && invalid_files.is_empty() {
+        progress.finish("Downloaded all program pairs!");
+        Ok(())
+    } else {
+        progress.finish("Finished downloading with failures");
+        Err(DownloaderError::Incomplete {
+            failed_pairs,
+            invalid_files,
+            total_pairs,
+        })
+    }
 }
 
-/// Downloads the specified source files from a Git repository.
+/// Downloads the given program pairs, such as those whose metadata changed,
+/// with their own progress bar.
 ///
-/// This function clones the repository (if not already cached) into
-/// `repository_clones/<language>/<repository_name>`, then copies the listed
-/// `source_files` into the given `program_directory`.
+/// # Arguments
 ///
-/// A progress bar is displayed on standard output to track cloning progress.
+/// - `pairs` - The program pairs to download.
+/// - `options` - Options controlling the download.
 ///
-/// # Side Effects
+/// # Returns
 ///
-/// - Creates `repository_clones/`, which is a local cache for git clones.
-/// - May overwrite files at `program_directory`.
+/// Returns `Ok(())` on success, or a [`DownloaderError`] if any step fails,
+/// including [`DownloaderError::Incomplete`] if some pairs fail to download.
+pub fn download_pairs(
+    pairs: &[ProgramPair],
+    options: &DownloadOptions,
+) -> Result<(), DownloaderError> {
+    let progress = DownloadProgress::new(pairs.len() as u64)?;
+    download_pairs_with_progress(pairs, options, &progress)
+}
+
+/// Downloads the given program pairs, reporting progress to the given
+/// display, such as one that sends [`DownloadEvent`]s to the TUI.
 ///
 /// # Arguments
 ///
-/// - `program_name` - Name of the program being downloaded (used for progress messages).
-/// - `program_language` - Language of the program (affects repository clone path).
-/// - `program_directory` - Destination directory for the downloaded source files.
-/// - `repository_url` - Git URL of the repository to clone.
-/// - `source_files` - Paths (relative to repo root) of files or directories to copy.
+/// - `pairs` - The program pairs to download.
+/// - `options` - Options controlling the download.
+/// - `progress` - Displays the download's progress; it should count
+///   `pairs.len()` pairs.
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` if all files were successfully downloaded and copied, or
-/// [`DownloadError`] on failure.
-fn download_files(
-    program_name: &str,
-    program_language: Language,
-    program_directory: &Path,
-    repository_url: &str,
-    source_files: &[String],
+/// Returns `Ok(())` on success, or a [`DownloaderError`] if any step fails,
+/// including [`DownloaderError::Incomplete`] if some pairs fail to download.
+pub fn download_pairs_with_progress(
+    pairs: &[ProgramPair],
+    options: &DownloadOptions,
+    progress: &DownloadProgress,
 ) -> Result<(), DownloaderError> {
-    let progress_bar = ProgressBar::new(80);
-
-    let repository_directory = download_with_git(&program_language, repository_url, &progress_bar)?;
-
-    progress_bar.set_style(ProgressStyle::default_spinner());
-    progress_bar.set_message("Copying files...");
+    configure_git(options)?;
 
-    // Copy given files from the repository to the given directory.
-    for file_path in source_files {
-        let file_name = Path::new(file_path).file_name().ok_or_else(|| {
-            DownloaderError::Io(format!("Failed to get file name for path '{file_path}'"))
-        })?;
+    let skiplist = SkipList::load_from(&FileSystem::new(&options.root))
+        .map_err(|error| DownloaderError::SkipList { error })?;
+    space::check(pairs, &skiplist, options)?;
+    let lock = CorpusLock::load(&options.root).map_err(|error| DownloaderError::Lock { error })?;
+    let failed_pairs = download_all(pairs, &skiplist, progress, options, &lock);
+    finish(
+        &options.root,
+        progress,
+        &lock,
+        failed_pairs,
+        Vec::new(),
+        pairs.len(),
+    )
+}
 
-        let source = repository_directory.join(file_path);
-        let destination = program_directory.join(file_name);
+/// Decides the process-wide libgit2 options of a download.
+///
+/// # Arguments
+///
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// The [`GitOptions`] with the download's timeout, CA bundle, and git
+/// configuration isolation.
+pub(crate) fn git_options(options: &DownloadOptions) -> GitOptions {
+    // The timeout also bounds how long libgit2 waits on a stalled server,
+    // since a transfer that receives nothing never reaches the progress
+    // callback that enforces it.
+    GitOptions {
+        connect_timeout: options.timeout,
+        server_timeout: options.timeout,
+        ca_bundle: options.ca_bundle.clone(),
+        isolate_config: options.isolate_git_config,
+    }
+}
 
-        // Copy files from destination to source.
-        if source.is_dir() {
-            utils::copy_files_from_directory(&source, &program_directory)?;
-        } else {
-            fs::copy(&source, &destination).map_err(|error| DownloaderError::IoCopy {
-                source: source.to_path_buf(),
-                destination: destination.to_path_buf(),
-                error,
-            })?;
+/// Applies the git settings of the download options to libgit2.
+///
+/// # Arguments
+///
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::GitConfig`] if the
+/// settings cannot be applied.
+fn configure_git(options: &DownloadOptions) -> Result<(), DownloaderError> {
+    git_options::apply(&git_options(options))
+        .map_err(|error| DownloaderError::GitConfig { error })?;
+    if options.git_backend == GitBackend::Cli {
+        if !git_cli::is_available() {
+            eprintln!("git is not installed; cloning with libgit2 instead");
+        } else if options.max_bandwidth.is_some() {
+            eprintln!("The git backend cannot limit bandwidth; ignoring --max-bandwidth");
         }
     }
-
-    progress_bar.finish_with_message(format!(
-        "Downloaded '{}' ({})",
-        program_name,
-        program_language.to_str()
-    ));
     Ok(())
 }
 
-/// Downloads a git repository using git clone.
+/// Downloads a list of program pairs, sorted by name unless
+/// [`DownloadOptions::shuffle`] is set, so that progress and reports are the
+/// same on every machine.
+///
+/// The program continues, rather than halts, if it fails to download
+/// a program pair.  Pairs in the skip list, and pairs skipped because their
+/// repositories are not cached in offline mode, are reported as skipped
+/// rather than failed.
 ///
 /// # Arguments
 ///
-/// - `program_language` - Either C or Rust.
-/// - `repository_url` - The URL to download with git.
-/// - `progress_bar` - A `ProgressBar` used to show the progress of the
-///                    download status of the current program-pair.
+/// - `pairs` - The program pairs to download.
+/// - `skiplist` - The program pairs known to be broken.
+/// - `progress` - Is updated each time a program pair is processed.
+/// - `options` - Options controlling the download.
+/// - `lock` - The commits repositories are locked to; repositories that are
+///   not locked yet are locked to the commits they resolve to.
 ///
 /// # Returns
 ///
-/// A `PathBuf` to the downloaded repository on success, or a
-/// [`DownloaderError`] on failure.
-fn download_with_git(
-    program_language: &Language,
-    repository_url: &str,
-    progress_bar: &ProgressBar,
-) -> Result<PathBuf, DownloaderError> {
-    let repository_clones_path =
-        Path::new(REPOSITORY_CLONES_DIRECTORY).join(program_language.to_str());
-    let repository_name = utils::get_repository_name(repository_url)?;
-
-    progress_bar.set_style(
-        ProgressStyle::default_bar()
-            .template("{bar:40.white/white} {pos}/{len} {msg}")
-            .map_err(|error| DownloaderError::ProgressBar(error.to_string()))?
-            .progress_chars("##-"),
-    );
-    progress_bar.set_message(format!("Cloning repository {repository_name}..."));
-
-    // Set up remote callbacks for progress tracking.
-    let mut remote_callbacks = RemoteCallbacks::new();
-    remote_callbacks.transfer_progress(|progress: git2::Progress| {
-        update_progress_bar_callback(progress, &repository_name, &progress_bar)
-    });
-
-    // Check if repository exists in `repository_clones/`, if not clone it.
-    // We store repositories in repository_clones/<language>/<repository_name>.
-    let repository = match Repository::open(repository_clones_path.join(&repository_name)) {
-        Ok(repository) => repository,
-        Err(_) => {
-            // Set up fetch options with progress-tracking callbacks.
-            let mut fetch_options = FetchOptions::new();
-            fetch_options.remote_callbacks(remote_callbacks);
-
-            // Clone only the latest commit to save time and space.
-            fetch_options.depth(1);
+/// The names of the program pairs that failed to download.
+fn download_all(
+    pairs: &[ProgramPair],
+    skiplist: &SkipList,
+    progress: &DownloadProgress,
+    options: &DownloadOptions,
+    lock: &CorpusLock,
+) -> Vec<String> {
+    if let Some(seed) = options.shuffle {
+        progress.println(format!("Shuffling the program pairs with seed {seed}"));
+    }
+    let pairs = order_pairs(pairs, options.shuffle);
+    // Offline downloads clone nothing.
+    if !options.offline {
+        progress.estimate(timings::estimate(
+            &options.root,
+            pairs.iter().copied(),
+            skiplist,
+        ));
+    }
+    // A cancelled download stops between pairs, so no pair is left half
+    // downloaded.
+    pairs
+        .into_iter()
+        .take_while(|_| !progress.is_cancelled())
+        .filter(|pair| !download_and_report(pair, skiplist, progress, options, lock))
+        .map(|pair| pair.program_name.clone())
+        .collect()
+}
 
-            // Clone the repository.
-            let mut builder = RepoBuilder::new();
-            builder.fetch_options(fetch_options);
-            builder
-                .clone(
-                    repository_url,
-                    &repository_clones_path.join(&repository_name),
-                )
-                .map_err(|error| DownloaderError::CloneRepository {
-                    repository_url: repository_url.to_string(),
-                    error,
-                })?
-        }
-    };
+/// Orders program pairs by name, or shuffles them.
+///
+/// Pairs are shuffled by sorting them by a hash of their name and the seed,
+/// so the same seed always yields the same order on every platform.
+///
+/// # Arguments
+///
+/// - `pairs` - The program pairs to order.
+/// - `shuffle` - The seed to shuffle the pairs with, or `None` to sort them
+///   by name.
+///
+/// # Returns
+///
+/// The ordered program pairs.
+fn order_pairs(pairs: &[ProgramPair], shuffle: Option<u64>) -> Vec<&ProgramPair> {
+    let mut ordered: Vec<&ProgramPair> = pairs.iter().collect();
+    match shuffle {
+        Some(seed) => ordered.sort_by_cached_key(|pair| {
+            (
+                utils::seeded_hash(&pair.program_name, seed),
+                pair.program_name.clone(),
+            )
+        }),
+        None => ordered.sort_by(|a, b| a.program_name.cmp(&b.program_name)),
+    }
+    ordered
+}
 
-    let repository_directory = repository
-        .workdir()
-        .ok_or_else(|| {
-            DownloaderError::Io(format!(
-                "Failed to find working directory for repository '{repository_name}'"
-            ))
-        })?
-        .to_path_buf();
-    Ok(repository_directory)
+/// Picks a seed to shuffle program pairs with when none is given.
+///
+/// # Returns
+///
+/// A seed derived from the current time.
+pub fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
 }
 
-/// Callback used to update the progress bar as a repository is cloned.
+/// Downloads a program pair, reporting whether it was downloaded, skipped,
+/// or failed.
 ///
 /// # Arguments
 ///
-/// - `repository_name` - The repository being cloned.
-/// - `progress` - Contains information about the current status of the download.
-/// - `progress_bar` - The progress bar to update.
+/// - `pair` - The program pair to download.
+/// - `skiplist` - The program pairs known to be broken.
+/// - `progress` - Is updated once the program pair is processed.
+/// - `options` - Options controlling the download.
+/// - `lock` - The commits repositories are locked to.
 ///
 /// # Returns
 ///
-/// True.  A progress bar callback function must return `true`.
-fn update_progress_bar_callback(
-    progress: git2::Progress,
-    repository_name: &str,
-    progress_bar: &ProgressBar,
+/// `false` if the program pair failed to download, and `true` if it was
+/// downloaded or skipped.
+fn download_and_report(
+    pair: &ProgramPair,
+    skiplist: &SkipList,
+    progress: &DownloadProgress,
+    options: &DownloadOptions,
+    lock: &CorpusLock,
 ) -> bool {
-    let received_objects = progress.received_objects();
-    let received_bytes = progress.received_bytes();
-    let total_objects = progress.total_objects();
-    let indexed_objects = progress.indexed_objects();
-
-    // Downloading objects.
-    if received_objects < total_objects {
-        progress_bar.set_length(total_objects as u64);
-        progress_bar.set_position(received_objects as u64);
-        progress_bar.set_message(format!(
-            "Cloning {repository_name}: ({received_bytes:.1} B)"
-        ));
-    }
-    // Processing downloaded objects.
-    else if indexed_objects < total_objects {
-        progress_bar.set_position(indexed_objects as u64);
-        progress_bar.set_message(format!(
-            "Indexing objects: {indexed_objects}/{total_objects}"
-        ));
+    if let Some(skip) = skiplist.get(&pair.program_name) {
+        progress.skip_pair(&pair.program_name, &skip.to_string());
+        return true;
     }
-    // Done with objects.
-    else {
-        progress_bar.set_message("Resolving deltas...");
+    if !options.force && is_up_to_date(pair, options, lock) {
+        progress.fresh_pair(&pair.program_name);
+        return true;
     }
 
+    progress.start_pair(&pair.program_name);
+    let result = download_program_pair(pair, progress, options, lock);
+    match result {
+        Ok(()) => progress.finish_pair(&pair.program_name),
+        Err(error @ DownloaderError::NotCached { .. }) => {
+            progress.skip_pair(&pair.program_name, &error.to_string())
+        }
+        Err(error) => {
+            progress.fail_pair(&pair.program_name, &error);
+            return false;
+        }
+    }
     true
 }
+
+/// Downloads a C-Rust program pair.
+///
+/// Checks if the C and Rust repositories exist, and clone them if they don't.
+/// Copy the C source files to program_pairs/<program_name>/c-program.
+/// Copy the Rust source files to program_pairs/<program_name>/rust-program.
+/// Record where both programs came from, and their licenses, in
+/// program_pairs/<program_name>/manifest.json.
+///
+/// # Side Effects
+///
+/// - Creates destination directories for program pairs at
+///   `program_pairs/<program-name>/`.
+/// - Writes the program pair's manifest.
+/// - Runs the post-download hook, if any, in the pair's directory.
+///
+/// # Arguments
+///
+/// - `pair` - A program pair.
+/// - `progress` - Displays the progress of repository clones.
+/// - `options` - Options controlling the download.
+/// - `lock` - The commits repositories are locked to.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`DownloaderError`] on failure.
+fn download_program_pair(
+    pair: &ProgramPair,
+    progress: &DownloadProgress,
+    options: &DownloadOptions,
+    lock: &CorpusLock,
+) -> Result<(), DownloaderError> {
+    // In offline mode, skip the pair before creating any directories unless
+    // all of its repositories are already cached.
+    if options.offline {
+        for program in pair.programs() {
+            let directory =
+                cache::clone_directory(&options.root, &program.source_url(&program.repository_url));
+            let cached = match program.source_type {
+                SourceType::Git => Repository::open(&directory).is_ok(),
+                SourceType::Tarball | SourceType::HttpFile => directory.is_dir(),
+            };
+            if !cached {
+                return Err(DownloaderError::NotCached {
+                    repository_url: program.repository_url.to_string(),
+                });
+            }
+        }
+    }
+
+    let program_name = &pair.program_name;
+    let base_program_path = pair_path(&options.root.join(PROGRAM_PAIRS_DIRECTORY), program_name)?;
+    let staging_path = pair_path(&options.root.join(STAGING_DIRECTORY), program_name)?;
+
+    // Decide what happens to files changed since the last download before
+    // any of them is overwritten.
+    let changes = conflict::find_local_changes(&base_program_path)?;
+    conflict::check(&changes, options.on_conflict, options.force)?;
+
+    // The pair is placed in a staging directory and only replaces its
+    // previous download once all of its files are placed, so a pair that
+    // fails or is interrupted never leaves a half-populated directory.  Other
+    // downloads of the pair, such as by another process, wait for this one.
+    let pair_lock = CacheLock::acquire(&staging_path, |holder| {
+        let holder = holder.map_or_else(String::new, |pid| format!(" (process {pid})"));
+        progress.println(format!(
+            "Waiting for another download of '{program_name}'{holder}"
+        ));
+    })
+    .map_err(|error| DownloaderError::Cache { error })?;
+    let staged = stage_program_pair(
+        pair,
+        &staging_path,
+        &base_program_path,
+        changes,
+        progress,
+        options,
+        lock,
+    );
+    // Neither the lock nor `.staging/` is left behind once no other pair is
+    // staged.
+    pair_lock.remove();
+    let _ = fs::remove_dir(options.root.join(STAGING_DIRECTORY));
+    let mut manifest = staged?;
+
+    if let Some(command) = &options.hook {
+        hook::run(command, program_name, &base_program_path)?;
+    }
+
+    // The fingerprint is only recorded once the hook succeeded, so that a
+    // pair whose hook failed is downloaded again.
+    manifest.fingerprint = fingerprint(pair, options, lock);
+    if manifest.fingerprint.is_some() {
+        manifest.save(&base_program_path)?;
+    }
+    Ok(())
+}
+
+/// Names the directory of a program pair within a directory such as
+/// `program_pairs/` or its staging directory.
+///
+/// Program names are validated when metadata is parsed, but the pair's
+/// directory is replaced and its previous download removed, so a name that
+/// would lead out of the directory, such as `../other` or an absolute path,
+/// is rejected here too.
+///
+/// # Arguments
+///
+/// - `directory` - The directory holding the pair's directory.
+/// - `program_name` - Name of the program pair.
+///
+/// # Returns
+///
+/// The pair's directory, or [`DownloaderError::PathEscape`] if the name is
+/// not a single plain path component.
+fn pair_path(directory: &Path, program_name: &str) -> Result<PathBuf, DownloaderError> {
+    let mut components = Path::new(program_name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(directory.join(program_name)),
+        _ => Err(DownloaderError::PathEscape {
+            path: PathBuf::from(program_name),
+            root: directory.to_path_buf(),
+        }),
+    }
+}
+
+/// Places the files of a program pair in its staging directory, applies the
+/// conflict policy to the files changed since the last download there, and,
+/// once all of them are placed, moves them into the pair's directory.
+///
+/// Changed files are only written back in the staging directory, so until
+/// the staged pair replaces the previous download in one step, they are
+/// still in place in the pair's directory.
+///
+/// # Arguments
+///
+/// - `pair` - A program pair.
+/// - `staging_path` - The pair's staging directory, under
+///   `program_pairs/.staging/`.
+/// - `pair_directory` - The program pair's directory.
+/// - `changes` - The pair's changed files, found before it was staged.
+/// - `progress` - Displays the progress of repository clones.
+/// - `options` - Options controlling the download.
+/// - `lock` - The commits repositories are locked to.
+///
+/// # Returns
+///
+/// The pair's saved [`Manifest`], without a fingerprint, on success, or a
+/// [`DownloaderError`] on failure, leaving the previous download in place.
+fn stage_program_pair(
+    pair: &ProgramPair,
+    staging_path: &Path,
+    pair_directory: &Path,
+    changes: Vec<LocalChange>,
+    progress: &DownloadProgress,
+    options: &DownloadOptions,
+    lock: &CorpusLock,
+) -> Result<Manifest, DownloaderError> {
+    prepare_staging(staging_path, pair_directory)?;
+    let staged =
+        place_program_pair(pair, staging_path, progress, options, lock).and_then(|manifest| {
+            keep_untracked_files(pair_directory, staging_path)?;
+            conflict::resolve(staging_path, changes, options.on_conflict, progress)?;
+            Ok(manifest)
+        });
+    match staged {
+        Ok(manifest) => {
+            promote(
+                staging_path,
+                pair_directory,
+                &options.root.join(PROGRAM_PAIRS_DIRECTORY),
+            )?;
+            Ok(manifest)
+        }
+        Err(error) => {
+            let _ = fs::remove_dir_all(staging_path);
+            Err(error)
+        }
+    }
+}
+
+/// Places the files of a program pair in an empty directory, and records the
+/// pair's manifest and provenance there.
+///
+/// # Arguments
+///
+/// - `pair` - A program pair.
+/// - `base_program_path` - The directory to place the pair in.
+/// - `progress` - Displays the progress of repository clones.
+/// - `options` - Options controlling the download.
+/// - `lock` - The commits repositories are locked to.
+///
+/// # Returns
+///
+/// The pair's saved [`Manifest`], without a fingerprint, on success, or a
+/// [`DownloaderError`] on failure.
+fn place_program_pair(
+    pair: &ProgramPair,
+    base_program_path: &Path,
+    progress: &DownloadProgress,
+    options: &DownloadOptions,
+    lock: &CorpusLock,
+) -> Result<Manifest, DownloaderError> {
+    let c_layout = program_layout(&pair.c_program, None, options);
+    let rust_layout = program_layout(&pair.rust_program, None, options);
+    let variant_layouts: BTreeMap<&str, ProgramLayout> = pair
+        .rust_variants
+        .iter()
+        .map(|variant| {
+            let label = variant.label.as_str();
+            (
+                label,
+                program_layout(&variant.program, Some(label), options),
+            )
+        })
+        .collect();
+    let c_program_path = base_program_path.join(&c_layout.directory);
+    let rust_program_path = base_program_path.join(&rust_layout.directory);
+
+    // Create the destination directories for the C and Rust source files.
+    fs::create_dir_all(&c_program_path).map_err(|source| DownloaderError::IoCreate {
+        path: c_program_path.clone(),
+        error: source,
+    })?;
+    fs::create_dir_all(&rust_program_path).map_err(|source| DownloaderError::IoCreate {
+        path: rust_program_path.clone(),
+        error: source,
+    })?;
+
+    let (c_program, c_files) = download_files(
+        &pair.c_program,
+        &c_program_path,
+        &c_layout,
+        progress,
+        options,
+        lock,
+    )?;
+    let (rust_program, rust_files) = download_files(
+        &pair.rust_program,
+        &rust_program_path,
+        &rust_layout,
+        progress,
+        options,
+        lock,
+    )?;
+    let mut files: Vec<FileProvenance> = c_files.into_iter().chain(rust_files).collect();
+
+    let mut rust_variants = BTreeMap::new();
+    for variant in &pair.rust_variants {
+        let layout = &variant_layouts[variant.label.as_str()];
+        let variant_path = base_program_path.join(&layout.directory);
+        fs::create_dir_all(&variant_path).map_err(|error| DownloaderError::IoCreate {
+            path: variant_path.clone(),
+            error,
+        })?;
+        let (variant_program, variant_files) = download_files(
+            &variant.program,
+            &variant_path,
+            layout,
+            progress,
+            options,
+            lock,
+        )?;
+        rust_variants.insert(variant.label.clone(), variant_program);
+        files.extend(variant_files);
+    }
+
+    let manifest = Manifest {
+        program_name: pair.program_name.clone(),
+        c_program,
+        rust_program,
+        rust_variants,
+        function_mappings: pair.function_mappings.clone(),
+        tags: pair.tags.clone(),
+        fingerprint: None,
+    };
+    manifest.save(base_program_path)?;
+    Provenance { files }.save(base_program_path)?;
+    Ok(manifest)
+}
+
+/// Clears the staging directory of a program pair before the pair is placed
+/// in it, recovering from a download that was interrupted while promoting
+/// the pair.
+///
+/// # Arguments
+///
+/// - `staging_path` - The pair's staging directory, under
+///   `program_pairs/.staging/`.
+/// - `pair_directory` - The program pair's directory.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::IoWrite`] if a
+/// leftover directory cannot be removed or restored.
+fn prepare_staging(staging_path: &Path, pair_directory: &Path) -> Result<(), DownloaderError> {
+    remove_directory(staging_path)?;
+    // A previous download that was moved aside but never replaced is put
+    // back, so the pair is not lost if this download fails too.
+    let previous_path = previous_download_path(staging_path);
+    if previous_path.exists() && !pair_directory.exists() {
+        fs::rename(&previous_path, pair_directory).map_err(|error| DownloaderError::IoWrite {
+            path: pair_directory.to_path_buf(),
+            error,
+        })?;
+    }
+    remove_directory(&previous_path)
+}
+
+/// Copies the files of a program pair's previous download that no download
+/// placed, such as notes, hook outputs, and `.orig` backups of changed
+/// files, into its staging directory, so that promoting the pair keeps them.
+/// A staged file at the same path replaces the previous one, as placing the
+/// pair over its previous download would.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The program pair's directory, which need not exist.
+/// - `staging_path` - The pair's staging directory, holding all of its
+///   placed files.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`DownloaderError`] if a file cannot be
+/// read or copied, so that the pair is not promoted without it.
+fn keep_untracked_files(pair_directory: &Path, staging_path: &Path) -> Result<(), DownloaderError> {
+    if !pair_directory.is_dir() {
+        return Ok(());
+    }
+    let mut placed: HashSet<PathBuf> = Provenance::load(pair_directory)
+        .map(|provenance| {
+            provenance
+                .files
+                .into_iter()
+                .map(|file| file.path.into())
+                .collect()
+        })
+        .unwrap_or_default();
+    placed.extend([PathBuf::from(MANIFEST_FILE), PathBuf::from(PROVENANCE_FILE)]);
+
+    for entry in WalkDir::new(pair_directory).min_depth(1) {
+        let entry = entry.map_err(|error| DownloaderError::IoRead {
+            path: pair_directory.to_path_buf(),
+            error: error.into(),
+        })?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(pair_directory)
+            .unwrap_or(entry.path());
+        let destination = staging_path.join(relative);
+        if placed.contains(relative) || fs::symlink_metadata(&destination).is_ok() {
+            continue;
+        }
+        let copy_error = |error| DownloaderError::IoWrite {
+            path: destination.clone(),
+            error,
+        };
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(copy_error)?;
+        }
+        if entry.path_is_symlink() {
+            let target = fs::read_link(entry.path()).map_err(|error| DownloaderError::IoRead {
+                path: entry.path().to_path_buf(),
+                error,
+            })?;
+            utils::symlink_file(&target, &destination).map_err(copy_error)?;
+        } else {
+            utils::copy_file(entry.path(), &destination).map_err(copy_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves a staged program pair into its directory, replacing its previous
+/// download.
+///
+/// The previous download is first moved aside next to the staging
+/// directory, since a directory cannot be renamed over another, and put
+/// back if the staged pair cannot be moved into place.  Since the previous
+/// download is then removed, the pair's directory is checked to lie directly
+/// within `program_pairs/` first.
+///
+/// # Arguments
+///
+/// - `staging_path` - The pair's staging directory, holding all of its
+///   files.
+/// - `pair_directory` - The program pair's directory.
+/// - `pairs_directory` - The `program_pairs/` directory, which must hold
+///   `pair_directory`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, [`DownloaderError::PathEscape`] if the pair's
+/// directory is not within `pairs_directory`, or [`DownloaderError::IoWrite`]
+/// if the pair cannot be moved into place.
+fn promote(
+    staging_path: &Path,
+    pair_directory: &Path,
+    pairs_directory: &Path,
+) -> Result<(), DownloaderError> {
+    // Neither `program_pairs/` itself nor a directory nested deeper in it is
+    // a pair's directory.
+    let resolved = utils::ensure_within(pair_directory, pairs_directory)?;
+    let is_pair_directory = resolved
+        .parent()
+        .zip(pairs_directory.canonicalize().ok())
+        .is_some_and(|(parent, pairs_directory)| parent == pairs_directory);
+    if !is_pair_directory {
+        return Err(DownloaderError::PathEscape {
+            path: pair_directory.to_path_buf(),
+            root: pairs_directory.to_path_buf(),
+        });
+    }
+    let promote_error = |error| DownloaderError::IoWrite {
+        path: pair_directory.to_path_buf(),
+        error,
+    };
+    let previous_path = previous_download_path(staging_path);
+    let replaces = pair_directory.exists();
+    if replaces {
+        fs::rename(pair_directory, &previous_path).map_err(promote_error)?;
+    }
+    if let Err(error) = fs::rename(staging_path, pair_directory) {
+        if replaces {
+            let _ = fs::rename(&previous_path, pair_directory);
+        }
+        return Err(promote_error(error));
+    }
+    // The pair is complete, so its previous download is only removed on a
+    // best-effort basis; the next download of the pair removes it otherwise.
+    let _ = fs::remove_dir_all(&previous_path);
+    Ok(())
+}
+
+/// Names where a program pair's previous download is moved aside while the
+/// pair is promoted.
+///
+/// # Arguments
+///
+/// - `staging_path` - The pair's staging directory.
+///
+/// # Returns
+///
+/// The staging directory's path suffixed with `.previous`.
+fn previous_download_path(staging_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.previous", staging_path.display()))
+}
+
+/// Removes a directory and its contents if it exists.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::IoWrite`] if the
+/// directory exists but cannot be removed.
+fn remove_directory(path: &Path) -> Result<(), DownloaderError> {
+    match fs::remove_dir_all(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(DownloaderError::IoWrite {
+            path: path.to_path_buf(),
+            error,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Fingerprints what downloading a program pair depends on: its metadata,
+/// the commits or checksums its programs are pinned to, the contents of its
+/// patches, and the download options that change which files are placed and
+/// how.
+///
+/// # Arguments
+///
+/// - `pair` - The program pair.
+/// - `options` - Options controlling the download.
+/// - `lock` - The commits repositories are locked to.
+///
+/// # Returns
+///
+/// The SHA-256 of the inputs as hex, or `None` if a repository is not
+/// locked yet, so the commit it would be downloaded at is unknown, or a
+/// patch cannot be read.
+fn fingerprint(pair: &ProgramPair, options: &DownloadOptions, lock: &CorpusLock) -> Option<String> {
+    // The name only decides the pair's directory, so leave it out to keep
+    // renamed pairs up to date.  Domain tags are added to the manifest by
+    // `analyze --classify` itself, so classifying leaves pairs up to date.
+    let unnamed = ProgramPair {
+        program_name: String::new(),
+        tags: pair
+            .tags
+            .iter()
+            .filter(|tag| !analyze::is_domain_label(tag))
+            .cloned()
+            .collect(),
+        ..pair.clone()
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(&(unnamed, &options.extensions)).ok()?);
+    for program in pair.programs() {
+        let pin = match program.source_type {
+            SourceType::Git => {
+                lock.commit(program.repository_url.as_str(), program.branch.as_deref())?
+            }
+            SourceType::Tarball | SourceType::HttpFile => program.checksum.clone()?,
+        };
+        hasher.update(pin);
+        // Editing a patch changes the files it places.
+        for patch_file in &program.patches {
+            hasher.update(fs::read(options.root.join(patch_file.to_path())).ok()?);
+        }
+    }
+    hasher.update(format!(
+        "{:?} {:?} {:?} {:?} {:?} {:?}",
+        options.link,
+        options.hook,
+        options.layout,
+        options.include_build_files,
+        options.transforms,
+        options.allow_binary
+    ));
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+/// Checks whether a program pair was downloaded with the same fingerprint
+/// it has now, so downloading it again would place the same files.
+///
+/// # Arguments
+///
+/// - `pair` - The program pair.
+/// - `options` - Options controlling the download.
+/// - `lock` - The commits repositories are locked to.
+///
+/// # Returns
+///
+/// Whether the pair's manifest records its current fingerprint.
+fn is_up_to_date(pair: &ProgramPair, options: &DownloadOptions, lock: &CorpusLock) -> bool {
+    let pair_directory = options
+        .root
+        .join(PROGRAM_PAIRS_DIRECTORY)
+        .join(&pair.program_name);
+    fingerprint(pair, options, lock).is_some_and(|fingerprint| {
+        Manifest::load(&pair_directory)
+            .and_then(|manifest| manifest.fingerprint)
+            .is_some_and(|recorded| recorded == fingerprint)
+    })
+}
+
+/// Where the files of a program are placed within its pair's directory.
+struct ProgramLayout {
+    /// Name of the program's directory.
+    directory: String,
+    /// Whether the files of `source_paths` directories are flattened into
+    /// the program's directory.
+    flatten: bool,
+}
+
+/// Decides where the files of a program are placed, from the `--layout`
+/// preset if one is given and from the metadata otherwise.
+///
+/// # Arguments
+///
+/// - `program` - The program.
+/// - `label` - The label of the Rust variant the program is, if any, which
+///   suffixes its default directory, as in `rust-program-ripgrep`.
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// The program's [`ProgramLayout`].
+fn program_layout(
+    program: &Program,
+    label: Option<&str>,
+    options: &DownloadOptions,
+) -> ProgramLayout {
+    let directory = |name: &str| match label {
+        Some(label) => variant_directory(name, label),
+        None => name.to_string(),
+    };
+    let default_directory = directory(program.language.program_directory());
+    match options.layout {
+        Some(Layout::Flat) => ProgramLayout {
+            directory: default_directory,
+            flatten: true,
+        },
+        Some(Layout::Mirrored) => ProgramLayout {
+            directory: default_directory,
+            flatten: false,
+        },
+        Some(Layout::ByLanguage) => ProgramLayout {
+            directory: directory(program.language.to_str()),
+            flatten: true,
+        },
+        None => ProgramLayout {
+            directory: program
+                .output_directory
+                .clone()
+                .unwrap_or(default_directory),
+            flatten: program.flatten.unwrap_or(true),
+        },
+    }
+}
+
+/// Downloads the source files of a program from its Git repository, tarball,
+/// or file.
+///
+/// This function clones the repository (if not already cached) into
+/// `repository_clones/<host>/<path>` and checks out the commit it is locked
+/// to, locking it to the commit it resolved to if it is not locked yet, or
+/// fetches and verifies the program's tarball or file into the same place,
+/// then copies the program's
+/// `source_paths`, except its `exclude_paths`, into the given
+/// `program_directory`, along with its build files and any license files at
+/// the repository root.
+///
+/// A nested progress bar tracks cloning progress.
+///
+/// # Side Effects
+///
+/// - Creates `repository_clones/`, which is a local cache for git clones.
+/// - May overwrite files at `program_directory`.
+///
+/// # Arguments
+///
+/// - `program` - The program to download.
+/// - `program_directory` - Destination directory for the downloaded source files.
+/// - `layout` - Where the program's files are placed within its pair's
+///   directory.
+/// - `progress` - Displays the progress of the repository clone.
+/// - `options` - Options controlling the download.
+/// - `lock` - The commits repositories are locked to.
+///
+/// # Returns
+///
+/// The [`ProgramManifest`] describing the program's origin and license, and
+/// the [`FileProvenance`] of every copied file, if all files were
+/// successfully downloaded and copied, or [`DownloaderError`] on failure.
+fn download_files(
+    program: &Program,
+    program_directory: &Path,
+    layout: &ProgramLayout,
+    progress: &DownloadProgress,
+    options: &DownloadOptions,
+    lock: &CorpusLock,
+) -> Result<(ProgramManifest, Vec<FileProvenance>), DownloaderError> {
+    let repository_url = program.repository_url.as_str();
+    let repository_name = utils::get_repository_name(repository_url)?;
+    let clone_progress = progress.start_clone(&format!(
+        "{repository_name} ({})",
+        program.language.to_str()
+    ))?;
+
+    let clone_depth = options
+        .clone_depth
+        .or(program.clone_depth)
+        .unwrap_or(DEFAULT_CLONE_DEPTH);
+    // Tarballs and files are pinned by their checksums instead of the lock.
+    let is_git = program.source_type == SourceType::Git;
+    let checkout = download_with_mirrors(
+        program,
+        clone_depth,
+        lock.commit(repository_url, program.branch.as_deref())
+            .filter(|_| is_git)
+            .as_deref(),
+        &clone_progress,
+        options,
+    )?;
+    if is_git {
+        lock.lock(repository_url, program.branch.as_deref(), &checkout.commit);
+    }
+
+    clone_progress.copying();
+
+    // Files are linked when requested, but the program only counts as linked
+    // if none had to be copied instead.
+    let all_linked = AtomicBool::new(true);
+    let on_placed = |placed: PlacedFile| {
+        clone_progress.copied(&placed.path, placed.bytes);
+        if !placed.linked {
+            clone_progress.metrics().copied(placed.bytes);
+            all_linked.store(false, Ordering::Relaxed);
+        }
+    };
+
+    // Resolve the clone once so every copied path can be checked against it.
+    let repository_directory = utils::ensure_within(&checkout.directory, &checkout.directory)?;
+
+    // Records where a copied file came from; `source` is under
+    // `repository_directory` and `file_name` is the copy's name.
+    let mut provenance = Vec::new();
+    let mut record = |source: &Path, file_name: &str| {
+        let source_path = source
+            .strip_prefix(&repository_directory)
+            .ok()
+            .and_then(utils::slash_path)
+            .unwrap_or_else(|| source.display().to_string());
+        provenance.push(FileProvenance {
+            path: format!("{}/{file_name}", layout.directory),
+            repository_url: checkout.url.to_string(),
+            branch: program.branch.clone(),
+            commit: checkout.commit.clone(),
+            source_path,
+            transforms: Vec::new(),
+            sha256: None,
+        });
+    };
+
+    let exclude: Vec<PathBuf> = program
+        .exclude_paths
+        .iter()
+        .map(|path| repository_directory.join(path.to_path()))
+        .collect();
+
+    let extensions = program.extensions.extend(&options.extensions);
+    let store = options.root.join(STORE_OBJECTS_DIRECTORY);
+
+    // Copy given files from the repository to the given directory.
+    for source_path in &program.source_paths {
+        let file_path = source_path.to_path();
+        let file_name = file_path.file_name().ok_or_else(|| {
+            DownloaderError::Io(format!("Failed to get file name for path '{source_path}'"))
+        })?;
+        // Excluded files are skipped whether they are listed themselves or
+        // found in a directory.
+        if exclude
+            .iter()
+            .any(|path| repository_directory.join(&file_path).starts_with(path))
+        {
+            continue;
+        }
+
+        // Metadata paths and symbolic links in the clone must not reach
+        // outside the repository or the program's directory.
+        let source = utils::ensure_within(
+            &repository_directory.join(&file_path),
+            &repository_directory,
+        )?;
+        let destination = program_directory.join(file_name);
+        remove_stale_link(&destination)?;
+        let destination = utils::ensure_within(&destination, program_directory)?;
+
+        // Copy files from destination to source.
+        if source.is_dir() {
+            let copied = utils::copy_files_from_directory(
+                &source,
+                program_directory,
+                &exclude,
+                &extensions,
+                &repository_directory,
+                utils::Placement {
+                    link: options.link,
+                    store: &store,
+                    flatten: layout.flatten,
+                    allow_binary: options.allow_binary,
+                },
+                &on_placed,
+            )?;
+            for (path, file_name) in &copied.copied {
+                record(path, file_name);
+            }
+            for path in &copied.binaries {
+                let path = path.strip_prefix(&repository_directory).unwrap_or(path);
+                clone_progress.println(format!(
+                    "Skipped binary file '{}' (pass --allow-binary to copy it)",
+                    path.display()
+                ));
+            }
+        } else {
+            let placed = utils::place_file(&source, &destination, options.link, &store).map_err(
+                |error| DownloaderError::IoCopy {
+                    source: source.to_path_buf(),
+                    destination: destination.to_path_buf(),
+                    error,
+                },
+            )?;
+            on_placed(placed);
+            // Record the path from the metadata rather than the resolved
+            // one, which differs if it is a symbolic link.
+            record(
+                &repository_directory.join(&file_path),
+                &file_name.to_string_lossy(),
+            );
+        }
+    }
+
+    // Places a file from the repository directly in the program's
+    // directory, under its own name.
+    let mut place_beside_sources = |source: &Path| -> Result<Option<String>, DownloaderError> {
+        let Some(file_name) = source.file_name() else {
+            return Ok(None);
+        };
+        let resolved = utils::ensure_within(source, &repository_directory)?;
+        let destination = program_directory.join(file_name);
+        remove_stale_link(&destination)?;
+        let destination = utils::ensure_within(&destination, program_directory)?;
+        let placed =
+            utils::place_file(&resolved, &destination, options.link, &store).map_err(|error| {
+                DownloaderError::IoCopy {
+                    source: resolved.to_path_buf(),
+                    destination,
+                    error,
+                }
+            })?;
+        on_placed(placed);
+        let file_name = file_name.to_string_lossy().into_owned();
+        record(source, &file_name);
+        Ok(Some(file_name))
+    };
+
+    // Copy the files needed to build the program, which metadata rarely
+    // lists among its sources.
+    let mut build_files: Vec<PathBuf> = program
+        .build_files
+        .iter()
+        .map(|path| repository_directory.join(path.to_path()))
+        .collect();
+    if options.include_build_files {
+        build_files.extend(build_files::find_build_files(
+            &repository_directory,
+            &program.language,
+            &program.source_paths,
+        ));
+    }
+    let mut build_file_names: Vec<String> = Vec::new();
+    for source in &build_files {
+        // A file listed in the metadata takes precedence over a detected
+        // file of the same name.
+        let file_name = source.file_name().unwrap_or_default().to_string_lossy();
+        if build_file_names.iter().any(|placed| *placed == file_name) {
+            continue;
+        }
+        if let Some(file_name) = place_beside_sources(source)? {
+            build_file_names.push(file_name);
+        }
+    }
+
+    // Copy license files so the program can be redistributed with its terms.
+    let license_files = license::find_license_files(&repository_directory);
+    let mut license_file_names = Vec::new();
+    for source in &license_files {
+        if let Some(file_name) = place_beside_sources(source)? {
+            license_file_names.push(file_name);
+        }
+    }
+
+    // Apply the corpus's own fixes to the placed files.  Patched files are
+    // written as copies, so they no longer link to the repository.
+    for patch_file in &program.patches {
+        let patch_file = options.root.join(patch_file.to_path());
+        let patched = patch::apply(&patch_file, program_directory).map_err(|error| {
+            DownloaderError::Patch {
+                patch: patch_file,
+                error,
+            }
+        })?;
+        if !patched.is_empty() {
+            all_linked.store(false, Ordering::Relaxed);
+        }
+    }
+
+    // Transform the placed sources after patching, since patches are
+    // written against the upstream files.  Transformed files are written as
+    // copies, like patched ones.
+    if !options.transforms.is_empty() {
+        for file in &mut provenance {
+            let Some(path) = placed_path(file, program_directory, layout) else {
+                continue;
+            };
+            let transformed =
+                transform::transform_file(&path, &options.transforms).map_err(|error| {
+                    DownloaderError::Transform {
+                        path: path.clone(),
+                        error,
+                    }
+                })?;
+            if transformed {
+                file.transforms = options.transforms.clone();
+                all_linked.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Hash the files as placed, so changes made to them later are found
+    // before they are overwritten.
+    for file in &mut provenance {
+        if let Some(path) = placed_path(file, program_directory, layout) {
+            file.sha256 = store::hash_file(&path).ok();
+        }
+    }
+
+    let manifest = ProgramManifest {
+        repository_url: repository_url.to_string(),
+        mirror_url: (checkout.url != &program.repository_url).then(|| checkout.url.to_string()),
+        license: license::detect_license(&license_files),
+        license_files: license_file_names,
+        build_files: build_file_names,
+        patches: program
+            .patches
+            .iter()
+            .map(|path| path.as_str().to_string())
+            .collect(),
+        materialization: match options.link {
+            Some(link) if all_linked.load(Ordering::Relaxed) => link.into(),
+            _ => Materialization::Copy,
+        },
+        directory: (layout.directory != program.language.side().program_directory())
+            .then(|| layout.directory.clone()),
+        keep_subdirectories: !layout.flatten,
+    };
+    Ok((manifest, provenance))
+}
+
+/// Finds where a copied file of a program was placed.
+///
+/// # Arguments
+///
+/// - `file` - The file's provenance.
+/// - `program_directory` - The program's directory.
+/// - `layout` - Where the program's files are placed within its pair's
+///   directory.
+///
+/// # Returns
+///
+/// The file's path, or `None` if its provenance records a path outside of
+/// the program's directory.
+fn placed_path(
+    file: &FileProvenance,
+    program_directory: &Path,
+    layout: &ProgramLayout,
+) -> Option<PathBuf> {
+    let file_name = file
+        .path
+        .strip_prefix(&layout.directory)
+        .and_then(|path| path.strip_prefix('/'))?;
+    Some(program_directory.join(file_name))
+}
+
+/// Removes a symbolic link left in a program's directory by an earlier
+/// download with `--link symbolic`, which would otherwise resolve outside of
+/// the directory.
+fn remove_stale_link(destination: &Path) -> Result<(), DownloaderError> {
+    utils::remove_symlink(destination).map_err(|error| {
+        DownloaderError::Io(format!(
+            "Failed to remove link '{}': {}",
+            destination.display(),
+            error
+        ))
+    })
+}
+
+/// Downloads a git repository using git clone.
+///
+/// Repositories are cached by URL, so a repository shared by several
+/// programs, in either language, is only cloned once.  A cached shallow clone
+/// with less history than requested is deepened to the depth requested.
+/// Submodules, when needed, are initialized recursively after cloning.
+///
+/// # Arguments
+///
+/// - `repository_url` - The URL to download with git.
+/// - `clone_depth` - Number of commits of history to clone, where 0 clones
+///   the full history.
+/// - `needs_submodules` - Whether to clone the repository's submodules.
+/// - `branch` - The branch to download, or `None` for the default branch.
+/// - `locked_commit` - The commit to check out, from `corpus.lock`, or
+///   `None` to check out the head of `branch`, or to keep the commit the
+///   clone has checked out if there is no branch.
+/// - `clone_progress` - The progress bar showing the clone's progress.
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// The working directory of the downloaded repository and the SHA of its
+/// checked-out commit on success, or a [`DownloaderError`] on failure.  In
+/// offline mode, a repository that is not cached yields
+/// [`DownloaderError::NotCached`].
+pub(crate) fn download_with_git(
+    repository_url: &str,
+    clone_depth: u64,
+    needs_submodules: bool,
+    branch: Option<&str>,
+    locked_commit: Option<&str>,
+    clone_progress: &CloneProgress,
+    options: &DownloadOptions,
+) -> Result<(PathBuf, String), DownloaderError> {
+    let repository_path = cache::clone_directory(&options.root, repository_url);
+    let repository_name = utils::get_repository_name(repository_url)?;
+    let use_cli = options.git_backend == GitBackend::Cli && git_cli::is_available();
+    let limits = TransferLimits::new(options);
+    let timed_out = |error| limits.or_timed_out(repository_url, error);
+
+    // A broken clone, such as one left by an interrupted clone, is cloned
+    // again rather than failing confusingly once its files are copied.
+    if repository_path.exists()
+        && let Some(problem) = cache::diagnose(&repository_path)
+    {
+        if options.offline {
+            return Err(DownloaderError::UnhealthyClone {
+                repository_url: repository_url.to_string(),
+                problem: problem.to_string(),
+            });
+        }
+        clone_progress.println(format!(
+            "Cached clone of '{repository_name}' is unhealthy ({problem}); cloning it again"
+        ));
+        cache::remove_clone(&options.root, &repository_path).map_err(|error| {
+            DownloaderError::Io(format!(
+                "Failed to remove unhealthy clone of '{repository_name}': {error}"
+            ))
+        })?;
+    }
+
+    // Check if repository exists in `repository_clones/`, if not clone it.
+    let started = Instant::now();
+    let mut cloned = false;
+    let repository = match Repository::open(&repository_path) {
+        Ok(repository)
+            if repository.is_shallow()
+                && (clone_depth == 0 || history_depth(&repository) < clone_depth) =>
+        {
+            if options.offline {
+                clone_progress.println(format!(
+                    "Using shallow clone of '{repository_name}'; offline mode cannot fetch more \
+                     of its history"
+                ));
+            } else if use_cli {
+                git_cli::fetch_history(
+                    repository_url,
+                    &repository_path,
+                    clone_depth,
+                    clone_progress,
+                    options,
+                    limits.deadline(),
+                )
+                .map_err(timed_out)?;
+            } else {
+                let fetch_options = fetch_options(clone_progress, &limits, options);
+                fetch_history(&repository, clone_depth, fetch_options)
+                    .map_err(|error| DownloaderError::FetchRepository {
+                        repository_url: repository_url.to_string(),
+                        error,
+                    })
+                    .map_err(timed_out)?;
+            }
+            repository
+        }
+        Ok(repository) => repository,
+        Err(_) if options.offline => {
+            return Err(DownloaderError::NotCached {
+                repository_url: repository_url.to_string(),
+            });
+        }
+        Err(_) if use_cli => {
+            git_cli::clone(
+                repository_url,
+                &repository_path,
+                clone_depth,
+                branch,
+                clone_progress,
+                options,
+                limits.deadline(),
+            )
+            .map_err(timed_out)?;
+            cloned = true;
+            Repository::open(&repository_path).map_err(|error| {
+                DownloaderError::CloneRepository {
+                    repository_url: repository_url.to_string(),
+                    error,
+                }
+            })?
+        }
+        Err(_) => {
+            let mut fetch_options = fetch_options(clone_progress, &limits, options);
+
+            // Depths beyond `i32::MAX` cannot be requested, and are in
+            // practice the full history anyway.
+            if utils::supports_shallow_fetch(repository_url) {
+                fetch_options.depth(i32::try_from(clone_depth).unwrap_or(0));
+            }
+
+            // Clone the repository.
+            let mut builder = RepoBuilder::new();
+            builder.fetch_options(fetch_options);
+            if let Some(branch) = branch {
+                builder.branch(branch);
+            }
+            let repository = builder
+                .clone(repository_url, &repository_path)
+                .map_err(|error| DownloaderError::CloneRepository {
+                    repository_url: repository_url.to_string(),
+                    error,
+                })
+                .map_err(timed_out)?;
+            cloned = true;
+            repository
+        }
+    };
+
+    // Timings only improve later estimates, so failing to record one should
+    // not fail the download.
+    if cloned {
+        let bytes = utils::directory_size(&repository_path);
+        clone_progress.metrics().downloaded(bytes);
+        if let Err(error) =
+            timings::record_clone(&options.root, repository_url, started.elapsed(), bytes)
+        {
+            clone_progress.println(format!("Failed to record clone timing: {error}"));
+        }
+    } else {
+        clone_progress.metrics().cache_hit();
+    }
+
+    // A clone made for another branch, or a shallow clone, which has only
+    // the branch it was made for, may lack the branch or its locked commit.
+    if let Some(branch) = branch
+        && !options.offline
+    {
+        let has_branch = repository
+            .find_reference(&format!("refs/remotes/origin/{branch}"))
+            .is_ok();
+        let has_locked_commit = locked_commit.is_none_or(|commit| {
+            Oid::from_str(commit).is_ok_and(|oid| repository.find_commit(oid).is_ok())
+        });
+        if !has_branch || !has_locked_commit {
+            // The locked commit may be anywhere in the branch's history.
+            let depth = if has_locked_commit { clone_depth } else { 0 };
+            if use_cli {
+                git_cli::fetch_branch(
+                    repository_url,
+                    &repository_path,
+                    branch,
+                    depth,
+                    clone_progress,
+                    options,
+                    limits.deadline(),
+                )
+                .map_err(timed_out)?;
+            } else {
+                let mut fetch_options = fetch_options(clone_progress, &limits, options);
+                if repository.is_shallow() && utils::supports_shallow_fetch(repository_url) {
+                    // libgit2 treats the maximum depth as a request to
+                    // unshallow the clone.
+                    let depth = i32::try_from(depth).unwrap_or(0);
+                    fetch_options.depth(if depth > 0 { depth } else { i32::MAX });
+                }
+                fetch_branch(&repository, branch, fetch_options)
+                    .map_err(|error| DownloaderError::Branch {
+                        repository_url: repository_url.to_string(),
+                        branch: branch.to_string(),
+                        error,
+                    })
+                    .map_err(timed_out)?;
+            }
+        }
+    }
+
+    // The submodules to update depend on the commit checked out.
+    let commit = match (locked_commit, branch) {
+        (Some(locked_commit), _) => Some(locked_commit.to_string()),
+        (None, Some(branch)) => {
+            let head =
+                branch_head(&repository, branch).map_err(|error| DownloaderError::Branch {
+                    repository_url: repository_url.to_string(),
+                    branch: branch.to_string(),
+                    error,
+                })?;
+            Some(head)
+        }
+        (None, None) => None,
+    };
+    if let Some(commit) = commit {
+        check_out_commit(
+            &repository,
+            repository_url,
+            &commit,
+            clone_progress,
+            &limits,
+            options,
+        )
+        .map_err(timed_out)?;
+    }
+
+    if needs_submodules && use_cli {
+        git_cli::update_submodules(
+            repository_url,
+            &repository_path,
+            clone_progress,
+            options,
+            limits.deadline(),
+        )
+        .map_err(timed_out)?;
+    } else if needs_submodules {
+        update_submodules(&repository, clone_progress, &limits, options)
+            .map_err(|error| DownloaderError::UpdateSubmodules {
+                repository_url: repository_url.to_string(),
+                error,
+            })
+            .map_err(timed_out)?;
+    }
+
+    // The cache index only guides pruning, so failing to update it should
+    // not fail the download.
+    if let Err(error) = cache::record_use(&options.root, &repository_path) {
+        clone_progress.println(format!("Failed to update cache index: {error}"));
+    }
+
+    let repository_directory = repository
+        .workdir()
+        .ok_or_else(|| {
+            DownloaderError::Io(format!(
+                "Failed to find working directory for repository '{repository_name}'"
+            ))
+        })?
+        .to_path_buf();
+    let commit = repository
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|error| DownloaderError::ResolveHead {
+            repository_url: repository_url.to_string(),
+            error,
+        })?
+        .id()
+        .to_string();
+    Ok((repository_directory, commit))
+}
+
+/// Downloads a program's tarball or file into `repository_clones/`, which
+/// caches it by URL like a clone.
+///
+/// The source is fetched unless it is cached with the checksum in the
+/// metadata, and is discarded if the fetched bytes do not match the
+/// checksum.  Tarballs are extracted, without their top-level directory if
+/// every file is in one, as in release tarballs such as `hello-2.12/`.
+/// Files are saved under the last segment of their URL.
+///
+/// # Arguments
+///
+/// - `url` - The URL of the tarball or file.
+/// - `program` - The program whose source is downloaded.
+/// - `clone_progress` - The progress bar tracking the download.
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// The directory holding the source's files and the source's SHA-256 on
+/// success, or a [`DownloaderError`] on failure.  In offline mode, a source
+/// that is not cached yields [`DownloaderError::NotCached`].
+fn download_source(
+    url: &str,
+    program: &Program,
+    clone_progress: &CloneProgress,
+    options: &DownloadOptions,
+) -> Result<(PathBuf, String), DownloaderError> {
+    let checksum = program.checksum.as_deref().unwrap_or_default();
+    let directory = cache::clone_directory(&options.root, url);
+    if fs::read_to_string(directory.join(SOURCE_CHECKSUM_FILE))
+        .is_ok_and(|cached| cached.trim() == checksum)
+    {
+        clone_progress.metrics().cache_hit();
+        return Ok((directory, checksum.to_string()));
+    }
+    if options.offline {
+        return Err(DownloaderError::NotCached {
+            repository_url: url.to_string(),
+        });
+    }
+
+    let bytes = fetch_source(url, options)?;
+    let actual = format!("{:x}", Sha256::digest(&bytes));
+    if actual != checksum {
+        return Err(DownloaderError::ChecksumMismatch {
+            url: url.to_string(),
+            expected: checksum.to_string(),
+            actual,
+        });
+    }
+    clone_progress.metrics().downloaded(bytes.len() as u64);
+
+    // Extract beside the cache entry and move it into place once complete,
+    // so an interrupted download never leaves a partial entry.
+    let extract_error = |error| DownloaderError::Extract {
+        url: url.to_string(),
+        error,
+    };
+    let partial = PathBuf::from(format!("{}.partial", directory.display()));
+    if partial.exists() {
+        fs::remove_dir_all(&partial).map_err(extract_error)?;
+    }
+    fs::create_dir_all(&partial).map_err(extract_error)?;
+    let root = if program.source_type == SourceType::Tarball {
+        unpack_tarball(&bytes, &partial).map_err(extract_error)?
+    } else {
+        let file_name = Url::parse(url)
+            .ok()
+            .and_then(|url| Some(url.path_segments()?.next_back()?.to_string()))
+            .filter(|file_name| !file_name.is_empty())
+            .ok_or_else(|| DownloaderError::FetchSource {
+                url: url.to_string(),
+                message: "the URL does not end in a file name".to_string(),
+            })?;
+        fs::write(partial.join(file_name), &bytes).map_err(extract_error)?;
+        partial.clone()
+    };
+    fs::write(root.join(SOURCE_CHECKSUM_FILE), checksum).map_err(extract_error)?;
+    if directory.exists() {
+        fs::remove_dir_all(&directory).map_err(extract_error)?;
+    }
+    fs::rename(&root, &directory).map_err(extract_error)?;
+    if partial.exists() {
+        fs::remove_dir_all(&partial).map_err(extract_error)?;
+    }
+    Ok((directory, checksum.to_string()))
+}
+
+/// Fetches the bytes of a tarball or file.
+///
+/// `http` and `https` URLs are fetched through the configured proxy and CA
+/// bundle, `ftp` URLs with the system `curl` binary, and `file` URLs are
+/// read from disk.
+///
+/// # Arguments
+///
+/// - `url` - The URL of the tarball or file.
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// The fetched bytes, or [`DownloaderError::FetchSource`] on failure.
+fn fetch_source(url: &str, options: &DownloadOptions) -> Result<Vec<u8>, DownloaderError> {
+    let fetch_error = |message: String| DownloaderError::FetchSource {
+        url: url.to_string(),
+        message,
+    };
+    let parsed = Url::parse(url).map_err(|error| fetch_error(error.to_string()))?;
+    match parsed.scheme() {
+        "file" => {
+            let path = parsed
+                .to_file_path()
+                .map_err(|()| fetch_error("not a local path".to_string()))?;
+            fs::read(path).map_err(|error| fetch_error(error.to_string()))
+        }
+        "ftp" => {
+            let mut command = Command::new("curl");
+            command.args(["--silent", "--show-error", "--fail"]);
+            if let Some(proxy) = &options.proxy {
+                command.arg("--proxy").arg(proxy);
+            }
+            if let Some(timeout) = options.timeout {
+                command.arg("--max-time").arg(timeout.as_secs().to_string());
+            }
+            if let Some(max_bandwidth) = options.max_bandwidth {
+                command.arg("--limit-rate").arg(max_bandwidth.to_string());
+            }
+            let output = command
+                .arg(url)
+                .output()
+                .map_err(|error| fetch_error(format!("failed to run `curl`: {error}")))?;
+            if !output.status.success() {
+                return Err(fetch_error(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ));
+            }
+            Ok(output.stdout)
+        }
+        _ => {
+            let mut builder = Client::builder().user_agent(utils::USER_AGENT);
+            if let Some(proxy) = &options.proxy {
+                let proxy = Proxy::all(proxy).map_err(|error| fetch_error(error.to_string()))?;
+                builder = builder.proxy(proxy);
+            }
+            if let Some(timeout) = options.timeout {
+                builder = builder.timeout(timeout);
+            }
+            // Only bundle files can be given to reqwest; directories of
+            // certificates apply to git alone.
+            if let Some(ca_bundle) = options.ca_bundle.as_ref().filter(|path| path.is_file()) {
+                let pem = fs::read(ca_bundle).map_err(|error| fetch_error(error.to_string()))?;
+                let certificates = Certificate::from_pem_bundle(&pem)
+                    .map_err(|error| fetch_error(error.to_string()))?;
+                for certificate in certificates {
+                    builder = builder.add_root_certificate(certificate);
+                }
+            }
+            builder
+                .build()
+                .and_then(|client| client.get(parsed).send())
+                .and_then(|response| response.error_for_status())
+                .and_then(|response| response.bytes())
+                .map(|bytes| bytes.to_vec())
+                .map_err(|error| fetch_error(error.to_string()))
+        }
+    }
+}
+
+/// Extracts a tar archive, gzipped or not, into a directory.
+///
+/// # Arguments
+///
+/// - `bytes` - The archive.
+/// - `directory` - The empty directory to extract the archive into.
+///
+/// # Returns
+///
+/// The directory the archive's paths are relative to: its only top-level
+/// directory if it has one, or else `directory`; or an [`io::Error`] on
+/// failure.
+fn unpack_tarball(bytes: &[u8], directory: &Path) -> io::Result<PathBuf> {
+    let reader: Box<dyn Read> = if bytes.starts_with(&GZIP_MAGIC) {
+        Box::new(GzDecoder::new(bytes))
+    } else {
+        Box::new(bytes)
+    };
+    // `unpack` refuses entries that would be written outside `directory`.
+    Archive::new(reader).unpack(directory)?;
+
+    let entries = fs::read_dir(directory)?.collect::<io::Result<Vec<_>>>()?;
+    match entries.as_slice() {
+        [entry] if entry.file_type()?.is_dir() => Ok(entry.path()),
+        _ => Ok(directory.to_path_buf()),
+    }
+}
+
+/// Clones a program's repository, or fetches its tarball or file, falling
+/// back to its mirrors if the repository URL fails.
+///
+/// URLs whose clone is already cached are tried first, so a repository
+/// fetched from a mirror is not cloned again from its repository URL.
+///
+/// # Arguments
+///
+/// - `program` - The program whose repository is cloned.
+/// - `clone_depth` - Number of commits of history to clone; 0 clones the full
+///   history.
+/// - `locked_commit` - The commit to check out, if the repository is locked.
+/// - `clone_progress` - The progress bar tracking the clone.
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// The [`Checkout`] of the URL that was cloned on success, or the
+/// [`DownloaderError`] of the last URL tried if every URL fails.
+fn download_with_mirrors<'a>(
+    program: &'a Program,
+    clone_depth: u64,
+    locked_commit: Option<&str>,
+    clone_progress: &CloneProgress,
+    options: &DownloadOptions,
+) -> Result<Checkout<'a>, DownloaderError> {
+    let mut urls: Vec<&RepoUrl> = iter::once(&program.repository_url)
+        .chain(&program.mirror_urls)
+        .collect();
+    urls.sort_by_key(|url| {
+        !cache::clone_directory(&options.root, &program.source_url(url)).exists()
+    });
+
+    let mut last_error = None;
+    for (index, url) in urls.iter().enumerate() {
+        let source_url = program.source_url(url);
+        let clone = cache::clone_directory(&options.root, &source_url);
+        let lock = CacheLock::acquire(&clone, |holder| {
+            let holder = holder.map_or_else(String::new, |pid| format!(" (process {pid})"));
+            clone_progress.println(format!("Waiting for another download of '{url}'{holder}"));
+        })
+        .map_err(|error| DownloaderError::Cache { error })?;
+        // Tarballs and files are moved into place once complete, so only a
+        // clone can be left half updated.
+        if lock.interrupted() && program.source_type == SourceType::Git {
+            clone_progress.println(format!(
+                "A download of '{url}' was interrupted; restoring its cached clone"
+            ));
+            cache::restore_clone(&options.root, &clone)
+                .map_err(|error| DownloaderError::Cache { error })?;
+        }
+
+        let result = match program.source_type {
+            SourceType::Git => download_with_git(
+                url.as_str(),
+                clone_depth,
+                program.needs_submodules,
+                program.branch.as_deref(),
+                locked_commit,
+                clone_progress,
+                options,
+            ),
+            SourceType::Tarball | SourceType::HttpFile => {
+                download_source(&source_url, program, clone_progress, options)
+            }
+        };
+        match result {
+            Ok((directory, commit)) => {
+                return Ok(Checkout {
+                    url,
+                    directory,
+                    commit,
+                    _lock: lock,
+                });
+            }
+            Err(error) => {
+                if let Some(next_url) = urls.get(index + 1) {
+                    clone_progress.println(format!("{error}; trying '{next_url}'"));
+                }
+                last_error = Some(error);
+            }
+        }
+    }
+    Err(last_error.expect("Unreachable because a program always has a repository URL"))
+}
+
+/// Checks out the commit a repository is locked to, fetching the clone's
+/// full history first if the commit is not in it, such as in a shallow clone
+/// made after the repository moved on.
+///
+/// # Arguments
+///
+/// - `repository` - The cached clone.
+/// - `repository_url` - The URL the clone was made from.
+/// - `commit` - SHA of the locked commit.
+/// - `clone_progress` - The progress bar showing the fetch's progress.
+/// - `limits` - The timeout and bandwidth limit of the clone.
+/// - `options` - Options controlling the download; in offline mode, only
+///   commits already in the clone can be checked out.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::LockedCommit`] if the
+/// commit cannot be found or checked out.
+fn check_out_commit(
+    repository: &Repository,
+    repository_url: &str,
+    commit: &str,
+    clone_progress: &CloneProgress,
+    limits: &TransferLimits,
+    options: &DownloadOptions,
+) -> Result<(), DownloaderError> {
+    let locked_commit_error = |error| DownloaderError::LockedCommit {
+        repository_url: repository_url.to_string(),
+        commit: commit.to_string(),
+        error,
+    };
+    let oid = Oid::from_str(commit).map_err(locked_commit_error)?;
+    if repository.head().ok().and_then(|head| head.target()) == Some(oid) {
+        return Ok(());
+    }
+
+    if repository.find_commit(oid).is_err() && !options.offline {
+        if options.git_backend == GitBackend::Cli && git_cli::is_available() {
+            let path = repository.workdir().unwrap_or_else(|| repository.path());
+            git_cli::fetch_history(
+                repository_url,
+                path,
+                0,
+                clone_progress,
+                options,
+                limits.deadline(),
+            )?;
+        } else {
+            fetch_history(
+                repository,
+                0,
+                fetch_options(clone_progress, limits, options),
+            )
+            .map_err(|error| DownloaderError::FetchRepository {
+                repository_url: repository_url.to_string(),
+                error,
+            })?;
+        }
+    }
+
+    let object = repository
+        .find_commit(oid)
+        .map_err(locked_commit_error)?
+        .into_object();
+    repository
+        .checkout_tree(&object, Some(CheckoutBuilder::new().force()))
+        .and_then(|()| repository.set_head_detached(oid))
+        .map_err(locked_commit_error)
+}
+
+/// Fetches a branch of a clone into its remote-tracking branch.
+///
+/// # Arguments
+///
+/// - `repository` - The clone.
+/// - `branch` - The branch to fetch.
+/// - `fetch_options` - Options for the fetch, from [`fetch_options`].
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`git2::Error`] on failure.
+fn fetch_branch(
+    repository: &Repository,
+    branch: &str,
+    mut fetch_options: FetchOptions,
+) -> Result<(), git2::Error> {
+    repository.find_remote("origin")?.fetch(
+        &[lock::branch_refspec(Some(branch))],
+        Some(&mut fetch_options),
+        None,
+    )
+}
+
+/// Finds the commit at the head of a clone's remote-tracking branch.
+///
+/// # Returns
+///
+/// SHA of the commit, or a [`git2::Error`] if the clone lacks the branch.
+fn branch_head(repository: &Repository, branch: &str) -> Result<String, git2::Error> {
+    let commit = repository
+        .find_reference(&format!("refs/remotes/origin/{branch}"))?
+        .peel_to_commit()?;
+    Ok(commit.id().to_string())
+}
+
+/// Creates the options shared by every clone and fetch.
+///
+/// # Arguments
+///
+/// - `clone_progress` - The progress bar tracking the transfer.
+/// - `limits` - The timeout and bandwidth limit of the clone.
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// [`FetchOptions`] that report progress, enforce the clone's limits, and
+/// connect through the configured proxy.
+fn fetch_options<'a>(
+    clone_progress: &'a CloneProgress,
+    limits: &'a TransferLimits,
+    options: &'a DownloadOptions,
+) -> FetchOptions<'a> {
+    let mut remote_callbacks = RemoteCallbacks::new();
+    remote_callbacks.transfer_progress(|progress: git2::Progress| {
+        limits.check(progress.received_bytes()) && clone_progress.update(progress)
+    });
+
+    let mut fetch_options = FetchOptions::new();
+    fetch_options
+        .remote_callbacks(remote_callbacks)
+        .proxy_options(proxy_options(options));
+    fetch_options
+}
+
+/// Creates the proxy settings of a connection to a remote.
+///
+/// # Arguments
+///
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// [`ProxyOptions`] connecting through the configured proxy.
+pub(crate) fn proxy_options(options: &DownloadOptions) -> ProxyOptions<'_> {
+    // Without an explicit proxy, libgit2 detects one from the `http.proxy`
+    // git configuration or the `HTTP_PROXY` and `HTTPS_PROXY` environment
+    // variables.
+    let mut proxy_options = ProxyOptions::new();
+    match &options.proxy {
+        Some(proxy) => proxy_options.url(proxy),
+        None => proxy_options.auto(),
+    };
+    proxy_options
+}
+
+/// Initializes and updates the submodules of a repository, recursively.
+///
+/// Submodules are cloned with their full history, since the commit a
+/// repository pins may not be the latest commit of the submodule.
+///
+/// # Arguments
+///
+/// - `repository` - The repository whose submodules to update.
+/// - `clone_progress` - The progress bar showing the clones' progress.
+/// - `limits` - The timeout and bandwidth limit of the repository's clone.
+/// - `options` - Options controlling the download; in offline mode, only
+///   submodules that are already cloned can be updated.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`git2::Error`] on failure.
+fn update_submodules(
+    repository: &Repository,
+    clone_progress: &CloneProgress,
+    limits: &TransferLimits,
+    options: &DownloadOptions,
+) -> Result<(), git2::Error> {
+    for mut submodule in repository.submodules()? {
+        let mut update_options = SubmoduleUpdateOptions::new();
+        update_options.fetch(fetch_options(clone_progress, limits, options));
+        update_options.allow_fetch(!options.offline);
+        submodule.update(true, Some(&mut update_options))?;
+
+        update_submodules(&submodule.open()?, clone_progress, limits, options)?;
+    }
+    Ok(())
+}
+
+/// Counts the commits along the first parents of a clone's `HEAD`, which for
+/// a shallow clone is the depth it was cloned or last deepened to.
+fn history_depth(repository: &Repository) -> u64 {
+    let Ok(mut revwalk) = repository.revwalk() else {
+        return 0;
+    };
+    if revwalk.push_head().is_err() || revwalk.simplify_first_parent().is_err() {
+        return 0;
+    }
+    revwalk.take_while(Result::is_ok).count() as u64
+}
+
+/// Fetches the history of a clone, deepening it if it is shallow, along with
+/// any commits added upstream since it was made.
+///
+/// # Arguments
+///
+/// - `repository` - The clone.
+/// - `depth` - Number of commits of history to deepen a shallow clone to,
+///   where 0 fetches the full history.
+/// - `fetch_options` - Options for the fetch, from [`fetch_options`].
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`git2::Error`] on failure.
+fn fetch_history(
+    repository: &Repository,
+    depth: u64,
+    mut fetch_options: FetchOptions,
+) -> Result<(), git2::Error> {
+    // libgit2 treats the maximum depth as a request to unshallow the clone.
+    fetch_options.depth(
+        i32::try_from(depth)
+            .ok()
+            .filter(|depth| *depth > 0)
+            .unwrap_or(i32::MAX),
+    );
+
+    repository
+        .find_remote("origin")?
+        .fetch::<&str>(&[], Some(&mut fetch_options), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that a pair is only promoted into a directory directly within
+    /// `program_pairs/`, and that a directory elsewhere is left alone.
+    #[test]
+    fn test_promote() {
+        let root = tempfile::tempdir().unwrap();
+        let pairs_directory = root.path().join("program_pairs");
+        let staging_path = pairs_directory.join(".staging/hello");
+        fs::create_dir_all(staging_path.join("c-program")).unwrap();
+        let victim = root.path().join("victim");
+        fs::create_dir(&victim).unwrap();
+        fs::write(victim.join("keep.txt"), "Keep me\n").unwrap();
+
+        for pair_directory in [
+            pairs_directory.join("../victim"),
+            victim.clone(),
+            pairs_directory.clone(),
+        ] {
+            assert!(
+                matches!(
+                    promote(&staging_path, &pair_directory, &pairs_directory),
+                    Err(DownloaderError::PathEscape { .. })
+                ),
+                "{}",
+                pair_directory.display()
+            );
+        }
+        assert!(victim.join("keep.txt").is_file());
+        assert!(staging_path.is_dir());
+
+        let pair_directory = pairs_directory.join("hello");
+        promote(&staging_path, &pair_directory, &pairs_directory).unwrap();
+        assert!(pair_directory.join("c-program").is_dir());
+        assert!(!staging_path.exists());
+    }
+
+    /// Tests that a pair's directory is only named by a single plain path
+    /// component, so no program name leads out of `program_pairs/`.
+    #[test]
+    fn test_pair_path() {
+        let directory = Path::new("corpus/program_pairs");
+        assert_eq!(
+            directory.join("c++filt"),
+            pair_path(directory, "c++filt").unwrap()
+        );
+        for program_name in ["../victim", "..", ".", "a/b", "/tmp/victim", ""] {
+            assert!(
+                matches!(
+                    pair_path(directory, program_name),
+                    Err(DownloaderError::PathEscape { .. })
+                ),
+                "{program_name}"
+            );
+        }
+    }
+
+    /// Tests that bandwidth limits accept sizes with binary units, with or
+    /// without `/s`, and reject anything else.
+    #[test]
+    fn test_parse_bandwidth() {
+        for (rate, expected) in [
+            ("500K", 500 * 1024),
+            ("500KiB/s", 500 * 1024),
+            ("1G", 1024 * 1024 * 1024),
+            ("2M/s", 2 * 1024 * 1024),
+            ("1.5M", 3 * 512 * 1024),
+            ("4096", 4096),
+            (" 10 KB ", 10 * 1024),
+            ("0", 0),
+        ] {
+            assert_eq!(Ok(expected), parse_bandwidth(rate), "{rate}");
+        }
+        for rate in ["", "fast", "M", "-1K", "10X", "10/s/s", "1e3"] {
+            assert!(parse_bandwidth(rate).is_err(), "{rate}");
+        }
+    }
+
+    /// Tests that a transfer is slowed to the bandwidth limit, that a limit
+    /// of zero does not slow it, and that the pause ends at the timeout.
+    #[test]
+    fn test_transfer_limits() {
+        let limits = |timeout, max_bandwidth| TransferLimits {
+            started: Instant::now(),
+            timeout,
+            max_bandwidth,
+        };
+
+        // 20 KiB at 100 KiB/s takes a fifth of a second.
+        let started = Instant::now();
+        assert!(limits(None, Some(100 * 1024)).check(20 * 1024));
+        assert!(started.elapsed() >= Duration::from_millis(150));
+
+        let started = Instant::now();
+        assert!(limits(None, Some(0)).check(usize::MAX));
+        assert!(limits(None, None).check(usize::MAX));
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        // A megabyte at a byte per second would take days, but the clone
+        // times out first.
+        let started = Instant::now();
+        assert!(!limits(Some(Duration::from_millis(50)), Some(1)).check(1024 * 1024));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,180 @@
+//! # Async Downloader
+//!
+//! This module offers the download pipeline to async code, such as a web
+//! service serving the corpus, when the `async` feature is enabled.  Program
+//! pairs are downloaded concurrently as tokio tasks.  Cloning and copying
+//! block, whether through libgit2 or the `git` command, so each pair runs on
+//! tokio's blocking thread pool rather than on the runtime's workers.
+//!
+//! Pairs that share a repository also share its cached clone, so they are
+//! downloaded one at a time.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    panic,
+    sync::{Arc, mpsc::Sender},
+};
+
+use tokio::{
+    sync::{Mutex, Semaphore},
+    task::{self, JoinError, JoinSet},
+};
+
+use crate::corpus::{
+    downloader::{self, DownloadEvent, DownloadOptions},
+    effects::FileSystem,
+    errors::DownloaderError,
+    lock::CorpusLock,
+    progress::DownloadProgress,
+    schema::ProgramPair,
+    skiplist::SkipList,
+    space, timings, utils,
+};
+
+/// Reads all metadata files in `metadata/` and downloads all program pairs,
+/// several at a time, without blocking the async runtime.
+///
+/// Must be called within a tokio runtime.  Dropping the returned future stops
+/// new pairs from starting, but pairs already downloading run to completion.
+///
+/// # Arguments
+///
+/// - `demo` - True to download only the program pairs in `metadata/demo/`.
+/// - `options` - Options controlling the download.
+/// - `concurrency` - The most program pairs downloaded at once; at least one
+///   pair is always downloaded.
+/// - `events` - Receives the progress of the download, or `None` to draw
+///   progress bars.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`DownloaderError`] if any step fails,
+/// including [`DownloaderError::Incomplete`] if some pairs fail to download.
+pub async fn download_program_pairs(
+    demo: bool,
+    options: DownloadOptions,
+    concurrency: usize,
+    events: Option<Sender<DownloadEvent>>,
+) -> Result<(), DownloaderError> {
+    let options = Arc::new(options);
+    let setup_options = Arc::clone(&options);
+    let (metadata, invalid_files, skiplist, lock) =
+        run_blocking(move || -> Result<_, DownloaderError> {
+            let options = &setup_options;
+            downloader::configure_git(options)?;
+            let storage = FileSystem::new(&options.root);
+            let (metadata, invalid_files) = downloader::plan_download(demo, options, &storage)?;
+            let skiplist = SkipList::load_from(&storage)
+                .map_err(|error| DownloaderError::SkipList { error })?;
+            space::check(
+                metadata.iter().flat_map(|metadata| &metadata.pairs),
+                &skiplist,
+                options,
+            )?;
+            let lock =
+                CorpusLock::load(&options.root).map_err(|error| DownloaderError::Lock { error })?;
+            Ok((metadata, invalid_files, skiplist, lock))
+        })
+        .await?;
+
+    let pairs: Vec<ProgramPair> = metadata
+        .into_iter()
+        .flat_map(|metadata| metadata.pairs)
+        .collect();
+    // Pairs start in the same order as in the blocking download.
+    let pairs: Vec<ProgramPair> = downloader::order_pairs(&pairs, options.shuffle)
+        .into_iter()
+        .cloned()
+        .collect();
+    let total_pairs = pairs.len();
+    let progress = Arc::new(match events {
+        Some(events) => DownloadProgress::with_events(total_pairs as u64, events)?,
+        None => DownloadProgress::new(total_pairs as u64)?,
+    });
+    if let Some(seed) = options.shuffle {
+        progress.println(format!("Shuffling the program pairs with seed {seed}"));
+    }
+    // Offline downloads clone nothing.
+    if !options.offline {
+        progress.estimate(timings::estimate(&options.root, &pairs, &skiplist));
+    }
+    let skiplist = Arc::new(skiplist);
+    let lock = Arc::new(lock);
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+
+    // Each pair holds the mutexes of its repositories while it downloads.
+    // They are taken in URL order, so pairs sharing repositories cannot
+    // deadlock.
+    let mut repository_mutexes: BTreeMap<String, Arc<Mutex<()>>> = BTreeMap::new();
+    let mut tasks = JoinSet::new();
+    for pair in pairs {
+        let urls: BTreeSet<String> = pair
+            .programs()
+            .map(|program| utils::normalize_repository_url(program.repository_url.as_str()))
+            .collect();
+        let mutexes: Vec<Arc<Mutex<()>>> = urls
+            .into_iter()
+            .map(|url| Arc::clone(repository_mutexes.entry(url).or_default()))
+            .collect();
+        let semaphore = Arc::clone(&semaphore);
+        let skiplist = Arc::clone(&skiplist);
+        let progress = Arc::clone(&progress);
+        let options = Arc::clone(&options);
+        let lock = Arc::clone(&lock);
+        tasks.spawn(async move {
+            let mut guards = Vec::new();
+            for mutex in mutexes {
+                guards.push(mutex.lock_owned().await);
+            }
+            let _permit = semaphore.acquire_owned().await;
+            let program_name = pair.program_name.clone();
+            let downloaded = run_blocking(move || {
+                downloader::download_and_report(&pair, &skiplist, &progress, &options, &lock)
+            })
+            .await;
+            (program_name, downloaded)
+        });
+    }
+
+    // Failed pairs are sorted by name when reported.
+    let mut failed_pairs = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        let (program_name, downloaded) = unwrap_task(result);
+        if !downloaded {
+            failed_pairs.push(program_name);
+        }
+    }
+
+    run_blocking(move || {
+        downloader::finish(
+            &options.root,
+            &progress,
+            &lock,
+            failed_pairs,
+            invalid_files,
+            total_pairs,
+        )
+    })
+    .await
+}
+
+/// Runs blocking work on tokio's blocking thread pool.
+///
+/// # Returns
+///
+/// The work's result; a panic in the work is resumed in the caller.
+async fn run_blocking<T, F>(work: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    unwrap_task(task::spawn_blocking(work).await)
+}
+
+/// Returns the output of a finished task, resuming its panic if it panicked.
+fn unwrap_task<T>(result: Result<T, JoinError>) -> T {
+    match result {
+        Ok(output) => output,
+        Err(error) => panic::resume_unwind(error.into_panic()),
+    }
+}
@@ -0,0 +1,317 @@
+//! # Effects
+//!
+//! This module holds the traits through which parsing and download planning
+//! read files and fetch metadata, so that they do not touch the file system
+//! or git themselves: [`Storage`] reads and writes files, and [`Fetcher`]
+//! brings `metadata/` up to date with a metadata repository.  The downloader
+//! uses [`FileSystem`] and [`GitFetcher`], while [`MemoryStorage`] holds the
+//! files in memory, such as for tests or tools embedding the parser that
+//! keep metadata elsewhere than on disk.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::RwLock,
+};
+
+use crate::{
+    corpus::{downloader::DownloadOptions, errors::DownloaderError, remote_metadata},
+    paths,
+};
+
+/// Reads and writes the files of a corpus.
+///
+/// Paths are relative to the corpus root, or absolute.
+pub trait Storage: Sync {
+    /// Reads a file as UTF-8.
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Lists the paths of the files and directories in a directory, in no
+    /// particular order.
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Whether a file or directory exists at a path.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Whether a file exists at a path.
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// Writes a file, replacing it if it exists.  Its directory must exist.
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Creates a directory and any missing parents.
+    fn create_dir_all(&self, directory: &Path) -> io::Result<()>;
+}
+
+/// Brings `metadata/` up to date with a metadata repository.
+pub trait Fetcher: Sync {
+    /// Fetches a metadata repository and copies its metadata into
+    /// `metadata/`.
+    ///
+    /// # Arguments
+    ///
+    /// - `repository_url` - URL of the metadata repository.
+    /// - `options` - Options controlling the download.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or a [`DownloaderError`] if the
+    /// repository cannot be fetched or its files cannot be copied.
+    fn sync_metadata(
+        &self,
+        repository_url: &str,
+        options: &DownloadOptions,
+    ) -> Result<(), DownloaderError>;
+}
+
+/// The files on disk, relative to a corpus root.
+#[derive(Debug, Default)]
+pub struct FileSystem {
+    /// The corpus root, or an empty path for the current directory.
+    root: PathBuf,
+}
+
+impl FileSystem {
+    /// Creates storage reading and writing the files under a corpus root.
+    ///
+    /// # Arguments
+    ///
+    /// - `root` - The corpus root, or an empty path for the current
+    ///   directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileSystem { root: root.into() }
+    }
+
+    /// Resolves a path against the corpus root, with the paths under
+    /// `metadata/` resolved into the directory given with `--metadata-dir`.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        self.root.join(paths::metadata_path(path))
+    }
+}
+
+impl Storage for FileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(self.resolve(path))
+    }
+
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<PathBuf>> {
+        // Paths are listed relative to the root, as they were given.
+        self.resolve(directory)
+            .read_dir()?
+            .map(|entry| entry.map(|entry| directory.join(entry.file_name())))
+            .collect()
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.resolve(path).exists()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.resolve(path).is_file()
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(self.resolve(path), contents)
+    }
+
+    fn create_dir_all(&self, directory: &Path) -> io::Result<()> {
+        fs::create_dir_all(self.resolve(directory))
+    }
+}
+
+/// Fetches metadata repositories with git, through the clone cache.
+pub struct GitFetcher;
+
+impl Fetcher for GitFetcher {
+    fn sync_metadata(
+        &self,
+        repository_url: &str,
+        options: &DownloadOptions,
+    ) -> Result<(), DownloaderError> {
+        remote_metadata::sync(repository_url, options)
+    }
+}
+
+/// Files held in memory.
+///
+/// Directories exist once created or once a file is written into them.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    /// The contents of each file, keyed by normalized path.
+    files: RwLock<BTreeMap<PathBuf, Vec<u8>>>,
+    /// The directories created, keyed by normalized path.
+    directories: RwLock<BTreeSet<PathBuf>>,
+}
+
+impl MemoryStorage {
+    /// Creates storage holding the given files.
+    ///
+    /// # Arguments
+    ///
+    /// - `files` - The path and contents of each file.
+    pub fn with_files<P: AsRef<Path>, C: AsRef<[u8]>>(
+        files: impl IntoIterator<Item = (P, C)>,
+    ) -> Self {
+        let storage = MemoryStorage::default();
+        for (path, contents) in files {
+            let path = normalize(path.as_ref());
+            if let Some(parent) = path.parent() {
+                storage.add_directories(parent);
+            }
+            storage
+                .files
+                .write()
+                .unwrap_or_else(|error| error.into_inner())
+                .insert(path, contents.as_ref().to_vec());
+        }
+        storage
+    }
+
+    /// Records a directory and its parents as created.
+    fn add_directories(&self, directory: &Path) {
+        let mut directories = self
+            .directories
+            .write()
+            .unwrap_or_else(|error| error.into_inner());
+        for ancestor in directory.ancestors() {
+            if ancestor.as_os_str().is_empty() {
+                break;
+            }
+            directories.insert(ancestor.to_path_buf());
+        }
+    }
+
+    /// Whether a directory was created or holds files.
+    fn is_dir(&self, directory: &Path) -> bool {
+        directory.as_os_str().is_empty()
+            || self
+                .directories
+                .read()
+                .unwrap_or_else(|error| error.into_inner())
+                .contains(directory)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let files = self.files.read().unwrap_or_else(|error| error.into_inner());
+        let contents = files.get(&normalize(path)).ok_or_else(|| not_found(path))?;
+        String::from_utf8(contents.clone())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<PathBuf>> {
+        let normalized = normalize(directory);
+        if !self.is_dir(&normalized) {
+            return Err(not_found(directory));
+        }
+        let files = self.files.read().unwrap_or_else(|error| error.into_inner());
+        let directories = self
+            .directories
+            .read()
+            .unwrap_or_else(|error| error.into_inner());
+        // Paths are listed as they would be by joining names to the given
+        // directory.
+        let names: BTreeSet<_> = files
+            .keys()
+            .chain(directories.iter())
+            .filter(|path| path.parent() == Some(&normalized))
+            .filter_map(|path| path.file_name())
+            .collect();
+        Ok(names.into_iter().map(|name| directory.join(name)).collect())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        self.is_dir(&path) || self.is_file(&path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files
+            .read()
+            .unwrap_or_else(|error| error.into_inner())
+            .contains_key(&normalize(path))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let normalized = normalize(path);
+        if !normalized
+            .parent()
+            .is_some_and(|parent| self.is_dir(parent))
+        {
+            return Err(not_found(path));
+        }
+        self.files
+            .write()
+            .unwrap_or_else(|error| error.into_inner())
+            .insert(normalized, contents.to_vec());
+        Ok(())
+    }
+
+    fn create_dir_all(&self, directory: &Path) -> io::Result<()> {
+        self.add_directories(&normalize(directory));
+        Ok(())
+    }
+}
+
+/// Drops the `.` components of a path, so that `./metadata` and `metadata`
+/// name the same entry.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// The error of reading a path that does not exist.
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("'{}' does not exist", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that memory storage lists, reads, and writes files like a file
+    /// system.
+    #[test]
+    fn test_memory_storage() {
+        let storage = MemoryStorage::with_files([("metadata/demo/a.json", "{}")]);
+        assert!(storage.is_file(Path::new("./metadata/demo/a.json")));
+        assert!(storage.exists(Path::new("metadata/demo")));
+        assert!(!storage.is_file(Path::new("metadata/demo")));
+        assert_eq!(
+            vec![PathBuf::from("metadata/demo")],
+            storage.read_dir(Path::new("metadata")).unwrap()
+        );
+        assert!(storage.read_dir(Path::new("metadata/project")).is_err());
+        assert!(
+            storage
+                .write(Path::new("metadata/project/b.json"), b"")
+                .is_err()
+        );
+
+        storage
+            .create_dir_all(Path::new("metadata/project"))
+            .unwrap();
+        storage
+            .write(Path::new("metadata/project/b.json"), b"[]")
+            .unwrap();
+        assert_eq!(
+            "[]",
+            storage
+                .read_to_string(Path::new("metadata/project/b.json"))
+                .unwrap()
+        );
+        assert_eq!(
+            vec![
+                PathBuf::from("metadata/demo"),
+                PathBuf::from("metadata/project")
+            ],
+            storage.read_dir(Path::new("metadata")).unwrap()
+        );
+    }
+}
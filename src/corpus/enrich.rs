@@ -0,0 +1,403 @@
+//! # Repository Enrichment
+//!
+//! This module queries the GitHub and GitLab APIs for information about the
+//! repository of every program, such as its star count and last commit date,
+//! and stores it in `metadata/enriched/repositories.json`.  The metadata files
+//! themselves are left untouched.
+//!
+//! Set `GITHUB_TOKEN` or `GITLAB_TOKEN`, or the tokens in the `auth` section
+//! of `corpus.toml`, to authenticate and avoid the stricter rate limits of
+//! anonymous requests.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    corpus::{config::AuthConfig, errors::EnrichError, github::GitHubClient, parser, utils},
+    paths::{
+        self, DEMO_METADATA_DIRECTORY, ENRICHED_METADATA_FILE, INDIVIDUAL_METADATA_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// Information about a repository retrieved from its host's API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositoryInfo {
+    /// Number of stars the repository has.
+    pub stars: u64,
+    /// Date of the latest commit on the default branch, in RFC 3339 format.
+    pub last_commit_date: Option<String>,
+    /// Name of the default branch.
+    pub default_branch: Option<String>,
+    /// License reported by the host: an SPDX identifier on GitHub, or the
+    /// license key on GitLab.
+    pub license: Option<String>,
+    /// The language making up most of the repository.
+    pub primary_language: Option<String>,
+    /// Size of the repository in bytes as reported by the host, used to
+    /// estimate the disk space a clone needs.  GitLab only reports it to
+    /// authenticated members of the project.
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+/// Repository information for every program in the metadata.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EnrichedMetadata {
+    /// Repository information keyed by normalized repository URL.
+    pub repositories: BTreeMap<String, RepositoryInfo>,
+}
+
+impl EnrichedMetadata {
+    /// Loads the enriched metadata of a corpus, or empty metadata if
+    /// `enrich` has not been run yet.
+    ///
+    /// # Arguments
+    ///
+    /// - `root` - The corpus root, or an empty path for the current
+    ///   directory.
+    ///
+    /// # Returns
+    ///
+    /// The [`EnrichedMetadata`] on success and [`EnrichError`] on failure.
+    pub fn load(root: &Path) -> Result<Self, EnrichError> {
+        let path = &root.join(paths::metadata_path(ENRICHED_METADATA_FILE));
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path).map_err(|error| EnrichError::IoRead {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        serde_json::from_str(&contents).map_err(|error| EnrichError::Serialize { error })
+    }
+
+    /// Looks up the information about a repository.
+    ///
+    /// # Arguments
+    ///
+    /// - `repository_url` - The repository's URL, in any form that
+    ///   normalizes to the same URL.
+    pub fn get(&self, repository_url: &str) -> Option<&RepositoryInfo> {
+        self.repositories
+            .get(&utils::normalize_repository_url(repository_url))
+    }
+
+    /// Writes the enriched metadata to `metadata/enriched/repositories.json`.
+    fn save(&self) -> Result<(), EnrichError> {
+        let path = &paths::metadata_path(ENRICHED_METADATA_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|error| EnrichError::IoWrite {
+                path: parent.to_path_buf(),
+                error,
+            })?;
+        }
+        let contents =
+            serde_json::to_string_pretty(self).map_err(|error| EnrichError::Serialize { error })?;
+        fs::write(path, contents).map_err(|error| EnrichError::IoWrite {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+}
+
+/// A GitHub repository, as returned by `GET /repos/{owner}/{repo}`.
+#[derive(Deserialize)]
+struct GitHubRepository {
+    stargazers_count: u64,
+    /// Size of the repository in KiB.
+    size: u64,
+    default_branch: Option<String>,
+    language: Option<String>,
+    license: Option<GitHubLicense>,
+}
+
+/// The license of a GitHub repository.
+#[derive(Deserialize)]
+struct GitHubLicense {
+    spdx_id: Option<String>,
+}
+
+/// A commit, as returned by `GET /repos/{owner}/{repo}/commits`.
+#[derive(Deserialize)]
+struct GitHubCommit {
+    commit: GitHubCommitDetails,
+}
+
+/// The Git details of a GitHub commit.
+#[derive(Deserialize)]
+struct GitHubCommitDetails {
+    committer: Option<GitHubSignature>,
+}
+
+/// The committer of a GitHub commit.
+#[derive(Deserialize)]
+struct GitHubSignature {
+    date: String,
+}
+
+/// A GitLab project, as returned by
+/// `GET /projects/{id}?license=true&statistics=true`.
+#[derive(Deserialize)]
+struct GitLabProject {
+    star_count: u64,
+    default_branch: Option<String>,
+    license: Option<GitLabLicense>,
+    statistics: Option<GitLabStatistics>,
+}
+
+/// The statistics of a GitLab project.
+#[derive(Deserialize)]
+struct GitLabStatistics {
+    /// Size of the repository in bytes.
+    repository_size: u64,
+}
+
+/// The license of a GitLab project.
+#[derive(Deserialize)]
+struct GitLabLicense {
+    key: String,
+}
+
+/// A commit, as returned by `GET /projects/{id}/repository/commits`.
+#[derive(Deserialize)]
+struct GitLabCommit {
+    committed_date: String,
+}
+
+/// Queries the API of every repository referenced by the metadata and stores
+/// the results in `metadata/enriched/repositories.json`.
+///
+/// Repositories that cannot be queried, such as those hosted outside GitHub
+/// and GitLab, are reported and keep any information from earlier runs.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`EnrichError`] on failure, including
+/// [`EnrichError::Incomplete`] if any query failed; repositories on other
+/// hosts are skipped rather than failed.
+///
+/// # Arguments
+///
+/// - `auth` - The API tokens to authenticate with.
+pub fn enrich(auth: &AuthConfig) -> Result<(), EnrichError> {
+    let repository_urls = metadata_repository_urls();
+    let client = utils::http_client().map_err(|error| EnrichError::Client { error })?;
+    let github = GitHubClient::new(auth).map_err(|error| EnrichError::GitHub { error })?;
+
+    let mut enriched = EnrichedMetadata::load(Path::new(""))?;
+    let mut enriched_count = 0;
+    let mut failed_count = 0;
+    for (key, repository_url) in &repository_urls {
+        match fetch_repository_info(&client, &github, repository_url, auth) {
+            Ok(info) => {
+                println!("Enriched '{repository_url}' ({} stars)", info.stars);
+                enriched.repositories.insert(key.clone(), info);
+                enriched_count += 1;
+            }
+            Err(error @ EnrichError::UnsupportedHost { .. }) => {
+                eprintln!("Skipped '{repository_url}': {error}")
+            }
+            Err(error) => {
+                eprintln!("Failed to enrich '{repository_url}': {error}");
+                failed_count += 1;
+            }
+        }
+    }
+    if enriched_count > 0 {
+        enriched.save()?;
+    }
+
+    println!(
+        "Enriched {enriched_count} of {} repositories",
+        repository_urls.len()
+    );
+    if failed_count > 0 {
+        return Err(EnrichError::Incomplete {
+            failed: failed_count,
+            total: repository_urls.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Collects the repository URLs of every program in the metadata.
+///
+/// Metadata files that fail to parse are reported and skipped.
+///
+/// # Returns
+///
+/// A map from normalized repository URL to a URL as written in the metadata,
+/// so each repository is queried once.
+fn metadata_repository_urls() -> BTreeMap<String, String> {
+    let mut repository_urls = BTreeMap::new();
+    for directory in [
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+        DEMO_METADATA_DIRECTORY,
+    ] {
+        let metadata_files = match parser::parse_directory(Path::new(directory)) {
+            Ok(metadata_files) => metadata_files,
+            Err(error) => {
+                eprintln!("Failed to read '{directory}': {error}");
+                continue;
+            }
+        };
+        for (path, metadata) in metadata_files {
+            match metadata {
+                Ok(metadata) => {
+                    for pair in &metadata.pairs {
+                        for program in pair.programs() {
+                            repository_urls
+                                .entry(utils::normalize_repository_url(
+                                    program.repository_url.as_str(),
+                                ))
+                                .or_insert_with(|| program.repository_url.to_string());
+                        }
+                    }
+                }
+                Err(error) => eprintln!("Failed to parse '{}': {}", path.display(), error),
+            }
+        }
+    }
+    repository_urls
+}
+
+/// Queries the API of a repository's host.
+///
+/// # Arguments
+///
+/// - `client` - The HTTP client used for GitLab requests.
+/// - `github` - The GitHub API client.
+/// - `repository_url` - The repository's URL.
+/// - `auth` - The API tokens to authenticate with.
+///
+/// # Returns
+///
+/// The [`RepositoryInfo`] on success, or [`EnrichError`] if the host is not
+/// supported or a request fails.
+fn fetch_repository_info(
+    client: &Client,
+    github: &GitHubClient,
+    repository_url: &str,
+    auth: &AuthConfig,
+) -> Result<RepositoryInfo, EnrichError> {
+    let normalized = utils::normalize_repository_url(repository_url);
+    let (host, path) = normalized
+        .split_once('/')
+        .ok_or_else(|| EnrichError::UnsupportedHost {
+            repository_url: repository_url.to_string(),
+        })?;
+
+    if host == "github.com" {
+        fetch_github(github, path)
+    } else if host.contains("gitlab") {
+        fetch_gitlab(client, host, path, auth.gitlab_token())
+    } else {
+        Err(EnrichError::UnsupportedHost {
+            repository_url: repository_url.to_string(),
+        })
+    }
+}
+
+/// Queries the GitHub API for the repository at `owner/repo`.
+fn fetch_github(github: &GitHubClient, path: &str) -> Result<RepositoryInfo, EnrichError> {
+    let github_error = |error| EnrichError::GitHub { error };
+    let endpoint = format!("/repos/{path}");
+    let repository: GitHubRepository = github.get(&endpoint, &[]).map_err(github_error)?;
+    let commits: Vec<GitHubCommit> = github
+        .get(&format!("{endpoint}/commits"), &[("per_page", "1")])
+        .map_err(github_error)?;
+
+    Ok(RepositoryInfo {
+        stars: repository.stargazers_count,
+        last_commit_date: commits
+            .into_iter()
+            .next()
+            .and_then(|commit| commit.commit.committer)
+            .map(|committer| committer.date),
+        default_branch: repository.default_branch,
+        // GitHub reports licenses it cannot identify as "NOASSERTION".
+        license: repository
+            .license
+            .and_then(|license| license.spdx_id)
+            .filter(|spdx_id| spdx_id != "NOASSERTION"),
+        primary_language: repository.language,
+        size: Some(repository.size * 1024),
+    })
+}
+
+/// Queries the GitLab API of `host` for the project at `path`, with `token`
+/// if one is given.
+fn fetch_gitlab(
+    client: &Client,
+    host: &str,
+    path: &str,
+    token: Option<String>,
+) -> Result<RepositoryInfo, EnrichError> {
+    let authorize = |request: RequestBuilder| match &token {
+        Some(token) => request.header("PRIVATE-TOKEN", token),
+        None => request,
+    };
+
+    let base_url = format!(
+        "https://{host}/api/v4/projects/{}",
+        path.replace('/', "%2F")
+    );
+
+    let project_url = format!("{base_url}?license=true&statistics=true");
+    let project: GitLabProject = get_json(authorize(client.get(&project_url)), &project_url)?;
+
+    let commits_url = format!("{base_url}/repository/commits?per_page=1");
+    let commits: Vec<GitLabCommit> = get_json(authorize(client.get(&commits_url)), &commits_url)?;
+
+    // GitLab reports the percentage of the repository in each language.
+    let languages_url = format!("{base_url}/languages");
+    let languages: HashMap<String, f64> =
+        get_json(authorize(client.get(&languages_url)), &languages_url)?;
+
+    Ok(RepositoryInfo {
+        stars: project.star_count,
+        last_commit_date: commits
+            .into_iter()
+            .next()
+            .map(|commit| commit.committed_date),
+        default_branch: project.default_branch,
+        license: project.license.map(|license| license.key),
+        primary_language: languages
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(language, _)| language),
+        size: project
+            .statistics
+            .map(|statistics| statistics.repository_size),
+    })
+}
+
+/// Sends a request and deserializes its JSON response.
+///
+/// # Arguments
+///
+/// - `request` - The request to send.
+/// - `url` - The requested URL, used in error messages.
+///
+/// # Returns
+///
+/// The deserialized response on success, or [`EnrichError::Request`] if the
+/// request fails, returns an error status, or returns unexpected JSON.
+fn get_json<T: DeserializeOwned>(request: RequestBuilder, url: &str) -> Result<T, EnrichError> {
+    request
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json())
+        .map_err(|error| EnrichError::Request {
+            url: url.to_string(),
+            error,
+        })
+}
@@ -2,12 +2,15 @@
 //!
 //! This module defines custom error types used throughout the [`corpus`] module.
 
-use std::{io, path::PathBuf};
+use std::{io, path::PathBuf, time::Duration};
 
 use thiserror;
 
+use crate::corpus::utils;
+
 /// Errors that occur when a metadata file is being parsed.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum ParserError {
     /// Failed to read a file or directory.
     #[error("Failed to read '{path}': {error}")]
@@ -27,20 +30,220 @@ pub enum ParserError {
         error: serde_json::Error,
     },
 
-    /// Failed to serialize some Rust struct to a JSON value.
-    #[error("Failed to deserialize to JSON: {error}")]
+    /// Metadata values are malformed in ways the JSON schema cannot express.
+    #[error("Invalid metadata:{}", format_issues(.issues))]
+    InvalidValue {
+        /// Every malformed value found in the metadata.
+        issues: Vec<ValueIssue>,
+    },
+
+    /// Failed to build a validator from the JSON schema.
+    #[error("Invalid metadata schema: {error}")]
+    Schema {
+        /// The underlying `jsonschema::ValidationError`.
+        /// Type string because `ValidationError` requires lifetimes.
+        error: String,
+    },
+
+    /// Metadata violates the JSON schema.
+    #[error("Failed to validate metadata:{}", format_issues(.issues))]
+    Validation {
+        /// Every violation found in the metadata.
+        issues: Vec<ValidationIssue>,
+    },
+}
+
+/// Errors that occur when a URL or path in metadata is malformed.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum SchemaError {
+    /// A repository or documentation URL is malformed.
+    #[error("Invalid URL '{url}': {reason}")]
+    InvalidUrl {
+        /// The malformed URL.
+        url: String,
+        /// Why the URL is malformed.
+        reason: &'static str,
+    },
+
+    /// A source or exclude path is empty, absolute, or leaves the repository.
+    #[error("Invalid source path '{path}': {reason}")]
+    InvalidSourcePath {
+        /// The malformed path.
+        path: String,
+        /// Why the path is malformed.
+        reason: &'static str,
+    },
+
+    /// A function mapping's line range ends before it starts.
+    #[error("Invalid line range {start_line}-{end_line} of function '{function}'")]
+    ReversedLineRange {
+        /// Name of the function.
+        function: String,
+        /// The range's first line.
+        start_line: u64,
+        /// The range's last line.
+        end_line: u64,
+    },
+
+    /// A tarball or file source has no checksum to verify it with.
+    #[error("A '{source_type}' source needs a checksum")]
+    MissingChecksum {
+        /// The program's `source_type`.
+        source_type: &'static str,
+    },
+
+    /// The `release_tag` and `asset` of a program do not name a release
+    /// asset.
+    #[error("Invalid release asset: {reason}")]
+    InvalidRelease {
+        /// Why the release asset is invalid.
+        reason: &'static str,
+    },
+
+    /// The `language` of a program does not belong on its side of the pair.
+    #[error("Invalid language '{language}' for the {side}: expected {expected}")]
+    InvalidLanguage {
+        /// The language given.
+        language: &'static str,
+        /// Which program of the pair, "C program" or "Rust program".
+        side: &'static str,
+        /// The languages allowed on that side.
+        expected: &'static str,
+    },
+
+    /// The label of a pair's Rust variant is malformed or repeated.
+    #[error("Invalid Rust variant '{label}': {reason}")]
+    InvalidVariant {
+        /// The variant's label.
+        label: String,
+        /// Why the label is invalid.
+        reason: &'static str,
+    },
+
+    /// A checksum is not a SHA-256 in hexadecimal.
+    #[error("Invalid checksum '{checksum}': expected 64 hexadecimal digits")]
+    InvalidChecksum {
+        /// The malformed checksum.
+        checksum: String,
+    },
+
+    /// A program name cannot name its pair's directory under
+    /// `program_pairs/`, such as because it holds a `/` or starts with `.`.
+    #[error(
+        "Invalid program name '{program_name}': use letters, digits, '-', '_', '.', and '+', \
+         not starting with '.'"
+    )]
+    InvalidProgramName {
+        /// The invalid program name.
+        program_name: String,
+    },
+
+    /// A branch is not a valid git branch name, or is set on a source that
+    /// is not a git repository.
+    #[error("Invalid branch '{branch}': {reason}")]
+    InvalidBranch {
+        /// The invalid branch name.
+        branch: String,
+        /// Why the branch is invalid.
+        reason: &'static str,
+    },
+
+    /// An output directory is not a plain directory name, or both programs
+    /// of a pair would share it.
+    #[error("Invalid output directory '{directory}': {reason}")]
+    InvalidOutputDirectory {
+        /// The invalid directory name.
+        directory: String,
+        /// Why the directory name is invalid.
+        reason: &'static str,
+    },
+}
+
+/// One violation of the JSON schema by a metadata file.
+#[derive(Debug, serde::Serialize)]
+pub struct ValidationIssue {
+    /// JSON pointer to the offending value, such as `/pairs/0/program_name`.
+    pub instance_path: String,
+    /// JSON pointer to the violated constraint within the schema.
+    pub schema_path: String,
+    /// The violated schema keyword, such as `required` or `type`.
+    pub constraint: String,
+    /// The offending value.
+    pub value: serde_json::Value,
+    /// A description of the violation.
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let instance_path = match self.instance_path.as_str() {
+            "" => "/",
+            path => path,
+        };
+        write!(f, "{instance_path}: {} [{}]", self.message, self.constraint)
+    }
+}
+
+/// A metadata value malformed in a way the JSON schema cannot express.
+#[derive(Debug)]
+pub struct ValueIssue {
+    /// The program pair with the malformed value, as written in the
+    /// metadata.
+    pub program_name: String,
+    /// The malformed value.
+    pub error: SchemaError,
+}
+
+impl std::fmt::Display for ValueIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}': {}", self.program_name, self.error)
+    }
+}
+
+/// Formats issues one per line, for [`ParserError::Validation`] and
+/// [`ParserError::InvalidValue`].
+fn format_issues(issues: &[impl std::fmt::Display]) -> String {
+    issues.iter().map(|issue| format!("\n  {issue}")).collect()
+}
+
+/// Errors that occur when metadata is written to a file.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum WriterError {
+    /// The metadata violates the JSON schema.
+    #[error("Refusing to write invalid metadata: {error}")]
+    Invalid {
+        /// The underlying validation error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// The file to replace holds project metadata, which parsed metadata
+    /// cannot be written back as without flattening its
+    /// `project_information` into every pair.
+    #[error("Refusing to replace project metadata '{path}'; edit its JSON document instead")]
+    ProjectFile {
+        /// The project metadata file.
+        path: PathBuf,
+    },
+
+    /// Failed to serialize the metadata.
+    #[error("Failed to serialize to JSON: {error}")]
     Serialize {
         /// The underlying serialization error.
         #[source]
         error: serde_json::Error,
     },
 
-    /// Failed to validate some JSON schema.
-    #[error("Failed to deserialize to JSON: {error}")]
-    Validation {
-        /// The underlying `jsonschema::ValidationError`.
-        /// Type string because `ValidationError` requires lifetimes.
-        error: String,
+    /// Failed to write the metadata file.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
     },
 }
 
@@ -49,6 +252,7 @@ pub enum ParserError {
 /// Some of these relate to downloads from a git repository.  Others relate
 /// to local conditions such as file reading.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum DownloaderError {
     /// Failed to read a file or directory.
     #[error("Failed to read '{path}': {error}")]
@@ -70,6 +274,24 @@ pub enum DownloaderError {
         error: io::Error,
     },
 
+    /// Failed to write a file.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to serialize a manifest or other bookkeeping file.
+    #[error("Failed to serialize to JSON: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+
     /// Failed to copy a file or directory from `source` to `destination`.
     #[error("Failed to copy '{source}' to '{destination}': {error}")]
     IoCopy {
@@ -82,6 +304,16 @@ pub enum DownloaderError {
         error: io::Error,
     },
 
+    /// Failed to transform a placed source file.
+    #[error("Failed to transform '{path}': {error}")]
+    Transform {
+        /// The source file.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
     /// Generic I/O error.
     #[error("IO error: {0}")]
     Io(String),
@@ -96,7 +328,2567 @@ pub enum DownloaderError {
         error: git2::Error,
     },
 
+    /// Failed to fetch the full history of a cached shallow clone.
+    #[error("Failed to fetch history of repository '{repository_url}': {error}")]
+    FetchRepository {
+        /// The URL of the repository that failed to fetch.
+        repository_url: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// Failed to clone or fetch the repository given with `--metadata-repo`.
+    #[error("Failed to fetch metadata repository '{repository_url}': {error}")]
+    MetadataRepository {
+        /// The URL of the metadata repository.
+        repository_url: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// The metadata repository lacks the directory its metadata is read
+    /// from.
+    #[error("Metadata repository '{repository_url}' has no directory '{path}'")]
+    MetadataDirectory {
+        /// The URL of the metadata repository.
+        repository_url: String,
+        /// The directory, relative to the repository's root.
+        path: PathBuf,
+    },
+
+    /// Metadata files changed locally would be overwritten or removed by
+    /// the metadata repository's.
+    #[error(
+        "Metadata files changed since they were copied from '{repository_url}' would be \
+         overwritten: {paths}; pass --force to overwrite them"
+    )]
+    MetadataConflict {
+        /// The URL of the metadata repository.
+        repository_url: String,
+        /// The changed files within `metadata/`, comma-separated.
+        paths: String,
+    },
+
+    /// Failed to initialize or update the submodules of a repository.
+    #[error("Failed to update submodules of repository '{repository_url}': {error}")]
+    UpdateSubmodules {
+        /// The URL of the repository whose submodules failed to update.
+        repository_url: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// The system `git` binary failed to clone, fetch, or update a
+    /// repository.
+    #[error("`git {command}` failed for repository '{repository_url}': {message}")]
+    GitCommand {
+        /// The git subcommand that failed, such as `clone`.
+        command: String,
+        /// The URL of the repository.
+        repository_url: String,
+        /// The last error git printed, or its exit status.
+        message: String,
+    },
+
+    /// Cloning a repository took longer than the download's timeout, so it
+    /// was aborted.
+    #[error("Timed out after {}s cloning repository '{repository_url}'", timeout.as_secs())]
+    TimedOut {
+        /// The URL of the repository.
+        repository_url: String,
+        /// The timeout the clone exceeded.
+        timeout: Duration,
+    },
+
+    /// Failed to read a metadata directory.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory that could not be read.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to find the commit checked out in a clone.
+    #[error("Failed to resolve the checked-out commit of '{repository_url}': {error}")]
+    ResolveHead {
+        /// The URL of the repository.
+        repository_url: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// A source or destination path resolves outside of the directory it must
+    /// stay within, e.g. through `..` or a symbolic link.
+    #[error("Path '{path}' escapes '{root}'")]
+    PathEscape {
+        /// The offending path.
+        path: PathBuf,
+        /// The directory the path must stay within.
+        root: PathBuf,
+    },
+
+    /// Two files of a `source_paths` directory are flattened to the same
+    /// name, so one would overwrite the other.
+    #[error(
+        "Files '{first}' and '{second}' would both be flattened to '{name}'; set `flatten` to \
+         false to keep their directories"
+    )]
+    FlattenCollision {
+        /// The file found first.
+        first: PathBuf,
+        /// The file found second.
+        second: PathBuf,
+        /// The flattened name of both files.
+        name: String,
+    },
+
+    /// Failed to apply git settings, such as isolating the download from the
+    /// user's git configuration or setting server timeouts.
+    #[error("Failed to configure git: {error}")]
+    GitConfig {
+        /// The underlying error.
+        #[source]
+        error: GitOptionsError,
+    },
+
+    /// Failed to read or write `corpus.lock`.
+    #[error("Failed to update the lock file: {error}")]
+    Lock {
+        /// The underlying lock file error.
+        #[source]
+        error: LockError,
+    },
+
+    /// Failed to check out the commit a repository is locked to, such as
+    /// one that was removed upstream by a force-push.
+    #[error("Failed to check out locked commit '{commit}' of '{repository_url}': {error}")]
+    LockedCommit {
+        /// The URL of the repository.
+        repository_url: String,
+        /// SHA of the locked commit.
+        commit: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// A branch of a repository could not be fetched or found.
+    #[error("Failed to fetch branch '{branch}' of '{repository_url}': {error}")]
+    Branch {
+        /// The URL of the repository.
+        repository_url: String,
+        /// The branch.
+        branch: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// Failed to fetch a tarball or file source.
+    #[error("Failed to fetch '{url}': {message}")]
+    FetchSource {
+        /// The URL of the tarball or file.
+        url: String,
+        /// Why the fetch failed.
+        message: String,
+    },
+
+    /// A fetched tarball or file does not have the checksum in its metadata,
+    /// so it was discarded.
+    #[error("Checksum mismatch for '{url}': expected {expected}, found {actual}")]
+    ChecksumMismatch {
+        /// The URL of the tarball or file.
+        url: String,
+        /// SHA-256 given by the metadata.
+        expected: String,
+        /// SHA-256 of the fetched bytes.
+        actual: String,
+    },
+
+    /// Failed to extract a tarball.
+    #[error("Failed to extract '{url}': {error}")]
+    Extract {
+        /// The URL of the tarball.
+        url: String,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// A repository is not cached and offline mode forbids cloning it.
+    #[error("Repository '{repository_url}' is not cached and offline mode forbids cloning it")]
+    NotCached {
+        /// The URL of the repository that is not cached.
+        repository_url: String,
+    },
+
+    /// A cached clone is broken and offline mode forbids cloning it again.
+    #[error(
+        "Cached clone of '{repository_url}' is unhealthy ({problem}) and offline mode forbids \
+         cloning it again"
+    )]
+    UnhealthyClone {
+        /// The URL of the repository whose clone is broken.
+        repository_url: String,
+        /// What is wrong with the clone.
+        problem: String,
+    },
+
+    /// Failed to lock or restore a cached clone.
+    #[error("Failed to use the clone cache: {error}")]
+    Cache {
+        /// The underlying cache error.
+        #[source]
+        error: CacheError,
+    },
+
+    /// A patch from the metadata could not be applied to a program.
+    #[error("Failed to apply patch '{patch}': {error}")]
+    Patch {
+        /// The patch file.
+        patch: PathBuf,
+        /// Why the patch could not be applied.
+        #[source]
+        error: PatchError,
+    },
+
+    /// Files of a program pair were changed since it was downloaded, and the
+    /// conflict policy forbids overwriting them.
+    #[error("Files changed since the pair was downloaded would be overwritten: {paths}; {hint}")]
+    LocalChanges {
+        /// The changed files within the pair's directory, comma-separated.
+        paths: String,
+        /// How to download the pair anyway.
+        hint: &'static str,
+    },
+
+    /// The post-download hook failed in a program pair's directory.
+    #[error("Post-download hook '{command}' failed: {status}{output}")]
+    Hook {
+        /// The hook command.
+        command: String,
+        /// How the command failed, such as its exit status.
+        status: String,
+        /// The last lines the command printed, each on its own indented
+        /// line.
+        output: String,
+    },
+
+    /// The download is estimated to need more disk space than is available.
+    #[error(
+        "Downloading needs an estimated {} but only {} is available on the filesystem of \
+         '{path}'; free some space or pass --skip-space-check",
+        utils::format_size(*required),
+        utils::format_size(*available)
+    )]
+    InsufficientSpace {
+        /// The directory whose filesystem is too full.
+        path: PathBuf,
+        /// The estimated number of bytes the download needs.
+        required: u64,
+        /// The number of bytes available to this process.
+        available: u64,
+    },
+
+    /// Failed to query the available disk space.
+    #[error("Failed to query the available space of '{path}': {error}")]
+    FreeSpace {
+        /// The directory whose filesystem was queried.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to load the repository information recorded by `enrich`.
+    #[error("Failed to load enriched metadata: {error}")]
+    Enriched {
+        /// The underlying enrichment error.
+        #[source]
+        error: EnrichError,
+    },
+
+    /// Failed to load the skip list.
+    #[error("Failed to load skip list: {error}")]
+    SkipList {
+        /// The underlying skip list error.
+        #[source]
+        error: SkipListError,
+    },
+
+    /// Failed to load the profile to download.
+    #[error("Failed to load profile: {error}")]
+    Profile {
+        /// The underlying profile error.
+        #[source]
+        error: ProfileError,
+    },
+
+    /// The corpus root given to a [`Downloader`] is not a readable
+    /// directory.
+    ///
+    /// [`Downloader`]: crate::corpus::downloader::Downloader
+    #[error("Failed to read the corpus root '{path}': {error}")]
+    Root {
+        /// The corpus root.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to create the thread pool of a [`Downloader`].
+    ///
+    /// [`Downloader`]: crate::corpus::downloader::Downloader
+    #[error("Failed to create the thread pool: {error}")]
+    ThreadPool {
+        /// The underlying thread pool error.
+        #[source]
+        error: rayon::ThreadPoolBuildError,
+    },
+
+    /// The profile to download names pairs that are not in the metadata.
+    #[error("Profile '{profile}' names unknown pairs: {pairs}")]
+    UnknownProfilePairs {
+        /// Name of the profile.
+        profile: String,
+        /// The unknown pair names, separated by commas.
+        pairs: String,
+    },
+
     /// Failed to create a progress bar.
     #[error("Failed to create progress bar: {0}")]
     ProgressBar(String),
+
+    /// Some metadata files failed to parse in strict mode, so nothing was
+    /// downloaded.
+    #[error(
+        "Aborted because {} metadata files failed to parse:{}",
+        invalid_files.len(),
+        format_invalid_files(invalid_files)
+    )]
+    InvalidMetadata {
+        /// Metadata files that failed to parse and their errors.
+        invalid_files: Vec<(PathBuf, ParserError)>,
+    },
+
+    /// Some program pairs failed to download or some metadata files failed to
+    /// parse; the rest were downloaded.
+    #[error("{}", format_incomplete(failed_pairs, invalid_files, *total_pairs))]
+    Incomplete {
+        /// Names of the program pairs that failed to download.
+        failed_pairs: Vec<String>,
+        /// Metadata files that failed to parse and their errors.
+        invalid_files: Vec<(PathBuf, ParserError)>,
+        /// Number of program pairs in the metadata that parsed.
+        total_pairs: usize,
+    },
+}
+
+/// Formats the message of [`DownloaderError::Incomplete`].
+fn format_incomplete(
+    failed_pairs: &[String],
+    invalid_files: &[(PathBuf, ParserError)],
+    total_pairs: usize,
+) -> String {
+    let mut parts = Vec::new();
+    if !failed_pairs.is_empty() {
+        parts.push(format!(
+            "Failed to download {} of {total_pairs} program pairs: {}",
+            failed_pairs.len(),
+            failed_pairs.join(", ")
+        ));
+    }
+    if !invalid_files.is_empty() {
+        parts.push(format!(
+            "Failed to parse {} metadata files:{}",
+            invalid_files.len(),
+            format_invalid_files(invalid_files)
+        ));
+    }
+    parts.join("; ")
+}
+
+/// Formats metadata files that failed to parse, one per line, each followed
+/// by its error.
+fn format_invalid_files(invalid_files: &[(PathBuf, ParserError)]) -> String {
+    invalid_files
+        .iter()
+        .map(|(path, error)| {
+            let error = error.to_string().replace('\n', "\n  ");
+            format!("\n  {}: {error}", path.display())
+        })
+        .collect()
+}
+
+/// Errors that occur while computing statistics over downloaded program pairs.
+#[derive(thiserror::Error, Debug)]
+pub enum StatsError {
+    /// Failed to read a file or directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to load the repository information recorded by `enrich`.
+    #[error("Failed to load enriched metadata: {error}")]
+    Enriched {
+        /// The underlying enrichment error.
+        #[source]
+        error: EnrichError,
+    },
+
+    /// Failed to load the skip list.
+    #[error("Failed to load skip list: {error}")]
+    SkipList {
+        /// The underlying skip list error.
+        #[source]
+        error: SkipListError,
+    },
+}
+
+/// Errors that occur while comparing the C and Rust programs of a pair.
+#[derive(thiserror::Error, Debug)]
+pub enum DiffError {
+    /// The requested program pair has not been downloaded.
+    #[error("Program pair '{program_name}' not found in program_pairs/")]
+    PairNotFound {
+        /// Name of the program pair.
+        program_name: String,
+    },
+
+    /// Failed to serialize function mappings.
+    #[error("Failed to serialize to JSON: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+}
+
+/// Errors that occur while showing the source files of a program pair.
+#[derive(thiserror::Error, Debug)]
+pub enum ShowError {
+    /// The requested program pair has not been downloaded.
+    #[error("Program pair '{program_name}' not found in program_pairs/")]
+    PairNotFound {
+        /// Name of the program pair.
+        program_name: String,
+    },
+
+    /// The requested file is not a source file of the program pair.
+    #[error("Program pair '{program_name}' has no C or Rust file '{file}'")]
+    FileNotFound {
+        /// Name of the program pair.
+        program_name: String,
+        /// The requested file.
+        file: String,
+    },
+
+    /// The program pair has no C or Rust source files.
+    #[error("Program pair '{program_name}' has no C or Rust source files")]
+    NoSourceFiles {
+        /// Name of the program pair.
+        program_name: String,
+    },
+
+    /// Failed to read a source file.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+}
+
+/// Errors that occur while deleting downloaded program pairs and clones.
+#[derive(thiserror::Error, Debug)]
+pub enum DeleteError {
+    /// The requested program pair has not been downloaded.
+    #[error("Program pair '{program_name}' not found in program_pairs/")]
+    PairNotFound {
+        /// Name of the program pair.
+        program_name: String,
+    },
+
+    /// Failed to read a directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to delete a directory.
+    #[error("Failed to delete '{path}': {error}")]
+    IoDelete {
+        /// The directory that could not be deleted.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to ask the user for confirmation.
+    #[error("Failed to read confirmation: {error}")]
+    Prompt {
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+}
+
+/// Errors that occur while analyzing downloaded program pairs.
+#[derive(thiserror::Error, Debug)]
+pub enum AnalyzeError {
+    /// The requested program pair has not been downloaded.
+    #[error("Program pair '{program_name}' not found in program_pairs/")]
+    PairNotFound {
+        /// Name of the program pair.
+        program_name: String,
+    },
+
+    /// Failed to read a file or directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a file.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to serialize or deserialize analysis results.
+    #[error("Failed to serialize analysis results: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// Failed to tag a pair's manifest with its domain.
+    #[error("Failed to update manifest: {error}")]
+    Manifest {
+        /// The underlying error.
+        #[source]
+        error: DownloaderError,
+    },
+}
+
+/// Errors that occur while building downloaded program pairs.
+#[derive(thiserror::Error, Debug)]
+pub enum BuildError {
+    /// The requested program pair has not been downloaded.
+    #[error("Program pair '{program_name}' not found in program_pairs/")]
+    PairNotFound {
+        /// Name of the program pair.
+        program_name: String,
+    },
+
+    /// Failed to read a file or directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a file.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to serialize or deserialize build results.
+    #[error("Failed to serialize build results: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+}
+
+/// Errors that occur while splitting the corpus.
+#[derive(thiserror::Error, Debug)]
+pub enum SplitError {
+    /// The split fractions are not between 0 and 1 or add up to more than 1.
+    #[error(
+        "Invalid split fractions: train {train} and test {test} must be between 0 and 1 \
+         and add up to at most 1"
+    )]
+    InvalidFractions {
+        /// The fraction of pairs in the training set.
+        train: f64,
+        /// The fraction of pairs in the test set.
+        test: f64,
+    },
+
+    /// Failed to read a file or directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a file or directory.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to serialize a split manifest.
+    #[error("Failed to serialize split manifest: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+}
+
+/// Errors that occur while auditing the links in the metadata.
+#[derive(thiserror::Error, Debug)]
+pub enum AuditError {
+    /// Failed to create the HTTP client.
+    #[error("Failed to create HTTP client: {error}")]
+    Client {
+        /// The underlying reqwest error.
+        #[source]
+        error: reqwest::Error,
+    },
+
+    /// Failed to create the GitHub API client.
+    #[error("{error}")]
+    GitHub {
+        /// The underlying error.
+        #[source]
+        error: GitHubError,
+    },
+
+    /// Some links are dead or lead to renamed or archived repositories.
+    #[error(
+        "Found {dead} dead links, {renamed} renamed repositories, and {archived} archived \
+             repositories"
+    )]
+    Problems {
+        /// Number of links that cannot be reached.
+        dead: usize,
+        /// Number of repositories that moved to another URL.
+        renamed: usize,
+        /// Number of archived repositories.
+        archived: usize,
+    },
+}
+
+/// Errors that occur while comparing downloaded program pairs with their
+/// upstream repositories.
+#[derive(thiserror::Error, Debug)]
+pub enum OutdatedError {
+    /// Failed to list the downloaded program pairs.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Some repositories could not be looked up; the pairs copied from the
+    /// rest were checked.
+    #[error("Failed to look up {failed} of {total} repositories")]
+    Incomplete {
+        /// Number of repositories that could not be looked up.
+        failed: usize,
+        /// Number of repositories looked up.
+        total: usize,
+    },
+}
+
+/// Errors that occur while searching GitHub for candidate program pairs.
+#[derive(thiserror::Error, Debug)]
+pub enum DiscoverError {
+    /// A search request failed or returned an unexpected response.
+    #[error("{error}")]
+    GitHub {
+        /// The underlying error.
+        #[source]
+        error: GitHubError,
+    },
+
+    /// The searches for some tools failed; the candidates of the rest were
+    /// written.
+    #[error("Failed to search rewrites of {failed} of {total} tools")]
+    Incomplete {
+        /// Number of tools whose searches failed.
+        failed: usize,
+        /// Number of tools searched.
+        total: usize,
+    },
+
+    /// Failed to serialize the draft metadata.
+    #[error("Failed to serialize to JSON: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// Failed to write the drafts file or create its directory.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to read the existing drafts file.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// The output file exists but does not hold drafts, so it is not
+    /// overwritten.
+    #[error("Refusing to overwrite '{path}', which is not a drafts file: {message}")]
+    NotDrafts {
+        /// The output file.
+        path: PathBuf,
+        /// Why the file is not a drafts file.
+        message: String,
+    },
+}
+
+/// Errors that occur while renaming a program pair.
+#[derive(thiserror::Error, Debug)]
+pub enum RenameError {
+    /// The new name cannot be used as a pair's directory name.
+    #[error("'{name}' is not a valid pair name; use letters, digits, '-', '_', '.', and '+'")]
+    InvalidName {
+        /// The rejected name.
+        name: String,
+    },
+
+    /// No metadata file has a program pair with the name.
+    #[error("Program pair '{program_name}' not found in metadata/")]
+    PairNotFound {
+        /// Name of the program pair.
+        program_name: String,
+    },
+
+    /// A program pair with the new name already exists in the metadata or
+    /// in `program_pairs/`.
+    #[error("Program pair '{program_name}' already exists")]
+    AlreadyExists {
+        /// Name of the existing program pair.
+        program_name: String,
+    },
+
+    /// A metadata file or the skip list is not valid JSON.
+    #[error("Failed to deserialize '{path}': {error}")]
+    Deserialize {
+        /// The file that could not be deserialized.
+        path: PathBuf,
+        /// The underlying deserialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// A metadata file fails to parse.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata file.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// A metadata file cannot be written back once the pair is renamed,
+    /// such as because it would violate the schema.
+    #[error("'{path}' is invalid once renamed: {error}")]
+    Write {
+        /// The metadata file.
+        path: PathBuf,
+        /// The underlying writer error.
+        #[source]
+        error: WriterError,
+    },
+
+    /// Failed to update the manifest of the renamed pair.
+    #[error("Failed to update manifest: {error}")]
+    Manifest {
+        /// The underlying error.
+        #[source]
+        error: DownloaderError,
+    },
+
+    /// Failed to read a file or directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a file or move a directory.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+}
+
+/// Errors that occur while exporting the corpus as a dataset.
+#[derive(thiserror::Error, Debug)]
+pub enum ExportError {
+    /// Failed to parse a metadata directory.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory that could not be read.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to read a directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write the dataset.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to load the analyses recorded by `analyze`.
+    #[error("Failed to load the recorded analyses: {error}")]
+    Analysis {
+        /// The underlying analysis error.
+        #[source]
+        error: AnalyzeError,
+    },
+}
+
+/// Errors that occur while building the SQLite index of the corpus.
+#[cfg(feature = "index")]
+#[derive(thiserror::Error, Debug)]
+pub enum IndexError {
+    /// Failed to parse a metadata directory.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory that could not be read.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to load the skip list.
+    #[error("Failed to load skip list: {error}")]
+    SkipList {
+        /// The underlying skip list error.
+        #[source]
+        error: SkipListError,
+    },
+
+    /// Failed to compute statistics for the downloaded program pairs.
+    #[error("Failed to compute statistics: {error}")]
+    Stats {
+        /// The underlying statistics error.
+        #[source]
+        error: StatsError,
+    },
+
+    /// Failed to delete the temporary file of an earlier build.
+    #[error("Failed to delete '{path}': {error}")]
+    IoDelete {
+        /// The path that could not be deleted.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to move the new database into place.
+    #[error("Failed to replace '{path}': {error}")]
+    IoReplace {
+        /// The database file.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write to the database.
+    #[error("Failed to write database '{path}': {error}")]
+    Database {
+        /// The database file.
+        path: PathBuf,
+        /// The underlying SQLite error.
+        #[source]
+        error: rusqlite::Error,
+    },
+}
+
+/// Errors that occur while rendering a report of the corpus.
+#[derive(thiserror::Error, Debug)]
+pub enum SiteError {
+    /// Failed to parse a metadata directory.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory that could not be read.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to compute statistics for the downloaded program pairs.
+    #[error("Failed to compute statistics: {error}")]
+    Stats {
+        /// The underlying statistics error.
+        #[source]
+        error: StatsError,
+    },
+
+    /// The `pairs/` directory of the output holds a file that is not a page
+    /// of an earlier report, so it is not cleared.
+    #[error("'{path}' was not written by `report`; remove it or choose another --output directory")]
+    NotReport {
+        /// The file that is not a page of a report.
+        path: PathBuf,
+    },
+
+    /// Failed to delete the pages of an earlier report.
+    #[error("Failed to delete '{path}': {error}")]
+    IoDelete {
+        /// The path that could not be deleted.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a page of the report.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+}
+
+/// Errors that occur while recording or comparing corpus releases.
+#[derive(thiserror::Error, Debug)]
+pub enum ReleaseError {
+    /// The version is not of the form `X.Y` or `X.Y.Z`.
+    #[error("Invalid version '{version}'; expected X.Y or X.Y.Z")]
+    InvalidVersion {
+        /// The version given.
+        version: String,
+    },
+
+    /// A snapshot of the version was already recorded.
+    #[error("'{path}' already exists; releases are never overwritten")]
+    Exists {
+        /// The existing snapshot.
+        path: PathBuf,
+    },
+
+    /// Failed to parse a metadata directory or file.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory or file that could not be parsed.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to read a snapshot.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a snapshot or release notes.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to serialize a snapshot.
+    #[error("Failed to serialize snapshot: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// A snapshot is not valid JSON or is missing fields.
+    #[error("Failed to parse snapshot '{path}': {error}")]
+    Deserialize {
+        /// The snapshot.
+        path: PathBuf,
+        /// The underlying deserialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+}
+
+/// Errors that occur while managing the repository clone cache.
+#[derive(thiserror::Error, Debug)]
+pub enum CacheError {
+    /// Failed to parse the metadata needed to find referenced clones.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata file that could not be parsed.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to read a file or directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a file.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to serialize or deserialize the cache index.
+    #[error("Failed to serialize cache index: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// A size such as "10G" could not be parsed.
+    #[error("Invalid size '{size}'; expected a number with an optional unit such as 10G")]
+    InvalidSize {
+        /// The malformed size.
+        size: String,
+    },
+
+    /// No cached clone matches the given repository.
+    #[error("No cached clone matches '{repository}'")]
+    CloneNotFound {
+        /// The repository that was searched for.
+        repository: String,
+    },
+
+    /// Several cached clones match the given repository.
+    #[error("'{repository}' matches several cached clones: {candidates}")]
+    AmbiguousRepository {
+        /// The repository that was searched for.
+        repository: String,
+        /// The matching clones.
+        candidates: String,
+    },
+
+    /// A download is using the cached clone to remove.
+    #[error("'{path}' is in use by a download; try again once it finishes")]
+    CloneInUse {
+        /// The clone's directory.
+        path: PathBuf,
+    },
+
+    /// Cached clones failed their health check.
+    #[error("{count} cached clones are unhealthy; pass --remove to clone them again")]
+    UnhealthyClones {
+        /// The number of unhealthy clones.
+        count: usize,
+    },
+
+    /// Failed to delete a file or directory.
+    #[error("Failed to delete '{path}': {error}")]
+    IoDelete {
+        /// The path that could not be deleted.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+}
+
+/// Errors that occur while querying the GitHub API.
+#[derive(thiserror::Error, Debug)]
+pub enum GitHubError {
+    /// Failed to create the HTTP client.
+    #[error("Failed to create HTTP client: {error}")]
+    Client {
+        /// The underlying HTTP error.
+        #[source]
+        error: reqwest::Error,
+    },
+
+    /// The URL of an API endpoint is malformed, such as because
+    /// `GITHUB_API_URL` is.
+    #[error("Invalid GitHub API URL '{url}'; check GITHUB_API_URL")]
+    InvalidUrl {
+        /// The malformed URL.
+        url: String,
+    },
+
+    /// A request failed or returned an error status.
+    #[error("Request to '{url}' failed: {error}")]
+    Request {
+        /// The requested URL.
+        url: String,
+        /// The underlying HTTP error.
+        #[source]
+        error: reqwest::Error,
+    },
+
+    /// The rate limit was reached and does not reset soon.
+    #[error(
+        "Request to '{url}' was rate limited for {wait}s{}",
+        if *authenticated { "" } else { "; set GITHUB_TOKEN for a higher limit" }
+    )]
+    RateLimited {
+        /// The requested URL.
+        url: String,
+        /// Seconds until the rate limit resets.
+        wait: u64,
+        /// Whether the request was authenticated with a token.
+        authenticated: bool,
+    },
+
+    /// A response is not the JSON expected.
+    #[error("Unexpected response from '{url}': {error}")]
+    Deserialize {
+        /// The requested URL.
+        url: String,
+        /// The underlying deserialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+}
+
+/// Errors that occur while querying repository hosts for enriched metadata.
+#[derive(thiserror::Error, Debug)]
+pub enum EnrichError {
+    /// Failed to create the HTTP client.
+    #[error("Failed to create HTTP client: {error}")]
+    Client {
+        /// The underlying HTTP error.
+        #[source]
+        error: reqwest::Error,
+    },
+
+    /// An API request failed or returned an unexpected response.
+    #[error("Request to '{url}' failed: {error}")]
+    Request {
+        /// The requested URL.
+        url: String,
+        /// The underlying HTTP error.
+        #[source]
+        error: reqwest::Error,
+    },
+
+    /// A GitHub API request failed.
+    #[error("{error}")]
+    GitHub {
+        /// The underlying error.
+        #[source]
+        error: GitHubError,
+    },
+
+    /// Some repositories could not be queried; the rest were enriched.
+    #[error("Failed to enrich {failed} of {total} repositories")]
+    Incomplete {
+        /// Number of repositories whose queries failed.
+        failed: usize,
+        /// Number of repositories in the metadata.
+        total: usize,
+    },
+
+    /// The repository is not hosted on GitHub or GitLab.
+    #[error("Repository '{repository_url}' is not hosted on GitHub or GitLab")]
+    UnsupportedHost {
+        /// The repository's URL.
+        repository_url: String,
+    },
+
+    /// Failed to read a file.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a file or create a directory.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to serialize or deserialize the enriched metadata.
+    #[error("Failed to serialize enriched metadata: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+}
+
+/// Errors that occur when listing program pairs.
+#[derive(thiserror::Error, Debug)]
+pub enum ListError {
+    /// Failed to read a metadata directory.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory that could not be read.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to load the repository information recorded by `enrich`.
+    #[error("Failed to load enriched metadata: {error}")]
+    Enriched {
+        /// The underlying enrichment error.
+        #[source]
+        error: EnrichError,
+    },
+
+    /// Failed to load the skip list.
+    #[error("Failed to load skip list: {error}")]
+    SkipList {
+        /// The underlying skip list error.
+        #[source]
+        error: SkipListError,
+    },
+}
+
+/// Errors that occur when serving the corpus over HTTP.
+#[derive(thiserror::Error, Debug)]
+pub enum ServeError {
+    /// Failed to listen on the server's address.
+    #[error("Failed to listen on '{address}': {message}")]
+    Bind {
+        /// The address that could not be listened on.
+        address: String,
+        /// Why the address could not be listened on.
+        message: String,
+    },
+}
+
+/// Errors that occur while reading `metadata/skiplist.json`.
+#[derive(thiserror::Error, Debug)]
+pub enum SkipListError {
+    /// Failed to read the skip list.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// The skip list is not valid JSON or has unexpected fields.
+    #[error("Failed to parse '{path}': {error}")]
+    Deserialize {
+        /// The skip list's path.
+        path: PathBuf,
+        /// The underlying JSON error.
+        #[source]
+        error: serde_json::Error,
+    },
+}
+
+/// Errors that occur while loading a download profile.
+#[derive(thiserror::Error, Debug)]
+pub enum ProfileError {
+    /// Failed to read the profiles file.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// The profiles file is not valid JSON or has unexpected fields.
+    #[error("Failed to parse '{path}': {error}")]
+    Deserialize {
+        /// The profiles file's path.
+        path: PathBuf,
+        /// The underlying JSON error.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// No profile has the requested name.
+    #[error("Unknown profile '{name}'; available profiles: {available}")]
+    Unknown {
+        /// The requested name.
+        name: String,
+        /// The names of the available profiles, separated by commas.
+        available: String,
+    },
+
+    /// A profile lists a metadata file that does not exist.
+    #[error("Profile '{profile}' lists missing metadata file '{path}'")]
+    MissingFile {
+        /// Name of the profile.
+        profile: String,
+        /// The missing file.
+        path: PathBuf,
+    },
+
+    /// A profile lists a metadata file outside the directories metadata is
+    /// parsed from, so its pairs would never be downloaded.
+    #[error(
+        "Profile '{profile}' lists '{path}', which is not in metadata/project/, \
+         metadata/individual/, or metadata/demo/"
+    )]
+    OutsideMetadata {
+        /// Name of the profile.
+        profile: String,
+        /// The file outside the metadata directories.
+        path: PathBuf,
+    },
+
+    /// Failed to write the built-in demo metadata.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+}
+
+/// Errors that occur while applying a patch to a downloaded program.
+#[derive(thiserror::Error, Debug)]
+pub enum PatchError {
+    /// Failed to read the patch or a file it changes.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a patched file.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// The patch is not a unified diff.
+    #[error("Malformed patch at line {line}: {reason}")]
+    Malformed {
+        /// The line of the patch, counting from 1.
+        line: usize,
+        /// What is wrong with the line.
+        reason: &'static str,
+    },
+
+    /// A file the patch changes is not a relative path within the program's
+    /// directory.
+    #[error("Invalid path '{path}' in patch")]
+    InvalidPath {
+        /// The path as written in the patch.
+        path: String,
+    },
+
+    /// A hunk's context or removed lines are not in the file.
+    #[error("Hunk {hunk} of '{path}' does not apply")]
+    HunkMismatch {
+        /// The file the hunk changes.
+        path: String,
+        /// The hunk's number within the file's changes, counting from 1.
+        hunk: usize,
+    },
+
+    /// The patch creates a file that already exists, or changes one that
+    /// does not.
+    #[error("'{path}' {reason}")]
+    UnexpectedFile {
+        /// The file the patch creates or changes.
+        path: String,
+        /// Whether the file exists or is missing.
+        reason: &'static str,
+    },
+}
+
+/// Errors that occur while reading, writing, or refreshing `corpus.lock`.
+#[derive(thiserror::Error, Debug)]
+pub enum LockError {
+    /// Failed to read the lock file.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write the lock file.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// The lock file is not valid TOML or has unexpected fields.
+    #[error("Failed to parse '{path}': {error}")]
+    Deserialize {
+        /// The lock file's path.
+        path: PathBuf,
+        /// The underlying TOML error.
+        #[source]
+        error: toml::de::Error,
+    },
+
+    /// Failed to serialize the lock file.
+    #[error("Failed to serialize the lock file: {error}")]
+    Serialize {
+        /// The underlying TOML error.
+        #[source]
+        error: toml::ser::Error,
+    },
+
+    /// The lock file was written by a newer version of this tool.
+    #[error("Unsupported lock file version {version}")]
+    UnsupportedVersion {
+        /// The version of the lock file.
+        version: u32,
+    },
+
+    /// A repository to refresh is not in the lock file.
+    #[error("Repository '{url}' is not locked")]
+    NotLocked {
+        /// The repository's normalized URL.
+        url: String,
+    },
+
+    /// Failed to fetch the latest commit of a repository.
+    #[error("Failed to fetch '{url}': {error}")]
+    Fetch {
+        /// The repository's normalized URL.
+        url: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+}
+
+/// Errors that occur in the interactive browser.
+#[derive(thiserror::Error, Debug)]
+pub enum TuiError {
+    /// Failed to read a metadata directory.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory that could not be read.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to load the skip list.
+    #[error("Failed to load skip list: {error}")]
+    SkipList {
+        /// The underlying skip list error.
+        #[source]
+        error: SkipListError,
+    },
+
+    /// Failed to draw to or read from the terminal.
+    #[error("Terminal error: {error}")]
+    Terminal {
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+}
+
+/// Errors that occur when searching the corpus.
+#[derive(thiserror::Error, Debug)]
+pub enum SearchError {
+    /// Failed to read a metadata directory.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory that could not be read.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to read the downloaded program pairs.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+}
+
+/// Errors that occur while watching metadata for changes.
+#[derive(thiserror::Error, Debug)]
+pub enum WatchError {
+    /// Failed to read a metadata directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to parse the metadata directory.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory that could not be read.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Failed to watch the metadata directories.
+    #[error("Failed to watch metadata: {error}")]
+    Watch {
+        /// The underlying watcher error.
+        #[source]
+        error: notify::Error,
+    },
+}
+
+/// Errors that occur while looking up information for new metadata entries.
+#[derive(thiserror::Error, Debug)]
+pub enum MetadataError {
+    /// Failed to create the HTTP client.
+    #[error("Failed to create HTTP client: {error}")]
+    Client {
+        /// The underlying HTTP error.
+        #[source]
+        error: reqwest::Error,
+    },
+
+    /// An API request failed or returned an unexpected response.
+    #[error("Request to '{url}' failed: {error}")]
+    Request {
+        /// The requested URL.
+        url: String,
+        /// The underlying HTTP error.
+        #[source]
+        error: reqwest::Error,
+    },
+
+    /// No crate with the given name exists on crates.io.
+    #[error("Crate '{crate_name}' was not found on crates.io")]
+    CrateNotFound {
+        /// The requested crate name.
+        crate_name: String,
+    },
+
+    /// The crate does not list a repository, so it cannot be downloaded.
+    #[error("Crate '{crate_name}' does not list a repository URL")]
+    NoRepository {
+        /// The crate's name.
+        crate_name: String,
+    },
+
+    /// Failed to serialize a metadata entry.
+    #[error("Failed to serialize to JSON: {error}")]
+    Serialize {
+        /// The underlying serialization error.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// The name cannot be used as a metadata file name.
+    #[error("'{name}' is not a valid name; use letters, digits, '-', '_', '.', and '+'")]
+    InvalidName {
+        /// The rejected name.
+        name: String,
+    },
+
+    /// A metadata file with the name already exists.
+    #[error("'{path}' already exists")]
+    AlreadyExists {
+        /// The existing metadata file.
+        path: PathBuf,
+    },
+
+    /// The skeleton metadata does not conform to the schema, which has
+    /// changed without the skeleton being updated.
+    #[error("Skeleton metadata is invalid: {error}")]
+    Skeleton {
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// No Makefile in the repository builds the program.
+    #[error("No Makefile.am in '{repository}' builds '{program}'")]
+    ProgramNotFound {
+        /// The requested program.
+        program: String,
+        /// The repository that was searched.
+        repository: PathBuf,
+    },
+
+    /// The crate has no binary or library target with the given name.
+    #[error("No target '{target}' found in the crate at '{directory}'")]
+    CrateTargetNotFound {
+        /// The requested binary, or the default targets looked for.
+        target: String,
+        /// The crate's directory.
+        directory: PathBuf,
+    },
+
+    /// A crate's `Cargo.toml` could not be parsed.
+    #[error("Failed to parse '{path}': {error}")]
+    CargoManifest {
+        /// The `Cargo.toml` file.
+        path: PathBuf,
+        /// The underlying deserialization error.
+        #[source]
+        error: toml::de::Error,
+    },
+
+    /// The repository to discover sources in is neither a local checkout
+    /// nor a repository URL.
+    #[error("'{repository}' is neither a directory nor a repository URL")]
+    NotARepository {
+        /// The given repository.
+        repository: String,
+    },
+
+    /// Failed to clone the repository to discover sources in.
+    #[error("Failed to clone '{repository_url}': {error}")]
+    Clone {
+        /// The repository's URL.
+        repository_url: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// Failed to lock or record the use of a cached clone.
+    #[error("Failed to use the repository cache: {error}")]
+    Cache {
+        /// The underlying cache error.
+        #[source]
+        error: CacheError,
+    },
+
+    /// A metadata file to write discovered sources to cannot be parsed.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata file.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// A metadata file to write discovered sources to has no pair with the
+    /// program's name.
+    #[error("'{path}' has no pair named '{program_name}'")]
+    PairNotInFile {
+        /// The program's name.
+        program_name: String,
+        /// The metadata file.
+        path: PathBuf,
+    },
+
+    /// The metadata file cannot be written back with the discovered sources,
+    /// such as because it would violate the schema.
+    #[error("Failed to write the sources to '{path}': {error}")]
+    Write {
+        /// The metadata file.
+        path: PathBuf,
+        /// The underlying writer error.
+        #[source]
+        error: WriterError,
+    },
+
+    /// Failed to read a file or directory.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The path that could not be read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write a metadata file.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+}
+
+/// Errors that occur when linting metadata files.
+#[derive(thiserror::Error, Debug)]
+pub enum LintError {
+    /// Failed to read a metadata directory.
+    #[error("Failed to parse '{path}': {error}")]
+    Parse {
+        /// The metadata directory that could not be read.
+        path: PathBuf,
+        /// The underlying parser error.
+        #[source]
+        error: ParserError,
+    },
+
+    /// Some metadata files are invalid.
+    #[error("{invalid_files} of {total_files} metadata files are invalid")]
+    Invalid {
+        /// Number of invalid metadata files.
+        invalid_files: usize,
+        /// Number of metadata files checked.
+        total_files: usize,
+    },
+
+    /// Some repository URLs are spelled in more than one way.
+    #[error("{repositories} repositories are spelled inconsistently")]
+    InconsistentUrls {
+        /// Number of repositories with more than one spelling.
+        repositories: usize,
+    },
+
+    /// Some pairs list translation tools that are not recognized.
+    #[error("{count} pairs list unrecognized translation tools")]
+    UnrecognizedTools {
+        /// Number of pairs with unrecognized translation tools.
+        count: usize,
+    },
+
+    /// Some downloaded pairs look like their C and Rust programs implement
+    /// different tools.
+    #[error("{count} pairs look like their C and Rust programs are different tools")]
+    SuspiciousPairs {
+        /// Number of suspicious pairs.
+        count: usize,
+    },
+}
+
+/// Errors that occur when loading or applying the configuration file.
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    /// Failed to read the configuration file.
+    #[error("Failed to read '{path}': {error}")]
+    IoRead {
+        /// The configuration file.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// The configuration file is not valid TOML or has unknown keys.
+    #[error("Failed to parse '{path}': {error}")]
+    Deserialize {
+        /// The configuration file.
+        path: PathBuf,
+        /// The underlying deserialization error.
+        #[source]
+        error: toml::de::Error,
+    },
+
+    /// A value in the configuration file is malformed.
+    #[error("Invalid '{key}' in '{path}': {message}")]
+    InvalidValue {
+        /// The configuration file.
+        path: PathBuf,
+        /// The key whose value is malformed, such as `download.max_bandwidth`.
+        key: &'static str,
+        /// Why the value is malformed.
+        message: String,
+    },
+
+    /// Failed to change into the corpus root.
+    #[error("Failed to change into the corpus root '{path}': {error}")]
+    Root {
+        /// The corpus root.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to create the thread pool.
+    #[error("Failed to create the thread pool: {error}")]
+    ThreadPool {
+        /// The underlying thread pool error.
+        #[source]
+        error: rayon::ThreadPoolBuildError,
+    },
+}
+
+/// Errors that occur when diagnosing the environment.
+#[derive(thiserror::Error, Debug)]
+pub enum DoctorError {
+    /// Some checks of the environment failed.
+    #[error("{failed} of {total} checks failed; see the suggested fixes above")]
+    Failed {
+        /// Number of failed checks.
+        failed: usize,
+        /// Number of checks run.
+        total: usize,
+    },
+}
+
+/// Errors that occur when checking or creating the layout of a corpus root.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum InitError {
+    /// The corpus root has no metadata directory.
+    #[error(
+        "The metadata directory '{directory}' does not exist; a corpus root holds metadata \
+         files in 'metadata/project/', 'metadata/individual/', and 'metadata/demo/'.  Pass \
+         --root to run in another corpus root, --metadata-dir to read metadata from another \
+         directory, or run `init` to create the layout"
+    )]
+    MissingMetadata {
+        /// The metadata directory: `metadata/` in the corpus root, which is
+        /// the current directory unless --root was given, or the directory
+        /// given with --metadata-dir.
+        directory: PathBuf,
+    },
+
+    /// Failed to create a directory or file of the layout.
+    #[error("Failed to write '{path}': {error}")]
+    IoWrite {
+        /// The path that could not be written.
+        path: PathBuf,
+        /// The underlying I/O error.
+        #[source]
+        error: io::Error,
+    },
+
+    /// Failed to write the demo metadata.
+    #[error("Failed to write the demo metadata: {error}")]
+    Profile {
+        /// The underlying profile error.
+        #[source]
+        error: ProfileError,
+    },
+}
+
+/// Errors that occur when publishing the downloaded corpus to a git remote.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum PublishError {
+    /// No program pairs were downloaded.
+    #[error("'{path}' does not exist; download the program pairs before publishing them")]
+    NoPairs {
+        /// The program pairs directory.
+        path: PathBuf,
+    },
+
+    /// Failed to record the snapshot in the local publish repository.
+    #[error("Failed to commit the snapshot in '{path}': {error}")]
+    Commit {
+        /// The local publish repository.
+        path: PathBuf,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// Failed to fetch the published snapshots from the remote.
+    #[error("Failed to fetch '{remote_url}': {error}")]
+    Fetch {
+        /// The URL of the remote.
+        remote_url: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// Failed to push the snapshot to the remote.
+    #[error("Failed to push to '{remote_url}': {error}")]
+    Push {
+        /// The URL of the remote.
+        remote_url: String,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// Failed to apply git settings, such as the CA bundle, to the
+    /// connection to the remote.
+    #[error("Failed to configure git: {error}")]
+    GitConfig {
+        /// The underlying error.
+        #[source]
+        error: GitOptionsError,
+    },
+
+    /// The remote refused the snapshot, such as when its branch is
+    /// protected.
+    #[error("'{remote_url}' rejected the snapshot: {message}")]
+    Rejected {
+        /// The URL of the remote.
+        remote_url: String,
+        /// The remote's reason.
+        message: String,
+    },
+}
+
+/// Errors that occur when the process-wide libgit2 options are being set.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum GitOptionsError {
+    /// Failed to use a CA bundle for verifying HTTPS servers.
+    #[error("Failed to use CA bundle '{path}': {error}")]
+    CaBundle {
+        /// The CA bundle's path.
+        path: PathBuf,
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// libgit2 rejected an option.
+    #[error("Failed to set libgit2 options: {error}")]
+    Set {
+        /// The underlying git error.
+        #[source]
+        error: git2::Error,
+    },
+
+    /// The options were already set differently in this process, and cannot
+    /// change while clones may be running.
+    #[error("libgit2 options were already set differently in this process")]
+    AlreadySet,
+}
+
+/// The category of a failure, which determines the exit code of the process
+/// so that scripts can tell failures apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Any other failure, such as an unknown program pair or an invalid
+    /// argument.
+    Other,
+    /// The command finished, but some items, such as program pairs, failed.
+    PartialFailure,
+    /// Metadata files do not match the schema or contain malformed values.
+    InvalidMetadata,
+    /// A repository or API could not be reached.
+    Network,
+    /// Reading or writing local files failed.
+    Io,
+}
+
+impl ErrorKind {
+    /// Returns the exit code of the process for this kind of failure.
+    ///
+    /// # Returns
+    ///
+    /// 1 for other failures, 2 for partial failures, 3 for invalid metadata,
+    /// 4 for network failures, and 5 for I/O failures.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::PartialFailure => 2,
+            ErrorKind::InvalidMetadata => 3,
+            ErrorKind::Network => 4,
+            ErrorKind::Io => 5,
+        }
+    }
+}
+
+/// Errors that can be categorized into an [`ErrorKind`].
+pub trait Classify: std::error::Error {
+    /// Returns the category of the error.
+    fn kind(&self) -> ErrorKind;
+}
+
+/// Categorizes a libgit2 error by the component that raised it.
+fn git_error_kind(error: &git2::Error) -> ErrorKind {
+    match error.class() {
+        // libgit2 reports a refused connection as an OS error too.
+        git2::ErrorClass::Os if error.message().starts_with("failed to connect") => {
+            ErrorKind::Network
+        }
+        git2::ErrorClass::Os | git2::ErrorClass::Filesystem => ErrorKind::Io,
+        git2::ErrorClass::Config => ErrorKind::Other,
+        _ => ErrorKind::Network,
+    }
+}
+
+impl Classify for ParserError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ParserError::IoRead { .. } => ErrorKind::Io,
+            ParserError::Schema { .. } => ErrorKind::Other,
+            ParserError::Deserialize { .. }
+            | ParserError::InvalidValue { .. }
+            | ParserError::Validation { .. } => ErrorKind::InvalidMetadata,
+        }
+    }
+}
+
+impl Classify for SchemaError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidMetadata
+    }
+}
+
+impl Classify for WriterError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            WriterError::Invalid { error } => error.kind(),
+            WriterError::ProjectFile { .. } | WriterError::Serialize { .. } => ErrorKind::Other,
+            WriterError::IoWrite { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for DownloaderError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DownloaderError::IoRead { .. }
+            | DownloaderError::IoCreate { .. }
+            | DownloaderError::IoWrite { .. }
+            | DownloaderError::IoCopy { .. }
+            | DownloaderError::Transform { .. }
+            | DownloaderError::Io(_)
+            | DownloaderError::Extract { .. }
+            | DownloaderError::InsufficientSpace { .. }
+            | DownloaderError::FreeSpace { .. }
+            | DownloaderError::Root { .. } => ErrorKind::Io,
+            DownloaderError::CloneRepository { error, .. }
+            | DownloaderError::FetchRepository { error, .. }
+            | DownloaderError::MetadataRepository { error, .. }
+            | DownloaderError::UpdateSubmodules { error, .. }
+            | DownloaderError::LockedCommit { error, .. }
+            | DownloaderError::Branch { error, .. } => git_error_kind(error),
+            DownloaderError::Lock { error } => error.kind(),
+            DownloaderError::Cache { error } => error.kind(),
+            DownloaderError::Patch { error, .. } => error.kind(),
+            DownloaderError::Parse { error, .. } => error.kind(),
+            DownloaderError::SkipList { error } => error.kind(),
+            DownloaderError::Profile { error } => error.kind(),
+            DownloaderError::Enriched { error } => error.kind(),
+            DownloaderError::GitConfig { error } => error.kind(),
+            DownloaderError::NotCached { .. }
+            | DownloaderError::UnhealthyClone { .. }
+            | DownloaderError::GitCommand { .. }
+            | DownloaderError::FetchSource { .. }
+            | DownloaderError::TimedOut { .. } => ErrorKind::Network,
+            DownloaderError::PathEscape { .. }
+            | DownloaderError::FlattenCollision { .. }
+            | DownloaderError::ChecksumMismatch { .. }
+            | DownloaderError::UnknownProfilePairs { .. } => ErrorKind::InvalidMetadata,
+            DownloaderError::Serialize { .. }
+            | DownloaderError::ResolveHead { .. }
+            | DownloaderError::Hook { .. }
+            | DownloaderError::LocalChanges { .. }
+            | DownloaderError::MetadataConflict { .. }
+            | DownloaderError::ThreadPool { .. }
+            | DownloaderError::ProgressBar(_) => ErrorKind::Other,
+            DownloaderError::InvalidMetadata { .. } | DownloaderError::MetadataDirectory { .. } => {
+                ErrorKind::InvalidMetadata
+            }
+            DownloaderError::Incomplete { invalid_files, .. } if !invalid_files.is_empty() => {
+                ErrorKind::InvalidMetadata
+            }
+            DownloaderError::Incomplete { .. } => ErrorKind::PartialFailure,
+        }
+    }
+}
+
+impl Classify for StatsError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            StatsError::IoRead { .. } => ErrorKind::Io,
+            StatsError::Enriched { error } => error.kind(),
+            StatsError::SkipList { error } => error.kind(),
+        }
+    }
+}
+
+impl Classify for DiffError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DiffError::PairNotFound { .. } | DiffError::Serialize { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for ShowError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ShowError::PairNotFound { .. }
+            | ShowError::FileNotFound { .. }
+            | ShowError::NoSourceFiles { .. } => ErrorKind::Other,
+            ShowError::IoRead { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for DeleteError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DeleteError::PairNotFound { .. } => ErrorKind::Other,
+            DeleteError::IoRead { .. }
+            | DeleteError::IoDelete { .. }
+            | DeleteError::Prompt { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for AnalyzeError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            AnalyzeError::PairNotFound { .. } | AnalyzeError::Serialize { .. } => ErrorKind::Other,
+            AnalyzeError::IoRead { .. } | AnalyzeError::IoWrite { .. } => ErrorKind::Io,
+            AnalyzeError::Manifest { error } => error.kind(),
+        }
+    }
+}
+
+impl Classify for ReleaseError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ReleaseError::Parse { error, .. } => error.kind(),
+            ReleaseError::IoRead { .. } | ReleaseError::IoWrite { .. } => ErrorKind::Io,
+            ReleaseError::InvalidVersion { .. }
+            | ReleaseError::Exists { .. }
+            | ReleaseError::Serialize { .. }
+            | ReleaseError::Deserialize { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for BuildError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            BuildError::PairNotFound { .. } | BuildError::Serialize { .. } => ErrorKind::Other,
+            BuildError::IoRead { .. } | BuildError::IoWrite { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for SplitError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SplitError::InvalidFractions { .. } | SplitError::Serialize { .. } => ErrorKind::Other,
+            SplitError::IoRead { .. } | SplitError::IoWrite { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for AuditError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            AuditError::Client { .. } => ErrorKind::Other,
+            AuditError::GitHub { error } => error.kind(),
+            AuditError::Problems { .. } => ErrorKind::InvalidMetadata,
+        }
+    }
+}
+
+impl Classify for OutdatedError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            OutdatedError::IoRead { .. } => ErrorKind::Io,
+            OutdatedError::Incomplete { .. } => ErrorKind::PartialFailure,
+        }
+    }
+}
+
+impl Classify for DiscoverError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DiscoverError::GitHub { error } => error.kind(),
+            DiscoverError::Incomplete { .. } => ErrorKind::PartialFailure,
+            DiscoverError::IoWrite { .. } | DiscoverError::IoRead { .. } => ErrorKind::Io,
+            DiscoverError::NotDrafts { .. } => ErrorKind::InvalidMetadata,
+            DiscoverError::Serialize { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for RenameError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            RenameError::InvalidName { .. }
+            | RenameError::PairNotFound { .. }
+            | RenameError::AlreadyExists { .. } => ErrorKind::Other,
+            RenameError::Deserialize { .. } => ErrorKind::InvalidMetadata,
+            RenameError::Parse { error, .. } => error.kind(),
+            RenameError::Write { error, .. } => error.kind(),
+            RenameError::Manifest { error } => error.kind(),
+            RenameError::IoRead { .. } | RenameError::IoWrite { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for ExportError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ExportError::Parse { error, .. } => error.kind(),
+            ExportError::Analysis { error } => error.kind(),
+            ExportError::IoRead { .. } | ExportError::IoWrite { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+#[cfg(feature = "index")]
+impl Classify for IndexError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            IndexError::Parse { error, .. } => error.kind(),
+            IndexError::SkipList { error } => error.kind(),
+            IndexError::Stats { error } => error.kind(),
+            IndexError::IoDelete { .. }
+            | IndexError::IoReplace { .. }
+            | IndexError::Database { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for SiteError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SiteError::Parse { error, .. } => error.kind(),
+            SiteError::Stats { error } => error.kind(),
+            SiteError::NotReport { .. } => ErrorKind::Other,
+            SiteError::IoDelete { .. } | SiteError::IoWrite { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for CacheError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            CacheError::Parse { error, .. } => error.kind(),
+            CacheError::IoRead { .. }
+            | CacheError::IoWrite { .. }
+            | CacheError::IoDelete { .. } => ErrorKind::Io,
+            CacheError::Serialize { .. }
+            | CacheError::InvalidSize { .. }
+            | CacheError::CloneNotFound { .. }
+            | CacheError::AmbiguousRepository { .. }
+            | CacheError::CloneInUse { .. }
+            | CacheError::UnhealthyClones { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for GitHubError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            GitHubError::Request { .. } | GitHubError::RateLimited { .. } => ErrorKind::Network,
+            GitHubError::Client { .. }
+            | GitHubError::InvalidUrl { .. }
+            | GitHubError::Deserialize { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for EnrichError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            EnrichError::Request { .. } => ErrorKind::Network,
+            EnrichError::GitHub { error } => error.kind(),
+            EnrichError::Incomplete { .. } => ErrorKind::PartialFailure,
+            EnrichError::IoRead { .. } | EnrichError::IoWrite { .. } => ErrorKind::Io,
+            EnrichError::Client { .. }
+            | EnrichError::UnsupportedHost { .. }
+            | EnrichError::Serialize { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for LockError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            LockError::IoRead { .. } | LockError::IoWrite { .. } => ErrorKind::Io,
+            LockError::Deserialize { .. } | LockError::UnsupportedVersion { .. } => {
+                ErrorKind::InvalidMetadata
+            }
+            LockError::Fetch { error, .. } => git_error_kind(error),
+            LockError::Serialize { .. } | LockError::NotLocked { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for ProfileError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ProfileError::IoRead { .. } | ProfileError::IoWrite { .. } => ErrorKind::Io,
+            ProfileError::Deserialize { .. }
+            | ProfileError::MissingFile { .. }
+            | ProfileError::OutsideMetadata { .. } => ErrorKind::InvalidMetadata,
+            ProfileError::Unknown { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for SkipListError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SkipListError::IoRead { .. } => ErrorKind::Io,
+            SkipListError::Deserialize { .. } => ErrorKind::InvalidMetadata,
+        }
+    }
+}
+
+impl Classify for TuiError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            TuiError::Parse { error, .. } => error.kind(),
+            TuiError::SkipList { error } => error.kind(),
+            TuiError::Terminal { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for ListError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ListError::Parse { error, .. } => error.kind(),
+            ListError::Enriched { error } => error.kind(),
+            ListError::SkipList { error } => error.kind(),
+        }
+    }
+}
+
+impl Classify for ServeError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ServeError::Bind { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for PatchError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            PatchError::IoRead { .. } | PatchError::IoWrite { .. } => ErrorKind::Io,
+            PatchError::Malformed { .. } | PatchError::InvalidPath { .. } => {
+                ErrorKind::InvalidMetadata
+            }
+            PatchError::HunkMismatch { .. } | PatchError::UnexpectedFile { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for SearchError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SearchError::Parse { error, .. } => error.kind(),
+            SearchError::IoRead { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for WatchError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            WatchError::IoRead { .. } | WatchError::Watch { .. } => ErrorKind::Io,
+            WatchError::Parse { error, .. } => error.kind(),
+        }
+    }
+}
+
+impl Classify for MetadataError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            MetadataError::Request { .. } | MetadataError::Clone { .. } => ErrorKind::Network,
+            MetadataError::Client { .. }
+            | MetadataError::CrateNotFound { .. }
+            | MetadataError::NoRepository { .. }
+            | MetadataError::Serialize { .. }
+            | MetadataError::InvalidName { .. }
+            | MetadataError::AlreadyExists { .. }
+            | MetadataError::ProgramNotFound { .. }
+            | MetadataError::CrateTargetNotFound { .. }
+            | MetadataError::CargoManifest { .. }
+            | MetadataError::NotARepository { .. }
+            | MetadataError::PairNotInFile { .. } => ErrorKind::Other,
+            MetadataError::Skeleton { error } | MetadataError::Parse { error, .. } => error.kind(),
+            MetadataError::Write { error, .. } => error.kind(),
+            MetadataError::Cache { error } => error.kind(),
+            MetadataError::IoRead { .. } | MetadataError::IoWrite { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl Classify for LintError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            LintError::Parse { error, .. } => error.kind(),
+            LintError::Invalid { .. }
+            | LintError::InconsistentUrls { .. }
+            | LintError::UnrecognizedTools { .. }
+            | LintError::SuspiciousPairs { .. } => ErrorKind::InvalidMetadata,
+        }
+    }
+}
+
+impl Classify for ConfigError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ConfigError::IoRead { .. } | ConfigError::Root { .. } => ErrorKind::Io,
+            ConfigError::Deserialize { .. }
+            | ConfigError::InvalidValue { .. }
+            | ConfigError::ThreadPool { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for DoctorError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DoctorError::Failed { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for InitError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            InitError::MissingMetadata { .. } => ErrorKind::InvalidMetadata,
+            InitError::IoWrite { .. } => ErrorKind::Io,
+            InitError::Profile { error } => error.kind(),
+        }
+    }
+}
+
+impl Classify for PublishError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            PublishError::NoPairs { .. } | PublishError::Rejected { .. } => ErrorKind::Other,
+            PublishError::GitConfig { error } => error.kind(),
+            PublishError::Commit { error, .. }
+            | PublishError::Fetch { error, .. }
+            | PublishError::Push { error, .. } => git_error_kind(error),
+        }
+    }
+}
+
+impl Classify for GitOptionsError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            GitOptionsError::CaBundle { .. } => ErrorKind::Io,
+            GitOptionsError::Set { .. } | GitOptionsError::AlreadySet => ErrorKind::Other,
+        }
+    }
 }
@@ -0,0 +1,497 @@
+//! # Dataset Export
+//!
+//! This module exports the downloaded program pairs as a dataset that
+//! machine learning tools can load directly, such as with the `json` loader
+//! of HuggingFace `datasets`.  Each record holds a pair's metadata together
+//! with the full text of its C and Rust sources, or, for evaluating
+//! translators file by file, the Rust file each C file was most likely
+//! translated into.
+//!
+//! Files are aligned by how alike their names are, using the Dice
+//! coefficient of their character bigrams, and by the share of function
+//! names they have in common.  Each C file is aligned with the Rust file
+//! scoring highest, so several C files may share one Rust file, as when a
+//! translation merges them.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    corpus::{
+        analysis::ast,
+        analyze::{self, TranslationOrigin},
+        errors::ExportError,
+        manifest::{self, Manifest, ProgramManifest},
+        parser,
+        provenance::Provenance,
+        schema::{Features, FunctionMapping, Language, ProgramPair, TranslationTool},
+        utils,
+    },
+    paths::{
+        DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// Directory names too common in Rust paths to identify a C file, such as
+/// the `src` of `src/uu/cat/src/cat.rs`.
+const GENERIC_DIRECTORIES: [&str; 3] = ["src", "lib", "bin"];
+
+/// The file format of an exported dataset.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// One JSON object per line.
+    Jsonl,
+    /// One JSON object per program pair, aligning each C file with its most
+    /// likely Rust counterpart.
+    Alignment,
+}
+
+/// What each exported record describes.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Granularity {
+    /// One record per program pair, with the sources of both programs.
+    Pair,
+    /// One record per source file.
+    File,
+}
+
+/// The metadata shared by every record of a program pair.  Fields are `null`
+/// for pairs that are no longer in the metadata.
+#[derive(Debug, Serialize)]
+struct PairInfo {
+    program_name: String,
+    program_description: Option<String>,
+    translation_tools: Vec<TranslationTool>,
+    tags: Vec<String>,
+    translation_origin: Option<TranslationOrigin>,
+    feature_relationship: Option<Features>,
+    c_repository_url: Option<String>,
+    rust_repository_url: Option<String>,
+    c_license: Option<String>,
+    rust_license: Option<String>,
+}
+
+/// A source file of a program.
+#[derive(Debug, Serialize)]
+struct SourceFile {
+    /// Path of the file in its repository, or in the pair's directory if the
+    /// pair has no provenance file.
+    path: String,
+    /// The file's text.
+    content: String,
+}
+
+/// A record describing a whole program pair.
+#[derive(Debug, Serialize)]
+struct PairRecord<'a> {
+    #[serde(flatten)]
+    info: &'a PairInfo,
+    c_files: &'a [SourceFile],
+    rust_files: &'a [SourceFile],
+    function_mappings: &'a [FunctionMapping],
+}
+
+/// A record describing one source file of a program pair.
+#[derive(Debug, Serialize)]
+struct FileRecord<'a> {
+    #[serde(flatten)]
+    info: &'a PairInfo,
+    language: &'a Language,
+    #[serde(flatten)]
+    file: &'a SourceFile,
+}
+
+/// A record aligning the C files of a program pair with its Rust files.
+#[derive(Debug, Serialize)]
+struct AlignmentRecord<'a> {
+    #[serde(flatten)]
+    info: &'a PairInfo,
+    alignments: Vec<FileAlignment<'a>>,
+    /// Rust files that no C file is aligned with.
+    unaligned_rust_files: Vec<&'a str>,
+}
+
+/// The Rust file a C file was most likely translated into.
+#[derive(Debug, Serialize)]
+struct FileAlignment<'a> {
+    c_file: &'a str,
+    /// `None` if no Rust file resembles the C file.
+    rust_file: Option<&'a str>,
+    /// Confidence in the alignment, from 0 to 1.
+    confidence: f64,
+    /// How alike the files' names are, from 0 to 1.
+    name_similarity: f64,
+    /// Share of the files' function names they have in common, or `None` if
+    /// neither file defines a function.
+    function_similarity: Option<f64>,
+}
+
+/// Exports the downloaded program pairs as a dataset.
+///
+/// # Arguments
+///
+/// - `format` - The file format of the dataset.
+/// - `granularity` - Whether each record describes a pair or a file.
+/// - `origins` - The translation origins of the pairs to export, or empty
+///   to export every pair.
+/// - `output` - The file to write, or `None` for stdout.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`ExportError`] on failure.
+pub fn export(
+    format: ExportFormat,
+    granularity: Granularity,
+    origins: &[TranslationOrigin],
+    output: Option<&Path>,
+) -> Result<(), ExportError> {
+    let metadata = metadata_pairs()?;
+    // Pairs are exported without a translation origin if `analyze` has not
+    // recorded one, and left out when filtering by origin.
+    let analyses = analyze::load_results().map_err(|error| ExportError::Analysis { error })?;
+    if !origins.is_empty()
+        && !analyses
+            .values()
+            .any(|analysis| analysis.translation_origin.is_some())
+    {
+        eprintln!("No translation origins are recorded; run `analyze --translation-origin` first");
+    }
+    let program_pairs = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    let pair_directories =
+        utils::list_program_pairs(program_pairs).map_err(|error| ExportError::IoRead {
+            path: program_pairs.to_path_buf(),
+            error,
+        })?;
+
+    let output_path = output.map_or_else(|| PathBuf::from("stdout"), Path::to_path_buf);
+    let write_error = |error| ExportError::IoWrite {
+        path: output_path.clone(),
+        error,
+    };
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path).map_err(write_error)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    let mut records = 0;
+    let mut pairs = 0;
+    for pair_directory in &pair_directories {
+        let Some(program_name) = pair_directory.file_name() else {
+            continue;
+        };
+        let program_name = program_name.to_string_lossy().into_owned();
+        let manifest = Manifest::load(pair_directory);
+        let provenance = Provenance::load(pair_directory).unwrap_or_default();
+        let pair = metadata.get(&program_name);
+        let translation_origin = analyses
+            .get(&program_name)
+            .and_then(|analysis| analysis.translation_origin.as_ref())
+            .map(|report| report.origin);
+        if !origins.is_empty()
+            && !translation_origin.is_some_and(|origin| origins.contains(&origin))
+        {
+            continue;
+        }
+        pairs += 1;
+        let info = pair_info(program_name, pair, manifest.as_ref(), translation_origin);
+        let c_files = source_files(pair_directory, &Language::C, &provenance);
+        let rust_files = source_files(pair_directory, &Language::Rust, &provenance);
+
+        match (format, granularity) {
+            (ExportFormat::Jsonl, Granularity::Pair) => {
+                let record = PairRecord {
+                    info: &info,
+                    c_files: &c_files,
+                    rust_files: &rust_files,
+                    function_mappings: manifest
+                        .as_ref()
+                        .map_or(&[], |manifest| &manifest.function_mappings),
+                };
+                write_line(&mut writer, &record).map_err(write_error)?;
+                records += 1;
+            }
+            (ExportFormat::Alignment, _) => {
+                let (alignments, unaligned_rust_files) = align(&c_files, &rust_files);
+                let record = AlignmentRecord {
+                    info: &info,
+                    alignments,
+                    unaligned_rust_files,
+                };
+                write_line(&mut writer, &record).map_err(write_error)?;
+                records += 1;
+            }
+            (ExportFormat::Jsonl, Granularity::File) => {
+                let files = [(Language::C, &c_files), (Language::Rust, &rust_files)];
+                for (language, files) in &files {
+                    for file in files.iter() {
+                        let record = FileRecord {
+                            info: &info,
+                            language,
+                            file,
+                        };
+                        write_line(&mut writer, &record).map_err(write_error)?;
+                        records += 1;
+                    }
+                }
+            }
+        }
+    }
+    writer.flush().map_err(write_error)?;
+
+    eprintln!("Exported {records} records from {pairs} program pairs");
+    Ok(())
+}
+
+/// Parses every metadata file, keyed by program name.
+///
+/// Files that fail to parse are reported and skipped, so their pairs are
+/// exported without metadata.
+///
+/// # Returns
+///
+/// Every program pair in the metadata, or [`ExportError`] if a metadata
+/// directory cannot be read.
+fn metadata_pairs() -> Result<HashMap<String, ProgramPair>, ExportError> {
+    let mut pairs = HashMap::new();
+    for directory in [
+        DEMO_METADATA_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+    ] {
+        let metadata_files =
+            parser::parse_directory(Path::new(directory)).map_err(|error| ExportError::Parse {
+                path: directory.into(),
+                error,
+            })?;
+        for (path, metadata) in metadata_files {
+            match metadata {
+                Ok(metadata) => pairs.extend(
+                    metadata
+                        .pairs
+                        .into_iter()
+                        .map(|pair| (pair.program_name.clone(), pair)),
+                ),
+                Err(error) => eprintln!("Failed to parse '{}': {}", path.display(), error),
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// Collects the metadata of a downloaded program pair.
+///
+/// Repository URLs come from the manifest when possible, since it records
+/// the mirror the files were copied from if the repository URL failed.
+fn pair_info<'a>(
+    program_name: String,
+    pair: Option<&ProgramPair>,
+    manifest: Option<&'a Manifest>,
+    translation_origin: Option<TranslationOrigin>,
+) -> PairInfo {
+    let copied_from = |program: &'a ProgramManifest| {
+        program
+            .mirror_url
+            .as_ref()
+            .unwrap_or(&program.repository_url)
+    };
+    let repository_url = |manifest_url: Option<&String>, metadata_url: Option<&str>| {
+        manifest_url
+            .cloned()
+            .or_else(|| metadata_url.map(str::to_string))
+    };
+    PairInfo {
+        program_description: pair.map(|pair| pair.program_description.clone()),
+        translation_tools: pair.map_or_else(Vec::new, |pair| pair.translation_tools.clone()),
+        tags: pair.map_or_else(Vec::new, |pair| pair.tags.clone()),
+        translation_origin,
+        feature_relationship: pair.map(|pair| pair.feature_relationship.clone()),
+        c_repository_url: repository_url(
+            manifest.map(|manifest| copied_from(&manifest.c_program)),
+            pair.map(|pair| pair.c_program.repository_url.as_str()),
+        ),
+        rust_repository_url: repository_url(
+            manifest.map(|manifest| copied_from(&manifest.rust_program)),
+            pair.map(|pair| pair.rust_program.repository_url.as_str()),
+        ),
+        c_license: manifest.and_then(|manifest| manifest.c_program.license.clone()),
+        rust_license: manifest.and_then(|manifest| manifest.rust_program.license.clone()),
+        program_name,
+    }
+}
+
+/// Reads the source files of one program of a downloaded pair.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The program pair's directory.
+/// - `language` - Which program of the pair to read.
+/// - `provenance` - The pair's provenance, used to name files by their path
+///   in the repository.
+///
+/// # Returns
+///
+/// The program's readable source files, sorted by their path in the pair.
+fn source_files(
+    pair_directory: &Path,
+    language: &Language,
+    provenance: &Provenance,
+) -> Vec<SourceFile> {
+    let program_directory = manifest::program_directory(pair_directory, language);
+    utils::list_files(&program_directory)
+        .into_iter()
+        .filter(|path| Language::from_path(path).as_ref() == Some(language))
+        .filter_map(|path| {
+            let content = String::from_utf8_lossy(&fs::read(&path).ok()?).into_owned();
+            let relative = utils::slash_path(path.strip_prefix(pair_directory).ok()?)?;
+            let path = provenance
+                .source_path(&relative)
+                .map_or_else(|| relative.clone(), str::to_string);
+            Some(SourceFile { path, content })
+        })
+        .collect()
+}
+
+/// Aligns each C file of a program pair with the Rust file it was most
+/// likely translated into.
+///
+/// # Arguments
+///
+/// - `c_files` - The C program's source files.
+/// - `rust_files` - The Rust program's source files.
+///
+/// # Returns
+///
+/// The alignment of each C file, in order, and the paths of the Rust files
+/// that no C file is aligned with.
+fn align<'a>(
+    c_files: &'a [SourceFile],
+    rust_files: &'a [SourceFile],
+) -> (Vec<FileAlignment<'a>>, Vec<&'a str>) {
+    let function_names = |file: &SourceFile, language: &Language| -> HashSet<String> {
+        ast::extract(&file.content, language)
+            .into_iter()
+            .map(|function| function.name.replace('_', "").to_lowercase())
+            .collect()
+    };
+    let rust_functions: Vec<HashSet<String>> = rust_files
+        .iter()
+        .map(|file| function_names(file, &Language::Rust))
+        .collect();
+
+    let mut aligned = vec![false; rust_files.len()];
+    let mut alignments = Vec::new();
+    for c_file in c_files {
+        let c_functions = function_names(c_file, &Language::C);
+        let mut best = FileAlignment {
+            c_file: &c_file.path,
+            rust_file: None,
+            confidence: 0.0,
+            name_similarity: 0.0,
+            function_similarity: None,
+        };
+        let mut best_index = None;
+        for (index, (rust_file, rust_functions)) in
+            rust_files.iter().zip(&rust_functions).enumerate()
+        {
+            let name_similarity = name_similarity(&c_file.path, &rust_file.path);
+            let function_similarity =
+                (!c_functions.is_empty() || !rust_functions.is_empty()).then(|| {
+                    c_functions.intersection(rust_functions).count() as f64
+                        / c_functions.union(rust_functions).count() as f64
+                });
+            let confidence = function_similarity.map_or(name_similarity, |function_similarity| {
+                (name_similarity + function_similarity) / 2.0
+            });
+            if confidence > best.confidence {
+                best = FileAlignment {
+                    c_file: &c_file.path,
+                    rust_file: Some(&rust_file.path),
+                    confidence: round_score(confidence),
+                    name_similarity: round_score(name_similarity),
+                    function_similarity: function_similarity.map(round_score),
+                };
+                best_index = Some(index);
+            }
+        }
+        if let Some(index) = best_index {
+            aligned[index] = true;
+        }
+        alignments.push(best);
+    }
+
+    let unaligned = rust_files
+        .iter()
+        .zip(aligned)
+        .filter_map(|(file, aligned)| (!aligned).then_some(file.path.as_str()))
+        .collect();
+    (alignments, unaligned)
+}
+
+/// Scores how alike the names of a C file and a Rust file are, from 0 to 1.
+///
+/// The C file's stem is compared with the Rust file's stem and with the
+/// directories above it, since a Rust program's files are often named
+/// `main.rs` or `lib.rs` in a directory named after the C file.
+fn name_similarity(c_path: &str, rust_path: &str) -> f64 {
+    // Compares names ignoring case, extensions, and punctuation.
+    let normalize = |component: &str| -> String {
+        let stem = component
+            .rsplit_once('.')
+            .map_or(component, |(stem, _)| stem);
+        stem.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    };
+    let c_stem = normalize(c_path.rsplit('/').next().unwrap_or(c_path));
+    let mut components = rust_path.split('/').rev();
+    let rust_stem = normalize(components.next().unwrap_or(rust_path));
+    components
+        .filter(|directory| !GENERIC_DIRECTORIES.contains(directory))
+        .map(|directory| bigram_similarity(&c_stem, &normalize(directory)))
+        .fold(bigram_similarity(&c_stem, &rust_stem), f64::max)
+}
+
+/// Computes the Dice coefficient of the character bigrams of two names,
+/// which is 1 for equal names and 0 for names without a bigram in common.
+fn bigram_similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return if a.is_empty() { 0.0 } else { 1.0 };
+    }
+    let bigrams = |name: &str| -> Vec<(char, char)> {
+        let chars: Vec<char> = name.chars().collect();
+        chars.windows(2).map(|pair| (pair[0], pair[1])).collect()
+    };
+    let a = bigrams(a);
+    let mut b = bigrams(b);
+    let total = a.len() + b.len();
+    if total == 0 {
+        return 0.0;
+    }
+    let mut shared = 0;
+    for bigram in &a {
+        if let Some(index) = b.iter().position(|other| other == bigram) {
+            b.swap_remove(index);
+            shared += 1;
+        }
+    }
+    2.0 * shared as f64 / total as f64
+}
+
+/// Rounds a score to three decimal places for output.
+fn round_score(score: f64) -> f64 {
+    (score * 1000.0).round() / 1000.0
+}
+
+/// Writes a record as one line of JSON.
+fn write_line<T: Serialize>(writer: &mut dyn Write, record: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writer.write_all(b"\n")
+}
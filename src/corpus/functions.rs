@@ -0,0 +1,161 @@
+//! # Comment and Literal Stripping
+//!
+//! This module strips comments and literals from C and Rust source code so
+//! that the tokens left can be matched without a full parser, such as to
+//! count decision points or find identifiers.  Function definitions are
+//! found from syntax trees by [`ast`](crate::corpus::analysis::ast).
+
+use crate::corpus::schema::Language;
+
+/// Replaces comments, string literals, character literals, and (for C)
+/// preprocessor directives with spaces, preserving line breaks so that line
+/// numbers are unchanged.
+pub fn strip_comments_and_literals(source: &str, language: &Language) -> String {
+    blank_source(source, language, true)
+}
+
+/// Removes comments, keeping their line breaks so that line numbers are
+/// unchanged.  Block comments within a line become one space, so the tokens
+/// around them stay apart, and the whitespace before line comments is
+/// removed with them.
+pub fn strip_comments(source: &str, language: &Language) -> String {
+    blank_source(source, language, false)
+}
+
+/// Blanks comments and, if `blank_literals` is set, literals and (for C)
+/// preprocessor directives; otherwise comments are removed and literals are
+/// kept.
+fn blank_source(source: &str, language: &Language, blank_literals: bool) -> String {
+    let nested_comments = matches!(language, Language::Rust);
+    let chars: Vec<char> = source.chars().collect();
+    let mut output = String::with_capacity(source.len());
+    let blank = |c: char| if c == '\n' { '\n' } else { ' ' };
+    let literal = |c: char| if blank_literals { blank(c) } else { c };
+    let mut at_line_start = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if blank_literals && language.is_source() && at_line_start && c == '#' {
+            // Skip the directive, including escaped line continuations.
+            while i < chars.len() && !(chars[i] == '\n' && (i == 0 || chars[i - 1] != '\\')) {
+                output.push(blank(chars[i]));
+                i += 1;
+            }
+        } else if c == '/' && next == Some('/') {
+            if !blank_literals {
+                let trimmed = output.trim_end_matches([' ', '\t']).len();
+                output.truncate(trimmed);
+            }
+            // Removed comments keep the `\r` of a `\r\n` line ending.
+            while i < chars.len() && chars[i] != '\n' && (blank_literals || chars[i] != '\r') {
+                if blank_literals {
+                    output.push(' ');
+                }
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            let mut depth = 0;
+            let mut line_breaks = 0;
+            while i < chars.len() {
+                if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                    depth = if nested_comments { depth + 1 } else { 1 };
+                    if blank_literals {
+                        output.push_str("  ");
+                    }
+                    i += 2;
+                } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    depth -= 1;
+                    if blank_literals {
+                        output.push_str("  ");
+                    }
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    if chars[i] == '\n' {
+                        line_breaks += 1;
+                    }
+                    if blank_literals || chars[i] == '\n' {
+                        output.push(blank(chars[i]));
+                    }
+                    i += 1;
+                }
+            }
+            if !blank_literals && line_breaks == 0 {
+                output.push(' ');
+            }
+        } else if nested_comments
+            && c == 'r'
+            && matches!(next, Some('"' | '#'))
+            && !chars
+                .get(i.wrapping_sub(1))
+                .is_some_and(|&c| is_identifier_char(c))
+        {
+            // Rust raw string such as `r#"..."#`, which may contain quotes.
+            let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+            if chars.get(i + 1 + hashes) != Some(&'"') {
+                output.push(c);
+                i += 1;
+                continue;
+            }
+            let terminator: String = std::iter::once('"')
+                .chain(std::iter::repeat_n('#', hashes))
+                .collect();
+            let body_start = i + 2 + hashes;
+            let body: String = chars[body_start..].iter().collect();
+            let end = body.find(&terminator).map_or(chars.len(), |offset| {
+                body_start + body[..offset].chars().count() + terminator.len()
+            });
+            for &c in &chars[i..end] {
+                output.push(literal(c));
+            }
+            i = end;
+        } else if c == '"' {
+            output.push(literal(c));
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                if chars[i] == '\\' {
+                    output.push(literal(chars[i]));
+                    i += 1;
+                }
+                if let Some(&c) = chars.get(i) {
+                    output.push(literal(c));
+                }
+                i += 1;
+            }
+            if let Some(&c) = chars.get(i) {
+                output.push(literal(c));
+            } else if blank_literals {
+                output.push(' ');
+            }
+            i += 1;
+        } else if c == '\'' && (next == Some('\\') || chars.get(i + 2) == Some(&'\'')) {
+            // Character literal; a lone quote is a Rust lifetime.
+            let end = (i + 2..chars.len())
+                .find(|&j| chars[j] == '\'' && (j > i + 2 || next != Some('\\')))
+                .unwrap_or(chars.len() - 1);
+            for &c in &chars[i..=end] {
+                output.push(if blank_literals { ' ' } else { c });
+            }
+            i = end + 1;
+        } else {
+            output.push(c);
+            i += 1;
+        }
+        at_line_start = match output.chars().next_back() {
+            Some('\n') => true,
+            Some(c) if c.is_whitespace() => at_line_start,
+            _ => false,
+        };
+    }
+
+    output
+}
+
+/// Returns whether `c` can appear in a C or Rust identifier.
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
@@ -0,0 +1,418 @@
+//! # Git Command-Line Backend
+//!
+//! This module clones and fetches repositories with the system `git` binary
+//! instead of libgit2, for features libgit2 lacks, such as credential
+//! helpers.  Progress is read from the lines `git --progress` prints on
+//! standard error and shown on the same bars as libgit2's transfer progress.
+
+use std::{
+    fs,
+    io::{BufReader, Read},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{OnceLock, mpsc},
+    thread,
+    time::Instant,
+};
+
+use git2::Repository;
+
+use crate::corpus::{
+    downloader::DownloadOptions, errors::DownloaderError, lock, progress::CloneProgress, utils,
+};
+
+/// The progress of one phase of a git command, parsed from a line such as
+/// `Receiving objects:  45% (450/1000), 1.20 MiB | 500.00 KiB/s`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GitProgress<'a> {
+    /// Name of the phase, such as `Receiving objects`.
+    pub phase: &'a str,
+    /// Number of items done so far.
+    pub done: u64,
+    /// Total number of items in the phase.
+    pub total: u64,
+    /// Bytes transferred and transfer rate, such as `1.20 MiB | 500.00 KiB/s`,
+    /// if git reports them.
+    pub transfer: Option<&'a str>,
+}
+
+/// Checks whether the system `git` binary can be run.  The answer is cached,
+/// so git is only run once per process.
+pub fn is_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        Command::new("git")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    })
+}
+
+/// Clones a repository.
+///
+/// # Arguments
+///
+/// - `repository_url` - The URL to clone.
+/// - `path` - Where to clone the repository; its parents are created.
+/// - `clone_depth` - Number of commits of history to clone, where 0 clones
+///   the full history.
+/// - `branch` - The branch to check out, or `None` for the default branch.
+/// - `clone_progress` - The progress bar showing the clone's progress.
+/// - `options` - Options controlling the download.
+/// - `deadline` - When to abort the clone, if ever.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::GitCommand`] if git
+/// fails or is aborted, in which case the partial clone is removed.
+pub fn clone(
+    repository_url: &str,
+    path: &Path,
+    clone_depth: u64,
+    branch: Option<&str>,
+    clone_progress: &CloneProgress,
+    options: &DownloadOptions,
+    deadline: Option<Instant>,
+) -> Result<(), DownloaderError> {
+    let mut command = git(options);
+    command.args(["clone", "--progress"]);
+    if clone_depth > 0 {
+        command.arg(format!("--depth={clone_depth}"));
+    }
+    if let Some(branch) = branch {
+        command.arg(format!("--branch={branch}"));
+    }
+    command.arg("--").arg(repository_url).arg(path);
+    let result = run(command, "clone", repository_url, clone_progress, deadline);
+
+    // git removes a failed clone itself, but not one it was killed during.
+    if result.is_err() && path.exists() {
+        let _ = fs::remove_dir_all(path);
+    }
+    result
+}
+
+/// Fetches the history of a clone, deepening it if it is shallow, along with
+/// any commits added upstream since it was made.
+///
+/// # Arguments
+///
+/// - `repository_url` - The URL the clone was made from, for error messages.
+/// - `path` - The clone.
+/// - `depth` - Number of commits of history to deepen a shallow clone to,
+///   where 0 fetches the full history.
+/// - `clone_progress` - The progress bar showing the fetch's progress.
+/// - `options` - Options controlling the download.
+/// - `deadline` - When to abort the fetch, if ever.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::GitCommand`] if git
+/// fails.
+pub fn fetch_history(
+    repository_url: &str,
+    path: &Path,
+    depth: u64,
+    clone_progress: &CloneProgress,
+    options: &DownloadOptions,
+    deadline: Option<Instant>,
+) -> Result<(), DownloaderError> {
+    let mut command = git(options);
+    command.arg("-C").arg(path).args(["fetch", "--progress"]);
+    // git refuses to unshallow a complete clone.
+    if Repository::open(path).is_ok_and(|repository| repository.is_shallow()) {
+        if depth > 0 {
+            command.arg(format!("--depth={depth}"));
+        } else {
+            command.arg("--unshallow");
+        }
+    }
+    command.arg("origin");
+    run(command, "fetch", repository_url, clone_progress, deadline)
+}
+
+/// Fetches a branch of a clone into its remote-tracking branch.
+///
+/// # Arguments
+///
+/// - `repository_url` - The URL the clone was made from, for error messages.
+/// - `path` - The clone.
+/// - `branch` - The branch to fetch.
+/// - `depth` - Number of commits of history to fetch into a shallow clone,
+///   where 0 fetches the full history; a complete clone stays complete.
+/// - `clone_progress` - The progress bar showing the fetch's progress.
+/// - `options` - Options controlling the download.
+/// - `deadline` - When to abort the fetch, if ever.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::GitCommand`] if git
+/// fails.
+pub fn fetch_branch(
+    repository_url: &str,
+    path: &Path,
+    branch: &str,
+    depth: u64,
+    clone_progress: &CloneProgress,
+    options: &DownloadOptions,
+    deadline: Option<Instant>,
+) -> Result<(), DownloaderError> {
+    let mut command = git(options);
+    command.arg("-C").arg(path).args(["fetch", "--progress"]);
+    if Repository::open(path).is_ok_and(|repository| repository.is_shallow()) {
+        command.arg(match depth {
+            0 => "--unshallow".to_string(),
+            depth => format!("--depth={depth}"),
+        });
+    }
+    command
+        .arg("origin")
+        .arg(lock::branch_refspec(Some(branch)));
+    run(command, "fetch", repository_url, clone_progress, deadline)
+}
+
+/// Initializes and updates the submodules of a repository, recursively.
+///
+/// # Arguments
+///
+/// - `repository_url` - The URL the repository was cloned from, for error
+///   messages.
+/// - `path` - The repository.
+/// - `clone_progress` - The progress bar showing the clones' progress.
+/// - `options` - Options controlling the download; in offline mode, only
+///   submodules that are already cloned can be updated.
+/// - `deadline` - When to abort the update, if ever.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`DownloaderError::GitCommand`] if git
+/// fails.
+pub fn update_submodules(
+    repository_url: &str,
+    path: &Path,
+    clone_progress: &CloneProgress,
+    options: &DownloadOptions,
+    deadline: Option<Instant>,
+) -> Result<(), DownloaderError> {
+    let mut command = git(options);
+    command.arg("-C").arg(path).args([
+        "submodule",
+        "update",
+        "--init",
+        "--recursive",
+        "--progress",
+    ]);
+    if options.offline {
+        command.arg("--no-fetch");
+    }
+    run(
+        command,
+        "submodule update",
+        repository_url,
+        clone_progress,
+        deadline,
+    )
+}
+
+/// Creates a `git` command that applies the download options.
+///
+/// Credentials are never prompted for, since a prompt would be hidden by the
+/// progress bars, but credential helpers still run.
+fn git(options: &DownloadOptions) -> Command {
+    let mut command = Command::new("git");
+    command.env("GIT_TERMINAL_PROMPT", "0");
+    if let Some(proxy) = &options.proxy {
+        command.arg("-c").arg(format!("http.proxy={proxy}"));
+    }
+    if let Some(ca_bundle) = &options.ca_bundle {
+        let key = if ca_bundle.is_dir() {
+            "http.sslCAPath"
+        } else {
+            "http.sslCAInfo"
+        };
+        command
+            .arg("-c")
+            .arg(format!("{key}={}", ca_bundle.display()));
+    }
+    if options.isolate_git_config {
+        command
+            .env("GIT_CONFIG_GLOBAL", utils::NULL_DEVICE)
+            .env("GIT_CONFIG_NOSYSTEM", "1");
+    }
+    command
+}
+
+/// Runs a git command, showing its progress on a progress bar.
+///
+/// # Arguments
+///
+/// - `command` - The git command, with `--progress` among its arguments.
+/// - `name` - Name of the git subcommand, for error messages.
+/// - `repository_url` - The repository the command works on, for error
+///   messages.
+/// - `clone_progress` - The progress bar to update.
+/// - `deadline` - When to kill git, if ever.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if git succeeds, or [`DownloaderError::GitCommand`] with
+/// the last error git printed otherwise.
+fn run(
+    mut command: Command,
+    name: &str,
+    repository_url: &str,
+    clone_progress: &CloneProgress,
+    deadline: Option<Instant>,
+) -> Result<(), DownloaderError> {
+    let git_error = |message: String| DownloaderError::GitCommand {
+        command: name.to_string(),
+        repository_url: repository_url.to_string(),
+        message,
+    };
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| git_error(error.to_string()))?;
+
+    // git's output is read on another thread, so that a git that stops
+    // printing can still be killed at the deadline.  Progress lines end in a
+    // carriage return so that terminals overwrite them, while other messages
+    // end in a newline.
+    let (sender, receiver) = mpsc::channel();
+    if let Some(stderr) = child.stderr.take() {
+        thread::spawn(move || {
+            let mut line = Vec::new();
+            for byte in BufReader::new(stderr).bytes() {
+                let Ok(byte) = byte else {
+                    break;
+                };
+                if byte != b'\r' && byte != b'\n' {
+                    line.push(byte);
+                } else if sender
+                    .send(String::from_utf8_lossy(&line).into_owned())
+                    .is_err()
+                {
+                    break;
+                } else {
+                    line.clear();
+                }
+            }
+        });
+    }
+
+    let mut errors = Vec::new();
+    loop {
+        let line = match deadline {
+            Some(deadline) => {
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => receiver.recv().map_err(mpsc::RecvTimeoutError::from),
+        };
+        match line {
+            Ok(line) => match parse_progress(&line) {
+                Some(progress) => clone_progress.update_git(&progress),
+                None if line.starts_with("fatal:") || line.starts_with("error:") => {
+                    errors.push(line);
+                }
+                None => {}
+            },
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(git_error("killed at the deadline".to_string()));
+            }
+        }
+    }
+
+    let status = child.wait().map_err(|error| git_error(error.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(git_error(
+            errors
+                .pop()
+                .unwrap_or_else(|| format!("git exited with {status}")),
+        ))
+    }
+}
+
+/// Parses a progress line printed by `git --progress`.
+///
+/// # Arguments
+///
+/// - `line` - A line of git's standard error, such as `Resolving deltas:
+///   100% (50/50), done.`; progress relayed from the server is prefixed with
+///   `remote: `.
+///
+/// # Returns
+///
+/// The [`GitProgress`] the line reports, or `None` if it is not a progress
+/// line.
+pub fn parse_progress(line: &str) -> Option<GitProgress<'_>> {
+    let line = line.strip_prefix("remote: ").unwrap_or(line);
+    let (phase, rest) = line.split_once(": ")?;
+    let (_, counts) = rest.split_once('(')?;
+    let (counts, rest) = counts.split_once(')')?;
+    let (done, total) = counts.split_once('/')?;
+    let transfer = rest
+        .trim_start_matches(',')
+        .trim()
+        .trim_end_matches("done.")
+        .trim_end_matches([',', ' ']);
+
+    Some(GitProgress {
+        phase: phase.trim(),
+        done: done.trim().parse().ok()?,
+        total: total.trim().parse().ok()?,
+        transfer: (!transfer.is_empty()).then_some(transfer),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that progress lines of local and remote phases are parsed, and
+    /// other lines are not.
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(
+            Some(GitProgress {
+                phase: "Receiving objects",
+                done: 450,
+                total: 1000,
+                transfer: Some("1.20 MiB | 500.00 KiB/s"),
+            }),
+            parse_progress("Receiving objects:  45% (450/1000), 1.20 MiB | 500.00 KiB/s")
+        );
+        assert_eq!(
+            Some(GitProgress {
+                phase: "Receiving objects",
+                done: 1000,
+                total: 1000,
+                transfer: Some("2.00 MiB | 1.00 MiB/s"),
+            }),
+            parse_progress("Receiving objects: 100% (1000/1000), 2.00 MiB | 1.00 MiB/s, done.")
+        );
+        assert_eq!(
+            Some(GitProgress {
+                phase: "Counting objects",
+                done: 10,
+                total: 10,
+                transfer: None,
+            }),
+            parse_progress("remote: Counting objects: 100% (10/10), done.")
+        );
+        assert_eq!(None, parse_progress("Cloning into 'diffutils'..."));
+        assert_eq!(
+            None,
+            parse_progress("fatal: repository 'https://example.com/' not found")
+        );
+    }
+}
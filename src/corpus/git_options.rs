@@ -0,0 +1,141 @@
+//! # libgit2 Options
+//!
+//! libgit2 keeps its server timeouts, CA certificates, and configuration
+//! search paths in process-wide options, which every clone and fetch reads
+//! without synchronization, so changing one while another thread clones is
+//! undefined behaviour.  This
+//! module is the only place that sets them: once per process, before the
+//! command setting them starts its first libgit2 operation, and never again
+//! afterwards.
+//!
+//! The binary runs one command per process.  Applications using the library
+//! share the options across every download they run: the first sets them,
+//! and a later one asking for different options fails with
+//! [`GitOptionsError::AlreadySet`] rather than changing them under clones
+//! that may still be running.  Such applications must not use libgit2
+//! themselves while the first download starts.
+
+use std::{path::PathBuf, sync::Mutex, time::Duration};
+
+use git2::{ConfigLevel, opts};
+
+use crate::corpus::{errors::GitOptionsError, utils};
+
+/// The process-wide libgit2 options a command needs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitOptions {
+    /// How long to wait while connecting to a server, or `None` for
+    /// libgit2's default.
+    pub connect_timeout: Option<Duration>,
+    /// How long to wait on a connected server that sends nothing, or `None`
+    /// for libgit2's default.
+    pub server_timeout: Option<Duration>,
+    /// A PEM file of certificates, or a directory of them, to verify HTTPS
+    /// servers against, or `None` for the system's certificates.
+    pub ca_bundle: Option<PathBuf>,
+    /// Whether to hide the user's global, XDG, and system git configuration,
+    /// by pointing their search paths at an empty location.
+    pub isolate_config: bool,
+}
+
+/// The options set in this process, once they are.
+static APPLIED: Mutex<Option<GitOptions>> = Mutex::new(None);
+
+/// Sets the process-wide libgit2 options, unless they are already set to the
+/// same options.
+///
+/// # Arguments
+///
+/// - `options` - The options to set.
+///
+/// # Returns
+///
+/// Returns `Ok(())` once the options are set, or a [`GitOptionsError`] if
+/// libgit2 rejects them or they were already set to different options.
+pub fn apply(options: &GitOptions) -> Result<(), GitOptionsError> {
+    let mut applied = APPLIED.lock().unwrap_or_else(|error| error.into_inner());
+    match &*applied {
+        Some(applied) if applied == options => return Ok(()),
+        Some(_) => return Err(GitOptionsError::AlreadySet),
+        None => {}
+    }
+
+    if let Some(ca_bundle) = &options.ca_bundle
+        && !ca_bundle.exists()
+    {
+        return Err(GitOptionsError::CaBundle {
+            path: ca_bundle.clone(),
+            error: git2::Error::from_str("no such file or directory"),
+        });
+    }
+    // SAFETY: the options are unset, and `APPLIED` is held until they are
+    // recorded as set, so no command of this crate has started a libgit2
+    // operation: each sets the options it needs before its first one.
+    // Options left half set by a failure are only ever set again here.
+    unsafe { set(options) }?;
+    *applied = Some(options.clone());
+    Ok(())
+}
+
+/// Sets the libgit2 options requested.
+///
+/// # Safety
+///
+/// No libgit2 operation may run in the process while the options change.
+unsafe fn set(options: &GitOptions) -> Result<(), GitOptionsError> {
+    let set_error = |error| GitOptionsError::Set { error };
+    if let Some(timeout) = options.connect_timeout {
+        unsafe { opts::set_server_connect_timeout_in_milliseconds(milliseconds(timeout)) }
+            .map_err(set_error)?;
+    }
+    if let Some(timeout) = options.server_timeout {
+        unsafe { opts::set_server_timeout_in_milliseconds(milliseconds(timeout)) }
+            .map_err(set_error)?;
+    }
+    if let Some(ca_bundle) = &options.ca_bundle {
+        unsafe {
+            if ca_bundle.is_dir() {
+                opts::set_ssl_cert_dir(ca_bundle)
+            } else {
+                opts::set_ssl_cert_file(ca_bundle)
+            }
+        }
+        .map_err(|error| GitOptionsError::CaBundle {
+            path: ca_bundle.clone(),
+            error,
+        })?;
+    }
+    if options.isolate_config {
+        for level in [ConfigLevel::Global, ConfigLevel::XDG, ConfigLevel::System] {
+            unsafe { opts::set_search_path(level, utils::NULL_DEVICE) }.map_err(set_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// Converts a timeout into the milliseconds libgit2 takes, saturating at the
+/// longest timeout it accepts.
+fn milliseconds(timeout: Duration) -> i32 {
+    i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the options can be set again to the same options, but not
+    /// changed once set.
+    #[test]
+    fn test_apply_once() {
+        let options = GitOptions {
+            connect_timeout: Some(Duration::from_secs(10)),
+            ..GitOptions::default()
+        };
+        apply(&options).unwrap();
+        apply(&options).unwrap();
+        assert!(matches!(
+            apply(&GitOptions::default()),
+            Err(GitOptionsError::AlreadySet)
+        ));
+    }
+}
@@ -0,0 +1,356 @@
+//! # GitHub API Client
+//!
+//! This module sends the requests of every command that queries the GitHub
+//! API, such as `enrich`, `discover`, and `audit`.  Requests are
+//! authenticated with `GITHUB_TOKEN` or the token in `corpus.toml`, which is
+//! only sent over HTTPS to the API's own host, wait out
+//! the rate limit when it is nearly reset instead of failing, and are
+//! revalidated with the `ETag` of the previous response, cached in the user's
+//! cache directory, since GitHub does not count unchanged responses against
+//! the rate limit.
+//!
+//! Set `GITHUB_API_URL`, as GitHub Actions does, to query a GitHub Enterprise
+//! server instead of `api.github.com`.
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{
+    StatusCode, Url,
+    blocking::{Client, Response},
+    header::{ETAG, HeaderMap, IF_NONE_MATCH, RETRY_AFTER},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+
+use crate::corpus::{config::AuthConfig, errors::GitHubError, utils};
+
+/// URL of the GitHub API, unless `GITHUB_API_URL` is set.
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Version of the GitHub REST API requested.
+const API_VERSION: &str = "2022-11-28";
+
+/// Directory within the user's cache directory holding cached responses.
+const CACHE_DIRECTORY: &str = "c-rust-program-pairs/github";
+
+/// Longest time to wait for the rate limit to reset before giving up.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Number of times a request is sent before a rate limit is reported.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// A response cached with its `ETag`.
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    /// The response's `ETag` header.
+    etag: String,
+    /// The response's body.
+    body: String,
+}
+
+/// A client of the GitHub API.
+pub struct GitHubClient {
+    /// The HTTP client used for requests.
+    client: Client,
+    /// URL of the API, without a trailing `/`.
+    api_url: String,
+    /// The token requests are authenticated with, if any.
+    token: Option<String>,
+    /// Directory of cached responses, or `None` if the platform has no cache
+    /// directory.
+    cache_directory: Option<PathBuf>,
+}
+
+impl GitHubClient {
+    /// Creates a client.
+    ///
+    /// # Arguments
+    ///
+    /// - `auth` - The API tokens to authenticate with.
+    ///
+    /// # Returns
+    ///
+    /// The [`GitHubClient`], or [`GitHubError::Client`] if the HTTP client
+    /// cannot be created.
+    pub fn new(auth: &AuthConfig) -> Result<Self, GitHubError> {
+        let api_url = env::var("GITHUB_API_URL")
+            .unwrap_or_else(|_| DEFAULT_API_URL.to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let token = auth.github_token();
+        if token.is_some() && !api_url.starts_with("https://") {
+            eprintln!("Not sending the GitHub token to '{api_url}', which is not HTTPS");
+        }
+        Ok(GitHubClient {
+            client: utils::http_client().map_err(|error| GitHubError::Client { error })?,
+            api_url,
+            token,
+            cache_directory: dirs::cache_dir().map(|directory| directory.join(CACHE_DIRECTORY)),
+        })
+    }
+
+    /// Sends a `GET` request to an API endpoint and deserializes its JSON
+    /// response.
+    ///
+    /// # Arguments
+    ///
+    /// - `endpoint` - The endpoint's path, such as `/repos/owner/repo`.
+    /// - `query` - Query parameters, which are URL-encoded.
+    ///
+    /// # Returns
+    ///
+    /// The deserialized response, or a [`GitHubError`] if the request fails,
+    /// returns an error status, stays rate limited, or returns unexpected
+    /// JSON.
+    pub fn get<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, GitHubError> {
+        let url = format!("{}{endpoint}", self.api_url);
+        let mut url = Url::parse(&url).map_err(|_| GitHubError::InvalidUrl { url })?;
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        let cache_file = self.cache_file(&url);
+        let cached: Option<CachedResponse> = cache_file
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok());
+
+        let response = self.send(&url, cached.as_ref().map(|cached| cached.etag.as_str()))?;
+        let body = match (response.status(), cached) {
+            (StatusCode::NOT_MODIFIED, Some(cached)) => cached.body,
+            _ => {
+                let etag = response
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .map(str::to_string);
+                let body = response.text().map_err(|error| GitHubError::Request {
+                    url: url.to_string(),
+                    error,
+                })?;
+                // The cache only saves requests, so failing to write it is
+                // not an error.
+                if let (Some(etag), Some(path)) = (etag, &cache_file) {
+                    let cached = CachedResponse {
+                        etag,
+                        body: body.clone(),
+                    };
+                    if let Ok(contents) = serde_json::to_string(&cached) {
+                        let _ = path
+                            .parent()
+                            .map(fs::create_dir_all)
+                            .map(|_| fs::write(path, contents));
+                    }
+                }
+                body
+            }
+        };
+        serde_json::from_str(&body).map_err(|error| GitHubError::Deserialize {
+            url: url.to_string(),
+            error,
+        })
+    }
+
+    /// Sends a request, waiting out the rate limit if it resets soon.
+    ///
+    /// # Arguments
+    ///
+    /// - `url` - The requested URL.
+    /// - `etag` - The `ETag` of the cached response, if any.
+    ///
+    /// # Returns
+    ///
+    /// The successful or `304 Not Modified` response, or a [`GitHubError`].
+    fn send(&self, url: &Url, etag: Option<&str>) -> Result<Response, GitHubError> {
+        let request_error = |error| GitHubError::Request {
+            url: url.to_string(),
+            error,
+        };
+        let mut attempt = 1;
+        loop {
+            let mut request = self
+                .client
+                .get(url.clone())
+                .header("Accept", "application/vnd.github+json")
+                .header("X-GitHub-Api-Version", API_VERSION);
+            if let Some(token) = self.token_for(url) {
+                request = request.bearer_auth(token);
+            }
+            if let Some(etag) = etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            let response = request.send().map_err(request_error)?;
+            let status = response.status();
+            if status.is_success() || status == StatusCode::NOT_MODIFIED {
+                return Ok(response);
+            }
+
+            let Some(wait) = rate_limit_wait(status, response.headers(), SystemTime::now()) else {
+                return Err(request_error(response.error_for_status().unwrap_err()));
+            };
+            if wait > MAX_RATE_LIMIT_WAIT || attempt == MAX_ATTEMPTS {
+                return Err(GitHubError::RateLimited {
+                    url: url.to_string(),
+                    wait: wait.as_secs(),
+                    authenticated: self.token_for(url).is_some(),
+                });
+            }
+            eprintln!(
+                "Reached the GitHub API rate limit; retrying in {}s",
+                wait.as_secs()
+            );
+            thread::sleep(wait);
+            attempt += 1;
+        }
+    }
+
+    /// Finds the token to authenticate a request with, which is only sent
+    /// over HTTPS to the host of the API, so that it never travels in the
+    /// clear or to another server.
+    ///
+    /// # Arguments
+    ///
+    /// - `url` - The requested URL.
+    ///
+    /// # Returns
+    ///
+    /// The token, or `None` if there is none or the URL must not get it.
+    fn token_for(&self, url: &Url) -> Option<&str> {
+        let api_url = Url::parse(&self.api_url).ok()?;
+        let is_api = url.scheme() == "https"
+            && url.host_str() == api_url.host_str()
+            && url.port_or_known_default() == api_url.port_or_known_default();
+        self.token.as_deref().filter(|_| is_api)
+    }
+
+    /// Finds the file a response from a URL is cached in.
+    ///
+    /// Responses to authenticated requests are cached apart from those to
+    /// anonymous requests and those of other tokens, since they can differ,
+    /// such as by listing private repositories.
+    fn cache_file(&self, url: &Url) -> Option<PathBuf> {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_str());
+        if let Some(token) = self.token_for(url) {
+            hasher.update(b"\0");
+            hasher.update(token);
+        }
+        let key = format!("{:x}", hasher.finalize());
+        Some(self.cache_directory.as_ref()?.join(format!("{key}.json")))
+    }
+}
+
+/// Decides how long to wait before retrying a rate limited request.
+///
+/// # Arguments
+///
+/// - `status` - The response's status.
+/// - `headers` - The response's headers.
+/// - `now` - The current time.
+///
+/// # Returns
+///
+/// How long to wait, from the `Retry-After` header of a secondary rate
+/// limit or the `X-RateLimit-Reset` time of an exhausted primary rate limit,
+/// or `None` if the response is not a rate limit.
+fn rate_limit_wait(status: StatusCode, headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.parse::<u64>().ok();
+    if let Some(seconds) = header(RETRY_AFTER.as_str()) {
+        return Some(Duration::from_secs(seconds));
+    }
+    if header("x-ratelimit-remaining") != Some(0) {
+        return None;
+    }
+    let reset = UNIX_EPOCH + Duration::from_secs(header("x-ratelimit-reset")?);
+    // Wait a second past the reset, so the retry lands after it.
+    let wait = reset.duration_since(now).unwrap_or_default();
+    Some(wait + Duration::from_secs(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    /// Tests that the wait is read from whichever rate limit was reached,
+    /// and that other errors are not retried.
+    #[test]
+    fn test_rate_limit_wait() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, HeaderValue::from_str(value).unwrap());
+            }
+            headers
+        };
+
+        let primary = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1010"),
+        ]);
+        assert_eq!(
+            Some(Duration::from_secs(11)),
+            rate_limit_wait(StatusCode::FORBIDDEN, &primary, now)
+        );
+        let secondary = headers(&[("retry-after", "30"), ("x-ratelimit-remaining", "12")]);
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            rate_limit_wait(StatusCode::TOO_MANY_REQUESTS, &secondary, now)
+        );
+
+        let forbidden = headers(&[("x-ratelimit-remaining", "12")]);
+        assert_eq!(
+            None,
+            rate_limit_wait(StatusCode::FORBIDDEN, &forbidden, now)
+        );
+        assert_eq!(None, rate_limit_wait(StatusCode::NOT_FOUND, &primary, now));
+    }
+
+    /// Tests that the token is only sent over HTTPS to the API's host, and
+    /// that responses are cached per token.
+    #[test]
+    fn test_token_for() {
+        let client = |token: Option<&str>| GitHubClient {
+            client: utils::http_client().unwrap(),
+            api_url: DEFAULT_API_URL.to_string(),
+            token: token.map(str::to_string),
+            cache_directory: Some(PathBuf::from("cache")),
+        };
+        let url = |url: &str| Url::parse(url).unwrap();
+        let authenticated = client(Some("secret"));
+        let api = url("https://api.github.com/repos/owner/repo");
+        assert_eq!(Some("secret"), authenticated.token_for(&api));
+        for other in [
+            "http://api.github.com/repos/owner/repo",
+            "https://api.github.com:8443/repos/owner/repo",
+            "https://example.com/repos/owner/repo",
+        ] {
+            assert_eq!(None, authenticated.token_for(&url(other)), "{other}");
+        }
+
+        let anonymous = client(None);
+        assert_eq!(None, anonymous.token_for(&api));
+        assert_ne!(anonymous.cache_file(&api), authenticated.cache_file(&api));
+        assert_ne!(
+            client(Some("other")).cache_file(&api),
+            authenticated.cache_file(&api)
+        );
+        assert_eq!(
+            anonymous.cache_file(&url("http://api.github.com/")),
+            authenticated.cache_file(&url("http://api.github.com/"))
+        );
+    }
+}
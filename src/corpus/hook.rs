@@ -0,0 +1,74 @@
+//! # Post-Download Hooks
+//!
+//! This module runs the command given with `--hook` inside each program pair
+//! directory right after the pair is downloaded, such as to format the Rust
+//! sources or generate tags.  The command's output is captured so that it
+//! does not garble the progress bars, and is shown only if the command fails.
+
+use std::{path::Path, process::Command};
+
+use crate::corpus::errors::DownloaderError;
+
+/// Number of lines of a failed hook's output included in its error.
+const MAX_OUTPUT_LINES: usize = 20;
+
+/// Runs a hook command in a downloaded program pair's directory.
+///
+/// The command is run by the system shell, `sh -c` or `cmd /C` on Windows,
+/// with the pair's name in the `PROGRAM_NAME` environment variable.
+///
+/// # Arguments
+///
+/// - `command` - The command to run.
+/// - `program_name` - Name of the downloaded program pair.
+/// - `pair_directory` - The program pair's directory, in which the command
+///   runs.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the command succeeds, or [`DownloaderError::Hook`]
+/// with the end of its output if it cannot be run or fails.
+pub fn run(
+    command: &str,
+    program_name: &str,
+    pair_directory: &Path,
+) -> Result<(), DownloaderError> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let hook_error = |status: String, output: String| DownloaderError::Hook {
+        command: command.to_string(),
+        status,
+        output,
+    };
+
+    let output = shell
+        .arg(command)
+        .current_dir(pair_directory)
+        .env("PROGRAM_NAME", program_name)
+        .output()
+        .map_err(|error| hook_error(format!("could not be run: {error}"), String::new()))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    // Standard output and error are shown together, since the command's
+    // error may be on either.
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let lines: Vec<&str> = text.lines().collect();
+    let tail = lines[lines.len().saturating_sub(MAX_OUTPUT_LINES)..]
+        .iter()
+        .map(|line| format!("\n    {line}"))
+        .collect();
+    Err(hook_error(output.status.to_string(), tail))
+}
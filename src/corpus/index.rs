@@ -0,0 +1,483 @@
+//! # SQLite Index
+//!
+//! This module loads the metadata, provenance, and statistics of the corpus
+//! into a SQLite database, so the corpus can be queried with SQL.  It is only
+//! built with the `index` feature, which bundles SQLite.  The database is
+//! rebuilt from scratch every time, into a temporary file that replaces the
+//! previous database once complete, and holds four tables:
+//!
+//! - `pairs` - Every program pair in the metadata or in `program_pairs/`.
+//! - `programs` - The C and Rust program of each pair.
+//! - `files` - Every file copied into a downloaded program.
+//! - `commits` - The repository commits that files were copied from.
+//!
+//! ```sql
+//! SELECT pairs.program_name, SUM(files.tokens)
+//! FROM pairs
+//! JOIN programs ON programs.program_name = pairs.program_name
+//! JOIN files ON files.program_id = programs.id
+//! WHERE programs.language = 'rust'
+//! GROUP BY pairs.program_name;
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, Transaction, params};
+
+use crate::{
+    corpus::{
+        errors::IndexError,
+        manifest::{Manifest, ProgramManifest},
+        parser,
+        provenance::{FileProvenance, Provenance},
+        schema::{Language, Program, ProgramPair},
+        skiplist::SkipList,
+        stats::{self, Analyses, PairStats, ProgramStats},
+    },
+    paths::{
+        DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// The tables of the index.
+const SCHEMA: &str = "
+CREATE TABLE pairs (
+    program_name TEXT PRIMARY KEY,
+    program_description TEXT,
+    translation_tools TEXT,
+    feature_relationship TEXT,
+    downloaded INTEGER NOT NULL,
+    skip_reason TEXT
+);
+
+CREATE TABLE programs (
+    id INTEGER PRIMARY KEY,
+    program_name TEXT NOT NULL REFERENCES pairs (program_name),
+    language TEXT NOT NULL,
+    repository_url TEXT NOT NULL,
+    mirror_url TEXT,
+    documentation_url TEXT,
+    license TEXT,
+    code_lines INTEGER,
+    comment_lines INTEGER,
+    blank_lines INTEGER,
+    tokens INTEGER,
+    UNIQUE (program_name, language)
+);
+
+CREATE TABLE commits (
+    id INTEGER PRIMARY KEY,
+    repository_url TEXT NOT NULL,
+    sha TEXT NOT NULL,
+    UNIQUE (repository_url, sha)
+);
+
+CREATE TABLE files (
+    id INTEGER PRIMARY KEY,
+    program_id INTEGER NOT NULL REFERENCES programs (id),
+    path TEXT NOT NULL,
+    source_path TEXT,
+    commit_id INTEGER REFERENCES commits (id),
+    code_lines INTEGER,
+    comment_lines INTEGER,
+    blank_lines INTEGER,
+    tokens INTEGER
+);
+";
+
+/// A program pair to index, from its metadata, its download, or both.
+#[derive(Default)]
+struct IndexedPair {
+    /// The pair's metadata, if it is still in a metadata file.
+    metadata: Option<ProgramPair>,
+    /// The pair's manifest, if it was downloaded.
+    manifest: Option<Manifest>,
+    /// The pair's provenance, if it was downloaded.
+    provenance: Option<Provenance>,
+    /// The pair's statistics, if it was downloaded.
+    stats: Option<PairStats>,
+}
+
+/// Builds a SQLite database indexing the metadata, provenance, and
+/// statistics of every program pair, replacing the database if it exists.
+///
+/// The previous database is kept if the new one cannot be built.
+///
+/// # Arguments
+///
+/// - `database` - Path of the database file.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`IndexError`] on failure.
+pub fn index(database: &Path) -> Result<(), IndexError> {
+    let mut pairs: BTreeMap<String, IndexedPair> = BTreeMap::new();
+    for pair in metadata_pairs()? {
+        let program_name = pair.program_name.clone();
+        pairs.entry(program_name).or_default().metadata = Some(pair);
+    }
+    let program_pairs = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    for pair_stats in stats::collect_stats(program_pairs, Analyses::default())
+        .map_err(|error| IndexError::Stats { error })?
+    {
+        let pair_directory = program_pairs.join(&pair_stats.program_name);
+        let pair = pairs.entry(pair_stats.program_name.clone()).or_default();
+        pair.manifest = Manifest::load(&pair_directory);
+        pair.provenance = Provenance::load(&pair_directory);
+        pair.stats = Some(pair_stats);
+    }
+    let skiplist = SkipList::load().map_err(|error| IndexError::SkipList { error })?;
+
+    let files = replace_database(database, |path| {
+        let mut connection = Connection::open(path)?;
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(SCHEMA)?;
+        let mut files = 0;
+        for (program_name, pair) in &pairs {
+            files += insert_pair(&transaction, program_name, pair, &skiplist)?;
+        }
+        transaction.commit()?;
+        Ok(files)
+    })?;
+
+    println!(
+        "Indexed {} program pairs and {} files into '{}'",
+        pairs.len(),
+        files,
+        database.display()
+    );
+    Ok(())
+}
+
+/// Builds a database in a temporary file beside it, and moves it into place
+/// once complete, so that a failed build leaves the previous database as it
+/// was.
+///
+/// # Arguments
+///
+/// - `database` - Path of the database file.
+/// - `build` - Builds the database at the path it is given.
+///
+/// # Returns
+///
+/// What `build` returns on success, or [`IndexError`] on failure, in which
+/// case the temporary file is removed.
+fn replace_database<T>(
+    database: &Path,
+    build: impl FnOnce(&Path) -> rusqlite::Result<T>,
+) -> Result<T, IndexError> {
+    let mut name = OsString::from(".");
+    name.push(database.file_name().unwrap_or_default());
+    name.push(".tmp");
+    let temporary = database.with_file_name(name);
+    // A build that was killed leaves its temporary file behind.
+    remove_if_exists(&temporary)?;
+
+    let result = build(&temporary)
+        .map_err(|error| IndexError::Database {
+            path: database.to_path_buf(),
+            error,
+        })
+        .and_then(|value| {
+            fs::rename(&temporary, database).map_err(|error| IndexError::IoReplace {
+                path: database.to_path_buf(),
+                error,
+            })?;
+            Ok(value)
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Removes a file, if it exists.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`IndexError::IoDelete`] on failure.
+fn remove_if_exists(path: &Path) -> Result<(), IndexError> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(IndexError::IoDelete {
+            path: PathBuf::from(path),
+            error,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Parses every metadata file.
+///
+/// Files that fail to parse are reported and skipped, so their pairs are
+/// only indexed if they were downloaded.
+///
+/// # Returns
+///
+/// Every program pair in the metadata, or [`IndexError`] if a metadata
+/// directory cannot be read.
+fn metadata_pairs() -> Result<Vec<ProgramPair>, IndexError> {
+    let mut pairs = Vec::new();
+    for directory in [
+        DEMO_METADATA_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+    ] {
+        let metadata_files =
+            parser::parse_directory(Path::new(directory)).map_err(|error| IndexError::Parse {
+                path: directory.into(),
+                error,
+            })?;
+        for (path, metadata) in metadata_files {
+            match metadata {
+                Ok(metadata) => pairs.extend(metadata.pairs),
+                Err(error) => eprintln!("Failed to parse '{}': {}", path.display(), error),
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// Inserts a program pair with its programs and files.
+///
+/// # Returns
+///
+/// The number of files inserted.
+fn insert_pair(
+    transaction: &Transaction,
+    program_name: &str,
+    pair: &IndexedPair,
+    skiplist: &SkipList,
+) -> rusqlite::Result<usize> {
+    let metadata = pair.metadata.as_ref();
+    let translation_tools = metadata
+        .map(|metadata| serde_json::to_string(&metadata.translation_tools).unwrap_or_default());
+    transaction.execute(
+        "INSERT INTO pairs (program_name, program_description, translation_tools, \
+         feature_relationship, downloaded, skip_reason) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            program_name,
+            metadata.map(|metadata| &metadata.program_description),
+            translation_tools,
+            metadata.map(|metadata| metadata.feature_relationship.to_str()),
+            pair.manifest.is_some(),
+            skiplist.get(program_name).map(|skip| skip.to_string()),
+        ],
+    )?;
+
+    let mut files = 0;
+    let stats = pair.stats.as_ref();
+    let sides = [
+        (
+            Language::C,
+            metadata.map(|metadata| &metadata.c_program),
+            stats.map(|stats| &stats.c_program),
+        ),
+        (
+            Language::Rust,
+            metadata.map(|metadata| &metadata.rust_program),
+            stats.map(|stats| &stats.rust_program),
+        ),
+    ];
+    for (language, program, program_stats) in sides {
+        let program_manifest = pair
+            .manifest
+            .as_ref()
+            .map(|manifest| manifest.program(&language));
+        // A pair in neither the metadata nor a manifest has no known
+        // repository, so only its files are missing.
+        let Some(program_id) = insert_program(
+            transaction,
+            program_name,
+            &language,
+            program,
+            program_manifest,
+            program_stats,
+        )?
+        else {
+            continue;
+        };
+        let directory = program_manifest
+            .and_then(|manifest| manifest.directory.as_deref())
+            .unwrap_or(language.program_directory());
+        files += insert_files(
+            transaction,
+            program_id,
+            directory,
+            program_stats,
+            pair.provenance.as_ref(),
+        )?;
+    }
+    Ok(files)
+}
+
+/// Inserts the C or Rust program of a pair.
+///
+/// # Returns
+///
+/// The program's row ID, or `None` if neither its metadata nor its manifest
+/// is known.
+fn insert_program(
+    transaction: &Transaction,
+    program_name: &str,
+    language: &Language,
+    program: Option<&Program>,
+    manifest: Option<&ProgramManifest>,
+    stats: Option<&ProgramStats>,
+) -> rusqlite::Result<Option<i64>> {
+    let Some(repository_url) = program
+        .map(|program| program.repository_url.as_str())
+        .or(manifest.map(|manifest| manifest.repository_url.as_str()))
+    else {
+        return Ok(None);
+    };
+    transaction.execute(
+        "INSERT INTO programs (program_name, language, repository_url, mirror_url, \
+         documentation_url, license, code_lines, comment_lines, blank_lines, tokens) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            program_name,
+            program
+                .map_or(language, |program| &program.language)
+                .to_str(),
+            repository_url,
+            manifest.and_then(|manifest| manifest.mirror_url.as_ref()),
+            program.map(|program| program.documentation_url.as_str()),
+            manifest.and_then(|manifest| manifest.license.as_ref()),
+            stats.map(|stats| stats.lines.code),
+            stats.map(|stats| stats.lines.comment),
+            stats.map(|stats| stats.lines.blank),
+            stats.map(|stats| stats.tokens),
+        ],
+    )?;
+    Ok(Some(transaction.last_insert_rowid()))
+}
+
+/// Inserts the files copied into a downloaded program, with the commit each
+/// was copied from.
+///
+/// Source files are inserted with their statistics.  Other copied files,
+/// such as license files, are only known from the provenance file and are
+/// inserted without statistics.  `program_directory` is the name of the
+/// program's directory within its pair's directory.
+///
+/// # Returns
+///
+/// The number of files inserted.
+fn insert_files(
+    transaction: &Transaction,
+    program_id: i64,
+    program_directory: &str,
+    stats: Option<&ProgramStats>,
+    provenance: Option<&Provenance>,
+) -> rusqlite::Result<usize> {
+    let mut origins: HashMap<&str, &FileProvenance> = provenance
+        .map(|provenance| {
+            provenance
+                .files
+                .iter()
+                .filter(|file| {
+                    file.path
+                        .strip_prefix(program_directory)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                })
+                .map(|file| (file.path.as_str(), file))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut files = 0;
+    for file in stats.map_or(&[][..], |stats| &stats.files) {
+        let path = Path::new(program_directory).join(&file.path);
+        let path = path.to_string_lossy().replace('\\', "/");
+        let origin = origins.remove(path.as_str());
+        let commit_id = origin
+            .map(|origin| insert_commit(transaction, origin))
+            .transpose()?;
+        transaction.execute(
+            "INSERT INTO files (program_id, path, source_path, commit_id, code_lines, \
+             comment_lines, blank_lines, tokens) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                program_id,
+                path,
+                origin.map(|origin| &origin.source_path),
+                commit_id,
+                file.lines.code,
+                file.lines.comment,
+                file.lines.blank,
+                file.tokens,
+            ],
+        )?;
+        files += 1;
+    }
+
+    let mut others: Vec<&FileProvenance> = origins.into_values().collect();
+    others.sort_by(|a, b| a.path.cmp(&b.path));
+    for origin in others {
+        let commit_id = insert_commit(transaction, origin)?;
+        transaction.execute(
+            "INSERT INTO files (program_id, path, source_path, commit_id) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![program_id, origin.path, origin.source_path, commit_id],
+        )?;
+        files += 1;
+    }
+    Ok(files)
+}
+
+/// Inserts the commit a file was copied from, unless it was already
+/// inserted.
+///
+/// # Returns
+///
+/// The commit's row ID.
+fn insert_commit(transaction: &Transaction, origin: &FileProvenance) -> rusqlite::Result<i64> {
+    transaction.execute(
+        "INSERT OR IGNORE INTO commits (repository_url, sha) VALUES (?1, ?2)",
+        params![origin.repository_url, origin.commit],
+    )?;
+    transaction.query_row(
+        "SELECT id FROM commits WHERE repository_url = ?1 AND sha = ?2",
+        params![origin.repository_url, origin.commit],
+        |row| row.get(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that a built database replaces the previous one, and that a
+    /// failed build keeps it and removes its temporary file.
+    #[test]
+    fn test_replace_database() {
+        let directory = tempfile::tempdir().unwrap();
+        let database = directory.path().join("corpus.db");
+        fs::write(&database, "previous").unwrap();
+
+        let result = replace_database(&database, |path| {
+            fs::write(path, "partial").unwrap();
+            Err::<(), _>(rusqlite::Error::InvalidQuery)
+        });
+        assert!(matches!(result, Err(IndexError::Database { .. })));
+        assert_eq!("previous", fs::read_to_string(&database).unwrap());
+        assert!(!directory.path().join(".corpus.db.tmp").exists());
+
+        replace_database(&database, |path| {
+            let connection = Connection::open(path)?;
+            connection.execute_batch(SCHEMA)
+        })
+        .unwrap();
+        let connection = Connection::open(&database).unwrap();
+        let pairs: i64 = connection
+            .query_row("SELECT COUNT(*) FROM pairs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(0, pairs);
+        assert!(!directory.path().join(".corpus.db.tmp").exists());
+    }
+}
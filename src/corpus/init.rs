@@ -0,0 +1,89 @@
+//! # Corpus Layout
+//!
+//! This module checks that the corpus root holds a `metadata/` directory
+//! before commands that read metadata run, so that running in the wrong
+//! directory fails with what layout is expected rather than with a missing
+//! file.  `init` creates the layout: the metadata directories, the schema,
+//! and the demo metadata, leaving whatever already exists alone.
+
+use std::{env, fs};
+
+use crate::{
+    corpus::{effects::FileSystem, errors::InitError, parser, profile::Profile},
+    paths::{
+        self, DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, METADATA_DIRECTORY,
+        METADATA_SCHEMA_FILE, PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// Checks that the current directory is a corpus root, or that the
+/// directory given with `--metadata-dir` exists.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the metadata directory exists, or
+/// [`InitError::MissingMetadata`] if it does not.
+pub fn check_layout() -> Result<(), InitError> {
+    let directory = paths::metadata_path(METADATA_DIRECTORY);
+    if directory.is_dir() {
+        return Ok(());
+    }
+    Err(InitError::MissingMetadata {
+        directory: env::current_dir()
+            .unwrap_or_else(|_| ".".into())
+            .join(directory),
+    })
+}
+
+/// Creates the layout of a corpus root in the current directory, with the
+/// metadata in the directory given with `--metadata-dir`, if any.
+///
+/// The metadata directories are created empty, except `metadata/demo/`,
+/// which gets the demo metadata built into the binary, and the schema
+/// built into the binary is written to `metadata/metadata.schema.json` for
+/// editors to validate against.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`InitError`] on failure.
+pub fn init() -> Result<(), InitError> {
+    let mut created = false;
+    if Profile::Demo
+        .write_built_in_demo(&FileSystem::default())
+        .map_err(|error| InitError::Profile { error })?
+    {
+        println!(
+            "Created '{}/' with the demo metadata",
+            paths::metadata_path(DEMO_METADATA_DIRECTORY).display()
+        );
+        created = true;
+    }
+
+    for directory in [PROJECT_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY] {
+        let path = paths::metadata_path(directory);
+        if path.exists() {
+            continue;
+        }
+        fs::create_dir_all(&path).map_err(|error| InitError::IoWrite {
+            path: path.clone(),
+            error,
+        })?;
+        println!("Created '{}/'", path.display());
+        created = true;
+    }
+
+    let schema = paths::metadata_path(METADATA_SCHEMA_FILE);
+    if !schema.exists() {
+        fs::write(&schema, parser::BUILT_IN_SCHEMA).map_err(|error| InitError::IoWrite {
+            path: schema.clone(),
+            error,
+        })?;
+        println!("Created '{}'", schema.display());
+        created = true;
+    }
+
+    if !created {
+        println!("The corpus layout already exists");
+    }
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! # License Detection
+//!
+//! This module finds the license files of an upstream repository and
+//! identifies their licenses by SPDX identifier.  Redistributing the corpus
+//! requires shipping each program's license alongside its sources.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// File name prefixes, in uppercase, of license files.
+const LICENSE_FILE_PREFIXES: [&str; 3] = ["LICENSE", "LICENCE", "COPYING"];
+
+/// Phrases identifying a license, checked in order, paired with the SPDX
+/// identifier of the license.  Every phrase of an entry must appear in the
+/// license text, compared case-insensitively with whitespace collapsed.
+///
+/// More specific licenses come first; for example, the LGPL text mentions
+/// the GPL, and the BSD-3-Clause text contains the BSD-2-Clause text.
+const LICENSE_PHRASES: [(&[&str], &str); 14] = [
+    (&["gnu affero general public license"], "AGPL-3.0-or-later"),
+    (
+        &["gnu lesser general public license", "version 3"],
+        "LGPL-3.0-or-later",
+    ),
+    (
+        &["gnu lesser general public license", "version 2.1"],
+        "LGPL-2.1-or-later",
+    ),
+    (&["gnu library general public license"], "LGPL-2.0-or-later"),
+    (
+        &["gnu general public license", "version 3"],
+        "GPL-3.0-or-later",
+    ),
+    (
+        &["gnu general public license", "version 2"],
+        "GPL-2.0-or-later",
+    ),
+    (&["apache license", "version 2.0"], "Apache-2.0"),
+    (&["mozilla public license", "2.0"], "MPL-2.0"),
+    (&["boost software license"], "BSL-1.0"),
+    (
+        &["this is free and unencumbered software released into the public domain"],
+        "Unlicense",
+    ),
+    (
+        &["permission to use, copy, modify, and/or distribute this software for any purpose"],
+        "ISC",
+    ),
+    (&["permission is hereby granted, free of charge"], "MIT"),
+    (
+        &[
+            "redistribution and use in source and binary forms",
+            "neither the name",
+        ],
+        "BSD-3-Clause",
+    ),
+    (
+        &["redistribution and use in source and binary forms"],
+        "BSD-2-Clause",
+    ),
+];
+
+/// Finds the license files at the root of a repository.
+///
+/// License files are those whose names start with `LICENSE`, `LICENCE`, or
+/// `COPYING` in any case, such as `LICENSE-MIT` or `COPYING.LIB`.
+///
+/// # Arguments
+///
+/// - `repository_directory` - The root of a cloned repository.
+///
+/// # Returns
+///
+/// The paths of the license files, sorted by name.
+pub fn find_license_files(repository_directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = repository_directory.read_dir() else {
+        return Vec::new();
+    };
+
+    let mut license_files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_uppercase();
+            LICENSE_FILE_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    license_files.sort();
+    license_files
+}
+
+/// Identifies the licenses of a set of license files.
+///
+/// Repositories with several license files, such as the `LICENSE-MIT` and
+/// `LICENSE-APACHE` files common in Rust projects, are typically dual
+/// licensed, so distinct licenses are combined with `OR`.
+///
+/// # Arguments
+///
+/// - `license_files` - Paths to license files.
+///
+/// # Returns
+///
+/// An SPDX license expression such as "MIT OR Apache-2.0", or `None` if no
+/// license could be identified.
+pub fn detect_license(license_files: &[PathBuf]) -> Option<String> {
+    let mut identifiers: Vec<&str> = Vec::new();
+    for path in license_files {
+        if let Ok(bytes) = fs::read(path)
+            && let Some(identifier) = detect_spdx(&String::from_utf8_lossy(&bytes))
+            && !identifiers.contains(&identifier)
+        {
+            identifiers.push(identifier);
+        }
+    }
+    (!identifiers.is_empty()).then(|| identifiers.join(" OR "))
+}
+
+/// Identifies a license from its text.
+///
+/// # Arguments
+///
+/// - `text` - The contents of a license file.
+///
+/// # Returns
+///
+/// The SPDX identifier of the license, or `None` if it is not recognized.
+pub fn detect_spdx(text: &str) -> Option<&'static str> {
+    let text = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    LICENSE_PHRASES
+        .iter()
+        .find(|(phrases, _)| phrases.iter().all(|phrase| text.contains(phrase)))
+        .map(|(_, identifier)| *identifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that common licenses are identified from their text.
+    #[test]
+    fn test_detect_spdx() {
+        assert_eq!(
+            Some("GPL-3.0-or-later"),
+            detect_spdx("GNU GENERAL PUBLIC LICENSE\n   Version 3, 29 June 2007")
+        );
+        assert_eq!(
+            Some("LGPL-2.1-or-later"),
+            detect_spdx(
+                "GNU LESSER GENERAL PUBLIC LICENSE\nVersion 2.1, February 1999\n\
+                 ... the GNU General Public License ..."
+            )
+        );
+        assert_eq!(
+            Some("MIT"),
+            detect_spdx("MIT License\n\nPermission is hereby granted, free of\ncharge, to any")
+        );
+        assert_eq!(
+            Some("Apache-2.0"),
+            detect_spdx("Apache License\nVersion 2.0, January 2004")
+        );
+        assert_eq!(None, detect_spdx("All rights reserved."));
+    }
+}
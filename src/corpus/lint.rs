@@ -0,0 +1,379 @@
+//! # Metadata Linting
+//!
+//! This module checks metadata files against the JSON schema and prints a
+//! consolidated report of every violation, and of every value malformed in a
+//! way the schema cannot express, so contributors can fix them all in one
+//! pass.  Repository URLs spelled differently across the metadata,
+//! such as with and without a `.git` suffix, are flagged too, as are
+//! translation tools that are not recognized.  With `--semantic`, downloaded
+//! pairs whose C and Rust programs look like different tools are flagged as
+//! well.  With `--json`, the problems are printed as a JSON object instead.
+
+use std::{
+    collections::BTreeMap,
+    iter,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    corpus::{
+        errors::{LintError, ParserError, ValidationIssue},
+        output,
+        parser::{self, MetadataFiles},
+        schema::TranslationTool,
+        semantic, utils,
+    },
+    paths::{
+        self, DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// Maximum length of an offending value printed in the report.
+const MAX_VALUE_LENGTH: usize = 80;
+
+/// The spellings of repositories, by normalized URL, with the pairs using
+/// each spelling.
+type InconsistentUrls<'a> = BTreeMap<String, BTreeMap<&'a str, Vec<String>>>;
+
+/// Every problem found by [`lint`], as printed with `--json`.
+#[derive(Debug, Default, Serialize)]
+struct LintResult<'a> {
+    /// Number of metadata files checked.
+    checked_files: usize,
+    /// The metadata files that failed to parse.
+    invalid_files: Vec<InvalidFile<'a>>,
+    /// The repositories whose URL is spelled in more than one way.
+    inconsistent_urls: InconsistentUrls<'a>,
+    /// The pairs with unrecognized translation tools, with their spellings.
+    unrecognized_tools: Vec<PairProblems<'a>>,
+    /// The pairs whose C and Rust programs look like different tools, with
+    /// why; empty unless `--semantic` was given.
+    suspicious_pairs: Vec<PairProblems<'a>>,
+    /// Number of pairs whose semantic check was skipped because they are
+    /// not downloaded.
+    unchecked_pairs: usize,
+}
+
+/// A metadata file that failed to parse.
+#[derive(Debug, Serialize)]
+struct InvalidFile<'a> {
+    /// Path of the file.
+    path: &'a Path,
+    /// Why the file failed to parse.
+    message: String,
+    /// The file's schema violations, empty if it failed for another reason,
+    /// such as malformed JSON.
+    issues: &'a [ValidationIssue],
+    /// The file's values that are malformed in ways the schema cannot
+    /// express, such as a program name leading out of `program_pairs/`.
+    invalid_values: Vec<InvalidValue<'a>>,
+}
+
+/// A value of a metadata file that is malformed in a way the JSON schema
+/// cannot express.
+#[derive(Debug, Serialize)]
+struct InvalidValue<'a> {
+    /// Name of the program pair, as written in the metadata.
+    program_name: &'a str,
+    /// Why the value is malformed.
+    message: String,
+}
+
+/// The problems with one program pair of a metadata file.
+#[derive(Debug, Serialize)]
+struct PairProblems<'a> {
+    /// Path of the metadata file.
+    path: &'a Path,
+    /// Name of the program pair.
+    program_name: &'a str,
+    /// The problems found.
+    problems: Vec<String>,
+}
+
+/// Validates metadata files and prints every problem found.
+///
+/// # Arguments
+///
+/// - `paths` - Metadata files or directories of them to check; when empty,
+///   every metadata directory is checked.
+/// - `semantic` - Whether to also check that the C and Rust programs of each
+///   downloaded pair implement the same tool.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if every file is valid, [`LintError::Invalid`] if any
+/// file is invalid, and another [`LintError`] on failure.
+pub fn lint(paths: &[PathBuf], semantic: bool) -> Result<(), LintError> {
+    let paths = if paths.is_empty() {
+        [
+            PROJECT_METADATA_DIRECTORY,
+            INDIVIDUAL_METADATA_DIRECTORY,
+            DEMO_METADATA_DIRECTORY,
+        ]
+        .map(paths::metadata_path)
+        .to_vec()
+    } else {
+        paths.to_vec()
+    };
+
+    let mut metadata_files = MetadataFiles::new();
+    for path in &paths {
+        if path.is_dir() {
+            metadata_files.extend(parser::parse_directory(path).map_err(|error| {
+                LintError::Parse {
+                    path: path.clone(),
+                    error,
+                }
+            })?);
+        } else {
+            metadata_files.push((path.clone(), parser::parse(path)));
+        }
+    }
+
+    let mut result = LintResult {
+        checked_files: metadata_files.len(),
+        ..LintResult::default()
+    };
+    for (path, metadata) in &metadata_files {
+        if let Err(error) = metadata {
+            result.invalid_files.push(InvalidFile {
+                path,
+                message: error.to_string(),
+                issues: match error {
+                    ParserError::Validation { issues } => issues,
+                    _ => &[],
+                },
+                invalid_values: match error {
+                    ParserError::InvalidValue { issues } => issues
+                        .iter()
+                        .map(|issue| InvalidValue {
+                            program_name: &issue.program_name,
+                            message: issue.error.to_string(),
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                },
+            });
+        }
+    }
+    result.inconsistent_urls = inconsistent_urls(&metadata_files);
+    result.unrecognized_tools = unrecognized_tools(&metadata_files);
+    if semantic {
+        (result.suspicious_pairs, result.unchecked_pairs) = suspicious_pairs(&metadata_files);
+    }
+
+    if output::json() {
+        output::print_json(&result);
+    } else {
+        print_result(&result);
+    }
+
+    let invalid_files = result.invalid_files.len();
+    if invalid_files > 0 {
+        return Err(LintError::Invalid {
+            invalid_files,
+            total_files: metadata_files.len(),
+        });
+    }
+    if !result.inconsistent_urls.is_empty() {
+        return Err(LintError::InconsistentUrls {
+            repositories: result.inconsistent_urls.len(),
+        });
+    }
+    if !result.unrecognized_tools.is_empty() {
+        return Err(LintError::UnrecognizedTools {
+            count: result.unrecognized_tools.len(),
+        });
+    }
+    if !result.suspicious_pairs.is_empty() {
+        return Err(LintError::SuspiciousPairs {
+            count: result.suspicious_pairs.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Finds the pairs of the valid metadata files whose C and Rust programs
+/// look like different tools, with why.
+///
+/// # Returns
+///
+/// The suspicious pairs, and how many pairs could not be checked because
+/// they have not been downloaded.
+fn suspicious_pairs(metadata_files: &MetadataFiles) -> (Vec<PairProblems<'_>>, usize) {
+    let mut suspicious = Vec::new();
+    let mut unchecked = 0;
+    for (path, metadata) in metadata_files {
+        let Ok(metadata) = metadata else {
+            continue;
+        };
+        for pair in &metadata.pairs {
+            let Some(problems) = semantic::check(pair) else {
+                unchecked += 1;
+                continue;
+            };
+            if !problems.is_empty() {
+                suspicious.push(PairProblems {
+                    path,
+                    program_name: &pair.program_name,
+                    problems,
+                });
+            }
+        }
+    }
+    (suspicious, unchecked)
+}
+
+/// Finds the translation tools of the valid metadata files that are not
+/// recognized, which fragment statistics if they misspell a known tool.
+///
+/// # Returns
+///
+/// The pairs with unrecognized translation tools, with their spellings.
+fn unrecognized_tools(metadata_files: &MetadataFiles) -> Vec<PairProblems<'_>> {
+    let mut unrecognized = Vec::new();
+    for (path, metadata) in metadata_files {
+        let Ok(metadata) = metadata else {
+            continue;
+        };
+        for pair in &metadata.pairs {
+            let tools: Vec<String> = pair
+                .translation_tools
+                .iter()
+                .filter_map(|tool| match tool {
+                    TranslationTool::Other(spelling) => Some(spelling.clone()),
+                    _ => None,
+                })
+                .collect();
+            if !tools.is_empty() {
+                unrecognized.push(PairProblems {
+                    path,
+                    program_name: &pair.program_name,
+                    problems: tools,
+                });
+            }
+        }
+    }
+    unrecognized
+}
+
+/// Finds the repositories whose URL is spelled in more than one way across
+/// the valid metadata files, with where each spelling is used.
+///
+/// # Returns
+///
+/// The spellings of each inconsistently spelled repository, by normalized
+/// URL, with the pairs using each spelling.
+fn inconsistent_urls(metadata_files: &MetadataFiles) -> InconsistentUrls<'_> {
+    // Spellings of each repository, and the pairs using each spelling.
+    let mut repositories: InconsistentUrls = BTreeMap::new();
+    for (path, metadata) in metadata_files {
+        let Ok(metadata) = metadata else {
+            continue;
+        };
+        for pair in &metadata.pairs {
+            for program in pair.programs() {
+                for url in iter::once(&program.repository_url).chain(&program.mirror_urls) {
+                    repositories
+                        .entry(utils::normalize_repository_url(url.as_str()))
+                        .or_default()
+                        .entry(url.as_str())
+                        .or_default()
+                        .push(format!("{} ({})", path.display(), pair.program_name));
+                }
+            }
+        }
+    }
+    repositories.retain(|_, spellings| spellings.len() >= 2);
+    repositories
+}
+
+/// Prints every problem found by [`lint`] as text.
+fn print_result(result: &LintResult) {
+    for file in &result.invalid_files {
+        print_problems(file);
+    }
+
+    for (repository, spellings) in &result.inconsistent_urls {
+        println!(
+            "Repository '{}' is spelled {} ways:",
+            repository,
+            spellings.len()
+        );
+        for (spelling, locations) in spellings {
+            println!("  {spelling}");
+            for location in locations {
+                println!("      {location}");
+            }
+        }
+    }
+
+    for pair in &result.unrecognized_tools {
+        println!(
+            "{} ({}): unrecognized translation tools '{}'; known tools are {}",
+            pair.path.display(),
+            pair.program_name,
+            pair.problems.join("', '"),
+            TranslationTool::KNOWN.join(", ")
+        );
+    }
+
+    for pair in &result.suspicious_pairs {
+        println!(
+            "{} ({}): suspicious pair",
+            pair.path.display(),
+            pair.program_name
+        );
+        for problem in &pair.problems {
+            println!("      {problem}");
+        }
+    }
+    if result.unchecked_pairs > 0 {
+        println!(
+            "Skipped the semantic check of {} pairs that are not downloaded",
+            result.unchecked_pairs
+        );
+    }
+
+    println!(
+        "Checked {} metadata files: {} invalid",
+        result.checked_files,
+        result.invalid_files.len()
+    );
+}
+
+/// Prints the problems with an invalid metadata file.
+///
+/// Schema violations are listed with the violated part of the schema and
+/// the offending value, and malformed values with their pair; other
+/// errors, such as malformed JSON, are printed as is.
+fn print_problems(file: &InvalidFile) {
+    if !file.invalid_values.is_empty() {
+        println!(
+            "{}: {} invalid values",
+            file.path.display(),
+            file.invalid_values.len()
+        );
+        for value in &file.invalid_values {
+            println!("  '{}': {}", value.program_name, value.message);
+        }
+        return;
+    }
+    if file.issues.is_empty() {
+        println!("{}: {}", file.path.display(), file.message);
+        return;
+    }
+
+    println!("{}: {} violations", file.path.display(), file.issues.len());
+    for issue in file.issues {
+        let mut value = issue.value.to_string();
+        if value.chars().count() > MAX_VALUE_LENGTH {
+            value = value.chars().take(MAX_VALUE_LENGTH).collect::<String>() + "...";
+        }
+        println!("  {issue}");
+        println!("      schema: {}", issue.schema_path);
+        println!("      value:  {value}");
+    }
+}
@@ -0,0 +1,192 @@
+//! # Program Pair Listing
+//!
+//! This module lists the program pairs in the metadata, optionally sorted by
+//! the popularity of their repositories as recorded by `enrich`, or narrowed
+//! down to the pairs with some tags, including the domains recorded by
+//! `analyze --classify`.  Pairs in the skip list are marked with the reason
+//! they are skipped, and pairs with several Rust rewrites with the labels of
+//! their variants.  With `--json`, the pairs are printed as a JSON object
+//! instead of a table.
+
+use std::{cmp::Reverse, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    corpus::{
+        analyze, enrich::EnrichedMetadata, errors::ListError, output, parser, schema::ProgramPair,
+        skiplist::SkipList,
+    },
+    paths::{INDIVIDUAL_METADATA_DIRECTORY, PROJECT_METADATA_DIRECTORY},
+};
+
+/// How the listed program pairs are ordered.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ListSort {
+    /// Alphabetically by program name.
+    Name,
+    /// By the combined stars of the C and Rust repositories, most first.
+    Stars,
+}
+
+/// A listed program pair, as printed with `--json`.
+#[derive(Debug, Serialize)]
+struct ListedPair<'a> {
+    /// Name of the program pair.
+    program_name: &'a str,
+    /// Description of the program pair.
+    program_description: &'a str,
+    /// Stars of the C repository, if it was enriched.
+    c_stars: Option<u64>,
+    /// Stars of the Rust repository, if it was enriched.
+    rust_stars: Option<u64>,
+    /// Tags of the program pair.
+    tags: &'a [String],
+    /// Labels of the pair's Rust variants, with the stars of each variant's
+    /// repository if it was enriched.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rust_variants: Vec<ListedVariant<'a>>,
+    /// Why the pair is skipped, if it is in the skip list.
+    skipped: Option<String>,
+}
+
+/// A Rust variant of a listed program pair, as printed with `--json`.
+#[derive(Debug, Serialize)]
+struct ListedVariant<'a> {
+    /// Label of the variant.
+    label: &'a str,
+    /// Stars of the variant's repository, if it was enriched.
+    stars: Option<u64>,
+}
+
+/// The program pairs printed by `list --json`.
+#[derive(Debug, Serialize)]
+struct ListResult<'a> {
+    /// The listed pairs, in the order requested.
+    pairs: Vec<ListedPair<'a>>,
+}
+
+/// Prints every program pair in the metadata.
+///
+/// Star counts come from `metadata/enriched/`; pairs whose repositories have
+/// not been enriched show `-` and are listed last when sorting by stars.
+///
+/// # Arguments
+///
+/// - `sort` - How to order the program pairs.
+/// - `tags` - Only list the pairs with at least one of these tags; every pair
+///   is listed if it is empty.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`ListError`] on failure.
+pub fn list(sort: ListSort, tags: &[String]) -> Result<(), ListError> {
+    let enriched =
+        EnrichedMetadata::load(Path::new("")).map_err(|error| ListError::Enriched { error })?;
+    let skiplist = SkipList::load().map_err(|error| ListError::SkipList { error })?;
+    // Pairs are only tagged with a domain once `analyze --classify` has
+    // recorded one.
+    let analyses = analyze::load_results().unwrap_or_default();
+
+    let mut pairs: Vec<ProgramPair> = Vec::new();
+    for directory in [PROJECT_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY] {
+        let metadata_files =
+            parser::parse_directory(Path::new(directory)).map_err(|error| ListError::Parse {
+                path: directory.into(),
+                error,
+            })?;
+        for (path, metadata) in metadata_files {
+            match metadata {
+                Ok(metadata) => pairs.extend(
+                    metadata
+                        .pairs
+                        .into_iter()
+                        .map(|mut pair| {
+                            analyze::add_domain_tag(&mut pair, &analyses);
+                            pair
+                        })
+                        .filter(|pair| pair.has_any_tag(tags)),
+                ),
+                Err(error) => eprintln!("Failed to parse '{}': {}", path.display(), error),
+            }
+        }
+    }
+
+    let stars = |url: &str| enriched.get(url).map(|info| info.stars);
+    let total_stars = |pair: &ProgramPair| match (
+        stars(pair.c_program.repository_url.as_str()),
+        stars(pair.rust_program.repository_url.as_str()),
+    ) {
+        (None, None) => None,
+        (c_stars, rust_stars) => Some(c_stars.unwrap_or(0) + rust_stars.unwrap_or(0)),
+    };
+
+    pairs.sort_by(|a, b| a.program_name.cmp(&b.program_name));
+    if let ListSort::Stars = sort {
+        // The sort is stable, so pairs with equal stars stay sorted by name.
+        pairs.sort_by_key(|pair| Reverse(total_stars(pair)));
+    }
+
+    if output::json() {
+        let pairs = pairs
+            .iter()
+            .map(|pair| ListedPair {
+                program_name: &pair.program_name,
+                program_description: &pair.program_description,
+                c_stars: stars(pair.c_program.repository_url.as_str()),
+                rust_stars: stars(pair.rust_program.repository_url.as_str()),
+                tags: &pair.tags,
+                rust_variants: pair
+                    .rust_variants
+                    .iter()
+                    .map(|variant| ListedVariant {
+                        label: &variant.label,
+                        stars: stars(variant.program.repository_url.as_str()),
+                    })
+                    .collect(),
+                skipped: skiplist
+                    .get(&pair.program_name)
+                    .map(|skip| skip.to_string()),
+            })
+            .collect();
+        output::print_json(&ListResult { pairs });
+        return Ok(());
+    }
+
+    let format_stars = |stars: Option<u64>| stars.map_or("-".to_string(), |s| s.to_string());
+    println!(
+        "{:<24} {:>8} {:>8}  Description",
+        "Pair", "C stars", "Rs stars"
+    );
+    for pair in &pairs {
+        let tags = if pair.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [tags: {}]", pair.tags.join(", "))
+        };
+        let variants = if pair.rust_variants.is_empty() {
+            String::new()
+        } else {
+            let labels: Vec<&str> = pair
+                .rust_variants
+                .iter()
+                .map(|variant| variant.label.as_str())
+                .collect();
+            format!(" [variants: {}]", labels.join(", "))
+        };
+        let skipped = skiplist
+            .get(&pair.program_name)
+            .map_or(String::new(), |skip| format!(" [skipped: {skip}]"));
+        println!(
+            "{:<24} {:>8} {:>8}  {}{}{}{}",
+            pair.program_name,
+            format_stars(stars(pair.c_program.repository_url.as_str())),
+            format_stars(stars(pair.rust_program.repository_url.as_str())),
+            pair.program_description,
+            variants,
+            tags,
+            skipped
+        );
+    }
+    Ok(())
+}
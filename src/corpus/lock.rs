@@ -0,0 +1,319 @@
+//! # Corpus Lock File
+//!
+//! This module reads and writes `corpus.lock`, which pins the commit every
+//! repository resolved to when it was first downloaded, much like
+//! `Cargo.lock` pins dependency versions.  Later downloads check out the
+//! locked commits, so everyone who downloads the corpus gets the same files
+//! even as upstream repositories move on; `update --refresh-lock` advances
+//! the locked commits deliberately.  A repository whose programs are
+//! downloaded at a `branch` is locked once for each branch.
+//!
+//! ```toml
+//! version = 1
+//!
+//! [[repository]]
+//! url = "github.com/uutils/coreutils"
+//! commit = "8d1e4b2f0c3a5e6d7b9a1c2e3f4d5b6a7c8e9f0a"
+//!
+//! [[repository]]
+//! url = "github.com/example/tool"
+//! branch = "rust-rewrite"
+//! commit = "0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e"
+//! ```
+
+use std::{
+    collections::{BTreeMap, btree_map::Entry},
+    fs,
+    path::Path,
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use git2::{FetchOptions, ProxyOptions, Repository};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{cache, errors::LockError, utils},
+    paths::LOCK_FILE,
+};
+
+/// Version of the lock file format.
+const LOCK_VERSION: u32 = 1;
+
+/// Comment written at the top of the lock file.
+const LOCK_HEADER: &str = "# Generated by `download`; run `update --refresh-lock` to advance the \
+                           locked commits.\n";
+
+/// Length of the abbreviated commit SHAs printed when refreshing.
+const SHORT_SHA_LENGTH: usize = 7;
+
+/// The contents of `corpus.lock`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LockFile {
+    /// Version of the lock file format.
+    version: u32,
+    /// The locked repositories, sorted by URL and branch.
+    #[serde(default, rename = "repository")]
+    repositories: Vec<LockedRepository>,
+}
+
+/// The locked commit of one repository.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LockedRepository {
+    /// The repository's normalized URL, such as `github.com/owner/repo`.
+    url: String,
+    /// The branch the commit was resolved on, or `None` for the default
+    /// branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    /// SHA of the locked commit.
+    commit: String,
+}
+
+/// The repository a commit is locked for: its normalized URL and branch, or
+/// `None` for its default branch.
+type LockKey = (String, Option<String>);
+
+/// The commits repositories are locked to.
+///
+/// The commits are behind a mutex, so that pairs downloaded concurrently can
+/// share the lock.
+#[derive(Debug, Default)]
+pub struct CorpusLock {
+    /// SHA of the locked commit of each repository and branch.
+    commits: Mutex<BTreeMap<LockKey, String>>,
+    /// Whether commits were locked since the lock file was loaded.
+    changed: AtomicBool,
+}
+
+impl CorpusLock {
+    /// Reads `corpus.lock`.
+    ///
+    /// # Arguments
+    ///
+    /// - `root` - The corpus root, or an empty path for the current
+    ///   directory.
+    ///
+    /// # Returns
+    ///
+    /// The [`CorpusLock`], empty if there is no lock file yet, or
+    /// [`LockError`] if it cannot be read or parsed.
+    pub fn load(root: &Path) -> Result<CorpusLock, LockError> {
+        let path = &root.join(LOCK_FILE);
+        if !path.exists() {
+            return Ok(CorpusLock::default());
+        }
+        let contents = fs::read_to_string(path).map_err(|error| LockError::IoRead {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let file: LockFile = toml::from_str(&contents).map_err(|error| LockError::Deserialize {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        if file.version != LOCK_VERSION {
+            return Err(LockError::UnsupportedVersion {
+                version: file.version,
+            });
+        }
+        Ok(CorpusLock {
+            commits: Mutex::new(
+                file.repositories
+                    .into_iter()
+                    .map(|repository| ((repository.url, repository.branch), repository.commit))
+                    .collect(),
+            ),
+            changed: AtomicBool::new(false),
+        })
+    }
+
+    /// Writes `corpus.lock` if commits were locked since it was loaded.
+    ///
+    /// # Arguments
+    ///
+    /// - `root` - The corpus root, or an empty path for the current
+    ///   directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success and [`LockError`] on failure.
+    pub fn save(&self, root: &Path) -> Result<(), LockError> {
+        if !self.changed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let file = LockFile {
+            version: LOCK_VERSION,
+            repositories: self
+                .commits()
+                .iter()
+                .map(|((url, branch), commit)| LockedRepository {
+                    url: url.clone(),
+                    branch: branch.clone(),
+                    commit: commit.clone(),
+                })
+                .collect(),
+        };
+        let contents = toml::to_string(&file).map_err(|error| LockError::Serialize { error })?;
+        let path = &root.join(LOCK_FILE);
+        fs::write(path, format!("{LOCK_HEADER}\n{contents}")).map_err(|error| LockError::IoWrite {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    /// Looks up the commit a repository is locked to.
+    ///
+    /// # Arguments
+    ///
+    /// - `repository_url` - The repository's URL, in any spelling.
+    /// - `branch` - The branch downloaded, or `None` for the default branch.
+    ///
+    /// # Returns
+    ///
+    /// SHA of the locked commit, or `None` if the repository is not locked.
+    pub fn commit(&self, repository_url: &str, branch: Option<&str>) -> Option<String> {
+        self.commits()
+            .get(&lock_key(repository_url, branch))
+            .cloned()
+    }
+
+    /// Locks a repository to a commit, if it is not locked yet.
+    ///
+    /// # Arguments
+    ///
+    /// - `repository_url` - The repository's URL, in any spelling.
+    /// - `branch` - The branch downloaded, or `None` for the default branch.
+    /// - `commit` - SHA of the commit the repository resolved to.
+    pub fn lock(&self, repository_url: &str, branch: Option<&str>, commit: &str) {
+        if let Entry::Vacant(entry) = self.commits().entry(lock_key(repository_url, branch)) {
+            entry.insert(commit.to_string());
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Locks the commits for reading or writing.
+    ///
+    /// A download that panicked while holding the commits cannot have left
+    /// them half-written, so a poisoned mutex is used as is.
+    fn commits(&self) -> MutexGuard<'_, BTreeMap<LockKey, String>> {
+        self.commits.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Builds the key a repository's commit is locked under.
+fn lock_key(repository_url: &str, branch: Option<&str>) -> LockKey {
+    (
+        utils::normalize_repository_url(repository_url),
+        branch.map(str::to_string),
+    )
+}
+
+/// Advances the locked commits of repositories to the latest commits of
+/// their default branches, or of the branches they are locked for.
+///
+/// The latest commit is fetched into each repository's cached clone, so the
+/// next `download` checks it out without fetching again.  Repositories that
+/// are not cached are unlocked instead, and locked when next downloaded.
+///
+/// # Arguments
+///
+/// - `repositories` - URLs of the repositories to refresh; every locked
+///   repository is refreshed if it is empty.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`LockError`] on failure.
+pub fn refresh(repositories: &[String]) -> Result<(), LockError> {
+    let lock = CorpusLock::load(Path::new(""))?;
+    let mut commits = lock.commits();
+    let selected: Vec<String> = repositories
+        .iter()
+        .map(|url| utils::normalize_repository_url(url))
+        .collect();
+    for url in &selected {
+        if !commits.keys().any(|(locked, _)| locked == url) {
+            return Err(LockError::NotLocked { url: url.clone() });
+        }
+    }
+
+    let keys: Vec<LockKey> = commits
+        .keys()
+        .filter(|(url, _)| selected.is_empty() || selected.contains(url))
+        .cloned()
+        .collect();
+    for key in keys {
+        let (url, branch) = &key;
+        let clone = cache::clone_directory(Path::new(""), url);
+        let url = match branch {
+            Some(branch) => format!("{url}@{branch}"),
+            None => url.clone(),
+        };
+        let Ok(repository) = Repository::open(&clone) else {
+            commits.remove(&key);
+            println!("Unlocked '{url}'; it is not cached, so it is locked when next downloaded");
+            continue;
+        };
+        let latest =
+            fetch_head(&repository, branch.as_deref()).map_err(|error| LockError::Fetch {
+                url: url.clone(),
+                error,
+            })?;
+        let previous = commits.insert(key.clone(), latest.clone());
+        if previous.as_deref() == Some(latest.as_str()) {
+            println!("'{url}' is up to date at {}", short_sha(&latest));
+        } else {
+            println!(
+                "Locked '{}' at {} (was {})",
+                url,
+                short_sha(&latest),
+                previous.as_deref().map_or("-", short_sha)
+            );
+        }
+    }
+    drop(commits);
+    lock.changed.store(true, Ordering::Relaxed);
+    lock.save(Path::new(""))
+}
+
+/// Fetches the commit at the head of a clone's remote default branch, or of
+/// another of its branches.
+///
+/// # Returns
+///
+/// SHA of the fetched commit, or a [`git2::Error`] on failure.
+pub fn fetch_head(repository: &Repository, branch: Option<&str>) -> Result<String, git2::Error> {
+    let mut proxy_options = ProxyOptions::new();
+    proxy_options.auto();
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.proxy_options(proxy_options);
+    // Keep shallow clones shallow.
+    let mut remote = repository.find_remote("origin")?;
+    if repository.is_shallow() && remote.url().is_some_and(utils::supports_shallow_fetch) {
+        fetch_options.depth(1);
+    }
+    remote.fetch(&[branch_refspec(branch)], Some(&mut fetch_options), None)?;
+    let commit = repository
+        .find_reference("FETCH_HEAD")?
+        .peel_to_commit()?
+        .id()
+        .to_string();
+    Ok(commit)
+}
+
+/// Builds the refspec that fetches a branch into its remote-tracking branch,
+/// or the remote's default branch if `branch` is `None`.
+pub fn branch_refspec(branch: Option<&str>) -> String {
+    match branch {
+        Some(branch) => format!("+refs/heads/{branch}:refs/remotes/origin/{branch}"),
+        None => "HEAD".to_string(),
+    }
+}
+
+/// Abbreviates a commit SHA.
+pub fn short_sha(commit: &str) -> &str {
+    &commit[..commit.len().min(SHORT_SHA_LENGTH)]
+}
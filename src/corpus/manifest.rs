@@ -0,0 +1,172 @@
+//! # Program Pair Manifests
+//!
+//! Every downloaded program pair has a manifest at
+//! `program_pairs/<program_name>/manifest.json` describing where its
+//! programs came from and under which licenses they are distributed.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::corpus::{
+    errors::DownloaderError,
+    schema::{FunctionMapping, Language},
+};
+
+/// Name of the manifest file within a program pair's directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// The manifest of a downloaded program pair.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Name of the program pair.
+    pub program_name: String,
+    /// Information about the C program.
+    pub c_program: ProgramManifest,
+    /// Information about the Rust program.
+    pub rust_program: ProgramManifest,
+    /// Information about the pair's Rust variants, by label; each records
+    /// the directory it was placed in.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rust_variants: BTreeMap<String, ProgramManifest>,
+    /// The C functions and the Rust functions that translate them, from the
+    /// pair's metadata.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub function_mappings: Vec<FunctionMapping>,
+    /// The pair's tags, from its metadata and the domain recorded by
+    /// `analyze --classify`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Fingerprint of the pair's metadata, pinned commits, and download
+    /// options when it was downloaded; the pair is downloaded again once it
+    /// changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+/// The manifest entry of the C or Rust program of a pair.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProgramManifest {
+    /// The repository the program was copied from.
+    pub repository_url: String,
+    /// The mirror the program was copied from instead, if the repository URL
+    /// could not be cloned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror_url: Option<String>,
+    /// The SPDX license expression detected from the license files, if any.
+    pub license: Option<String>,
+    /// Names of the license files copied next to the program's sources.
+    pub license_files: Vec<String>,
+    /// Names of the build files copied next to the program's sources.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub build_files: Vec<String>,
+    /// Paths of the patch files applied to the program, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<String>,
+    /// How the program's files were placed into its directory.
+    #[serde(default)]
+    pub materialization: Materialization,
+    /// Name of the program's directory within the pair's directory, if not
+    /// the language's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    /// Whether the files of `source_paths` directories kept their
+    /// subdirectories rather than being flattened.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keep_subdirectories: bool,
+}
+
+/// How the files of a program were placed into `program_pairs/`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Materialization {
+    /// The files were copied, including when linking them failed, such as
+    /// across filesystems.
+    #[default]
+    Copy,
+    /// Every file is a hard link to its file in `repository_clones/`.
+    Hardlink,
+    /// Every file is a symbolic link to its file in `repository_clones/`.
+    Symlink,
+    /// Every file is a hard link to its content in `store/objects/`.
+    Store,
+}
+
+impl Manifest {
+    /// Reads the manifest of a program pair.
+    ///
+    /// # Arguments
+    ///
+    /// - `pair_directory` - The program pair's directory.
+    ///
+    /// # Returns
+    ///
+    /// The [`Manifest`], or `None` if the pair has no readable manifest, such
+    /// as a pair downloaded before manifests were written.
+    pub fn load(pair_directory: &Path) -> Option<Manifest> {
+        let contents = fs::read_to_string(pair_directory.join(MANIFEST_FILE)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Writes the manifest into a program pair's directory.
+    ///
+    /// # Arguments
+    ///
+    /// - `pair_directory` - The program pair's directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success and [`DownloaderError`] on failure.
+    pub fn save(&self, pair_directory: &Path) -> Result<(), DownloaderError> {
+        let path = pair_directory.join(MANIFEST_FILE);
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|error| DownloaderError::Serialize { error })?;
+        fs::write(&path, contents).map_err(|error| DownloaderError::IoWrite { path, error })
+    }
+
+    /// Returns the manifest entry of the C or Rust program.
+    pub fn program(&self, language: &Language) -> &ProgramManifest {
+        if language.is_source() {
+            &self.c_program
+        } else {
+            &self.rust_program
+        }
+    }
+}
+
+/// Returns the name of the directory the C or Rust program of a downloaded
+/// pair was placed in, as recorded in the pair's manifest.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The program pair's directory.
+/// - `language` - Which program of the pair.
+///
+/// # Returns
+///
+/// The directory's name, or the language's default, such as `c-program`, if
+/// the manifest names none or the pair has no manifest.
+pub fn program_directory_name(pair_directory: &Path, language: &Language) -> String {
+    Manifest::load(pair_directory)
+        .and_then(|manifest| manifest.program(language).directory.clone())
+        .unwrap_or_else(|| language.program_directory().to_string())
+}
+
+/// Returns the directory the C or Rust program of a downloaded pair was
+/// placed in.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The program pair's directory.
+/// - `language` - Which program of the pair.
+///
+/// # Returns
+///
+/// The program's directory within `pair_directory`.
+pub fn program_directory(pair_directory: &Path, language: &Language) -> PathBuf {
+    pair_directory.join(program_directory_name(pair_directory, language))
+}
@@ -0,0 +1,451 @@
+//! # Metadata Authoring
+//!
+//! This module helps contributors write metadata files by looking up program
+//! information from package registries and build files and printing it as
+//! metadata entries ready to paste into a file in `metadata/`, or writing
+//! them into one, and by creating skeleton metadata files to fill in.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use git2::{FetchOptions, Repository, build::RepoBuilder};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    corpus::{
+        automake,
+        cache::{self, CacheLock},
+        call_graph,
+        errors::{MetadataError, ParserError},
+        parser, rust_modules,
+        schema::RepoUrl,
+        utils, writer,
+    },
+    paths::{self, INDIVIDUAL_METADATA_DIRECTORY, PROJECT_METADATA_DIRECTORY},
+};
+
+/// URL of crates.io, unless `CRATES_IO_URL` is set.
+const DEFAULT_CRATES_IO_URL: &str = "https://crates.io";
+
+/// Skeleton of a project metadata file; `NAME` is replaced by the project's
+/// name and the other values are examples to replace.
+const PROJECT_TEMPLATE: &str = r#"{
+    "$comment": "Replace the examples and delete this; metadata/README.md describes each field.",
+    "project_information": {
+        "program_name": "NAME",
+        "translation_tools": ["manual"],
+        "feature_relationship": "rust_equivalent_to_c",
+        "c_program": {
+            "documentation_url": "https://www.gnu.org/software/NAME/",
+            "repository_url": "https://git.savannah.gnu.org/git/NAME.git"
+        },
+        "rust_program": {
+            "documentation_url": "https://github.com/OWNER/NAME/blob/main/README.md",
+            "repository_url": "https://github.com/OWNER/NAME"
+        }
+    },
+    "pairs": [
+        {
+            "program_name": "PROGRAM",
+            "program_description": "One sentence describing what PROGRAM does.",
+            "c_program": {
+                "source_paths": ["src/PROGRAM.c", "src/system.h"]
+            },
+            "rust_program": {
+                "source_paths": ["src/uu/PROGRAM/src"]
+            }
+        }
+    ]
+}
+"#;
+
+/// Skeleton of an individual metadata file; `NAME` is replaced by the
+/// program's name and the other values are examples to replace.
+const INDIVIDUAL_TEMPLATE: &str = r#"{
+    "$comment": "Replace the examples and delete this; metadata/README.md describes each field.",
+    "pairs": [
+        {
+            "program_name": "NAME",
+            "program_description": "One sentence describing what NAME does.",
+            "translation_tools": ["manual"],
+            "feature_relationship": "rust_superset_of_c",
+            "c_program": {
+                "documentation_url": "https://www.gnu.org/software/NAME/",
+                "repository_url": "https://github.com/OWNER/NAME",
+                "source_paths": ["src/NAME.c", "src/NAME.h"]
+            },
+            "rust_program": {
+                "documentation_url": "https://docs.rs/NAME",
+                "repository_url": "https://github.com/OWNER/NAME-rs",
+                "source_paths": ["src"]
+            }
+        }
+    ]
+}
+"#;
+
+/// The kind of metadata file created by [`new`].
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum MetadataKind {
+    /// A C project and its Rust rewrite containing several programs, created
+    /// in `metadata/project/`.
+    Project,
+    /// Unrelated programs each from their own repositories, created in
+    /// `metadata/individual/`.
+    Individual,
+}
+
+/// A response from `GET /api/v1/crates/{name}`.
+#[derive(Deserialize)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: CrateInfo,
+}
+
+/// The fields of a crate used to fill in metadata.
+#[derive(Deserialize)]
+struct CrateInfo {
+    name: String,
+    description: Option<String>,
+    repository: Option<String>,
+    documentation: Option<String>,
+    max_stable_version: Option<String>,
+    max_version: String,
+}
+
+/// The `rust_program` section of an individual metadata pair.
+#[derive(Serialize)]
+struct RustProgram {
+    documentation_url: String,
+    repository_url: String,
+    source_paths: Vec<String>,
+}
+
+/// Looks up a crate on crates.io and prints a `rust_program` section for it.
+///
+/// The section uses the crate's repository and documentation URLs, falling
+/// back to docs.rs for documentation.  `source_paths` defaults to `src/` and
+/// should be narrowed to the program's sources before committing.  Set
+/// `CRATES_IO_URL` to query a mirror of the registry instead.
+///
+/// # Arguments
+///
+/// - `crate_name` - Name of the Rust rewrite on crates.io.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`MetadataError`] on failure.
+pub fn rust(crate_name: &str) -> Result<(), MetadataError> {
+    let client = utils::http_client().map_err(|error| MetadataError::Client { error })?;
+    let registry_url =
+        env::var("CRATES_IO_URL").unwrap_or_else(|_| DEFAULT_CRATES_IO_URL.to_string());
+    let url = format!(
+        "{}/api/v1/crates/{crate_name}",
+        registry_url.trim_end_matches('/')
+    );
+
+    let request_error = |error| MetadataError::Request {
+        url: url.clone(),
+        error,
+    };
+    let response = client.get(&url).send().map_err(request_error)?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(MetadataError::CrateNotFound {
+            crate_name: crate_name.to_string(),
+        });
+    }
+    let krate = response
+        .error_for_status()
+        .and_then(|response| response.json::<CrateResponse>())
+        .map_err(request_error)?
+        .krate;
+
+    let repository_url = krate
+        .repository
+        .ok_or_else(|| MetadataError::NoRepository {
+            crate_name: krate.name.clone(),
+        })?;
+    let program = RustProgram {
+        documentation_url: krate
+            .documentation
+            .unwrap_or_else(|| format!("https://docs.rs/{}", krate.name)),
+        repository_url,
+        source_paths: vec!["src/".to_string()],
+    };
+
+    // Details that have no place in the metadata go to stderr, so stdout can
+    // be redirected or piped straight into an editor.
+    eprintln!(
+        "{} {}: {}",
+        krate.name,
+        krate.max_stable_version.unwrap_or(krate.max_version),
+        krate.description.unwrap_or_default().trim()
+    );
+    println!("\"rust_program\": {}", to_metadata_json(&program)?);
+    Ok(())
+}
+
+/// Discovers the sources of a program in a repository and prints them as
+/// `source_paths`, or writes them to the program's pair in a metadata file.
+///
+/// A crate directory with a `Cargo.toml` is taken to hold a Rust program,
+/// whose sources are found by following the module tree of the binary named
+/// after the program, or of the crate's default binary.  Other repositories
+/// are taken to hold a C program, whose sources are found in their
+/// `Makefile.am` files.
+///
+/// # Arguments
+///
+/// - `program_name` - Name of the program: its executable, or its binary.
+/// - `repository` - A local checkout, or the URL of a repository, which is
+///   cloned into `repository_clones/` unless it already is.
+/// - `crate_directory` - The directory of the Rust crate relative to the
+///   checkout, such as a member of a workspace.
+/// - `prune` - Whether to drop the sources of a C program that are not
+///   reachable from its `main` through its call graph.
+/// - `write` - The metadata file whose pair named `program_name` gets the
+///   sources as the `source_paths` of its C or Rust program, or `None` to
+///   print them.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`MetadataError`] on failure.
+pub fn discover(
+    program_name: &str,
+    repository: &str,
+    crate_directory: &Path,
+    prune: bool,
+    write: Option<&Path>,
+) -> Result<(), MetadataError> {
+    let checkout = checkout(repository)?;
+    let is_rust = checkout.join(crate_directory).join("Cargo.toml").is_file();
+    let source_paths = if is_rust {
+        match rust_modules::find_sources(&checkout, crate_directory, Some(program_name)) {
+            Err(MetadataError::CrateTargetNotFound { .. }) => {
+                rust_modules::find_sources(&checkout, crate_directory, None)?
+            }
+            sources => sources?,
+        }
+    } else {
+        let source_paths = automake::find_sources(&checkout, program_name)?;
+        if prune {
+            call_graph::prune(&checkout, source_paths)?
+        } else {
+            source_paths
+        }
+    };
+
+    let Some(path) = write else {
+        println!("\"source_paths\": {}", to_metadata_json(&source_paths)?);
+        return Ok(());
+    };
+    let contents = fs::read_to_string(path).map_err(|error| MetadataError::IoRead {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let parse_error = |error| MetadataError::Parse {
+        path: path.to_path_buf(),
+        error,
+    };
+    parser::parse_str(&contents).map_err(parse_error)?;
+
+    // The sources are merged into the file's own JSON rather than into the
+    // parsed metadata, which would be written back in the individual format,
+    // so that a project file keeps its structure and every key its order.
+    let mut document: Value = serde_json::from_str(&contents)
+        .map_err(|error| parse_error(ParserError::Deserialize { error }))?;
+    let section = if is_rust { "rust_program" } else { "c_program" };
+    let program = document
+        .get_mut("pairs")
+        .and_then(Value::as_array_mut)
+        .and_then(|pairs| {
+            pairs
+                .iter_mut()
+                .find(|pair| pair["program_name"] == program_name)
+        })
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| MetadataError::PairNotInFile {
+            program_name: program_name.to_string(),
+            path: path.to_path_buf(),
+        })?
+        .entry(section)
+        .or_insert_with(|| Value::Object(Map::new()));
+    if let Some(program) = program.as_object_mut() {
+        program.insert(
+            "source_paths".to_string(),
+            Value::from(source_paths.clone()),
+        );
+    }
+    writer::write_document(path, &document).map_err(|error| MetadataError::Write {
+        path: path.to_path_buf(),
+        error,
+    })?;
+
+    println!(
+        "Wrote {} source paths to the {section} of '{program_name}' in '{}'",
+        source_paths.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Finds the local checkout of a repository, cloning it into
+/// `repository_clones/` if it is given by URL and not cached yet.
+///
+/// # Arguments
+///
+/// - `repository` - A directory, or the URL of a repository.
+///
+/// # Returns
+///
+/// The checkout's directory, or [`MetadataError`] if the repository is
+/// neither a directory nor a URL, or cannot be cloned.
+fn checkout(repository: &str) -> Result<PathBuf, MetadataError> {
+    let path = Path::new(repository);
+    if path.is_dir() {
+        return Ok(path.to_path_buf());
+    }
+    let url = RepoUrl::new(repository.to_string()).map_err(|_| MetadataError::NotARepository {
+        repository: repository.to_string(),
+    })?;
+
+    let clone = cache::clone_directory(Path::new(""), url.as_str());
+    let _lock = CacheLock::acquire(&clone, |_| {
+        eprintln!("Waiting for another download of '{repository}'");
+    })
+    .map_err(|error| MetadataError::Cache { error })?;
+    if Repository::open(&clone).is_err() {
+        eprintln!("Cloning '{repository}'");
+        let mut fetch_options = FetchOptions::new();
+        if utils::supports_shallow_fetch(repository) {
+            fetch_options.depth(1);
+        }
+        RepoBuilder::new()
+            .fetch_options(fetch_options)
+            .clone(url.as_str(), &clone)
+            .map_err(|error| MetadataError::Clone {
+                repository_url: repository.to_string(),
+                error,
+            })?;
+    }
+    cache::record_use(Path::new(""), &clone).map_err(|error| MetadataError::Cache { error })?;
+    Ok(clone)
+}
+
+/// Creates a skeleton metadata file named after a project or program.
+///
+/// The file is checked against the schema before it is written, and an
+/// existing file is never overwritten.
+///
+/// # Arguments
+///
+/// - `kind` - Whether to create project or individual metadata.
+/// - `name` - Name of the project or program; also the file's name.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`MetadataError`] on failure.
+pub fn new(kind: MetadataKind, name: &str) -> Result<(), MetadataError> {
+    if !is_valid_name(name) {
+        return Err(MetadataError::InvalidName {
+            name: name.to_string(),
+        });
+    }
+
+    let metadata = skeleton(kind, name);
+    let metadata_json: Value =
+        serde_json::from_str(&metadata).map_err(|error| MetadataError::Serialize { error })?;
+    parser::validate_metadata(&metadata_json).map_err(|error| MetadataError::Skeleton { error })?;
+
+    let directory = match kind {
+        MetadataKind::Project => PROJECT_METADATA_DIRECTORY,
+        MetadataKind::Individual => INDIVIDUAL_METADATA_DIRECTORY,
+    };
+    let path = paths::metadata_path(directory).join(format!("{name}.json"));
+    let write_error = |error: io::Error| match error.kind() {
+        io::ErrorKind::AlreadyExists => MetadataError::AlreadyExists { path: path.clone() },
+        _ => MetadataError::IoWrite {
+            path: path.clone(),
+            error,
+        },
+    };
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(metadata.as_bytes()))
+        .map_err(write_error)?;
+
+    println!(
+        "Created '{}'; replace its example values, then check it with `cargo run lint`",
+        path.display()
+    );
+    Ok(())
+}
+
+/// Checks whether a name can be used as a file or directory name and as a
+/// JSON string without escaping, as the names of metadata files and program
+/// pairs are.
+///
+/// # Arguments
+///
+/// - `name` - The name to check.
+///
+/// # Returns
+///
+/// Whether the name is non-empty, does not start with `.`, and only contains
+/// ASCII letters, digits, `-`, `_`, `.`, and `+`.
+pub fn is_valid_name(name: &str) -> bool {
+    let is_valid_character =
+        |character: char| character.is_ascii_alphanumeric() || "-_.+".contains(character);
+    !name.is_empty() && !name.starts_with('.') && name.chars().all(is_valid_character)
+}
+
+/// Fills in a metadata template.
+///
+/// # Arguments
+///
+/// - `kind` - Which template to fill in.
+/// - `name` - Name of the project or program.
+///
+/// # Returns
+///
+/// The contents of the skeleton metadata file.
+fn skeleton(kind: MetadataKind, name: &str) -> String {
+    match kind {
+        MetadataKind::Project => PROJECT_TEMPLATE,
+        MetadataKind::Individual => INDIVIDUAL_TEMPLATE,
+    }
+    .replace("NAME", name)
+}
+
+/// Serializes a value as JSON indented with four spaces, like the metadata
+/// files.
+fn to_metadata_json<T: Serialize>(value: &T) -> Result<String, MetadataError> {
+    utils::to_metadata_json(value).map_err(|error| MetadataError::Serialize { error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that both skeletons conform to the schema.
+    #[test]
+    fn test_skeletons_are_valid() {
+        for kind in [MetadataKind::Project, MetadataKind::Individual] {
+            let metadata: Value = serde_json::from_str(&skeleton(kind, "grep")).unwrap();
+            let result = parser::validate_metadata(&metadata);
+            assert!(
+                result.is_ok(),
+                "Skeleton of {kind:?} metadata is invalid: {:?}",
+                result.err()
+            );
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! # Run Metrics
+//!
+//! This module records how each download ran in `run-metrics.json` at the
+//! corpus root: how long it took, how many bytes it downloaded, how often
+//! the clone cache was hit, how many program pairs succeeded or failed, and
+//! how many bytes it wrote into the corpus.  The file is only written
+//! locally and replaced by every download, so that the performance of the
+//! tool itself can be compared across versions without any telemetry.
+
+use std::{
+    fs,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{corpus::progress::PairOutcome, paths::RUN_METRICS_FILE};
+
+/// Collects the metrics of a download while it runs, from any thread.
+pub struct MetricsRecorder {
+    /// When the download started.
+    started: Instant,
+    /// Seconds since the Unix epoch when the download started.
+    started_at: u64,
+    /// Bytes of the repositories cloned and the tarballs and files fetched.
+    bytes_downloaded: AtomicU64,
+    /// Number of repositories, tarballs, and files cloned or fetched.
+    cache_misses: AtomicU64,
+    /// Number of repositories, tarballs, and files used from the cache.
+    cache_hits: AtomicU64,
+    /// Bytes written under `repository_clones/` and `program_pairs/`.
+    bytes_written: AtomicU64,
+}
+
+/// The metrics of one download, as written to `run-metrics.json`.
+#[derive(Debug, Serialize)]
+struct RunMetrics {
+    /// Version of the tool that ran the download.
+    version: &'static str,
+    /// Seconds since the Unix epoch when the download started.
+    started_at: u64,
+    /// How long the download took, in seconds.
+    duration_seconds: f64,
+    /// Bytes of the repositories cloned and the tarballs and files fetched.
+    bytes_downloaded: u64,
+    /// Number of repositories, tarballs, and files cloned or fetched.
+    cache_misses: u64,
+    /// Number of repositories, tarballs, and files used from the cache.
+    cache_hits: u64,
+    /// The fraction of repositories, tarballs, and files used from the
+    /// cache, or `None` if none were needed.
+    cache_hit_rate: Option<f64>,
+    /// Number of program pairs downloaded or already up to date.
+    pairs_succeeded: usize,
+    /// Number of program pairs that failed to download.
+    pairs_failed: usize,
+    /// Number of program pairs skipped, such as those in the skip list.
+    pairs_skipped: usize,
+    /// Bytes written under `repository_clones/` and `program_pairs/`: the
+    /// repositories cloned, the tarballs and files fetched, and the files
+    /// copied into program pairs rather than linked.
+    bytes_written: u64,
+}
+
+impl MetricsRecorder {
+    /// Starts recording the metrics of a download.
+    pub fn new() -> Self {
+        MetricsRecorder {
+            started: Instant::now(),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            bytes_downloaded: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a repository cloned, or a tarball or file fetched, instead of
+    /// used from the cache.
+    ///
+    /// # Arguments
+    ///
+    /// - `bytes` - Size of the clone or of the fetched source.
+    pub fn downloaded(&self, bytes: u64) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        self.bytes_downloaded.fetch_add(bytes, Ordering::Relaxed);
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Counts a file copied into a program pair.
+    ///
+    /// # Arguments
+    ///
+    /// - `bytes` - Size of the copied file.
+    pub fn copied(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Counts a repository, tarball, or file used from the cache.
+    pub fn cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Writes the metrics of the finished download to `run-metrics.json`.
+    ///
+    /// # Arguments
+    ///
+    /// - `root` - The corpus root, or an empty path for the current
+    ///   directory.
+    /// - `outcomes` - How each processed program pair ended.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or a message explaining why the file
+    /// cannot be written.
+    pub fn save(&self, root: &Path, outcomes: &[PairOutcome]) -> Result<(), String> {
+        let count = |matches: fn(&PairOutcome) -> bool| {
+            outcomes.iter().filter(|outcome| matches(outcome)).count()
+        };
+        let cache_hits = self.cache_hits.load(Ordering::Relaxed);
+        let cache_misses = self.cache_misses.load(Ordering::Relaxed);
+        let needed = cache_hits + cache_misses;
+        let metrics = RunMetrics {
+            version: env!("CARGO_PKG_VERSION"),
+            started_at: self.started_at,
+            duration_seconds: self.started.elapsed().as_secs_f64(),
+            bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+            cache_misses,
+            cache_hits,
+            cache_hit_rate: (needed > 0).then(|| cache_hits as f64 / needed as f64),
+            pairs_succeeded: count(|outcome| {
+                matches!(
+                    outcome,
+                    PairOutcome::Downloaded { .. } | PairOutcome::UpToDate { .. }
+                )
+            }),
+            pairs_failed: count(|outcome| matches!(outcome, PairOutcome::Failed { .. })),
+            pairs_skipped: count(|outcome| matches!(outcome, PairOutcome::Skipped { .. })),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        };
+        let json = serde_json::to_string_pretty(&metrics).map_err(|error| error.to_string())?;
+        fs::write(root.join(RUN_METRICS_FILE), json).map_err(|error| error.to_string())
+    }
+}
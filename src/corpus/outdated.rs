@@ -0,0 +1,182 @@
+//! # Outdated Program Pairs
+//!
+//! This module compares the commit every downloaded program pair was copied
+//! from, as recorded in its provenance, with the current head of its upstream
+//! repository, listed as `git ls-remote` does, and reports the pairs whose
+//! upstream has moved.  The check only reads: nothing is fetched, so how
+//! many commits behind the corpus copy is can only be counted when the
+//! repository's cached clone already has the upstream head and the history
+//! leading to it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    time::Duration,
+};
+
+use git2::{Oid, Repository};
+use rayon::prelude::*;
+
+use crate::{
+    corpus::{
+        audit, cache,
+        errors::OutdatedError,
+        git_options::{self, GitOptions},
+        lock,
+        provenance::Provenance,
+        utils,
+    },
+    paths::PROGRAM_PAIRS_DIRECTORY,
+};
+
+/// How long listing a repository waits for its server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A repository a program was downloaded from: its URL and its branch, or
+/// `None` for its default branch.
+type Upstream = (String, Option<String>);
+
+/// Lists the downloaded program pairs whose upstream repositories have moved
+/// past the commits they were copied from.
+///
+/// Pairs without a provenance file, and files copied from tarball and file
+/// sources, are skipped.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if every repository was looked up, whether or not any
+/// pair is outdated, [`OutdatedError::Incomplete`] if some could not be, and
+/// another [`OutdatedError`] on failure.
+pub fn outdated() -> Result<(), OutdatedError> {
+    let program_pairs = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    let pair_directories =
+        utils::list_program_pairs(program_pairs).map_err(|error| OutdatedError::IoRead {
+            path: program_pairs.to_path_buf(),
+            error,
+        })?;
+
+    // The commits each pair was copied from, by repository.
+    let mut pairs: Vec<(String, BTreeSet<(Upstream, String)>)> = Vec::new();
+    for directory in &pair_directories {
+        let Some(name) = directory.file_name() else {
+            continue;
+        };
+        let name = name.to_string_lossy().into_owned();
+        let Some(provenance) = Provenance::load(directory) else {
+            eprintln!("Skipped '{name}'; it has no provenance, so download it again");
+            continue;
+        };
+        let origins = provenance
+            .files
+            .into_iter()
+            .filter(|file| is_git_commit(&file.commit))
+            .map(|file| ((file.repository_url, file.branch), file.commit))
+            .collect();
+        pairs.push((name, origins));
+    }
+
+    // Keep an unreachable server from stalling the check, unless an
+    // application using the library already set libgit2's options.
+    let _ = git_options::apply(&GitOptions {
+        connect_timeout: Some(CONNECT_TIMEOUT),
+        ..GitOptions::default()
+    });
+    let upstreams: BTreeSet<&Upstream> = pairs
+        .iter()
+        .flat_map(|(_, origins)| origins.iter().map(|(upstream, _)| upstream))
+        .collect();
+    let heads: BTreeMap<&Upstream, Result<String, String>> = upstreams
+        .into_par_iter()
+        .map(|upstream| (upstream, remote_head(&upstream.0, upstream.1.as_deref())))
+        .collect();
+    for ((url, _), head) in &heads {
+        if let Err(reason) = head {
+            eprintln!("Failed to look up '{url}': {reason}");
+        }
+    }
+
+    let mut outdated = 0;
+    for (name, origins) in &pairs {
+        let mut moved = false;
+        for (upstream, commit) in origins {
+            let Some(Ok(head)) = heads.get(upstream) else {
+                continue;
+            };
+            if head == commit {
+                continue;
+            }
+            moved = true;
+            let behind = commits_behind(upstream, commit, head).map_or_else(
+                || "an unknown number of commits behind".to_string(),
+                |count| format!("{count} commits behind"),
+            );
+            println!(
+                "'{name}' is outdated: '{}' moved from {} to {} ({behind})",
+                upstream.0,
+                lock::short_sha(commit),
+                lock::short_sha(head)
+            );
+        }
+        outdated += usize::from(moved);
+    }
+    println!("Checked {} program pairs: {outdated} outdated", pairs.len());
+
+    let failed = heads.values().filter(|head| head.is_err()).count();
+    if failed > 0 {
+        return Err(OutdatedError::Incomplete {
+            failed,
+            total: heads.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Decides whether a file's recorded commit is a git commit.
+///
+/// Tarball and file sources record the SHA-256 of what was fetched instead,
+/// and have no upstream head to compare with.
+fn is_git_commit(commit: &str) -> bool {
+    commit.len() == 40 && Oid::from_str(commit).is_ok()
+}
+
+/// Finds the commit at the head of a repository's default branch, or of
+/// another of its branches, as `git ls-remote` does.
+///
+/// # Arguments
+///
+/// - `url` - The repository's URL.
+/// - `branch` - The branch, or `None` for the default branch.
+///
+/// # Returns
+///
+/// SHA of the head commit, or why the repository cannot be listed or has no
+/// such branch.
+fn remote_head(url: &str, branch: Option<&str>) -> Result<String, String> {
+    audit::list_references(url, branch)?.ok_or_else(|| "'HEAD' not found".to_string())
+}
+
+/// Counts the commits between the commit a program was copied from and its
+/// upstream head in the repository's cached clone, which is left as it is.
+///
+/// # Arguments
+///
+/// - `upstream` - The repository and branch.
+/// - `commit` - SHA of the commit the program was copied from.
+/// - `head` - SHA of the upstream head.
+///
+/// # Returns
+///
+/// The number of commits the head is ahead of `commit`, or `None` if the
+/// repository is not cached, its clone is shallow, or the clone has not
+/// fetched either commit.
+fn commits_behind((url, _): &Upstream, commit: &str, head: &str) -> Option<usize> {
+    let repository = Repository::open(cache::clone_directory(Path::new(""), url)).ok()?;
+    // A shallow clone lacks the history in between.
+    if repository.is_shallow() {
+        return None;
+    }
+    let (commit, head) = (Oid::from_str(commit).ok()?, Oid::from_str(head).ok()?);
+    repository.find_commit(head).ok()?;
+    let (behind, _) = repository.graph_ahead_behind(head, commit).ok()?;
+    Some(behind)
+}
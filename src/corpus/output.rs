@@ -0,0 +1,41 @@
+//! # Structured Output
+//!
+//! This module records whether `--json` was given, which makes `download`,
+//! `demo`, `list`, `stats`, `lint`, `delete`, `build`, `cache`, `doctor`, and
+//! `audit` print their results as a single JSON object on stdout instead of text, so that scripts and
+//! notebooks can read them without scraping tables.  Progress and other
+//! messages still go to stderr.  Other commands reject `--json` rather than
+//! ignore it.  There is no `verify` command; `lint` is what checks the
+//! metadata, and `cache fsck` the cached clones.
+
+use std::sync::OnceLock;
+
+use serde::Serialize;
+
+/// Whether results are printed as JSON, set once from `--json`.
+static JSON: OnceLock<bool> = OnceLock::new();
+
+/// Makes commands print their results as JSON, for `--json`.
+///
+/// # Arguments
+///
+/// - `json` - Whether results are printed as JSON.
+pub fn set_json(json: bool) {
+    let _ = JSON.set(json);
+}
+
+/// Returns whether commands print their results as JSON.
+pub fn json() -> bool {
+    JSON.get().copied().unwrap_or(false)
+}
+
+/// Prints the result of a command as one line of JSON on stdout.
+///
+/// # Arguments
+///
+/// - `result` - The result, whose fields are named after what they hold.
+pub fn print_json(result: &impl Serialize) {
+    let json =
+        serde_json::to_string(result).expect("Unreachable because results only have string keys");
+    println!("{json}");
+}
@@ -1,29 +1,83 @@
 //! # Metadata Parsing and Validation
 //!
 //! The main entry point is [`parse`], which takes a path to a JSON metadata
-//! file and returns a [`Metadata`] instance.
+//! file and returns a [`Metadata`] instance.  Parsing reads files only
+//! through a [`Storage`], so [`parse_with`] and [`parse_directory_with`]
+//! parse metadata held in memory as well as on disk.
 
 use std::{
-    fs,
-    path::{Path, PathBuf},
+    borrow::Cow,
+    collections::BTreeSet,
+    path::{self, Component, Path, PathBuf},
+    sync::OnceLock,
 };
 
-use jsonschema;
-use serde::Serialize;
+use jsonschema::Validator;
+use rayon::prelude::*;
 use serde_json::Value;
 
-use crate::{
-    corpus::{
-        errors::ParserError,
-        metadata_structs::{
-            CRustProgramPairSchema, FeatureRelationship, IndividualProgramPair,
-            ProjectPairsMetadataProjectInformation, ProjectProgramPair,
-        },
-        schema::{Features, Language, Metadata, Program, ProgramPair},
+use crate::corpus::{
+    effects::{FileSystem, Storage},
+    errors::{ParserError, SchemaError, ValidationIssue, ValueIssue},
+    metadata,
+    metadata_structs::{
+        self, Asset, Branch, BuildFiles, CRustProgramPairSchema, Checksum, ExcludePaths,
+        Extensions, FeatureRelationship, FunctionMappings, IndividualProgram,
+        IndividualProgramPair, MirrorUrls, NeedsSubmodules, OutputDirectory, Patches,
+        ProjectGlobalProgram, ProjectPairsMetadataProjectInformation, ProjectProgram,
+        ProjectProgramPair, ReleaseTag, Tags,
     },
-    paths::METADATA_SCHEMA_FILE,
+    schema::{
+        DocUrl, ExtensionFilter, Features, FunctionLocation, FunctionMapping, Language, Metadata,
+        Program, ProgramPair, RelativeSourcePath, ReleaseAsset, RepoUrl, RustVariant, SourceType,
+        TranslationTool, variant_directory,
+    },
+    utils::normalize_repository_url,
 };
 
+/// The metadata schema built into the binary, so metadata can be parsed
+/// without `metadata/metadata.schema.json` at hand.
+pub const BUILT_IN_SCHEMA: &str = include_str!("../../metadata/metadata.schema.json");
+
+/// The path of each metadata file in a directory paired with the result of
+/// parsing it.
+pub type MetadataFiles = Vec<(PathBuf, Result<Metadata, ParserError>)>;
+
+/// Validators for the two kinds of metadata, compiled from the schema file
+/// by the first validation and reused by every later one.
+struct Validators {
+    /// Validates metadata with `project_information`.
+    project: Validator,
+    /// Validates metadata without `project_information`.
+    individual: Validator,
+}
+
+/// The compiled validators, once the schema has been read successfully.
+static VALIDATORS: OnceLock<Validators> = OnceLock::new();
+
+/// The schema file given with `--schema`, used instead of the built-in
+/// schema.
+static SCHEMA_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Validates metadata against a schema file instead of the built-in schema.
+///
+/// Must be called before any metadata is parsed; later calls, and calls
+/// after the first parse, have no effect.
+///
+/// # Arguments
+///
+/// - `path` - The schema file, which is made absolute so that changing into
+///   the corpus root does not change which file it names.
+pub fn set_schema_file(path: &Path) {
+    let path = path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let _ = SCHEMA_FILE.set(path);
+}
+
+/// Returns the schema file given with `--schema`, if any.
+pub fn schema_file() -> Option<&'static Path> {
+    SCHEMA_FILE.get().map(PathBuf::as_path)
+}
+
 /// Parses a JSON metadata file describing C-Rust program pairs into a
 /// [`Metadata`] struct.
 ///
@@ -36,70 +90,264 @@ use crate::{
 /// A [`Metadata`] instance containing program pair data on success and
 /// [`ParserError`] on failure.
 pub fn parse(path: &Path) -> Result<Metadata, ParserError> {
-    // Read metadata file and deserialize it into a
-    // [`CRustProgramPairSchema`] enum.
-    let raw_metadata = fs::read_to_string(path).map_err(|error| ParserError::IoRead {
-        path: path.to_path_buf(),
-        error,
-    })?;
-    let metadata: CRustProgramPairSchema =
-        serde_json::from_str(&raw_metadata).map_err(|error| ParserError::Deserialize { error })?;
+    parse_with(&FileSystem::default(), path)
+}
 
-    // Validate metadata with our JSON schema.
-    validate_metadata(&metadata)?;
+/// Parses a JSON metadata file read from a [`Storage`] into a [`Metadata`]
+/// struct.
+///
+/// # Arguments
+///
+/// - `storage` - The storage holding the file.
+/// - `path` - The JSON metadata file.
+///
+/// # Returns
+///
+/// A [`Metadata`] instance containing program pair data on success and
+/// [`ParserError`] on failure.
+pub fn parse_with(storage: &dyn Storage, path: &Path) -> Result<Metadata, ParserError> {
+    let raw_metadata = storage
+        .read_to_string(path)
+        .map_err(|error| ParserError::IoRead {
+            path: path.to_path_buf(),
+            error,
+        })?;
+    parse_contents(storage, &raw_metadata)
+}
+
+/// Parses the contents of a JSON metadata file into a [`Metadata`] struct.
+///
+/// The contents are untrusted: malformed input of any kind is reported as a
+/// [`ParserError`], never a panic.
+///
+/// # Arguments
+///
+/// - `raw_metadata` - The JSON contents of a metadata file.
+///
+/// # Returns
+///
+/// A [`Metadata`] instance containing program pair data on success and
+/// [`ParserError`] on failure.
+pub fn parse_str(raw_metadata: &str) -> Result<Metadata, ParserError> {
+    parse_contents(&FileSystem::default(), raw_metadata)
+}
+
+/// Parses the contents of a JSON metadata file, validating them against the
+/// schema given with [`set_schema_file`] as read from a [`Storage`].
+fn parse_contents(storage: &dyn Storage, raw_metadata: &str) -> Result<Metadata, ParserError> {
+    // Deserialize the metadata into a [`CRustProgramPairSchema`] enum.
+    let metadata_json: Value =
+        serde_json::from_str(raw_metadata).map_err(|error| ParserError::Deserialize { error })?;
+
+    // Validate metadata with our JSON schema before deserializing it, so that
+    // every violation is reported rather than only the first one.
+    validate_metadata_with(storage, &metadata_json)?;
+    let metadata: CRustProgramPairSchema = serde_json::from_value(metadata_json)
+        .map_err(|error| ParserError::Deserialize { error })?;
 
     // Create data structure conditioned on the metadata type.
     match metadata {
-        CRustProgramPairSchema::IndividualPairsMetadata { pairs } => {
-            let metadata = parse_individual(&pairs);
-            return Ok(metadata);
-        }
+        CRustProgramPairSchema::IndividualPairsMetadata { pairs } => parse_individual(&pairs),
         CRustProgramPairSchema::ProjectPairsMetadata {
             pairs,
             project_information,
-        } => {
-            let metadata = parse_project(&pairs, &project_information);
-            return Ok(metadata);
-        }
+        } => parse_project(&pairs, &project_information),
     }
 }
 
-/// Validates metadata against the project's JSON schema.
+/// Parses every metadata file in a directory, in parallel.
+///
+/// # Arguments
+///
+/// - `directory` - A directory of JSON metadata files.
+///
+/// # Returns
+///
+/// The path and parse result of each metadata file sorted by path, or
+/// [`ParserError`] if the directory cannot be read.
+pub fn parse_directory(directory: &Path) -> Result<MetadataFiles, ParserError> {
+    parse_directory_with(&FileSystem::default(), directory)
+}
+
+/// Parses every metadata file in a directory of a [`Storage`], in parallel.
 ///
 /// # Arguments
 ///
-/// - `metadata` - A JSON serializable struct that represents some metadata.
+/// - `storage` - The storage holding the directory.
+/// - `directory` - A directory of JSON metadata files.
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` on success and [`ParserError`] on failure.
-fn validate_metadata<T: Serialize>(metadata: &T) -> Result<(), ParserError> {
-    // Create a validator based on the JSON schema.
-    let schema_str =
-        fs::read_to_string(METADATA_SCHEMA_FILE).map_err(|error| ParserError::IoRead {
-            path: PathBuf::from(METADATA_SCHEMA_FILE),
+/// The path and parse result of each metadata file sorted by path, or
+/// [`ParserError`] if the directory cannot be read.
+pub fn parse_directory_with(
+    storage: &dyn Storage,
+    directory: &Path,
+) -> Result<MetadataFiles, ParserError> {
+    let mut paths = storage
+        .read_dir(directory)
+        .map_err(|error| ParserError::IoRead {
+            path: directory.to_path_buf(),
             error,
         })?;
-    let schema: Value =
-        serde_json::from_str(&schema_str).map_err(|error| ParserError::Deserialize { error })?;
-    let validator =
-        jsonschema::validator_for(&schema).map_err(|error| ParserError::Validation {
-            error: error.to_string(),
-        })?;
+    paths.sort();
 
-    // Convert metadata to a JSON `Value` type.
-    let metadata_json =
-        serde_json::to_value(metadata).map_err(|error| ParserError::Serialize { error })?;
+    Ok(paths
+        .into_par_iter()
+        .map(|path| {
+            let metadata = parse_with(storage, &path);
+            (path, metadata)
+        })
+        .collect())
+}
 
-    if let Err(error) = validator.validate(&metadata_json) {
-        return Err(ParserError::Validation {
-            error: format!("Failed to validate metadata: {error}"),
-        });
+/// Validates metadata against the project's JSON schema.
+///
+/// # Arguments
+///
+/// - `metadata` - The JSON contents of a metadata file.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`ParserError`] on failure.  Schema
+/// violations yield [`ParserError::Validation`] listing every violation.
+pub fn validate_metadata(metadata: &Value) -> Result<(), ParserError> {
+    validate_metadata_with(&FileSystem::default(), metadata)
+}
+
+/// Validates metadata against the project's JSON schema, reading the schema
+/// given with [`set_schema_file`], if any, from a [`Storage`].
+///
+/// # Arguments
+///
+/// - `storage` - The storage holding the schema file.
+/// - `metadata` - The JSON contents of a metadata file.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`ParserError`] on failure.  Schema
+/// violations yield [`ParserError::Validation`] listing every violation.
+pub fn validate_metadata_with(storage: &dyn Storage, metadata: &Value) -> Result<(), ParserError> {
+    let validators = validators(storage)?;
+    let validator = if metadata.get("project_information").is_some() {
+        &validators.project
+    } else {
+        &validators.individual
+    };
+
+    let issues: Vec<ValidationIssue> = validator
+        .iter_errors(metadata)
+        .map(|error| {
+            let message = error.to_string();
+            let schema_path = error.schema_path().as_str().to_string();
+            ValidationIssue {
+                instance_path: error.instance_path().as_str().to_string(),
+                constraint: schema_path
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+                schema_path,
+                value: error.instance().clone().into_owned(),
+                message,
+            }
+        })
+        .collect();
+    if !issues.is_empty() {
+        return Err(ParserError::Validation { issues });
     }
 
     Ok(())
 }
 
+/// Returns the validators compiled from the metadata schema, compiling the
+/// schema on first use.
+///
+/// The built-in schema is used unless [`set_schema_file`] gave another,
+/// which is read from the storage of the first call to succeed.  A failure
+/// is not cached, so a later call retries.  Concurrent first calls may each
+/// compile the schema, but only one result is kept.
+///
+/// # Arguments
+///
+/// - `storage` - The storage holding the schema file.
+///
+/// # Returns
+///
+/// The [`Validators`] on success and [`ParserError`] if the schema cannot be
+/// read or compiled.
+fn validators(storage: &dyn Storage) -> Result<&'static Validators, ParserError> {
+    if let Some(validators) = VALIDATORS.get() {
+        return Ok(validators);
+    }
+
+    let schema_str = match schema_file() {
+        Some(path) => {
+            let schema_str = storage
+                .read_to_string(path)
+                .map_err(|error| ParserError::IoRead {
+                    path: path.to_path_buf(),
+                    error,
+                })?;
+            Cow::Owned(schema_str)
+        }
+        None => Cow::Borrowed(BUILT_IN_SCHEMA),
+    };
+    let schema: Value =
+        serde_json::from_str(&schema_str).map_err(|error| ParserError::Deserialize { error })?;
+    let compile = |is_project| {
+        jsonschema::validator_for(&metadata_schema(&schema, is_project)).map_err(|error| {
+            ParserError::Schema {
+                error: error.to_string(),
+            }
+        })
+    };
+    let validators = Validators {
+        project: compile(true)?,
+        individual: compile(false)?,
+    };
+    Ok(VALIDATORS.get_or_init(|| validators))
+}
+
+/// Selects the schema that a kind of metadata file is meant to conform to.
+///
+/// The schema's root is a `oneOf` of the individual and project metadata
+/// schemas, and metadata matching neither fails with a single `oneOf` error
+/// that hides the actual violations.  Metadata with `project_information` is
+/// meant to be project metadata, and other metadata individual metadata, so
+/// it is validated against that alternative alone.
+///
+/// # Arguments
+///
+/// - `schema` - The metadata JSON schema.
+/// - `is_project` - Whether to select the project metadata schema rather than
+///   the individual one.
+///
+/// # Returns
+///
+/// The alternative's schema, including the shared definitions, or the whole
+/// schema if it has no matching alternative.
+fn metadata_schema(schema: &Value, is_project: bool) -> Value {
+    let alternative = schema["oneOf"].as_array().and_then(|alternatives| {
+        alternatives.iter().find(|alternative| {
+            let requires_project_information = alternative["required"]
+                .as_array()
+                .is_some_and(|required| required.iter().any(|key| key == "project_information"));
+            requires_project_information == is_project
+        })
+    });
+
+    match alternative {
+        Some(alternative) => {
+            let mut alternative = alternative.clone();
+            alternative["$schema"] = schema["$schema"].clone();
+            alternative["definitions"] = schema["definitions"].clone();
+            alternative
+        }
+        None => schema.clone(),
+    }
+}
+
 /// Parses an individual-type metadata and returns a [`Metadata`] data structure.
 ///
 /// # Arguments
@@ -108,31 +356,220 @@ fn validate_metadata<T: Serialize>(metadata: &T) -> Result<(), ParserError> {
 ///
 /// # Returns
 ///
-/// A [`Metadata`] data structure.
-fn parse_individual(pairs: &[IndividualProgramPair]) -> Metadata {
-    let pairs: Vec<ProgramPair> = pairs
-        .into_iter()
-        .map(|pair| ProgramPair {
-            program_name: pair.program_name.to_string(),
-            program_description: pair.program_description.to_string(),
-            translation_tools: pair.translation_tools.0.clone(),
-            feature_relationship: map_feature_relationship(pair.feature_relationship),
-            c_program: Program {
-                language: Language::C,
-                documentation_url: pair.c_program.documentation_url.to_string(),
-                repository_url: pair.c_program.repository_url.to_string(),
-                source_paths: pair.c_program.source_paths.0.clone(),
-            },
-            rust_program: Program {
-                language: Language::Rust,
-                documentation_url: pair.rust_program.documentation_url.to_string(),
-                repository_url: pair.rust_program.repository_url.to_string(),
-                source_paths: pair.rust_program.source_paths.0.clone(),
-            },
+/// A [`Metadata`] data structure on success, or [`ParserError::InvalidValue`]
+/// listing every malformed URL or path.
+fn parse_individual(pairs: &[IndividualProgramPair]) -> Result<Metadata, ParserError> {
+    let mut issues = Vec::new();
+    let pairs = pairs
+        .iter()
+        .filter_map(|pair| {
+            let mut errors = Vec::new();
+            let parsed = individual_pair(pair, &mut errors);
+            issues.extend(value_issues(&pair.program_name, errors));
+            parsed
         })
         .collect();
+    if !issues.is_empty() {
+        return Err(ParserError::InvalidValue { issues });
+    }
+
+    Ok(Metadata { pairs })
+}
 
-    Metadata { pairs }
+/// Parses one pair of individual-type metadata.
+///
+/// # Arguments
+///
+/// - `pair` - The pair as specified in the JSON schema.
+/// - `errors` - Where every malformed value of the pair is recorded.
+///
+/// # Returns
+///
+/// The [`ProgramPair`], or `None` if any value is malformed.
+fn individual_pair(
+    pair: &IndividualProgramPair,
+    errors: &mut Vec<SchemaError>,
+) -> Option<ProgramPair> {
+    let program_name = check(program_name(&pair.program_name), errors);
+    let function_mappings = check(function_mappings(&pair.function_mappings), errors);
+    let (c_language, rust_language) = check(
+        languages(pair.c_program.language, pair.rust_program.language),
+        errors,
+    )?;
+    let (c_output_directory, rust_output_directory) = check(
+        output_directories(
+            (&c_language, &pair.c_program.output_directory),
+            (&rust_language, &pair.rust_program.output_directory),
+        ),
+        errors,
+    )?;
+    let directories = [
+        (&c_output_directory, &c_language),
+        (&rust_output_directory, &rust_language),
+    ]
+    .map(|(directory, language)| {
+        directory
+            .clone()
+            .unwrap_or_else(|| language.program_directory().to_string())
+    });
+    let rust_variants = rust_variants(&pair.rust_variants, directories.into(), errors);
+    let c_program = individual_program(&pair.c_program, c_language, c_output_directory, errors);
+    let rust_program = individual_program(
+        &pair.rust_program,
+        rust_language,
+        rust_output_directory,
+        errors,
+    );
+    Some(ProgramPair {
+        program_name: program_name?,
+        program_description: pair.program_description.to_string(),
+        translation_tools: translation_tools(&pair.translation_tools.0),
+        feature_relationship: map_feature_relationship(pair.feature_relationship),
+        c_program: c_program?,
+        rust_program: rust_program?,
+        rust_variants: rust_variants?,
+        function_mappings: function_mappings?,
+        tags: tags(&[&pair.tags]),
+    })
+}
+
+/// Parses one program of individual-type metadata.
+///
+/// # Arguments
+///
+/// - `program` - The program as specified in the JSON schema.
+/// - `language` - The program's validated language.
+/// - `output_directory` - The program's validated `output_directory`.
+/// - `errors` - Where every malformed value of the program is recorded.
+///
+/// # Returns
+///
+/// The [`Program`], or `None` if a URL, path, or source is malformed.
+fn individual_program(
+    program: &IndividualProgram,
+    language: Language,
+    output_directory: Option<String>,
+    errors: &mut Vec<SchemaError>,
+) -> Option<Program> {
+    let source = check(
+        source(
+            &program.repository_url,
+            program.source_type,
+            &program.checksum,
+            &program.release_tag,
+            &program.asset,
+        ),
+        errors,
+    );
+    let branch = source
+        .as_ref()
+        .and_then(|(source_type, ..)| check(branch(&program.branch, *source_type), errors));
+    let documentation_url = check(DocUrl::new(program.documentation_url.to_string()), errors);
+    let repository_url = check(RepoUrl::new(program.repository_url.to_string()), errors);
+    let mirror_urls = check(mirror_urls(&program.mirror_urls), errors);
+    let source_paths = check(source_paths(&program.source_paths.0), errors);
+    let exclude_paths = check(exclude_paths(&program.exclude_paths), errors);
+    let build_files = check(build_files(&program.build_files), errors);
+    let patches = check(patches(&program.patches), errors);
+    let (source_type, checksum, release) = source?;
+    Some(Program {
+        language: language.clone(),
+        documentation_url: documentation_url?,
+        repository_url: repository_url?,
+        mirror_urls: mirror_urls?,
+        source_paths: source_paths?,
+        exclude_paths: exclude_paths?,
+        build_files: build_files?,
+        patches: patches?,
+        extensions: extension_filter(
+            language,
+            &program.include_extensions,
+            &program.exclude_extensions,
+        ),
+        clone_depth: program.clone_depth.as_ref().map(|depth| depth.0),
+        branch: branch?,
+        needs_submodules: needs_submodules(&program.needs_submodules),
+        source_type,
+        checksum,
+        release,
+        output_directory,
+        flatten: program.flatten.as_ref().map(|flatten| flatten.0),
+    })
+}
+
+/// Parses and validates the optional `rust_variants` field of a pair.
+///
+/// # Arguments
+///
+/// - `variants` - The `rust_variants` field, if present.
+/// - `directories` - The directories of the pair's C and Rust programs,
+///   which the variants may not share.
+/// - `errors` - Where every malformed value of the variants is recorded.
+///
+/// # Returns
+///
+/// The [`RustVariant`]s in order, or `None` if a label is not lowercase and
+/// hyphenated or is repeated, a program is malformed, or two programs of the
+/// pair would be placed in the same directory.
+fn rust_variants(
+    variants: &Option<metadata_structs::RustVariants>,
+    mut directories: BTreeSet<String>,
+    errors: &mut Vec<SchemaError>,
+) -> Option<Vec<RustVariant>> {
+    let previous_errors = errors.len();
+    let mut labels = BTreeSet::new();
+    let mut parsed = Vec::new();
+    for variant in variants.iter().flat_map(|variants| variants.iter()) {
+        let label = variant.label.to_string();
+        let is_hyphenated = label.split('-').all(|word| {
+            !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        });
+        if !is_hyphenated {
+            errors.push(SchemaError::InvalidVariant {
+                label,
+                reason: "expected lowercase words separated by hyphens",
+            });
+            continue;
+        }
+        if !labels.insert(label.clone()) {
+            errors.push(SchemaError::InvalidVariant {
+                label,
+                reason: "the label is used by another variant",
+            });
+            continue;
+        }
+        let Some((_, language)) = check(languages(None, variant.program.language), errors) else {
+            continue;
+        };
+        let output_directory = variant
+            .program
+            .output_directory
+            .as_ref()
+            .map(output_directory_name)
+            .transpose();
+        let Some(output_directory) = check(output_directory, errors) else {
+            continue;
+        };
+        let directory = output_directory
+            .clone()
+            .unwrap_or_else(|| variant_directory(language.program_directory(), &label));
+        if !directories.insert(directory.clone()) {
+            errors.push(SchemaError::InvalidOutputDirectory {
+                directory,
+                reason: "a variant cannot share a directory with another program of its pair",
+            });
+            continue;
+        }
+        if let Some(program) =
+            individual_program(&variant.program, language, output_directory, errors)
+        {
+            parsed.push(RustVariant { label, program });
+        }
+    }
+    (errors.len() == previous_errors).then_some(parsed)
 }
 
 /// Parses an project-type metadata and returns a [`Metadata`] data structure.
@@ -143,39 +580,680 @@ fn parse_individual(pairs: &[IndividualProgramPair]) -> Metadata {
 ///
 /// # Returns
 ///
-/// A [`Metadata`] data structure.
+/// A [`Metadata`] data structure on success, or [`ParserError::InvalidValue`]
+/// listing every malformed URL or path.
 fn parse_project(
     pairs: &[ProjectProgramPair],
     project_information: &ProjectPairsMetadataProjectInformation,
-) -> Metadata {
-    let pairs: Vec<ProgramPair> = pairs
-        .into_iter()
-        .map(|pair| ProgramPair {
-            program_name: pair.program_name.to_string(),
-            program_description: pair.program_description.to_string(),
-            translation_tools: project_information.translation_tools.0.clone(),
-            feature_relationship: map_feature_relationship(
-                project_information.feature_relationship,
-            ),
-            c_program: Program {
-                language: Language::C,
-                documentation_url: project_information.c_program.documentation_url.to_string(),
-                repository_url: project_information.c_program.repository_url.to_string(),
-                source_paths: pair.c_program.source_paths.0.clone(),
-            },
-            rust_program: Program {
-                language: Language::Rust,
-                documentation_url: project_information
-                    .rust_program
-                    .documentation_url
-                    .to_string(),
-                repository_url: project_information.rust_program.repository_url.to_string(),
-                source_paths: pair.rust_program.source_paths.0.clone(),
-            },
+) -> Result<Metadata, ParserError> {
+    let mut issues = Vec::new();
+    let pairs = pairs
+        .iter()
+        .filter_map(|pair| {
+            let mut errors = Vec::new();
+            let parsed = project_pair(pair, project_information, &mut errors);
+            issues.extend(value_issues(&pair.program_name, errors));
+            parsed
         })
         .collect();
+    if !issues.is_empty() {
+        return Err(ParserError::InvalidValue { issues });
+    }
+
+    Ok(Metadata { pairs })
+}
+
+/// Parses one pair of project-type metadata.
+///
+/// # Arguments
+///
+/// - `pair` - The pair as specified in the JSON schema.
+/// - `project_information` - The fields shared by every pair of the project.
+/// - `errors` - Where every malformed value of the pair is recorded.
+///
+/// # Returns
+///
+/// The [`ProgramPair`], or `None` if any value is malformed.
+fn project_pair(
+    pair: &ProjectProgramPair,
+    project_information: &ProjectPairsMetadataProjectInformation,
+    errors: &mut Vec<SchemaError>,
+) -> Option<ProgramPair> {
+    let c_global = &project_information.c_program;
+    let rust_global = &project_information.rust_program;
+    let program_name = check(program_name(&pair.program_name), errors);
+    let function_mappings = check(function_mappings(&pair.function_mappings), errors);
+    let (c_language, rust_language) =
+        check(languages(c_global.language, rust_global.language), errors)?;
+    let (c_output_directory, rust_output_directory) = check(
+        output_directories(
+            (&c_language, &pair.c_program.output_directory),
+            (&rust_language, &pair.rust_program.output_directory),
+        ),
+        errors,
+    )?;
+    let c_program = project_program(
+        (c_global, &pair.c_program),
+        c_language,
+        c_output_directory,
+        errors,
+    );
+    let rust_program = project_program(
+        (rust_global, &pair.rust_program),
+        rust_language,
+        rust_output_directory,
+        errors,
+    );
+    Some(ProgramPair {
+        program_name: program_name?,
+        program_description: pair.program_description.to_string(),
+        translation_tools: translation_tools(&project_information.translation_tools.0),
+        feature_relationship: map_feature_relationship(project_information.feature_relationship),
+        c_program: c_program?,
+        rust_program: rust_program?,
+        function_mappings: function_mappings?,
+        rust_variants: Vec::new(),
+        tags: tags(&[&project_information.tags, &pair.tags]),
+    })
+}
+
+/// Parses one program of project-type metadata.
+///
+/// # Arguments
+///
+/// - `(global, program)` - The fields of the program shared by every pair of
+///   the project, and those of this pair.
+/// - `language` - The program's validated language.
+/// - `output_directory` - The program's validated `output_directory`.
+/// - `errors` - Where every malformed value of the program is recorded.
+///
+/// # Returns
+///
+/// The [`Program`], or `None` if a URL, path, or source is malformed.
+fn project_program(
+    (global, program): (&ProjectGlobalProgram, &ProjectProgram),
+    language: Language,
+    output_directory: Option<String>,
+    errors: &mut Vec<SchemaError>,
+) -> Option<Program> {
+    let source = check(
+        source(
+            &global.repository_url,
+            global.source_type,
+            &global.checksum,
+            &global.release_tag,
+            &global.asset,
+        ),
+        errors,
+    );
+    let branch = source
+        .as_ref()
+        .and_then(|(source_type, ..)| check(branch(&global.branch, *source_type), errors));
+    let documentation_url = check(DocUrl::new(global.documentation_url.to_string()), errors);
+    let repository_url = check(RepoUrl::new(global.repository_url.to_string()), errors);
+    let mirror_urls = check(mirror_urls(&global.mirror_urls), errors);
+    let source_paths = check(source_paths(&program.source_paths.0), errors);
+    let exclude_paths = check(exclude_paths(&program.exclude_paths), errors);
+    let build_files = check(build_files(&program.build_files), errors);
+    let patches = check(patches(&program.patches), errors);
+    let (source_type, checksum, release) = source?;
+    Some(Program {
+        language: language.clone(),
+        documentation_url: documentation_url?,
+        repository_url: repository_url?,
+        mirror_urls: mirror_urls?,
+        source_paths: source_paths?,
+        exclude_paths: exclude_paths?,
+        build_files: build_files?,
+        patches: patches?,
+        extensions: extension_filter(
+            language,
+            &program.include_extensions,
+            &program.exclude_extensions,
+        ),
+        clone_depth: global.clone_depth.as_ref().map(|depth| depth.0),
+        branch: branch?,
+        needs_submodules: needs_submodules(&global.needs_submodules),
+        source_type,
+        checksum,
+        release,
+        output_directory,
+        flatten: program.flatten.as_ref().map(|flatten| flatten.0),
+    })
+}
+
+/// Records the error of a result, so that parsing goes on to find every
+/// other malformed value rather than stopping at the first.
+///
+/// # Arguments
+///
+/// - `result` - The result of validating a value.
+/// - `errors` - Where the error is recorded.
+///
+/// # Returns
+///
+/// The validated value, or `None` if it is malformed.
+fn check<T>(result: Result<T, SchemaError>, errors: &mut Vec<SchemaError>) -> Option<T> {
+    result.map_err(|error| errors.push(error)).ok()
+}
+
+/// Attributes the malformed values of a pair to it.
+///
+/// # Arguments
+///
+/// - `program_name` - The name of the pair, as written in the metadata.
+/// - `errors` - The malformed values of the pair.
+///
+/// # Returns
+///
+/// A [`ValueIssue`] for each malformed value.
+fn value_issues(
+    program_name: &str,
+    errors: Vec<SchemaError>,
+) -> impl Iterator<Item = ValueIssue> + '_ {
+    errors.into_iter().map(|error| ValueIssue {
+        program_name: program_name.to_string(),
+        error,
+    })
+}
+
+/// Validates the `source_paths` field of a program.
+///
+/// # Arguments
+///
+/// - `paths` - The `source_paths` field.
+///
+/// # Returns
+///
+/// The source paths, or [`SchemaError`] if any path is malformed.
+fn source_paths(paths: &[String]) -> Result<Vec<RelativeSourcePath>, SchemaError> {
+    paths
+        .iter()
+        .map(|path| RelativeSourcePath::new(path.clone()))
+        .collect()
+}
+
+/// Unwraps and validates the optional `exclude_paths` field of a program.
+///
+/// # Arguments
+///
+/// - `paths` - The `exclude_paths` field, if present.
+///
+/// # Returns
+///
+/// The excluded paths, or an empty list if the field is absent, or
+/// [`SchemaError`] if any path is malformed.
+fn exclude_paths(paths: &Option<ExcludePaths>) -> Result<Vec<RelativeSourcePath>, SchemaError> {
+    paths
+        .as_ref()
+        .map_or(Ok(Vec::new()), |paths| source_paths(&paths.0))
+}
+
+/// Unwraps and validates the optional `build_files` field of a program.
+///
+/// # Arguments
+///
+/// - `paths` - The `build_files` field, if present.
+///
+/// # Returns
+///
+/// The build files, or an empty list if the field is absent, or
+/// [`SchemaError`] if any path is malformed.
+fn build_files(paths: &Option<BuildFiles>) -> Result<Vec<RelativeSourcePath>, SchemaError> {
+    paths
+        .as_ref()
+        .map_or(Ok(Vec::new()), |paths| source_paths(&paths.0))
+}
+
+/// Unwraps and validates the optional `patches` field of a program.
+///
+/// # Arguments
+///
+/// - `paths` - The `patches` field, if present.
+///
+/// # Returns
+///
+/// The patch files, or an empty list if the field is absent, or
+/// [`SchemaError`] if any path is malformed.
+fn patches(paths: &Option<Patches>) -> Result<Vec<RelativeSourcePath>, SchemaError> {
+    paths
+        .as_ref()
+        .map_or(Ok(Vec::new()), |paths| source_paths(&paths.0))
+}
+
+/// Builds the extension filter of a program from its optional
+/// `include_extensions` and `exclude_extensions` fields.
+///
+/// # Arguments
+///
+/// - `language` - The program's language, whose default extensions are used
+///   if `include_extensions` is absent.
+/// - `include` - The `include_extensions` field, if present.
+/// - `exclude` - The `exclude_extensions` field, if present.
+///
+/// # Returns
+///
+/// The program's [`ExtensionFilter`].
+fn extension_filter(
+    language: Language,
+    include: &Option<Extensions>,
+    exclude: &Option<Extensions>,
+) -> ExtensionFilter {
+    let exclude = exclude
+        .iter()
+        .flat_map(|extensions| extensions.0.iter().map(|extension| extension.as_str()));
+    match include {
+        Some(include) => ExtensionFilter::new(include.0.iter().map(|e| e.as_str()), exclude),
+        None => ExtensionFilter::new(language.default_extensions(), exclude),
+    }
+}
+
+/// Unwraps and validates the optional `mirror_urls` field of a program.
+///
+/// # Arguments
+///
+/// - `urls` - The `mirror_urls` field, if present.
+///
+/// # Returns
+///
+/// The mirror URLs, or an empty list if the field is absent, or
+/// [`SchemaError`] if any URL is malformed.
+fn mirror_urls(urls: &Option<MirrorUrls>) -> Result<Vec<RepoUrl>, SchemaError> {
+    urls.as_ref().map_or(Ok(Vec::new()), |urls| {
+        urls.0
+            .iter()
+            .map(|url| RepoUrl::new(url.to_string()))
+            .collect()
+    })
+}
+
+/// Unwraps and validates the optional `function_mappings` field of a pair.
+///
+/// # Arguments
+///
+/// - `mappings` - The `function_mappings` field, if present.
+///
+/// # Returns
+///
+/// The function mappings, or an empty list if the field is absent, or
+/// [`SchemaError`] if a file path or line range is malformed.
+fn function_mappings(
+    mappings: &Option<FunctionMappings>,
+) -> Result<Vec<FunctionMapping>, SchemaError> {
+    let location = |location: &metadata_structs::FunctionLocation| {
+        let start_line = location.start_line.map(u64::from);
+        let end_line = location.end_line.map(u64::from);
+        if let (Some(start_line), Some(end_line)) = (start_line, end_line)
+            && end_line < start_line
+        {
+            return Err(SchemaError::ReversedLineRange {
+                function: location.function.to_string(),
+                start_line,
+                end_line,
+            });
+        }
+        Ok(FunctionLocation {
+            file: RelativeSourcePath::new(location.file.to_string())?,
+            function: location.function.to_string(),
+            start_line,
+            end_line,
+        })
+    };
+
+    mappings.as_ref().map_or(Ok(Vec::new()), |mappings| {
+        mappings
+            .0
+            .iter()
+            .map(|mapping| {
+                Ok(FunctionMapping {
+                    c: location(&mapping.c)?,
+                    rust: location(&mapping.rust)?,
+                })
+            })
+            .collect()
+    })
+}
+
+/// Unwraps the optional `needs_submodules` field of a program.
+///
+/// # Arguments
+///
+/// - `needs_submodules` - The `needs_submodules` field, if present.
+///
+/// # Returns
+///
+/// Whether submodules are needed, which is false if the field is absent.
+fn needs_submodules(needs_submodules: &Option<NeedsSubmodules>) -> bool {
+    needs_submodules
+        .as_ref()
+        .is_some_and(|needs_submodules| needs_submodules.0)
+}
+
+/// Validates the `source_type`, `checksum`, `release_tag`, and `asset`
+/// fields of a program.
+///
+/// # Arguments
+///
+/// - `repository_url` - The `repository_url` field, whose releases hold the
+///   release asset.
+/// - `source_type` - The `source_type` field, if present.
+/// - `checksum` - The `checksum` field, if present.
+/// - `release_tag` - The `release_tag` field, if present.
+/// - `asset` - The `asset` field, if present.
+///
+/// # Returns
+///
+/// The [`SourceType`], which is git if the field is absent, or a tarball for
+/// a release asset, the checksum in lowercase, and the [`ReleaseAsset`], or
+/// [`SchemaError`] if a tarball or file source has no checksum, the checksum
+/// is not hexadecimal, or the release asset is incomplete, a git source, not
+/// a plain file name, or not in a GitHub repository.
+fn source(
+    repository_url: &str,
+    source_type: Option<metadata_structs::SourceType>,
+    checksum: &Option<Checksum>,
+    release_tag: &Option<ReleaseTag>,
+    asset: &Option<Asset>,
+) -> Result<(SourceType, Option<String>, Option<ReleaseAsset>), SchemaError> {
+    let release = match (release_tag, asset) {
+        (Some(_), Some(asset))
+            if matches!(asset.as_str(), "" | "." | "..") || asset.contains(['/', '\\']) =>
+        {
+            return Err(SchemaError::InvalidRelease {
+                reason: "asset must be a file name",
+            });
+        }
+        (Some(_), Some(_)) if !is_github_repository(repository_url) => {
+            return Err(SchemaError::InvalidRelease {
+                reason: "release assets are only downloaded from github.com repositories",
+            });
+        }
+        (Some(release_tag), Some(asset)) => Some(ReleaseAsset {
+            tag: release_tag.to_string(),
+            asset: asset.to_string(),
+        }),
+        (None, None) => None,
+        _ => {
+            return Err(SchemaError::InvalidRelease {
+                reason: "release_tag and asset must be set together",
+            });
+        }
+    };
+    let source_type = match source_type {
+        None if release.is_some() => SourceType::Tarball,
+        None | Some(metadata_structs::SourceType::Git) => SourceType::Git,
+        Some(metadata_structs::SourceType::Tarball) => SourceType::Tarball,
+        Some(metadata_structs::SourceType::HttpFile) => SourceType::HttpFile,
+    };
+    let checksum = checksum
+        .as_ref()
+        .map(|checksum| {
+            if checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+                Ok(checksum.to_ascii_lowercase())
+            } else {
+                Err(SchemaError::InvalidChecksum {
+                    checksum: checksum.to_string(),
+                })
+            }
+        })
+        .transpose()?;
+    if release.is_some() && source_type == SourceType::Git {
+        return Err(SchemaError::InvalidRelease {
+            reason: "a release asset is a tarball or file, not a git source",
+        });
+    }
+    if source_type != SourceType::Git && checksum.is_none() {
+        return Err(SchemaError::MissingChecksum {
+            source_type: source_type.to_str(),
+        });
+    }
+    Ok((source_type, checksum, release))
+}
+
+/// Checks whether a repository URL is an HTTP(S) URL of a repository on
+/// github.com, the only host whose release asset URLs are known.
+///
+/// # Arguments
+///
+/// - `url` - The repository URL.
+///
+/// # Returns
+///
+/// Whether `url` is an `https` or `http` URL whose host is `github.com`.
+fn is_github_repository(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return false;
+    }
+    let normalized = normalize_repository_url(&url);
+    let host = normalized.split('/').next().unwrap_or_default();
+    host.split(':').next() == Some("github.com")
+}
 
-    Metadata { pairs }
+/// Validates the `program_name` field of a pair, which names the pair's
+/// directory under `program_pairs/`, so it must not lead out of it.
+///
+/// # Arguments
+///
+/// - `program_name` - The `program_name` field.
+///
+/// # Returns
+///
+/// The program name, or [`SchemaError::InvalidProgramName`] if it is not a
+/// valid name by [`metadata::is_valid_name`].
+fn program_name(program_name: &str) -> Result<String, SchemaError> {
+    if metadata::is_valid_name(program_name) {
+        Ok(program_name.to_string())
+    } else {
+        Err(SchemaError::InvalidProgramName {
+            program_name: program_name.to_string(),
+        })
+    }
+}
+
+/// Validates the optional `branch` field of a program.
+///
+/// # Arguments
+///
+/// - `branch` - The `branch` field, if present.
+/// - `source_type` - The program's [`SourceType`]; only git repositories
+///   have branches.
+///
+/// # Returns
+///
+/// The branch, if set, or [`SchemaError::InvalidBranch`] if it is not a
+/// valid branch name or the source is not a git repository.
+fn branch(branch: &Option<Branch>, source_type: SourceType) -> Result<Option<String>, SchemaError> {
+    let Some(branch) = branch else {
+        return Ok(None);
+    };
+    let invalid = |reason| SchemaError::InvalidBranch {
+        branch: branch.to_string(),
+        reason,
+    };
+    if source_type != SourceType::Git {
+        return Err(invalid("only git sources have branches"));
+    }
+    if !is_valid_branch_name(branch.as_str()) {
+        return Err(invalid("not a valid git branch name"));
+    }
+    Ok(Some(branch.to_string()))
+}
+
+/// Checks whether a name is a valid git branch name, following the rules of
+/// `git check-ref-format --branch`, so that parsing does not need git.
+///
+/// # Arguments
+///
+/// - `name` - The branch name.
+///
+/// # Returns
+///
+/// Whether `refs/heads/<name>` is a valid reference name and `name` does not
+/// start with `-`, which the git command would read as an option.
+fn is_valid_branch_name(name: &str) -> bool {
+    if name.is_empty()
+        || name == "@"
+        || name.starts_with('-')
+        || name.ends_with('.')
+        || name.contains("..")
+        || name.contains("@{")
+    {
+        return false;
+    }
+    let forbidden = |c: char| c.is_ascii_control() || " ~^:?*[\\".contains(c);
+    if name.chars().any(forbidden) {
+        return false;
+    }
+    // Splitting on `/` also rejects leading, trailing and repeated slashes,
+    // which leave empty components.
+    name.split('/').all(|component| {
+        !component.is_empty() && !component.starts_with('.') && !component.ends_with(".lock")
+    })
+}
+
+/// Validates the optional `language` fields of a pair's programs.
+///
+/// # Arguments
+///
+/// - `c_language` - The C program's `language` field, if present.
+/// - `rust_language` - The Rust program's `language` field, if present.
+///
+/// # Returns
+///
+/// The languages of the C and Rust programs, which default to C and Rust,
+/// or [`SchemaError::InvalidLanguage`] if the C program is not written in a
+/// language that is translated from, such as C++, or the Rust program not
+/// in one that is translated to, such as Go.
+fn languages(
+    c_language: Option<metadata_structs::Language>,
+    rust_language: Option<metadata_structs::Language>,
+) -> Result<(Language, Language), SchemaError> {
+    let language = |language| match language {
+        metadata_structs::Language::C => Language::C,
+        metadata_structs::Language::Cpp => Language::Cpp,
+        metadata_structs::Language::Rust => Language::Rust,
+        metadata_structs::Language::Go => Language::Go,
+        metadata_structs::Language::Zig => Language::Zig,
+    };
+    let c_language = c_language.map_or(Language::C, language);
+    let rust_language = rust_language.map_or(Language::Rust, language);
+    if !c_language.is_source() {
+        return Err(SchemaError::InvalidLanguage {
+            language: c_language.to_str(),
+            side: "C program",
+            expected: "c or cpp",
+        });
+    }
+    if rust_language.is_source() {
+        return Err(SchemaError::InvalidLanguage {
+            language: rust_language.to_str(),
+            side: "Rust program",
+            expected: "rust, go, or zig",
+        });
+    }
+    Ok((c_language, rust_language))
+}
+
+/// Validates the `output_directory` fields of a pair's programs.
+///
+/// # Arguments
+///
+/// - `c_program` - The C program's language and `output_directory` field,
+///   if present.
+/// - `rust_program` - The Rust program's language and `output_directory`
+///   field, if present.
+///
+/// # Returns
+///
+/// The output directories of the C and Rust programs, if set, or
+/// [`SchemaError`] if one is not a plain directory name or both programs
+/// would be placed in the same directory.
+fn output_directories(
+    (c_language, c_directory): (&Language, &Option<OutputDirectory>),
+    (rust_language, rust_directory): (&Language, &Option<OutputDirectory>),
+) -> Result<(Option<String>, Option<String>), SchemaError> {
+    let c_directory = c_directory
+        .as_ref()
+        .map(output_directory_name)
+        .transpose()?;
+    let rust_directory = rust_directory
+        .as_ref()
+        .map(output_directory_name)
+        .transpose()?;
+    let c_name = c_directory
+        .as_deref()
+        .unwrap_or(c_language.program_directory());
+    let rust_name = rust_directory
+        .as_deref()
+        .unwrap_or(rust_language.program_directory());
+    if c_name == rust_name {
+        return Err(SchemaError::InvalidOutputDirectory {
+            directory: c_name.to_string(),
+            reason: "the C and Rust programs cannot share a directory",
+        });
+    }
+    Ok((c_directory, rust_directory))
+}
+
+/// Validates an `output_directory` field.
+///
+/// # Arguments
+///
+/// - `directory` - The field.
+///
+/// # Returns
+///
+/// The directory name, or [`SchemaError::InvalidOutputDirectory`] if it is
+/// not a plain directory name.
+fn output_directory_name(directory: &OutputDirectory) -> Result<String, SchemaError> {
+    let mut components = Path::new(directory.as_str()).components();
+    let is_name = matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+        && !directory.contains(['/', '\\']);
+    if !is_name {
+        return Err(SchemaError::InvalidOutputDirectory {
+            directory: directory.to_string(),
+            reason: "expected a directory name without separators",
+        });
+    }
+    Ok(directory.to_string())
+}
+
+/// Recognizes the tools in the `translation_tools` field of a pair.
+///
+/// # Arguments
+///
+/// - `tools` - The tools as spelled in the metadata.
+///
+/// # Returns
+///
+/// The [`TranslationTool`] of each spelling, with known tools normalized.
+fn translation_tools(tools: &[String]) -> Vec<TranslationTool> {
+    tools
+        .iter()
+        .map(|tool| TranslationTool::parse(tool))
+        .collect()
+}
+
+/// Merges the optional `tags` fields that apply to a pair, such as those of
+/// a project and of the pair itself.
+///
+/// # Arguments
+///
+/// - `tag_lists` - The `tags` fields, if present.
+///
+/// # Returns
+///
+/// The tags of every field, sorted and without duplicates.
+fn tags(tag_lists: &[&Option<Tags>]) -> Vec<String> {
+    let tags: BTreeSet<String> = tag_lists
+        .iter()
+        .copied()
+        .flatten()
+        .flat_map(|tags| tags.iter().map(|tag| tag.to_string()))
+        .collect();
+    tags.into_iter().collect()
 }
 
 /// Convert from the `feature_relationship` field in
@@ -199,6 +1277,8 @@ fn map_feature_relationship(relationship: FeatureRelationship) -> Features {
 
 #[cfg(test)]
 mod tests {
+    use proptest::{prelude::*, test_runner::TestCaseError};
+
     use super::*;
     use crate::paths::{INDIVIDUAL_METADATA_DIRECTORY, PROJECT_METADATA_DIRECTORY};
 
@@ -227,4 +1307,519 @@ mod tests {
             result.err()
         );
     }
+
+    /// Tests that validation reports every schema violation rather than only
+    /// the first.
+    #[test]
+    fn test_validate_reports_every_violation() {
+        let metadata = serde_json::json!({
+            "pairs": [{
+                "program_name": "",
+                "program_description": "A program",
+                "translation_tools": "manual",
+                "feature_relationship": "rust_equivalent_to_c",
+                "c_program": {
+                    "documentation_url": "https://example.com",
+                    "repository_url": "https://example.com/c"
+                },
+                "rust_program": {
+                    "documentation_url": "https://example.com",
+                    "repository_url": "https://example.com/rust",
+                    "source_paths": ["src/main.rs"]
+                }
+            }]
+        });
+
+        let Err(ParserError::Validation { issues }) = validate_metadata(&metadata) else {
+            panic!("Expected validation to fail");
+        };
+        let mut violations: Vec<(&str, &str)> = issues
+            .iter()
+            .map(|issue| (issue.instance_path.as_str(), issue.constraint.as_str()))
+            .collect();
+        violations.sort();
+        assert_eq!(
+            vec![
+                ("/pairs/0/c_program", "required"),
+                ("/pairs/0/program_name", "minLength"),
+                ("/pairs/0/translation_tools", "type"),
+            ],
+            violations
+        );
+    }
+
+    /// Tests that parsing reports every malformed value of every pair rather
+    /// than only the first.
+    #[test]
+    fn test_parse_reports_every_invalid_value() {
+        let pair = |program_name: &str, c_path: &str, rust_path: &str| {
+            serde_json::json!({
+                "program_name": program_name,
+                "program_description": "A program",
+                "translation_tools": ["manual"],
+                "feature_relationship": "rust_equivalent_to_c",
+                "c_program": {
+                    "documentation_url": "https://example.com",
+                    "repository_url": "https://example.com/c",
+                    "source_paths": [c_path]
+                },
+                "rust_program": {
+                    "documentation_url": "https://example.com",
+                    "repository_url": "https://example.com/rust",
+                    "source_paths": [rust_path]
+                }
+            })
+        };
+        let mut reversed = pair("reversed", "main.c", "src");
+        reversed["function_mappings"] = serde_json::json!([{
+            "c": { "file": "main.c", "function": "main", "start_line": 5, "end_line": 1 },
+            "rust": { "file": "src/main.rs", "function": "main" }
+        }]);
+        let metadata = serde_json::json!({
+            "pairs": [
+                reversed,
+                pair("valid", "main.c", "src"),
+                pair("paths", "../main.c", "/src"),
+            ]
+        });
+
+        let result = parse_str(&metadata.to_string());
+        let Err(ParserError::InvalidValue { issues }) = result else {
+            panic!("Expected parsing to fail: {result:?}");
+        };
+        let issues: Vec<(&str, &SchemaError)> = issues
+            .iter()
+            .map(|issue| (issue.program_name.as_str(), &issue.error))
+            .collect();
+        assert!(
+            matches!(
+                issues[..],
+                [
+                    ("reversed", SchemaError::ReversedLineRange { .. }),
+                    ("paths", SchemaError::InvalidSourcePath { .. }),
+                    ("paths", SchemaError::InvalidSourcePath { .. }),
+                ]
+            ),
+            "{issues:?}"
+        );
+    }
+
+    /// Tests that output directories must be plain names that the two
+    /// programs of a pair do not share.
+    #[test]
+    fn test_output_directories() {
+        let directory = |name: &str| Some(OutputDirectory::try_from(name).unwrap());
+        let (c, rust, go) = (&Language::C, &Language::Rust, &Language::Go);
+        assert_eq!(
+            (Some("src".to_string()), None),
+            output_directories((c, &directory("src")), (rust, &None)).unwrap()
+        );
+        for name in ["..", "a/b", "a\\b", "/"] {
+            assert!(
+                output_directories((c, &directory(name)), (rust, &None)).is_err(),
+                "{name}"
+            );
+        }
+        assert!(output_directories((c, &directory("src")), (rust, &directory("src"))).is_err());
+        assert!(output_directories((c, &None), (rust, &directory("c-program"))).is_err());
+        assert!(output_directories((c, &None), (go, &directory("rust-program"))).is_ok());
+    }
+
+    /// Tests that programs default to C and Rust and stay on their side.
+    #[test]
+    fn test_languages() {
+        use metadata_structs::Language as Spelling;
+        assert_eq!(
+            (Language::C, Language::Rust),
+            languages(None, None).unwrap()
+        );
+        assert_eq!(
+            (Language::Cpp, Language::Go),
+            languages(Some(Spelling::Cpp), Some(Spelling::Go)).unwrap()
+        );
+        assert!(languages(Some(Spelling::Rust), None).is_err());
+        assert!(languages(None, Some(Spelling::C)).is_err());
+    }
+
+    /// Tests that release assets must be plain file names in GitHub
+    /// repositories, and that both fields are set together.
+    #[test]
+    fn test_source_release() {
+        let source = |url: &str, asset: &str| {
+            super::source(
+                url,
+                None,
+                &Some(Checksum::try_from("ab".repeat(32).as_str()).unwrap()),
+                &Some(ReleaseTag::try_from("v1.0").unwrap()),
+                &Some(Asset::try_from(asset).unwrap()),
+            )
+        };
+        let github = "https://github.com/gnu/hello";
+        let (source_type, _, release) = source(github, "hello-1.0.tar.gz").unwrap();
+        assert_eq!(SourceType::Tarball, source_type);
+        assert_eq!(
+            Some(ReleaseAsset {
+                tag: "v1.0".to_string(),
+                asset: "hello-1.0.tar.gz".to_string(),
+            }),
+            release
+        );
+        assert!(source("https://www.GitHub.com/gnu/hello.git", "hello.tar.gz").is_ok());
+        assert!(Asset::try_from("").is_err());
+        for asset in [".", "..", "a/b", "a\\b", "..\\a"] {
+            assert!(source(github, asset).is_err(), "{asset}");
+        }
+        for url in [
+            "https://gitlab.com/gnu/hello",
+            "https://github.com.example.com/gnu/hello",
+            "https://example.com/github.com/gnu/hello",
+            "git@github.com:gnu/hello.git",
+            "file:///github.com/gnu/hello",
+        ] {
+            assert!(source(url, "hello.tar.gz").is_err(), "{url}");
+        }
+        assert!(super::source("https://gitlab.com/gnu/hello", None, &None, &None, &None).is_ok());
+        assert!(
+            super::source(
+                github,
+                None,
+                &None,
+                &Some(ReleaseTag::try_from("v1.0").unwrap()),
+                &None
+            )
+            .is_err()
+        );
+    }
+
+    /// Tests that program names cannot lead out of `program_pairs/`.
+    #[test]
+    fn test_program_name() {
+        assert_eq!("c++filt", program_name("c++filt").unwrap());
+        for name in [
+            "../victim",
+            "..",
+            ".hidden",
+            "a/b",
+            "/tmp/victim",
+            "a\\b",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    program_name(name),
+                    Err(SchemaError::InvalidProgramName { .. })
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    /// Tests that branches must be valid git branch names on git sources.
+    #[test]
+    fn test_branch() {
+        let branch = |name: &str| Some(Branch::try_from(name).unwrap());
+        assert_eq!(
+            Some("rust-rewrite".to_string()),
+            super::branch(&branch("rust-rewrite"), SourceType::Git).unwrap()
+        );
+        assert_eq!(None, super::branch(&None, SourceType::Tarball).unwrap());
+        for name in [
+            "-b",
+            "a..b",
+            "a b",
+            "main.lock",
+            "a//b",
+            "a/",
+            ".a",
+            "a/.b",
+            "a.",
+            "a@{1",
+            "a~1",
+            "a:b",
+            "a\\b",
+            "a\0b",
+            "a?b",
+            "a*b",
+            "a[b",
+            "a^b",
+            "a\tb",
+            "a\x7fb",
+            "/a",
+            "@",
+        ] {
+            assert!(
+                super::branch(&branch(name), SourceType::Git).is_err(),
+                "{name}"
+            );
+        }
+        for name in ["feature/x", "v1.0", "a.lock-free", "@x"] {
+            assert!(
+                super::branch(&branch(name), SourceType::Git).is_ok(),
+                "{name}"
+            );
+        }
+        assert!(super::branch(&branch("main"), SourceType::Tarball).is_err());
+    }
+
+    /// Generates a program's documentation URL.
+    fn documentation_url() -> impl Strategy<Value = String> {
+        "https?://[a-z]{1,8}\\.(com|org)(/[a-z0-9-]{1,8}){0,2}"
+    }
+
+    /// Generates a program's repository URL.
+    fn url() -> impl Strategy<Value = String> {
+        prop_oneof![
+            documentation_url(),
+            "git://[a-z]{1,8}\\.org/[a-z]{1,8}\\.git",
+            "file:///tmp/[a-z]{1,8}",
+        ]
+    }
+
+    /// Generates a source path relative to a repository's root.
+    fn source_path() -> impl Strategy<Value = String> {
+        "[a-z]{1,8}(/[a-z_]{1,8}){0,2}(\\.(c|h|rs))?"
+    }
+
+    /// Generates the fields of a program that a project's pairs share.
+    fn global_program() -> impl Strategy<Value = Value> {
+        (
+            documentation_url(),
+            url(),
+            prop::option::of(0..100u64),
+            prop::option::of("[a-z][a-z0-9-]{0,10}"),
+            prop::option::of(prop::collection::vec(url(), 0..3)),
+            prop::option::of(any::<bool>()),
+        )
+            .prop_map(
+                |(documentation_url, repository_url, clone_depth, branch, mirrors, submodules)| {
+                    let mut program = serde_json::json!({
+                        "documentation_url": documentation_url,
+                        "repository_url": repository_url,
+                    });
+                    let fields = program.as_object_mut().unwrap();
+                    if let Some(clone_depth) = clone_depth {
+                        fields.insert("clone_depth".into(), clone_depth.into());
+                    }
+                    if let Some(branch) = branch {
+                        fields.insert("branch".into(), branch.into());
+                    }
+                    if let Some(mirrors) = mirrors {
+                        fields.insert("mirror_urls".into(), mirrors.into());
+                    }
+                    if let Some(submodules) = submodules {
+                        fields.insert("needs_submodules".into(), submodules.into());
+                    }
+                    program
+                },
+            )
+    }
+
+    /// Generates the fields of a program that are specific to one pair.
+    fn specific_program() -> impl Strategy<Value = Value> {
+        (
+            prop::collection::vec(source_path(), 1..4),
+            prop::option::of(prop::collection::vec(source_path(), 0..3)),
+            prop::option::of(prop::collection::vec("\\.?[a-z]{1,4}", 0..3)),
+            prop::option::of(any::<bool>()),
+        )
+            .prop_map(|(source_paths, exclude_paths, extensions, flatten)| {
+                let mut program = serde_json::json!({ "source_paths": source_paths });
+                let fields = program.as_object_mut().unwrap();
+                if let Some(exclude_paths) = exclude_paths {
+                    fields.insert("exclude_paths".into(), exclude_paths.into());
+                }
+                if let Some(extensions) = extensions {
+                    fields.insert("include_extensions".into(), extensions.into());
+                }
+                if let Some(flatten) = flatten {
+                    fields.insert("flatten".into(), flatten.into());
+                }
+                program
+            })
+    }
+
+    /// Generates a complete program, as in individual metadata.
+    fn individual_program() -> impl Strategy<Value = Value> {
+        (global_program(), specific_program()).prop_map(|(mut program, specific)| {
+            let fields = program.as_object_mut().unwrap();
+            fields.extend(specific.as_object().unwrap().clone());
+            program
+        })
+    }
+
+    /// Generates the fields that every pair and project has.
+    fn pair_fields() -> impl Strategy<Value = Value> {
+        (
+            "[a-z][a-z0-9_-]{0,15}",
+            prop::collection::vec(
+                prop_oneof![
+                    Just("c2rust"),
+                    Just("Manual"),
+                    Just("LLM assisted"),
+                    Just("rust2c")
+                ],
+                0..3,
+            ),
+            prop_oneof![
+                Just("rust_subset_of_c"),
+                Just("rust_equivalent_to_c"),
+                Just("rust_superset_of_c"),
+                Just("overlapping"),
+            ],
+            prop::option::of(prop::collection::btree_set("[a-z]{1,8}", 0..3)),
+        )
+            .prop_map(|(name, tools, relationship, tags)| {
+                let mut fields = serde_json::json!({
+                    "program_name": name,
+                    "translation_tools": tools,
+                    "feature_relationship": relationship,
+                });
+                if let Some(tags) = tags {
+                    fields["tags"] = tags.into_iter().collect::<Vec<_>>().into();
+                }
+                fields
+            })
+    }
+
+    /// Generates valid individual or project metadata documents.
+    fn metadata_document() -> impl Strategy<Value = Value> {
+        let individual_pair = (
+            pair_fields(),
+            ".{0,20}",
+            individual_program(),
+            individual_program(),
+        )
+            .prop_map(|(mut pair, description, c_program, rust_program)| {
+                pair["program_description"] = description.into();
+                pair["c_program"] = c_program;
+                pair["rust_program"] = rust_program;
+                pair
+            });
+        let individual = prop::collection::vec(individual_pair, 0..4)
+            .prop_map(|pairs| serde_json::json!({ "pairs": pairs }));
+
+        let project_pair = (
+            "[a-z][a-z0-9_-]{0,15}",
+            ".{0,20}",
+            specific_program(),
+            specific_program(),
+        )
+            .prop_map(|(name, description, c_program, rust_program)| {
+                serde_json::json!({
+                    "program_name": name,
+                    "program_description": description,
+                    "c_program": c_program,
+                    "rust_program": rust_program,
+                })
+            });
+        let project = (
+            pair_fields(),
+            global_program(),
+            global_program(),
+            prop::collection::vec(project_pair, 0..4),
+        )
+            .prop_map(|(mut information, c_program, rust_program, pairs)| {
+                information["c_program"] = c_program;
+                information["rust_program"] = rust_program;
+                serde_json::json!({ "project_information": information, "pairs": pairs })
+            });
+
+        prop_oneof![individual, project]
+    }
+
+    /// Generates any JSON value, to be put where metadata expects another.
+    fn json_value() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::from),
+            any::<i64>().prop_map(Value::from),
+            any::<f64>().prop_map(Value::from),
+            ".{0,12}".prop_map(Value::from),
+            prop_oneof![Just(""), Just(".."), Just("/etc"), Just("-b"), Just("a b")]
+                .prop_map(Value::from),
+        ];
+        leaf.prop_recursive(3, 16, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Value::from),
+                prop::collection::btree_map("[a-z_]{1,8}", inner, 0..4)
+                    .prop_map(|map| Value::Object(map.into_iter().collect())),
+            ]
+        })
+    }
+
+    /// Lists the JSON pointer of every value within a document.
+    fn pointers(value: &Value, pointer: String, all: &mut Vec<String>) {
+        match value {
+            Value::Object(fields) => {
+                for (key, field) in fields {
+                    pointers(field, format!("{pointer}/{key}"), all);
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    pointers(item, format!("{pointer}/{index}"), all);
+                }
+            }
+            _ => {}
+        }
+        all.push(pointer);
+    }
+
+    proptest! {
+        /// Tests that generated metadata documents parse, keeping their
+        /// pairs in order.
+        #[test]
+        fn test_parse_generated_metadata(document in metadata_document()) {
+            let metadata = parse_str(&document.to_string());
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(error) => return Err(TestCaseError::fail(format!("{error}"))),
+            };
+            let names: Vec<&str> = metadata
+                .pairs
+                .iter()
+                .map(|pair| pair.program_name.as_str())
+                .collect();
+            let expected: Vec<&str> = document["pairs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|pair| pair["program_name"].as_str().unwrap())
+                .collect();
+            prop_assert_eq!(expected, names);
+        }
+
+        /// Tests that metadata with any value replaced or removed is parsed
+        /// or rejected, but never panics.
+        #[test]
+        fn test_parse_mutated_metadata(
+            mut document in metadata_document(),
+            target in any::<prop::sample::Index>(),
+            replacement in prop::option::of(json_value()),
+        ) {
+            let mut all = Vec::new();
+            pointers(&document, String::new(), &mut all);
+            let pointer = target.get(&all).clone();
+            match (replacement, pointer.rsplit_once('/')) {
+                (Some(replacement), _) => *document.pointer_mut(&pointer).unwrap() = replacement,
+                (None, Some((parent, key))) => match document.pointer_mut(parent).unwrap() {
+                    Value::Object(fields) => {
+                        fields.remove(key);
+                    }
+                    Value::Array(items) => {
+                        items.remove(key.parse::<usize>().unwrap());
+                    }
+                    _ => unreachable!(),
+                },
+                (None, None) => document = Value::Null,
+            }
+            let _ = parse_str(&document.to_string());
+        }
+
+        /// Tests that arbitrary text is rejected without panicking.
+        #[test]
+        fn test_parse_arbitrary_text(text in ".{0,64}") {
+            let _ = parse_str(&text);
+        }
+    }
 }
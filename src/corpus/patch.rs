@@ -0,0 +1,470 @@
+//! # Patches
+//!
+//! This module applies the patches listed in a program's `patches` metadata
+//! to its downloaded files, so corpus-specific fixes, such as removing
+//! platform-specific code or stubbing out headers, are made the same way on
+//! every download rather than by hand.
+//!
+//! Patches are unified diffs, as written by `diff -u` or `git diff`, with
+//! paths relative to the program's directory; a leading `a/` or `b/` is
+//! stripped.  Files can be changed, created, and deleted, but not renamed.
+//! A hunk whose lines have moved since the patch was written is applied
+//! where its lines are now, but its lines must match exactly.
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use crate::corpus::{errors::PatchError, schema::RelativeSourcePath};
+
+/// The changes a patch makes to one file.
+#[derive(Debug, PartialEq, Eq)]
+struct FilePatch {
+    /// The path of the file before the patch, or `None` if it creates the
+    /// file.
+    old_path: Option<String>,
+    /// The path of the file after the patch, or `None` if it deletes the
+    /// file.
+    new_path: Option<String>,
+    /// The changed regions of the file, in order.
+    hunks: Vec<Hunk>,
+    /// Whether the patched file ends with a newline, if the patch says.
+    ends_with_newline: Option<bool>,
+}
+
+/// One changed region of a file.
+#[derive(Debug, PartialEq, Eq)]
+struct Hunk {
+    /// The line the region starts at before the patch, counting from 1, or
+    /// the line after which lines are inserted if it has no old lines.
+    old_start: usize,
+    /// The lines of the region.
+    lines: Vec<HunkLine>,
+}
+
+/// A line of a hunk.
+#[derive(Debug, PartialEq, Eq)]
+enum HunkLine {
+    /// A line kept by the patch.
+    Context(String),
+    /// A line removed by the patch.
+    Remove(String),
+    /// A line added by the patch.
+    Add(String),
+}
+
+/// The lines of a file, and whether its last line ends with a newline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileLines {
+    lines: Vec<String>,
+    ends_with_newline: bool,
+}
+
+impl FileLines {
+    /// Splits a file's contents into lines.
+    fn parse(contents: &str) -> FileLines {
+        FileLines {
+            lines: contents.lines().map(str::to_string).collect(),
+            ends_with_newline: contents.is_empty() || contents.ends_with('\n'),
+        }
+    }
+
+    /// Joins the lines back into the file's contents.
+    fn contents(&self) -> String {
+        let mut contents = self.lines.join("\n");
+        if self.ends_with_newline && !self.lines.is_empty() {
+            contents.push('\n');
+        }
+        contents
+    }
+}
+
+/// Applies a patch to the files in a directory.
+///
+/// Every hunk is checked before any file is written, so a patch that does
+/// not apply leaves the directory unchanged.  Patched files are written as
+/// new files, so a file linked into the directory by `--link` is replaced
+/// rather than changed through the link.
+///
+/// # Arguments
+///
+/// - `patch` - The patch file.
+/// - `directory` - The directory the patch's paths are relative to.
+///
+/// # Returns
+///
+/// The paths of the created, changed, and deleted files, relative to
+/// `directory`, or a [`PatchError`] if the patch is malformed or does not
+/// apply.
+pub fn apply(patch: &Path, directory: &Path) -> Result<Vec<String>, PatchError> {
+    let text = fs::read_to_string(patch).map_err(|error| PatchError::IoRead {
+        path: patch.to_path_buf(),
+        error,
+    })?;
+
+    // The patched contents of each file, or `None` if it is deleted, by
+    // path.
+    let mut files: BTreeMap<String, Option<FileLines>> = BTreeMap::new();
+    for file_patch in parse(&text)? {
+        // A changed file is patched under its new name, so a patch written
+        // by `diff -u ls.c.orig ls.c` applies to `ls.c`.
+        let Some(path) = file_patch
+            .new_path
+            .as_ref()
+            .or(file_patch.old_path.as_ref())
+        else {
+            continue;
+        };
+        let current = match files.get(path) {
+            Some(lines) => lines.clone(),
+            None => read(directory, path)?,
+        };
+        let unexpected = |reason| PatchError::UnexpectedFile {
+            path: path.clone(),
+            reason,
+        };
+        let mut lines = match (current, file_patch.old_path.is_some()) {
+            (Some(lines), true) => lines,
+            (None, false) => FileLines {
+                lines: Vec::new(),
+                ends_with_newline: true,
+            },
+            (None, true) => return Err(unexpected("does not exist")),
+            (Some(_), false) => return Err(unexpected("already exists")),
+        };
+        lines.lines = apply_hunks(&lines.lines, &file_patch.hunks).map_err(|hunk| {
+            PatchError::HunkMismatch {
+                path: path.clone(),
+                hunk,
+            }
+        })?;
+        if let Some(ends_with_newline) = file_patch.ends_with_newline {
+            lines.ends_with_newline = ends_with_newline;
+        }
+        files.insert(path.clone(), file_patch.new_path.is_some().then_some(lines));
+    }
+
+    for (path, lines) in &files {
+        let target = directory.join(path);
+        let removed = match fs::remove_file(&target) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        };
+        let written = removed.and_then(|_| match lines {
+            Some(lines) => {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&target, lines.contents())
+            }
+            None => Ok(()),
+        });
+        written.map_err(|error| PatchError::IoWrite {
+            path: target,
+            error,
+        })?;
+    }
+    Ok(files.into_keys().collect())
+}
+
+/// Reads a file the patch changes.
+///
+/// # Returns
+///
+/// The file's lines, `None` if it does not exist, or a [`PatchError`] if it
+/// cannot be read.
+fn read(directory: &Path, path: &str) -> Result<Option<FileLines>, PatchError> {
+    let path = directory.join(path);
+    match fs::read(&path) {
+        Ok(bytes) => Ok(Some(FileLines::parse(&String::from_utf8_lossy(&bytes)))),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(PatchError::IoRead { path, error }),
+    }
+}
+
+/// Parses a unified diff into the changes it makes to each file.
+///
+/// Lines outside of the file changes, such as the `diff --git` and `index`
+/// lines of `git diff` or a commit message, are ignored.
+///
+/// # Returns
+///
+/// The changes to each file, in order, or [`PatchError`] if the patch is
+/// malformed.
+fn parse(text: &str) -> Result<Vec<FilePatch>, PatchError> {
+    let mut lines = text.lines().enumerate().peekable();
+    let mut file_patches = Vec::new();
+    while let Some((index, line)) = lines.next() {
+        let Some(old_path) = line.strip_prefix("--- ") else {
+            continue;
+        };
+        let malformed = |line: usize, reason| PatchError::Malformed {
+            line: line + 1,
+            reason,
+        };
+        let new_path = match lines.next() {
+            Some((_, line)) if line.starts_with("+++ ") => &line[4..],
+            _ => return Err(malformed(index + 1, "expected a '+++' line after '---'")),
+        };
+        let mut file_patch = FilePatch {
+            old_path: patch_path(old_path)?,
+            new_path: patch_path(new_path)?,
+            hunks: Vec::new(),
+            ends_with_newline: None,
+        };
+
+        while let Some((index, header)) = lines.next_if(|(_, line)| line.starts_with("@@ ")) {
+            let (old_start, mut old_count, mut new_count) =
+                hunk_header(header).ok_or_else(|| malformed(index, "invalid hunk header"))?;
+            let mut hunk = Hunk {
+                old_start,
+                lines: Vec::new(),
+            };
+            while old_count > 0 || new_count > 0 {
+                let Some((index, line)) = lines.next() else {
+                    return Err(malformed(index, "the hunk ends early"));
+                };
+                // Some editors strip the space of empty context lines.
+                let (marker, content) = match line.chars().next() {
+                    Some(marker) => (marker, &line[marker.len_utf8()..]),
+                    None => (' ', ""),
+                };
+                let (hunk_line, old, new) = match marker {
+                    ' ' => (HunkLine::Context(content.to_string()), 1, 1),
+                    '-' => (HunkLine::Remove(content.to_string()), 1, 0),
+                    '+' => (HunkLine::Add(content.to_string()), 0, 1),
+                    _ => return Err(malformed(index, "expected ' ', '-', or '+'")),
+                };
+                if old > old_count || new > new_count {
+                    return Err(malformed(index, "the hunk is longer than its header says"));
+                }
+                old_count -= old;
+                new_count -= new;
+                hunk.lines.push(hunk_line);
+                // A "\ No newline at end of file" marker follows the last
+                // line of the old or new file.
+                if lines.next_if(|(_, line)| line.starts_with('\\')).is_some() {
+                    match hunk.lines.last() {
+                        Some(HunkLine::Remove(_)) => file_patch.ends_with_newline = Some(true),
+                        _ => file_patch.ends_with_newline = Some(false),
+                    }
+                }
+            }
+            file_patch.hunks.push(hunk);
+        }
+        if file_patch.hunks.is_empty() {
+            return Err(malformed(index + 1, "expected a hunk after '+++'"));
+        }
+        file_patches.push(file_patch);
+    }
+    Ok(file_patches)
+}
+
+/// Parses a hunk header, such as `@@ -12,5 +12,7 @@ fn main()`.
+///
+/// # Returns
+///
+/// The old start line and the numbers of old and new lines, or `None` if
+/// the header is malformed.
+fn hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+    let mut ranges = header.strip_prefix("@@ ")?.split(' ');
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(ranges.next()?.strip_prefix('-')?)?;
+    let (_, new_count) = range(ranges.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_count, new_count))
+}
+
+/// Validates the path of a file in a `---` or `+++` line.
+///
+/// # Returns
+///
+/// The path without any timestamp or `a/` or `b/` prefix, `None` for
+/// `/dev/null`, or [`PatchError::InvalidPath`] if the path is absolute or
+/// leaves the directory.
+fn patch_path(path: &str) -> Result<Option<String>, PatchError> {
+    let path = path.split('\t').next().unwrap_or_default().trim_end();
+    if path == "/dev/null" {
+        return Ok(None);
+    }
+    let relative = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    RelativeSourcePath::new(relative.to_string())
+        .map(|_| Some(relative.to_string()))
+        .map_err(|_| PatchError::InvalidPath {
+            path: path.to_string(),
+        })
+}
+
+/// Applies hunks to the lines of a file.
+///
+/// Each hunk is looked for at the line its header gives, moved as far as
+/// the previous hunk was found from its own, and then ever further before
+/// and after it, but never before the previous hunk.
+///
+/// # Returns
+///
+/// The patched lines, or the number of the first hunk, counting from 1,
+/// whose lines are not in the file.
+fn apply_hunks(lines: &[String], hunks: &[Hunk]) -> Result<Vec<String>, usize> {
+    let mut patched = Vec::new();
+    // The next unpatched line, and how far the previous hunk was found from
+    // the line its header gives.
+    let mut next = 0;
+    let mut shift: isize = 0;
+    for (number, hunk) in hunks.iter().enumerate() {
+        let old: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(line) | HunkLine::Remove(line) => Some(line.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect();
+        let matches = |start: usize| {
+            start + old.len() <= lines.len()
+                && old
+                    .iter()
+                    .zip(&lines[start..])
+                    .all(|(old, line)| old == line)
+        };
+        // A hunk without old lines inserts after its start line.
+        let start = if old.is_empty() {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        let expected = start.saturating_add_signed(shift).max(next);
+        let found = (0..=lines.len())
+            .flat_map(|distance| {
+                [
+                    expected.checked_add(distance),
+                    expected.checked_sub(distance),
+                ]
+            })
+            .flatten()
+            .filter(|start| *start >= next)
+            .find(|start| matches(*start))
+            .ok_or(number + 1)?;
+
+        patched.extend_from_slice(&lines[next..found]);
+        for line in &hunk.lines {
+            match line {
+                HunkLine::Context(line) | HunkLine::Add(line) => patched.push(line.clone()),
+                HunkLine::Remove(_) => {}
+            }
+        }
+        next = found + old.len();
+        shift = found as isize - start as isize;
+    }
+    patched.extend_from_slice(&lines[next..]);
+    Ok(patched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits text into owned lines.
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    /// Tests that a `git diff` patch is parsed, ignoring its extra lines.
+    #[test]
+    fn test_parse() {
+        let patch = "diff --git a/ls.c b/ls.c\n\
+                     index 1234567..89abcde 100644\n\
+                     --- a/ls.c\n\
+                     +++ b/ls.c\n\
+                     @@ -1,2 +1,2 @@\n\
+                     \x20#include <stdio.h>\n\
+                     -#include <selinux.h>\n\
+                     +#include \"stub.h\"\n\
+                     \\ No newline at end of file\n";
+        assert_eq!(
+            vec![FilePatch {
+                old_path: Some("ls.c".to_string()),
+                new_path: Some("ls.c".to_string()),
+                hunks: vec![Hunk {
+                    old_start: 1,
+                    lines: vec![
+                        HunkLine::Context("#include <stdio.h>".to_string()),
+                        HunkLine::Remove("#include <selinux.h>".to_string()),
+                        HunkLine::Add("#include \"stub.h\"".to_string()),
+                    ],
+                }],
+                ends_with_newline: Some(false),
+            }],
+            parse(patch).unwrap()
+        );
+        assert!(parse("--- a/ls.c\n+++ b/ls.c\n@@ -1,2 +1,2 @@\n a\n").is_err());
+        assert!(parse("--- a/../ls.c\n+++ b/ls.c\n@@ -1 +1 @@\n-a\n+b\n").is_err());
+    }
+
+    /// Tests that hunks apply where their lines moved to, and fail where
+    /// their lines are missing.
+    #[test]
+    fn test_apply_hunks() {
+        let patch = parse(
+            "--- a/f\n+++ b/f\n\
+             @@ -2,2 +2,2 @@\n b\n-c\n+C\n\
+             @@ -5,0 +6 @@\n+F\n",
+        )
+        .unwrap();
+        let hunks = &patch[0].hunks;
+        assert_eq!(
+            Ok(lines("a\nb\nC\nd\ne\nF")),
+            apply_hunks(&lines("a\nb\nc\nd\ne"), hunks)
+        );
+        // Two new lines at the top move both hunks down.
+        assert_eq!(
+            Ok(lines("x\ny\na\nb\nC\nd\ne\nF")),
+            apply_hunks(&lines("x\ny\na\nb\nc\nd\ne"), hunks)
+        );
+        assert_eq!(Err(1), apply_hunks(&lines("a\nb\nd\ne"), hunks));
+    }
+
+    /// Tests that files are created, changed, and deleted, and that nothing
+    /// is written if any hunk fails.
+    #[test]
+    fn test_apply() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        fs::write(root.join("main.c"), "int main(void) {\n    return 1;\n}\n").unwrap();
+        fs::write(root.join("old.h"), "#pragma once\n").unwrap();
+        let patch = root.join("fix.patch");
+        fs::write(
+            &patch,
+            "--- a/main.c\n+++ b/main.c\n@@ -2 +2 @@\n-    return 1;\n+    return 0;\n\
+             --- /dev/null\n+++ b/stub.h\n@@ -0,0 +1 @@\n+#define STUB 1\n\
+             --- a/old.h\n+++ /dev/null\n@@ -1 +0,0 @@\n-#pragma once\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec!["main.c", "old.h", "stub.h"],
+            apply(&patch, root).unwrap()
+        );
+        assert_eq!(
+            "int main(void) {\n    return 0;\n}\n",
+            fs::read_to_string(root.join("main.c")).unwrap()
+        );
+        assert_eq!(
+            "#define STUB 1\n",
+            fs::read_to_string(root.join("stub.h")).unwrap()
+        );
+        assert!(!root.join("old.h").exists());
+
+        // The patch no longer applies, and leaves the files as they are.
+        assert!(matches!(
+            apply(&patch, root),
+            Err(PatchError::HunkMismatch { hunk: 1, .. })
+        ));
+        assert!(root.join("stub.h").exists());
+    }
+}
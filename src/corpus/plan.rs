@@ -0,0 +1,317 @@
+//! # Download Planning
+//!
+//! This module decides which program pairs a download covers: it brings the
+//! metadata up to date with a metadata repository, loads the profile, parses
+//! the profile's metadata files, and keeps the pairs with the requested tags
+//! and languages.  It reads files only through a [`Storage`], fetches
+//! metadata only through a [`Fetcher`], and prints nothing, so that the same
+//! planning runs on metadata held in memory, such as in tests.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use crate::corpus::{
+    analyze,
+    downloader::{DownloadOptions, ParsedMetadata},
+    effects::{Fetcher, Storage},
+    errors::{DownloaderError, ParserError},
+    parser,
+    profile::{self, Profile},
+    schema::Metadata,
+};
+
+/// The program pairs a download covers.
+pub struct Plan {
+    /// The parsed metadata of each file, keeping only the pairs to download.
+    pub metadata: Vec<Metadata>,
+    /// The paths of the metadata files that failed to parse and their
+    /// errors.
+    pub invalid_files: Vec<(PathBuf, ParserError)>,
+    /// Whether the built-in demo metadata was written, as `metadata/demo/`
+    /// was missing.
+    pub wrote_demo: bool,
+}
+
+/// Parses the metadata files of the program pairs to download, keeping only
+/// the pairs with the requested tags.
+///
+/// Files that fail to parse are skipped and returned, unless
+/// [`DownloadOptions::strict`] is set.  The metadata is first brought up to
+/// date with [`DownloadOptions::metadata_repository`], if it is set.
+///
+/// # Arguments
+///
+/// - `demo` - True to parse only the metadata files in `metadata/demo/`,
+///   whatever [`DownloadOptions::profile`] is.
+/// - `options` - Options controlling the download.
+/// - `storage` - The storage holding the metadata.
+/// - `fetcher` - Fetches the metadata repository.
+///
+/// # Returns
+///
+/// The [`Plan`], keeping only the pairs in the profile, or a
+/// [`DownloaderError`] if the
+/// metadata repository cannot be fetched, the profile cannot be loaded or
+/// names unknown pairs, directory reading fails, or, in strict mode, any file
+/// fails to parse.
+pub fn parse_pairs(
+    demo: bool,
+    options: &DownloadOptions,
+    storage: &dyn Storage,
+    fetcher: &dyn Fetcher,
+) -> Result<Plan, DownloaderError> {
+    if let Some(repository_url) = &options.metadata_repository {
+        fetcher.sync_metadata(repository_url, options)?;
+    }
+    let profile_name = if demo {
+        profile::DEMO_PROFILE
+    } else {
+        options.profile.as_deref().unwrap_or(profile::FULL_PROFILE)
+    };
+    let profile =
+        Profile::load(storage, profile_name).map_err(|error| DownloaderError::Profile { error })?;
+    let wrote_demo = profile
+        .write_built_in_demo(storage)
+        .map_err(|error| DownloaderError::Profile { error })?;
+
+    let mut metadata = Vec::new();
+    let mut invalid_files = Vec::new();
+    for directory in &profile.directories() {
+        let (parsed, invalid) = parse_metadata_directory(storage, directory, &profile)?;
+        metadata.extend(parsed);
+        invalid_files.extend(invalid);
+    }
+    // A pair can be selected from both the demo and the other metadata.
+    let mut seen = BTreeSet::new();
+    for metadata in &mut metadata {
+        metadata
+            .pairs
+            .retain(|pair| seen.insert(pair.program_name.clone()));
+    }
+    if let Profile::Defined(definition) = &profile {
+        let unknown: Vec<&str> = definition
+            .pairs
+            .iter()
+            .filter(|pair| !seen.contains(*pair))
+            .map(String::as_str)
+            .collect();
+        // The pairs may be in files that failed to parse, which are reported
+        // instead.
+        if !unknown.is_empty() && invalid_files.is_empty() {
+            return Err(DownloaderError::UnknownProfilePairs {
+                profile: profile_name.to_string(),
+                pairs: unknown.join(", "),
+            });
+        }
+    }
+    if options.strict && !invalid_files.is_empty() {
+        return Err(DownloaderError::InvalidMetadata { invalid_files });
+    }
+    // Pairs are only tagged with a domain once `analyze --classify` has
+    // recorded one.
+    let analyses = analyze::load_results_from(storage).unwrap_or_default();
+    for metadata in &mut metadata {
+        for pair in &mut metadata.pairs {
+            analyze::add_domain_tag(pair, &analyses);
+        }
+        metadata
+            .pairs
+            .retain(|pair| pair.has_any_tag(&options.tags) && pair.is_in(&options.languages));
+    }
+    Ok(Plan {
+        metadata,
+        invalid_files,
+        wrote_demo,
+    })
+}
+
+/// Parses the metadata files in a directory, keeping the pairs in a profile.
+///
+/// Files that fail to parse are skipped.
+///
+/// # Arguments
+///
+/// - `storage` - The storage holding the directory.
+/// - `directory` - The directory containing the metadata JSON files,
+///   typically `metadata/individual/` or `metadata/projects/`.
+/// - `profile` - The profile whose pairs are kept.
+///
+/// # Returns
+///
+/// The parsed [`Metadata`](crate::corpus::schema::Metadata) of each file
+/// along with the paths of the files that failed to parse and their errors,
+/// or a [`DownloaderError`] if directory reading fails.
+pub fn parse_metadata_directory(
+    storage: &dyn Storage,
+    directory: &Path,
+    profile: &Profile,
+) -> Result<ParsedMetadata, DownloaderError> {
+    let metadata_files = parser::parse_directory_with(storage, directory).map_err(|error| {
+        DownloaderError::Parse {
+            path: directory.to_path_buf(),
+            error,
+        }
+    })?;
+
+    let mut metadata = Vec::new();
+    let mut invalid_files = Vec::new();
+    for (path, parsed) in metadata_files {
+        match parsed {
+            Ok(mut parsed) => {
+                parsed
+                    .pairs
+                    .retain(|pair| profile.includes(&path, &pair.program_name));
+                metadata.push(parsed);
+            }
+            Err(error) => invalid_files.push((path, error)),
+        }
+    }
+
+    Ok((metadata, invalid_files))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        corpus::effects::MemoryStorage,
+        paths::{
+            DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROFILES_FILE,
+            PROJECT_METADATA_DIRECTORY,
+        },
+    };
+
+    /// Fetches metadata repositories by writing fixed files to storage, and
+    /// records the repositories fetched.
+    struct FakeFetcher<'a> {
+        /// The storage to write the files to.
+        storage: &'a MemoryStorage,
+        /// The path and contents of each file a fetch writes.
+        files: &'a [(&'a str, &'a str)],
+        /// The URL of each repository fetched.
+        fetched: Mutex<Vec<String>>,
+    }
+
+    impl<'a> FakeFetcher<'a> {
+        fn new(storage: &'a MemoryStorage, files: &'a [(&'a str, &'a str)]) -> Self {
+            FakeFetcher {
+                storage,
+                files,
+                fetched: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Fetcher for FakeFetcher<'_> {
+        fn sync_metadata(
+            &self,
+            repository_url: &str,
+            _options: &DownloadOptions,
+        ) -> Result<(), DownloaderError> {
+            self.fetched
+                .lock()
+                .unwrap()
+                .push(repository_url.to_string());
+            for (path, contents) in self.files {
+                let path = Path::new(path);
+                if let Some(parent) = path.parent() {
+                    self.storage.create_dir_all(parent).unwrap();
+                }
+                self.storage.write(path, contents.as_bytes()).unwrap();
+            }
+            Ok(())
+        }
+    }
+
+    /// The names of the planned pairs.
+    fn pair_names(plan: &Plan) -> Vec<&str> {
+        plan.metadata
+            .iter()
+            .flat_map(|metadata| &metadata.pairs)
+            .map(|pair| pair.program_name.as_str())
+            .collect()
+    }
+
+    /// Tests that the demo is planned from the built-in demo metadata,
+    /// written to storage rather than to disk.
+    #[test]
+    fn test_parse_pairs_in_memory() {
+        let storage = MemoryStorage::default();
+        let fetcher = FakeFetcher::new(&storage, &[]);
+        let options = DownloadOptions::default();
+        let planned = parse_pairs(true, &options, &storage, &fetcher).unwrap();
+        assert_eq!(vec!["diff", "cmp"], pair_names(&planned));
+        assert!(planned.wrote_demo);
+        assert!(storage.is_file(&Path::new(DEMO_METADATA_DIRECTORY).join("diffutils.json")));
+        assert!(fetcher.fetched.lock().unwrap().is_empty());
+
+        let planned = parse_pairs(true, &options, &storage, &fetcher).unwrap();
+        assert!(!planned.wrote_demo);
+    }
+
+    /// Tests that the metadata repository is fetched before the metadata is
+    /// parsed, and that files failing to parse are returned rather than
+    /// failing the plan.
+    #[test]
+    fn test_parse_pairs_fetches_metadata_repository() {
+        let storage = MemoryStorage::default();
+        Profile::Demo.write_built_in_demo(&storage).unwrap();
+        let demo = storage
+            .read_to_string(&Path::new(DEMO_METADATA_DIRECTORY).join("diffutils.json"))
+            .unwrap();
+        let files = [
+            ("metadata/project/diffutils.json", demo.as_str()),
+            ("metadata/individual/broken.json", "{"),
+        ];
+        let fetcher = FakeFetcher::new(&storage, &files);
+        let options = DownloadOptions {
+            metadata_repository: Some("https://example.com/metadata.git".to_string()),
+            ..DownloadOptions::default()
+        };
+
+        let planned = parse_pairs(false, &options, &storage, &fetcher).unwrap();
+        assert_eq!(
+            vec!["https://example.com/metadata.git"],
+            *fetcher.fetched.lock().unwrap()
+        );
+        assert_eq!(vec!["diff", "cmp"], pair_names(&planned));
+        assert_eq!(
+            vec![PathBuf::from("metadata/individual/broken.json")],
+            planned
+                .invalid_files
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /// Tests that a profile in storage selects pairs and reports unknown
+    /// ones.
+    #[test]
+    fn test_parse_pairs_with_profile() {
+        let profiles = r#"{"profiles": {"one": {"pairs": ["cmp"]}, "bad": {"pairs": ["x"]}}}"#;
+        let storage = MemoryStorage::with_files([(PROFILES_FILE, profiles)]);
+        let fetcher = FakeFetcher::new(&storage, &[]);
+        Profile::Demo.write_built_in_demo(&storage).unwrap();
+        for directory in [PROJECT_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY] {
+            storage.create_dir_all(Path::new(directory)).unwrap();
+        }
+
+        let mut options = DownloadOptions {
+            profile: Some("one".to_string()),
+            ..DownloadOptions::default()
+        };
+        let planned = parse_pairs(false, &options, &storage, &fetcher).unwrap();
+        assert_eq!(vec!["cmp"], pair_names(&planned));
+
+        options.profile = Some("bad".to_string());
+        assert!(matches!(
+            parse_pairs(false, &options, &storage, &fetcher),
+            Err(DownloaderError::UnknownProfilePairs { .. })
+        ));
+    }
+}
@@ -0,0 +1,232 @@
+//! # Download Profiles
+//!
+//! This module reads `metadata/profiles.json`, which names curated subsets of
+//! the corpus, such as the pairs of a benchmark, so that experiments can each
+//! download their own subset with `download --profile NAME` without copying
+//! metadata around.  A profile lists metadata files, whose pairs it includes
+//! entirely, and the names of further pairs to include from any metadata
+//! file.  The built-in `full` profile holds the project and individual
+//! metadata and `demo` the demo metadata, unless `profiles.json` redefines
+//! them.  The demo metadata is built into the binary and written to
+//! `metadata/demo/` when that does not exist, so that an installed binary
+//! can run the demo from any directory.
+//!
+//! ```json
+//! {
+//!     "profiles": {
+//!         "benchmark": {
+//!             "metadata_files": ["metadata/project/coreutils.json"],
+//!             "pairs": ["grep", "find"]
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    corpus::{effects::Storage, errors::ProfileError},
+    paths::{
+        DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROFILES_FILE,
+        PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// Name of the built-in profile holding every project and individual pair.
+pub const FULL_PROFILE: &str = "full";
+
+/// Name of the built-in profile holding the pairs in `metadata/demo/`.
+pub const DEMO_PROFILE: &str = "demo";
+
+/// The file names and contents of the demo metadata built into the binary.
+const BUILT_IN_DEMO: &[(&str, &str)] = &[(
+    "diffutils.json",
+    include_str!("../../metadata/demo/diffutils.json"),
+)];
+
+/// A profile defined in `metadata/profiles.json`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileDefinition {
+    /// Metadata files, relative to the corpus root, whose pairs are all
+    /// included; they must be in `metadata/project/`,
+    /// `metadata/individual/`, or `metadata/demo/`, which are the
+    /// directories parsed.
+    pub metadata_files: Vec<PathBuf>,
+    /// Names of further pairs to include.
+    pub pairs: Vec<String>,
+}
+
+/// The contents of `metadata/profiles.json`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfilesFile {
+    /// The profiles, keyed by name.
+    profiles: BTreeMap<String, ProfileDefinition>,
+}
+
+/// The subset of the corpus to download.
+#[derive(Debug, Clone)]
+pub enum Profile {
+    /// Every pair in `metadata/project/` and `metadata/individual/`.
+    Full,
+    /// Every pair in `metadata/demo/`.
+    Demo,
+    /// The pairs selected by a profile in `metadata/profiles.json`.
+    Defined(ProfileDefinition),
+}
+
+impl Profile {
+    /// Loads a profile by name.
+    ///
+    /// # Arguments
+    ///
+    /// - `storage` - The storage holding `profiles.json` and the metadata.
+    /// - `name` - Name of the profile, such as `benchmark`.
+    ///
+    /// # Returns
+    ///
+    /// The [`Profile`] on success, or [`ProfileError`] if `profiles.json`
+    /// cannot be read, no profile has the name, or the profile lists a
+    /// metadata file that does not exist or is outside the metadata
+    /// directories.
+    pub fn load(storage: &dyn Storage, name: &str) -> Result<Self, ProfileError> {
+        let path = Path::new(PROFILES_FILE);
+        let mut file = ProfilesFile::default();
+        if storage.exists(path) {
+            let contents = storage
+                .read_to_string(path)
+                .map_err(|error| ProfileError::IoRead {
+                    path: path.to_path_buf(),
+                    error,
+                })?;
+            file = serde_json::from_str(&contents).map_err(|error| ProfileError::Deserialize {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        }
+
+        let Some(definition) = file.profiles.remove(name) else {
+            return match name {
+                FULL_PROFILE => Ok(Profile::Full),
+                DEMO_PROFILE => Ok(Profile::Demo),
+                _ => {
+                    let mut available: Vec<&str> =
+                        file.profiles.keys().map(String::as_str).collect();
+                    available.extend([FULL_PROFILE, DEMO_PROFILE]);
+                    available.sort_unstable();
+                    available.dedup();
+                    Err(ProfileError::Unknown {
+                        name: name.to_string(),
+                        available: available.join(", "),
+                    })
+                }
+            };
+        };
+        let directories = Profile::Defined(ProfileDefinition::default()).directories();
+        if let Some(outside) = definition.metadata_files.iter().find(|metadata_file| {
+            !directories.iter().any(|directory| {
+                metadata_file
+                    .parent()
+                    .is_some_and(|parent| same_path(parent, directory))
+            })
+        }) {
+            return Err(ProfileError::OutsideMetadata {
+                profile: name.to_string(),
+                path: outside.clone(),
+            });
+        }
+        if let Some(missing) = definition
+            .metadata_files
+            .iter()
+            .find(|metadata_file| !storage.is_file(metadata_file))
+        {
+            return Err(ProfileError::MissingFile {
+                profile: name.to_string(),
+                path: missing.clone(),
+            });
+        }
+        Ok(Profile::Defined(definition))
+    }
+
+    /// Writes the built-in demo metadata to `metadata/demo/` if the profile
+    /// is the built-in `demo` and that directory does not exist, so that the
+    /// demo does not depend on a checkout of the repository.
+    ///
+    /// # Arguments
+    ///
+    /// - `storage` - The storage to write the metadata to.
+    ///
+    /// # Returns
+    ///
+    /// Whether the metadata was written, or [`ProfileError::IoWrite`] if it
+    /// cannot be.
+    pub fn write_built_in_demo(&self, storage: &dyn Storage) -> Result<bool, ProfileError> {
+        let directory = Path::new(DEMO_METADATA_DIRECTORY);
+        if !matches!(self, Profile::Demo) || storage.exists(directory) {
+            return Ok(false);
+        }
+        storage
+            .create_dir_all(directory)
+            .map_err(|error| ProfileError::IoWrite {
+                path: directory.to_path_buf(),
+                error,
+            })?;
+        for (name, contents) in BUILT_IN_DEMO {
+            let path = directory.join(name);
+            storage
+                .write(&path, contents.as_bytes())
+                .map_err(|error| ProfileError::IoWrite { path, error })?;
+        }
+        Ok(true)
+    }
+
+    /// The directories whose metadata files the profile's pairs are taken
+    /// from.
+    pub fn directories(&self) -> Vec<PathBuf> {
+        let directories: &[&str] = match self {
+            Profile::Full => &[PROJECT_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY],
+            Profile::Demo => &[DEMO_METADATA_DIRECTORY],
+            Profile::Defined(_) => &[
+                PROJECT_METADATA_DIRECTORY,
+                INDIVIDUAL_METADATA_DIRECTORY,
+                DEMO_METADATA_DIRECTORY,
+            ],
+        };
+        directories.iter().map(PathBuf::from).collect()
+    }
+
+    /// Decides whether a program pair belongs to the profile.
+    ///
+    /// # Arguments
+    ///
+    /// - `metadata_file` - The metadata file the pair is defined in.
+    /// - `program_name` - Name of the pair.
+    pub fn includes(&self, metadata_file: &Path, program_name: &str) -> bool {
+        match self {
+            Profile::Full | Profile::Demo => true,
+            Profile::Defined(definition) => {
+                definition
+                    .metadata_files
+                    .iter()
+                    .any(|listed| same_path(listed, metadata_file))
+                    || definition.pairs.iter().any(|pair| pair == program_name)
+            }
+        }
+    }
+}
+
+/// Compares two relative paths, ignoring `.` components, so that
+/// `./metadata/demo/x.json` matches `metadata/demo/x.json`.
+fn same_path(a: &Path, b: &Path) -> bool {
+    fn components(path: &Path) -> impl Iterator<Item = Component<'_>> {
+        path.components()
+            .filter(|component| *component != Component::CurDir)
+    }
+    components(a).eq(components(b))
+}
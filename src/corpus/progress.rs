@@ -0,0 +1,664 @@
+//! # Download Progress
+//!
+//! This module renders the progress of a download on the terminal.  A single
+//! [`MultiProgress`] holds one overall bar counting processed program pairs
+//! and a nested bar for each repository being cloned.  Messages are printed
+//! above the bars so that they do not interleave with them.
+//!
+//! Before pairs start, the time the download is expected to take is printed
+//! from the [`Estimates`] of past clones, and each pair's estimate is shown
+//! while it downloads.
+//!
+//! Callers that draw the progress themselves, such as the TUI or a GUI or
+//! web frontend embedding this crate, instead receive it as
+//! [`DownloadEvent`]s while the bars stay hidden.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
+    time::{Duration, Instant},
+};
+
+use indicatif::{HumanDuration, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde::Serialize;
+
+use crate::corpus::{
+    errors::DownloaderError, git_cli::GitProgress, metrics::MetricsRecorder, timings::Estimates,
+    utils,
+};
+
+/// Template for the overall bar counting processed program pairs.
+const OVERALL_TEMPLATE: &str = "{bar:40.white/white} {pos}/{len} pairs (ETA {eta}) {msg}";
+
+/// Template for the nested bar of a repository being cloned.
+const CLONE_TEMPLATE: &str = "  {bar:30.white/white} {pos}/{len} objects (ETA {eta}) {msg}";
+
+/// A change in the progress of a download, sent to callers that draw the
+/// progress themselves.
+///
+/// Every program pair that is processed ends with exactly one of
+/// [`DownloadEvent::PairFinished`], [`DownloadEvent::PairUpToDate`],
+/// [`DownloadEvent::PairSkipped`], or [`DownloadEvent::PairFailed`].  Every
+/// line the terminal display would print is also sent, as a
+/// [`DownloadEvent::Message`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DownloadEvent {
+    /// A program pair started downloading.
+    PairStarted {
+        /// Name of the program pair.
+        program_name: String,
+    },
+    /// A repository clone, or the copy of files out of it, progressed.
+    CloneProgress {
+        /// The repository and the language of the program, such as
+        /// `diffutils (C)`.
+        repository_name: String,
+        /// Number of objects or other items processed.
+        position: u64,
+        /// Total number of items, or 0 if unknown.
+        length: u64,
+        /// What the clone is doing, such as `Cloning diffutils: 1.2 MiB`.
+        message: String,
+    },
+    /// A repository clone finished and its files were copied.
+    CloneFinished {
+        /// The repository and the language of the program.
+        repository_name: String,
+    },
+    /// A file was copied, or linked, into a program pair's directory.
+    FileCopied {
+        /// Where the file was placed, under `program_pairs/`.
+        path: PathBuf,
+        /// Size of the file in bytes.
+        bytes: u64,
+    },
+    /// A program pair was downloaded.
+    PairFinished {
+        /// Name of the program pair.
+        program_name: String,
+    },
+    /// A program pair was not downloaded again, because it is up to date.
+    PairUpToDate {
+        /// Name of the program pair.
+        program_name: String,
+    },
+    /// A program pair was not downloaded, because it is in the skip list or
+    /// its repositories are not cached in offline mode.
+    PairSkipped {
+        /// Name of the program pair.
+        program_name: String,
+        /// Why the pair was skipped.
+        reason: String,
+    },
+    /// A program pair failed to download.
+    PairFailed {
+        /// Name of the program pair.
+        program_name: String,
+        /// The error the pair failed with.
+        error: String,
+    },
+    /// A message that would be printed above the bars.
+    Message(String),
+}
+
+/// How a processed program pair ended, as printed by `download --json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum PairOutcome {
+    /// The pair was downloaded.
+    Downloaded {
+        /// Name of the program pair.
+        program_name: String,
+    },
+    /// The pair was up to date, so not downloaded again.
+    UpToDate {
+        /// Name of the program pair.
+        program_name: String,
+    },
+    /// The pair was not downloaded.
+    Skipped {
+        /// Name of the program pair.
+        program_name: String,
+        /// Why the pair was skipped.
+        reason: String,
+    },
+    /// The pair failed to download.
+    Failed {
+        /// Name of the program pair.
+        program_name: String,
+        /// The error the pair failed with.
+        error: String,
+    },
+}
+
+impl PairOutcome {
+    /// Returns the name of the program pair.
+    pub fn program_name(&self) -> &str {
+        match self {
+            PairOutcome::Downloaded { program_name }
+            | PairOutcome::UpToDate { program_name }
+            | PairOutcome::Skipped { program_name, .. }
+            | PairOutcome::Failed { program_name, .. } => program_name,
+        }
+    }
+}
+
+/// The progress bars of a download.
+pub struct DownloadProgress {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    events: Option<Sender<DownloadEvent>>,
+    /// The expected duration of each pair, by program name.
+    estimates: Mutex<HashMap<String, Duration>>,
+    /// How each processed pair ended, in the order they ended.
+    outcomes: Mutex<Vec<PairOutcome>>,
+    /// The metrics of the download, written to `run-metrics.json`.
+    metrics: Arc<MetricsRecorder>,
+    /// Whether the download was asked to stop before its next pair.
+    cancelled: AtomicBool,
+}
+
+impl DownloadProgress {
+    /// Creates the progress display with an overall bar.
+    ///
+    /// # Arguments
+    ///
+    /// - `total_pairs` - The number of program pairs to be processed.
+    ///
+    /// # Returns
+    ///
+    /// The [`DownloadProgress`], or [`DownloaderError`] if the bar style is
+    /// invalid.
+    pub fn new(total_pairs: u64) -> Result<Self, DownloaderError> {
+        let multi = MultiProgress::new();
+        let overall = multi.add(ProgressBar::new(total_pairs));
+        overall.set_style(bar_style(OVERALL_TEMPLATE)?);
+        Ok(DownloadProgress {
+            multi,
+            overall,
+            events: None,
+            estimates: Mutex::default(),
+            outcomes: Mutex::default(),
+            metrics: Arc::new(MetricsRecorder::new()),
+            cancelled: AtomicBool::new(false),
+        })
+    }
+
+    /// Creates a progress display that sends its progress as events instead
+    /// of drawing bars.
+    ///
+    /// # Arguments
+    ///
+    /// - `total_pairs` - The number of program pairs to be processed.
+    /// - `events` - Receives the progress of the download.
+    ///
+    /// # Returns
+    ///
+    /// The [`DownloadProgress`], or [`DownloaderError`] if the bar style is
+    /// invalid.
+    pub fn with_events(
+        total_pairs: u64,
+        events: Sender<DownloadEvent>,
+    ) -> Result<Self, DownloaderError> {
+        let mut progress = Self::new(total_pairs)?;
+        progress.multi.set_draw_target(ProgressDrawTarget::hidden());
+        progress.events = Some(events);
+        Ok(progress)
+    }
+
+    /// Creates a progress display that draws no bars, for clones outside of
+    /// the pairs of a download, such as of a metadata repository.  Messages
+    /// are printed to standard error.
+    ///
+    /// # Returns
+    ///
+    /// The [`DownloadProgress`], or [`DownloaderError`] if the bar style is
+    /// invalid.
+    pub fn hidden() -> Result<Self, DownloaderError> {
+        let progress = Self::new(0)?;
+        progress.multi.set_draw_target(ProgressDrawTarget::hidden());
+        Ok(progress)
+    }
+
+    /// Prints how long the download is expected to take, and keeps how long
+    /// each pair is expected to take, to show while it downloads.
+    ///
+    /// # Arguments
+    ///
+    /// - `estimates` - The [`Estimates`] of the pairs about to be downloaded.
+    pub fn estimate(&self, estimates: Estimates) {
+        if !estimates.total.is_zero() {
+            let unknown = match estimates.unknown {
+                0 => String::new(),
+                unknown => format!(", plus {unknown} repositories that cannot be estimated"),
+            };
+            self.println(format!(
+                "Cloning is expected to take about {}{unknown}",
+                HumanDuration(estimates.total)
+            ));
+        }
+        if let Ok(mut pairs) = self.estimates.lock() {
+            *pairs = estimates.pairs;
+        }
+    }
+
+    /// Shows which program pair is currently being downloaded, and how long
+    /// it is expected to take if it was estimated.
+    pub fn start_pair(&self, program_name: &str) {
+        let estimate = self
+            .estimates
+            .lock()
+            .ok()
+            .and_then(|estimates| estimates.get(program_name).copied());
+        self.overall.set_message(match estimate {
+            Some(estimate) => format!(
+                "Downloading '{program_name}' (about {})...",
+                HumanDuration(estimate)
+            ),
+            None => format!("Downloading '{program_name}'..."),
+        });
+        send(
+            &self.events,
+            DownloadEvent::PairStarted {
+                program_name: program_name.to_string(),
+            },
+        );
+    }
+
+    /// Counts a program pair as downloaded.
+    pub fn finish_pair(&self, program_name: &str) {
+        self.println(format!("Downloaded '{program_name}'"));
+        self.overall.inc(1);
+        self.record(PairOutcome::Downloaded {
+            program_name: program_name.to_string(),
+        });
+        send(
+            &self.events,
+            DownloadEvent::PairFinished {
+                program_name: program_name.to_string(),
+            },
+        );
+    }
+
+    /// Counts a program pair as up to date, so not downloaded again.
+    pub fn fresh_pair(&self, program_name: &str) {
+        self.println(format!("'{program_name}' is up to date"));
+        self.overall.inc(1);
+        self.record(PairOutcome::UpToDate {
+            program_name: program_name.to_string(),
+        });
+        send(
+            &self.events,
+            DownloadEvent::PairUpToDate {
+                program_name: program_name.to_string(),
+            },
+        );
+    }
+
+    /// Counts a program pair as processed without downloading it.
+    ///
+    /// # Arguments
+    ///
+    /// - `program_name` - Name of the program pair.
+    /// - `reason` - Why the pair was skipped.
+    pub fn skip_pair(&self, program_name: &str, reason: &str) {
+        self.println(format!("Skipped '{program_name}' ({reason})"));
+        self.overall.inc(1);
+        self.record(PairOutcome::Skipped {
+            program_name: program_name.to_string(),
+            reason: reason.to_string(),
+        });
+        send(
+            &self.events,
+            DownloadEvent::PairSkipped {
+                program_name: program_name.to_string(),
+                reason: reason.to_string(),
+            },
+        );
+    }
+
+    /// Counts a program pair as failed.
+    ///
+    /// # Arguments
+    ///
+    /// - `program_name` - Name of the program pair.
+    /// - `error` - The error the pair failed with.
+    pub fn fail_pair(&self, program_name: &str, error: &DownloaderError) {
+        self.println(format!("Failed to download '{program_name}': {error}"));
+        self.overall.inc(1);
+        self.record(PairOutcome::Failed {
+            program_name: program_name.to_string(),
+            error: error.to_string(),
+        });
+        send(
+            &self.events,
+            DownloadEvent::PairFailed {
+                program_name: program_name.to_string(),
+                error: error.to_string(),
+            },
+        );
+    }
+
+    /// Adds a nested bar tracking the clone of a repository.
+    ///
+    /// # Arguments
+    ///
+    /// - `repository_name` - The repository being cloned.
+    ///
+    /// # Returns
+    ///
+    /// A [`CloneProgress`] that removes its bar when dropped.
+    pub fn start_clone(&self, repository_name: &str) -> Result<CloneProgress, DownloaderError> {
+        let bar = self.multi.insert_before(&self.overall, ProgressBar::new(0));
+        bar.set_style(bar_style(CLONE_TEMPLATE)?);
+        bar.set_message(format!("Cloning {repository_name}..."));
+        let clone_progress = CloneProgress {
+            bar,
+            repository_name: repository_name.to_string(),
+            events: self.events.clone(),
+            copied: Mutex::new(CopiedFiles::default()),
+            metrics: Arc::clone(&self.metrics),
+        };
+        clone_progress.report();
+        Ok(clone_progress)
+    }
+
+    /// Prints a message above the progress bars, or to standard error if
+    /// the bars are hidden because it is not a terminal.
+    pub fn println(&self, message: impl AsRef<str>) {
+        if let Some(events) = &self.events {
+            let _ = events.send(DownloadEvent::Message(message.as_ref().to_string()));
+        } else if self.multi.is_hidden() {
+            eprintln!("{}", message.as_ref());
+        } else {
+            // Printing only fails when the terminal is gone.
+            let _ = self.multi.println(message);
+        }
+    }
+
+    /// Completes the overall bar with a final message.
+    pub fn finish(&self, message: &'static str) {
+        self.overall.finish_with_message(message);
+    }
+
+    /// Returns how each processed program pair ended, sorted by name.
+    pub fn outcomes(&self) -> Vec<PairOutcome> {
+        let mut outcomes = self
+            .outcomes
+            .lock()
+            .map(|outcomes| outcomes.clone())
+            .unwrap_or_default();
+        outcomes.sort_by(|a, b| a.program_name().cmp(b.program_name()));
+        outcomes
+    }
+
+    /// Returns the metrics of the download, written to `run-metrics.json`.
+    pub fn metrics(&self) -> &MetricsRecorder {
+        &self.metrics
+    }
+
+    /// Asks the download to stop once the pair in progress ends, from any
+    /// thread.  The pairs not yet started are left as they are.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the download was asked to stop with
+    /// [`DownloadProgress::cancel`].
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Keeps how a program pair ended, for [`DownloadProgress::outcomes`].
+    fn record(&self, outcome: PairOutcome) {
+        if let Ok(mut outcomes) = self.outcomes.lock() {
+            outcomes.push(outcome);
+        }
+    }
+}
+
+/// The nested progress bar of one repository clone.
+pub struct CloneProgress {
+    bar: ProgressBar,
+    repository_name: String,
+    events: Option<Sender<DownloadEvent>>,
+    copied: Mutex<CopiedFiles>,
+    metrics: Arc<MetricsRecorder>,
+}
+
+/// The files copied out of a clone so far, for the copy throughput.
+#[derive(Default)]
+struct CopiedFiles {
+    /// When copying started.
+    started: Option<Instant>,
+    /// Number of files copied.
+    files: u64,
+    /// Number of bytes copied.
+    bytes: u64,
+}
+
+impl CloneProgress {
+    /// Returns the metrics of the download the clone is part of.
+    pub fn metrics(&self) -> &MetricsRecorder {
+        &self.metrics
+    }
+
+    /// Updates the bar from libgit2's transfer progress.
+    ///
+    /// The bar tracks objects, which libgit2 knows the total of, while the
+    /// message shows the bytes received and the transfer rate.
+    ///
+    /// # Arguments
+    ///
+    /// - `progress` - Contains information about the current status of the
+    ///   download.
+    ///
+    /// # Returns
+    ///
+    /// True.  A progress callback must return `true` to continue the clone.
+    pub fn update(&self, progress: git2::Progress) -> bool {
+        let received_objects = progress.received_objects();
+        let total_objects = progress.total_objects();
+        let indexed_objects = progress.indexed_objects();
+        self.bar.set_length(total_objects as u64);
+
+        // Downloading objects.
+        if received_objects < total_objects {
+            let received_bytes = progress.received_bytes() as u64;
+            let seconds = self.bar.elapsed().as_secs_f64().max(0.001);
+            self.bar.set_position(received_objects as u64);
+            self.bar.set_message(format!(
+                "Cloning {}: {} at {}/s",
+                self.repository_name,
+                utils::format_size(received_bytes),
+                utils::format_size((received_bytes as f64 / seconds) as u64)
+            ));
+        }
+        // Processing downloaded objects.
+        else if indexed_objects < total_objects {
+            self.bar.set_position(indexed_objects as u64);
+            self.bar
+                .set_message(format!("Indexing {}...", self.repository_name));
+        }
+        // Done with objects.
+        else {
+            self.bar.set_position(total_objects as u64);
+            self.bar.set_message("Resolving deltas...");
+        }
+
+        self.report();
+        true
+    }
+
+    /// Updates the bar from a progress line of the system `git` binary.
+    ///
+    /// The bar tracks the items of git's current phase, such as received
+    /// objects or resolved deltas, while the message names the phase and
+    /// shows the transfer rate when git reports it.
+    ///
+    /// # Arguments
+    ///
+    /// - `progress` - The progress parsed from git's output.
+    pub fn update_git(&self, progress: &GitProgress) {
+        self.bar.set_length(progress.total);
+        self.bar.set_position(progress.done);
+        self.bar.set_message(match progress.transfer {
+            Some(transfer) => format!("Cloning {}: {}", self.repository_name, transfer),
+            None => format!("{} of {}...", progress.phase, self.repository_name),
+        });
+        self.report();
+    }
+
+    /// Prints a message above the progress bars, or to standard error if
+    /// the bars are hidden because it is not a terminal.
+    pub fn println(&self, message: impl AsRef<str>) {
+        if let Some(events) = &self.events {
+            let _ = events.send(DownloadEvent::Message(message.as_ref().to_string()));
+        } else if self.bar.is_hidden() {
+            eprintln!("{}", message.as_ref());
+        } else {
+            self.bar.println(message);
+        }
+    }
+
+    /// Shows that files are being copied out of the clone.
+    pub fn copying(&self) {
+        if let Ok(mut copied) = self.copied.lock() {
+            *copied = CopiedFiles {
+                started: Some(Instant::now()),
+                ..CopiedFiles::default()
+            };
+        }
+        self.bar
+            .set_message(format!("Copying files from {}...", self.repository_name));
+        self.report();
+    }
+
+    /// Counts a file copied out of the clone and shows the copy throughput.
+    /// Files may be copied from several threads at once.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - Where the file was copied to.
+    /// - `bytes` - Size of the copied file.
+    pub fn copied(&self, path: &Path, bytes: u64) {
+        send(
+            &self.events,
+            DownloadEvent::FileCopied {
+                path: path.to_path_buf(),
+                bytes,
+            },
+        );
+        let Ok(mut copied) = self.copied.lock() else {
+            return;
+        };
+        copied.files += 1;
+        copied.bytes += bytes;
+        let seconds = copied
+            .started
+            .map_or(0.0, |started| started.elapsed().as_secs_f64())
+            .max(0.001);
+        self.bar.set_message(format!(
+            "Copying files from {}: {} files, {} at {}/s",
+            self.repository_name,
+            copied.files,
+            utils::format_size(copied.bytes),
+            utils::format_size((copied.bytes as f64 / seconds) as u64)
+        ));
+        self.report();
+    }
+
+    /// Sends the bar's state to the caller drawing the progress, if any.
+    fn report(&self) {
+        send(
+            &self.events,
+            DownloadEvent::CloneProgress {
+                repository_name: self.repository_name.clone(),
+                position: self.bar.position(),
+                length: self.bar.length().unwrap_or(0),
+                message: self.bar.message(),
+            },
+        );
+    }
+}
+
+impl Drop for CloneProgress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+        send(
+            &self.events,
+            DownloadEvent::CloneFinished {
+                repository_name: self.repository_name.clone(),
+            },
+        );
+    }
+}
+
+/// Sends an event to the caller drawing the progress, if any.  Events are
+/// dropped once the caller stops listening.
+fn send(events: &Option<Sender<DownloadEvent>>, event: DownloadEvent) {
+    if let Some(events) = events {
+        let _ = events.send(event);
+    }
+}
+
+/// Builds a progress bar style from a template.
+fn bar_style(template: &str) -> Result<ProgressStyle, DownloaderError> {
+    Ok(ProgressStyle::default_bar()
+        .template(template)
+        .map_err(|error| DownloaderError::ProgressBar(error.to_string()))?
+        .progress_chars("##-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    /// Tests that a pair's download is reported as events, in order.
+    #[test]
+    fn test_download_events() {
+        let (sender, events) = mpsc::channel();
+        let progress = DownloadProgress::with_events(1, sender).unwrap();
+        progress.start_pair("cat");
+        let clone_progress = progress.start_clone("coreutils (C)").unwrap();
+        clone_progress.copied(Path::new("program_pairs/cat/c-program/cat.c"), 42);
+        drop(clone_progress);
+        progress.finish_pair("cat");
+
+        let events: Vec<DownloadEvent> = events.try_iter().collect();
+        assert_eq!(
+            DownloadEvent::PairStarted {
+                program_name: "cat".to_string()
+            },
+            events[0]
+        );
+        assert!(matches!(events[1], DownloadEvent::CloneProgress { .. }));
+        assert_eq!(
+            DownloadEvent::FileCopied {
+                path: PathBuf::from("program_pairs/cat/c-program/cat.c"),
+                bytes: 42
+            },
+            events[2]
+        );
+        assert_eq!(
+            &events[events.len() - 3..],
+            [
+                DownloadEvent::CloneFinished {
+                    repository_name: "coreutils (C)".to_string()
+                },
+                DownloadEvent::Message("Downloaded 'cat'".to_string()),
+                DownloadEvent::PairFinished {
+                    program_name: "cat".to_string()
+                },
+            ]
+        );
+    }
+}
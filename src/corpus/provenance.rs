@@ -0,0 +1,102 @@
+//! # Program Pair Provenance
+//!
+//! Every downloaded program pair has a provenance file at
+//! `program_pairs/<program_name>/provenance.json` recording, for every copied
+//! file, the repository, commit, and path it was copied from, and the
+//! transforms that changed it, so the file can be traced back upstream after
+//! the clone cache is deleted.
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::corpus::{errors::DownloaderError, transform::Transform};
+
+/// Name of the provenance file within a program pair's directory.
+pub const PROVENANCE_FILE: &str = "provenance.json";
+
+/// The provenance of every file of a downloaded program pair.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The origin of each copied file.
+    pub files: Vec<FileProvenance>,
+}
+
+/// The origin of one copied file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileProvenance {
+    /// Path of the file within the program pair's directory, separated by
+    /// `/`, such as `c-program/ls.c`.
+    pub path: String,
+    /// The repository the file was copied from; a mirror's URL if the
+    /// repository URL could not be cloned.
+    pub repository_url: String,
+    /// The branch the repository was downloaded at, if not its default
+    /// branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// SHA of the commit that was checked out when the file was copied, or
+    /// the SHA-256 of the tarball or file it was copied from.
+    pub commit: String,
+    /// Path of the file within the repository, separated by `/`.
+    pub source_path: String,
+    /// The transforms that changed the file after it was copied, in the
+    /// order they ran.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<Transform>,
+    /// SHA-256 of the file as it was placed, after its transforms, so local
+    /// changes to it are found before it is overwritten.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl Provenance {
+    /// Reads the provenance file of a program pair.
+    ///
+    /// # Arguments
+    ///
+    /// - `pair_directory` - The program pair's directory.
+    ///
+    /// # Returns
+    ///
+    /// The [`Provenance`], or `None` if the pair has no readable provenance
+    /// file, such as a pair downloaded before provenance was recorded.
+    pub fn load(pair_directory: &Path) -> Option<Provenance> {
+        let contents = fs::read_to_string(pair_directory.join(PROVENANCE_FILE)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Looks up where a file of the program pair was copied from.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - Path of the file within the program pair's directory,
+    ///   separated by `/`, such as `c-program/ls.c`.
+    ///
+    /// # Returns
+    ///
+    /// The file's path within its repository, or `None` if it has no
+    /// recorded provenance.
+    pub fn source_path(&self, path: &str) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|file| file.path == path)
+            .map(|file| file.source_path.as_str())
+    }
+
+    /// Writes the provenance file into a program pair's directory.
+    ///
+    /// # Arguments
+    ///
+    /// - `pair_directory` - The program pair's directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success and [`DownloaderError`] on failure.
+    pub fn save(&self, pair_directory: &Path) -> Result<(), DownloaderError> {
+        let path = pair_directory.join(PROVENANCE_FILE);
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|error| DownloaderError::Serialize { error })?;
+        fs::write(&path, contents).map_err(|error| DownloaderError::IoWrite { path, error })
+    }
+}
@@ -0,0 +1,330 @@
+//! # Publishing
+//!
+//! This module mirrors the downloaded corpus to a git remote, so that the
+//! assembled program pairs can be distributed, and audited, as a history of
+//! snapshots rather than rebuilt from metadata by every user.  Each `publish`
+//! commits the contents of `program_pairs/`, including every pair's
+//! manifest, as one commit on the remote's `main` branch and pushes it.
+//!
+//! The snapshots are recorded in the bare repository `publish.git/`, whose
+//! working directory is `program_pairs/`.  The remote's branch is fetched
+//! before committing, so a snapshot published from another checkout of the
+//! corpus builds on the remote's history instead of being rejected.  Files
+//! linked into `program_pairs/` with `--link symbolic` point into
+//! `repository_clones/`, which the remote lacks, so their content is
+//! published instead of the links.
+
+use std::{cell::Cell, fs, path::Path};
+
+use git2::{
+    Cred, CredentialType, FetchOptions, IndexAddOption, Oid, PushOptions, RemoteCallbacks,
+    Repository, Signature,
+};
+
+use crate::{
+    corpus::{
+        downloader::{self, DownloadOptions},
+        errors::PublishError,
+        git_options,
+        manifest::MANIFEST_FILE,
+    },
+    paths::{PROGRAM_PAIRS_DIRECTORY, PUBLISH_REPOSITORY_DIRECTORY, STAGING_DIRECTORY},
+};
+
+/// Name of the branch the snapshots are pushed to.
+const PUBLISH_BRANCH: &str = "main";
+
+/// Name of the remote the snapshots are pushed to in `publish.git/`.
+const PUBLISH_REMOTE: &str = "origin";
+
+/// Number of times credentials are offered before authentication fails.
+const CREDENTIAL_ATTEMPTS: usize = 3;
+
+/// The git file mode of a symbolic link.
+const LINK_MODE: u32 = 0o120_000;
+
+/// The git file mode of a regular file.
+const FILE_MODE: u32 = 0o100_644;
+
+/// The git file mode of an executable file.
+const EXECUTABLE_MODE: u32 = 0o100_755;
+
+/// Commits the downloaded program pairs as a snapshot and pushes it.
+///
+/// Nothing is committed if the program pairs did not change since the
+/// latest snapshot, which is still pushed if the remote lacks it.
+///
+/// # Arguments
+///
+/// - `remote_url` - URL of the git repository to push the snapshot to.
+/// - `message` - The snapshot's commit message, or `None` to describe how
+///   many program pairs it holds.
+/// - `options` - The download options whose proxy, CA bundle, timeout, and
+///   git configuration isolation the connection to the remote uses.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`PublishError`] on failure.
+pub fn publish(
+    remote_url: &str,
+    message: Option<&str>,
+    options: &DownloadOptions,
+) -> Result<(), PublishError> {
+    let pairs_directory = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    if !pairs_directory.is_dir() {
+        return Err(PublishError::NoPairs {
+            path: pairs_directory.to_path_buf(),
+        });
+    }
+    git_options::apply(&downloader::git_options(options))
+        .map_err(|error| PublishError::GitConfig { error })?;
+    let commit_error = |error| PublishError::Commit {
+        path: PUBLISH_REPOSITORY_DIRECTORY.into(),
+        error,
+    };
+    let repository = open_repository(remote_url).map_err(commit_error)?;
+
+    fetch(&repository, options).map_err(|error| PublishError::Fetch {
+        remote_url: remote_url.to_string(),
+        error,
+    })?;
+    let parent = latest_snapshot(&repository).map_err(commit_error)?;
+    let pairs = count_pairs(pairs_directory);
+    let message = message.map_or_else(
+        || format!("Snapshot of {pairs} program pairs"),
+        str::to_string,
+    );
+    let snapshot = commit(&repository, parent, &message).map_err(commit_error)?;
+    if Some(snapshot) == parent {
+        eprintln!("The program pairs did not change since the latest snapshot");
+    }
+
+    let rejection = push(&repository, options).map_err(|error| PublishError::Push {
+        remote_url: remote_url.to_string(),
+        error,
+    })?;
+    if let Some(message) = rejection {
+        return Err(PublishError::Rejected {
+            remote_url: remote_url.to_string(),
+            message,
+        });
+    }
+    println!("Published {pairs} program pairs to '{remote_url}' as {snapshot}");
+    Ok(())
+}
+
+/// Opens `publish.git/`, creating it if needed, with `program_pairs/` as its
+/// working directory and the remote pointing at `remote_url`.
+fn open_repository(remote_url: &str) -> Result<Repository, git2::Error> {
+    let repository = match Repository::open_bare(PUBLISH_REPOSITORY_DIRECTORY) {
+        Ok(repository) => repository,
+        Err(_) => Repository::init_bare(PUBLISH_REPOSITORY_DIRECTORY)?,
+    };
+    repository.set_workdir(Path::new(PROGRAM_PAIRS_DIRECTORY), false)?;
+    match repository.find_remote(PUBLISH_REMOTE) {
+        Ok(remote) if remote.url() == Some(remote_url) => {}
+        Ok(_) => repository.remote_set_url(PUBLISH_REMOTE, remote_url)?,
+        Err(_) => {
+            repository.remote(PUBLISH_REMOTE, remote_url)?;
+        }
+    }
+    Ok(repository)
+}
+
+/// Fetches the published branch from the remote, which has none before the
+/// first snapshot.
+fn fetch(repository: &Repository, options: &DownloadOptions) -> Result<(), git2::Error> {
+    let mut fetch_options = FetchOptions::new();
+    fetch_options
+        .remote_callbacks(callbacks(repository))
+        .proxy_options(downloader::proxy_options(options));
+    let refspec =
+        format!("+refs/heads/{PUBLISH_BRANCH}:refs/remotes/{PUBLISH_REMOTE}/{PUBLISH_BRANCH}");
+    repository
+        .find_remote(PUBLISH_REMOTE)?
+        .fetch(&[refspec], Some(&mut fetch_options), None)
+}
+
+/// Finds the latest snapshot, preferring the remote's, since a snapshot
+/// that was never pushed is superseded by the next one.
+///
+/// # Returns
+///
+/// The snapshot's commit, or `None` before the first snapshot.
+fn latest_snapshot(repository: &Repository) -> Result<Option<Oid>, git2::Error> {
+    let remote_branch = format!("refs/remotes/{PUBLISH_REMOTE}/{PUBLISH_BRANCH}");
+    let local_branch = format!("refs/heads/{PUBLISH_BRANCH}");
+    for reference in [remote_branch, local_branch] {
+        match repository.refname_to_id(&reference) {
+            Ok(id) => return Ok(Some(id)),
+            Err(error) if error.code() == git2::ErrorCode::NotFound => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(None)
+}
+
+/// Commits the contents of `program_pairs/` on the published branch.
+///
+/// # Arguments
+///
+/// - `repository` - The publish repository.
+/// - `parent` - The latest snapshot, if any.
+/// - `message` - The commit message.
+///
+/// # Returns
+///
+/// The new snapshot, or `parent` if the program pairs did not change.
+fn commit(repository: &Repository, parent: Option<Oid>, message: &str) -> Result<Oid, git2::Error> {
+    // The index is rebuilt from scratch so that deleted pairs leave the
+    // snapshot too.
+    let mut index = repository.index()?;
+    index.clear()?;
+    // Pairs still being downloaded are left out.
+    let staging = Path::new(STAGING_DIRECTORY)
+        .strip_prefix(PROGRAM_PAIRS_DIRECTORY)
+        .unwrap_or(Path::new(STAGING_DIRECTORY));
+    let mut skip_staging = |path: &Path, _: &[u8]| i32::from(path.starts_with(staging));
+    index.add_all(["*"], IndexAddOption::DEFAULT, Some(&mut skip_staging))?;
+    dereference_links(repository, &mut index)?;
+    index.write()?;
+    let tree = repository.find_tree(index.write_tree()?)?;
+    let parent = parent
+        .map(|parent| repository.find_commit(parent))
+        .transpose()?;
+    let branch = format!("refs/heads/{PUBLISH_BRANCH}");
+    if let Some(parent) = &parent
+        && parent.tree_id() == tree.id()
+    {
+        repository.reference(&branch, parent.id(), true, "publish: unchanged")?;
+        return Ok(parent.id());
+    }
+
+    // Machines publishing from CI may have no git identity configured.
+    let signature = repository
+        .signature()
+        .or_else(|_| Signature::now("c-rust-program-pairs", "publish@localhost"))?;
+    repository.commit(
+        Some(&branch),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+    )
+}
+
+/// Replaces the symbolic links added to the index with the content of the
+/// files they point to.
+///
+/// # Arguments
+///
+/// - `repository` - The publish repository, whose working directory is
+///   `program_pairs/`.
+/// - `index` - Its index, holding the contents of `program_pairs/`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`git2::Error`] if a link's target
+/// cannot be read, such as once its clone was deleted.
+fn dereference_links(repository: &Repository, index: &mut git2::Index) -> Result<(), git2::Error> {
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("the publish repository has no working directory"))?;
+    let links: Vec<_> = index
+        .iter()
+        .filter(|entry| entry.mode == LINK_MODE)
+        .collect();
+    for mut entry in links {
+        let path = workdir.join(String::from_utf8_lossy(&entry.path).as_ref());
+        let read_error = |error| {
+            git2::Error::from_str(&format!(
+                "Failed to read the file '{}' links to: {error}",
+                path.display()
+            ))
+        };
+        let metadata = fs::metadata(&path).map_err(read_error)?;
+        let content = fs::read(&path).map_err(read_error)?;
+        entry.mode = if is_executable(&metadata) {
+            EXECUTABLE_MODE
+        } else {
+            FILE_MODE
+        };
+        entry.file_size = u32::try_from(content.len()).unwrap_or(u32::MAX);
+        index.add_frombuffer(&entry, &content)?;
+    }
+    Ok(())
+}
+
+/// Whether a file may be executed by its owner.
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o100 != 0
+}
+
+/// Whether a file may be executed by its owner, which git only records on
+/// Unix.
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Pushes the published branch to the remote.
+///
+/// # Returns
+///
+/// The remote's reason for refusing the branch, or `None` if it was
+/// accepted.
+fn push(repository: &Repository, options: &DownloadOptions) -> Result<Option<String>, git2::Error> {
+    let rejection = Cell::new(None);
+    let mut callbacks = callbacks(repository);
+    callbacks.push_update_reference(|_, status| {
+        rejection.set(status.map(str::to_string));
+        Ok(())
+    });
+    let mut push_options = PushOptions::new();
+    push_options
+        .remote_callbacks(callbacks)
+        .proxy_options(downloader::proxy_options(options));
+    let refspec = format!("refs/heads/{PUBLISH_BRANCH}:refs/heads/{PUBLISH_BRANCH}");
+    repository
+        .find_remote(PUBLISH_REMOTE)?
+        .push(&[refspec], Some(&mut push_options))?;
+    Ok(rejection.take())
+}
+
+/// Creates the callbacks authenticating with the remote, through the SSH
+/// agent or git's credential helpers, as `git push` would.
+fn callbacks<'a>(repository: &Repository) -> RemoteCallbacks<'a> {
+    let config = repository.config().ok();
+    let mut attempts = 0;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        attempts += 1;
+        if attempts > CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str("authentication failed"));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        match &config {
+            Some(config) if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) => {
+                Cred::credential_helper(config, url, username)
+            }
+            _ => Cred::default(),
+        }
+    });
+    callbacks
+}
+
+/// Counts the downloaded program pairs, which have a manifest.
+fn count_pairs(pairs_directory: &Path) -> usize {
+    fs::read_dir(pairs_directory)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(MANIFEST_FILE).is_file())
+        .count()
+}
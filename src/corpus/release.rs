@@ -0,0 +1,363 @@
+//! # Corpus Releases
+//!
+//! This module versions the corpus.  A snapshot records the metadata of
+//! every program pair, together with the commits the downloaded pairs were
+//! copied from, in `releases/corpus-v<version>.json`.  Two snapshots can be
+//! compared to generate release notes listing the pairs that were added,
+//! removed, or changed between them.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{errors::ReleaseError, manifest, parser, provenance::Provenance, schema::Language},
+    paths::{
+        DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY, RELEASES_DIRECTORY,
+    },
+};
+
+/// Length of the abbreviated commit SHAs in release notes.
+const SHORT_SHA_LENGTH: usize = 7;
+
+/// The state of the corpus at a release.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The version of the release, such as `1.2`.
+    pub version: String,
+    /// When the snapshot was taken, in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// The state of each program pair, by name.
+    pub pairs: BTreeMap<String, PairSnapshot>,
+}
+
+/// The state of one program pair at a release.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairSnapshot {
+    /// The metadata file defining the pair.
+    pub metadata_file: PathBuf,
+    /// The pair's metadata, as it was parsed.
+    pub metadata: serde_json::Value,
+    /// The commit the C program was copied from, if the pair was downloaded.
+    pub c_commit: Option<String>,
+    /// The commit the Rust program was copied from, if the pair was
+    /// downloaded.
+    pub rust_commit: Option<String>,
+}
+
+/// Records the state of the corpus in `releases/corpus-v<version>.json`.
+///
+/// # Arguments
+///
+/// - `version` - The version of the release, as `X.Y` or `X.Y.Z`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`ReleaseError`] on failure, including
+/// when a snapshot of the version already exists.
+pub fn snapshot(version: &str) -> Result<(), ReleaseError> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let parts: Vec<&str> = version.split('.').collect();
+    if !(2..=3).contains(&parts.len())
+        || parts
+            .iter()
+            .any(|part| part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()))
+    {
+        return Err(ReleaseError::InvalidVersion {
+            version: version.to_string(),
+        });
+    }
+    let path = snapshot_path(version);
+    if path.exists() {
+        return Err(ReleaseError::Exists { path });
+    }
+
+    let mut pairs = BTreeMap::new();
+    for directory in [
+        DEMO_METADATA_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+    ] {
+        let metadata_files =
+            parser::parse_directory(Path::new(directory)).map_err(|error| ReleaseError::Parse {
+                path: directory.into(),
+                error,
+            })?;
+        for (metadata_file, metadata) in metadata_files {
+            let metadata = metadata.map_err(|error| ReleaseError::Parse {
+                path: metadata_file.clone(),
+                error,
+            })?;
+            for pair in metadata.pairs {
+                let pair_directory = Path::new(PROGRAM_PAIRS_DIRECTORY).join(&pair.program_name);
+                let provenance = Provenance::load(&pair_directory);
+                let commit = |language: &Language| {
+                    let prefix = format!(
+                        "{}/",
+                        manifest::program_directory_name(&pair_directory, language)
+                    );
+                    provenance.as_ref().and_then(|provenance| {
+                        provenance
+                            .files
+                            .iter()
+                            .find(|file| file.path.starts_with(&prefix))
+                            .map(|file| file.commit.clone())
+                    })
+                };
+                let pair_snapshot = PairSnapshot {
+                    metadata_file: metadata_file.clone(),
+                    metadata: serde_json::to_value(&pair)
+                        .map_err(|error| ReleaseError::Serialize { error })?,
+                    c_commit: commit(&Language::C),
+                    rust_commit: commit(&Language::Rust),
+                };
+                pairs.insert(pair.program_name, pair_snapshot);
+            }
+        }
+    }
+
+    let snapshot = Snapshot {
+        version: version.to_string(),
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs()),
+        pairs,
+    };
+    let contents = serde_json::to_string_pretty(&snapshot)
+        .map_err(|error| ReleaseError::Serialize { error })?;
+    fs::create_dir_all(RELEASES_DIRECTORY).map_err(|error| ReleaseError::IoWrite {
+        path: PathBuf::from(RELEASES_DIRECTORY),
+        error,
+    })?;
+    fs::write(&path, contents).map_err(|error| ReleaseError::IoWrite {
+        path: path.clone(),
+        error,
+    })?;
+
+    let downloaded = snapshot
+        .pairs
+        .values()
+        .filter(|pair| pair.c_commit.is_some() && pair.rust_commit.is_some())
+        .count();
+    println!(
+        "Recorded {} program pairs ({} downloaded) in '{}'",
+        snapshot.pairs.len(),
+        downloaded,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Prints release notes listing the program pairs added, removed, and
+/// changed between two snapshots, as Markdown.
+///
+/// # Arguments
+///
+/// - `from` - The earlier snapshot, as a version or a path.
+/// - `to` - The later snapshot, as a version or a path.
+/// - `output` - The file to write the release notes to, or `None` for
+///   stdout.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`ReleaseError`] on failure.
+pub fn changelog(from: &str, to: &str, output: Option<&Path>) -> Result<(), ReleaseError> {
+    let from = load_snapshot(from)?;
+    let to = load_snapshot(to)?;
+    let notes = release_notes(&from, &to);
+    match output {
+        Some(path) => fs::write(path, notes).map_err(|error| ReleaseError::IoWrite {
+            path: path.to_path_buf(),
+            error,
+        }),
+        None => {
+            print!("{notes}");
+            Ok(())
+        }
+    }
+}
+
+/// Renders the release notes between two snapshots.
+///
+/// # Arguments
+///
+/// - `from` - The earlier snapshot.
+/// - `to` - The later snapshot.
+///
+/// # Returns
+///
+/// The release notes as Markdown.
+pub fn release_notes(from: &Snapshot, to: &Snapshot) -> String {
+    let added: Vec<(&String, &PairSnapshot)> = to
+        .pairs
+        .iter()
+        .filter(|(name, _)| !from.pairs.contains_key(*name))
+        .collect();
+    let removed: Vec<(&String, &PairSnapshot)> = from
+        .pairs
+        .iter()
+        .filter(|(name, _)| !to.pairs.contains_key(*name))
+        .collect();
+    let changed: Vec<(&String, Vec<String>)> = to
+        .pairs
+        .iter()
+        .filter_map(|(name, pair)| {
+            let changes = changes(from.pairs.get(name)?, pair);
+            (!changes.is_empty()).then_some((name, changes))
+        })
+        .collect();
+
+    let mut notes = String::new();
+    let _ = writeln!(notes, "# Corpus v{}", to.version);
+    let _ = writeln!(notes);
+    let _ = writeln!(
+        notes,
+        "{} program pairs; {} added, {} removed, and {} changed since v{}.",
+        to.pairs.len(),
+        added.len(),
+        removed.len(),
+        changed.len(),
+        from.version
+    );
+    for (heading, pairs) in [("Added", &added), ("Removed", &removed)] {
+        if pairs.is_empty() {
+            continue;
+        }
+        let _ = writeln!(notes, "\n## {heading}\n");
+        for (name, pair) in pairs.iter() {
+            match pair.metadata["program_description"].as_str() {
+                Some(description) => {
+                    let _ = writeln!(notes, "- `{name}`: {description}");
+                }
+                None => {
+                    let _ = writeln!(notes, "- `{name}`");
+                }
+            }
+        }
+    }
+    if !changed.is_empty() {
+        let _ = writeln!(notes, "\n## Changed\n");
+        for (name, changes) in &changed {
+            let _ = writeln!(notes, "- `{}`: {}", name, changes.join("; "));
+        }
+    }
+    notes
+}
+
+/// Describes how a program pair changed between two snapshots.
+fn changes(from: &PairSnapshot, to: &PairSnapshot) -> Vec<String> {
+    let mut changes = Vec::new();
+    for (language, from_commit, to_commit) in [
+        ("C", &from.c_commit, &to.c_commit),
+        ("Rust", &from.rust_commit, &to.rust_commit),
+    ] {
+        if from_commit != to_commit {
+            changes.push(format!(
+                "{} revision {} → {}",
+                language,
+                short_sha(from_commit.as_deref()),
+                short_sha(to_commit.as_deref())
+            ));
+        }
+    }
+    if from.metadata != to.metadata {
+        changes.push("metadata changed".to_string());
+    }
+    changes
+}
+
+/// Abbreviates a commit SHA, or shows `-` for a pair that was not
+/// downloaded.
+fn short_sha(commit: Option<&str>) -> &str {
+    commit.map_or("-", |commit| &commit[..commit.len().min(SHORT_SHA_LENGTH)])
+}
+
+/// Returns the path of the snapshot of a version.
+fn snapshot_path(version: &str) -> PathBuf {
+    Path::new(RELEASES_DIRECTORY).join(format!("corpus-v{version}.json"))
+}
+
+/// Reads a snapshot given as a path to it or as its version.
+fn load_snapshot(snapshot: &str) -> Result<Snapshot, ReleaseError> {
+    let path = if Path::new(snapshot).is_file() {
+        PathBuf::from(snapshot)
+    } else {
+        snapshot_path(snapshot.strip_prefix('v').unwrap_or(snapshot))
+    };
+    let contents = fs::read_to_string(&path).map_err(|error| ReleaseError::IoRead {
+        path: path.clone(),
+        error,
+    })?;
+    serde_json::from_str(&contents).map_err(|error| ReleaseError::Deserialize { path, error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a snapshot of pairs given by name, Rust commit, and description.
+    fn snapshot(version: &str, pairs: &[(&str, &str, &str)]) -> Snapshot {
+        Snapshot {
+            version: version.to_string(),
+            created_at: 0,
+            pairs: pairs
+                .iter()
+                .map(|(name, commit, description)| {
+                    let pair = PairSnapshot {
+                        metadata_file: PathBuf::from("metadata/individual/pairs.json"),
+                        metadata: serde_json::json!({ "program_description": description }),
+                        c_commit: None,
+                        rust_commit: Some(commit.to_string()),
+                    };
+                    (name.to_string(), pair)
+                })
+                .collect(),
+        }
+    }
+
+    /// Tests that added, removed, and changed pairs are listed.
+    #[test]
+    fn test_release_notes() {
+        let from = snapshot(
+            "1.0",
+            &[
+                ("cat", "aaaaaaaaaa", "Concatenates files."),
+                ("ls", "bbbbbbbbbb", "Lists files."),
+                ("rm", "cccccccccc", "Removes files."),
+            ],
+        );
+        let to = snapshot(
+            "1.1",
+            &[
+                ("cat", "aaaaaaaaaa", "Concatenates files."),
+                ("ls", "dddddddddd", "Lists directories."),
+                ("mv", "eeeeeeeeee", "Moves files."),
+            ],
+        );
+        assert_eq!(
+            "# Corpus v1.1\n\
+             \n\
+             3 program pairs; 1 added, 1 removed, and 1 changed since v1.0.\n\
+             \n\
+             ## Added\n\
+             \n\
+             - `mv`: Moves files.\n\
+             \n\
+             ## Removed\n\
+             \n\
+             - `rm`: Removes files.\n\
+             \n\
+             ## Changed\n\
+             \n\
+             - `ls`: Rust revision bbbbbbb → ddddddd; metadata changed\n",
+            release_notes(&from, &to)
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! # Remote Metadata
+//!
+//! This module fetches the metadata of a corpus from a git repository given
+//! with `--metadata-repo`, so that a centrally maintained corpus definition
+//! can be downloaded without a checkout of this project.  The repository is
+//! cloned into `repository_clones/` like any other, or fetched if it is
+//! cached, and the files of its `metadata/` directory, or of the directory
+//! given with `--subdirectory`, are copied into `metadata/` before the
+//! metadata is parsed.
+//!
+//! The files copied are recorded in `metadata/remote-files.json` with their
+//! contents as copied, so that a later sync removes the files the repository
+//! removed and refuses to overwrite files changed locally, unless `--force`
+//! is given.  Files are only written when their contents changed, so that
+//! `watch` does not see its own copies as edits, and local files the
+//! repository never had are kept.  Every copied metadata file must parse,
+//! or none is copied.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use git2::{FetchOptions, Repository, ResetType};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    corpus::{
+        cache::{self, CacheLock},
+        downloader::{self, DownloadOptions},
+        errors::DownloaderError,
+        git_options, parser,
+        progress::DownloadProgress,
+        utils,
+    },
+    paths::{
+        self, DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, METADATA_DIRECTORY,
+        METADATA_SYNC_FILE, PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// The SHA-256 of each file copied from the metadata repository, keyed by
+/// its `/`-separated path within `metadata/`.
+type SyncedFiles = BTreeMap<String, String>;
+
+/// Brings `metadata/` up to date with a metadata repository.
+///
+/// # Arguments
+///
+/// - `repository_url` - URL of the metadata repository.
+/// - `options` - Options controlling the download; in offline mode, the
+///   cached clone is used as is, and the repository must be cached.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`DownloaderError`] if the repository
+/// cannot be cloned or fetched, lacks its metadata directory, or its files
+/// cannot be copied or would overwrite files changed locally.
+pub fn sync(repository_url: &str, options: &DownloadOptions) -> Result<(), DownloaderError> {
+    git_options::apply(&downloader::git_options(options))
+        .map_err(|error| DownloaderError::GitConfig { error })?;
+    let clone = cache::clone_directory(&options.root, repository_url);
+    let lock = CacheLock::acquire(&clone, |_| {
+        eprintln!("Waiting for another download of '{repository_url}'");
+    })
+    .map_err(|error| DownloaderError::Cache { error })?;
+    if lock.interrupted() {
+        cache::restore_clone(&options.root, &clone)
+            .map_err(|error| DownloaderError::Cache { error })?;
+    }
+
+    // The clone is made, or checked for health, as any other repository's,
+    // and then brought up to date with the head of its remote.
+    let cached = Repository::open(&clone).is_ok();
+    if !cached && !options.offline {
+        eprintln!("Cloning metadata from '{repository_url}'");
+    }
+    let progress = DownloadProgress::hidden()?;
+    let clone_progress = progress.start_clone(&utils::get_repository_name(repository_url)?)?;
+    let (workdir, _) = downloader::download_with_git(
+        repository_url,
+        1,
+        false,
+        None,
+        None,
+        &clone_progress,
+        options,
+    )?;
+    // No metadata file references the clone, so `delete --unused` would
+    // otherwise remove it.
+    cache::record_metadata_repository(&options.root, &clone)
+        .map_err(|error| DownloaderError::Cache { error })?;
+    if cached && !options.offline {
+        eprintln!("Fetching metadata from '{repository_url}'");
+        Repository::open(&workdir)
+            .and_then(|repository| fetch_head(&repository, repository_url, options))
+            .map_err(|error| DownloaderError::MetadataRepository {
+                repository_url: repository_url.to_string(),
+                error,
+            })?;
+    }
+
+    let subdirectory = options
+        .metadata_subdirectory
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(METADATA_DIRECTORY));
+    let source = workdir.join(&subdirectory);
+    if !source.is_dir() {
+        return Err(DownloaderError::MetadataDirectory {
+            repository_url: repository_url.to_string(),
+            path: subdirectory,
+        });
+    }
+    let source = utils::ensure_within(&source, &workdir)?;
+    let updated = copy_metadata(&source, repository_url, options)?;
+    if updated > 0 {
+        eprintln!("Updated {updated} metadata files from '{repository_url}'");
+    }
+    // A repository may only hold one of the formats, but both are parsed.
+    for directory in [PROJECT_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY] {
+        let directory = options.root.join(paths::metadata_path(directory));
+        fs::create_dir_all(&directory).map_err(|error| DownloaderError::IoCreate {
+            path: directory,
+            error,
+        })?;
+    }
+    Ok(())
+}
+
+/// Fetches the head of a cached clone's remote and checks it out.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a [`git2::Error`] on failure.
+fn fetch_head(
+    repository: &Repository,
+    url: &str,
+    options: &DownloadOptions,
+) -> Result<(), git2::Error> {
+    // Only the latest commit is needed.
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.proxy_options(downloader::proxy_options(options));
+    if utils::supports_shallow_fetch(url) {
+        fetch_options.depth(1);
+    }
+    repository
+        .find_remote("origin")?
+        .fetch(&["HEAD"], Some(&mut fetch_options), None)?;
+    let head = repository.find_reference("FETCH_HEAD")?.peel_to_commit()?;
+    repository.reset(head.as_object(), ResetType::Hard, None)
+}
+
+/// Copies the metadata files of a clone whose contents differ, and removes
+/// those copied by an earlier sync that the clone no longer has.
+///
+/// Nothing is written if a file changed since it was copied, or a local
+/// file the repository never had, would be overwritten or removed, unless
+/// `--force` is given.
+///
+/// # Arguments
+///
+/// - `source` - The directory of the clone holding the metadata.
+/// - `repository_url` - URL of the metadata repository.
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// The number of files written or removed, or a [`DownloaderError`] if a
+/// file cannot be read or written, or
+/// [`DownloaderError::MetadataConflict`] listing the files changed locally.
+fn copy_metadata(
+    source: &Path,
+    repository_url: &str,
+    options: &DownloadOptions,
+) -> Result<usize, DownloaderError> {
+    let destination = options.root.join(paths::metadata_path(METADATA_DIRECTORY));
+    let record_path = options.root.join(paths::metadata_path(METADATA_SYNC_FILE));
+    let record_name = Path::new(METADATA_SYNC_FILE)
+        .strip_prefix(METADATA_DIRECTORY)
+        .unwrap_or(Path::new(METADATA_SYNC_FILE));
+    let synced = load_synced_files(&record_path)?;
+
+    // Every change is decided before any is made, so a conflict or a file
+    // that fails to parse leaves `metadata/` as it was.
+    let mut copied = SyncedFiles::new();
+    let mut writes = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut invalid_files = Vec::new();
+    for path in utils::list_files(source) {
+        let Ok(relative) = path.strip_prefix(source) else {
+            continue;
+        };
+        if relative.starts_with(".git") || relative == record_name {
+            continue;
+        }
+        let Some(key) = utils::slash_path(relative) else {
+            continue;
+        };
+        let contents = fs::read(&path).map_err(|error| DownloaderError::IoRead {
+            path: path.clone(),
+            error,
+        })?;
+        if is_metadata_file(relative)
+            && let Err(error) = parser::parse_str(&String::from_utf8_lossy(&contents))
+        {
+            invalid_files.push((PathBuf::from(&key), error));
+        }
+        copied.insert(key.clone(), hash(&contents));
+        let target = destination.join(relative);
+        match read_existing(&target)? {
+            Some(existing) if existing == contents => continue,
+            Some(existing) if synced.get(&key) != Some(&hash(&existing)) => {
+                conflicts.push(key);
+            }
+            _ => {}
+        }
+        writes.push((target, contents));
+    }
+    let mut removals = Vec::new();
+    for (key, recorded) in &synced {
+        // Only plain relative paths are removed, whatever the record holds.
+        let is_plain = utils::slash_path(Path::new(key)).is_some_and(|path| path == *key);
+        if copied.contains_key(key) || !is_plain {
+            continue;
+        }
+        let target = destination.join(key);
+        match read_existing(&target)? {
+            Some(existing) if hash(&existing) == *recorded => {}
+            Some(_) => conflicts.push(key.clone()),
+            None => continue,
+        }
+        removals.push(target);
+    }
+    // The repository's metadata is downloaded straight away, so none of it
+    // is copied unless all of it parses.
+    if !invalid_files.is_empty() {
+        return Err(DownloaderError::InvalidMetadata { invalid_files });
+    }
+    if !conflicts.is_empty() && !options.force {
+        return Err(DownloaderError::MetadataConflict {
+            repository_url: repository_url.to_string(),
+            paths: conflicts.join(", "),
+        });
+    }
+
+    let updated = writes.len() + removals.len();
+    for (target, contents) in writes {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|error| DownloaderError::IoCreate {
+                path: parent.to_path_buf(),
+                error,
+            })?;
+        }
+        fs::write(&target, contents).map_err(|error| DownloaderError::IoWrite {
+            path: target,
+            error,
+        })?;
+    }
+    for target in removals {
+        fs::remove_file(&target).map_err(|error| DownloaderError::IoWrite {
+            path: target,
+            error,
+        })?;
+    }
+    if copied != synced {
+        let contents = serde_json::to_string_pretty(&copied)
+            .map_err(|error| DownloaderError::Serialize { error })?;
+        fs::write(&record_path, contents).map_err(|error| DownloaderError::IoWrite {
+            path: record_path,
+            error,
+        })?;
+    }
+    Ok(updated)
+}
+
+/// Checks whether a file of the metadata directory holds program pairs, as
+/// the JSON files of its project, individual, and demo directories do.
+///
+/// # Arguments
+///
+/// - `relative` - The file's path relative to the metadata directory.
+///
+/// # Returns
+///
+/// Whether the file is parsed as metadata.
+fn is_metadata_file(relative: &Path) -> bool {
+    let in_pairs_directory = [
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+        DEMO_METADATA_DIRECTORY,
+    ]
+    .iter()
+    .filter_map(|directory| Path::new(directory).strip_prefix(METADATA_DIRECTORY).ok())
+    .any(|directory| relative.parent() == Some(directory));
+    in_pairs_directory
+        && relative
+            .extension()
+            .is_some_and(|extension| extension == "json")
+}
+
+/// Loads the record of the files copied by the previous sync.
+///
+/// # Returns
+///
+/// The [`SyncedFiles`], empty before the first sync, or
+/// [`DownloaderError::IoRead`] if the record cannot be read or parsed.
+fn load_synced_files(path: &Path) -> Result<SyncedFiles, DownloaderError> {
+    let read_error = |error| DownloaderError::IoRead {
+        path: path.to_path_buf(),
+        error,
+    };
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|error| read_error(io::Error::new(io::ErrorKind::InvalidData, error))),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(SyncedFiles::new()),
+        Err(error) => Err(read_error(error)),
+    }
+}
+
+/// Reads a local metadata file, if it exists.
+///
+/// # Returns
+///
+/// The file's contents, `None` if it does not exist, or
+/// [`DownloaderError::IoRead`] if it cannot be read.
+fn read_existing(path: &Path) -> Result<Option<Vec<u8>>, DownloaderError> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(DownloaderError::IoRead {
+            path: path.to_path_buf(),
+            error,
+        }),
+    }
+}
+
+/// Hashes the contents of a file.
+///
+/// # Returns
+///
+/// The SHA-256 of the contents as hex.
+fn hash(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
@@ -0,0 +1,312 @@
+//! # Pair Renaming
+//!
+//! This module renames a program pair everywhere the corpus records its name:
+//! the `program_name` of its entries in the metadata files and the skip list,
+//! its directory in `program_pairs/`, and the manifest within that directory.
+//! Only the `program_name` of the pair's entries changes in the metadata
+//! files holding it; every other field, the file's format, and the order of
+//! its keys are kept, so that a project file keeps its `project_information`
+//! and the other pairs keep their fingerprints.  Provenance files record
+//! paths within the pair's directory, so they move along with it unchanged.
+//!
+//! Every file is renamed and checked in memory before anything changes.  The
+//! directory is moved first, and if a file cannot be written afterwards, the
+//! files already written are restored and the directory is moved back.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    corpus::{errors::RenameError, manifest::Manifest, metadata, parser, utils, writer},
+    paths::{
+        self, DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY, SKIPLIST_FILE,
+    },
+};
+
+/// A file to rewrite, with its contents before and after the rename.
+struct Edit {
+    /// Path of the file.
+    path: PathBuf,
+    /// Contents of the file before the rename.
+    original: String,
+    /// Contents of the file after the rename.
+    renamed: String,
+}
+
+/// Renames a program pair in the metadata, the skip list, and
+/// `program_pairs/`.
+///
+/// # Arguments
+///
+/// - `old` - The pair's current name.
+/// - `new` - The pair's new name.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`RenameError`] on failure.
+pub fn rename(old: &str, new: &str) -> Result<(), RenameError> {
+    if !metadata::is_valid_name(new) {
+        return Err(RenameError::InvalidName {
+            name: new.to_string(),
+        });
+    }
+
+    let mut edits = Vec::new();
+    for directory in [
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+        DEMO_METADATA_DIRECTORY,
+    ] {
+        for path in metadata_files(&paths::metadata_path(directory))? {
+            edits.extend(rename_in_metadata(&path, old, new)?);
+        }
+    }
+    if edits.is_empty() {
+        return Err(RenameError::PairNotFound {
+            program_name: old.to_string(),
+        });
+    }
+    let skiplist = paths::metadata_path(SKIPLIST_FILE);
+    if skiplist.is_file() {
+        edits.extend(rename_in_skiplist(&skiplist, old, new)?);
+    }
+    let old_directory = Path::new(PROGRAM_PAIRS_DIRECTORY).join(old);
+    let new_directory = Path::new(PROGRAM_PAIRS_DIRECTORY).join(new);
+    if new_directory.exists() {
+        return Err(RenameError::AlreadyExists {
+            program_name: new.to_string(),
+        });
+    }
+
+    let moved = old_directory.is_dir();
+    if moved {
+        move_pair_directory(&old_directory, &new_directory, new)?;
+    }
+    for (index, edit) in edits.iter().enumerate() {
+        if let Err(error) = fs::write(&edit.path, &edit.renamed) {
+            roll_back(&edits[..index], moved, &new_directory, &old_directory, old);
+            return Err(RenameError::IoWrite {
+                path: edit.path.clone(),
+                error,
+            });
+        }
+    }
+
+    for edit in &edits {
+        println!("Renamed '{old}' to '{new}' in '{}'", edit.path.display());
+    }
+    if moved {
+        println!(
+            "Moved '{}' to '{}'",
+            old_directory.display(),
+            new_directory.display()
+        );
+    }
+    Ok(())
+}
+
+/// Renames a program pair in a metadata file, in memory.
+///
+/// # Arguments
+///
+/// - `path` - The metadata file.
+/// - `old` - The pair's current name.
+/// - `new` - The pair's new name.
+///
+/// # Returns
+///
+/// The [`Edit`] of the file, `None` if it does not hold the pair, or a
+/// [`RenameError`] if it cannot be read or parsed, already holds a pair
+/// with the new name, or would be invalid once renamed.
+fn rename_in_metadata(path: &Path, old: &str, new: &str) -> Result<Option<Edit>, RenameError> {
+    let original = fs::read_to_string(path).map_err(|error| RenameError::IoRead {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let metadata = parser::parse_str(&original).map_err(|error| RenameError::Parse {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    if metadata.pairs.iter().any(|pair| pair.program_name == new) {
+        return Err(RenameError::AlreadyExists {
+            program_name: new.to_string(),
+        });
+    }
+    if !metadata.pairs.iter().any(|pair| pair.program_name == old) {
+        return Ok(None);
+    }
+
+    // The name is changed in the file's own JSON rather than in the parsed
+    // metadata, which would be written back in the individual format.
+    let mut document: Value =
+        serde_json::from_str(&original).map_err(|error| RenameError::Deserialize {
+            path: path.to_path_buf(),
+            error,
+        })?;
+    if let Some(entries) = document.get_mut("pairs").and_then(Value::as_array_mut) {
+        for entry in entries {
+            if let Some(name) = entry.get_mut("program_name")
+                && name == old
+            {
+                *name = Value::from(new);
+            }
+        }
+    }
+    let renamed = writer::document_contents(&document).map_err(|error| RenameError::Write {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    Ok(Some(Edit {
+        path: path.to_path_buf(),
+        original,
+        renamed,
+    }))
+}
+
+/// Renames a program pair in the skip list, in memory.
+///
+/// # Arguments
+///
+/// - `path` - The skip list.
+/// - `old` - The pair's current name.
+/// - `new` - The pair's new name.
+///
+/// # Returns
+///
+/// The [`Edit`] of the skip list, `None` if it does not list the pair, or a
+/// [`RenameError`] if it cannot be read, is not valid JSON, or already
+/// lists a pair with the new name.
+fn rename_in_skiplist(path: &Path, old: &str, new: &str) -> Result<Option<Edit>, RenameError> {
+    let original = fs::read_to_string(path).map_err(|error| RenameError::IoRead {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let deserialize_error = |error| RenameError::Deserialize {
+        path: path.to_path_buf(),
+        error,
+    };
+    let mut skiplist: Value = serde_json::from_str(&original).map_err(deserialize_error)?;
+    let mut found = false;
+    if let Some(entries) = skiplist.get_mut("pairs").and_then(Value::as_array_mut) {
+        if entries.iter().any(|entry| entry["program_name"] == new) {
+            return Err(RenameError::AlreadyExists {
+                program_name: new.to_string(),
+            });
+        }
+        for entry in entries {
+            if let Some(name) = entry.get_mut("program_name")
+                && name == old
+            {
+                *name = Value::from(new);
+                found = true;
+            }
+        }
+    }
+    if !found {
+        return Ok(None);
+    }
+    let renamed = utils::to_metadata_json(&skiplist).map_err(deserialize_error)? + "\n";
+    Ok(Some(Edit {
+        path: path.to_path_buf(),
+        original,
+        renamed,
+    }))
+}
+
+/// Moves a pair's directory to its new name and renames its manifest,
+/// moving the directory back if the manifest cannot be saved.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`RenameError`] on failure.
+fn move_pair_directory(
+    old_directory: &Path,
+    new_directory: &Path,
+    new: &str,
+) -> Result<(), RenameError> {
+    fs::rename(old_directory, new_directory).map_err(|error| RenameError::IoWrite {
+        path: new_directory.to_path_buf(),
+        error,
+    })?;
+    if let Some(mut manifest) = Manifest::load(new_directory) {
+        manifest.program_name = new.to_string();
+        if let Err(error) = manifest.save(new_directory) {
+            let _ = fs::rename(new_directory, old_directory);
+            return Err(RenameError::Manifest { error });
+        }
+    }
+    Ok(())
+}
+
+/// Restores the files already rewritten and moves the pair's directory back
+/// after a rename fails part way.  Failures are reported, since the
+/// original error is what the rename returns.
+///
+/// # Arguments
+///
+/// - `written` - The edits already written.
+/// - `moved` - Whether the pair's directory was moved.
+/// - `new_directory` - The directory the pair was moved to.
+/// - `old_directory` - The directory the pair was moved from.
+/// - `old` - The pair's original name.
+fn roll_back(written: &[Edit], moved: bool, new_directory: &Path, old_directory: &Path, old: &str) {
+    for edit in written {
+        if let Err(error) = fs::write(&edit.path, &edit.original) {
+            eprintln!("Failed to restore '{}': {error}", edit.path.display());
+        }
+    }
+    if !moved {
+        return;
+    }
+    if let Err(error) = fs::rename(new_directory, old_directory) {
+        eprintln!(
+            "Failed to move '{}' back to '{}': {error}",
+            new_directory.display(),
+            old_directory.display()
+        );
+        return;
+    }
+    if let Some(mut manifest) = Manifest::load(old_directory) {
+        manifest.program_name = old.to_string();
+        if let Err(error) = manifest.save(old_directory) {
+            eprintln!("Failed to restore the manifest of '{old}': {error}");
+        }
+    }
+}
+
+/// Lists the JSON files in a metadata directory, sorted.
+///
+/// # Arguments
+///
+/// - `directory` - The metadata directory, which may not exist.
+///
+/// # Returns
+///
+/// The paths of the files, or [`RenameError::IoRead`] if the directory
+/// cannot be read.
+fn metadata_files(directory: &Path) -> Result<Vec<PathBuf>, RenameError> {
+    if !directory.is_dir() {
+        return Ok(Vec::new());
+    }
+    let read_error = |error| RenameError::IoRead {
+        path: directory.to_path_buf(),
+        error,
+    };
+    let mut paths = Vec::new();
+    for entry in directory.read_dir().map_err(read_error)? {
+        let path = entry.map_err(read_error)?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
@@ -0,0 +1,107 @@
+//! # Error Reporting
+//!
+//! This module prints the error that ends a command on stderr, as text or as
+//! a JSON object, and maps it to the exit code of the process through its
+//! [`ErrorKind`].
+
+use std::{error::Error, process::ExitCode};
+
+use serde::Serialize;
+
+use crate::corpus::errors::{Classify, ErrorKind};
+
+/// How the error that ends a command is printed on stderr.
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// A human-readable message.
+    #[default]
+    Text,
+    /// A JSON object with the error's kind, exit code, message, and causes.
+    Json,
+}
+
+/// The error that ended a command, along with what the command was doing.
+#[derive(Debug)]
+pub struct CommandError {
+    /// What the command was doing, such as "Failed to download program pairs".
+    context: &'static str,
+    /// The category of the error.
+    kind: ErrorKind,
+    /// The underlying error.
+    error: Box<dyn Error>,
+}
+
+/// The JSON object printed for a [`CommandError`] with [`ErrorFormat::Json`].
+#[derive(Serialize)]
+struct ErrorReport<'a> {
+    kind: ErrorKind,
+    exit_code: u8,
+    context: &'a str,
+    message: String,
+    causes: Vec<String>,
+}
+
+/// Attaches context to the error of a command's result.
+pub trait Context {
+    /// Wraps the error, if any, into a [`CommandError`].
+    ///
+    /// # Arguments
+    ///
+    /// - `context` - What the command was doing.
+    ///
+    /// # Returns
+    ///
+    /// The result with its error wrapped.
+    fn context(self, context: &'static str) -> Result<(), CommandError>;
+}
+
+impl<E: Classify + 'static> Context for Result<(), E> {
+    fn context(self, context: &'static str) -> Result<(), CommandError> {
+        self.map_err(|error| CommandError {
+            context,
+            kind: error.kind(),
+            error: Box::new(error),
+        })
+    }
+}
+
+/// Prints the error of a command, if any, and returns the exit code.
+///
+/// # Arguments
+///
+/// - `result` - The result of the command.
+/// - `format` - How the error is printed.
+///
+/// # Returns
+///
+/// [`ExitCode::SUCCESS`] if the command succeeded, or the exit code of the
+/// error's [`ErrorKind`].
+pub fn report(result: Result<(), CommandError>, format: ErrorFormat) -> ExitCode {
+    let Err(error) = result else {
+        return ExitCode::SUCCESS;
+    };
+
+    match format {
+        ErrorFormat::Text => eprintln!("{}: {}", error.context, error.error),
+        ErrorFormat::Json => {
+            let mut causes = Vec::new();
+            let mut source = error.error.source();
+            while let Some(cause) = source {
+                causes.push(cause.to_string());
+                source = cause.source();
+            }
+            let report = ErrorReport {
+                kind: error.kind,
+                exit_code: error.kind.exit_code(),
+                context: error.context,
+                message: error.error.to_string(),
+                causes,
+            };
+            match serde_json::to_string(&report) {
+                Ok(json) => eprintln!("{json}"),
+                Err(_) => eprintln!("{}: {}", error.context, error.error),
+            }
+        }
+    }
+    ExitCode::from(error.kind.exit_code())
+}
@@ -0,0 +1,752 @@
+//! # Rust Module Graph
+//!
+//! This module finds the Rust sources of a binary in a crate by following
+//! its module tree: starting from the binary's root file, such as
+//! `src/main.rs`, every `mod foo;` declaration is resolved to `foo.rs` or
+//! `foo/mod.rs`, or to the file named by its `#[path]` attribute, the same
+//! way rustc does.  Modules declared inside inline `mod foo { ... }` blocks
+//! are resolved within the block's directory.
+//!
+//! A binary calls into its crate's library, and into other crates of the
+//! repository it depends on by `path`, through paths such as `use
+//! uucore::fs;` or `uucore::show!()`.  A path starting with the name of such
+//! a crate follows that crate's library module tree too, resolving the paths
+//! in its files against its own `Cargo.toml`, and dependencies inherited
+//! with `workspace = true` against the workspace's.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, iter,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, de::IgnoredAny};
+
+use crate::corpus::{errors::MetadataError, utils};
+
+/// The parts of a `Cargo.toml` that locate a crate's targets.
+#[derive(Debug, Default, Deserialize)]
+struct CargoManifest {
+    /// The `[package]` section.
+    package: Option<CargoPackage>,
+    /// The `[lib]` section.
+    lib: Option<CargoTarget>,
+    /// The `[[bin]]` sections.
+    #[serde(default)]
+    bin: Vec<CargoTarget>,
+    /// The `[dependencies]` section.
+    #[serde(default)]
+    dependencies: BTreeMap<String, CargoDependency>,
+    /// The `[workspace]` section.
+    workspace: Option<CargoWorkspace>,
+}
+
+/// The `[workspace]` section of a `Cargo.toml`.
+#[derive(Debug, Deserialize)]
+struct CargoWorkspace {
+    /// The `[workspace.dependencies]` section, which members inherit from.
+    #[serde(default)]
+    dependencies: BTreeMap<String, CargoDependency>,
+}
+
+/// A dependency in a `Cargo.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CargoDependency {
+    /// A dependency given by a table.
+    Detailed {
+        /// The crate's directory, relative to the `Cargo.toml`.
+        path: Option<PathBuf>,
+        /// Whether the dependency is inherited from the workspace.
+        #[serde(default)]
+        workspace: bool,
+    },
+    /// A dependency given by its version alone, such as `libc = "0.2"`.
+    Version(IgnoredAny),
+}
+
+/// The crates the paths in a crate's files can start with, by the name they
+/// are referred to with, and the directory of each.
+type CrateNames = BTreeMap<String, PathBuf>;
+
+/// The `[package]` section of a `Cargo.toml`.
+#[derive(Debug, Deserialize)]
+struct CargoPackage {
+    /// Name of the package, which is also the name of its default binary.
+    name: String,
+}
+
+/// A `[lib]` or `[[bin]]` section of a `Cargo.toml`.
+#[derive(Debug, Deserialize)]
+struct CargoTarget {
+    /// Name of the target.
+    name: Option<String>,
+    /// Root file of the target, relative to the crate's directory.
+    path: Option<PathBuf>,
+}
+
+/// A `mod foo;` declaration whose module is in another file.
+#[derive(Debug, PartialEq, Eq)]
+struct ModDeclaration {
+    /// Names of the inline `mod` blocks enclosing the declaration.
+    inline_path: Vec<String>,
+    /// Name of the declared module.
+    name: String,
+    /// The file given by a `#[path]` attribute on the declaration.
+    path: Option<String>,
+}
+
+/// A token of Rust source code, as far as module declarations need.
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    /// An identifier or keyword.
+    Ident(String),
+    /// The contents of a string literal.
+    Str(String),
+    /// Any other character outside comments and literals.
+    Punct(char),
+}
+
+/// Finds the Rust sources of a binary in a local checkout of its repository.
+///
+/// # Arguments
+///
+/// - `repository` - The root of the checkout.
+/// - `crate_directory` - The crate's directory, holding its `Cargo.toml`,
+///   relative to `repository`.
+/// - `bin` - Name of the binary; when `None`, the crate's default binary
+///   `src/main.rs`, its only `[[bin]]` target, or its library is used.
+///
+/// # Returns
+///
+/// Every `.rs` file reachable from the binary, and from the libraries of the
+/// crates its paths start with, as `/`-separated paths relative to the
+/// checkout's root, or [`MetadataError`] if the binary is not found.
+pub fn find_sources(
+    repository: &Path,
+    crate_directory: &Path,
+    bin: Option<&str>,
+) -> Result<Vec<String>, MetadataError> {
+    let read_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |error| MetadataError::IoRead { path, error }
+    };
+    let root = repository.canonicalize().map_err(read_error(repository))?;
+    let crate_directory = root.join(crate_directory);
+    let crate_directory = crate_directory.canonicalize().unwrap_or(crate_directory);
+    let manifest = read_manifest(&crate_directory)?;
+    let binary = binary_root(&crate_directory, &manifest, bin)?;
+
+    // The crates each crate followed can refer to, by the index given to it
+    // here, and the directories of the crates whose libraries are followed.
+    let mut crates = vec![crate_names(&root, &crate_directory, &manifest)?];
+    let mut libraries = BTreeSet::new();
+    let mut files = BTreeSet::new();
+    // Crate roots and `mod.rs` files keep their submodules in their own
+    // directory; other files keep them in a directory named after the file.
+    let mut pending: Vec<(PathBuf, bool, usize)> = vec![(binary, true, 0)];
+    while let Some((file, is_mod_root, crate_index)) = pending.pop() {
+        let Ok(file) = file.canonicalize() else {
+            continue;
+        };
+        if !file.starts_with(&root) || !files.insert(file.clone()) {
+            continue;
+        }
+        let contents = fs::read_to_string(&file).map_err(read_error(&file))?;
+        let tokens = tokenize(&contents);
+
+        for name in path_roots(&tokens) {
+            let Some(directory) = crates[crate_index].get(&name) else {
+                continue;
+            };
+            let directory = directory.canonicalize().unwrap_or(directory.clone());
+            if !libraries.insert(directory.clone()) {
+                continue;
+            }
+            let manifest = read_manifest(&directory)?;
+            let index = if directory == crate_directory {
+                0
+            } else {
+                crates.push(crate_names(&root, &directory, &manifest)?);
+                crates.len() - 1
+            };
+            pending.push((library_root(&directory, &manifest), true, index));
+        }
+
+        let parent = file.parent().unwrap_or(&root).to_path_buf();
+        let module_directory = if is_mod_root {
+            parent.clone()
+        } else {
+            let stem = file.file_stem().unwrap_or_default();
+            parent.join(stem)
+        };
+
+        for declaration in mod_declarations(&tokens) {
+            let inline_directory = declaration
+                .inline_path
+                .iter()
+                .fold(module_directory.clone(), |directory, name| {
+                    directory.join(name)
+                });
+            let resolved = match &declaration.path {
+                // Files named by `#[path]` are treated like `mod.rs` files.
+                Some(path) if declaration.inline_path.is_empty() => {
+                    Some((parent.join(utils::platform_path(path)), true))
+                }
+                Some(path) => Some((inline_directory.join(utils::platform_path(path)), true)),
+                None => {
+                    let flat = inline_directory.join(format!("{}.rs", declaration.name));
+                    let nested = inline_directory.join(&declaration.name).join("mod.rs");
+                    if flat.is_file() {
+                        Some((flat, false))
+                    } else if nested.is_file() {
+                        Some((nested, true))
+                    } else {
+                        None
+                    }
+                }
+            };
+            match resolved {
+                Some((module, is_mod_root)) => pending.push((module, is_mod_root, crate_index)),
+                None => eprintln!(
+                    "Module '{}' declared in '{}' was not found",
+                    declaration.name,
+                    file.display()
+                ),
+            }
+        }
+    }
+
+    Ok(files
+        .iter()
+        .filter_map(|file| utils::slash_path(file.strip_prefix(&root).ok()?))
+        .collect())
+}
+
+/// The root file of a crate's library, which may not exist.
+fn library_root(crate_directory: &Path, manifest: &CargoManifest) -> PathBuf {
+    let library = manifest.lib.as_ref().and_then(|lib| lib.path.as_deref());
+    crate_directory.join(library.unwrap_or(Path::new("src/lib.rs")))
+}
+
+/// Finds the crates of a repository the paths in a crate's files can start
+/// with: its own library, and its dependencies given by `path`, directly or
+/// through its workspace.
+///
+/// # Arguments
+///
+/// - `root` - The root of the checkout, which holds the workspace.
+/// - `crate_directory` - The crate's directory.
+/// - `manifest` - The crate's `Cargo.toml`.
+///
+/// # Returns
+///
+/// The [`CrateNames`] of the crate, or [`MetadataError`] if a `Cargo.toml`
+/// of its workspace cannot be read.
+fn crate_names(
+    root: &Path,
+    crate_directory: &Path,
+    manifest: &CargoManifest,
+) -> Result<CrateNames, MetadataError> {
+    let mut names = CrateNames::new();
+    let library_name = manifest
+        .lib
+        .as_ref()
+        .and_then(|lib| lib.name.as_deref())
+        .or(manifest
+            .package
+            .as_ref()
+            .map(|package| package.name.as_str()));
+    if let Some(name) = library_name {
+        names.insert(name.replace('-', "_"), crate_directory.to_path_buf());
+    }
+
+    let mut workspace = None;
+    for (name, dependency) in &manifest.dependencies {
+        let directory = match dependency {
+            CargoDependency::Detailed {
+                path: Some(path), ..
+            } => crate_directory.join(utils::platform_path(&path.to_string_lossy())),
+            CargoDependency::Detailed {
+                workspace: true, ..
+            } => {
+                if workspace.is_none() {
+                    workspace = Some(workspace_dependencies(root, crate_directory)?);
+                }
+                match workspace.as_ref().and_then(|workspace| workspace.get(name)) {
+                    Some(directory) => directory.clone(),
+                    None => continue,
+                }
+            }
+            _ => continue,
+        };
+        names.insert(name.replace('-', "_"), directory);
+    }
+    Ok(names)
+}
+
+/// Finds the dependencies given by `path` in the workspace a crate belongs
+/// to, the nearest directory up to the checkout's root whose `Cargo.toml`
+/// has a `[workspace]` section.
+///
+/// # Returns
+///
+/// The directory of each dependency by its name in `Cargo.toml`, none if
+/// the crate is not in a workspace, or [`MetadataError`] if a `Cargo.toml`
+/// cannot be read.
+fn workspace_dependencies(
+    root: &Path,
+    crate_directory: &Path,
+) -> Result<BTreeMap<String, PathBuf>, MetadataError> {
+    for directory in crate_directory
+        .ancestors()
+        .take_while(|directory| directory.starts_with(root))
+    {
+        let Some(workspace) = read_manifest(directory)?.workspace else {
+            continue;
+        };
+        return Ok(workspace
+            .dependencies
+            .into_iter()
+            .filter_map(|(name, dependency)| match dependency {
+                CargoDependency::Detailed {
+                    path: Some(path), ..
+                } => Some((
+                    name,
+                    directory.join(utils::platform_path(&path.to_string_lossy())),
+                )),
+                _ => None,
+            })
+            .collect());
+    }
+    Ok(BTreeMap::new())
+}
+
+/// Reads the `Cargo.toml` of a crate, or an empty manifest if it has none.
+fn read_manifest(crate_directory: &Path) -> Result<CargoManifest, MetadataError> {
+    let path = crate_directory.join("Cargo.toml");
+    if !path.is_file() {
+        return Ok(CargoManifest::default());
+    }
+    let contents = fs::read_to_string(&path).map_err(|error| MetadataError::IoRead {
+        path: path.clone(),
+        error,
+    })?;
+    toml::from_str(&contents).map_err(|error| MetadataError::CargoManifest { path, error })
+}
+
+/// Finds the root file of a binary the way Cargo does.
+///
+/// # Arguments
+///
+/// - `crate_directory` - The crate's directory.
+/// - `manifest` - The crate's `Cargo.toml`.
+/// - `bin` - Name of the binary, or `None` for the crate's default binary,
+///   falling back to its library.
+///
+/// # Returns
+///
+/// The binary's root file, or [`MetadataError::CrateTargetNotFound`].
+fn binary_root(
+    crate_directory: &Path,
+    manifest: &CargoManifest,
+    bin: Option<&str>,
+) -> Result<PathBuf, MetadataError> {
+    let package_name = manifest
+        .package
+        .as_ref()
+        .map(|package| package.name.as_str());
+    let main = crate_directory.join("src/main.rs");
+    let candidates = match bin {
+        Some(name) => {
+            let mut candidates: Vec<PathBuf> = manifest
+                .bin
+                .iter()
+                .filter(|target| target.name.as_deref() == Some(name))
+                .filter_map(|target| target.path.as_ref())
+                .map(|path| crate_directory.join(path))
+                .collect();
+            candidates.push(crate_directory.join("src/bin").join(format!("{name}.rs")));
+            candidates.push(crate_directory.join("src/bin").join(name).join("main.rs"));
+            if package_name == Some(name) {
+                candidates.push(main);
+            }
+            candidates
+        }
+        None => {
+            let mut candidates = vec![main];
+            if let [target] = manifest.bin.as_slice()
+                && let Some(path) = &target.path
+            {
+                candidates.push(crate_directory.join(path));
+            }
+            let library = manifest.lib.as_ref().and_then(|lib| lib.path.as_ref());
+            candidates
+                .push(crate_directory.join(library.map_or(Path::new("src/lib.rs"), |path| path)));
+            candidates
+        }
+    };
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| MetadataError::CrateTargetNotFound {
+            target: bin.unwrap_or("src/main.rs or src/lib.rs").to_string(),
+            directory: crate_directory.to_path_buf(),
+        })
+}
+
+/// Finds the names that paths start with, such as `uucore` in `use
+/// uucore::fs;` or `uucore::show!()`, and the crates named by `extern crate`.
+///
+/// # Arguments
+///
+/// - `tokens` - The [`Token`]s of a file.
+///
+/// # Returns
+///
+/// The first segment of every path of more than one segment.
+fn path_roots(tokens: &[Token]) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for (index, window) in tokens.windows(3).enumerate() {
+        let is_path_start =
+            index == 0 || !matches!(tokens[index - 1], Token::Punct(':') | Token::Punct('.'));
+        match window {
+            [Token::Ident(name), Token::Punct(':'), Token::Punct(':')] if is_path_start => {
+                names.insert(name.clone());
+            }
+            [
+                Token::Ident(keyword),
+                Token::Ident(krate),
+                Token::Ident(name),
+            ] if keyword == "extern" && krate == "crate" => {
+                names.insert(name.clone());
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Finds the declarations of modules in other files.
+///
+/// # Arguments
+///
+/// - `tokens` - The [`Token`]s of a file, without its comments and the
+///   contents of its string literals.
+///
+/// # Returns
+///
+/// Every `mod foo;` declaration, with the inline `mod` blocks enclosing it
+/// and its `#[path]` attribute.
+fn mod_declarations(tokens: &[Token]) -> Vec<ModDeclaration> {
+    let mut declarations = Vec::new();
+    // The inline modules enclosing the current token, with the brace depth
+    // inside each.
+    let mut inline_modules: Vec<(String, usize)> = Vec::new();
+    let mut depth = 0;
+    let mut path = None;
+    let mut index = 0;
+    while index < tokens.len() {
+        match &tokens[index..] {
+            [
+                Token::Punct('#'),
+                Token::Punct('['),
+                Token::Ident(attribute),
+                Token::Punct('='),
+                Token::Str(value),
+                Token::Punct(']'),
+                ..,
+            ] if attribute == "path" => {
+                path = Some(value.clone());
+                index += 6;
+                continue;
+            }
+            [
+                Token::Ident(keyword),
+                Token::Ident(name),
+                Token::Punct(';'),
+                ..,
+            ] if keyword == "mod" => {
+                declarations.push(ModDeclaration {
+                    inline_path: inline_modules
+                        .iter()
+                        .map(|(name, _)| name.clone())
+                        .collect(),
+                    name: name.clone(),
+                    path: path.take(),
+                });
+                index += 3;
+                continue;
+            }
+            [
+                Token::Ident(keyword),
+                Token::Ident(name),
+                Token::Punct('{'),
+                ..,
+            ] if keyword == "mod" => {
+                depth += 1;
+                inline_modules.push((name.clone(), depth));
+                path = None;
+                index += 3;
+                continue;
+            }
+            [Token::Punct('{'), ..] => {
+                depth += 1;
+                path = None;
+            }
+            [Token::Punct('}'), ..] => {
+                if inline_modules
+                    .last()
+                    .is_some_and(|(_, inner)| *inner == depth)
+                {
+                    inline_modules.pop();
+                }
+                depth = depth.saturating_sub(1);
+                path = None;
+            }
+            [Token::Punct(';'), ..] => path = None,
+            _ => {}
+        }
+        index += 1;
+    }
+    declarations
+}
+
+/// Splits Rust source code into identifiers, string literals, and
+/// punctuation, dropping comments, character literals, and whitespace.
+///
+/// # Arguments
+///
+/// - `source` - Rust source code.
+///
+/// # Returns
+///
+/// The [`Token`]s of the source.
+fn tokenize(source: &str) -> Vec<Token> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let next = chars.get(index + 1).copied();
+        if c == '/' && next == Some('/') {
+            while index < chars.len() && chars[index] != '\n' {
+                index += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            // Block comments nest.
+            let mut nesting = 0;
+            while index < chars.len() {
+                if chars[index] == '/' && chars.get(index + 1) == Some(&'*') {
+                    nesting += 1;
+                    index += 2;
+                } else if chars[index] == '*' && chars.get(index + 1) == Some(&'/') {
+                    nesting -= 1;
+                    index += 2;
+                    if nesting == 0 {
+                        break;
+                    }
+                } else {
+                    index += 1;
+                }
+            }
+        } else if c == '"' {
+            let (value, end) = string_literal(&chars, index + 1);
+            tokens.push(Token::Str(value));
+            index = end;
+        } else if c == 'r' && matches!(next, Some('"' | '#')) {
+            // Raw strings end at a quote followed by as many `#` as opened them.
+            let hashes = chars[index + 1..].iter().take_while(|&&c| c == '#').count();
+            let start = index + 1 + hashes;
+            if chars.get(start) != Some(&'"') {
+                tokens.push(Token::Ident("r".to_string()));
+                index += 1;
+                continue;
+            }
+            let terminator = raw_string_terminator(hashes);
+            let mut end = start + 1;
+            while end < chars.len() && !chars[end..].starts_with(&terminator) {
+                end += 1;
+            }
+            tokens.push(Token::Str(
+                chars[start + 1..end.min(chars.len())].iter().collect(),
+            ));
+            index = end + terminator.len();
+        } else if c == '\'' {
+            // A character literal such as 'a' or '\n', or a lifetime such as
+            // 'a, which is skipped along with its name.  The escaped
+            // character is skipped first, so that '\'' ends at its last quote.
+            if next == Some('\\') {
+                index += 3;
+                while index < chars.len() && chars[index] != '\'' {
+                    index += 1;
+                }
+                index += 1;
+            } else if chars.get(index + 2) == Some(&'\'') {
+                index += 3;
+            } else {
+                index += 1;
+                while index < chars.len() && is_identifier_char(chars[index]) {
+                    index += 1;
+                }
+            }
+        } else if is_identifier_char(c) {
+            let start = index;
+            while index < chars.len() && is_identifier_char(chars[index]) {
+                index += 1;
+            }
+            tokens.push(Token::Ident(chars[start..index].iter().collect()));
+        } else {
+            if !c.is_whitespace() {
+                tokens.push(Token::Punct(c));
+            }
+            index += 1;
+        }
+    }
+    tokens
+}
+
+/// Reads a string literal whose opening quote precedes `start`.
+///
+/// # Returns
+///
+/// The literal's contents, with escapes kept as written, and the index just
+/// past its closing quote.
+fn string_literal(chars: &[char], start: usize) -> (String, usize) {
+    let mut index = start;
+    while index < chars.len() && chars[index] != '"' {
+        index += if chars[index] == '\\' { 2 } else { 1 };
+    }
+    let end = index.min(chars.len());
+    (chars[start..end].iter().collect(), end + 1)
+}
+
+/// The closing quote of a raw string opened with `hashes` `#` characters.
+fn raw_string_terminator(hashes: usize) -> Vec<char> {
+    iter::once('"').chain(iter::repeat_n('#', hashes)).collect()
+}
+
+/// Checks whether a character can be part of an identifier.
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that module declarations are found with their `#[path]`
+    /// attributes and enclosing inline modules, ignoring comments, strings,
+    /// and inline modules' own items.
+    #[test]
+    fn test_mod_declarations() {
+        let source = r##"
+            // mod commented;
+            /* mod /* nested */ commented; */
+            const TEXT: &str = "mod quoted;";
+            const RAW: &str = r#"mod raw;"#;
+            fn lifetime<'a>(c: char) -> bool { c == '{' }
+
+            pub mod flat;
+            #[cfg(unix)]
+            #[path = "platform/unix.rs"]
+            pub(crate) mod platform;
+            mod outer {
+                mod inner;
+                fn f() { if true {} }
+            }
+            mod after;
+        "##;
+        let declaration = |inline_path: &[&str], name: &str, path: Option<&str>| ModDeclaration {
+            inline_path: inline_path.iter().map(|name| name.to_string()).collect(),
+            name: name.to_string(),
+            path: path.map(str::to_string),
+        };
+        assert_eq!(
+            vec![
+                declaration(&[], "flat", None),
+                declaration(&[], "platform", Some("platform/unix.rs")),
+                declaration(&["outer"], "inner", None),
+                declaration(&[], "after", None),
+            ],
+            mod_declarations(&tokenize(source))
+        );
+    }
+
+    /// Tests that escaped quotes end character literals at their last quote.
+    #[test]
+    fn test_tokenize_escaped_quote() {
+        assert_eq!(
+            vec![
+                Token::Ident("let".to_string()),
+                Token::Ident("c".to_string()),
+                Token::Punct('='),
+                Token::Punct(';'),
+                Token::Ident("mod".to_string()),
+                Token::Ident("after".to_string()),
+                Token::Punct(';'),
+            ],
+            tokenize("let c = '\\''; mod after;")
+        );
+        assert_eq!(
+            vec![Token::Punct('('), Token::Punct(')')],
+            tokenize("('\\\\')")
+        );
+    }
+
+    /// Tests that the sources of a binary are its module tree and the
+    /// library trees of the crates of its repository that its paths start
+    /// with, directly or through its workspace.
+    #[test]
+    fn test_find_sources() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        let files = [
+            (
+                "Cargo.toml",
+                "[workspace]\n\
+                 [workspace.dependencies]\n\
+                 uucore = { path = \"src/uucore\" }\n\
+                 unused = { path = \"src/unused\" }\n",
+            ),
+            (
+                "src/uu/cmp/Cargo.toml",
+                "[package]\nname = \"uu_cmp\"\n\
+                 [[bin]]\nname = \"cmp\"\npath = \"src/main.rs\"\n\
+                 [dependencies]\n\
+                 uucore = { workspace = true }\n\
+                 unused = { workspace = true }\n\
+                 libc = \"0.2\"\n",
+            ),
+            (
+                "src/uu/cmp/src/main.rs",
+                "mod parse;\nfn main() { uu_cmp::run(); libc::exit(0); }\n",
+            ),
+            ("src/uu/cmp/src/parse.rs", "mod flags;\n"),
+            ("src/uu/cmp/src/parse/flags.rs", ""),
+            ("src/uu/cmp/src/lib.rs", "pub mod cmp;\n"),
+            ("src/uu/cmp/src/cmp.rs", "use uucore::{display, fs};\n"),
+            ("src/uucore/src/lib.rs", "pub mod fs;\n"),
+            ("src/uucore/src/fs/mod.rs", ""),
+            ("src/unused/src/lib.rs", ""),
+        ];
+        for (path, contents) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        assert_eq!(
+            vec![
+                "src/uu/cmp/src/cmp.rs",
+                "src/uu/cmp/src/lib.rs",
+                "src/uu/cmp/src/main.rs",
+                "src/uu/cmp/src/parse/flags.rs",
+                "src/uu/cmp/src/parse.rs",
+                "src/uucore/src/fs/mod.rs",
+                "src/uucore/src/lib.rs",
+            ],
+            find_sources(root, Path::new("src/uu/cmp"), Some("cmp")).unwrap()
+        );
+    }
+}
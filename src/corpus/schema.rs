@@ -5,51 +5,678 @@
 //! about program pairs after JSON parsing is complete. By contrast, structs
 //! defined in file `metadata-structs.rs` are used during JSON parsing.
 
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
 use serde::{Deserialize, Serialize};
 
+use crate::corpus::{errors::SchemaError, utils};
+
 /// The metadata from a single .json metadata file, containing
 /// an array of program pairs.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Metadata {
+    /// The program pairs, in the order of the file.
     pub pairs: Vec<ProgramPair>,
 }
 
 /// One C-Rust program pair.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Fields are added as the metadata schema grows, so pairs are parsed or
+/// deserialized rather than built field by field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ProgramPair {
+    /// Name of the pair, which names its directory under `program_pairs/`.
     pub program_name: String,
+    /// What the programs do.
     pub program_description: String,
-    pub translation_tools: Vec<String>,
+    /// The tools the C program was translated into Rust with.
+    pub translation_tools: Vec<TranslationTool>,
+    /// How the features of the Rust program relate to the C program's.
     pub feature_relationship: Features,
+    /// The C program.
     pub c_program: Program,
+    /// The Rust program.
     pub rust_program: Program,
+    /// Competing Rust rewrites of the C program besides `rust_program`,
+    /// such as ripgrep next to a port of grep.
+    #[serde(default)]
+    pub rust_variants: Vec<RustVariant>,
+    /// The C functions and the Rust functions that translate them.
+    pub function_mappings: Vec<FunctionMapping>,
+    /// The pair's tags, and its project's, sorted and without duplicates.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl ProgramPair {
+    /// Checks whether the pair has at least one of the given tags.
+    ///
+    /// # Arguments
+    ///
+    /// - `tags` - The tags to look for; an empty list matches every pair.
+    ///
+    /// # Returns
+    ///
+    /// Whether the pair is selected by `tags`.
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        tags.is_empty() || tags.iter().any(|tag| self.tags.contains(tag))
+    }
+
+    /// Checks whether both programs of the pair are written in the given
+    /// languages.
+    ///
+    /// # Arguments
+    ///
+    /// - `languages` - The languages to look for; an empty list matches
+    ///   every pair.
+    ///
+    /// # Returns
+    ///
+    /// Whether the pair is selected by `languages`.
+    pub fn is_in(&self, languages: &[Language]) -> bool {
+        languages.is_empty()
+            || self
+                .programs()
+                .all(|program| languages.contains(&program.language))
+    }
+
+    /// Lists every program of the pair: the C program, the Rust program,
+    /// and the Rust variants in order.
+    pub fn programs(&self) -> impl Iterator<Item = &Program> {
+        [&self.c_program, &self.rust_program]
+            .into_iter()
+            .chain(self.rust_variants.iter().map(|variant| &variant.program))
+    }
+}
+
+/// A Rust rewrite of a pair's C program besides its `rust_program`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RustVariant {
+    /// Lowercase, hyphenated name of the rewrite, such as `ripgrep`, which
+    /// names its directory, `rust-program-<label>`.
+    pub label: String,
+    /// The rewrite.
+    pub program: Program,
+}
+
+/// Names the default directory of a Rust variant.
+///
+/// # Arguments
+///
+/// - `directory` - The directory the variant's language would default to,
+///   such as `rust-program`.
+/// - `label` - The variant's label.
+///
+/// # Returns
+///
+/// The directory name suffixed with the label, as in `rust-program-ripgrep`.
+pub fn variant_directory(directory: &str, label: &str) -> String {
+    format!("{directory}-{label}")
+}
+
+/// A C function and the Rust function that translates it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionMapping {
+    /// The C function.
+    pub c: FunctionLocation,
+    /// The Rust function.
+    pub rust: FunctionLocation,
+}
+
+/// A function of a program, located in its repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionLocation {
+    /// Path of the file defining the function, relative to the repository
+    /// root.
+    pub file: RelativeSourcePath,
+    /// Name of the function.
+    pub function: String,
+    /// First line of the function's definition, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<u64>,
+    /// Last line of the function's definition, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<u64>,
 }
 
 /// One C or Rust program.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Like [`ProgramPair`], fields are added as the metadata schema grows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Program {
+    /// The language the program is written in.
     pub language: Language,
-    pub documentation_url: String,
-    pub repository_url: String,
-    pub source_paths: Vec<String>,
+    /// URL of the program's documentation.
+    pub documentation_url: DocUrl,
+    /// URL of the repository, tarball, or file the program is downloaded
+    /// from.
+    pub repository_url: RepoUrl,
+    /// URLs tried in order when `repository_url` cannot be downloaded.
+    pub mirror_urls: Vec<RepoUrl>,
+    /// Files and directories of the program's sources, relative to the
+    /// repository root.
+    pub source_paths: Vec<RelativeSourcePath>,
+    /// Files and directories within `source_paths` that are not copied,
+    /// whether listed in `source_paths` or found in one of its directories.
+    pub exclude_paths: Vec<RelativeSourcePath>,
+    /// Files needed to build the program, copied next to its sources.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub build_files: Vec<RelativeSourcePath>,
+    /// Patch files in this repository, applied in order after the program's
+    /// files are copied.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<RelativeSourcePath>,
+    /// The extensions copied from `source_paths` directories.
+    pub extensions: ExtensionFilter,
+    /// Number of commits of history to clone, if the metadata sets it.
+    pub clone_depth: Option<u64>,
+    /// Branch of the repository to download, if not its default branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Whether the repository's submodules are cloned too.
+    pub needs_submodules: bool,
+    /// What `repository_url` and `mirror_urls` point to.
+    #[serde(default)]
+    pub source_type: SourceType,
+    /// SHA-256 of a tarball or file source in lowercase hexadecimal; always
+    /// set unless `source_type` is [`SourceType::Git`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// The GitHub release asset downloaded instead of cloning the
+    /// repository; `repository_url` and `mirror_urls` then name the
+    /// repositories whose releases hold it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<ReleaseAsset>,
+    /// Name of the program's directory within its pair's directory, if the
+    /// metadata names one other than the language's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_directory: Option<String>,
+    /// Whether files copied from `source_paths` directories are flattened
+    /// into the program's directory, if the metadata says.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flatten: Option<bool>,
+}
+
+impl Program {
+    /// Returns the URL the program's source is downloaded from.
+    ///
+    /// # Arguments
+    ///
+    /// - `url` - The program's repository URL or one of its mirror URLs.
+    ///
+    /// # Returns
+    ///
+    /// The URL of the program's release asset in the repository at `url`,
+    /// with the tag and asset name percent-encoded, or `url` itself if the
+    /// program has no release asset.
+    pub fn source_url(&self, url: &RepoUrl) -> String {
+        match &self.release {
+            Some(release) => format!(
+                "{}/releases/download/{}/{}",
+                url.as_str().trim_end_matches('/').trim_end_matches(".git"),
+                encode_path_segment(&release.tag),
+                encode_path_segment(&release.asset)
+            ),
+            None => url.to_string(),
+        }
+    }
+}
+
+/// Percent-encodes a segment of a URL's path, leaving only the characters
+/// that are never reserved, so that a `#`, `?`, `%`, `/`, or space in it
+/// stays part of the segment.
+///
+/// # Arguments
+///
+/// - `segment` - The path segment.
+///
+/// # Returns
+///
+/// The encoded segment.
+fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::new();
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// An asset of a GitHub release, such as the source tarball published with
+/// a version, downloaded instead of a repository's current state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseAsset {
+    /// Tag of the release, such as `v1.2.0`.
+    pub tag: String,
+    /// File name of the asset, such as `hello-1.2.0.tar.gz`.
+    pub asset: String,
+}
+
+/// What a program's repository URL and mirror URLs point to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceType {
+    /// A git repository, which is cloned.
+    #[default]
+    Git,
+    /// A tar archive, optionally gzipped, which is extracted.
+    Tarball,
+    /// A single file, fetched over HTTP, FTP, or from a `file` URL.
+    HttpFile,
+}
+
+impl SourceType {
+    /// Converts the enum type to a string.
+    ///
+    /// # Returns
+    ///
+    /// The source type as written in metadata files, such as "tarball".
+    pub fn to_str(self) -> &'static str {
+        match self {
+            SourceType::Git => "git",
+            SourceType::Tarball => "tarball",
+            SourceType::HttpFile => "http_file",
+        }
+    }
+}
+
+/// The file extensions copied from `source_paths` directories.  Extensions
+/// are stored without a leading `.` and compared case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionFilter {
+    /// Extensions that are copied.
+    pub include: Vec<String>,
+    /// Extensions that are never copied, even if included.
+    pub exclude: Vec<String>,
+}
+
+impl ExtensionFilter {
+    /// Creates a filter, stripping any leading `.` from the extensions.
+    ///
+    /// # Arguments
+    ///
+    /// - `include` - Extensions that are copied.
+    /// - `exclude` - Extensions that are never copied.
+    ///
+    /// # Returns
+    ///
+    /// The [`ExtensionFilter`].
+    pub fn new<I, E>(include: I, exclude: E) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+        E: IntoIterator,
+        E::Item: AsRef<str>,
+    {
+        let normalize = |extension: &str| extension.trim_start_matches('.').to_string();
+        ExtensionFilter {
+            include: include.into_iter().map(|e| normalize(e.as_ref())).collect(),
+            exclude: exclude.into_iter().map(|e| normalize(e.as_ref())).collect(),
+        }
+    }
+
+    /// Extends the filter with extensions given on the command line.
+    ///
+    /// # Arguments
+    ///
+    /// - `other` - Extensions to include and exclude in addition to this
+    ///   filter's.
+    ///
+    /// # Returns
+    ///
+    /// The combined [`ExtensionFilter`]; exclusions win over inclusions.
+    pub fn extend(&self, other: &ExtensionFilter) -> Self {
+        ExtensionFilter {
+            include: self.include.iter().chain(&other.include).cloned().collect(),
+            exclude: self.exclude.iter().chain(&other.exclude).cloned().collect(),
+        }
+    }
+
+    /// Checks whether a file is copied.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - Path to a file.
+    ///
+    /// # Returns
+    ///
+    /// True if the file's extension is included and not excluded.
+    pub fn matches(&self, path: &Path) -> bool {
+        let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+            return false;
+        };
+        let contains = |extensions: &[String]| {
+            extensions
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(extension))
+        };
+        contains(&self.include) && !contains(&self.exclude)
+    }
+}
+
+/// URL schemes a repository can be cloned, or a tarball or file fetched,
+/// from.
+const REPOSITORY_URL_SCHEMES: [&str; 6] = ["https", "http", "git", "ssh", "file", "ftp"];
+
+/// URL schemes documentation can be served from.
+const DOCUMENTATION_URL_SCHEMES: [&str; 2] = ["https", "http"];
+
+/// The URL of a Git repository, such as `https://github.com/user/repo` or
+/// `git@github.com:user/repo.git`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RepoUrl(String);
+
+impl RepoUrl {
+    /// Validates a repository URL.
+    ///
+    /// # Arguments
+    ///
+    /// - `url` - A URL with an `https`, `http`, `git`, `ssh`, `file`, or
+    ///   `ftp` scheme, or an scp-like `user@host:path` address.
+    ///
+    /// # Returns
+    ///
+    /// The [`RepoUrl`] on success and [`SchemaError::InvalidUrl`] if the URL
+    /// is malformed.
+    pub fn new(url: String) -> Result<Self, SchemaError> {
+        // scp-like addresses have no scheme, but a user before the host.
+        let is_scp_like = !url.contains("://")
+            && url
+                .split_once(':')
+                .is_some_and(|(user_host, path)| user_host.contains('@') && !path.is_empty());
+        if !is_scp_like {
+            validate_url(&url, &REPOSITORY_URL_SCHEMES)?;
+        }
+        Ok(RepoUrl(url))
+    }
+
+    /// Returns the URL as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The URL of a program's documentation, such as `https://docs.rs/crate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DocUrl(String);
+
+impl DocUrl {
+    /// Validates a documentation URL.
+    ///
+    /// # Arguments
+    ///
+    /// - `url` - A URL with an `https` or `http` scheme.
+    ///
+    /// # Returns
+    ///
+    /// The [`DocUrl`] on success and [`SchemaError::InvalidUrl`] if the URL
+    /// is malformed.
+    pub fn new(url: String) -> Result<Self, SchemaError> {
+        validate_url(&url, &DOCUMENTATION_URL_SCHEMES)?;
+        Ok(DocUrl(url))
+    }
+
+    /// Returns the URL as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A path to a file or directory relative to a repository's root, which
+/// cannot leave the repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RelativeSourcePath(String);
+
+impl RelativeSourcePath {
+    /// Validates a source path.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - A relative path, separated by `/`, without `..` components.
+    ///
+    /// # Returns
+    ///
+    /// The [`RelativeSourcePath`] on success and
+    /// [`SchemaError::InvalidSourcePath`] if the path is empty, absolute, or
+    /// contains `..`.
+    pub fn new(path: String) -> Result<Self, SchemaError> {
+        let invalid = |reason| SchemaError::InvalidSourcePath {
+            path: path.clone(),
+            reason,
+        };
+        if path.trim().is_empty() {
+            return Err(invalid("the path is empty"));
+        }
+        // Windows paths such as `C:\src` or `\\server\share` are absolute too.
+        let has_drive = path.as_bytes().get(1) == Some(&b':');
+        if path.starts_with(['/', '\\']) || has_drive {
+            return Err(invalid("the path must be relative to the repository root"));
+        }
+        if path.split(['/', '\\']).any(|component| component == "..") {
+            return Err(invalid("the path must not contain '..'"));
+        }
+        Ok(RelativeSourcePath(path))
+    }
+
+    /// Returns the path as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Converts the `/`-separated path into a path using the platform's
+    /// separator, so it can be joined onto a canonical directory on Windows.
+    ///
+    /// # Returns
+    ///
+    /// The path relative to the repository root.
+    pub fn to_path(&self) -> PathBuf {
+        utils::platform_path(&self.0)
+    }
+}
+
+/// Implements the conversions and formatting shared by the validating
+/// newtypes.
+macro_rules! impl_string_newtype {
+    ($($name:ident),*) => {$(
+        impl TryFrom<String> for $name {
+            type Error = SchemaError;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                $name::new(value)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> String {
+                value.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+    )*};
+}
+
+impl_string_newtype!(RepoUrl, DocUrl, RelativeSourcePath);
+
+/// Checks that a URL has one of the given schemes, followed by a host (or,
+/// for `file` URLs, a path), and no whitespace.
+///
+/// # Arguments
+///
+/// - `url` - The URL to check.
+/// - `schemes` - The accepted schemes.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the URL is well formed and
+/// [`SchemaError::InvalidUrl`] otherwise.
+fn validate_url(url: &str, schemes: &[&str]) -> Result<(), SchemaError> {
+    let invalid = |reason| SchemaError::InvalidUrl {
+        url: url.to_string(),
+        reason,
+    };
+    if url.chars().any(char::is_whitespace) {
+        return Err(invalid("the URL contains whitespace"));
+    }
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or_else(|| invalid("the URL has no scheme"))?;
+    if !schemes.contains(&scheme.to_ascii_lowercase().as_str()) {
+        return Err(invalid("the URL has an unsupported scheme"));
+    }
+    if rest.trim_start_matches('/').is_empty() || (scheme != "file" && rest.starts_with('/')) {
+        return Err(invalid("the URL has no host"));
+    }
+    Ok(())
 }
 
 /// Specifies the feature set of the Rust project in relation to its C counterpart.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Features {
+    /// The Rust program implements some of the C program's features.
     RustSubsetOfC,
+    /// The programs implement the same features.
     RustEquivalentToC,
+    /// The Rust program implements all of the C program's features and more.
     RustSupersetOfC,
+    /// Each program implements features the other lacks.
     Overlapping,
 }
 
+impl Features {
+    /// Converts the enum type to a string.
+    ///
+    /// # Returns
+    ///
+    /// The relationship as written in metadata files, such as
+    /// "rust_subset_of_c".
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Features::RustSubsetOfC => "rust_subset_of_c",
+            Features::RustEquivalentToC => "rust_equivalent_to_c",
+            Features::RustSupersetOfC => "rust_superset_of_c",
+            Features::Overlapping => "overlapping",
+        }
+    }
+}
+
+/// A tool used to translate a C program into Rust.
+///
+/// Metadata may spell known tools in any case and with remarks in
+/// parentheses, such as `"C2Rust (manual fixups)"`; they are normalized when
+/// parsed and written in their canonical spelling, such as `"c2rust"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum TranslationTool {
+    /// The C2Rust transpiler.
+    C2Rust,
+    /// The Corrode transpiler.
+    Corrode,
+    /// Translated by hand.
+    Manual,
+    /// Translated with the help of a large language model.
+    LlmAssisted,
+    /// Any other tool, spelled as in the metadata.
+    Other(String),
+}
+
+impl TranslationTool {
+    /// The canonical spellings of the known tools.
+    pub const KNOWN: [&'static str; 4] = ["c2rust", "corrode", "manual", "llm_assisted"];
+
+    /// Recognizes a tool from its spelling in a metadata file.
+    ///
+    /// # Arguments
+    ///
+    /// - `spelling` - The tool as written in `translation_tools`.
+    ///
+    /// # Returns
+    ///
+    /// The known tool, ignoring case, punctuation, and remarks in
+    /// parentheses, or [`TranslationTool::Other`] with the trimmed spelling.
+    pub fn parse(spelling: &str) -> Self {
+        let without_remarks = spelling.split('(').next().unwrap_or_default();
+        let key: String = without_remarks
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect();
+        match key.as_str() {
+            "c2rust" => TranslationTool::C2Rust,
+            "corrode" => TranslationTool::Corrode,
+            "manual" | "manually" | "hand" | "byhand" | "handwritten" => TranslationTool::Manual,
+            "llm" | "llmassisted" | "ai" | "aiassisted" | "chatgpt" | "copilot" => {
+                TranslationTool::LlmAssisted
+            }
+            _ => TranslationTool::Other(spelling.trim().to_string()),
+        }
+    }
+
+    /// Converts the tool to its canonical spelling.
+    ///
+    /// # Returns
+    ///
+    /// The tool as written in metadata files, such as "c2rust", or the
+    /// spelling of an [`TranslationTool::Other`] tool.
+    pub fn to_str(&self) -> &str {
+        match self {
+            TranslationTool::C2Rust => "c2rust",
+            TranslationTool::Corrode => "corrode",
+            TranslationTool::Manual => "manual",
+            TranslationTool::LlmAssisted => "llm_assisted",
+            TranslationTool::Other(spelling) => spelling,
+        }
+    }
+}
+
+impl From<String> for TranslationTool {
+    fn from(spelling: String) -> Self {
+        TranslationTool::parse(&spelling)
+    }
+}
+
+impl From<TranslationTool> for String {
+    fn from(tool: TranslationTool) -> Self {
+        tool.to_str().to_string()
+    }
+}
+
+impl fmt::Display for TranslationTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
 /// The language in which the program is written.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum Language {
+    /// C.
     C,
+    /// C++.
+    Cpp,
+    /// Rust.
     Rust,
+    /// Go.
+    Go,
+    /// Zig.
+    Zig,
 }
 
 impl Language {
@@ -57,11 +684,254 @@ impl Language {
     ///
     /// # Returns
     ///
-    /// The string "c" or "rust".
+    /// The string "c", "cpp", "rust", "go", or "zig".
     pub fn to_str(&self) -> &'static str {
         match self {
             Language::C => "c",
+            Language::Cpp => "cpp",
             Language::Rust => "rust",
+            Language::Go => "go",
+            Language::Zig => "zig",
+        }
+    }
+
+    /// Returns whether programs in this language are translated from, and
+    /// so take the `c_program` side of a pair, rather than translated to.
+    ///
+    /// # Returns
+    ///
+    /// `true` for C and C++, and `false` for Rust, Go, and Zig.
+    pub fn is_source(&self) -> bool {
+        matches!(self, Language::C | Language::Cpp)
+    }
+
+    /// Returns the language whose side of a pair this language takes, which
+    /// names the side's default directory and its manifest entry.
+    ///
+    /// # Returns
+    ///
+    /// [`Language::C`] for source languages and [`Language::Rust`] for the
+    /// others.
+    pub fn side(&self) -> Language {
+        if self.is_source() {
+            Language::C
+        } else {
+            Language::Rust
+        }
+    }
+
+    /// Returns the name of the directory holding this language's half of a
+    /// downloaded program pair.
+    ///
+    /// # Returns
+    ///
+    /// The language's name followed by "-program", such as "c-program".
+    pub fn program_directory(&self) -> &'static str {
+        match self {
+            Language::C => "c-program",
+            Language::Cpp => "cpp-program",
+            Language::Rust => "rust-program",
+            Language::Go => "go-program",
+            Language::Zig => "zig-program",
+        }
+    }
+
+    /// Returns the extensions copied from this language's `source_paths`
+    /// directories unless the metadata says otherwise.
+    ///
+    /// # Returns
+    ///
+    /// The extensions of the language's sources, headers, and, for Rust and
+    /// Go, package manifests, such as `["c", "h"]` for C and
+    /// `["rs", "toml"]` for Rust.
+    pub fn default_extensions(&self) -> &'static [&'static str] {
+        match self {
+            Language::C => &["c", "h"],
+            Language::Cpp => &["cpp", "cc", "cxx", "hpp", "hh", "h"],
+            Language::Rust => &["rs", "toml"],
+            Language::Go => &["go", "mod"],
+            Language::Zig => &["zig"],
+        }
+    }
+
+    /// Determines the language of a source file from its extension.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - Path to a source file.
+    ///
+    /// # Returns
+    ///
+    /// The [`Language`], with `.h` files taken to be C, or `None` if the
+    /// extension belongs to no known language.  Use
+    /// [`Language::from_program_path`] to tell C++ headers apart.
+    pub fn from_path(path: &Path) -> Option<Language> {
+        match path.extension()?.to_str()? {
+            "c" | "h" => Some(Language::C),
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Some(Language::Cpp),
+            "rs" => Some(Language::Rust),
+            "go" => Some(Language::Go),
+            "zig" => Some(Language::Zig),
+            _ => None,
+        }
+    }
+
+    /// Determines the language of a file of a program from its extension,
+    /// taking `.h` headers to be C++ in programs with C++ sources, since C
+    /// and C++ share the extension.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - Path to a source file of the program.
+    /// - `files` - Every file of the program.
+    ///
+    /// # Returns
+    ///
+    /// The [`Language`], or `None` if the extension belongs to no known
+    /// language.
+    pub fn from_program_path(path: &Path, files: &[PathBuf]) -> Option<Language> {
+        let language = Language::from_path(path)?;
+        let is_header = path.extension().is_some_and(|extension| extension == "h");
+        if is_header
+            && files
+                .iter()
+                .any(|file| Language::from_path(file) == Some(Language::Cpp))
+        {
+            Some(Language::Cpp)
+        } else {
+            Some(language)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that files are matched by extension, with exclusions winning.
+    #[test]
+    fn test_extension_filter() {
+        let filter = ExtensionFilter::new(Language::C.default_extensions(), [".h"]);
+        assert_eq!(vec!["c", "h"], filter.include);
+        assert_eq!(vec!["h"], filter.exclude);
+        assert!(filter.matches(Path::new("src/ls.c")));
+        assert!(filter.matches(Path::new("src/LS.C")));
+        assert!(!filter.matches(Path::new("src/ls.h")));
+        assert!(!filter.matches(Path::new("Makefile")));
+
+        let filter = filter.extend(&ExtensionFilter::new(["inc"], ["c"]));
+        assert!(filter.matches(Path::new("src/tables.inc")));
+        assert!(!filter.matches(Path::new("src/ls.c")));
+    }
+
+    /// Tests that headers are C++ only in programs with C++ sources.
+    #[test]
+    fn test_from_program_path() {
+        let header = Path::new("src/ls.h");
+        let c_files = [PathBuf::from("src/ls.c"), header.to_path_buf()];
+        let cpp_files = [PathBuf::from("src/ls.cpp"), header.to_path_buf()];
+        assert_eq!(
+            Some(Language::C),
+            Language::from_program_path(header, &c_files)
+        );
+        assert_eq!(
+            Some(Language::Cpp),
+            Language::from_program_path(header, &cpp_files)
+        );
+        assert_eq!(
+            Some(Language::C),
+            Language::from_program_path(Path::new("src/ls.c"), &cpp_files)
+        );
+        assert_eq!(
+            None,
+            Language::from_program_path(Path::new("README"), &cpp_files)
+        );
+    }
+
+    /// Tests that spellings of known translation tools are normalized and
+    /// other tools kept as written.
+    #[test]
+    fn test_translation_tool() {
+        for spelling in ["c2rust", "C2Rust", " c2rust (manual fixups)", "C2-Rust"] {
+            assert_eq!(TranslationTool::C2Rust, TranslationTool::parse(spelling));
+        }
+        assert_eq!(
+            TranslationTool::Manual,
+            TranslationTool::parse("Hand-written")
+        );
+        assert_eq!(
+            TranslationTool::LlmAssisted,
+            TranslationTool::parse("LLM assisted")
+        );
+        assert_eq!(
+            TranslationTool::Other("rust2c".to_string()),
+            TranslationTool::parse(" rust2c ")
+        );
+        assert_eq!(
+            "[\"llm_assisted\",\"rust2c\"]",
+            serde_json::to_string(&[
+                TranslationTool::LlmAssisted,
+                TranslationTool::parse("rust2c")
+            ])
+            .unwrap()
+        );
+    }
+
+    /// Tests that well-formed repository URLs are accepted and malformed ones
+    /// rejected.
+    #[test]
+    fn test_repo_url() {
+        for url in [
+            "https://github.com/uutils/coreutils",
+            "git://git.savannah.gnu.org/diffutils.git",
+            "git@github.com:uutils/coreutils.git",
+            "file:///tmp/repository",
+            "ftp://ftp.gnu.org/gnu/hello/hello-2.12.tar.gz",
+        ] {
+            assert!(RepoUrl::new(url.to_string()).is_ok(), "{url}");
+        }
+        for url in [
+            "",
+            "github.com/uutils/coreutils",
+            "gopher://example.com/repository",
+            "https://",
+            "https:///path",
+            "https://github.com/uutils/core utils",
+        ] {
+            assert!(RepoUrl::new(url.to_string()).is_err(), "{url}");
+        }
+    }
+
+    /// Tests that release tags and asset names are percent-encoded as URL
+    /// path segments.
+    #[test]
+    fn test_encode_path_segment() {
+        assert_eq!("v1.2.0", encode_path_segment("v1.2.0"));
+        assert_eq!(
+            "hello-1.0_~a.tar.gz",
+            encode_path_segment("hello-1.0_~a.tar.gz")
+        );
+        assert_eq!(
+            "release%2F1%20%231%3F%25",
+            encode_path_segment("release/1 #1?%")
+        );
+    }
+
+    /// Tests that source paths must stay within the repository.
+    #[test]
+    fn test_relative_source_path() {
+        for path in ["src/ls.c", "src/", "lib/./util.h", "file..name.c"] {
+            assert!(RelativeSourcePath::new(path.to_string()).is_ok(), "{path}");
+        }
+        for path in [
+            "",
+            "/etc/passwd",
+            "../other/file.c",
+            "src/../../x",
+            "C:\\src",
+            "\\x",
+        ] {
+            assert!(RelativeSourcePath::new(path.to_string()).is_err(), "{path}");
         }
     }
 }
@@ -0,0 +1,116 @@
+//! # Corpus Search
+//!
+//! This module searches the program pairs in the metadata by name and
+//! description, and optionally the sources of downloaded program pairs, for a
+//! case-insensitive substring.
+
+use std::{fs, path::Path};
+
+use crate::{
+    corpus::{errors::SearchError, manifest, parser, schema::Language, utils},
+    paths::{INDIVIDUAL_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY, PROJECT_METADATA_DIRECTORY},
+};
+
+/// Maximum number of characters of a matching source line that are printed.
+const MAX_LINE_LENGTH: usize = 100;
+
+/// Prints the program pairs whose name or description contains a query and,
+/// if `code` is set, every line of the downloaded sources containing it.
+///
+/// # Arguments
+///
+/// - `query` - The text to search for, ignoring case.
+/// - `code` - Also search the sources in `program_pairs/`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`SearchError`] on failure.
+pub fn search(query: &str, code: bool) -> Result<(), SearchError> {
+    let query = query.to_lowercase();
+    let matches = |text: &str| text.to_lowercase().contains(&query);
+
+    let mut pairs = Vec::new();
+    for directory in [PROJECT_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY] {
+        let metadata_files =
+            parser::parse_directory(Path::new(directory)).map_err(|error| SearchError::Parse {
+                path: directory.into(),
+                error,
+            })?;
+        for (path, metadata) in metadata_files {
+            match metadata {
+                Ok(metadata) => pairs.extend(metadata.pairs),
+                Err(error) => eprintln!("Failed to parse '{}': {}", path.display(), error),
+            }
+        }
+    }
+    pairs.retain(|pair| matches(&pair.program_name) || matches(&pair.program_description));
+    pairs.sort_by(|a, b| a.program_name.cmp(&b.program_name));
+
+    for pair in &pairs {
+        println!("{:<24} {}", pair.program_name, pair.program_description);
+    }
+    println!("{} matching pairs", pairs.len());
+
+    if code {
+        search_sources(&matches)?;
+    }
+    Ok(())
+}
+
+/// Prints every line of the downloaded sources for which `matches` holds,
+/// grouped by program pair and language.
+///
+/// Files that are not valid UTF-8 are skipped.
+///
+/// # Arguments
+///
+/// - `matches` - Whether a line matches the query.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`SearchError`] if `program_pairs/` cannot
+/// be read.
+fn search_sources(matches: &dyn Fn(&str) -> bool) -> Result<(), SearchError> {
+    let program_pairs = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    if !program_pairs.exists() {
+        println!("No program pairs downloaded; run `download` to search their sources");
+        return Ok(());
+    }
+    let pair_directories =
+        utils::list_program_pairs(program_pairs).map_err(|error| SearchError::IoRead {
+            path: program_pairs.to_path_buf(),
+            error,
+        })?;
+
+    let mut total_matches = 0;
+    for pair_directory in &pair_directories {
+        let pair_name = pair_directory
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        for language in [Language::C, Language::Rust] {
+            let mut heading_printed = false;
+            for file in utils::list_files(&manifest::program_directory(pair_directory, &language)) {
+                let Ok(contents) = fs::read_to_string(&file) else {
+                    continue;
+                };
+                for (number, line) in contents.lines().enumerate() {
+                    if !matches(line) {
+                        continue;
+                    }
+                    if !heading_printed {
+                        println!();
+                        println!("{pair_name} ({})", language.to_str());
+                        heading_printed = true;
+                    }
+                    let line: String = line.trim().chars().take(MAX_LINE_LENGTH).collect();
+                    println!("  {}:{}: {line}", file.display(), number + 1);
+                    total_matches += 1;
+                }
+            }
+        }
+    }
+    println!();
+    println!("{total_matches} matching source lines");
+    Ok(())
+}
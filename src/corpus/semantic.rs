@@ -0,0 +1,368 @@
+//! # Semantic Pair Checks
+//!
+//! This module heuristically checks that the C and Rust programs of a pair
+//! implement the same tool, to catch pairs whose Rust repository is, for
+//! example, a library wrapping the C program rather than a rewrite of it.
+//! The checks read the cached clones in `repository_clones/` and the
+//! downloaded sources in `program_pairs/`, so pairs that have not been
+//! downloaded are skipped.
+//!
+//! A pair is flagged if its Rust repository:
+//!
+//! - looks like bindings to C code, such as a `-sys` crate, a crate with a
+//!   `links` key, or one that builds C code with `cc` or `bindgen`;
+//! - never mentions the program's name in its name, its `Cargo.toml`, or its
+//!   README;
+//! - accepts few of the long options that the C program's help text and
+//!   `getopt_long` table list.  Options are read from the sources rather
+//!   than from `--help` output, since `build` does not keep runnable
+//!   binaries.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    corpus::{
+        cache, manifest,
+        schema::{Language, ProgramPair},
+        utils,
+    },
+    paths::PROGRAM_PAIRS_DIRECTORY,
+};
+
+/// Build dependencies that compile C code or generate bindings to it.
+const BINDING_BUILD_DEPENDENCIES: [&str; 3] = ["bindgen", "cc", "cmake"];
+
+/// What the second field of a C `getopt_long` table entry starts with.
+const ARGUMENT_KINDS: [&str; 6] = [
+    "no_argument",
+    "required_argument",
+    "optional_argument",
+    "0",
+    "1",
+    "2",
+];
+
+/// Minimum number of long options the C program must have for the options
+/// to be compared.
+const MIN_OPTIONS: usize = 3;
+
+/// Share of the C program's long options the Rust program must also have.
+const MIN_SHARED_OPTIONS: f64 = 0.25;
+
+/// The parts of a `Cargo.toml` the checks read.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CargoManifest {
+    /// The `[package]` section.
+    package: Option<CargoPackage>,
+    /// The `[[bin]]` sections.
+    #[serde(default)]
+    bin: Vec<CargoTarget>,
+    /// The `[dependencies]` section, by crate name.
+    #[serde(default)]
+    dependencies: BTreeMap<String, toml::Value>,
+    /// The `[build-dependencies]` section, by crate name.
+    #[serde(default)]
+    build_dependencies: BTreeMap<String, toml::Value>,
+}
+
+/// The `[package]` section of a `Cargo.toml`.
+#[derive(Debug, Deserialize)]
+struct CargoPackage {
+    /// Name of the package.
+    name: String,
+    /// The native library the package links to, if any.
+    links: Option<String>,
+}
+
+/// A `[[bin]]` section of a `Cargo.toml`.
+#[derive(Debug, Deserialize)]
+struct CargoTarget {
+    /// Name of the binary.
+    name: Option<String>,
+}
+
+/// Checks that the C and Rust programs of a pair implement the same tool.
+///
+/// # Arguments
+///
+/// - `pair` - The program pair to check.
+///
+/// # Returns
+///
+/// Why the pair looks suspicious, empty if it does not, or `None` if the
+/// Rust repository is not cloned, so the pair cannot be checked.
+pub fn check(pair: &ProgramPair) -> Option<Vec<String>> {
+    let rust_clone =
+        cache::clone_directory(Path::new(""), pair.rust_program.repository_url.as_str());
+    if !rust_clone.is_dir() {
+        return None;
+    }
+    let manifest = read_manifest(&rust_clone);
+    let name = pair.program_name.to_lowercase();
+
+    let mut problems = wrapper_signs(&manifest, &name);
+
+    let repository_name = utils::get_repository_name(pair.rust_program.repository_url.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let mut names: Vec<String> = manifest
+        .bin
+        .iter()
+        .filter_map(|bin| bin.name.clone())
+        .chain(manifest.package.iter().map(|package| package.name.clone()))
+        .chain([repository_name])
+        .collect();
+    names.extend(words(&read_readme(&rust_clone)));
+    if !names.iter().any(|word| mentions(word, &name)) {
+        problems.push(format!(
+            "the Rust repository's name, Cargo.toml, and README never mention '{}'",
+            pair.program_name
+        ));
+    }
+
+    let pair_directory = Path::new(PROGRAM_PAIRS_DIRECTORY).join(&pair.program_name);
+    let c_options = program_options(&pair_directory, &Language::C);
+    let rust_options = program_options(&pair_directory, &Language::Rust);
+    if c_options.len() >= MIN_OPTIONS {
+        let shared = c_options.intersection(&rust_options).count();
+        if (shared as f64) < c_options.len() as f64 * MIN_SHARED_OPTIONS {
+            problems.push(format!(
+                "the Rust program has only {} of the C program's {} long options",
+                shared,
+                c_options.len()
+            ));
+        }
+    }
+
+    Some(problems)
+}
+
+/// Lists the signs in a `Cargo.toml` that a crate binds to C code rather
+/// than reimplementing it.
+///
+/// # Arguments
+///
+/// - `manifest` - The Rust repository's `Cargo.toml`.
+/// - `name` - The program's name in lowercase.
+///
+/// # Returns
+///
+/// A description of each sign found.
+fn wrapper_signs(manifest: &CargoManifest, name: &str) -> Vec<String> {
+    let mut signs = Vec::new();
+    if let Some(package) = &manifest.package {
+        if package.name.ends_with("-sys") {
+            signs.push(format!(
+                "the Rust crate '{}' is a -sys crate, which usually binds a C library",
+                package.name
+            ));
+        }
+        if let Some(links) = &package.links {
+            signs.push(format!(
+                "the Rust crate links to the native library '{links}'"
+            ));
+        }
+    }
+    // The program's name must be a whole part of the crate's, so that
+    // `openssl-sys` is not taken for bindings to `ls`.
+    for dependency in manifest.dependencies.keys() {
+        if let Some(library) = dependency.strip_suffix("-sys")
+            && mentions(library, name)
+        {
+            signs.push(format!(
+                "the Rust crate depends on '{dependency}', which likely binds the C program"
+            ));
+        }
+    }
+    for dependency in BINDING_BUILD_DEPENDENCIES {
+        if manifest.build_dependencies.contains_key(dependency) {
+            signs.push(format!(
+                "the Rust crate builds C code with the '{dependency}' build dependency"
+            ));
+        }
+    }
+    signs
+}
+
+/// Reads the `Cargo.toml` at the root of a clone, or an empty manifest if it
+/// has none or it cannot be parsed.
+fn read_manifest(clone: &Path) -> CargoManifest {
+    fs::read_to_string(clone.join("Cargo.toml"))
+        .ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Reads the README at the root of a clone, or an empty string if it has
+/// none.
+fn read_readme(clone: &Path) -> String {
+    let Ok(entries) = fs::read_dir(clone) else {
+        return String::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_file()
+                && path.file_name().is_some_and(|file_name| {
+                    file_name
+                        .to_string_lossy()
+                        .to_lowercase()
+                        .starts_with("readme")
+                })
+        })
+        .and_then(|path| fs::read(path).ok())
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default()
+}
+
+/// Splits text into lowercase words of letters, digits, `-`, `_`, and `+`,
+/// which program names are made of.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '+')))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Returns whether a word, such as a crate name, mentions a program's name,
+/// either whole or as one of its `-` or `_` separated parts, as in
+/// `uu_ls`.
+fn mentions(word: &str, name: &str) -> bool {
+    let word = word.to_lowercase();
+    word == name || word.split(['-', '_']).any(|part| part == name)
+}
+
+/// Collects the long options of one program of a downloaded pair.
+fn program_options(pair_directory: &Path, language: &Language) -> BTreeSet<String> {
+    let mut options = BTreeSet::new();
+    for path in utils::list_files(&manifest::program_directory(pair_directory, language)) {
+        if Language::from_path(&path).as_ref() == Some(language)
+            && let Ok(bytes) = fs::read(&path)
+        {
+            options.extend(long_options(&String::from_utf8_lossy(&bytes)));
+        }
+    }
+    options
+}
+
+/// Finds the long options that some source code mentions.
+///
+/// Options are `--name` words, such as in help text, `{"name", ...}`
+/// entries of a C `getopt_long` table, and clap's `long("name")` and
+/// `long = "name"` arguments.
+///
+/// # Arguments
+///
+/// - `source` - C or Rust source code.
+///
+/// # Returns
+///
+/// The names of the options, without the leading `--`.
+pub fn long_options(source: &str) -> BTreeSet<String> {
+    let is_option_char = |c: char| c.is_ascii_alphanumeric() || c == '-';
+    let option_name = |text: &str| -> Option<String> {
+        let name: String = text.chars().take_while(|&c| is_option_char(c)).collect();
+        (name.len() > 1 && name.starts_with(|c: char| c.is_ascii_lowercase())).then_some(name)
+    };
+
+    let mut options = BTreeSet::new();
+    for (index, _) in source.match_indices("--") {
+        let preceded_by_option_char = source[..index]
+            .chars()
+            .next_back()
+            .is_some_and(|c| is_option_char(c) || c == '_');
+        if !preceded_by_option_char && let Some(name) = option_name(&source[index + 2..]) {
+            options.insert(name);
+        }
+    }
+    for (index, _) in source.match_indices("{\"") {
+        let rest = &source[index + 2..];
+        if let Some(name) = option_name(rest)
+            && rest[name.len()..].starts_with("\",")
+            && ARGUMENT_KINDS
+                .iter()
+                .any(|kind| rest[name.len() + 2..].trim_start().starts_with(kind))
+        {
+            options.insert(name);
+        }
+    }
+    for prefix in ["long(\"", "long = \""] {
+        for (index, _) in source.match_indices(prefix) {
+            if let Some(name) = option_name(&source[index + prefix.len()..]) {
+                options.insert(name);
+            }
+        }
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that long options are found in help text, `getopt_long` tables,
+    /// and clap arguments, but not in comment rules or decrements.
+    #[test]
+    fn test_long_options() {
+        let c_source = r#"
+/* ------------------------------------------ */
+static struct option long_options[] = {
+    {"all", no_argument, NULL, 'a'},
+    {"block-size", required_argument, NULL, 'B'},
+    {NULL, 0, NULL, 0}
+};
+static const char *usage = "  -h, --help  display this help";
+int main(void) { i--; return x-->0; }
+"#;
+        let expected: BTreeSet<String> = ["all", "block-size", "help"].map(String::from).into();
+        assert_eq!(expected, long_options(c_source));
+
+        let rust_source = r#"
+let all = Arg::new("all").long("all");
+#[arg(long = "block-size")]
+"#;
+        let expected: BTreeSet<String> = ["all", "block-size"].map(String::from).into();
+        assert_eq!(expected, long_options(rust_source));
+    }
+
+    /// Tests that -sys crates, native libraries, and C build dependencies are
+    /// flagged as wrappers.
+    #[test]
+    fn test_wrapper_signs() {
+        let manifest: CargoManifest = toml::from_str(
+            r#"
+[package]
+name = "zstd-rs"
+links = "zstd"
+
+[dependencies]
+zstd-sys = "2"
+clap = "4"
+
+[build-dependencies]
+cc = "1"
+"#,
+        )
+        .unwrap();
+        assert_eq!(3, wrapper_signs(&manifest, "zstd").len());
+        assert!(wrapper_signs(&CargoManifest::default(), "zstd").is_empty());
+
+        let manifest: CargoManifest = toml::from_str(
+            r#"
+[dependencies]
+openssl-sys = "0.9"
+"#,
+        )
+        .unwrap();
+        assert!(wrapper_signs(&manifest, "ls").is_empty());
+        assert_eq!(1, wrapper_signs(&manifest, "openssl").len());
+    }
+}
@@ -0,0 +1,403 @@
+//! # Corpus Server
+//!
+//! This module serves the corpus over HTTP, so that teammates can browse
+//! the program pairs and fetch downloaded ones without cloning every
+//! repository themselves.  Every endpoint speaks JSON, except for pair
+//! archives, which are streamed as gzipped tarballs while they are built.
+//!
+//! - `GET /pairs` lists the program pairs and whether each is downloaded.
+//! - `GET /pairs/<name>` returns a pair's metadata.
+//! - `GET /pairs/<name>/archive` streams a downloaded pair as
+//!   `<name>.tar.gz`.
+//! - `POST /downloads` downloads the pairs named in the body's `pairs`, or
+//!   every pair if the body is empty.
+//! - `GET /downloads` reports the running or last download.
+//!
+//! Requests are handled on their own threads, and one download runs at a
+//! time, in the background.
+
+use std::{
+    io::{self, Cursor, Write},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+use flate2::{Compression, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use crate::{
+    corpus::{
+        downloader::{self, DownloadOptions},
+        errors::{DownloaderError, ServeError},
+        manifest::MANIFEST_FILE,
+        parser,
+        schema::ProgramPair,
+    },
+    paths::{INDIVIDUAL_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY, PROJECT_METADATA_DIRECTORY},
+};
+
+/// An endpoint of the server.
+#[derive(Debug, PartialEq, Eq)]
+enum Route<'a> {
+    /// `GET /pairs`.
+    ListPairs,
+    /// `GET /pairs/<name>`.
+    Pair(&'a str),
+    /// `GET /pairs/<name>/archive`.
+    Archive(&'a str),
+    /// `POST /downloads`.
+    StartDownload,
+    /// `GET /downloads`.
+    DownloadStatus,
+    /// Any other method or path.
+    NotFound,
+}
+
+/// A program pair as listed by `GET /pairs`.
+#[derive(Debug, Serialize)]
+struct PairSummary<'a> {
+    /// Name of the program.
+    program_name: &'a str,
+    /// Description of the program.
+    program_description: &'a str,
+    /// The pair's tags.
+    tags: &'a [String],
+    /// Whether the pair is downloaded in `program_pairs/`.
+    downloaded: bool,
+}
+
+/// The body of `POST /downloads`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DownloadRequest {
+    /// Names of the program pairs to download; every pair if empty.
+    #[serde(default)]
+    pairs: Vec<String>,
+}
+
+/// The state of the running or last download, as reported by
+/// `GET /downloads`.
+#[derive(Debug, Default, Clone, Serialize)]
+struct DownloadStatus {
+    /// Whether a download is running.
+    running: bool,
+    /// Names of the program pairs being or last downloaded.
+    pairs: Vec<String>,
+    /// Names of the program pairs that failed to download last time.
+    failed_pairs: Vec<String>,
+    /// Why the last download failed, if it did.
+    error: Option<String>,
+}
+
+/// The state shared by the threads handling requests.
+struct ServerState {
+    /// Options controlling the downloads started over HTTP.
+    options: DownloadOptions,
+    /// The running or last download.
+    download: Mutex<DownloadStatus>,
+}
+
+/// Serves the corpus over HTTP until interrupted.
+///
+/// # Arguments
+///
+/// - `address` - The address to listen on, such as `127.0.0.1:8080`.
+/// - `options` - Options controlling the downloads started over HTTP.
+///
+/// # Returns
+///
+/// Returns [`ServeError`] if the server cannot listen on `address`; it
+/// otherwise runs until the process is interrupted.
+pub fn serve(address: &str, options: &DownloadOptions) -> Result<(), ServeError> {
+    let server = Server::http(address).map_err(|error| ServeError::Bind {
+        address: address.to_string(),
+        message: error.to_string(),
+    })?;
+    println!("Serving the corpus on http://{address}");
+
+    let state = Arc::new(ServerState {
+        options: options.clone(),
+        download: Mutex::new(DownloadStatus::default()),
+    });
+    for request in server.incoming_requests() {
+        let state = Arc::clone(&state);
+        thread::spawn(move || handle(request, &state));
+    }
+    Ok(())
+}
+
+/// Answers a request, logging any failure to send the response.
+fn handle(mut request: Request, state: &Arc<ServerState>) {
+    let url = request.url().to_string();
+    let result = match route(request.method(), &url) {
+        Route::ListPairs => request.respond(list_pairs()),
+        Route::Pair(name) => request.respond(pair_metadata(name)),
+        Route::Archive(name) => match archive(name) {
+            Ok(response) => request.respond(response),
+            Err(response) => request.respond(response),
+        },
+        Route::StartDownload => {
+            let mut body = String::new();
+            let response = match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => start_download(&body, state),
+                Err(error) => error_response(400, &format!("Failed to read body: {error}")),
+            };
+            request.respond(response)
+        }
+        Route::DownloadStatus => {
+            let status = lock_status(state).clone();
+            request.respond(json_response(200, &status))
+        }
+        Route::NotFound => request.respond(error_response(404, "Not found")),
+    };
+    if let Err(error) = result {
+        eprintln!("Failed to respond to '{url}': {error}");
+    }
+}
+
+/// Finds the endpoint a request is for.
+///
+/// # Arguments
+///
+/// - `method` - The request's method.
+/// - `url` - The request's path, with any query string.
+///
+/// # Returns
+///
+/// The [`Route`] of the endpoint, with the program name it names, if any.
+fn route<'a>(method: &Method, url: &'a str) -> Route<'a> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    match (method, segments.as_slice()) {
+        (Method::Get, ["pairs"]) => Route::ListPairs,
+        (Method::Get, ["pairs", name]) => Route::Pair(name),
+        (Method::Get, ["pairs", name, "archive"]) => Route::Archive(name),
+        (Method::Get, ["downloads"]) => Route::DownloadStatus,
+        (Method::Post, ["downloads"]) => Route::StartDownload,
+        _ => Route::NotFound,
+    }
+}
+
+/// Answers `GET /pairs` with every program pair, sorted by name.
+fn list_pairs() -> Response<Cursor<Vec<u8>>> {
+    let pairs = match load_pairs() {
+        Ok(pairs) => pairs,
+        Err(message) => return error_response(500, &message),
+    };
+    let summaries: Vec<PairSummary> = pairs
+        .iter()
+        .map(|pair| PairSummary {
+            program_name: &pair.program_name,
+            program_description: &pair.program_description,
+            tags: &pair.tags,
+            downloaded: is_downloaded(&pair.program_name),
+        })
+        .collect();
+    json_response(200, &summaries)
+}
+
+/// Answers `GET /pairs/<name>` with the metadata of a program pair.
+fn pair_metadata(name: &str) -> Response<Cursor<Vec<u8>>> {
+    match load_pairs() {
+        Ok(pairs) => match pairs.iter().find(|pair| pair.program_name == name) {
+            Some(pair) => json_response(200, pair),
+            None => error_response(404, &format!("No program pair is named '{name}'")),
+        },
+        Err(message) => error_response(500, &message),
+    }
+}
+
+/// Answers `GET /pairs/<name>/archive` by streaming the pair's directory in
+/// `program_pairs/` as a gzipped tarball, built on another thread while it
+/// is sent.
+///
+/// # Returns
+///
+/// The streaming response, or an error response if the pair is not
+/// downloaded.
+fn archive(name: &str) -> Result<Response<io::PipeReader>, Response<Cursor<Vec<u8>>>> {
+    if !is_downloaded(name) {
+        return Err(error_response(
+            404,
+            &format!("Program pair '{name}' is not downloaded"),
+        ));
+    }
+    let (reader, writer) =
+        io::pipe().map_err(|error| error_response(500, &format!("Failed to stream: {error}")))?;
+    let directory = Path::new(PROGRAM_PAIRS_DIRECTORY).join(name);
+    let archive_root = name.to_string();
+    thread::spawn(move || {
+        let mut archive = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+        let result = archive
+            .append_dir_all(&archive_root, &directory)
+            .and_then(|()| archive.into_inner())
+            .and_then(|encoder| encoder.finish())
+            .and_then(|mut writer| writer.flush());
+        // The client may have hung up; the response ends either way.
+        if let Err(error) = result {
+            eprintln!("Failed to archive '{archive_root}': {error}");
+        }
+    });
+
+    let mut response = Response::new(StatusCode(200), Vec::new(), reader, None, None)
+        .with_header(content_type("application/gzip"));
+    if let Ok(header) = Header::from_bytes(
+        "Content-Disposition",
+        format!("attachment; filename=\"{name}.tar.gz\""),
+    ) {
+        response.add_header(header);
+    }
+    Ok(response)
+}
+
+/// Answers `POST /downloads` by starting a download in the background,
+/// unless one is already running.
+///
+/// # Arguments
+///
+/// - `body` - The request's body, a [`DownloadRequest`], or empty to
+///   download every pair.
+/// - `state` - The state shared by the server's threads.
+fn start_download(body: &str, state: &Arc<ServerState>) -> Response<Cursor<Vec<u8>>> {
+    let request: DownloadRequest = if body.trim().is_empty() {
+        DownloadRequest::default()
+    } else {
+        match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(error) => return error_response(400, &format!("Invalid body: {error}")),
+        }
+    };
+    let pairs = match load_pairs() {
+        Ok(pairs) => pairs,
+        Err(message) => return error_response(500, &message),
+    };
+    if let Some(unknown) = request
+        .pairs
+        .iter()
+        .find(|name| !pairs.iter().any(|pair| &pair.program_name == *name))
+    {
+        return error_response(404, &format!("No program pair is named '{unknown}'"));
+    }
+    let pairs: Vec<ProgramPair> = pairs
+        .into_iter()
+        .filter(|pair| request.pairs.is_empty() || request.pairs.contains(&pair.program_name))
+        .collect();
+
+    let status = {
+        let mut status = lock_status(state);
+        if status.running {
+            return error_response(409, "A download is already running");
+        }
+        *status = DownloadStatus {
+            running: true,
+            pairs: pairs.iter().map(|pair| pair.program_name.clone()).collect(),
+            ..DownloadStatus::default()
+        };
+        status.clone()
+    };
+
+    let state = Arc::clone(state);
+    thread::spawn(move || {
+        let result = downloader::download_pairs(&pairs, &state.options);
+        let mut status = lock_status(&state);
+        status.running = false;
+        if let Err(error) = result {
+            if let DownloaderError::Incomplete { failed_pairs, .. } = &error {
+                status.failed_pairs = failed_pairs.clone();
+            }
+            status.error = Some(error.to_string());
+        }
+    });
+    json_response(202, &status)
+}
+
+/// Locks the status of the running or last download.
+///
+/// A download that panicked cannot have left the status half-written, so a
+/// poisoned mutex is used as is.
+fn lock_status(state: &ServerState) -> MutexGuard<'_, DownloadStatus> {
+    state
+        .download
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Reads the program pairs of the project and individual metadata, sorted
+/// by name; metadata files that fail to parse are reported and skipped.
+///
+/// # Returns
+///
+/// The program pairs, or a message describing why a metadata directory
+/// cannot be read.
+fn load_pairs() -> Result<Vec<ProgramPair>, String> {
+    let mut pairs = Vec::new();
+    for directory in [PROJECT_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY] {
+        let metadata_files = parser::parse_directory(Path::new(directory))
+            .map_err(|error| format!("Failed to parse '{directory}': {error}"))?;
+        for (path, metadata) in metadata_files {
+            match metadata {
+                Ok(metadata) => pairs.extend(metadata.pairs),
+                Err(error) => eprintln!("Failed to parse '{}': {}", path.display(), error),
+            }
+        }
+    }
+    pairs.sort_by(|a, b| a.program_name.cmp(&b.program_name));
+    Ok(pairs)
+}
+
+/// Returns whether a program pair is downloaded, judged by its manifest,
+/// which is written once both programs have been copied.
+fn is_downloaded(name: &str) -> bool {
+    Path::new(PROGRAM_PAIRS_DIRECTORY)
+        .join(name)
+        .join(MANIFEST_FILE)
+        .is_file()
+}
+
+/// Builds a JSON response.
+fn json_response<T: Serialize + ?Sized>(status: u16, body: &T) -> Response<Cursor<Vec<u8>>> {
+    match serde_json::to_vec_pretty(body) {
+        Ok(body) => Response::from_data(body)
+            .with_status_code(status)
+            .with_header(content_type("application/json")),
+        Err(error) => Response::from_string(format!("Failed to serialize response: {error}"))
+            .with_status_code(500),
+    }
+}
+
+/// Builds a JSON response whose body is `{"error": message}`.
+fn error_response(status: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    json_response(status, &json!({ "error": message }))
+}
+
+/// Builds a `Content-Type` header.
+fn content_type(mime_type: &str) -> Header {
+    Header::from_bytes("Content-Type", mime_type).expect("Unreachable because MIME types are ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that requests are routed by method and path, ignoring query
+    /// strings and trailing slashes.
+    #[test]
+    fn test_route() {
+        assert_eq!(Route::ListPairs, route(&Method::Get, "/pairs/"));
+        assert_eq!(Route::Pair("ls"), route(&Method::Get, "/pairs/ls?pretty"));
+        assert_eq!(
+            Route::Archive("ls"),
+            route(&Method::Get, "/pairs/ls/archive")
+        );
+        assert_eq!(Route::StartDownload, route(&Method::Post, "/downloads"));
+        assert_eq!(Route::DownloadStatus, route(&Method::Get, "/downloads"));
+        assert_eq!(Route::NotFound, route(&Method::Post, "/pairs"));
+        assert_eq!(Route::NotFound, route(&Method::Get, "/pairs/ls/source"));
+    }
+}
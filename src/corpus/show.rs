@@ -0,0 +1,454 @@
+//! # Source Viewer
+//!
+//! This module prints a source file of a downloaded program pair next to its
+//! counterpart in the pair's other program, with syntax highlighting, so
+//! curators can judge how well a pair's programs line up without opening an
+//! editor.  A file's counterpart is the file of the other program with the
+//! same name, or else the one defining the most functions matched by name,
+//! as in `diff`.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use ratatui::crossterm::terminal;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Style, Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::{self, LinesWithEndings},
+};
+
+use crate::{
+    corpus::{analysis::ast, diff, errors::ShowError, manifest, schema::Language, utils},
+    paths::PROGRAM_PAIRS_DIRECTORY,
+};
+
+/// Width assumed when the output is not a terminal, such as when it is
+/// piped.
+const DEFAULT_WIDTH: usize = 160;
+
+/// Columns a tab is expanded to.
+const TAB_WIDTH: usize = 4;
+
+/// Separates the C and Rust columns in the side-by-side layout.
+const SEPARATOR: &str = " │ ";
+
+/// Resets the terminal's colors.
+const RESET: &str = "\x1b[0m";
+
+/// The syntax definitions used for highlighting, which take a while to load.
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// The color theme used for highlighting.
+static THEME: LazyLock<Theme> = LazyLock::new(|| {
+    ThemeSet::load_defaults()
+        .themes
+        .remove("base16-ocean.dark")
+        .unwrap_or_default()
+});
+
+/// How the two files are laid out.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ShowLayout {
+    /// In two columns, cutting off long lines.
+    SideBySide,
+    /// One after the other, at full width.
+    Unified,
+}
+
+/// A source file of one program of a pair.
+struct SourceFile {
+    /// The language of the program.
+    language: Language,
+    /// Path of the file relative to its program directory.
+    relative: PathBuf,
+    /// Path of the file.
+    path: PathBuf,
+}
+
+/// A line of source code split into runs of the same style.
+type StyledLine = Vec<(Style, String)>;
+
+/// Prints a source file of a downloaded program pair next to its
+/// counterpart in the pair's other program.
+///
+/// Colors are used when printing to a terminal, unless `NO_COLOR` is set.
+///
+/// # Arguments
+///
+/// - `program_name` - Name of the program pair in `program_pairs/`.
+/// - `file` - Path or name of a C or Rust file of the pair, relative to its
+///   program directory; by default, the C file defining `main`.
+/// - `layout` - How to lay out the two files.
+/// - `width` - Width of the output in columns; by default, the terminal's.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`ShowError`] on failure.
+pub fn show(
+    program_name: &str,
+    file: Option<&str>,
+    layout: ShowLayout,
+    width: Option<usize>,
+) -> Result<(), ShowError> {
+    let pair_directory = Path::new(PROGRAM_PAIRS_DIRECTORY).join(program_name);
+    if !pair_directory.is_dir() {
+        return Err(ShowError::PairNotFound {
+            program_name: program_name.to_string(),
+        });
+    }
+    let c_files = source_files(&pair_directory, Language::C);
+    let rust_files = source_files(&pair_directory, Language::Rust);
+
+    let selected = match file {
+        Some(file) => find_file(c_files.iter().chain(&rust_files), file).ok_or_else(|| {
+            ShowError::FileNotFound {
+                program_name: program_name.to_string(),
+                file: file.to_string(),
+            }
+        })?,
+        None => main_file(&c_files)
+            .or_else(|| main_file(&rust_files))
+            .ok_or_else(|| ShowError::NoSourceFiles {
+                program_name: program_name.to_string(),
+            })?,
+    };
+    let (c_file, rust_file) = if selected.language.is_source() {
+        (
+            Some(selected),
+            counterpart(selected, &rust_files, &pair_directory),
+        )
+    } else {
+        (
+            counterpart(selected, &c_files, &pair_directory),
+            Some(selected),
+        )
+    };
+
+    let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let c_lines = read_lines(c_file, color)?;
+    let rust_lines = read_lines(rust_file, color)?;
+    let c_title = title(c_file, &pair_directory, &Language::C);
+    let rust_title = title(rust_file, &pair_directory, &Language::Rust);
+
+    match layout {
+        ShowLayout::SideBySide => {
+            let width = width.unwrap_or_else(terminal_width);
+            let rows = c_lines.len().max(rust_lines.len());
+            let number_width = rows.to_string().len();
+            // Each column holds a line number, a space, and the line.
+            let column = (width.saturating_sub(SEPARATOR.chars().count()) / 2)
+                .saturating_sub(number_width + 1)
+                .max(1);
+            let plain = |text: &str| render(&[(Style::default(), text.to_string())], column, false);
+            // The padding of the Rust column is trimmed from each row.
+            let header = format!(
+                "{:number_width$} {}{SEPARATOR}{:number_width$} {}",
+                "",
+                plain(&c_title),
+                "",
+                plain(&rust_title)
+            );
+            println!("{}", header.trim_end());
+            for index in 0..rows {
+                let cell = |lines: &[StyledLine]| match lines.get(index) {
+                    Some(line) => format!(
+                        "{:>number_width$} {}",
+                        index + 1,
+                        render(line, column, color)
+                    ),
+                    None => " ".repeat(number_width + 1 + column),
+                };
+                let row = format!("{}{SEPARATOR}{}", cell(&c_lines), cell(&rust_lines));
+                println!("{}", row.trim_end());
+            }
+        }
+        ShowLayout::Unified => {
+            for (title, lines) in [(&c_title, &c_lines), (&rust_title, &rust_lines)] {
+                println!("==> {title} <==");
+                let number_width = lines.len().to_string().len();
+                for (index, line) in lines.iter().enumerate() {
+                    println!(
+                        "{:>number_width$} {}",
+                        index + 1,
+                        render(line, usize::MAX, color)
+                    );
+                }
+                println!();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lists the C or Rust source files of a downloaded pair.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The program pair's directory.
+/// - `language` - Which program of the pair to list.
+///
+/// # Returns
+///
+/// The program's source files, sorted by path.
+fn source_files(pair_directory: &Path, language: Language) -> Vec<SourceFile> {
+    let program_directory = manifest::program_directory(pair_directory, &language);
+    utils::list_files(&program_directory)
+        .into_iter()
+        .filter(|path| Language::from_path(path).as_ref() == Some(&language))
+        .map(|path| SourceFile {
+            language: language.clone(),
+            relative: path
+                .strip_prefix(&program_directory)
+                .unwrap_or(&path)
+                .to_path_buf(),
+            path,
+        })
+        .collect()
+}
+
+/// Finds a source file by its path relative to its program directory, or
+/// else by its file name.
+fn find_file<'a>(
+    files: impl Iterator<Item = &'a SourceFile> + Clone,
+    file: &str,
+) -> Option<&'a SourceFile> {
+    let file = Path::new(file);
+    files
+        .clone()
+        .find(|source| source.relative == file)
+        .or_else(|| {
+            files
+                .into_iter()
+                .find(|source| source.relative.file_name() == file.file_name())
+        })
+}
+
+/// Picks the file defining `main`, or else the first file.
+fn main_file(files: &[SourceFile]) -> Option<&SourceFile> {
+    files
+        .iter()
+        .find(|source| {
+            ast::extract_file(&source.path, &source.language)
+                .iter()
+                .any(|function| function.name == "main")
+        })
+        .or_else(|| files.first())
+}
+
+/// Finds the counterpart of a source file among the files of the pair's
+/// other program.
+///
+/// The counterpart is the file with the same name apart from its extension,
+/// such as `cat.rs` for `cat.c`, or else the file defining the most
+/// functions that match the functions of `file` by name, or else the only
+/// file of the other program.
+///
+/// # Arguments
+///
+/// - `file` - The source file to find the counterpart of.
+/// - `candidates` - The source files of the other program.
+/// - `pair_directory` - The program pair's directory.
+///
+/// # Returns
+///
+/// The counterpart, or `None` if no file corresponds to `file`.
+fn counterpart<'a>(
+    file: &SourceFile,
+    candidates: &'a [SourceFile],
+    pair_directory: &Path,
+) -> Option<&'a SourceFile> {
+    if let Some(candidate) = candidates
+        .iter()
+        .find(|candidate| candidate.relative.file_stem() == file.relative.file_stem())
+    {
+        return Some(candidate);
+    }
+
+    let relative = file.relative.display().to_string();
+    let functions: Vec<diff::LocatedFunction> = ast::extract_file(&file.path, &file.language)
+        .into_iter()
+        .map(|function| diff::LocatedFunction {
+            file: relative.clone(),
+            function,
+        })
+        .collect();
+    let is_source = file.language.is_source();
+    let other_language = if is_source {
+        Language::Rust
+    } else {
+        Language::C
+    };
+    let others = diff::collect_functions(pair_directory, &other_language);
+    let report = if is_source {
+        diff::match_functions(functions, others)
+    } else {
+        diff::match_functions(others, functions)
+    };
+    let mut matches: HashMap<String, usize> = HashMap::new();
+    for (c_function, rust_function) in report.matched {
+        let other = if is_source { rust_function } else { c_function };
+        *matches.entry(other.file).or_default() += 1;
+    }
+    let best = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let count = matches.get(&candidate.relative.display().to_string())?;
+            Some((count, candidate))
+        })
+        // Ties go to the first file by path.
+        .fold(
+            None,
+            |best: Option<(&usize, &SourceFile)>, (count, candidate)| match best {
+                Some((best_count, _)) if best_count >= count => best,
+                _ => Some((count, candidate)),
+            },
+        );
+    match best {
+        Some((_, candidate)) => Some(candidate),
+        None if candidates.len() == 1 => candidates.first(),
+        None => None,
+    }
+}
+
+/// Reads a source file into lines, highlighted if `color` is set.
+///
+/// # Arguments
+///
+/// - `file` - The source file, or `None` for a missing counterpart.
+/// - `color` - Whether to highlight the source code.
+///
+/// # Returns
+///
+/// The lines of the file, or [`ShowError::IoRead`] if it cannot be read.
+fn read_lines(file: Option<&SourceFile>, color: bool) -> Result<Vec<StyledLine>, ShowError> {
+    let Some(file) = file else {
+        return Ok(Vec::new());
+    };
+    // Files that are not valid UTF-8 are read lossily.
+    let bytes = fs::read(&file.path).map_err(|error| ShowError::IoRead {
+        path: file.path.clone(),
+        error,
+    })?;
+    let source = String::from_utf8_lossy(&bytes);
+    if !color {
+        return Ok(source
+            .lines()
+            .map(|line| vec![(Style::default(), line.to_string())])
+            .collect());
+    }
+
+    let extension = match file.language {
+        Language::C => "c",
+        Language::Cpp => "cpp",
+        Language::Rust => "rs",
+        Language::Go => "go",
+        Language::Zig => "zig",
+    };
+    let syntax = SYNTAXES
+        .find_syntax_by_extension(extension)
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, &THEME);
+    Ok(LinesWithEndings::from(&source)
+        .map(|line| match highlighter.highlight_line(line, &SYNTAXES) {
+            Ok(ranges) => ranges
+                .into_iter()
+                .map(|(style, text)| (style, text.to_string()))
+                .collect(),
+            Err(_) => vec![(Style::default(), line.to_string())],
+        })
+        .collect())
+}
+
+/// Renders a line, cut off and padded to a width.
+///
+/// # Arguments
+///
+/// - `line` - The line to render.
+/// - `width` - The number of columns to fill.
+/// - `color` - Whether to color the line with its styles.
+///
+/// # Returns
+///
+/// The line, with tabs expanded and terminal escape codes if `color` is set.
+fn render(line: &[(Style, String)], width: usize, color: bool) -> String {
+    let mut rendered = String::new();
+    let mut columns = 0;
+    'runs: for (style, text) in line {
+        let mut run = String::new();
+        for character in text.chars() {
+            let (expanded, count) = match character {
+                '\n' | '\r' => continue,
+                '\t' => (' ', TAB_WIDTH - columns % TAB_WIDTH),
+                character => (character, 1),
+            };
+            if columns + count > width {
+                push_run(&mut rendered, *style, &run, color);
+                break 'runs;
+            }
+            run.extend(std::iter::repeat_n(expanded, count));
+            columns += count;
+        }
+        push_run(&mut rendered, *style, &run, color);
+    }
+    if color {
+        rendered.push_str(RESET);
+    }
+    if width != usize::MAX {
+        rendered.push_str(&" ".repeat(width - columns));
+    }
+    rendered
+}
+
+/// Appends a run of text in one style to a rendered line.
+fn push_run(rendered: &mut String, style: Style, run: &str, color: bool) {
+    if run.is_empty() {
+        return;
+    }
+    if color {
+        rendered.push_str(&util::as_24_bit_terminal_escaped(&[(style, run)], false));
+    } else {
+        rendered.push_str(run);
+    }
+}
+
+/// Names a file in the header of its column.
+fn title(file: Option<&SourceFile>, pair_directory: &Path, language: &Language) -> String {
+    let program_directory = manifest::program_directory_name(pair_directory, language);
+    match file {
+        Some(file) => Path::new(&program_directory)
+            .join(&file.relative)
+            .display()
+            .to_string(),
+        None => format!("(no counterpart in {program_directory}/)"),
+    }
+}
+
+/// The width of the terminal, or [`DEFAULT_WIDTH`] if the output is not a
+/// terminal.
+fn terminal_width() -> usize {
+    if !io::stdout().is_terminal() {
+        return DEFAULT_WIDTH;
+    }
+    terminal::size().map_or(DEFAULT_WIDTH, |(columns, _)| usize::from(columns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that rendering expands tabs to the next tab stop, drops the
+    /// newline, and pads or truncates to the column width.
+    #[test]
+    fn test_render_expands_tabs_and_pads() {
+        let line = vec![(Style::default(), "\tab\tc\n".to_string())];
+        assert_eq!("    ab  c   ", render(&line, 12, false));
+        assert_eq!("    a", render(&line, 5, false));
+        assert_eq!("    ab  c", render(&line, usize::MAX, false));
+    }
+}
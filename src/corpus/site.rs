@@ -0,0 +1,532 @@
+//! # Corpus Report
+//!
+//! This module renders the downloaded program pairs as a static HTML site,
+//! such as for publishing the corpus on GitHub Pages.  The site has an index
+//! page listing every pair with its statistics, and a page per pair showing
+//! its metadata and the files of its C and Rust programs side by side, each
+//! expandable to its source text.
+//!
+//! ```text
+//! site/
+//! ├── index.html
+//! ├── style.css
+//! └── pairs/
+//!     ├── cat.html
+//!     └── ls.html
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    corpus::{
+        errors::SiteError,
+        manifest::{self, Manifest, ProgramManifest},
+        parser,
+        schema::{Language, ProgramPair},
+        stats::{self, Analyses, PairStats, ProgramStats},
+    },
+    paths::{
+        DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// Directory of the pair pages within the site.
+const PAIRS_DIRECTORY: &str = "pairs";
+
+/// The tag every page carries, by which the pages of an earlier report are
+/// told apart from files the tool did not write.
+const GENERATOR_META: &str = "<meta name=\"generator\" content=\"c-rust-program-pairs\">";
+
+/// Files larger than this many bytes are listed without their source text,
+/// to keep pages loadable.
+const MAX_SOURCE_BYTES: u64 = 512 * 1024;
+
+/// The stylesheet shared by every page.
+const STYLESHEET: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 80rem; padding: 0 1rem; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border-bottom: 1px solid #ddd; padding: 0.3rem 0.5rem; text-align: left; }
+td.number, th.number { text-align: right; }
+dt { font-weight: bold; }
+.programs { display: grid; gap: 2rem; grid-template-columns: 1fr 1fr; }
+.programs > section { min-width: 0; }
+.files { list-style: none; padding: 0; }
+pre { background: #f6f8fa; overflow-x: auto; padding: 0.5rem; }
+.tag { background: #eef; border-radius: 0.5rem; margin-right: 0.3rem; padding: 0 0.4rem; }
+";
+
+/// The output format of a report.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// A static HTML site.
+    Html,
+}
+
+/// Renders the downloaded program pairs as a report.
+///
+/// Pages of pairs from earlier reports are removed, so the report only
+/// shows the pairs currently in `program_pairs/`.  An output directory
+/// holding a `pairs/` directory that no report wrote is refused rather than
+/// cleared.
+///
+/// # Arguments
+///
+/// - `format` - The output format of the report.
+/// - `output` - The directory to write the site to.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`SiteError`] on failure.
+pub fn report(format: ReportFormat, output: &Path) -> Result<(), SiteError> {
+    let ReportFormat::Html = format;
+    let metadata = metadata_pairs()?;
+    let program_pairs = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    let pairs = stats::collect_stats(program_pairs, Analyses::default())
+        .map_err(|error| SiteError::Stats { error })?;
+
+    let pairs_directory = output.join(PAIRS_DIRECTORY);
+    remove_earlier_pages(&pairs_directory)?;
+    fs::create_dir_all(&pairs_directory).map_err(|error| SiteError::IoWrite {
+        path: pairs_directory.clone(),
+        error,
+    })?;
+
+    let mut rows = Vec::new();
+    for pair in &pairs {
+        let pair_directory = program_pairs.join(&pair.program_name);
+        let manifest = Manifest::load(&pair_directory);
+        let metadata = metadata.get(&pair.program_name);
+        write_file(
+            &pairs_directory.join(format!("{}.html", pair.program_name)),
+            &pair_page(pair, &pair_directory, metadata, manifest.as_ref()),
+        )?;
+        rows.push(index_row(pair, metadata, manifest.as_ref()));
+    }
+    write_file(&output.join("index.html"), &index_page(&pairs, &rows))?;
+    write_file(&output.join("style.css"), STYLESHEET)?;
+
+    println!(
+        "Wrote a report of {} program pairs to '{}'",
+        pairs.len(),
+        output.join("index.html").display()
+    );
+    Ok(())
+}
+
+/// Removes the pages of an earlier report from its `pairs/` directory.
+///
+/// Only the pages directly in the directory are removed, and only once
+/// every file there is known to be one: an HTML page carrying
+/// [`GENERATOR_META`].
+///
+/// # Arguments
+///
+/// - `pairs_directory` - The `pairs/` directory of the report, which may
+///   not exist.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, [`SiteError::NotReport`] if the directory
+/// holds anything but pages of a report, or [`SiteError::IoDelete`] if it
+/// cannot be read or a page cannot be removed.
+fn remove_earlier_pages(pairs_directory: &Path) -> Result<(), SiteError> {
+    if !pairs_directory.exists() {
+        return Ok(());
+    }
+    let delete_error = |error| SiteError::IoDelete {
+        path: pairs_directory.to_path_buf(),
+        error,
+    };
+    let mut pages = Vec::new();
+    for entry in fs::read_dir(pairs_directory).map_err(delete_error)? {
+        let path = entry.map_err(delete_error)?.path();
+        let is_page = path.is_file()
+            && path
+                .extension()
+                .is_some_and(|extension| extension == "html")
+            && fs::read_to_string(&path).is_ok_and(|page| page.contains(GENERATOR_META));
+        if !is_page {
+            return Err(SiteError::NotReport { path });
+        }
+        pages.push(path);
+    }
+    for page in pages {
+        fs::remove_file(&page).map_err(|error| SiteError::IoDelete { path: page, error })?;
+    }
+    Ok(())
+}
+
+/// Parses every metadata file.
+///
+/// Files that fail to parse are reported and skipped, so their pairs are
+/// shown without metadata.
+///
+/// # Returns
+///
+/// Every program pair in the metadata by name, or [`SiteError`] if a
+/// metadata directory cannot be read.
+fn metadata_pairs() -> Result<HashMap<String, ProgramPair>, SiteError> {
+    let mut pairs = HashMap::new();
+    for directory in [
+        DEMO_METADATA_DIRECTORY,
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+    ] {
+        let metadata_files =
+            parser::parse_directory(Path::new(directory)).map_err(|error| SiteError::Parse {
+                path: directory.into(),
+                error,
+            })?;
+        for (path, metadata) in metadata_files {
+            match metadata {
+                Ok(metadata) => pairs.extend(
+                    metadata
+                        .pairs
+                        .into_iter()
+                        .map(|pair| (pair.program_name.clone(), pair)),
+                ),
+                Err(error) => eprintln!("Failed to parse '{}': {}", path.display(), error),
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// Renders the index page, listing every pair with its statistics.
+///
+/// # Arguments
+///
+/// - `pairs` - The statistics of every downloaded pair.
+/// - `rows` - The table row of each pair, from [`index_row`].
+///
+/// # Returns
+///
+/// The HTML of the page.
+fn index_page(pairs: &[PairStats], rows: &[String]) -> String {
+    let c_code: usize = pairs.iter().map(|pair| pair.c_program.lines.code).sum();
+    let rust_code: usize = pairs.iter().map(|pair| pair.rust_program.lines.code).sum();
+    let mut body = format!(
+        "<h1>C-Rust Program Pairs</h1>\n\
+         <p>{} program pairs with {c_code} lines of C code and {rust_code} lines of Rust \
+         code.</p>\n\
+         <table>\n<thead><tr><th>Pair</th><th>Description</th><th>Tags</th>\
+         <th class=\"number\">C code</th><th class=\"number\">Rust code</th>\
+         <th class=\"number\">Ratio</th><th class=\"number\">C tokens</th>\
+         <th class=\"number\">Rust tokens</th><th>Licenses</th></tr></thead>\n<tbody>\n",
+        pairs.len()
+    );
+    for row in rows {
+        body.push_str(row);
+    }
+    body.push_str("</tbody>\n</table>\n");
+    page("C-Rust Program Pairs", "style.css", &body)
+}
+
+/// Renders a pair's row of the index page's table.
+///
+/// # Arguments
+///
+/// - `pair` - The pair's statistics.
+/// - `metadata` - The pair's metadata, if it is still in a metadata file.
+/// - `manifest` - The pair's manifest, if it has one.
+///
+/// # Returns
+///
+/// The HTML of the row.
+fn index_row(
+    pair: &PairStats,
+    metadata: Option<&ProgramPair>,
+    manifest: Option<&Manifest>,
+) -> String {
+    let licenses: Vec<&str> = manifest.map_or_else(Vec::new, |manifest| {
+        [&manifest.c_program, &manifest.rust_program]
+            .into_iter()
+            .map(|program| program.license.as_deref().unwrap_or("unknown"))
+            .collect()
+    });
+    format!(
+        "<tr><td><a href=\"{PAIRS_DIRECTORY}/{}\">{}</a></td><td>{}</td><td>{}</td>\
+         <td class=\"number\">{}</td><td class=\"number\">{}</td>\
+         <td class=\"number\">{}</td><td class=\"number\">{}</td>\
+         <td class=\"number\">{}</td><td>{}</td></tr>\n",
+        escape(&page_url(&pair.program_name)),
+        escape(&pair.program_name),
+        escape(metadata.map_or("", |metadata| &metadata.program_description)),
+        tags(manifest.map_or(&[], |manifest| &manifest.tags)),
+        pair.c_program.lines.code,
+        pair.rust_program.lines.code,
+        ratio(pair.c_program.lines.code, pair.rust_program.lines.code),
+        pair.c_program.tokens,
+        pair.rust_program.tokens,
+        escape(&licenses.join(" / ")),
+    )
+}
+
+/// Renders the page of a pair, with its metadata and the files of its C and
+/// Rust programs side by side.
+///
+/// # Arguments
+///
+/// - `pair` - The pair's statistics, listing its source files.
+/// - `pair_directory` - The pair's directory in `program_pairs/`.
+/// - `metadata` - The pair's metadata, if it is still in a metadata file.
+/// - `manifest` - The pair's manifest, if it has one.
+///
+/// # Returns
+///
+/// The HTML of the page.
+fn pair_page(
+    pair: &PairStats,
+    pair_directory: &Path,
+    metadata: Option<&ProgramPair>,
+    manifest: Option<&Manifest>,
+) -> String {
+    let mut body = format!(
+        "<p><a href=\"../index.html\">All program pairs</a></p>\n<h1>{}</h1>\n",
+        escape(&pair.program_name)
+    );
+    let mut details = Vec::new();
+    if let Some(metadata) = metadata {
+        let _ = writeln!(body, "<p>{}</p>", escape(&metadata.program_description));
+        let tools: Vec<String> = metadata
+            .translation_tools
+            .iter()
+            .map(|tool| tool.to_string())
+            .collect();
+        details.push(("Translation tools", escape(&tools.join(", "))));
+        details.push((
+            "Feature relationship",
+            escape(metadata.feature_relationship.to_str()),
+        ));
+    }
+    if let Some(manifest) = manifest.filter(|manifest| !manifest.tags.is_empty()) {
+        details.push(("Tags", tags(&manifest.tags)));
+    }
+    body.push_str(&definitions(&details));
+
+    body.push_str("<div class=\"programs\">\n");
+    for (language, program) in [
+        (Language::C, &pair.c_program),
+        (Language::Rust, &pair.rust_program),
+    ] {
+        let program_manifest = manifest.map(|manifest| manifest.program(&language));
+        body.push_str(&program_section(
+            &language,
+            program,
+            &manifest::program_directory(pair_directory, &language),
+            program_manifest,
+        ));
+    }
+    body.push_str("</div>\n");
+
+    if let Some(manifest) = manifest.filter(|manifest| !manifest.function_mappings.is_empty()) {
+        body.push_str(
+            "<h2>Function mappings</h2>\n<table>\n<thead><tr><th>C function</th>\
+             <th>Rust function</th></tr></thead>\n<tbody>\n",
+        );
+        for mapping in &manifest.function_mappings {
+            let _ = writeln!(
+                body,
+                "<tr><td><code>{}</code> in <code>{}</code></td>\
+                 <td><code>{}</code> in <code>{}</code></td></tr>",
+                escape(&mapping.c.function),
+                escape(mapping.c.file.as_str()),
+                escape(&mapping.rust.function),
+                escape(mapping.rust.file.as_str()),
+            );
+        }
+        body.push_str("</tbody>\n</table>\n");
+    }
+    page(&pair.program_name, "../style.css", &body)
+}
+
+/// Renders the section of a pair's page describing one of its programs.
+///
+/// # Arguments
+///
+/// - `language` - The language of the program.
+/// - `program` - The program's statistics, listing its source files.
+/// - `directory` - The program's `c-program/` or `rust-program/` directory.
+/// - `manifest` - The program's manifest entry, if the pair has a manifest.
+///
+/// # Returns
+///
+/// The HTML of the section.
+fn program_section(
+    language: &Language,
+    program: &ProgramStats,
+    directory: &Path,
+    manifest: Option<&ProgramManifest>,
+) -> String {
+    let name = match language {
+        Language::C => "C",
+        Language::Cpp => "C++",
+        Language::Rust => "Rust",
+        Language::Go => "Go",
+        Language::Zig => "Zig",
+    };
+    let mut details = Vec::new();
+    if let Some(manifest) = manifest {
+        let url = manifest
+            .mirror_url
+            .as_ref()
+            .unwrap_or(&manifest.repository_url);
+        details.push(("Repository", link(url)));
+        details.push((
+            "License",
+            escape(manifest.license.as_deref().unwrap_or("unknown")),
+        ));
+    }
+    details.push(("Lines of code", program.lines.code.to_string()));
+    details.push(("Tokens", program.tokens.to_string()));
+
+    let mut section = format!(
+        "<section>\n<h2>{name} program</h2>\n{}<ul class=\"files\">\n",
+        definitions(&details)
+    );
+    for file in &program.files {
+        let path = directory.join(&file.path);
+        let source = match fs::metadata(&path) {
+            Ok(metadata) if metadata.len() > MAX_SOURCE_BYTES => {
+                "<p>Too large to show.</p>".to_string()
+            }
+            Ok(_) => match fs::read(&path) {
+                Ok(bytes) => format!(
+                    "<pre><code>{}</code></pre>",
+                    escape(&String::from_utf8_lossy(&bytes))
+                ),
+                Err(error) => format!("<p>Failed to read: {}</p>", escape(&error.to_string())),
+            },
+            Err(error) => format!("<p>Failed to read: {}</p>", escape(&error.to_string())),
+        };
+        let _ = writeln!(
+            section,
+            "<li><details><summary><code>{}</code> ({} lines of code)</summary>{source}\
+             </details></li>",
+            escape(&file.path.to_string_lossy()),
+            file.lines.code,
+        );
+    }
+    section.push_str("</ul>\n</section>\n");
+    section
+}
+
+/// Wraps the body of a page in an HTML document.
+///
+/// # Arguments
+///
+/// - `title` - The page's title.
+/// - `stylesheet` - The path of the stylesheet relative to the page.
+/// - `body` - The HTML of the page's body.
+///
+/// # Returns
+///
+/// The HTML document.
+fn page(title: &str, stylesheet: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         {GENERATOR_META}\n<title>{}</title>\n<link rel=\"stylesheet\" href=\"{stylesheet}\">\n</head>\n\
+         <body>\n{body}</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+/// Renders a definition list of terms and their HTML descriptions.
+fn definitions(details: &[(&str, String)]) -> String {
+    let mut list = "<dl>\n".to_string();
+    for (term, description) in details {
+        let _ = writeln!(list, "<dt>{term}</dt><dd>{description}</dd>");
+    }
+    list.push_str("</dl>\n");
+    list
+}
+
+/// Renders tags as labels.
+fn tags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("<span class=\"tag\">{}</span>", escape(tag)))
+        .collect()
+}
+
+/// Renders a URL as a link, unless it is a local path, such as a `file://`
+/// repository.
+fn link(url: &str) -> String {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        format!("<a href=\"{0}\">{0}</a>", escape(url))
+    } else {
+        escape(url)
+    }
+}
+
+/// Formats the ratio of Rust to C lines of code, as in `stats`.
+fn ratio(c_code: usize, rust_code: usize) -> String {
+    if c_code > 0 {
+        format!("{:.2}", rust_code as f64 / c_code as f64)
+    } else {
+        "-".to_string()
+    }
+}
+
+/// Links to the page of a pair from the index page, percent-encoding
+/// characters that are not safe in a URL, such as the `+` of `c++filt`.
+///
+/// # Arguments
+///
+/// - `program_name` - The pair's name.
+///
+/// # Returns
+///
+/// The URL of the pair's page, `<program_name>.html`, relative to
+/// `pairs/`.
+fn page_url(program_name: &str) -> String {
+    let mut name = String::new();
+    for byte in program_name.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.') {
+            name.push(byte as char);
+        } else {
+            let _ = write!(name, "%{byte:02X}");
+        }
+    }
+    name.push_str(".html");
+    name
+}
+
+/// Escapes text for use in HTML content and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// Writes a page or other file of the site.
+///
+/// # Arguments
+///
+/// - `path` - The file to write.
+/// - `contents` - The contents of the file.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`SiteError::IoWrite`] on failure.
+fn write_file(path: &Path, contents: &str) -> Result<(), SiteError> {
+    fs::write(path, contents).map_err(|error| SiteError::IoWrite {
+        path: PathBuf::from(path),
+        error,
+    })
+}
@@ -0,0 +1,163 @@
+//! # Skip List
+//!
+//! This module reads `metadata/skiplist.json`, which lists program pairs
+//! that are known to be broken, such as pairs whose upstream repository was
+//! deleted, along with why.  The downloader skips these pairs instead of
+//! failing on them, and `list` and `stats` show why they are missing.
+//!
+//! ```json
+//! {
+//!     "pairs": [
+//!         {
+//!             "program_name": "ls",
+//!             "reason": "upstream_deleted",
+//!             "note": "The Rust repository was archived and removed."
+//!         }
+//!     ]
+//! }
+//! ```
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    corpus::{
+        effects::{FileSystem, Storage},
+        errors::SkipListError,
+    },
+    paths::SKIPLIST_FILE,
+};
+
+/// Why a program pair is skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The C or Rust repository, or the program's files, no longer exist.
+    UpstreamDeleted,
+    /// The pair cannot be redistributed under its licenses.
+    LicenseIssue,
+    /// Any other reason, explained by the entry's note.
+    Other,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::UpstreamDeleted => "upstream deleted",
+            SkipReason::LicenseIssue => "license issue",
+            SkipReason::Other => "other",
+        })
+    }
+}
+
+/// An entry of the skip list.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SkipListEntry {
+    /// Name of the skipped program pair.
+    program_name: String,
+    /// Why the pair is skipped.
+    reason: SkipReason,
+    /// Details about why the pair is skipped.
+    #[serde(default)]
+    note: Option<String>,
+}
+
+/// The contents of `metadata/skiplist.json`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SkipListFile {
+    /// The skipped program pairs.
+    pairs: Vec<SkipListEntry>,
+}
+
+/// Why one program pair is skipped.
+#[derive(Debug, Clone)]
+pub struct Skip {
+    /// The category of the reason.
+    pub reason: SkipReason,
+    /// Details about why the pair is skipped.
+    pub note: Option<String>,
+}
+
+impl fmt::Display for Skip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.note {
+            Some(note) => write!(f, "{}: {}", self.reason, note),
+            None => write!(f, "{}", self.reason),
+        }
+    }
+}
+
+/// The program pairs to skip, keyed by program name.
+#[derive(Debug, Default)]
+pub struct SkipList {
+    /// Why each skipped pair is skipped, sorted by program name.
+    pub pairs: BTreeMap<String, Skip>,
+}
+
+impl SkipList {
+    /// Loads `metadata/skiplist.json`, or an empty skip list if it does not
+    /// exist.
+    ///
+    /// # Returns
+    ///
+    /// The [`SkipList`] on success and [`SkipListError`] on failure.
+    pub fn load() -> Result<Self, SkipListError> {
+        Self::load_from(&FileSystem::default())
+    }
+
+    /// Loads `metadata/skiplist.json` from a [`Storage`], or an empty skip
+    /// list if it does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// - `storage` - The storage holding the skip list.
+    ///
+    /// # Returns
+    ///
+    /// The [`SkipList`] on success and [`SkipListError`] on failure.
+    pub fn load_from(storage: &dyn Storage) -> Result<Self, SkipListError> {
+        let path = Path::new(SKIPLIST_FILE);
+        if !storage.exists(path) {
+            return Ok(Self::default());
+        }
+        let contents = storage
+            .read_to_string(path)
+            .map_err(|error| SkipListError::IoRead {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        let file: SkipListFile =
+            serde_json::from_str(&contents).map_err(|error| SkipListError::Deserialize {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        let pairs = file
+            .pairs
+            .into_iter()
+            .map(|entry| {
+                let skip = Skip {
+                    reason: entry.reason,
+                    note: entry.note,
+                };
+                (entry.program_name, skip)
+            })
+            .collect();
+        Ok(SkipList { pairs })
+    }
+
+    /// Looks up why a program pair is skipped.
+    ///
+    /// # Arguments
+    ///
+    /// - `program_name` - Name of the program pair.
+    ///
+    /// # Returns
+    ///
+    /// The [`Skip`] of the pair, or `None` if it is not skipped.
+    pub fn get(&self, program_name: &str) -> Option<&Skip> {
+        self.pairs.get(program_name)
+    }
+}
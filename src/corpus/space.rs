@@ -0,0 +1,126 @@
+//! # Disk Space Preflight
+//!
+//! This module estimates the disk space needed to clone the repositories of
+//! the program pairs about to be downloaded, from the repository sizes
+//! recorded by `enrich`, and checks that the filesystem of
+//! `repository_clones/` has that much space available.  A download that
+//! would run out of space is aborted before it starts, rather than failing
+//! partway through with a full disk.
+
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    corpus::{
+        cache,
+        downloader::DownloadOptions,
+        enrich::EnrichedMetadata,
+        errors::DownloaderError,
+        schema::{ProgramPair, SourceType},
+        skiplist::SkipList,
+        utils,
+    },
+    paths::REPOSITORY_CLONES_DIRECTORY,
+};
+
+/// How many times its reported size a clone is estimated to take, since the
+/// working directory takes about as much space again as the git objects.
+pub const CLONE_SIZE_FACTOR: u64 = 2;
+
+/// Checks that there is enough disk space to clone the repositories of the
+/// program pairs that are not cached yet.
+///
+/// Repositories without a recorded size are left out of the estimate, with
+/// a warning, as are the pairs in the skip list.  Nothing is checked in
+/// offline mode, which clones nothing, or if
+/// [`DownloadOptions::skip_space_check`] is set.
+///
+/// # Arguments
+///
+/// - `pairs` - The program pairs about to be downloaded.
+/// - `skiplist` - The program pairs that will be skipped.
+/// - `options` - Options controlling the download.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the estimated space is available, or
+/// [`DownloaderError::InsufficientSpace`] if it is not.
+pub fn check<'a>(
+    pairs: impl IntoIterator<Item = &'a ProgramPair>,
+    skiplist: &SkipList,
+    options: &DownloadOptions,
+) -> Result<(), DownloaderError> {
+    if options.offline || options.skip_space_check {
+        return Ok(());
+    }
+
+    // Clones are keyed by normalized URL, so each is only counted once, and
+    // looked up by the URL as written, which the cache and `enrich`
+    // normalize themselves.  Tarballs and files are left out, as `enrich`
+    // cannot size them.
+    let uncached: BTreeMap<String, &str> = pairs
+        .into_iter()
+        .filter(|pair| skiplist.get(&pair.program_name).is_none())
+        .flat_map(|pair| pair.programs())
+        .filter(|program| program.source_type == SourceType::Git)
+        .map(|program| program.repository_url.as_str())
+        .filter(|url| !cache::clone_directory(&options.root, url).exists())
+        .map(|url| (utils::normalize_repository_url(url), url))
+        .collect();
+    if uncached.is_empty() {
+        return Ok(());
+    }
+
+    let enriched = EnrichedMetadata::load(&options.root)
+        .map_err(|error| DownloaderError::Enriched { error })?;
+    // Sizes come from the GitHub API, so a bogus one saturates rather than
+    // overflowing.
+    let mut required: u64 = 0;
+    let mut unknown = 0;
+    for url in uncached.values() {
+        match enriched.get(url).and_then(|info| info.size) {
+            Some(size) => {
+                required = required.saturating_add(size.saturating_mul(CLONE_SIZE_FACTOR))
+            }
+            None => unknown += 1,
+        }
+    }
+    if unknown > 0 {
+        eprintln!(
+            "The size of {unknown} of {} repositories to clone is unknown; run `enrich` to \
+             include them in the disk space estimate",
+            uncached.len()
+        );
+    }
+
+    let clones = options.root.join(REPOSITORY_CLONES_DIRECTORY);
+    let path = existing_ancestor(&clones);
+    let available = fs4::available_space(path).map_err(|error| DownloaderError::FreeSpace {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    if required > available {
+        return Err(DownloaderError::InsufficientSpace {
+            path: path.to_path_buf(),
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// Finds the closest ancestor of a path that exists, which is on the
+/// filesystem the path will be created on.
+///
+/// # Arguments
+///
+/// - `path` - A path that may not exist yet.
+///
+/// # Returns
+///
+/// The path itself if it exists, its closest existing ancestor, or the
+/// current directory.
+fn existing_ancestor(path: &Path) -> &Path {
+    path.ancestors()
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .unwrap_or(Path::new("."))
+}
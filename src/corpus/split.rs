@@ -0,0 +1,237 @@
+//! # Corpus Splits
+//!
+//! This module partitions the downloaded program pairs into training,
+//! validation, and test sets for machine learning workflows.  Each split is
+//! recorded in a manifest at `program_pairs/splits/<split>.json`, and can
+//! optionally be materialized as a directory of copies of, or symbolic links
+//! to, its pairs.
+//!
+//! Pairs are ordered by a hash of their name and the seed rather than by
+//! shuffling, so the same seed always yields the same splits, and pairs added
+//! to the corpus later do not move existing pairs between splits unless the
+//! split sizes change.
+
+use std::{fs, io, path::Path};
+
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::{
+    corpus::{errors::SplitError, utils},
+    paths::{PROGRAM_PAIRS_DIRECTORY, SPLITS_DIRECTORY},
+};
+
+/// The names of the splits, in the order pairs are assigned to them.
+const SPLIT_NAMES: [&str; 3] = ["train", "test", "validation"];
+
+/// How each split is materialized under `program_pairs/splits/`.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Materialize {
+    /// Copy each pair into its split's directory.
+    Copy,
+    /// Link to each pair from its split's directory.
+    Symlink,
+}
+
+/// The manifest of one split.
+#[derive(Debug, Serialize)]
+struct SplitManifest<'a> {
+    /// Name of the split, such as `train`.
+    split: &'a str,
+    /// The seed the splits were generated with.
+    seed: u64,
+    /// Names of the program pairs in the split, sorted.
+    pairs: Vec<String>,
+}
+
+/// Partitions the downloaded program pairs into training, validation, and
+/// test sets and writes a manifest for each.
+///
+/// # Arguments
+///
+/// - `train` - Fraction of pairs in the training set.
+/// - `test` - Fraction of pairs in the test set; the remaining pairs form the
+///   validation set.
+/// - `seed` - Seed determining which pairs go into which set.
+/// - `materialize` - How to create a directory per split, if at all.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`SplitError`] on failure.
+pub fn split(
+    train: f64,
+    test: f64,
+    seed: u64,
+    materialize: Option<Materialize>,
+) -> Result<(), SplitError> {
+    // Allow for rounding, so fractions such as 0.7 and 0.3 are accepted.
+    let fits = train + test <= 1.0 + f64::EPSILON;
+    if !(0.0..=1.0).contains(&train) || !(0.0..=1.0).contains(&test) || !fits {
+        return Err(SplitError::InvalidFractions { train, test });
+    }
+
+    let program_pairs = Path::new(PROGRAM_PAIRS_DIRECTORY);
+    let pair_names: Vec<String> = utils::list_program_pairs(program_pairs)
+        .map_err(|error| SplitError::IoRead {
+            path: program_pairs.to_path_buf(),
+            error,
+        })?
+        .iter()
+        .filter_map(|path| Some(path.file_name()?.to_string_lossy().into_owned()))
+        .collect();
+    let splits = partition(pair_names, train, test, seed);
+
+    let splits_directory = Path::new(SPLITS_DIRECTORY);
+    fs::create_dir_all(splits_directory).map_err(|error| SplitError::IoWrite {
+        path: splits_directory.to_path_buf(),
+        error,
+    })?;
+    for (name, pairs) in SPLIT_NAMES.iter().zip(splits) {
+        println!("{name}: {} program pairs", pairs.len());
+
+        let path = splits_directory.join(format!("{name}.json"));
+        let manifest = SplitManifest {
+            split: name,
+            seed,
+            pairs,
+        };
+        let contents = serde_json::to_string_pretty(&manifest)
+            .map_err(|error| SplitError::Serialize { error })?;
+        fs::write(&path, contents).map_err(|error| SplitError::IoWrite { path, error })?;
+
+        if let Some(materialize) = materialize {
+            materialize_split(&splits_directory.join(name), &manifest.pairs, materialize)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Deterministically partitions program pairs into training, test, and
+/// validation sets.
+///
+/// # Arguments
+///
+/// - `pair_names` - Names of the program pairs.
+/// - `train` - Fraction of pairs in the training set.
+/// - `test` - Fraction of pairs in the test set.
+/// - `seed` - Seed determining the order pairs are assigned in.
+///
+/// # Returns
+///
+/// The sorted names of the pairs in the training, test, and validation sets.
+fn partition(mut pair_names: Vec<String>, train: f64, test: f64, seed: u64) -> [Vec<String>; 3] {
+    pair_names.sort_by_cached_key(|name| (utils::seeded_hash(name, seed), name.clone()));
+
+    let total = pair_names.len();
+    let train_count = ((total as f64 * train).round() as usize).min(total);
+    let test_count = ((total as f64 * test).round() as usize).min(total - train_count);
+    let validation = pair_names.split_off(train_count + test_count);
+    let test = pair_names.split_off(train_count);
+    let mut splits = [pair_names, test, validation];
+    for split in &mut splits {
+        split.sort();
+    }
+    splits
+}
+
+/// Recreates a split's directory with a copy of, or a link to, each of its
+/// program pairs.
+///
+/// # Arguments
+///
+/// - `directory` - The split's directory, such as `program_pairs/splits/train`.
+/// - `pairs` - Names of the split's program pairs.
+/// - `materialize` - Whether to copy or link the pairs.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`SplitError`] on failure.
+fn materialize_split(
+    directory: &Path,
+    pairs: &[String],
+    materialize: Materialize,
+) -> Result<(), SplitError> {
+    let write_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |error| SplitError::IoWrite { path, error }
+    };
+
+    // Start from an empty directory, so pairs that moved to another split do
+    // not linger.
+    if directory.exists() {
+        fs::remove_dir_all(directory).map_err(write_error(directory))?;
+    }
+    fs::create_dir_all(directory).map_err(write_error(directory))?;
+
+    for pair in pairs {
+        let destination = directory.join(pair);
+        match materialize {
+            Materialize::Copy => {
+                copy_directory(&Path::new(PROGRAM_PAIRS_DIRECTORY).join(pair), &destination)?
+            }
+            Materialize::Symlink => {
+                // Relative links keep working if `program_pairs/` is moved.
+                let target = Path::new("..").join("..").join(pair);
+                symlink_directory(&target, &destination).map_err(write_error(&destination))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Recursively copies a directory.
+fn copy_directory(source: &Path, destination: &Path) -> Result<(), SplitError> {
+    for entry in WalkDir::new(source) {
+        let entry = entry.map_err(|error| SplitError::IoRead {
+            path: source.to_path_buf(),
+            error: error.into(),
+        })?;
+        let Ok(relative) = entry.path().strip_prefix(source) else {
+            continue;
+        };
+        let path = destination.join(relative);
+        let result = if entry.file_type().is_dir() {
+            fs::create_dir_all(&path)
+        } else {
+            fs::copy(entry.path(), &path).map(|_| ())
+        };
+        result.map_err(|error| SplitError::IoWrite { path, error })?;
+    }
+    Ok(())
+}
+
+/// Creates a symbolic link to a directory.
+#[cfg(unix)]
+fn symlink_directory(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Creates a symbolic link to a directory.
+#[cfg(windows)]
+fn symlink_directory(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_dir(target, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that partitions have the requested sizes, cover every pair once,
+    /// and depend only on the seed.
+    #[test]
+    fn test_partition() {
+        let names: Vec<String> = (0..10).map(|i| format!("pair{i}")).collect();
+
+        let [train, test, validation] = partition(names.clone(), 0.6, 0.2, 7);
+        assert_eq!((6, 2, 2), (train.len(), test.len(), validation.len()));
+        let mut all: Vec<String> = [train.clone(), test, validation].concat();
+        all.sort();
+        assert_eq!(names, all);
+
+        let mut reversed = names.clone();
+        reversed.reverse();
+        assert_eq!(train, partition(reversed, 0.6, 0.2, 7)[0]);
+        assert_ne!(train, partition(names, 0.6, 0.2, 8)[0]);
+    }
+}
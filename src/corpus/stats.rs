@@ -0,0 +1,738 @@
+//! # Corpus Statistics
+//!
+//! This module computes statistics over the program pairs downloaded into
+//! `program_pairs/`, such as how many lines of code the C and Rust programs
+//! of each pair contain, or roughly how many tokens they take up in a
+//! language model's context window.  Repository information recorded by
+//! `enrich`, such as star counts, is reported alongside, and the counts can
+//! be totalled per tag.  How much of each Rust program relies on `unsafe`,
+//! and how complex the functions of each C program are, can be reported too,
+//! with the [`analyze`] module's analyses, which only run when asked for
+//! since they parse every file.
+//!
+//! With `--json`, every count of every pair is printed as a JSON object
+//! instead of tables, so the flags choosing which tables to print do not
+//! change it, except `--detailed` and `--dedup`, and `--unsafe` and
+//! `--complexity`, which add the counts of their analyses.
+
+pub mod loc;
+pub mod tokens;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    corpus::{
+        analysis::complexity::{self, Complexity, Difficulty},
+        analyze::unsafety::{self, UnsafeCounts},
+        enrich::{EnrichedMetadata, RepositoryInfo},
+        errors::StatsError,
+        manifest::{self, Manifest},
+        output,
+        schema::Language,
+        skiplist::SkipList,
+        store::{self, DedupStats},
+        utils,
+    },
+    paths::PROGRAM_PAIRS_DIRECTORY,
+};
+
+use loc::LineCounts;
+
+/// The row of `stats --by-tag` counting the pairs without tags.
+const UNTAGGED: &str = "(untagged)";
+
+/// Statistics for a single source file.
+#[derive(Debug, Serialize)]
+pub struct FileStats {
+    /// Path of the file relative to its program directory.
+    pub path: PathBuf,
+    /// Line counts of the file.
+    pub lines: LineCounts,
+    /// Approximate number of tokens in the file.
+    pub tokens: usize,
+    /// Counts of unsafe code in the file, all zero for C files, if they
+    /// were asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsafe_code: Option<UnsafeCounts>,
+    /// Complexity of the file's functions, all zero for Rust files, if it
+    /// was asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<Complexity>,
+}
+
+/// Statistics for the C or Rust program of a pair.
+#[derive(Debug, Default, Serialize)]
+pub struct ProgramStats {
+    /// Name of the program's directory within its pair's directory, such as
+    /// `c-program`.
+    pub directory: String,
+    /// Statistics for each source file in the program.
+    pub files: Vec<FileStats>,
+    /// Line counts summed over all source files.
+    pub lines: LineCounts,
+    /// Approximate number of tokens summed over all source files.
+    pub tokens: usize,
+    /// Counts of unsafe code summed over all source files, if they were
+    /// asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsafe_code: Option<UnsafeCounts>,
+    /// Complexity of the functions of all source files, if it was asked
+    /// for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<Complexity>,
+}
+
+/// The analyses [`collect_stats`] runs besides counting lines and tokens,
+/// which parse every file and so only run when asked for.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Analyses {
+    /// Whether to count the unsafe code of Rust files.
+    pub unsafe_code: bool,
+    /// Whether to measure the complexity of the functions of C files.
+    pub complexity: bool,
+}
+
+/// Statistics for one downloaded program pair.
+#[derive(Debug, Serialize)]
+pub struct PairStats {
+    /// Name of the program pair.
+    pub program_name: String,
+    /// Statistics for the C program.
+    pub c_program: ProgramStats,
+    /// Statistics for the Rust program.
+    pub rust_program: ProgramStats,
+    /// Statistics for the pair's Rust variants, by label.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub rust_variants: BTreeMap<String, ProgramStats>,
+}
+
+/// The statistics printed by `stats --json`.
+#[derive(Debug, Serialize)]
+struct StatsResult<'a> {
+    /// Statistics for each downloaded pair, sorted by name, with the counts
+    /// of every file only if `--detailed` was given.
+    pairs: &'a [PairStats],
+    /// Disk usage of identical files, if `--dedup` was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dedup: Option<DedupStats>,
+    /// Why each skipped pair is skipped, by program name.
+    skipped: BTreeMap<&'a str, String>,
+}
+
+/// Prints line count or token statistics for every downloaded program pair.
+///
+/// # Arguments
+///
+/// - `detailed` - Whether to also print the counts of every file.
+/// - `tokens` - Whether to print approximate token counts instead of line
+///   counts.
+/// - `max_tokens` - A context window size; pairs with more tokens in total
+///   are listed after the table.
+/// - `by_tag` - Whether to also print the totals of the pairs with each tag.
+/// - `unsafe_code` - Whether to also print the unsafe code counts of every
+///   Rust program.
+/// - `complexity` - Whether to also print the function complexity of every
+///   C program.
+/// - `dedup` - Whether to also print how much disk space identical files
+///   take up.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`StatsError`] on failure.
+pub fn stats(
+    detailed: bool,
+    tokens: bool,
+    max_tokens: Option<usize>,
+    by_tag: bool,
+    unsafe_code: bool,
+    complexity: bool,
+    dedup: bool,
+) -> Result<(), StatsError> {
+    let analyses = Analyses {
+        unsafe_code,
+        complexity,
+    };
+    let mut pairs = collect_stats(Path::new(PROGRAM_PAIRS_DIRECTORY), analyses)?;
+
+    if output::json() {
+        if !detailed {
+            for pair in &mut pairs {
+                pair.c_program.files.clear();
+                pair.rust_program.files.clear();
+                for variant in pair.rust_variants.values_mut() {
+                    variant.files.clear();
+                }
+            }
+        }
+        let dedup = if dedup {
+            Some(
+                store::dedup_stats(Path::new(PROGRAM_PAIRS_DIRECTORY))
+                    .map_err(|(path, error)| StatsError::IoRead { path, error })?,
+            )
+        } else {
+            None
+        };
+        let skiplist = SkipList::load().map_err(|error| StatsError::SkipList { error })?;
+        output::print_json(&StatsResult {
+            pairs: &pairs,
+            dedup,
+            skipped: skiplist
+                .pairs
+                .iter()
+                .map(|(program_name, skip)| (program_name.as_str(), skip.to_string()))
+                .collect(),
+        });
+        return Ok(());
+    }
+
+    if tokens {
+        print_token_table(&pairs, detailed, max_tokens);
+    } else {
+        print_line_table(&pairs, detailed);
+    }
+
+    if by_tag {
+        println!();
+        print_tag_table(&pairs, tokens);
+    }
+
+    if unsafe_code {
+        println!();
+        print_unsafe_table(&pairs, detailed);
+    }
+
+    if complexity {
+        println!();
+        print_complexity_table(&pairs, detailed);
+    }
+
+    if dedup {
+        let dedup = store::dedup_stats(Path::new(PROGRAM_PAIRS_DIRECTORY))
+            .map_err(|(path, error)| StatsError::IoRead { path, error })?;
+        println!();
+        print_dedup(&dedup);
+    }
+
+    // Skipped pairs are missing from the table unless they were downloaded
+    // before being skipped, so list why.
+    let skiplist = SkipList::load().map_err(|error| StatsError::SkipList { error })?;
+    if !skiplist.pairs.is_empty() {
+        println!();
+        println!("Skipped {} program pairs:", skiplist.pairs.len());
+        for (program_name, skip) in &skiplist.pairs {
+            println!("  {program_name:<22} {skip}");
+        }
+    }
+
+    let enriched =
+        EnrichedMetadata::load(Path::new("")).map_err(|error| StatsError::Enriched { error })?;
+    if !enriched.repositories.is_empty() {
+        println!();
+        print_repositories(&pairs, &enriched);
+    }
+
+    Ok(())
+}
+
+/// Computes statistics for every program pair in a directory.
+///
+/// # Arguments
+///
+/// - `directory` - The directory holding program pairs, typically
+///   `program_pairs/`.
+/// - `analyses` - The analyses to run besides counting lines and tokens.
+///
+/// # Returns
+///
+/// The [`PairStats`] of each pair sorted by name, or [`StatsError`] on
+/// failure.
+pub fn collect_stats(directory: &Path, analyses: Analyses) -> Result<Vec<PairStats>, StatsError> {
+    let pair_directories =
+        utils::list_program_pairs(directory).map_err(|error| StatsError::IoRead {
+            path: directory.to_path_buf(),
+            error,
+        })?;
+
+    let mut pairs = Vec::new();
+    for pair_directory in pair_directories {
+        let program_name = pair_directory
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let variant_directories = Manifest::load(&pair_directory)
+            .map(|manifest| manifest.rust_variants)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(label, variant)| Some((label, variant.directory?)));
+        let mut rust_variants = BTreeMap::new();
+        for (label, directory) in variant_directories {
+            rust_variants.insert(label, program_stats(&pair_directory, directory, analyses)?);
+        }
+        pairs.push(PairStats {
+            program_name,
+            c_program: program_stats(
+                &pair_directory,
+                manifest::program_directory_name(&pair_directory, &Language::C),
+                analyses,
+            )?,
+            rust_program: program_stats(
+                &pair_directory,
+                manifest::program_directory_name(&pair_directory, &Language::Rust),
+                analyses,
+            )?,
+            rust_variants,
+        });
+    }
+
+    Ok(pairs)
+}
+
+/// Computes statistics for the source files in a program directory.
+///
+/// Files that are not source files of a known language are ignored.
+///
+/// # Arguments
+///
+/// - `pair_directory` - The program pair's directory.
+/// - `directory` - Name of the program's directory within `pair_directory`.
+/// - `analyses` - The analyses to run besides counting lines and tokens.
+///
+/// # Returns
+///
+/// The [`ProgramStats`] of the program's directory, or [`StatsError`] on
+/// failure.
+fn program_stats(
+    pair_directory: &Path,
+    directory: String,
+    analyses: Analyses,
+) -> Result<ProgramStats, StatsError> {
+    let mut stats = ProgramStats {
+        directory,
+        unsafe_code: analyses.unsafe_code.then(UnsafeCounts::default),
+        complexity: analyses.complexity.then(Complexity::default),
+        ..ProgramStats::default()
+    };
+    let directory = &pair_directory.join(&stats.directory);
+    let files = utils::list_files(directory);
+    for path in &files {
+        let Some(language) = Language::from_program_path(path, &files) else {
+            continue;
+        };
+        // Files that are not valid UTF-8 are read lossily.
+        let bytes = fs::read(path).map_err(|error| StatsError::IoRead {
+            path: path.clone(),
+            error,
+        })?;
+        let source = String::from_utf8_lossy(&bytes);
+        let lines = loc::count_lines(&source, &language);
+        let tokens = tokens::count_tokens(&source, &language);
+        let unsafe_code = analyses.unsafe_code.then(|| match language {
+            Language::Rust => unsafety::count_unsafe(&source),
+            Language::C | Language::Cpp | Language::Go | Language::Zig => UnsafeCounts::default(),
+        });
+        let complexity = analyses.complexity.then(|| match language {
+            Language::C => complexity::measure(&source),
+            Language::Rust | Language::Cpp | Language::Go | Language::Zig => Complexity::default(),
+        });
+        stats.lines += lines;
+        stats.tokens += tokens;
+        if let (Some(total), Some(counts)) = (&mut stats.unsafe_code, unsafe_code) {
+            *total += counts;
+        }
+        if let (Some(total), Some(counts)) = (&mut stats.complexity, complexity) {
+            *total += counts;
+        }
+        stats.files.push(FileStats {
+            path: path.strip_prefix(directory).unwrap_or(path).to_path_buf(),
+            lines,
+            tokens,
+            unsafe_code,
+            complexity,
+        });
+    }
+    Ok(stats)
+}
+
+/// Prints the line counts of every pair, followed by their totals.
+///
+/// Each Rust variant of a pair is compared with the C program on a row of
+/// its own, labelled `<pair>:<label>`, and left out of the totals.
+fn print_line_table(pairs: &[PairStats], detailed: bool) {
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>8}",
+        "Pair", "C code", "C comment", "C blank", "Rust code", "Rs comment", "Rs blank", "Ratio"
+    );
+
+    let mut c_total = LineCounts::default();
+    let mut rust_total = LineCounts::default();
+    for pair in pairs {
+        print_row(
+            &pair.program_name,
+            &pair.c_program.lines,
+            &pair.rust_program.lines,
+        );
+        if detailed {
+            print_files(&Language::C, &pair.c_program);
+            print_files(&Language::Rust, &pair.rust_program);
+        }
+        for (label, variant) in &pair.rust_variants {
+            let name = format!("{}:{label}", pair.program_name);
+            print_row(&name, &pair.c_program.lines, &variant.lines);
+            if detailed {
+                print_files(&Language::Rust, variant);
+            }
+        }
+        c_total += pair.c_program.lines;
+        rust_total += pair.rust_program.lines;
+    }
+    print_row("Total", &c_total, &rust_total);
+}
+
+/// Prints the approximate token counts of every pair, followed by their
+/// totals and the pairs that do not fit in `max_tokens`.
+fn print_token_table(pairs: &[PairStats], detailed: bool, max_tokens: Option<usize>) {
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>8}",
+        "Pair", "C tokens", "Rs tokens", "Total", "Ratio"
+    );
+
+    let mut c_total = 0;
+    let mut rust_total = 0;
+    for pair in pairs {
+        print_token_row(
+            &pair.program_name,
+            pair.c_program.tokens,
+            pair.rust_program.tokens,
+        );
+        if detailed {
+            for (language, program) in [
+                (Language::C, &pair.c_program),
+                (Language::Rust, &pair.rust_program),
+            ] {
+                for file in &program.files {
+                    let path = Path::new(&program.directory).join(&file.path);
+                    if language.is_source() {
+                        println!("  {:<22} {:>10}", path.display(), file.tokens);
+                    } else {
+                        println!("  {:<22} {:>10} {:>10}", path.display(), "", file.tokens);
+                    }
+                }
+            }
+        }
+        for (label, variant) in &pair.rust_variants {
+            let name = format!("{}:{label}", pair.program_name);
+            print_token_row(&name, pair.c_program.tokens, variant.tokens);
+        }
+        c_total += pair.c_program.tokens;
+        rust_total += pair.rust_program.tokens;
+    }
+    print_token_row("Total", c_total, rust_total);
+
+    if let Some(max_tokens) = max_tokens {
+        let oversized: Vec<&str> = pairs
+            .iter()
+            .filter(|pair| pair.c_program.tokens + pair.rust_program.tokens > max_tokens)
+            .map(|pair| pair.program_name.as_str())
+            .collect();
+        println!();
+        println!(
+            "{} of {} program pairs exceed {} tokens{}{}",
+            oversized.len(),
+            pairs.len(),
+            max_tokens,
+            if oversized.is_empty() { "" } else { ": " },
+            oversized.join(", ")
+        );
+    }
+}
+
+/// Prints the line or token counts summed over the pairs with each tag, with
+/// the number of pairs after the tag.
+///
+/// Tags are read from each pair's manifest, so pairs downloaded before they
+/// were tagged are counted as untagged.  A pair with several tags is counted
+/// under each of them.
+fn print_tag_table(pairs: &[PairStats], tokens: bool) {
+    let mut tags: BTreeMap<String, Vec<&PairStats>> = BTreeMap::new();
+    for pair in pairs {
+        let manifest = Manifest::load(&Path::new(PROGRAM_PAIRS_DIRECTORY).join(&pair.program_name));
+        match manifest.map(|manifest| manifest.tags) {
+            Some(pair_tags) if !pair_tags.is_empty() => {
+                for tag in pair_tags {
+                    tags.entry(tag).or_default().push(pair);
+                }
+            }
+            _ => tags.entry(UNTAGGED.to_string()).or_default().push(pair),
+        }
+    }
+
+    if tokens {
+        println!(
+            "{:<24} {:>10} {:>10} {:>10} {:>8}",
+            "Tag", "C tokens", "Rs tokens", "Total", "Ratio"
+        );
+    } else {
+        println!(
+            "{:<24} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>8}",
+            "Tag", "C code", "C comment", "C blank", "Rust code", "Rs comment", "Rs blank", "Ratio"
+        );
+    }
+    for (tag, pairs) in &tags {
+        let name = format!("{tag} ({})", pairs.len());
+        if tokens {
+            let c_tokens = pairs.iter().map(|pair| pair.c_program.tokens).sum();
+            let rust_tokens = pairs.iter().map(|pair| pair.rust_program.tokens).sum();
+            print_token_row(&name, c_tokens, rust_tokens);
+        } else {
+            let mut c_lines = LineCounts::default();
+            let mut rust_lines = LineCounts::default();
+            for pair in pairs {
+                c_lines += pair.c_program.lines;
+                rust_lines += pair.rust_program.lines;
+            }
+            print_row(&name, &c_lines, &rust_lines);
+        }
+    }
+}
+
+/// Prints the unsafe code counts of every pair's Rust program, followed by
+/// their totals and how many Rust files could not be parsed.
+fn print_unsafe_table(pairs: &[PairStats], detailed: bool) {
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>10} {:>12}",
+        "Pair", "unsafe {}", "unsafe fn", "Raw ptrs", "extern C", "Per 100 LOC"
+    );
+
+    let mut total = UnsafeCounts::default();
+    let mut total_code = 0;
+    for pair in pairs {
+        let program = &pair.rust_program;
+        let unsafe_code = program.unsafe_code.unwrap_or_default();
+        print_unsafe_row(&pair.program_name, &unsafe_code, program.lines.code);
+        if detailed {
+            for file in &program.files {
+                let path = Path::new(&program.directory).join(&file.path);
+                print_unsafe_row(
+                    &format!("  {}", path.display()),
+                    &file.unsafe_code.unwrap_or_default(),
+                    file.lines.code,
+                );
+            }
+        }
+        total += unsafe_code;
+        total_code += program.lines.code;
+    }
+    print_unsafe_row("Total", &total, total_code);
+
+    if total.unparsed_files > 0 {
+        println!();
+        println!(
+            "{} Rust files could not be parsed and were not counted",
+            total.unparsed_files
+        );
+    }
+}
+
+/// Prints one row of the unsafe code table, with the `unsafe` blocks and
+/// functions per 100 lines of code.
+fn print_unsafe_row(name: &str, counts: &UnsafeCounts, code_lines: usize) {
+    let density = if code_lines > 0 {
+        let unsafe_items = counts.unsafe_blocks + counts.unsafe_fns;
+        format!("{:.2}", unsafe_items as f64 * 100.0 / code_lines as f64)
+    } else {
+        "-".to_string()
+    };
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>10} {:>12}",
+        name,
+        counts.unsafe_blocks,
+        counts.unsafe_fns,
+        counts.raw_pointers,
+        counts.extern_c_items,
+        density
+    );
+}
+
+/// Prints the function complexity of every pair's C program, followed by
+/// the totals and how many pairs fall into each difficulty.
+fn print_complexity_table(pairs: &[PairStats], detailed: bool) {
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "Pair", "C funcs", "Avg lines", "Avg CC", "Max CC", "Difficulty"
+    );
+
+    let mut total = Complexity::default();
+    let mut difficulties: BTreeMap<Difficulty, usize> = BTreeMap::new();
+    for pair in pairs {
+        let program = &pair.c_program;
+        let complexity = program.complexity.unwrap_or_default();
+        print_complexity_row(&pair.program_name, &complexity);
+        if detailed {
+            for file in &program.files {
+                let path = Path::new(&program.directory).join(&file.path);
+                print_complexity_row(
+                    &format!("  {}", path.display()),
+                    &file.complexity.unwrap_or_default(),
+                );
+            }
+        }
+        total += complexity;
+        *difficulties.entry(complexity.difficulty()).or_default() += 1;
+    }
+    print_complexity_row("Total", &total);
+
+    println!();
+    let buckets: Vec<String> = difficulties
+        .iter()
+        .map(|(difficulty, count)| format!("{count} {difficulty}"))
+        .collect();
+    println!("Program pairs by difficulty: {}", buckets.join(", "));
+}
+
+/// Prints how much disk space the files of the downloaded pairs take up,
+/// and how much of it identical files waste or `--link store` saves.
+fn print_dedup(dedup: &DedupStats) {
+    println!("{:<24} {:>10} {:>12}", "Deduplication", "Files", "Size");
+    println!(
+        "{:<24} {:>10} {:>12}",
+        "All files",
+        dedup.files,
+        utils::format_size(dedup.bytes)
+    );
+    println!(
+        "{:<24} {:>10} {:>12}",
+        "Distinct contents",
+        dedup.unique_files,
+        utils::format_size(dedup.unique_bytes)
+    );
+    println!(
+        "{:<24} {:>10} {:>12}",
+        "On disk",
+        "",
+        utils::format_size(dedup.disk_bytes)
+    );
+
+    println!();
+    println!(
+        "Saved by links: {}",
+        utils::format_size(dedup.bytes - dedup.disk_bytes)
+    );
+    if dedup.disk_bytes > dedup.unique_bytes {
+        println!(
+            "Duplicates still take up {}; download with `--link store` to share them",
+            utils::format_size(dedup.disk_bytes - dedup.unique_bytes)
+        );
+    }
+}
+
+/// Prints one row of the complexity table.
+fn print_complexity_row(name: &str, complexity: &Complexity) {
+    println!(
+        "{:<24} {:>10} {:>10.1} {:>10.1} {:>10} {:>10}",
+        name,
+        complexity.functions,
+        complexity.average_length(),
+        complexity.average_complexity(),
+        complexity.max_complexity,
+        complexity.difficulty().to_string()
+    );
+}
+
+/// Prints one row of the token statistics table.
+fn print_token_row(name: &str, c_tokens: usize, rust_tokens: usize) {
+    let ratio = if c_tokens > 0 {
+        format!("{:.2}", rust_tokens as f64 / c_tokens as f64)
+    } else {
+        "-".to_string()
+    };
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>8}",
+        name,
+        c_tokens,
+        rust_tokens,
+        c_tokens + rust_tokens,
+        ratio
+    );
+}
+
+/// Prints one row of the statistics table.
+fn print_row(name: &str, c_lines: &LineCounts, rust_lines: &LineCounts) {
+    let ratio = if c_lines.code > 0 {
+        format!("{:.2}", rust_lines.code as f64 / c_lines.code as f64)
+    } else {
+        "-".to_string()
+    };
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>8}",
+        name,
+        c_lines.code,
+        c_lines.comment,
+        c_lines.blank,
+        rust_lines.code,
+        rust_lines.comment,
+        rust_lines.blank,
+        ratio
+    );
+}
+
+/// Prints the line counts of every file in a program, indented under its
+/// pair and aligned with the columns of the program's language.
+fn print_files(language: &Language, program: &ProgramStats) {
+    for file in &program.files {
+        let path = Path::new(&program.directory).join(&file.path);
+        let counts = format!(
+            "{:>10} {:>10} {:>10}",
+            file.lines.code, file.lines.comment, file.lines.blank
+        );
+        if language.is_source() {
+            println!("  {:<22} {}", path.display(), counts);
+        } else {
+            println!("  {:<22} {:>32} {}", path.display(), "", counts);
+        }
+    }
+}
+
+/// Prints the star counts and last commit dates of each pair's repositories.
+///
+/// Repositories are found through each pair's manifest, so pairs downloaded
+/// before manifests were written show `-`.
+fn print_repositories(pairs: &[PairStats], enriched: &EnrichedMetadata) {
+    println!(
+        "{:<24} {:>10} {:>10} {:>12} {:>12}",
+        "Pair", "C stars", "Rs stars", "C commit", "Rs commit"
+    );
+    for pair in pairs {
+        let manifest = Manifest::load(&Path::new(PROGRAM_PAIRS_DIRECTORY).join(&pair.program_name));
+        let info = |url: fn(&Manifest) -> &str| {
+            manifest
+                .as_ref()
+                .and_then(|manifest| enriched.get(url(manifest)))
+        };
+        let c_info = info(|manifest| &manifest.c_program.repository_url);
+        let rust_info = info(|manifest| &manifest.rust_program.repository_url);
+
+        let stars = |info: Option<&RepositoryInfo>| {
+            info.map_or("-".to_string(), |info| info.stars.to_string())
+        };
+        // Only the date part of the RFC 3339 timestamp is shown.
+        let date = |info: Option<&RepositoryInfo>| {
+            info.and_then(|info| info.last_commit_date.as_deref())
+                .map_or("-", |date| date.get(..10).unwrap_or(date))
+                .to_string()
+        };
+        println!(
+            "{:<24} {:>10} {:>10} {:>12} {:>12}",
+            pair.program_name,
+            stars(c_info),
+            stars(rust_info),
+            date(c_info),
+            date(rust_info)
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! # Line Counting
+//!
+//! This module counts lines of code, comment lines, and blank lines in C and
+//! Rust source files, similar to tools like `cloc`.
+
+use std::ops::AddAssign;
+
+use serde::Serialize;
+
+use crate::corpus::schema::Language;
+
+/// Line counts for one or more source files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineCounts {
+    /// Lines containing code, including lines that also contain a comment.
+    pub code: usize,
+    /// Lines containing only comments.
+    pub comment: usize,
+    /// Lines containing only whitespace.
+    pub blank: usize,
+}
+
+impl AddAssign for LineCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.code += other.code;
+        self.comment += other.comment;
+        self.blank += other.blank;
+    }
+}
+
+/// Counts the lines of code, comments, and blank lines in some source code.
+///
+/// A line counts as code if it contains anything other than whitespace and
+/// comments.  Comment markers inside string and character literals are
+/// ignored.  Block comments nest in Rust but not in C.
+///
+/// # Arguments
+///
+/// - `source` - The source code.
+/// - `language` - The language the source code is written in.
+///
+/// # Returns
+///
+/// The [`LineCounts`] of `source`.
+pub fn count_lines(source: &str, language: &Language) -> LineCounts {
+    let nested_comments = matches!(language, Language::Rust);
+    let mut counts = LineCounts::default();
+    let mut comment_depth = 0;
+    let mut in_string = false;
+
+    for line in source.lines() {
+        if line.trim().is_empty() && !in_string {
+            counts.blank += 1;
+            continue;
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let starts_with = |i: usize, pattern: &str| {
+            pattern
+                .chars()
+                .enumerate()
+                .all(|(offset, c)| chars.get(i + offset) == Some(&c))
+        };
+        let mut has_code = false;
+        let mut has_comment = false;
+        let mut i = 0;
+
+        while i < chars.len() {
+            if comment_depth > 0 {
+                has_comment = true;
+                if starts_with(i, "*/") {
+                    comment_depth -= 1;
+                    i += 2;
+                } else if nested_comments && starts_with(i, "/*") {
+                    comment_depth += 1;
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            } else if in_string {
+                has_code = true;
+                match chars[i] {
+                    '\\' => i += 2,
+                    '"' => {
+                        in_string = false;
+                        i += 1;
+                    }
+                    _ => i += 1,
+                }
+            } else if chars[i].is_whitespace() {
+                i += 1;
+            } else if starts_with(i, "//") {
+                has_comment = true;
+                break;
+            } else if starts_with(i, "/*") {
+                has_comment = true;
+                comment_depth = 1;
+                i += 2;
+            } else if chars[i] == '"' {
+                has_code = true;
+                in_string = true;
+                i += 1;
+            } else if chars[i] == '\'' {
+                // Skip character literals so that quotes inside them are not
+                // mistaken for strings.  A lone quote is a Rust lifetime.
+                has_code = true;
+                if chars.get(i + 1) == Some(&'\\') {
+                    i = chars
+                        .get(i + 3..)
+                        .and_then(|rest| rest.iter().position(|&c| c == '\''))
+                        .map_or(chars.len(), |end| i + 4 + end);
+                } else if chars.get(i + 2) == Some(&'\'') {
+                    i += 3;
+                } else {
+                    i += 1;
+                }
+            } else {
+                has_code = true;
+                i += 1;
+            }
+        }
+
+        // C string literals only continue onto the next line when escaped.
+        if in_string && !nested_comments && !line.ends_with('\\') {
+            in_string = false;
+        }
+
+        if has_code {
+            counts.code += 1;
+        } else if has_comment {
+            counts.comment += 1;
+        } else {
+            counts.blank += 1;
+        }
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that code, comment, and blank lines are told apart in C.
+    #[test]
+    fn test_count_lines_c() {
+        let source = r#"/* A block
+ * comment. */
+
+#include <stdio.h> // trailing comment
+
+int main(void) {
+    puts("/* not a comment */");
+    return 0;
+}
+"#;
+        assert_eq!(
+            LineCounts {
+                code: 5,
+                comment: 2,
+                blank: 2
+            },
+            count_lines(source, &Language::C)
+        );
+    }
+
+    /// Tests that nested block comments and lifetimes are handled in Rust.
+    #[test]
+    fn test_count_lines_rust() {
+        let source = r#"/* outer /* inner */
+still a comment */
+/// Doc comment.
+fn first<'a>(s: &'a str) -> char {
+    let quote = '"';
+    s.chars().next().unwrap_or(quote)
+}
+"#;
+        assert_eq!(
+            LineCounts {
+                code: 4,
+                comment: 3,
+                blank: 0
+            },
+            count_lines(source, &Language::Rust)
+        );
+    }
+}
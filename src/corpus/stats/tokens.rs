@@ -0,0 +1,166 @@
+//! # Token Counting
+//!
+//! This module approximates how many tokens source files take up in a
+//! language model's context window.  It splits source code the way a
+//! lexer would rather than with a model's subword tokenizer, so counts are
+//! comparable across models but only roughly match any one of them.
+
+use crate::corpus::schema::Language;
+
+/// Operators of three characters that C lexes as one token.
+const C_OPERATORS_3: [&str; 3] = ["<<=", ">>=", "..."];
+
+/// Operators of two characters that C lexes as one token, along with comment
+/// markers.
+const C_OPERATORS_2: [&str; 23] = [
+    "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=", "%=",
+    "&=", "^=", "|=", "##", "//", "/*", "*/",
+];
+
+/// Operators of three characters that C++ lexes as one token.
+const CPP_OPERATORS_3: [&str; 5] = ["<<=", ">>=", "...", "->*", "<=>"];
+
+/// Operators of two characters that C++ lexes as one token, along with
+/// comment markers.
+const CPP_OPERATORS_2: [&str; 25] = [
+    "::", "->", ".*", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=",
+    "/=", "%=", "&=", "^=", "|=", "##", "//", "/*", "*/",
+];
+
+/// Operators of three characters that Rust lexes as one token.
+const RUST_OPERATORS_3: [&str; 4] = ["<<=", ">>=", "...", "..="];
+
+/// Operators of two characters that Rust lexes as one token, along with
+/// comment markers.
+const RUST_OPERATORS_2: [&str; 23] = [
+    "::", "->", "=>", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=", "%=",
+    "&=", "^=", "|=", "..", "//", "/*", "*/",
+];
+
+/// Operators of three characters that Go lexes as one token.
+const GO_OPERATORS_3: [&str; 4] = ["<<=", ">>=", "...", "&^="];
+
+/// Operators of two characters that Go lexes as one token, along with
+/// comment markers.
+const GO_OPERATORS_2: [&str; 24] = [
+    ":=", "<-", "&^", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=",
+    "/=", "%=", "&=", "^=", "|=", "//", "/*", "*/",
+];
+
+/// Operators of three characters that Zig lexes as one token.
+const ZIG_OPERATORS_3: [&str; 10] = [
+    "<<=", ">>=", "...", "<<|", "+%=", "-%=", "*%=", "+|=", "-|=", "*|=",
+];
+
+/// Operators of two characters that Zig lexes as one token, along with the
+/// comment marker.
+const ZIG_OPERATORS_2: [&str; 28] = [
+    "+%", "-%", "*%", "+|", "-|", "*|", "++", "**", "||", "=>", "..", ".*", ".?", "<<", ">>", "<=",
+    ">=", "==", "!=", "+=", "-=", "*=", "/=", "%=", "&=", "^=", "|=", "//",
+];
+
+/// Counts the approximate number of tokens in some source code.
+///
+/// Every identifier, keyword, number, operator, and punctuation mark counts
+/// as one token.  Comments and string literals are counted the same way, one
+/// token per word or punctuation mark, since they take up context too.
+///
+/// # Arguments
+///
+/// - `source` - The source code.
+/// - `language` - The language the source code is written in.
+///
+/// # Returns
+///
+/// The number of tokens in `source`.
+pub fn count_tokens(source: &str, language: &Language) -> usize {
+    let (operators_3, operators_2): (&[&str], &[&str]) = match language {
+        Language::C => (&C_OPERATORS_3, &C_OPERATORS_2),
+        Language::Cpp => (&CPP_OPERATORS_3, &CPP_OPERATORS_2),
+        Language::Rust => (&RUST_OPERATORS_3, &RUST_OPERATORS_2),
+        Language::Go => (&GO_OPERATORS_3, &GO_OPERATORS_2),
+        Language::Zig => (&ZIG_OPERATORS_3, &ZIG_OPERATORS_2),
+    };
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut tokens = 0;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let length = if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        } else if c.is_ascii_digit() {
+            // Numbers keep their decimal point, as in `0.5`, but not a range
+            // operator, as in `0..2`.
+            let mut end = 0;
+            let mut chars = rest.char_indices().peekable();
+            while let Some((i, c)) = chars.next() {
+                let decimal_point =
+                    c == '.' && chars.peek().is_some_and(|(_, next)| next.is_ascii_digit());
+                if !is_word(c) && !decimal_point {
+                    break;
+                }
+                end = i + c.len_utf8();
+            }
+            end
+        } else if is_word(c) {
+            rest.find(|c| !is_word(c)).unwrap_or(rest.len())
+        } else if operators_3
+            .iter()
+            .any(|operator| rest.starts_with(operator))
+        {
+            3
+        } else if operators_2
+            .iter()
+            .any(|operator| rest.starts_with(operator))
+        {
+            2
+        } else {
+            c.len_utf8()
+        };
+        tokens += 1;
+        rest = &rest[length..];
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that C operators, numbers, and comments are counted.
+    #[test]
+    fn test_count_tokens_c() {
+        let source = "int main(void) {\n    a->b <<= 1;\n    x = 0.5; // half of it\n}\n";
+        // int main ( void ) { a -> b <<= 1 ; x = 0.5 ; // half of it }
+        assert_eq!(21, count_tokens(source, &Language::C));
+    }
+
+    /// Tests that Rust paths, ranges, and string literals are counted.
+    #[test]
+    fn test_count_tokens_rust() {
+        let source = "let x: Vec<u8> = std::iter::once(1..=2);\nprintln!(\"héllo, world\");";
+        // let x : Vec < u8 > = std :: iter :: once ( 1 ..= 2 ) ;
+        // println ! ( " héllo , world " ) ;
+        assert_eq!(29, count_tokens(source, &Language::Rust));
+    }
+
+    /// Tests that the operators of C++, Go, and Zig are each counted as one
+    /// token, where C would split them.
+    #[test]
+    fn test_count_tokens_other_languages() {
+        // std :: vector < int > v ; a <=> b ;
+        assert_eq!(
+            12,
+            count_tokens("std::vector<int> v; a <=> b;", &Language::Cpp)
+        );
+        assert_eq!(
+            14,
+            count_tokens("std::vector<int> v; a <=> b;", &Language::C)
+        );
+        // x := <- ch &^ mask
+        assert_eq!(6, count_tokens("x := <-ch &^ mask", &Language::Go));
+        // a +%= b .? ;
+        assert_eq!(5, count_tokens("a +%= b.?;", &Language::Zig));
+    }
+}
@@ -0,0 +1,155 @@
+//! # Content-Addressed Store
+//!
+//! This module keeps one copy of each distinct file content downloaded with
+//! `--link store` in `store/objects/<sha256>`, and the files in
+//! `program_pairs/` are hard links to it.  Files that appear in several
+//! pairs, such as shared gnulib modules or vendored headers, then take up
+//! disk space once.  It also measures how much space identical files take
+//! up, for `stats --dedup`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use rayon::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    corpus::utils,
+    paths::{SPLITS_DIRECTORY, STAGING_DIRECTORY},
+};
+
+/// Numbers the temporary files objects are written to, so that threads
+/// storing the same content at once do not write to the same file.
+static NEXT_TEMPORARY: AtomicU64 = AtomicU64::new(0);
+
+/// Disk usage of the files of downloaded program pairs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DedupStats {
+    /// Number of files.
+    pub files: usize,
+    /// Total size of the files, counting each one in full.
+    pub bytes: u64,
+    /// Number of distinct file contents.
+    pub unique_files: usize,
+    /// Total size of the distinct contents, which is all the files would
+    /// take up if every duplicate were a link.
+    pub unique_bytes: u64,
+    /// Disk space the files take up, counting files that are hard links to
+    /// the same data once.
+    pub disk_bytes: u64,
+}
+
+/// Hard links a file to its object in the store, storing its content first
+/// if no file with the same content is stored yet.
+///
+/// # Arguments
+///
+/// - `objects` - The store's object directory, under the corpus root.
+/// - `source` - The file to store.
+/// - `destination` - Where to link it; must not exist.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`io::Error`] if the file cannot be
+/// stored or linked, such as across filesystems.
+pub fn link(objects: &Path, source: &Path, destination: &Path) -> io::Result<()> {
+    let object = objects.join(hash_file(source)?);
+    if !object.exists() {
+        fs::create_dir_all(objects)?;
+        // The content is renamed into place once written, so the object is
+        // never seen half-written.
+        let temporary = object.with_extension(format!(
+            "partial-{}-{}",
+            std::process::id(),
+            NEXT_TEMPORARY.fetch_add(1, Ordering::Relaxed)
+        ));
+        let stored =
+            utils::copy_file(source, &temporary).and_then(|_| fs::rename(&temporary, &object));
+        if stored.is_err() {
+            let _ = fs::remove_file(&temporary);
+            // Another thread may have stored the same content first.
+            if !object.exists() {
+                stored?;
+            }
+        }
+    }
+    fs::hard_link(&object, destination)
+}
+
+/// Measures how much disk space the files of the downloaded program pairs
+/// take up, and how much they would without duplicates.
+///
+/// Symbolic links, such as those made by `--link symbolic`, take up no space
+/// and are not counted, nor are the materialized splits or the pairs being
+/// downloaded.
+///
+/// # Arguments
+///
+/// - `directory` - The directory of the downloaded pairs, typically
+///   `program_pairs/`.
+///
+/// # Returns
+///
+/// The [`DedupStats`] of the files, or the path of a file that cannot be
+/// read with the [`io::Error`] reading it.
+pub fn dedup_stats(directory: &Path) -> Result<DedupStats, (PathBuf, io::Error)> {
+    let files: Vec<PathBuf> = utils::list_files(directory)
+        .into_iter()
+        .filter(|path| !path.starts_with(SPLITS_DIRECTORY) && !path.starts_with(STAGING_DIRECTORY))
+        .collect();
+    let hashed = files
+        .par_iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).map_err(|error| (path.clone(), error))?;
+            let hash = hash_file(path).map_err(|error| (path.clone(), error))?;
+            Ok((hash, metadata.len(), file_id(&metadata)))
+        })
+        .collect::<Result<Vec<_>, (PathBuf, io::Error)>>()?;
+
+    let mut stats = DedupStats::default();
+    let mut contents: HashMap<&str, u64> = HashMap::new();
+    let mut ids = HashSet::new();
+    for (hash, bytes, id) in &hashed {
+        stats.files += 1;
+        stats.bytes += bytes;
+        contents.insert(hash, *bytes);
+        if id.is_none_or(|id| ids.insert(id)) {
+            stats.disk_bytes += bytes;
+        }
+    }
+    stats.unique_files = contents.len();
+    stats.unique_bytes = contents.values().sum();
+    Ok(stats)
+}
+
+/// Computes the SHA-256 of a file's content, as lowercase hex.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Identifies the data a file's metadata refers to, which hard links to the
+/// same data share.
+///
+/// # Returns
+///
+/// The device and inode of the file, or `None` on platforms without them.
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
@@ -0,0 +1,188 @@
+//! # Download Timings
+//!
+//! This module records how long each repository took to clone, and how much
+//! space its clone takes up, in `repository_clones/download-timings.json`,
+//! so that later downloads can estimate how long they will take before they
+//! start.  A repository that was cloned before is expected to take as long
+//! as it did then, and one that never was is estimated from the size that
+//! `enrich` recorded for it, at the average rate of the recorded clones.
+//! Repositories that are cached are not cloned again, so they are left out.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{
+        cache::{self, CacheLock},
+        enrich::EnrichedMetadata,
+        errors::CacheError,
+        schema::{ProgramPair, SourceType},
+        skiplist::SkipList,
+        space, utils,
+    },
+    paths::TIMINGS_FILE,
+};
+
+/// The recorded clones, stored in `repository_clones/download-timings.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Timings {
+    /// The latest clone of each repository, keyed by normalized URL.
+    repositories: BTreeMap<String, CloneTiming>,
+}
+
+/// How long a repository took to clone and how large its clone is.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct CloneTiming {
+    /// Seconds the clone took.
+    seconds: f64,
+    /// Size of the clone on disk in bytes.
+    bytes: u64,
+}
+
+/// How long the program pairs of a download are expected to take.
+#[derive(Debug, Default)]
+pub struct Estimates {
+    /// The expected duration of each pair that clones a repository, by
+    /// program name.
+    pub pairs: HashMap<String, Duration>,
+    /// The expected duration of the whole download.
+    pub total: Duration,
+    /// Number of repositories to clone that could not be estimated, because
+    /// they were never cloned and have no size recorded by `enrich`.
+    pub unknown: usize,
+}
+
+/// Records how long a repository took to clone.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `repository_url` - The URL the repository was cloned from.
+/// - `duration` - How long the clone took.
+/// - `bytes` - Size of the clone on disk.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`CacheError`] on failure.
+pub fn record_clone(
+    root: &Path,
+    repository_url: &str,
+    duration: Duration,
+    bytes: u64,
+) -> Result<(), CacheError> {
+    let path = &root.join(TIMINGS_FILE);
+    let _lock = CacheLock::acquire(path, |_| {})?;
+    let mut timings = load(root)?;
+    timings.repositories.insert(
+        utils::normalize_repository_url(repository_url),
+        CloneTiming {
+            seconds: duration.as_secs_f64(),
+            bytes,
+        },
+    );
+    let contents =
+        serde_json::to_string_pretty(&timings).map_err(|error| CacheError::Serialize { error })?;
+    fs::write(path, contents).map_err(|error| CacheError::IoWrite {
+        path: PathBuf::from(path),
+        error,
+    })
+}
+
+/// Estimates how long downloading some program pairs will take, in the
+/// order they are downloaded.
+///
+/// A repository shared by several pairs is counted for the first of them,
+/// which clones it.  Pairs in the skip list, and tarballs and files, are
+/// left out.
+///
+/// # Arguments
+///
+/// - `root` - The corpus root, or an empty path for the current directory.
+/// - `pairs` - The program pairs about to be downloaded.
+/// - `skiplist` - The program pairs that will be skipped.
+///
+/// # Returns
+///
+/// The [`Estimates`], which are empty if nothing was ever timed.
+pub fn estimate<'a>(
+    root: &Path,
+    pairs: impl IntoIterator<Item = &'a ProgramPair>,
+    skiplist: &SkipList,
+) -> Estimates {
+    // Estimates only inform the user, so unreadable files leave them out.
+    let timings = load(root).unwrap_or_default();
+    let enriched = EnrichedMetadata::load(root).unwrap_or_default();
+    let (seconds, bytes) = timings
+        .repositories
+        .values()
+        .fold((0.0, 0u64), |(seconds, bytes), timing| {
+            (seconds + timing.seconds, bytes.saturating_add(timing.bytes))
+        });
+    let bytes_per_second = (seconds > 0.0).then(|| bytes as f64 / seconds);
+
+    let mut estimates = Estimates::default();
+    let mut seen = BTreeSet::new();
+    for pair in pairs {
+        if skiplist.get(&pair.program_name).is_some() {
+            continue;
+        }
+        let mut pair_seconds = None;
+        for program in pair.programs() {
+            let repository_url = program.repository_url.as_str();
+            let url = utils::normalize_repository_url(repository_url);
+            if program.source_type != SourceType::Git
+                || cache::clone_directory(root, repository_url).exists()
+                || !seen.insert(url.clone())
+            {
+                continue;
+            }
+            let seconds = timings
+                .repositories
+                .get(&url)
+                .map(|timing| timing.seconds)
+                .or_else(|| {
+                    let size = enriched
+                        .get(repository_url)?
+                        .size?
+                        .saturating_mul(space::CLONE_SIZE_FACTOR);
+                    Some(size as f64 / bytes_per_second?)
+                });
+            match seconds {
+                Some(seconds) => *pair_seconds.get_or_insert(0.0) += seconds,
+                None => estimates.unknown += 1,
+            }
+        }
+        if let Some(seconds) = pair_seconds {
+            // Recorded timings are read from disk, so one that is negative or
+            // too long for a `Duration` is clamped rather than panicking.
+            let duration = Duration::try_from_secs_f64(seconds).unwrap_or(if seconds > 0.0 {
+                Duration::MAX
+            } else {
+                Duration::ZERO
+            });
+            estimates.total = estimates.total.saturating_add(duration);
+            estimates.pairs.insert(pair.program_name.clone(), duration);
+        }
+    }
+    estimates
+}
+
+/// Loads the clones recorded under a corpus root, or none if nothing was
+/// recorded yet.
+fn load(root: &Path) -> Result<Timings, CacheError> {
+    let path = &root.join(TIMINGS_FILE);
+    if !path.exists() {
+        return Ok(Timings::default());
+    }
+    let contents = fs::read_to_string(path).map_err(|error| CacheError::IoRead {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    serde_json::from_str(&contents).map_err(|error| CacheError::Serialize { error })
+}
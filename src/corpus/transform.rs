@@ -0,0 +1,192 @@
+//! # Source Transforms
+//!
+//! This module rewrites the source files of downloaded programs as they are
+//! placed, such as to strip comments or normalize whitespace, so that
+//! preprocessing for downstream tools happens once, inside the corpus, and is
+//! recorded in each file's provenance.  Each transform is a
+//! [`SourceTransform`] pass; the passes requested with `--transform` run in
+//! the order given.  Only C and Rust sources are transformed, and files that
+//! are not valid UTF-8 are left as they are.
+
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::corpus::{functions, schema::Language};
+
+/// Number of columns between tab stops when tabs are expanded.
+const TAB_WIDTH: usize = 8;
+
+/// A pass that rewrites the contents of a source file.
+pub trait SourceTransform {
+    /// Rewrites a source file.
+    ///
+    /// # Arguments
+    ///
+    /// - `source` - The contents of the file.
+    /// - `language` - The language of the file.
+    ///
+    /// # Returns
+    ///
+    /// The rewritten contents.
+    fn apply(&self, source: &str, language: &Language) -> String;
+}
+
+/// The built-in transforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transform {
+    /// Remove comments, keeping their line breaks.
+    StripComments,
+    /// Convert `\r\n` and `\r` line endings to `\n`.
+    NormalizeLineEndings,
+    /// Replace tabs with spaces up to the next tab stop, every 8 columns.
+    ExpandTabs,
+    /// Remove spaces and tabs at the end of lines.
+    TrimTrailingWhitespace,
+}
+
+impl Transform {
+    /// The pass implementing the transform.
+    pub fn pass(self) -> &'static dyn SourceTransform {
+        match self {
+            Transform::StripComments => &StripComments,
+            Transform::NormalizeLineEndings => &NormalizeLineEndings,
+            Transform::ExpandTabs => &ExpandTabs,
+            Transform::TrimTrailingWhitespace => &TrimTrailingWhitespace,
+        }
+    }
+}
+
+/// Removes comments; see [`functions::strip_comments`].
+struct StripComments;
+
+impl SourceTransform for StripComments {
+    fn apply(&self, source: &str, language: &Language) -> String {
+        functions::strip_comments(source, language)
+    }
+}
+
+/// Converts every line ending to `\n`.
+struct NormalizeLineEndings;
+
+impl SourceTransform for NormalizeLineEndings {
+    fn apply(&self, source: &str, _language: &Language) -> String {
+        source.replace("\r\n", "\n").replace('\r', "\n")
+    }
+}
+
+/// Replaces tabs with spaces, keeping the columns text is aligned to.
+struct ExpandTabs;
+
+impl SourceTransform for ExpandTabs {
+    fn apply(&self, source: &str, _language: &Language) -> String {
+        let mut expanded = String::with_capacity(source.len());
+        let mut column = 0;
+        for c in source.chars() {
+            match c {
+                '\t' => {
+                    let spaces = TAB_WIDTH - column % TAB_WIDTH;
+                    expanded.extend(std::iter::repeat_n(' ', spaces));
+                    column += spaces;
+                }
+                '\n' | '\r' => {
+                    expanded.push(c);
+                    column = 0;
+                }
+                _ => {
+                    expanded.push(c);
+                    column += 1;
+                }
+            }
+        }
+        expanded
+    }
+}
+
+/// Removes the whitespace at the end of every line.
+struct TrimTrailingWhitespace;
+
+impl SourceTransform for TrimTrailingWhitespace {
+    fn apply(&self, source: &str, _language: &Language) -> String {
+        source
+            .split_inclusive('\n')
+            .map(|line| {
+                let content = line.trim_end_matches(['\n', '\r']);
+                let ending = &line[content.len()..];
+                format!("{}{ending}", content.trim_end_matches([' ', '\t']))
+            })
+            .collect()
+    }
+}
+
+/// Runs transforms over a placed file, replacing it with a copy if they
+/// change it, so that a linked file's target is left untouched.
+///
+/// # Arguments
+///
+/// - `path` - The placed file.
+/// - `transforms` - The transforms to run, in order.
+///
+/// # Returns
+///
+/// Whether the file was changed, or the I/O error of reading or writing it.
+/// Files that are not C or Rust sources or not valid UTF-8 are not changed.
+pub fn transform_file(path: &Path, transforms: &[Transform]) -> io::Result<bool> {
+    let Some(language) = Language::from_path(path) else {
+        return Ok(false);
+    };
+    let Ok(source) = String::from_utf8(fs::read(path)?) else {
+        return Ok(false);
+    };
+    let transformed = transforms
+        .iter()
+        .fold(source.clone(), |contents, transform| {
+            transform.pass().apply(&contents, &language)
+        });
+    if transformed == source {
+        return Ok(false);
+    }
+    fs::remove_file(path)?;
+    fs::write(path, transformed)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests each pass on its own and the passes in order.
+    #[test]
+    fn test_transforms() {
+        let source = "int main(void) {\t// entry\r\n\treturn '/'; /* \"//\" */ \r\n}\r\n";
+        let run = |transforms: &[Transform]| {
+            transforms
+                .iter()
+                .fold(source.to_string(), |contents, transform| {
+                    transform.pass().apply(&contents, &Language::C)
+                })
+        };
+
+        assert_eq!(
+            "int main(void) {\t// entry\n\treturn '/'; /* \"//\" */ \n}\n",
+            run(&[Transform::NormalizeLineEndings])
+        );
+        assert_eq!(
+            "int main(void) {        // entry\r\n        return '/'; /* \"//\" */ \r\n}\r\n",
+            run(&[Transform::ExpandTabs])
+        );
+        assert_eq!(
+            "int main(void) {\r\n\treturn '/';   \r\n}\r\n",
+            run(&[Transform::StripComments])
+        );
+        assert_eq!(
+            "int main(void) {\n\treturn '/';\n}\n",
+            run(&[
+                Transform::StripComments,
+                Transform::NormalizeLineEndings,
+                Transform::TrimTrailingWhitespace,
+            ])
+        );
+    }
+}
@@ -0,0 +1,558 @@
+//! # Interactive Browser
+//!
+//! This module implements the `tui` command, a terminal interface listing
+//! every program pair in the metadata with its download state.  Pairs can be
+//! selected and downloaded without leaving the interface, which shows the
+//! download's progress as it runs in the background.
+
+use std::{
+    path::Path,
+    sync::{
+        Arc,
+        mpsc::{self, Receiver},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Cell, Gauge, Paragraph, Row, Table, TableState},
+};
+
+use crate::{
+    corpus::{
+        downloader::{self, DownloadOptions},
+        errors::{DownloaderError, TuiError},
+        manifest::Manifest,
+        parser,
+        progress::{DownloadEvent, DownloadProgress},
+        schema::ProgramPair,
+        skiplist::SkipList,
+    },
+    paths::{INDIVIDUAL_METADATA_DIRECTORY, PROGRAM_PAIRS_DIRECTORY, PROJECT_METADATA_DIRECTORY},
+};
+
+/// How long to wait for a key press before checking the download again.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of download messages kept for display.
+const MAX_LOG_LINES: usize = 100;
+
+/// Whether a program pair has been downloaded into `program_pairs/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PairState {
+    /// The pair has a manifest, so both programs were downloaded.
+    Downloaded,
+    /// The pair's directory exists without a manifest, so its last download
+    /// failed.
+    Partial,
+    /// The pair has not been downloaded.
+    Missing,
+    /// The pair is in the skip list, for the given reason.
+    Skipped(String),
+}
+
+impl PairState {
+    /// Reads the download state of a program pair.
+    fn of(program_name: &str, skiplist: &SkipList) -> Self {
+        if let Some(skip) = skiplist.get(program_name) {
+            return PairState::Skipped(skip.to_string());
+        }
+        let directory = Path::new(PROGRAM_PAIRS_DIRECTORY).join(program_name);
+        if Manifest::load(&directory).is_some() {
+            PairState::Downloaded
+        } else if directory.exists() {
+            PairState::Partial
+        } else {
+            PairState::Missing
+        }
+    }
+
+    /// The state as shown in the table, with its color.
+    fn cell(&self) -> Cell<'static> {
+        let (text, color) = match self {
+            PairState::Downloaded => ("downloaded".to_string(), Color::Green),
+            PairState::Partial => ("failed".to_string(), Color::Red),
+            PairState::Missing => ("-".to_string(), Color::Reset),
+            PairState::Skipped(reason) => (format!("skipped ({reason})"), Color::Yellow),
+        };
+        Cell::from(text).style(Style::new().fg(color))
+    }
+}
+
+/// A program pair listed in the interface.
+struct Entry {
+    /// The pair's metadata.
+    pair: ProgramPair,
+    /// Whether the pair has been downloaded.
+    state: PairState,
+    /// Whether the pair is selected for download.
+    selected: bool,
+}
+
+/// A download running in the background.
+struct Download {
+    /// Receives the download's progress.
+    events: Receiver<DownloadEvent>,
+    /// The download's progress, through which it is cancelled.
+    progress: Arc<DownloadProgress>,
+    /// The thread running the download.
+    handle: JoinHandle<Result<(), DownloaderError>>,
+    /// Number of pairs processed so far.
+    finished_pairs: u64,
+    /// Number of pairs being downloaded.
+    total_pairs: u64,
+    /// The pair being downloaded.
+    current_pair: Option<String>,
+    /// The position, length, and message of the clone in progress.
+    clone: Option<(u64, u64, String)>,
+}
+
+/// The state of the interface.
+struct App {
+    /// Every program pair in the metadata, sorted by name.
+    entries: Vec<Entry>,
+    /// The highlighted row of the table.
+    table: TableState,
+    /// The download in progress, if any.
+    download: Option<Download>,
+    /// Messages from downloads, oldest first.
+    log: Vec<String>,
+    /// Options for downloads started from the interface.
+    options: DownloadOptions,
+    /// Whether the user asked to quit.  The interface only closes once the
+    /// download in progress, if any, has stopped.
+    quit: bool,
+}
+
+/// Runs the interactive browser until the user quits.
+///
+/// # Arguments
+///
+/// - `options` - Options for the downloads started from the browser.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`TuiError`] on failure.
+pub fn tui(options: &DownloadOptions) -> Result<(), TuiError> {
+    let skiplist = SkipList::load().map_err(|error| TuiError::SkipList { error })?;
+    let mut pairs = Vec::new();
+    for directory in [PROJECT_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY] {
+        let metadata_files =
+            parser::parse_directory(Path::new(directory)).map_err(|error| TuiError::Parse {
+                path: directory.into(),
+                error,
+            })?;
+        for (path, metadata) in metadata_files {
+            match metadata {
+                Ok(metadata) => pairs.extend(
+                    metadata
+                        .pairs
+                        .into_iter()
+                        .filter(|pair| pair.has_any_tag(&options.tags)),
+                ),
+                Err(error) => eprintln!("Failed to parse '{}': {}", path.display(), error),
+            }
+        }
+    }
+    pairs.sort_by(|a, b| a.program_name.cmp(&b.program_name));
+
+    let mut app = App {
+        entries: pairs
+            .into_iter()
+            .map(|pair| Entry {
+                state: PairState::of(&pair.program_name, &skiplist),
+                pair,
+                selected: false,
+            })
+            .collect(),
+        table: TableState::default().with_selected(Some(0)),
+        download: None,
+        log: Vec::new(),
+        options: options.clone(),
+        quit: false,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut app);
+    ratatui::restore();
+    result.map_err(|error| TuiError::Terminal { error })
+}
+
+/// Draws the interface and handles key presses until the user quits.
+fn run(terminal: &mut DefaultTerminal, app: &mut App) -> std::io::Result<()> {
+    while !app.quit || app.download.is_some() {
+        app.poll_download();
+        terminal.draw(|frame| draw(frame, app))?;
+        if event::poll(POLL_INTERVAL)?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            app.handle_key(key.code);
+        }
+    }
+    Ok(())
+}
+
+impl App {
+    /// Handles a key press.  Keys are ignored once the user asked to quit.
+    fn handle_key(&mut self, key: KeyCode) {
+        if self.quit {
+            return;
+        }
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.request_quit(),
+            KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+            KeyCode::Home | KeyCode::Char('g') => self.table.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.table.select_last(),
+            KeyCode::Char(' ') => {
+                if let Some(entry) = self.current_entry() {
+                    entry.selected = !entry.selected;
+                }
+                self.table.select_next();
+            }
+            KeyCode::Char('a') => {
+                let select = self.entries.iter().any(|entry| !entry.selected);
+                for entry in &mut self.entries {
+                    entry.selected = select;
+                }
+            }
+            KeyCode::Enter => self.start_download(),
+            _ => {}
+        }
+    }
+
+    /// Quits, cancelling the download in progress so that the interface
+    /// closes once its current pair ends.
+    fn request_quit(&mut self) {
+        self.quit = true;
+        if let Some(download) = &self.download {
+            download.progress.cancel();
+            self.push_log("Quitting once the pair in progress is downloaded".to_string());
+        }
+    }
+
+    /// The entry in the highlighted row.
+    fn current_entry(&mut self) -> Option<&mut Entry> {
+        let index = self.table.selected()?;
+        self.entries.get_mut(index)
+    }
+
+    /// Starts downloading the selected pairs, or the highlighted pair if none
+    /// are selected, unless a download is already running.
+    fn start_download(&mut self) {
+        if self.download.is_some() {
+            self.push_log("A download is already running".to_string());
+            return;
+        }
+
+        let mut pairs: Vec<ProgramPair> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.selected)
+            .map(|entry| entry.pair.clone())
+            .collect();
+        if pairs.is_empty()
+            && let Some(entry) = self.current_entry()
+        {
+            pairs.push(entry.pair.clone());
+        }
+        if pairs.is_empty() {
+            return;
+        }
+
+        let total_pairs = pairs.len() as u64;
+        let (sender, events) = mpsc::channel();
+        let progress = match DownloadProgress::with_events(total_pairs, sender) {
+            Ok(progress) => Arc::new(progress),
+            Err(error) => {
+                self.push_log(error.to_string());
+                return;
+            }
+        };
+        let options = self.options.clone();
+        let download_progress = Arc::clone(&progress);
+        let handle = thread::spawn(move || {
+            downloader::download_pairs_with_progress(&pairs, &options, &download_progress)
+        });
+        self.download = Some(Download {
+            events,
+            progress,
+            handle,
+            finished_pairs: 0,
+            total_pairs,
+            current_pair: None,
+            clone: None,
+        });
+    }
+
+    /// Applies the progress of the running download, and once it finishes,
+    /// reports its outcome and refreshes the pairs' download states.
+    fn poll_download(&mut self) {
+        let Some(download) = &mut self.download else {
+            return;
+        };
+        let mut messages = Vec::new();
+        for event in download.events.try_iter() {
+            match event {
+                DownloadEvent::PairStarted { program_name } => {
+                    download.current_pair = Some(program_name)
+                }
+                DownloadEvent::PairFinished { .. }
+                | DownloadEvent::PairUpToDate { .. }
+                | DownloadEvent::PairSkipped { .. }
+                | DownloadEvent::PairFailed { .. } => download.finished_pairs += 1,
+                DownloadEvent::Message(message) => messages.push(message),
+                DownloadEvent::CloneProgress {
+                    position,
+                    length,
+                    message,
+                    ..
+                } => download.clone = Some((position, length, message)),
+                DownloadEvent::CloneFinished { .. } => download.clone = None,
+                DownloadEvent::FileCopied { .. } => {}
+            }
+        }
+        for message in messages {
+            self.push_log(message);
+        }
+
+        let finished = self
+            .download
+            .as_ref()
+            .is_some_and(|download| download.handle.is_finished());
+        if !finished {
+            return;
+        }
+        let Some(download) = self.download.take() else {
+            return;
+        };
+        // Events sent just before the thread finished are still queued.
+        for event in download.events.try_iter() {
+            if let DownloadEvent::Message(message) = event {
+                self.push_log(message);
+            }
+        }
+        let outcome = match download.handle.join() {
+            Ok(Ok(())) => "Downloaded all program pairs!".to_string(),
+            Ok(Err(error)) => error.to_string(),
+            Err(_) => "The download stopped unexpectedly".to_string(),
+        };
+        self.push_log(outcome);
+
+        let skiplist = SkipList::load().unwrap_or_default();
+        for entry in &mut self.entries {
+            entry.state = PairState::of(&entry.pair.program_name, &skiplist);
+            if entry.state == PairState::Downloaded {
+                entry.selected = false;
+            }
+        }
+    }
+
+    /// Adds a message to the log, dropping the oldest beyond the limit.
+    fn push_log(&mut self, message: String) {
+        self.log.push(message);
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+}
+
+/// Draws the pair table, the download's progress, its messages, and the key
+/// bindings.
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [table_area, progress_area, log_area, help_area] = Layout::vertical([
+        Constraint::Min(5),
+        Constraint::Length(3),
+        Constraint::Length(8),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let selected_count = app.entries.iter().filter(|entry| entry.selected).count();
+    let rows = app.entries.iter().map(|entry| {
+        Row::new([
+            Cell::from(if entry.selected { "[x]" } else { "[ ]" }),
+            Cell::from(entry.pair.program_name.clone()),
+            entry.state.cell(),
+            Cell::from(entry.pair.program_description.clone()),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(3),
+            Constraint::Length(24),
+            Constraint::Length(24),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(
+        Row::new(["", "Pair", "State", "Description"])
+            .style(Style::new().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::bordered().title(format!(
+        " Program pairs ({} selected of {}) ",
+        selected_count,
+        app.entries.len()
+    )))
+    .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, table_area, &mut app.table);
+
+    let (ratio, label) = match &app.download {
+        Some(download) => (
+            download.finished_pairs as f64 / download.total_pairs.max(1) as f64,
+            format!(
+                "{}/{} pairs{}",
+                download.finished_pairs,
+                download.total_pairs,
+                download
+                    .current_pair
+                    .as_ref()
+                    .map_or(String::new(), |pair| format!(": downloading '{pair}'"))
+            ),
+        ),
+        None => (0.0, "Idle".to_string()),
+    };
+    let gauge = Gauge::default()
+        .block(Block::bordered().title(" Download "))
+        .ratio(ratio.clamp(0.0, 1.0))
+        .label(label);
+    frame.render_widget(gauge, progress_area);
+
+    // The clone in progress is shown above the most recent messages.
+    let visible_lines = usize::from(log_area.height.saturating_sub(2));
+    let mut lines: Vec<Line> = Vec::new();
+    if let Some((position, length, message)) = app
+        .download
+        .as_ref()
+        .and_then(|download| download.clone.as_ref())
+    {
+        let counts = if *length > 0 {
+            format!(" ({position}/{length})")
+        } else {
+            String::new()
+        };
+        lines.push(Line::styled(
+            format!("{message}{counts}"),
+            Style::new().fg(Color::Cyan),
+        ));
+    }
+    let remaining = visible_lines.saturating_sub(lines.len());
+    let start = app.log.len().saturating_sub(remaining);
+    lines.extend(
+        app.log[start..]
+            .iter()
+            .map(|message| Line::raw(message.as_str())),
+    );
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Messages ")),
+        log_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new(
+            "↑/↓ move  space select  a select all/none  enter download selected  q quit",
+        )
+        .style(Style::new().add_modifier(Modifier::DIM)),
+        help_area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// Creates an interface listing the pairs of an individual-metadata
+    /// file, none downloaded.
+    fn app() -> App {
+        let metadata_file = Path::new(INDIVIDUAL_METADATA_DIRECTORY).join("system-tools.json");
+        let metadata = parser::parse(&metadata_file).unwrap();
+        App {
+            entries: metadata
+                .pairs
+                .into_iter()
+                .map(|pair| Entry {
+                    pair,
+                    state: PairState::Missing,
+                    selected: false,
+                })
+                .collect(),
+            table: TableState::default().with_selected(Some(0)),
+            download: None,
+            log: Vec::new(),
+            options: DownloadOptions::default(),
+            quit: false,
+        }
+    }
+
+    /// Tests that pairs are selected one at a time or all at once.
+    #[test]
+    fn test_handle_key_selects_pairs() {
+        let mut app = app();
+        assert!(app.entries.len() > 1);
+
+        app.handle_key(KeyCode::Char(' '));
+        assert!(app.entries[0].selected);
+        assert!(!app.entries[1].selected);
+        assert_eq!(Some(1), app.table.selected());
+
+        app.handle_key(KeyCode::Char('a'));
+        assert!(app.entries.iter().all(|entry| entry.selected));
+        app.handle_key(KeyCode::Char('a'));
+        assert!(app.entries.iter().all(|entry| !entry.selected));
+
+        app.handle_key(KeyCode::Char('q'));
+        assert!(app.quit);
+    }
+
+    /// Tests that quitting cancels the download in progress, and that the
+    /// interface waits for it to stop while ignoring further keys.
+    #[test]
+    fn test_quit_cancels_download() {
+        let mut app = app();
+        let (sender, events) = mpsc::channel();
+        let progress = Arc::new(DownloadProgress::with_events(1, sender).unwrap());
+        let download_progress = Arc::clone(&progress);
+        let handle = thread::spawn(move || {
+            while !download_progress.is_cancelled() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            Ok(())
+        });
+        app.download = Some(Download {
+            events,
+            progress: Arc::clone(&progress),
+            handle,
+            finished_pairs: 0,
+            total_pairs: 1,
+            current_pair: None,
+            clone: None,
+        });
+
+        app.handle_key(KeyCode::Esc);
+        assert!(app.quit);
+        assert!(progress.is_cancelled());
+        app.handle_key(KeyCode::Char('a'));
+        assert!(app.entries.iter().all(|entry| !entry.selected));
+
+        let started = Instant::now();
+        while app.download.is_some() {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            app.poll_download();
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            Some("Downloaded all program pairs!"),
+            app.log.last().map(String::as_str)
+        );
+    }
+}
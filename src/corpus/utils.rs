@@ -3,112 +3,504 @@
 //! This module provides utility functions used in other parts of our code.
 
 use std::{
-    fs,
-    path::{Path, MAIN_SEPARATOR_STR},
+    collections::HashMap,
+    fs::{self, File, FileTimes},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    time::Duration,
 };
 
+use rayon::prelude::*;
+use reqwest::blocking::Client;
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
 use walkdir::WalkDir;
 
-use crate::corpus::errors::DownloaderError;
+use crate::{
+    corpus::{downloader::LinkMode, errors::DownloaderError, schema::ExtensionFilter, store},
+    paths::{SPLITS_DIRECTORY, STAGING_DIRECTORY},
+};
+
+/// A file placed into a program's directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacedFile {
+    /// Where the file was placed.
+    pub path: PathBuf,
+    /// Size of the file in bytes.
+    pub bytes: u64,
+    /// Whether the file was linked rather than copied.
+    pub linked: bool,
+}
+
+/// How the files copied from a directory are placed in the destination.
+#[derive(Debug, Clone, Copy)]
+pub struct Placement<'a> {
+    /// How to link the files instead of copying them, if at all.
+    pub link: Option<LinkMode>,
+    /// The object directory of the content-addressed store files are linked
+    /// into with [`LinkMode::Store`].
+    pub store: &'a Path,
+    /// Whether files in subdirectories are placed directly under the
+    /// destination, or keep their subdirectories.
+    pub flatten: bool,
+    /// Whether binary files are copied rather than skipped.
+    pub allow_binary: bool,
+}
+
+/// The files found by [`copy_files_from_directory`].
+#[derive(Debug, Default)]
+pub struct CopiedFiles {
+    /// The path of each copied file paired with its `/`-separated path in
+    /// the destination, sorted by path.
+    pub copied: Vec<(PathBuf, String)>,
+    /// The binary files that were skipped, sorted by path.
+    pub binaries: Vec<PathBuf>,
+}
+
+/// Number of bytes at the start of a file that are checked for binary
+/// content, as git does.
+const BINARY_SNIFF_BYTES: u64 = 8000;
 
-/// Count the number of immediate files in a directory, not including any
-/// files in sub-directories.
+/// Signatures at the start of common binary formats that may lack a NUL
+/// byte near their start: images, archives, and PDF documents.
+const BINARY_SIGNATURES: [&[u8]; 7] = [
+    b"\x89PNG",
+    b"\xFF\xD8\xFF",
+    b"GIF8",
+    b"%PDF-",
+    b"PK\x03\x04",
+    b"\x1F\x8B",
+    b"\x7FELF",
+];
+
+/// Copies or links the files with the given extensions from a directory to
+/// the destination.
+///
+/// When flattening, copied files will all be directly under the destination
+/// directory; nested directories will not be copied. Files will have their
+/// paths included in their name. For example, a file found in a
+/// subdirectory "module/file.txt" will have a final name of module-file.txt.
+/// Otherwise, files keep their paths, such as "module/file.txt".
+///
+/// The directory is walked first and the files are then copied in
+/// parallel, which matters for large trees such as a whole `src/`.  Files
+/// that would be flattened to the same name are rejected before any is
+/// copied, since which of them is kept would depend on the threads.  Binary
+/// files, such as committed object files and test images, are skipped unless
+/// `placement` allows them.
 ///
 /// # Arguments
 ///
-/// `directory` - A path to the directory.
+/// - `source` - The source directory to copy files from.
+/// - `destination` - The destination directory to copy files to.
+/// - `exclude` - Files and directories under `source` that are not copied.
+/// - `extensions` - The extensions of the files that are copied.
+/// - `root` - The directory every copied file must resolve into, after
+///   following symbolic links; typically the repository's root.
+/// - `placement` - How to link the files instead of copying them, if at
+///   all, whether to flatten them, and whether to copy binary files.
+/// - `on_placed` - Called with each placed file, from the thread that
+///   placed it.
 ///
 /// # Returns
 ///
-/// The number of immediate files in the directory, or a [`DownloaderError`]
-/// if the operation fails.
-pub fn count_files(directory: &Path) -> Result<usize, DownloaderError> {
-    let entries = directory
-        .read_dir()
-        .map_err(|error| DownloaderError::IoRead {
-            path: directory.to_path_buf(),
-            error,
-        })?;
+/// The [`CopiedFiles`] on success, and [`DownloaderError`] on failure,
+/// including [`DownloaderError::PathEscape`] if a file resolves outside of
+/// `root`, and [`DownloaderError::FlattenCollision`] if two files are
+/// flattened to the same name.
+pub fn copy_files_from_directory(
+    source: &Path,
+    destination: &Path,
+    exclude: &[PathBuf],
+    extensions: &ExtensionFilter,
+    root: &Path,
+    placement: Placement<'_>,
+    on_placed: &(dyn Fn(PlacedFile) + Sync),
+) -> Result<CopiedFiles, DownloaderError> {
+    let copy_error = |error| DownloaderError::IoCopy {
+        source: source.to_path_buf(),
+        destination: destination.to_path_buf(),
+        error,
+    };
 
-    let mut total_files = 0;
-    for entry in entries {
-        let entry = entry.map_err(|error| DownloaderError::IoRead {
-            path: directory.to_path_buf(),
-            error,
-        })?;
+    // Create destination directory in case it doesn't exist.
+    fs::create_dir_all(destination).map_err(copy_error)?;
+
+    // Iterate recursively through every file in `source`, skipping excluded
+    // files and directories, and collect those whose extension passes the
+    // filter.
+    let mut files = Vec::new();
+    let mut binaries = Vec::new();
+    let mut names = HashMap::new();
+    let entries = WalkDir::new(source)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| !exclude.iter().any(|path| entry.path().starts_with(path)));
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_file() && extensions.matches(path) {
+            // Include full path as filename but replace path
+            // separator with '-' in filename, unless keeping subdirectories.
+            let relative_path = path.strip_prefix(source).unwrap_or(path);
+            let filename = if placement.flatten {
+                flatten_relative_path(relative_path)
+            } else {
+                slash_path(relative_path)
+            };
+            let filename = filename.ok_or_else(|| {
+                DownloaderError::Io(format!("Failed to get filename for '{}'", path.display()))
+            })?;
+            let resolved = ensure_within(path, root)?;
+            if !placement.allow_binary && is_binary(&resolved).map_err(copy_error)? {
+                binaries.push(path.to_path_buf());
+                continue;
+            }
+            if let Some(first) = names.insert(filename.clone(), path.to_path_buf()) {
+                return Err(DownloaderError::FlattenCollision {
+                    first,
+                    second: path.to_path_buf(),
+                    name: filename,
+                });
+            }
+            files.push((path.to_path_buf(), resolved, filename));
+        }
+    }
 
-        let file_type = entry.file_type().map_err(|error| DownloaderError::IoRead {
-            path: directory.to_path_buf(),
-            error,
-        })?;
+    files.par_iter().try_for_each(|(_, resolved, filename)| {
+        let file_destination = destination.join(filename);
+        if let Some(parent) = file_destination.parent() {
+            fs::create_dir_all(parent).map_err(copy_error)?;
+        }
+        let placed = place_file(resolved, &file_destination, placement.link, placement.store)
+            .map_err(copy_error)?;
+        on_placed(placed);
+        Ok::<(), DownloaderError>(())
+    })?;
+
+    Ok(CopiedFiles {
+        copied: files
+            .into_iter()
+            .map(|(path, _, filename)| (path, filename))
+            .collect(),
+        binaries,
+    })
+}
+
+/// Checks whether a file is binary rather than text, from a NUL byte near its
+/// start, as git does, or the signature of a common binary format.
+///
+/// # Arguments
+///
+/// - `path` - Path to the file.
+///
+/// # Returns
+///
+/// Whether the file is binary, or the [`io::Error`] of reading it.
+pub fn is_binary(path: &Path) -> io::Result<bool> {
+    let mut start = Vec::new();
+    File::open(path)?
+        .take(BINARY_SNIFF_BYTES)
+        .read_to_end(&mut start)?;
+    Ok(start.contains(&0)
+        || BINARY_SIGNATURES
+            .iter()
+            .any(|signature| start.starts_with(signature)))
+}
 
-        if file_type.is_file() {
-            total_files += 1;
+/// Links a file into place, or copies it if it is not to be linked or
+/// cannot be, such as across filesystems or without permission to create
+/// symbolic links.
+///
+/// # Arguments
+///
+/// - `source` - The resolved path of the file to place.
+/// - `destination` - Where to place it, replacing any existing file.
+/// - `link` - How to link the file, or `None` to copy it.
+/// - `store` - The object directory of the content-addressed store, for
+///   [`LinkMode::Store`].
+///
+/// # Returns
+///
+/// The [`PlacedFile`], or an [`io::Error`] if the file could not be copied
+/// either.
+pub fn place_file(
+    source: &Path,
+    destination: &Path,
+    link: Option<LinkMode>,
+    store: &Path,
+) -> io::Result<PlacedFile> {
+    // A file from an earlier download is removed rather than overwritten,
+    // since links cannot replace it and writing through a link would change
+    // the file in `repository_clones/`.
+    match fs::remove_file(destination) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+        _ => {}
+    }
+    if let Some(link) = link {
+        let linked = match link {
+            LinkMode::Hard => fs::hard_link(source, destination),
+            LinkMode::Symbolic => symlink_file(source, destination),
+            LinkMode::Store => store::link(store, source, destination),
+        };
+        if linked.is_ok() {
+            let bytes = fs::metadata(source)?.len();
+            return Ok(PlacedFile {
+                path: destination.to_path_buf(),
+                bytes,
+                linked: true,
+            });
         }
     }
+    let bytes = copy_file(source, destination)?;
+    Ok(PlacedFile {
+        path: destination.to_path_buf(),
+        bytes,
+        linked: false,
+    })
+}
 
-    Ok(total_files)
+/// Removes a path if it is a symbolic link, such as one left by an earlier
+/// download with `--link symbolic`.
+///
+/// # Arguments
+///
+/// - `path` - The path to remove if it is a symbolic link.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if `path` is not, or no longer, a symbolic link, and an
+/// [`io::Error`] if it could not be removed.
+pub fn remove_symlink(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+        fs::remove_file(path)?;
+    }
+    Ok(())
 }
 
-/// Copies all .c, .h, and .rs files from a directory to the destination.
+/// Creates a symbolic link to a file.
+pub fn symlink_file(source: &Path, destination: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(source, destination);
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_file(source, destination);
+    #[cfg(not(any(unix, windows)))]
+    return Err(io::Error::from(io::ErrorKind::Unsupported));
+}
+
+/// Copies a file, preserving its permissions and its access and
+/// modification times.
+///
+/// # Arguments
 ///
-/// Copied files will all be directly under the destination directory;
-/// nested directories will not be copied. Files will have their paths
-/// included in their name. For example, a file found in a subdirectory
-/// "module/file.txt" will have a final name of module-file.txt.
+/// - `source` - The file to copy; symbolic links are followed.
+/// - `destination` - Where to copy it, replacing any existing file.
+///
+/// # Returns
+///
+/// The number of bytes copied, or an [`io::Error`] on failure.
+pub fn copy_file(source: &Path, destination: &Path) -> io::Result<u64> {
+    let mut reader = File::open(source)?;
+    let metadata = reader.metadata()?;
+    let mut writer = File::create(destination)?;
+    let bytes = io::copy(&mut reader, &mut writer)?;
+
+    let mut times = FileTimes::new();
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    if let Ok(modified) = metadata.modified() {
+        times = times.set_modified(modified);
+    }
+    writer.set_times(times)?;
+    drop(writer);
+
+    // Permissions are set last, since a read-only file could not be written.
+    fs::set_permissions(destination, metadata.permissions())?;
+    Ok(bytes)
+}
+
+/// A path that reads as empty on the current platform: `NUL` on Windows and
+/// `/dev/null` elsewhere.
+pub const NULL_DEVICE: &str = if cfg!(windows) { "NUL" } else { "/dev/null" };
+
+/// Converts a path from metadata, separated by `/`, into a path using the
+/// platform's separator.
+///
+/// Empty and `.` components are dropped, and `\` is also treated as a
+/// separator so that paths written on Windows behave the same everywhere.
 ///
 /// # Arguments
 ///
-/// - `source` - The source directory to copy files from.
-/// - `destination` - The destination directory to copy files to.
+/// - `path` - A relative path such as `src/uu/ls/src/ls.rs`.
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` on success and [`DownloaderError`] on failure.
-pub fn copy_files_from_directory(source: &Path, destination: &Path) -> Result<(), DownloaderError> {
-    // Create destination directory in case it doesn't exist.
-    fs::create_dir_all(destination).map_err(|error| DownloaderError::IoCopy {
-        source: source.to_path_buf(),
-        destination: destination.to_path_buf(),
-        error,
-    })?;
+/// The path built from the components of `path`.
+pub fn platform_path(path: &str) -> PathBuf {
+    path.split(['/', '\\'])
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect()
+}
+
+/// Flattens a relative path into a single file name by joining its
+/// components with `-`, whatever the platform's separator.
+///
+/// # Example
+///
+/// `uu/ls/src/ls.rs` is flattened to `uu-ls-src-ls.rs`.
+///
+/// # Arguments
+///
+/// - `path` - A path relative to a copied directory.
+///
+/// # Returns
+///
+/// The flattened file name, or `None` if `path` is not valid UTF-8 or is
+/// not a plain relative path.
+pub fn flatten_relative_path(path: &Path) -> Option<String> {
+    join_components(path, "-")
+}
+
+/// Converts a relative path into a string separated by `/`, whatever the
+/// platform's separator, such as for recording it in JSON.
+///
+/// # Arguments
+///
+/// - `path` - A relative path.
+///
+/// # Returns
+///
+/// The `/`-separated path, or `None` if `path` is not valid UTF-8 or is not
+/// a plain relative path.
+pub fn slash_path(path: &Path) -> Option<String> {
+    join_components(path, "/")
+}
 
-    // Iterate recursively through every file in `source`.
-    for entry in WalkDir::new(source).into_iter().filter_map(|e| e.ok()) {
+/// Joins the components of a plain relative path with a separator.
+fn join_components(path: &Path, separator: &str) -> Option<String> {
+    let components = path
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!components.is_empty()).then(|| components.join(separator))
+}
+
+/// Resolves a path and checks that it lies within a root directory.
+///
+/// Symbolic links are followed, so a link inside the root that points
+/// outside of it is caught.  A path that does not exist yet, such as a copy
+/// destination, is resolved through its parent directory.
+///
+/// # Arguments
+///
+/// - `path` - The path to check.
+/// - `root` - The directory the path must stay within.
+///
+/// # Returns
+///
+/// The resolved path on success, [`DownloaderError::PathEscape`] if it lies
+/// outside of `root`, or [`DownloaderError::IoRead`] if it cannot be
+/// resolved.
+pub fn ensure_within(path: &Path, root: &Path) -> Result<PathBuf, DownloaderError> {
+    let canonicalize = |path: &Path| {
+        path.canonicalize()
+            .map_err(|error| DownloaderError::IoRead {
+                path: path.to_path_buf(),
+                error,
+            })
+    };
+    let escape = || DownloaderError::PathEscape {
+        path: path.to_path_buf(),
+        root: root.to_path_buf(),
+    };
+
+    let root = canonicalize(root)?;
+    // Only paths that do not exist at all are resolved through their parent;
+    // a dangling symbolic link fails to resolve rather than being written
+    // through.
+    let resolved = if fs::symlink_metadata(path).is_ok() {
+        canonicalize(path)?
+    } else {
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(file_name)) => canonicalize(parent)?.join(file_name),
+            _ => return Err(escape()),
+        }
+    };
+
+    if resolved.starts_with(&root) {
+        Ok(resolved)
+    } else {
+        Err(escape())
+    }
+}
+
+/// Lists the downloaded program pairs, one directory per pair.
+///
+/// # Arguments
+///
+/// - `directory` - The directory holding program pairs, typically
+///   `program_pairs/`.
+///
+/// # Returns
+///
+/// The paths of every sub-directory of `directory` sorted by name, except
+/// `program_pairs/splits/` and `program_pairs/.staging/`, or an
+/// [`io::Error`] if `directory` cannot be read.
+pub fn list_program_pairs(directory: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let mut pairs = Vec::new();
+    for entry in directory.read_dir()? {
+        let entry = entry?;
         let path = entry.path();
-        if path.is_file() {
-            if let Some(extension) = path.extension() {
-                let extension = extension.to_str().ok_or_else(|| {
-                    DownloaderError::Io("Failed to retrieve file extension".to_string())
-                })?;
-
-                // Copy all `.c`, `.h`, and `.rs` files.
-                if matches!(extension, "c" | "h" | "rs") {
-                    // Include full path as filename but replace path
-                    // separator with '-' in filename.
-                    let relative_path = path.strip_prefix(source).unwrap_or(path);
-                    let filename = relative_path
-                        .to_str()
-                        .ok_or_else(|| {
-                            DownloaderError::Io(format!(
-                                "Failed to get filename for '{}'",
-                                path.display()
-                            ))
-                        })?
-                        .replace(MAIN_SEPARATOR_STR, "-");
-                    fs::copy(path, destination.join(filename)).map_err(|error| {
-                        DownloaderError::IoCopy {
-                            source: source.to_path_buf(),
-                            destination: destination.to_path_buf(),
-                            error,
-                        }
-                    })?;
-                }
-            }
+        if entry.file_type()?.is_dir()
+            && path != Path::new(SPLITS_DIRECTORY)
+            && path != Path::new(STAGING_DIRECTORY)
+        {
+            pairs.push(path);
         }
     }
+    pairs.sort();
+    Ok(pairs)
+}
 
-    Ok(())
+/// Hashes a program pair's name with a seed, giving a key that orders pairs
+/// the same way on every platform for the same seed.
+///
+/// The name is hashed with FNV-1a and mixed with the seed by SplitMix64's
+/// finalizer, both of which are stable across platforms and releases.
+pub fn seeded_hash(name: &str, seed: u64) -> u64 {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    let mut key = hash ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    key ^ (key >> 31)
+}
+
+/// Recursively lists every file within a directory.
+///
+/// Entries that cannot be read are skipped, and a missing directory yields an
+/// empty list.
+///
+/// # Arguments
+///
+/// - `directory` - The directory to search.
+///
+/// # Returns
+///
+/// The paths of all files under `directory`, sorted by path.
+pub fn list_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(directory)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .collect();
+    files.sort();
+    files
 }
 
 /// Extract a repository's name from its URL.
@@ -126,17 +518,205 @@ pub fn copy_files_from_directory(source: &Path, destination: &Path) -> Result<()
 ///
 /// The name of the repository on success or [`DownloaderError`] on failure.
 pub fn get_repository_name(url: &str) -> Result<String, DownloaderError> {
-    let last_segment = url
-        .trim_end_matches('/')
+    let normalized = normalize_repository_url(url);
+    let name = normalized
         .split('/')
-        .last()
+        .next_back()
         .expect("Unreachable because split always returns at least 1 element");
-    let name = last_segment.strip_suffix(".git").unwrap_or(last_segment);
     Ok(name.to_string())
 }
 
+/// Whether libgit2 can fetch a repository shallowly.
+///
+/// libgit2 fetches `file` URLs through its local transport, which fails any
+/// fetch given a depth with "shallow fetch is not supported by the local
+/// transport", so repositories on disk, such as mirrors on a shared drive or
+/// the fixtures of the end-to-end tests, are cloned and fetched in full.  The
+/// `git` backend clones them shallowly.
+///
+/// # Arguments
+///
+/// - `url` - Git repository URL.
+pub fn supports_shallow_fetch(url: &str) -> bool {
+    !url.to_ascii_lowercase().starts_with("file://")
+}
+
+/// Normalizes a repository URL so that different spellings of the same
+/// repository compare equal.
+///
+/// The scheme, user information, default port, trailing slashes, and `.git`
+/// suffix are removed, and the host is lowercased without a leading `www.`.
+/// SSH URLs in scp syntax, such as `git@github.com:owner/repo.git`, are
+/// treated as `ssh://git@github.com/owner/repo.git`, unless the path starts
+/// with a number, which is a port as in a normalized URL such as
+/// `localhost:8080/repo`.  Normalizing a normalized URL leaves it as is.
+///
+/// # Example
+///
+/// "https://GitHub.com/eza-community/eza.git",
+/// "http://github.com/eza-community/eza/", and
+/// "git@github.com:eza-community/eza.git" all normalize to
+/// "github.com/eza-community/eza".
+///
+/// # Arguments
+///
+/// - `url` - Git repository URL.
+///
+/// # Returns
+///
+/// The normalized URL.
+pub fn normalize_repository_url(url: &str) -> String {
+    let url = url.trim();
+    let without_scheme = match url.split_once("://") {
+        Some((_, rest)) => rest.to_string(),
+        // In scp syntax, the host is separated from the path by `:`.  A
+        // single letter before it is a Windows drive instead.
+        None => match url.split_once(':') {
+            Some((host, path))
+                if host.len() > 1 && !host.contains(['/', '\\']) && !starts_with_port(path) =>
+            {
+                format!("{host}/{}", path.trim_start_matches('/'))
+            }
+            _ => url.to_string(),
+        },
+    };
+    let mut without_suffix = without_scheme.trim_end_matches('/');
+    while let Some(stripped) = without_suffix.strip_suffix(".git") {
+        without_suffix = stripped.trim_end_matches('/');
+    }
+    let (host, path) = without_suffix
+        .split_once('/')
+        .unwrap_or((without_suffix, ""));
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    let host = match host.rsplit_once(':') {
+        Some((host, "22" | "80" | "443" | "9418")) => host,
+        _ => host,
+    };
+    let host = host.to_lowercase();
+    let host = host.trim_start_matches("www.");
+
+    if path.is_empty() {
+        host.to_string()
+    } else {
+        format!("{host}/{path}")
+    }
+}
+
+/// Whether the part of a URL after the host's `:` starts with a port, a
+/// number ending at the path or at the end of the URL.
+fn starts_with_port(rest: &str) -> bool {
+    let port = rest.split('/').next().unwrap_or_default();
+    !port.is_empty() && port.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Parses a human-readable size such as "10G" or "512MiB" into bytes.
+///
+/// Units are binary, so "1K" is 1024 bytes.  A number without a unit is a
+/// number of bytes.
+///
+/// # Arguments
+///
+/// - `size` - The size to parse.
+///
+/// # Returns
+///
+/// The size in bytes, or `None` if `size` is malformed.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let digits_end = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let number: f64 = size[..digits_end].parse().ok()?;
+    let multiplier: u64 = match size[digits_end..].trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Formats a number of bytes as a human-readable size such as "1.5 GiB".
+///
+/// # Arguments
+///
+/// - `bytes` - The size in bytes.
+///
+/// # Returns
+///
+/// The formatted size.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Computes the total size of the files in a directory, recursively.
+///
+/// # Arguments
+///
+/// - `directory` - The directory to measure.
+///
+/// # Returns
+///
+/// The total size in bytes of all readable files under `directory`.
+pub fn directory_size(directory: &Path) -> u64 {
+    WalkDir::new(directory)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// The user agent of this program's HTTP requests.
+pub const USER_AGENT: &str = concat!("c-rust-program-pairs/", env!("CARGO_PKG_VERSION"));
+
+/// Creates an HTTP client for querying web APIs such as GitHub and crates.io.
+///
+/// Requests identify this program by user agent, which both APIs require,
+/// and give up after 30 seconds.
+pub fn http_client() -> reqwest::Result<Client> {
+    Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(Duration::from_secs(30))
+        .build()
+}
+
+/// Serializes a value as JSON indented with four spaces, like the metadata
+/// files, so it can be pasted into one.
+///
+/// # Arguments
+///
+/// - `value` - The value to serialize.
+///
+/// # Returns
+///
+/// The indented JSON, or the serialization error.
+pub fn to_metadata_json<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    let mut json = Vec::new();
+    let mut serializer =
+        serde_json::Serializer::with_formatter(&mut json, PrettyFormatter::with_indent(b"    "));
+    value.serialize(&mut serializer)?;
+    Ok(String::from_utf8_lossy(&json).into_owned())
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::SystemTime;
+
     use super::*;
 
     #[test]
@@ -150,5 +730,223 @@ mod tests {
             "eza",
             get_repository_name("https://github.com/eza-community/eza").unwrap()
         );
+        assert_eq!(
+            "eza",
+            get_repository_name("git@github.com:eza-community/eza.git/").unwrap()
+        );
+    }
+
+    #[test]
+    /// Tests that different spellings of a repository URL normalize equally.
+    fn test_normalize_repository_url() {
+        for url in [
+            "https://github.com/eza-community/eza",
+            "https://github.com/eza-community/eza.git",
+            "https://GitHub.com/eza-community/eza/",
+            "http://user@github.com/eza-community/eza.git",
+            "git@github.com:eza-community/eza.git",
+            "ssh://git@github.com:22/eza-community/eza.git",
+            "https://www.github.com:443/eza-community/eza",
+        ] {
+            assert_eq!(
+                "github.com/eza-community/eza",
+                normalize_repository_url(url)
+            );
+        }
+        assert_eq!(
+            "localhost:8080/eza",
+            normalize_repository_url("http://localhost:8080/eza.git")
+        );
+        assert_eq!("/tmp/eza", normalize_repository_url("file:///tmp/eza"));
+    }
+
+    #[test]
+    /// Tests that normalizing a normalized URL leaves it as is.
+    fn test_normalize_repository_url_round_trip() {
+        for url in [
+            "https://github.com/eza-community/eza.git",
+            "git@github.com:eza-community/eza.git",
+            "http://localhost:8080/eza.git",
+            "ssh://git@example.com:2222/eza",
+            "https://example.com:8443",
+            "https://www.www.example.com/eza.git.git/",
+            "file:///tmp/eza",
+            "C:\\repositories\\eza",
+        ] {
+            let normalized = normalize_repository_url(url);
+            assert_eq!(normalized, normalize_repository_url(&normalized), "{url}");
+        }
+        assert_eq!(
+            "example.com:2222/eza",
+            normalize_repository_url("ssh://git@example.com:2222/eza")
+        );
+    }
+
+    #[test]
+    /// Tests that only repositories fetched through libgit2's local transport
+    /// are fetched in full.
+    fn test_supports_shallow_fetch() {
+        assert!(supports_shallow_fetch(
+            "https://github.com/eza-community/eza"
+        ));
+        assert!(supports_shallow_fetch(
+            "git@github.com:eza-community/eza.git"
+        ));
+        assert!(!supports_shallow_fetch("file:///tmp/eza"));
+        assert!(!supports_shallow_fetch("FILE:///tmp/eza"));
+    }
+
+    #[test]
+    /// Tests that human-readable sizes are parsed with binary units.
+    fn test_parse_size() {
+        assert_eq!(Some(512), parse_size("512"));
+        assert_eq!(Some(10 << 30), parse_size("10G"));
+        assert_eq!(Some(1536 << 10), parse_size("1.5MiB"));
+        assert_eq!(None, parse_size("10X"));
+        assert_eq!(None, parse_size("G"));
+    }
+
+    #[test]
+    /// Tests that metadata paths are split into platform path components.
+    fn test_platform_path() {
+        let expected: PathBuf = ["src", "uu", "ls", "ls.rs"].iter().collect();
+        assert_eq!(expected, platform_path("src/uu/ls/ls.rs"));
+        assert_eq!(expected, platform_path("src\\uu\\ls\\ls.rs"));
+        assert_eq!(expected, platform_path("./src//uu/ls/ls.rs"));
+        assert_eq!(PathBuf::from("src"), platform_path("src/"));
+    }
+
+    #[test]
+    /// Tests that nested paths are flattened into one file name.
+    fn test_flatten_relative_path() {
+        let path: PathBuf = ["uu", "ls", "src", "ls.rs"].iter().collect();
+        assert_eq!(
+            Some("uu-ls-src-ls.rs".to_string()),
+            flatten_relative_path(&path)
+        );
+        assert_eq!(
+            Some("ls.rs".to_string()),
+            flatten_relative_path(Path::new("ls.rs"))
+        );
+        assert_eq!(None, flatten_relative_path(Path::new("../ls.rs")));
+        assert_eq!(None, flatten_relative_path(Path::new("")));
+    }
+
+    #[test]
+    /// Tests that relative paths are recorded with `/` separators.
+    fn test_slash_path() {
+        let path: PathBuf = ["src", "uu", "ls.rs"].iter().collect();
+        assert_eq!(Some("src/uu/ls.rs".to_string()), slash_path(&path));
+        assert_eq!(None, slash_path(Path::new("/src/ls.rs")));
+    }
+
+    #[test]
+    /// Tests that copies keep the modification time and read-only permission
+    /// of their source.
+    fn test_copy_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let source = directory.path().join("ls.c");
+        let destination = directory.path().join("copy.c");
+        fs::write(&source, "int main(void) {}\n").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let mut permissions = fs::metadata(&source).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&source, permissions).unwrap();
+
+        assert_eq!(18, copy_file(&source, &destination).unwrap());
+        let metadata = fs::metadata(&destination).unwrap();
+        assert_eq!(modified, metadata.modified().unwrap());
+        assert!(metadata.permissions().readonly());
+    }
+
+    #[test]
+    /// Tests that files flattened to the same name are rejected before any
+    /// is copied.
+    fn test_copy_files_flatten_collision() {
+        let directory = tempfile::tempdir().unwrap();
+        let source = directory.path().join("src");
+        fs::create_dir_all(source.join("uu")).unwrap();
+        fs::write(source.join("uu").join("ls.c"), "int a;\n").unwrap();
+        fs::write(source.join("uu-ls.c"), "int b;\n").unwrap();
+        let destination = directory.path().join("pair");
+        let copy = |flatten| {
+            copy_files_from_directory(
+                &source,
+                &destination,
+                &[],
+                &ExtensionFilter::new(["c"], [""; 0]),
+                directory.path(),
+                Placement {
+                    link: None,
+                    store: directory.path(),
+                    flatten,
+                    allow_binary: false,
+                },
+                &|_| {},
+            )
+        };
+
+        assert!(matches!(
+            copy(true),
+            Err(DownloaderError::FlattenCollision { name, .. }) if name == "uu-ls.c"
+        ));
+        assert!(!destination.join("uu-ls.c").exists());
+        assert_eq!(2, copy(false).unwrap().copied.len());
+    }
+
+    #[test]
+    /// Tests that paths within the root resolve, even before they exist, and
+    /// that `..` and absolute paths leaving it are rejected.
+    fn test_ensure_within() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path().join("root");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(directory.path().join("secret.txt"), "").unwrap();
+        let root_canonical = root.canonicalize().unwrap();
+
+        assert_eq!(
+            root_canonical.join("src"),
+            ensure_within(&root.join("src"), &root).unwrap()
+        );
+        assert_eq!(
+            root_canonical.join("src").join("new.c"),
+            ensure_within(&root.join("src").join("new.c"), &root).unwrap()
+        );
+        for path in [
+            root.join("..").join("secret.txt"),
+            root.join("src").join("..").join("..").join("new.c"),
+            directory.path().join("secret.txt"),
+            directory.path().canonicalize().unwrap(),
+        ] {
+            assert!(
+                matches!(
+                    ensure_within(&path, &root),
+                    Err(DownloaderError::PathEscape { .. })
+                ),
+                "{}",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_binary() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = |name: &str, contents: &[u8]| {
+            let path = directory.path().join(name);
+            fs::write(&path, contents).unwrap();
+            is_binary(&path).unwrap()
+        };
+        assert!(!file("ls.c", b"int main(void) { return 0; }\n"));
+        assert!(!file("latin1.c", b"/* Caf\xE9 */\n"));
+        assert!(file("ls.o", b"\x7FELF\x02\x01\x01\x00"));
+        assert!(file("data.h", b"static const char data[] = \"\x00\";"));
+        assert!(file("logo.png", b"\x89PNG\r\n\x1A\n"));
     }
 }
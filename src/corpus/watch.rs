@@ -0,0 +1,364 @@
+//! # Metadata Watcher
+//!
+//! This module watches the metadata directories and downloads the program
+//! pairs whose entries change, to speed up the loop of editing metadata and
+//! checking the download while curating the corpus.
+//!
+//! Each metadata file's pairs are remembered from the last time it parsed, so
+//! only pairs that were added or edited are downloaded again; unchanged pairs
+//! in the same file are left alone.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde_json::Value;
+
+use crate::{
+    corpus::{
+        downloader::{self, DownloadOptions},
+        errors::WatchError,
+        parser,
+        schema::ProgramPair,
+    },
+    paths::{
+        self, DEMO_METADATA_DIRECTORY, INDIVIDUAL_METADATA_DIRECTORY, PROJECT_METADATA_DIRECTORY,
+    },
+};
+
+/// How long the metadata must stay unchanged before a burst of changes, such
+/// as an editor saving a file in several steps, is handled.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// The pairs of each metadata file as last parsed, keyed by the file's path
+/// and then by program name.
+type Snapshot = HashMap<PathBuf, BTreeMap<String, Value>>;
+
+/// Watches the metadata directories and downloads the program pairs that are
+/// added or edited, until interrupted.
+///
+/// Changes to the schema require restarting the watcher.
+///
+/// # Arguments
+///
+/// - `options` - Options controlling the downloads.
+///
+/// # Returns
+///
+/// Returns `Ok(())` once the watcher stops and [`WatchError`] if the
+/// directories cannot be watched.
+pub fn watch(options: &DownloadOptions) -> Result<(), WatchError> {
+    let mut directories = Vec::new();
+    for directory in [
+        PROJECT_METADATA_DIRECTORY,
+        INDIVIDUAL_METADATA_DIRECTORY,
+        DEMO_METADATA_DIRECTORY,
+    ] {
+        let directory = paths::metadata_path(directory);
+        directories.push(
+            directory
+                .canonicalize()
+                .map_err(|error| WatchError::IoRead {
+                    path: directory.clone(),
+                    error,
+                })?,
+        );
+    }
+
+    let mut snapshot = Snapshot::new();
+    for directory in &directories {
+        let metadata_files =
+            parser::parse_directory(directory).map_err(|error| WatchError::Parse {
+                path: directory.clone(),
+                error,
+            })?;
+        for (path, metadata) in metadata_files {
+            if let Ok(metadata) = metadata {
+                snapshot.insert(path, pair_values(&metadata.pairs));
+            }
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|error| WatchError::Watch { error })?;
+    for directory in &directories {
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(|error| WatchError::Watch { error })?;
+    }
+    println!("Watching metadata files for changes; press Ctrl-C to stop");
+
+    // Wait for a change, then gather the changes that follow it closely.
+    while let Ok(event) = receiver.recv() {
+        let mut changed_files = BTreeSet::new();
+        let mut event = Some(event);
+        while let Some(result) = event {
+            match result {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    changed_files.extend(event.paths.into_iter().filter(|path| {
+                        path.extension()
+                            .is_some_and(|extension| extension == "json")
+                            && path
+                                .parent()
+                                .is_some_and(|parent| directories.iter().any(|d| d == parent))
+                    }));
+                }
+                Ok(_) => {}
+                Err(error) => eprintln!("Failed to watch metadata: {error}"),
+            }
+            event = receiver.recv_timeout(DEBOUNCE).ok();
+        }
+
+        let changed_pairs = pairs_to_download(&changed_files, &mut snapshot, &options.tags);
+        if changed_pairs.is_empty() {
+            continue;
+        }
+        if let Err(error) = downloader::download_pairs(&changed_pairs, options) {
+            eprintln!("Failed to download changed pairs: {error}");
+        }
+    }
+    Ok(())
+}
+
+/// Gathers the pairs of the changed metadata files that were added or edited
+/// and are selected by the tags, updating the snapshot.
+///
+/// # Arguments
+///
+/// - `changed_files` - The metadata files that changed.
+/// - `snapshot` - The pairs of every metadata file as last parsed.
+/// - `tags` - Only pairs with one of these tags are downloaded; an empty
+///   list selects every pair.
+///
+/// # Returns
+///
+/// The pairs to download.
+fn pairs_to_download(
+    changed_files: &BTreeSet<PathBuf>,
+    snapshot: &mut Snapshot,
+    tags: &[String],
+) -> Vec<ProgramPair> {
+    changed_files
+        .iter()
+        .flat_map(|path| changed_pairs(path, snapshot))
+        .filter(|pair| pair.has_any_tag(tags))
+        .collect()
+}
+
+/// Parses a changed metadata file and updates the snapshot.
+///
+/// A file that was removed is forgotten, and a file that fails to parse is
+/// reported and keeps its previous pairs, so fixing it downloads whatever
+/// changed since it last parsed.
+///
+/// # Arguments
+///
+/// - `path` - The changed metadata file.
+/// - `snapshot` - The pairs of every metadata file as last parsed.
+///
+/// # Returns
+///
+/// The pairs of the file that were added or edited.
+fn changed_pairs(path: &Path, snapshot: &mut Snapshot) -> Vec<ProgramPair> {
+    if !path.exists() {
+        if snapshot.remove(path).is_some() {
+            println!(
+                "'{}' was removed; its downloaded pairs are kept",
+                path.display()
+            );
+        }
+        return Vec::new();
+    }
+
+    let metadata = match parser::parse(path) {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            eprintln!("Failed to parse '{}': {}", path.display(), error);
+            return Vec::new();
+        }
+    };
+
+    let values = pair_values(&metadata.pairs);
+    let previous = snapshot.get(path);
+    let changed: Vec<ProgramPair> = metadata
+        .pairs
+        .into_iter()
+        .filter(|pair| {
+            previous.and_then(|previous| previous.get(&pair.program_name))
+                != values.get(&pair.program_name)
+        })
+        .collect();
+    println!(
+        "'{}' changed; {} program pairs to download",
+        path.display(),
+        changed.len()
+    );
+    snapshot.insert(path.to_path_buf(), values);
+    changed
+}
+
+/// Serializes program pairs so they can be compared with later versions.
+///
+/// # Arguments
+///
+/// - `pairs` - The program pairs of a metadata file.
+///
+/// # Returns
+///
+/// Each pair's JSON value keyed by program name.
+fn pair_values(pairs: &[ProgramPair]) -> BTreeMap<String, Value> {
+    pairs
+        .iter()
+        .filter_map(|pair| {
+            let value = serde_json::to_value(pair).ok()?;
+            Some((pair.program_name.clone(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// Builds an individual-metadata pair with the given description and
+    /// tags.
+    fn pair(name: &str, description: &str, tags: &[&str]) -> Value {
+        json!({
+            "program_name": name,
+            "program_description": description,
+            "translation_tools": ["manual"],
+            "feature_relationship": "rust_equivalent_to_c",
+            "tags": tags,
+            "c_program": {
+                "documentation_url": "https://example.com",
+                "repository_url": format!("https://example.com/{name}"),
+                "source_paths": ["src"]
+            },
+            "rust_program": {
+                "documentation_url": "https://example.com",
+                "repository_url": format!("https://example.com/{name}-rs"),
+                "source_paths": ["src"]
+            }
+        })
+    }
+
+    /// Writes a metadata file holding the given pairs.
+    fn write(path: &Path, pairs: &[Value]) {
+        fs::write(path, json!({ "pairs": pairs }).to_string()).unwrap();
+    }
+
+    /// The names of the pairs to download after `path` changed.
+    fn changed(path: &Path, snapshot: &mut Snapshot, tags: &[&str]) -> Vec<String> {
+        let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+        pairs_to_download(&BTreeSet::from([path.to_path_buf()]), snapshot, &tags)
+            .into_iter()
+            .map(|pair| pair.program_name)
+            .collect()
+    }
+
+    /// Tests that only added or edited pairs are downloaded, leaving their
+    /// untouched siblings alone, and that removed pairs are forgotten.
+    #[test]
+    fn test_changed_pairs() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tools.json");
+        let mut snapshot = Snapshot::new();
+
+        write(
+            &path,
+            &[pair("cat", "Concatenates", &[]), pair("ls", "Lists", &[])],
+        );
+        assert_eq!(vec!["cat", "ls"], changed(&path, &mut snapshot, &[]));
+        assert!(changed(&path, &mut snapshot, &[]).is_empty());
+
+        write(
+            &path,
+            &[
+                pair("cat", "Concatenates files", &[]),
+                pair("ls", "Lists", &[]),
+            ],
+        );
+        assert_eq!(vec!["cat"], changed(&path, &mut snapshot, &[]));
+
+        write(&path, &[pair("ls", "Lists", &[])]);
+        assert!(changed(&path, &mut snapshot, &[]).is_empty());
+        assert_eq!(vec!["ls"], snapshot[&path].keys().collect::<Vec<_>>());
+
+        // A pair removed and then restored is downloaded again.
+        write(
+            &path,
+            &[
+                pair("cat", "Concatenates files", &[]),
+                pair("ls", "Lists", &[]),
+            ],
+        );
+        assert_eq!(vec!["cat"], changed(&path, &mut snapshot, &[]));
+
+        fs::remove_file(&path).unwrap();
+        assert!(changed(&path, &mut snapshot, &[]).is_empty());
+        assert!(!snapshot.contains_key(&path));
+    }
+
+    /// Tests that a file that fails to parse keeps its previous pairs, so
+    /// fixing it downloads only what changed since it last parsed.
+    #[test]
+    fn test_changed_pairs_unparsable() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tools.json");
+        let mut snapshot = Snapshot::new();
+        write(
+            &path,
+            &[pair("cat", "Concatenates", &[]), pair("ls", "Lists", &[])],
+        );
+        changed(&path, &mut snapshot, &[]);
+
+        fs::write(&path, "{ \"pairs\": [").unwrap();
+        assert!(changed(&path, &mut snapshot, &[]).is_empty());
+        assert_eq!(2, snapshot[&path].len());
+
+        write(
+            &path,
+            &[
+                pair("cat", "Concatenates", &[]),
+                pair("ls", "Lists files", &[]),
+            ],
+        );
+        assert_eq!(vec!["ls"], changed(&path, &mut snapshot, &[]));
+    }
+
+    /// Tests that only changed pairs with one of the tags are downloaded,
+    /// while the snapshot still records every pair.
+    #[test]
+    fn test_pairs_to_download_tags() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tools.json");
+        let mut snapshot = Snapshot::new();
+        write(
+            &path,
+            &[
+                pair("cat", "Concatenates", &["coreutils"]),
+                pair("grep", "Searches", &["search"]),
+            ],
+        );
+        assert_eq!(vec!["cat"], changed(&path, &mut snapshot, &["coreutils"]));
+        assert_eq!(2, snapshot[&path].len());
+
+        write(
+            &path,
+            &[
+                pair("cat", "Concatenates", &["coreutils"]),
+                pair("grep", "Searches files", &["search"]),
+            ],
+        );
+        assert!(changed(&path, &mut snapshot, &["coreutils"]).is_empty());
+    }
+}
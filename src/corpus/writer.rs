@@ -0,0 +1,265 @@
+//! # Metadata Writing
+//!
+//! This module writes parsed [`Metadata`] back to a metadata file, the
+//! inverse of [`parser::parse`], so that tools can generate or edit metadata
+//! without building its JSON by hand.  Metadata is written in the individual
+//! format, with the fields of every program spelled out, since [`Metadata`]
+//! does not record which pairs shared a project.  Optional fields holding
+//! their defaults are left out, and the rest are written in the order of
+//! the repository's own metadata files, starting with `program_name`, as
+//! they would be written by hand.
+//!
+//! [`write`] therefore refuses to replace a project metadata file.  Tools
+//! that edit existing metadata, in either format, change its JSON document
+//! and write that back with [`write_document`], which keeps the document's
+//! structure and the order of its keys.
+
+use std::{ffi::OsString, fs, path::Path};
+
+use serde_json::{Map, Value, json};
+
+use crate::corpus::{
+    errors::WriterError,
+    parser,
+    schema::{Metadata, Program, ProgramPair, SourceType},
+    utils,
+};
+
+/// Writes metadata to a file, which [`parser::parse`] reads back into equal
+/// metadata.
+///
+/// # Arguments
+///
+/// - `path` - The metadata file, which is replaced if it exists.
+/// - `metadata` - The metadata to write.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`WriterError`] if the metadata violates
+/// the schema, the file holds project metadata, or the file cannot be
+/// written; the file is left as it was then, since the contents are written
+/// to a temporary file beside it that replaces it only once complete.
+pub fn write(path: &Path, metadata: &Metadata) -> Result<(), WriterError> {
+    if is_project_file(path) {
+        return Err(WriterError::ProjectFile {
+            path: path.to_path_buf(),
+        });
+    }
+    write_contents(path, &to_contents(metadata)?)
+}
+
+/// Writes a metadata file's JSON document, such as one read from an existing
+/// file of either format and edited in place.
+///
+/// # Arguments
+///
+/// - `path` - The metadata file, which is replaced if it exists.
+/// - `document` - The JSON document to write.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or [`WriterError`] if the document violates
+/// the schema or the file cannot be written; the file is left as it was
+/// then, as with [`write()`].
+pub fn write_document(path: &Path, document: &Value) -> Result<(), WriterError> {
+    write_contents(path, &document_contents(document)?)
+}
+
+/// Replaces a file with new contents, through a temporary file beside it
+/// that replaces it only once complete.
+///
+/// # Arguments
+///
+/// - `path` - The file to write.
+/// - `contents` - Its new contents.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success and [`WriterError::IoWrite`] on failure.
+fn write_contents(path: &Path, contents: &str) -> Result<(), WriterError> {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    let temporary = path.with_file_name(name);
+    let result = fs::write(&temporary, contents).and_then(|()| fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result.map_err(|error| WriterError::IoWrite {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+/// Checks whether a file holds project metadata, which has
+/// `project_information` beside its pairs.
+///
+/// # Arguments
+///
+/// - `path` - The file, which need not exist.
+///
+/// # Returns
+///
+/// `true` if the file is a JSON object with `project_information`.
+fn is_project_file(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+        .is_some_and(|document| document.get("project_information").is_some())
+}
+
+/// Renders metadata as the contents [`write`] writes, so that several files
+/// can be checked before any is written.
+///
+/// # Arguments
+///
+/// - `metadata` - The metadata to render.
+///
+/// # Returns
+///
+/// The contents of the metadata file, or [`WriterError`] if the metadata
+/// violates the schema.
+pub fn to_contents(metadata: &Metadata) -> Result<String, WriterError> {
+    let pairs: Vec<Value> = metadata.pairs.iter().map(pair_json).collect();
+    document_contents(&json!({ "pairs": pairs }))
+}
+
+/// Renders a metadata file's JSON document, such as one read from an
+/// existing file and edited in place, after checking it against the schema.
+/// Unlike [`to_contents`], the document keeps its format and the order of
+/// its keys, so a project file keeps its `project_information`.
+///
+/// # Arguments
+///
+/// - `document` - The JSON document of the metadata file.
+///
+/// # Returns
+///
+/// The contents of the metadata file, or [`WriterError`] if the document
+/// violates the schema.
+pub fn document_contents(document: &Value) -> Result<String, WriterError> {
+    parser::validate_metadata(document).map_err(|error| WriterError::Invalid { error })?;
+    let contents =
+        utils::to_metadata_json(document).map_err(|error| WriterError::Serialize { error })?;
+    Ok(contents + "\n")
+}
+
+/// Builds the metadata entry of a program pair.
+///
+/// # Arguments
+///
+/// - `pair` - The program pair.
+///
+/// # Returns
+///
+/// The entry, as an element of an individual metadata file's `pairs`.
+fn pair_json(pair: &ProgramPair) -> Value {
+    let mut fields = Map::new();
+    fields.insert("program_name".into(), json!(pair.program_name));
+    fields.insert(
+        "program_description".into(),
+        json!(pair.program_description),
+    );
+    let tools: Vec<&str> = pair
+        .translation_tools
+        .iter()
+        .map(|tool| tool.to_str())
+        .collect();
+    fields.insert("translation_tools".into(), json!(tools));
+    fields.insert(
+        "feature_relationship".into(),
+        json!(pair.feature_relationship.to_str()),
+    );
+    fields.insert("c_program".into(), program_json(&pair.c_program));
+    fields.insert("rust_program".into(), program_json(&pair.rust_program));
+    if !pair.rust_variants.is_empty() {
+        let variants: Vec<Value> = pair
+            .rust_variants
+            .iter()
+            .map(|variant| {
+                json!({
+                    "label": variant.label,
+                    "program": program_json(&variant.program),
+                })
+            })
+            .collect();
+        fields.insert("rust_variants".into(), json!(variants));
+    }
+    if !pair.function_mappings.is_empty() {
+        fields.insert("function_mappings".into(), json!(pair.function_mappings));
+    }
+    if !pair.tags.is_empty() {
+        fields.insert("tags".into(), json!(pair.tags));
+    }
+    Value::Object(fields)
+}
+
+/// Builds the metadata of one program, leaving out the optional fields that
+/// hold their defaults.
+///
+/// # Arguments
+///
+/// - `program` - The C or Rust program.
+///
+/// # Returns
+///
+/// The program's `c_program` or `rust_program` object.
+fn program_json(program: &Program) -> Value {
+    let mut fields = Map::new();
+    fields.insert("documentation_url".into(), json!(program.documentation_url));
+    fields.insert("repository_url".into(), json!(program.repository_url));
+    if !program.mirror_urls.is_empty() {
+        fields.insert("mirror_urls".into(), json!(program.mirror_urls));
+    }
+    fields.insert("source_paths".into(), json!(program.source_paths));
+    if !program.exclude_paths.is_empty() {
+        fields.insert("exclude_paths".into(), json!(program.exclude_paths));
+    }
+    if !program.build_files.is_empty() {
+        fields.insert("build_files".into(), json!(program.build_files));
+    }
+    if !program.patches.is_empty() {
+        fields.insert("patches".into(), json!(program.patches));
+    }
+    if program.extensions.include != program.language.default_extensions() {
+        fields.insert(
+            "include_extensions".into(),
+            json!(program.extensions.include),
+        );
+    }
+    if !program.extensions.exclude.is_empty() {
+        fields.insert(
+            "exclude_extensions".into(),
+            json!(program.extensions.exclude),
+        );
+    }
+    if let Some(clone_depth) = program.clone_depth {
+        fields.insert("clone_depth".into(), json!(clone_depth));
+    }
+    if let Some(branch) = &program.branch {
+        fields.insert("branch".into(), json!(branch));
+    }
+    if program.needs_submodules {
+        fields.insert("needs_submodules".into(), json!(true));
+    }
+    if program.language != program.language.side() {
+        fields.insert("language".into(), json!(program.language.to_str()));
+    }
+    if program.source_type != SourceType::Git {
+        fields.insert("source_type".into(), json!(program.source_type.to_str()));
+    }
+    if let Some(checksum) = &program.checksum {
+        fields.insert("checksum".into(), json!(checksum));
+    }
+    if let Some(release) = &program.release {
+        fields.insert("release_tag".into(), json!(release.tag));
+        fields.insert("asset".into(), json!(release.asset));
+    }
+    if let Some(output_directory) = &program.output_directory {
+        fields.insert("output_directory".into(), json!(output_directory));
+    }
+    if let Some(flatten) = program.flatten {
+        fields.insert("flatten".into(), json!(flatten));
+    }
+    Value::Object(fields)
+}
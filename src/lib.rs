@@ -1,33 +1,308 @@
 //! # C-Rust program pair downloader
+//!
+//! Besides the binary, this crate is a library for research tools that
+//! read, write, or download the corpus themselves:
+//!
+//! - [`schema`] holds the parsed metadata of program pairs.
+//! - [`parse()`] reads a metadata file and [`write()`] writes one back;
+//!   [`write_document()`] writes back a file's edited JSON instead, such as
+//!   a project file's.
+//!   [`parse_with()`] reads one from a [`Storage`], such as the in-memory
+//!   [`MemoryStorage`], so that metadata tooling needs no file system.
+//! - [`Downloader`] downloads the program pairs of a corpus.
+//! - [`analysis::ast`] extracts the functions of C and Rust source files.
+//!
+//! Only the items exported here are public API, and they follow semantic
+//! versioning: an item is not removed or changed incompatibly without a new
+//! major version.  Types marked `#[non_exhaustive]`, such as the errors,
+//! [`DownloadOptions`], and [`schema::ProgramPair`], may gain variants or
+//! fields in minor versions.
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! let metadata = c_rust_program_pairs::parse(Path::new("metadata/individual/sha.json"))?;
+//! for pair in &metadata.pairs {
+//!     println!("{}: {}", pair.program_name, pair.program_description);
+//! }
+//! # Ok::<(), c_rust_program_pairs::ParserError>(())
+//! ```
 
 mod cli;
 mod corpus;
 mod paths;
 
-use std::{fs, io::Error, path::Path};
+use std::process::ExitCode;
 
 use clap::Parser;
 
-pub use corpus::download_program_pairs;
+pub use corpus::{
+    analysis, download_program_pairs, download_program_pairs_with_events,
+    downloader::{
+        ConflictPolicy, DownloadEvent, DownloadOptions, Downloader, GitBackend, Layout, LinkMode,
+    },
+    effects::{Fetcher, FileSystem, GitFetcher, MemoryStorage, Storage},
+    errors::{
+        Classify, DownloaderError, ErrorKind, ParserError, SchemaError, ValidationIssue,
+        ValueIssue, WriterError,
+    },
+    parser::{parse, parse_directory, parse_directory_with, parse_str, parse_with},
+    schema,
+    transform::Transform,
+    writer::{write, write_document},
+};
+
+#[cfg(feature = "async")]
+pub use corpus::downloader::async_downloader::download_program_pairs as download_program_pairs_async;
 
 use crate::{
-    cli::{Cli, Commands},
-    paths::{PROGRAM_PAIRS_DIRECTORY, REPOSITORY_CLONES_DIRECTORY},
+    cli::{CacheCommands, Cli, Commands, MetadataCommands},
+    corpus::{
+        build::BuildOptions,
+        config::Config,
+        report::{self, Context, ErrorFormat},
+    },
 };
 
 /// Downloads program pairs.
 ///
 /// Reads the command-line arguments supplied. If none are given, download
 /// all program pairs. If argument "demo" is given, download program pairs
-/// specified within the `demo/` directory.
-pub fn run() {
-    let cli = Cli::parse();
-    match cli.command {
-        None => corpus::download_program_pairs(false).expect("Failed to download program pairs"),
-        Some(Commands::Demo) => corpus::download_program_pairs(true).expect("Failed to run demo"),
-        Some(Commands::Download) => {
-            corpus::download_program_pairs(false).expect("Failed to download program pairs")
-        }
-        Some(Commands::Delete) => corpus::delete().expect("Failed to delete directories"),
+/// specified within the `demo/` directory.  Pass `--offline` to only use
+/// repositories that are already cached.
+///
+/// A failed command prints its error on stderr, as JSON if
+/// `--error-format json` is passed, and exits with the code of the error's
+/// [`ErrorKind`]: 1 for other failures, 2 for partial failures, 3 for
+/// invalid metadata, 4 for network failures, and 5 for I/O failures.
+pub fn run() -> ExitCode {
+    let cli = match Cli::try_parse().and_then(Cli::check_json) {
+        Ok(cli) => cli,
+        Err(error) => {
+            // Help and version requests are printed like errors, but succeed.
+            let _ = error.print();
+            return if error.use_stderr() {
+                ExitCode::from(ErrorKind::Other.exit_code())
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
+
+    // Relative schema and metadata paths are resolved before changing into
+    // the root.
+    if let Some(schema) = &cli.schema {
+        corpus::parser::set_schema_file(schema);
     }
+    if let Some(metadata_dir) = &cli.metadata_dir {
+        paths::set_metadata_directory(metadata_dir);
+    }
+    corpus::output::set_json(cli.json);
+    // Scripts reading results as JSON read errors as JSON too.
+    let error_format = if cli.json {
+        ErrorFormat::Json
+    } else {
+        cli.error_format
+    };
+
+    // Command-line flags override the defaults of the configuration file.
+    // The configuration file is looked up in the root given on the command
+    // line.
+    let config = match Config::load(cli.root.as_deref()).and_then(|config| {
+        config.apply(cli.jobs)?;
+        Ok(config)
+    }) {
+        Ok(config) => config,
+        Err(error) => {
+            let result = Err(error).context("Failed to load configuration");
+            return report::report(result, error_format);
+        }
+    };
+    let download_config = &config.download;
+
+    // Commands reading metadata explain the expected layout up front,
+    // rather than failing on the first metadata directory they read.  A
+    // download from a metadata repository creates the layout itself.
+    let fetches_metadata = download_config.metadata_repo.is_some()
+        || matches!(&cli.command, Some(Commands::Download(args)) if args.metadata_repo.is_some());
+    let downloads = matches!(cli.command, None | Some(Commands::Download(_)));
+    if !(fetches_metadata && downloads)
+        && cli.command.as_ref().is_none_or(Commands::reads_metadata)
+        && let Err(error) = corpus::init::check_layout()
+    {
+        let result = Err(error).context("Not in a corpus root");
+        return report::report(result, error_format);
+    }
+
+    let result = match cli.command {
+        None => corpus::download_program_pairs(false, &download_config.options())
+            .context("Failed to download program pairs"),
+        Some(Commands::Demo(args)) => {
+            corpus::download_program_pairs(true, &args.options(download_config))
+                .context("Failed to run demo")
+        }
+        Some(Commands::Download(args)) => {
+            corpus::download_program_pairs(false, &args.options(download_config))
+                .context("Failed to download program pairs")
+        }
+        Some(Commands::Watch(args)) => {
+            corpus::watch(&args.options(download_config)).context("Failed to watch metadata")
+        }
+        Some(Commands::Tui(args)) => {
+            corpus::tui(&args.options(download_config)).context("Failed to run interactive browser")
+        }
+        Some(Commands::Serve(args)) => {
+            corpus::serve(&args.address, &args.download.options(download_config))
+                .context("Failed to serve the corpus")
+        }
+        Some(Commands::Delete(args)) if args.unused => {
+            corpus::delete_unused().context("Failed to delete unused clones")
+        }
+        Some(Commands::Delete(args)) => {
+            corpus::delete(&args.scope(), args.yes).context("Failed to delete directories")
+        }
+        Some(Commands::Cache { command }) => match command {
+            CacheCommands::Size => corpus::cache::size(),
+            CacheCommands::Prune { max_size } => corpus::cache::prune(&max_size),
+            CacheCommands::Clear { repository } => corpus::cache::clear(&repository),
+            CacheCommands::Fsck { remove } => corpus::cache::fsck(remove),
+        }
+        .context("Failed to manage the repository cache"),
+        Some(Commands::Lint { paths, semantic }) => {
+            corpus::lint(&paths, semantic).context("Metadata is invalid")
+        }
+        Some(Commands::List { sort, tag }) => {
+            corpus::list(sort, &tag).context("Failed to list program pairs")
+        }
+        Some(Commands::Search { query, code }) => {
+            corpus::search(&query, code).context("Failed to search the corpus")
+        }
+        Some(Commands::Enrich) => corpus::enrich(&config.auth).context("Failed to enrich metadata"),
+        Some(Commands::Audit) => corpus::audit(&config.auth).context("Metadata has dead links"),
+        Some(Commands::Outdated) => {
+            corpus::outdated().context("Failed to check upstream repositories")
+        }
+        Some(Commands::Discover {
+            seed,
+            output,
+            limit,
+        }) => corpus::discover(&seed, &paths::metadata_path(output), limit, &config.auth)
+            .context("Failed to discover program pairs"),
+        Some(Commands::Metadata { command }) => match command {
+            MetadataCommands::Rust { crate_name } => corpus::metadata::rust(&crate_name),
+            MetadataCommands::Discover {
+                program_name,
+                repo,
+                crate_dir,
+                prune,
+                write,
+            } => corpus::metadata::discover(
+                &program_name,
+                &repo,
+                &crate_dir,
+                prune,
+                write.as_deref(),
+            ),
+        }
+        .context("Failed to look up metadata"),
+        Some(Commands::New { kind, name }) => {
+            corpus::metadata::new(kind, &name).context("Failed to create metadata")
+        }
+        Some(Commands::Rename { old, new }) => {
+            corpus::rename(&old, &new).context("Failed to rename program pair")
+        }
+        Some(Commands::Stats {
+            detailed,
+            tokens,
+            max_tokens,
+            by_tag,
+            unsafe_code,
+            complexity,
+            dedup,
+        }) => corpus::stats(
+            detailed,
+            tokens,
+            max_tokens,
+            by_tag,
+            unsafe_code,
+            complexity,
+            dedup,
+        )
+        .context("Failed to compute statistics"),
+        Some(Commands::Diff { pair }) => {
+            corpus::diff(&pair).context("Failed to compare program pair")
+        }
+        Some(Commands::Split {
+            train,
+            test,
+            seed,
+            materialize,
+        }) => corpus::split(train, test, seed, materialize).context("Failed to split corpus"),
+        Some(Commands::Export {
+            format,
+            granularity,
+            origin,
+            output,
+        }) => corpus::export(format, granularity, &origin, output.as_deref())
+            .context("Failed to export program pairs"),
+        Some(Commands::Doctor) => corpus::doctor().context("Environment has problems"),
+        Some(Commands::Init) => corpus::init().context("Failed to create the corpus layout"),
+        #[cfg(feature = "index")]
+        Some(Commands::Index { db }) => corpus::index(&db).context("Failed to index corpus"),
+        Some(Commands::Report { format, output }) => {
+            corpus::site::report(format, &output).context("Failed to write report")
+        }
+        Some(Commands::Show {
+            pair,
+            file,
+            layout,
+            width,
+        }) => corpus::show(&pair, file.as_deref(), layout, width)
+            .context("Failed to show program pair"),
+        Some(Commands::Functions { pair, suggest }) => {
+            corpus::diff::functions(&pair, suggest).context("Failed to list functions")
+        }
+        Some(Commands::Analyze {
+            pair,
+            translation_origin,
+            classify,
+        }) => corpus::analyze(pair.as_deref(), translation_origin, classify)
+            .context("Failed to analyze program pairs"),
+        Some(Commands::Update {
+            refresh_lock: _,
+            repositories,
+        }) => corpus::lock::refresh(&repositories).context("Failed to refresh lock file"),
+        Some(Commands::Snapshot { version }) => {
+            corpus::release::snapshot(&version).context("Failed to record snapshot")
+        }
+        Some(Commands::Changelog { from, to, output }) => {
+            corpus::release::changelog(&from, &to, output.as_deref())
+                .context("Failed to generate changelog")
+        }
+        Some(Commands::Publish {
+            remote,
+            message,
+            proxy,
+            ca_bundle,
+        }) => {
+            let mut options = download_config.options();
+            options.proxy = proxy.or(options.proxy);
+            options.ca_bundle = ca_bundle.or(options.ca_bundle);
+            corpus::publish(&remote, message.as_deref(), &options)
+                .context("Failed to publish the corpus")
+        }
+        Some(Commands::Build {
+            pair,
+            all: _,
+            c_mode,
+            cc,
+        }) => {
+            let options = BuildOptions {
+                c_mode,
+                compiler: cc,
+            };
+            corpus::build(pair.as_deref(), &options).context("Failed to build program pairs")
+        }
+    };
+    report::report(result, error_format)
 }
@@ -15,8 +15,9 @@
 //! ```sh
 //! cargo run demo
 //! ```
-use c_rust_program_pairs;
 
-fn main() {
-    c_rust_program_pairs::run();
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    c_rust_program_pairs::run()
 }
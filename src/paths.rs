@@ -1,7 +1,57 @@
 //! # Paths
 //!
 //! This module defines file system paths used throughout the project.
-//! All paths are relative to the project root.
+//! All paths are relative to the project root, except that the paths under
+//! `metadata/` resolve into the directory given with `--metadata-dir`, if
+//! any, through [`metadata_path`].
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// The directory given with `--metadata-dir`, as an absolute path.
+static METADATA_DIRECTORY_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Reads and writes metadata in a directory other than `metadata/`.  Only
+/// the first call has an effect.
+///
+/// # Arguments
+///
+/// - `directory` - The directory holding the metadata, resolved against the
+///   current directory if it is relative.
+pub fn set_metadata_directory(directory: &Path) {
+    let directory = std::path::absolute(directory).unwrap_or_else(|_| directory.to_path_buf());
+    let _ = METADATA_DIRECTORY_OVERRIDE.set(directory);
+}
+
+/// Resolves a path relative to the project root to where it is read from.
+///
+/// # Arguments
+///
+/// - `path` - A path relative to the project root, such as one of this
+///   module's constants.
+///
+/// # Returns
+///
+/// The path within the directory given with `--metadata-dir` if the path
+/// lies under `metadata/` and the directory was given, or the path itself
+/// otherwise.
+pub fn metadata_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    match (
+        METADATA_DIRECTORY_OVERRIDE.get(),
+        path.strip_prefix(METADATA_DIRECTORY),
+    ) {
+        (Some(directory), Ok(relative)) if relative.as_os_str().is_empty() => directory.clone(),
+        (Some(directory), Ok(relative)) => directory.join(relative),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Directory containing every metadata file, whose presence marks the corpus
+/// root.
+pub const METADATA_DIRECTORY: &str = "metadata";
 
 /// Path to the JSON schema for metadata files.
 pub const METADATA_SCHEMA_FILE: &str = "metadata/metadata.schema.json";
@@ -18,9 +68,69 @@ pub const INDIVIDUAL_METADATA_DIRECTORY: &str = "metadata/individual";
 /// for demo and testing purposes.
 pub const DEMO_METADATA_DIRECTORY: &str = "metadata/demo";
 
+/// File recording repository information, such as star counts, retrieved
+/// from the GitHub and GitLab APIs.
+pub const ENRICHED_METADATA_FILE: &str = "metadata/enriched/repositories.json";
+
+/// File recording the metadata files copied from the repository given with
+/// `--metadata-repo`, with the SHA-256 of each as copied, so that files
+/// changed locally are not overwritten and files the repository removed are
+/// removed too.
+pub const METADATA_SYNC_FILE: &str = "metadata/remote-files.json";
+
+/// File listing program pairs that are known to be broken, and why, so that
+/// they are skipped rather than downloaded.
+pub const SKIPLIST_FILE: &str = "metadata/skiplist.json";
+
+/// File naming curated subsets of the corpus, such as the pairs of a
+/// benchmark, that can be downloaded with `--profile`.
+pub const PROFILES_FILE: &str = "metadata/profiles.json";
+
+/// File pinning the commit each repository resolved to when it was first
+/// downloaded, so later downloads are reproducible.
+pub const LOCK_FILE: &str = "corpus.lock";
+
+/// File recording the duration, bytes downloaded, cache hit rate, pair
+/// outcomes, and peak disk usage of the latest download.
+pub const RUN_METRICS_FILE: &str = "run-metrics.json";
+
 /// Directory containing C-Rust program pairs.
 pub const PROGRAM_PAIRS_DIRECTORY: &str = "program_pairs";
 
+/// Directory in which program pairs are downloaded before they are moved
+/// into `program_pairs/`, so that a pair is never left half downloaded.
+pub const STAGING_DIRECTORY: &str = "program_pairs/.staging";
+
+/// Directory containing the manifests, and optionally the materialized
+/// directories, of the corpus's training, validation, and test splits.
+pub const SPLITS_DIRECTORY: &str = "program_pairs/splits";
+
+/// File recording which downloaded program pairs compile.
+pub const BUILD_RESULTS_FILE: &str = "program_pairs/build-results.json";
+
+/// File recording the analyses of downloaded program pairs, such as whether
+/// their Rust programs were machine-translated.
+pub const ANALYSIS_FILE: &str = "program_pairs/analysis.json";
+
+/// Directory of the content-addressed store, holding one copy of each
+/// distinct file content, named by its SHA-256, for `--link store`.
+pub const STORE_OBJECTS_DIRECTORY: &str = "store/objects";
+
+/// Directory containing snapshots of the corpus at each release, from which
+/// release notes are generated.
+pub const RELEASES_DIRECTORY: &str = "releases";
+
+/// Bare git repository recording each snapshot of `program_pairs/` pushed by
+/// `publish`.
+pub const PUBLISH_REPOSITORY_DIRECTORY: &str = "publish.git";
+
 /// Directory used as a local cache for repository clones to avoid repeatedly
 /// cloning repositories.
 pub const REPOSITORY_CLONES_DIRECTORY: &str = "repository_clones";
+
+/// File recording when each cached repository clone was last used.
+pub const CACHE_INDEX_FILE: &str = "repository_clones/cache-index.json";
+
+/// File recording how long each repository took to clone, for estimating
+/// how long downloads take.
+pub const TIMINGS_FILE: &str = "repository_clones/download-timings.json";
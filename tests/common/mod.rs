@@ -0,0 +1,248 @@
+//! # Test Fixtures
+//!
+//! This module creates throwaway working directories for end-to-end tests.
+//! Each [`Fixture`] holds the metadata schema, metadata files, and local git
+//! repositories referred to by `file://` URLs, so the full download pipeline
+//! runs without network access.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+};
+
+use git2::{BranchType, IndexAddOption, Repository, Signature, build::CheckoutBuilder};
+use serde_json::{Value, json};
+use tempfile::TempDir;
+
+/// A temporary directory laid out like the project root.
+pub struct Fixture {
+    directory: TempDir,
+}
+
+/// A local git repository created by a [`Fixture`].
+pub struct FixtureRepository {
+    /// The URL of the repository; `file://` for repositories created by
+    /// [`Fixture::repository`].
+    pub url: String,
+    /// The SHA of the repository's first commit.
+    pub head: String,
+}
+
+impl Fixture {
+    /// Creates a fixture with the metadata schema and empty metadata
+    /// directories.
+    pub fn new() -> Fixture {
+        let directory = TempDir::new().expect("Failed to create temporary directory");
+        let metadata = directory.path().join("metadata");
+        for subdirectory in ["project", "individual", "demo"] {
+            fs::create_dir_all(metadata.join(subdirectory))
+                .expect("Failed to create metadata directory");
+        }
+        fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("metadata/metadata.schema.json"),
+            metadata.join("metadata.schema.json"),
+        )
+        .expect("Failed to copy metadata schema");
+        Fixture { directory }
+    }
+
+    /// Returns a path within the fixture.
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.directory.path().join(relative)
+    }
+
+    /// Creates a git repository with one commit containing the given files.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - Name of the repository's directory.
+    /// - `files` - The `/`-separated path and contents of each file.
+    pub fn repository(&self, name: &str, files: &[(&str, &str)]) -> FixtureRepository {
+        let directory = self.path("repositories").join(name);
+        Repository::init(&directory).expect("Failed to create repository");
+        let head = self.commit(name, files);
+
+        // `file` URLs need `/` separators and a leading `/`, even on Windows.
+        let path = directory.to_string_lossy().replace('\\', "/");
+        FixtureRepository {
+            url: format!("file:///{}", path.trim_start_matches('/')),
+            head,
+        }
+    }
+
+    /// Writes files to a repository created by [`Fixture::repository`] and
+    /// commits them on top of its head, along with the removal of any file
+    /// deleted from its directory.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - Name of the repository's directory.
+    /// - `files` - The `/`-separated path and contents of each file.
+    ///
+    /// # Returns
+    ///
+    /// The SHA of the new commit.
+    pub fn commit(&self, name: &str, files: &[(&str, &str)]) -> String {
+        let directory = self.path("repositories").join(name);
+        let repository = Repository::open(&directory).expect("Failed to open repository");
+        for (path, contents) in files {
+            let path = directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+            fs::write(path, contents).expect("Failed to write file");
+        }
+
+        let mut index = repository.index().unwrap();
+        index
+            .add_all(["*"], IndexAddOption::DEFAULT, None)
+            .expect("Failed to stage files");
+        index
+            .update_all(["*"], None)
+            .expect("Failed to stage removed files");
+        index.write().unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Fixture", "fixture@example.com").unwrap();
+        let parent = repository
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let head = repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Add files",
+                &tree,
+                &parent.iter().collect::<Vec<_>>(),
+            )
+            .expect("Failed to commit files");
+        head.to_string()
+    }
+
+    /// Writes files to a branch of a repository created by
+    /// [`Fixture::repository`] and commits them on top of the branch, which
+    /// is created at the repository's head if it does not exist.  The
+    /// repository's head stays on its default branch.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - Name of the repository's directory.
+    /// - `branch` - Name of the branch.
+    /// - `files` - The `/`-separated path and contents of each file.
+    ///
+    /// # Returns
+    ///
+    /// The SHA of the new commit.
+    pub fn branch_commit(&self, name: &str, branch: &str, files: &[(&str, &str)]) -> String {
+        let directory = self.path("repositories").join(name);
+        let repository = Repository::open(&directory).expect("Failed to open repository");
+        let default_branch = repository.head().unwrap().name().unwrap().to_string();
+        if repository.find_branch(branch, BranchType::Local).is_err() {
+            let head = repository.head().unwrap().peel_to_commit().unwrap();
+            repository
+                .branch(branch, &head, false)
+                .expect("Failed to create branch");
+        }
+        let check_out = |reference: &str| {
+            repository.set_head(reference).unwrap();
+            repository
+                .checkout_head(Some(CheckoutBuilder::new().force()))
+                .expect("Failed to check out branch");
+        };
+        check_out(&format!("refs/heads/{branch}"));
+        let commit = self.commit(name, files);
+        check_out(&default_branch);
+        commit
+    }
+
+    /// Writes an individual metadata file.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - Name of the file, without the `.json` extension.
+    /// - `pairs` - The file's program pairs.
+    pub fn metadata(&self, name: &str, pairs: &[Value]) {
+        let path = self
+            .path("metadata/individual")
+            .join(format!("{name}.json"));
+        let metadata = json!({ "pairs": pairs });
+        fs::write(path, serde_json::to_string_pretty(&metadata).unwrap())
+            .expect("Failed to write metadata");
+    }
+
+    /// Runs the binary in the fixture, hiding the user's `corpus.toml`.
+    pub fn run(&self, arguments: &[&str]) -> Output {
+        self.command(arguments)
+            .output()
+            .expect("Failed to run binary")
+    }
+
+    /// Runs the binary in a directory of the fixture, such as to pass
+    /// `--root`.
+    pub fn run_in(&self, directory: &str, arguments: &[&str]) -> Output {
+        self.command(arguments)
+            .current_dir(self.path(directory))
+            .output()
+            .expect("Failed to run binary")
+    }
+
+    /// Runs the binary in the fixture with extra environment variables.
+    pub fn run_with_env(&self, arguments: &[&str], variables: &[(&str, &str)]) -> Output {
+        self.command(arguments)
+            .envs(variables.iter().copied())
+            .output()
+            .expect("Failed to run binary")
+    }
+
+    /// Starts the binary in the fixture without waiting for it, such as to
+    /// run a server; its output is discarded.
+    pub fn spawn(&self, arguments: &[&str]) -> Child {
+        self.command(arguments)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start binary")
+    }
+
+    /// Builds a command running the binary in the fixture, hiding the user's
+    /// `corpus.toml` and cache.
+    fn command(&self, arguments: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_c-rust-program-pairs"));
+        command
+            .args(arguments)
+            .current_dir(self.directory.path())
+            .env("XDG_CONFIG_HOME", self.path(".config"))
+            .env("XDG_CACHE_HOME", self.path(".cache"))
+            .env_remove("GITHUB_API_URL");
+        command
+    }
+}
+
+/// Builds an individual program pair.
+///
+/// # Arguments
+///
+/// - `name` - Name of the program.
+/// - `c_program` - The C repository and its source paths.
+/// - `rust_program` - The Rust repository and its source paths.
+pub fn pair(
+    name: &str,
+    c_program: (&FixtureRepository, &[&str]),
+    rust_program: (&FixtureRepository, &[&str]),
+) -> Value {
+    let program = |(repository, source_paths): (&FixtureRepository, &[&str])| {
+        json!({
+            "documentation_url": "https://example.com",
+            "repository_url": repository.url,
+            "source_paths": source_paths,
+        })
+    };
+    json!({
+        "program_name": name,
+        "program_description": format!("The {name} program."),
+        "translation_tools": ["manual"],
+        "feature_relationship": "rust_equivalent_to_c",
+        "c_program": program(c_program),
+        "rust_program": program(rust_program),
+    })
+}
@@ -0,0 +1,4324 @@
+//! End-to-end tests of the download pipeline against local repositories.
+
+mod common;
+
+use std::{fs, net::TcpListener, path::Path, thread};
+
+use common::{Fixture, FixtureRepository, pair};
+use serde_json::{Value, json};
+use walkdir::WalkDir;
+
+/// Creates the C and Rust repositories shared by the tests.
+fn repositories(fixture: &Fixture) -> (FixtureRepository, FixtureRepository) {
+    let c_repository = fixture.repository(
+        "c",
+        &[
+            ("hello.c", "int main(void) { return 0; }\n"),
+            ("lib/util.c", "void util(void) {}\n"),
+            ("lib/util.h", "void util(void);\n"),
+            ("lib/README.md", "Utilities\n"),
+        ],
+    );
+    let rust_repository = fixture.repository(
+        "rust",
+        &[
+            ("src/main.rs", "fn main() {}\n"),
+            ("Cargo.toml", "[package]\nname = \"hello\"\n"),
+        ],
+    );
+    (c_repository, rust_repository)
+}
+
+/// Lists the names of the files in a directory of the fixture.
+fn file_names(fixture: &Fixture, directory: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(fixture.path(directory))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Serves a JSON body for each of the given request paths, and `404 Not
+/// Found` for any other, ignoring query strings.
+///
+/// # Returns
+///
+/// The URL of the server.
+fn api_server(routes: &[(&str, Value)]) -> String {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let routes: Vec<(String, String)> = routes
+        .iter()
+        .map(|(path, body)| (path.to_string(), body.to_string()))
+        .collect();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request_line = String::new();
+            let mut reader = BufReader::new(&stream);
+            reader.read_line(&mut request_line).unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+            }
+            let target = request_line.split(' ').nth(1).unwrap_or_default();
+            let path = target.split('?').next().unwrap_or_default();
+            let response = match routes.iter().find(|(route, _)| route == path) {
+                Some((_, body)) => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\n\r\n{body}",
+                    body.len()
+                ),
+                None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string(),
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    format!("http://127.0.0.1:{port}")
+}
+
+/// Tests that a pair's files are copied, filtered by extension, and recorded
+/// with the commit they came from.
+#[test]
+fn test_download_copies_sources() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c", "lib"]),
+            (&rust_repository, &["src", "Cargo.toml"]),
+        )],
+    );
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["hello.c", "util.c", "util.h"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+    assert_eq!(
+        vec!["Cargo.toml", "main.rs"],
+        file_names(&fixture, "program_pairs/hello/rust-program")
+    );
+
+    let provenance: Value = serde_json::from_str(
+        &fs::read_to_string(fixture.path("program_pairs/hello/provenance.json")).unwrap(),
+    )
+    .unwrap();
+    let files = provenance["files"].as_array().unwrap();
+    assert_eq!(5, files.len());
+    for file in files {
+        let repository = if file["path"].as_str().unwrap().starts_with("c-program/") {
+            &c_repository
+        } else {
+            &rust_repository
+        };
+        assert_eq!(repository.url, file["repository_url"]);
+        assert_eq!(repository.head, file["commit"]);
+    }
+}
+
+/// Tests that repositories cloned with the system `git` binary, or with
+/// libgit2 if git is not installed, are copied from like libgit2's clones.
+#[test]
+fn test_download_with_git_cli() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    let output = fixture.run(&["download", "--git-backend", "cli"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["hello.c"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+    let provenance: Value = serde_json::from_str(
+        &fs::read_to_string(fixture.path("program_pairs/hello/provenance.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(c_repository.head, provenance["files"][0]["commit"]);
+}
+
+/// Tests that a shallow cached clone is deepened when more history is
+/// requested, with `--clone-depth` or `--full-history`, and kept otherwise.
+#[test]
+fn test_download_deepens_cached_clones() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    for version in 1..=4 {
+        fixture.commit("c", &[("hello.c", &format!("int version = {version};\n"))]);
+    }
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    // libgit2 cannot clone repositories on disk shallowly, but git can.
+    let download = |arguments: &[&str]| {
+        let mut command = vec!["download", "--force", "--git-backend", "cli"];
+        command.extend(arguments);
+        let output = fixture.run(&command);
+        assert!(output.status.success(), "{output:?}");
+        let clone = WalkDir::new(fixture.path("repository_clones"))
+            .into_iter()
+            .map(|entry| entry.unwrap().into_path())
+            .find(|path| path.ends_with("hello.c"))
+            .unwrap();
+        let repository = git2::Repository::open(clone.parent().unwrap()).unwrap();
+        let mut revwalk = repository.revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        (repository.is_shallow(), revwalk.count())
+    };
+
+    assert_eq!((true, 1), download(&[]));
+    assert_eq!((true, 3), download(&["--clone-depth", "3"]));
+    assert_eq!((true, 3), download(&["--clone-depth", "2"]));
+    assert_eq!((false, 5), download(&["--full-history"]));
+}
+
+/// Tests that the submodules of a repository whose metadata sets
+/// `needs_submodules` are cloned, so that source paths within them resolve.
+#[test]
+fn test_download_clones_submodules() {
+    let fixture = Fixture::new();
+    let (_, rust_repository) = repositories(&fixture);
+    let gnulib = fixture.repository("gnulib", &[("lib/util.c", "int util;\n")]);
+    let c_repository = fixture.repository("coreutils", &[("src/hello.c", "int main;\n")]);
+    let repository = git2::Repository::open(fixture.path("repositories/coreutils")).unwrap();
+    let mut submodule = repository
+        .submodule(&gnulib.url, Path::new("gnulib"), true)
+        .unwrap();
+    submodule.clone(None).unwrap();
+    submodule.add_finalize().unwrap();
+    fixture.commit("coreutils", &[]);
+
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["src/hello.c", "gnulib/lib/util.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("hello", std::slice::from_ref(&hello));
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+
+    hello["c_program"]["needs_submodules"] = json!(true);
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["hello.c", "util.c"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+}
+
+/// Tests that `--isolate-git-config` ignores the user's git configuration,
+/// such as a URL rewrite that breaks every clone.
+#[test]
+fn test_download_isolates_git_config() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    fs::create_dir_all(fixture.path(".config/git")).unwrap();
+    fs::write(
+        fixture.path(".config/git/config"),
+        "[url \"file:///nonexistent/\"]\n\tinsteadOf = file:///\n",
+    )
+    .unwrap();
+
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+    let output = fixture.run(&["download", "--isolate-git-config"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["hello.c"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+}
+
+/// Tests that a clone from a server that never responds is aborted at the
+/// timeout and its pair counted as failed.
+#[test]
+fn test_download_times_out() {
+    let fixture = Fixture::new();
+    let (_, rust_repository) = repositories(&fixture);
+
+    // Accept connections but never answer them.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let _connections: Vec<_> = listener.incoming().collect();
+    });
+    let stalled_repository = FixtureRepository {
+        url: format!("git://127.0.0.1:{port}/c"),
+        head: String::new(),
+    };
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&stalled_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    for backend in ["libgit2", "cli"] {
+        let output = fixture.run(&["download", "--timeout", "1", "--git-backend", backend]);
+        assert_eq!(Some(2), output.status.code(), "{output:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("Timed out after 1s"),
+            "{output:?}"
+        );
+    }
+}
+
+/// Tests that a pair that fails to download does not stop the others, and
+/// that the failure is reported through the exit code and on a line of its
+/// own.
+#[test]
+fn test_download_reports_partial_failure() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "pairs",
+        &[
+            pair(
+                "hello",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            ),
+            pair(
+                "missing",
+                (&c_repository, &["missing.c"]),
+                (&rust_repository, &["src"]),
+            ),
+        ],
+    );
+
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+    assert!(fixture.path("program_pairs/hello/manifest.json").is_file());
+    assert!(!fixture.path("program_pairs/missing/manifest.json").exists());
+
+    // Without a terminal the bars are hidden, and each message is printed
+    // on a line of its own rather than over a bar.
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains(['\r', '\x1b']), "{stderr:?}");
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert!(lines.contains(&"Downloaded 'hello'"), "{stderr}");
+    assert_eq!(
+        1,
+        lines
+            .iter()
+            .filter(|line| line.starts_with("Failed to download 'missing': "))
+            .count(),
+        "{stderr}"
+    );
+}
+
+/// Tests that `exclude_paths` leaves out files and directories, whether they
+/// are in a `source_paths` directory or listed in `source_paths` themselves.
+#[test]
+fn test_download_excludes_paths() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository(
+        "c",
+        &[
+            ("hello.c", "int main(void) { return 0; }\n"),
+            ("generated.c", "int generated;\n"),
+            ("lib/util.c", "void util(void) {}\n"),
+            ("lib/util.h", "void util(void);\n"),
+            ("lib/tests/test_util.c", "int main(void) { return 0; }\n"),
+        ],
+    );
+    let (_, rust_repository) = repositories(&fixture);
+    let mut entry = pair(
+        "hello",
+        (&c_repository, &["hello.c", "generated.c", "lib"]),
+        (&rust_repository, &["src"]),
+    );
+    entry["c_program"]["exclude_paths"] = json!(["generated.c", "lib/tests", "lib/util.h"]);
+    fixture.metadata("hello", &[entry]);
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["hello.c", "util.c"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+    let provenance =
+        fs::read_to_string(fixture.path("program_pairs/hello/provenance.json")).unwrap();
+    assert!(!provenance.contains("generated.c"), "{provenance}");
+}
+
+/// Tests that `--json` prints the results of `download`, `list`, `stats`,
+/// `lint`, and `delete` as JSON on stdout, and errors as JSON on stderr, and
+/// that commands printing only text reject it.
+#[test]
+fn test_json_output() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "pairs",
+        &[
+            pair(
+                "hello",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            ),
+            pair(
+                "missing",
+                (&c_repository, &["missing.c"]),
+                (&rust_repository, &["src"]),
+            ),
+        ],
+    );
+    let run = |arguments: &[&str]| {
+        let output = fixture.run(arguments);
+        let result: Value = serde_json::from_slice(&output.stdout)
+            .unwrap_or_else(|error| panic!("{error}: {output:?}"));
+        (output, result)
+    };
+
+    let (output, result) = run(&["download", "--json"]);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+    assert_eq!(2, result["total_pairs"]);
+    assert_eq!(
+        json!({"status": "downloaded", "program_name": "hello"}),
+        result["pairs"][0]
+    );
+    assert_eq!("failed", result["pairs"][1]["status"]);
+    // The error follows the progress messages on stderr.
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let error: Value = serde_json::from_str(stderr.lines().last().unwrap()).unwrap();
+    assert_eq!("partial_failure", error["kind"]);
+
+    let (output, result) = run(&["list", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!("hello", result["pairs"][0]["program_name"]);
+    assert_eq!("missing", result["pairs"][1]["program_name"]);
+
+    let (output, result) = run(&["stats", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!("hello", result["pairs"][0]["program_name"]);
+    assert_eq!(1, result["pairs"][0]["c_program"]["lines"]["code"]);
+    assert_eq!(json!([]), result["pairs"][0]["c_program"]["files"]);
+    // Unsafe code and complexity are only analyzed when asked for.
+    assert!(
+        result["pairs"][0]["rust_program"]
+            .get("unsafe_code")
+            .is_none()
+    );
+    assert!(result["pairs"][0]["c_program"].get("complexity").is_none());
+    let (output, result) = run(&["stats", "--json", "--unsafe", "--complexity"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        0,
+        result["pairs"][0]["rust_program"]["unsafe_code"]["unsafe_blocks"]
+    );
+    assert_eq!(
+        1,
+        result["pairs"][0]["c_program"]["complexity"]["functions"]
+    );
+
+    let (output, result) = run(&["lint", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(json!([]), result["invalid_files"]);
+
+    let (output, result) = run(&["delete", "--pairs", "--yes", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!("program_pairs", result["deleted"][0]["path"]);
+    assert!(!fixture.path("program_pairs").exists());
+
+    // Without program_pairs/, `stats` fails to read it rather than panicking.
+    let output = fixture.run(&["stats"]);
+    assert_eq!(Some(5), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Failed to read 'program_pairs'"),
+        "{output:?}"
+    );
+
+    let (output, result) = run(&["cache", "size", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(2, result["clones"].as_array().unwrap().len(), "{result}");
+    assert_eq!(2, result["clones"][0]["references"], "{result}");
+
+    let (output, result) = run(&["cache", "prune", "--max-size", "0", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(2, result["removed"].as_array().unwrap().len(), "{result}");
+    assert_eq!(0, result["total_bytes"], "{result}");
+
+    for arguments in [&["show", "hello", "--json"][..], &["--json", "outdated"]] {
+        let output = fixture.run(arguments);
+        assert_eq!(Some(1), output.status.code(), "{output:?}");
+        assert!(output.stdout.is_empty(), "{output:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("--json is only supported by"),
+            "{output:?}"
+        );
+    }
+}
+
+/// Tests that each class of failure exits with its own code.
+#[test]
+fn test_exit_codes() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["missing.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    // A port nothing listens on, so connecting to it is refused.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let unreachable = format!("https://127.0.0.1:{port}/metadata.git");
+
+    let exit_code = |arguments: &[&str]| fixture.run(arguments).status.code();
+    assert_eq!(Some(5), exit_code(&["stats"]));
+    assert_eq!(Some(1), exit_code(&["rename", "missing", "renamed"]));
+    assert_eq!(Some(2), exit_code(&["download"]));
+    assert_eq!(
+        Some(4),
+        exit_code(&["download", "--metadata-repo", &unreachable])
+    );
+    fs::write(fixture.path("metadata/individual/hello.json"), "{").unwrap();
+    assert_eq!(Some(3), exit_code(&["download"]));
+}
+
+/// Tests that `build` records which programs of the downloaded pairs
+/// compile, with the compiler output of those that do not.
+#[test]
+fn test_build() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository(
+        "c",
+        &[
+            ("hello.c", "int main(void) { return 0; }\n"),
+            ("broken.c", "int main(void) { return }\n"),
+        ],
+    );
+    let rust_repository = fixture.repository(
+        "rust",
+        &[
+            ("src/main.rs", "fn main() {}\n"),
+            // The binary is where the download flattens `src/main.rs` to.
+            (
+                "Cargo.toml",
+                "[package]\nname = \"hello\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+                 [[bin]]\nname = \"hello\"\npath = \"main.rs\"\n\n[workspace]\n",
+            ),
+        ],
+    );
+    fixture.metadata(
+        "hello",
+        &[
+            pair(
+                "broken",
+                (&c_repository, &["broken.c"]),
+                (&rust_repository, &["src"]),
+            ),
+            pair(
+                "hello",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["Cargo.toml", "src"]),
+            ),
+        ],
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    // A program that does not compile is recorded rather than failing.
+    let output = fixture.run(&["build", "--all"]);
+    assert!(output.status.success(), "{output:?}");
+    let results = fs::read_to_string(fixture.path("program_pairs/build-results.json")).unwrap();
+    let results: Value = serde_json::from_str(&results).unwrap();
+    assert_eq!(true, results["hello"]["c_program"]["compiled"], "{results}");
+    assert_eq!(
+        true, results["hello"]["rust_program"]["compiled"],
+        "{results}"
+    );
+    assert_eq!(false, results["broken"]["c_program"]["compiled"]);
+    assert!(
+        results["broken"]["c_program"]["log"]
+            .as_str()
+            .unwrap()
+            .contains("broken.c"),
+        "{results}"
+    );
+    assert_eq!(
+        "No Cargo.toml found",
+        results["broken"]["rust_program"]["log"]
+    );
+
+    // A compiler that cannot be run fails the C program too.
+    let output = fixture.run(&["build", "hello", "--cc", "missing-cc"]);
+    assert!(output.status.success(), "{output:?}");
+    let results = fs::read_to_string(fixture.path("program_pairs/build-results.json")).unwrap();
+    let results: Value = serde_json::from_str(&results).unwrap();
+    assert_eq!(false, results["hello"]["c_program"]["compiled"]);
+    assert_eq!(false, results["broken"]["c_program"]["compiled"]);
+
+    let output = fixture.run(&["build", "hello", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(true, result["pairs"]["hello"]["c_program"]["compiled"]);
+    // Only the pairs built are printed, not every recorded result.
+    assert_eq!(1, result["pairs"].as_object().unwrap().len(), "{result}");
+
+    let output = fixture.run(&["build", "missing"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+}
+
+/// Tests that a program whose repository URL cannot be cloned is copied from
+/// its mirror, which later downloads then use first.
+#[test]
+fn test_download_falls_back_to_mirrors() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let missing_url = format!("{}-missing", c_repository.url);
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    hello["c_program"]["repository_url"] = json!(missing_url);
+    hello["c_program"]["mirror_urls"] = json!([c_repository.url]);
+    fixture.metadata("hello", std::slice::from_ref(&hello));
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(&format!("trying '{}'", c_repository.url)),
+        "{output:?}"
+    );
+    assert_eq!(
+        vec!["hello.c"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+    let manifest: Value = serde_json::from_str(
+        &fs::read_to_string(fixture.path("program_pairs/hello/manifest.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(missing_url, manifest["c_program"]["repository_url"]);
+    assert_eq!(c_repository.url, manifest["c_program"]["mirror_url"]);
+
+    // The cached mirror is tried before the repository URL.
+    let output = fixture.run(&["download", "--force"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        !String::from_utf8_lossy(&output.stderr).contains("trying"),
+        "{output:?}"
+    );
+
+    // Once every URL fails, the pair fails.
+    hello["c_program"]["mirror_urls"] = json!([format!("{}-gone", c_repository.url)]);
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["download", "--force"]);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+}
+
+/// Tests that `search` matches pair names and descriptions ignoring case, and
+/// with `--code` the lines of the downloaded sources.
+#[test]
+fn test_search() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "pairs",
+        &[
+            pair(
+                "hello",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            ),
+            pair(
+                "util",
+                (&c_repository, &["lib"]),
+                (&rust_repository, &["src"]),
+            ),
+        ],
+    );
+    let search = |arguments: &[&str]| {
+        let mut command = vec!["search"];
+        command.extend(arguments);
+        let output = fixture.run(&command);
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    let stdout = search(&["UTIL"]);
+    assert!(
+        stdout.contains("util ") && !stdout.contains("hello "),
+        "{stdout}"
+    );
+    assert!(stdout.contains("1 matching pairs"), "{stdout}");
+    assert!(search(&["program"]).contains("2 matching pairs"));
+
+    // Sources are only searched with `--code`, once downloaded.
+    assert!(!search(&["main"]).contains("matching source lines"));
+    assert!(search(&["main", "--code"]).contains("No program pairs downloaded"));
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = search(&["MAIN", "--code"]);
+    assert!(stdout.contains("0 matching pairs"), "{stdout}");
+    assert!(stdout.contains("hello (c)"), "{stdout}");
+    assert!(
+        stdout.contains("hello.c:1: int main(void) { return 0; }"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("3 matching source lines"), "{stdout}");
+}
+
+/// Tests that `delete` only removes the directories its flags select, and
+/// nothing unless confirmed.
+#[test]
+fn test_delete_scopes() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "pairs",
+        &[
+            pair(
+                "hello",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            ),
+            pair(
+                "util",
+                (&c_repository, &["lib"]),
+                (&rust_repository, &["src"]),
+            ),
+        ],
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    let exists = |path: &str| fixture.path(path).exists();
+
+    // Without `--yes`, the prompt reads the end of input as no.
+    let output = fixture.run(&["delete", "--pair", "hello"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Aborted"));
+    assert!(exists("program_pairs/hello"));
+
+    let output = fixture.run(&["delete", "--pair", "hello", "--yes"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!exists("program_pairs/hello"));
+    assert!(exists("program_pairs/util"));
+
+    let output = fixture.run(&["delete", "--pair", "hello", "--yes"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+
+    let output = fixture.run(&["delete", "--language", "rust", "--yes"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!exists("program_pairs/util/rust-program"));
+    assert!(exists("program_pairs/util/c-program"));
+
+    let output = fixture.run(&["delete", "--clones", "--yes"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!exists("repository_clones"));
+    assert!(exists("program_pairs/util/c-program"));
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    let output = fixture.run(&["delete", "--yes"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!exists("program_pairs") && !exists("repository_clones"));
+}
+
+/// Tests that `delete --unused` removes the clones no metadata references
+/// any more, and keeps the clones that a pair still references.
+#[test]
+fn test_delete_unused() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let old_repository = fixture.repository("old", &[("old.c", "int main(void) { return 0; }\n")]);
+    let hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    let old = pair(
+        "old",
+        (&old_repository, &["old.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("pairs", &[hello.clone(), old]);
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    let clones = || {
+        let mut clones: Vec<String> = WalkDir::new(fixture.path("repository_clones"))
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.path().join(".git").is_dir())
+            .filter_map(|entry| Some(entry.file_name().to_str()?.to_string()))
+            .collect();
+        clones.sort();
+        clones
+    };
+    assert_eq!(vec!["c", "old", "rust"], clones());
+
+    // With every clone referenced, nothing is removed.
+    let output = fixture.run(&["delete", "--unused"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(vec!["c", "old", "rust"], clones());
+
+    fixture.metadata("pairs", &[hello]);
+    let output = fixture.run(&["delete", "--unused", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let deleted = result["deleted"].as_array().unwrap();
+    assert_eq!(1, deleted.len(), "{result}");
+    assert!(
+        deleted[0]["path"].as_str().unwrap().ends_with("old"),
+        "{result}"
+    );
+    assert_eq!(vec!["c", "rust"], clones());
+
+    // The kept clones are still used by downloads.
+    let output = fixture.run(&["download", "--offline"]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+/// Tests that offline downloads use cached clones and skip repositories that
+/// are not cached.
+#[test]
+fn test_download_offline() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    let output = fixture.run(&["download", "--offline"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!fixture.path("program_pairs/hello").exists());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("is not cached and offline mode forbids cloning it"),
+        "{output:?}"
+    );
+    assert!(!fixture.path("repository_clones").exists());
+
+    assert!(fixture.run(&["download"]).status.success());
+    fs::remove_dir_all(fixture.path("repositories")).unwrap();
+    fs::remove_dir_all(fixture.path("program_pairs")).unwrap();
+    let output = fixture.run(&["download", "--offline"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        fixture
+            .path("program_pairs/hello/c-program/hello.c")
+            .is_file()
+    );
+
+    // A pair with a repository that is not cached is skipped, while the
+    // others are still copied from the cache.
+    let other = fixture.repository("other", &[("src/main.rs", "fn main() {}\n")]);
+    fixture.metadata(
+        "other",
+        &[pair(
+            "other",
+            (&c_repository, &["hello.c"]),
+            (&other, &["src"]),
+        )],
+    );
+    fs::remove_dir_all(fixture.path("program_pairs")).unwrap();
+    let output = fixture.run(&["download", "--offline"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(fixture.path("program_pairs/hello").is_dir());
+    assert!(!fixture.path("program_pairs/other").exists());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("'{}' is not cached", other.url)),
+        "{stderr}"
+    );
+}
+
+/// Tests that `--metadata-repo` downloads the pairs of a metadata repository
+/// without local metadata, picks up its later commits, including removed
+/// files, and does not overwrite metadata changed locally without
+/// `--force`.
+#[test]
+fn test_download_metadata_repository() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let metadata_file = |name: &str| {
+        let pairs = [pair(
+            name,
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )];
+        serde_json::to_string_pretty(&json!({ "pairs": pairs })).unwrap()
+    };
+    let metadata_repository = fixture.repository(
+        "metadata",
+        &[("metadata/individual/hello.json", &metadata_file("hello"))],
+    );
+    fs::remove_dir_all(fixture.path("metadata")).unwrap();
+
+    let output = fixture.run(&["download", "--metadata-repo", &metadata_repository.url]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(fixture.path("metadata/individual/hello.json").is_file());
+    assert!(fixture.path("program_pairs/hello").is_dir());
+
+    fixture.commit(
+        "metadata",
+        &[("metadata/individual/world.json", &metadata_file("world"))],
+    );
+    let output = fixture.run(&["download", "--metadata-repo", &metadata_repository.url]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Updated 1 metadata files"),
+        "{output:?}"
+    );
+    assert_eq!(
+        vec!["hello", "world"],
+        file_names(&fixture, "program_pairs")
+    );
+
+    let local = fixture.path("metadata/individual/hello.json");
+    fs::write(&local, metadata_file("local")).unwrap();
+    fixture.commit(
+        "metadata",
+        &[("metadata/individual/hello.json", &metadata_file("remote"))],
+    );
+    let output = fixture.run(&["download", "--metadata-repo", &metadata_repository.url]);
+    assert!(!output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("individual/hello.json"),
+        "{output:?}"
+    );
+    assert_eq!(metadata_file("local"), fs::read_to_string(&local).unwrap());
+    let output = fixture.run(&[
+        "download",
+        "--force",
+        "--metadata-repo",
+        &metadata_repository.url,
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(metadata_file("remote"), fs::read_to_string(&local).unwrap());
+
+    fs::remove_file(fixture.path("repositories/metadata/metadata/individual/world.json")).unwrap();
+    fixture.commit("metadata", &[]);
+    let output = fixture.run(&["download", "--metadata-repo", &metadata_repository.url]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!fixture.path("metadata/individual/world.json").exists());
+}
+
+/// Tests that a metadata repository without `metadata/` is rejected unless
+/// `--subdirectory` names the directory holding its metadata.
+#[test]
+fn test_download_metadata_repository_subdirectory() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let pairs = [pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    )];
+    let metadata_repository = fixture.repository(
+        "metadata",
+        &[(
+            "corpus/individual/hello.json",
+            &serde_json::to_string_pretty(&json!({ "pairs": pairs })).unwrap(),
+        )],
+    );
+    fs::remove_dir_all(fixture.path("metadata")).unwrap();
+
+    let output = fixture.run(&["download", "--metadata-repo", &metadata_repository.url]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    let output = fixture.run(&[
+        "download",
+        "--metadata-repo",
+        &metadata_repository.url,
+        "--subdirectory",
+        "missing",
+    ]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("has no directory 'missing'"),
+        "{output:?}"
+    );
+
+    let output = fixture.run(&[
+        "download",
+        "--metadata-repo",
+        &metadata_repository.url,
+        "--subdirectory",
+        "corpus",
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(fixture.path("metadata/individual/hello.json").is_file());
+    assert!(fixture.path("program_pairs/hello").is_dir());
+}
+
+/// Tests that a metadata repository with an invalid file, such as one naming
+/// a pair outside `program_pairs/`, is rejected before anything is copied,
+/// and that `delete --unused` keeps the clone of the metadata repository.
+#[test]
+fn test_download_metadata_repository_validation() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let metadata_file = |name: &str| {
+        let pairs = [pair(
+            name,
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )];
+        serde_json::to_string_pretty(&json!({ "pairs": pairs })).unwrap()
+    };
+    let metadata_repository = fixture.repository(
+        "metadata",
+        &[
+            ("metadata/individual/hello.json", &metadata_file("hello")),
+            (
+                "metadata/individual/victim.json",
+                &metadata_file("../victim"),
+            ),
+        ],
+    );
+    fs::remove_dir_all(fixture.path("metadata")).unwrap();
+
+    let output = fixture.run(&["download", "--metadata-repo", &metadata_repository.url]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("individual/victim.json"),
+        "{output:?}"
+    );
+    assert!(!fixture.path("metadata/individual/hello.json").exists());
+    assert!(!fixture.path("program_pairs").exists());
+    assert!(!fixture.path("victim").exists());
+
+    fs::remove_file(fixture.path("repositories/metadata/metadata/individual/victim.json")).unwrap();
+    fixture.commit("metadata", &[]);
+    let output = fixture.run(&["download", "--metadata-repo", &metadata_repository.url]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(vec!["hello"], file_names(&fixture, "program_pairs"));
+
+    let output = fixture.run(&["delete", "--unused"]);
+    assert!(output.status.success(), "{output:?}");
+    let output = fixture.run(&[
+        "download",
+        "--offline",
+        "--metadata-repo",
+        &metadata_repository.url,
+    ]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+/// Tests that `demo` writes the built-in demo metadata outside a checkout,
+/// and leaves existing demo metadata alone.
+#[test]
+fn test_demo_writes_built_in_metadata() {
+    let fixture = Fixture::new();
+    fs::remove_dir_all(fixture.path("metadata")).unwrap();
+
+    // Offline, the demo's repositories are skipped rather than cloned.
+    let output = fixture.run(&["demo", "--offline"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Wrote the built-in demo metadata"),
+        "{output:?}"
+    );
+    let demo_file = fixture.path("metadata/demo/diffutils.json");
+    assert_eq!(
+        fs::read_to_string("metadata/demo/diffutils.json").unwrap(),
+        fs::read_to_string(&demo_file).unwrap()
+    );
+
+    fs::write(&demo_file, r#"{"pairs": []}"#).unwrap();
+    let output = fixture.run(&["demo", "--offline"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(r#"{"pairs": []}"#, fs::read_to_string(&demo_file).unwrap());
+}
+
+/// Tests that commands reading metadata outside a corpus root fail with the
+/// expected layout, and that `init` creates it.
+#[test]
+fn test_init() {
+    let fixture = Fixture::new();
+    fs::remove_dir_all(fixture.path("metadata")).unwrap();
+
+    let output = fixture.run(&["list"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("run `init`"),
+        "{output:?}"
+    );
+
+    let output = fixture.run(&["init"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["demo", "individual", "metadata.schema.json", "project"],
+        file_names(&fixture, "metadata")
+    );
+    assert!(fixture.path("metadata/demo/diffutils.json").is_file());
+    assert!(fixture.run(&["list"]).status.success());
+    assert!(fixture.run(&["lint"]).status.success());
+
+    let output = fixture.run(&["init"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("already exists"),
+        "{output:?}"
+    );
+}
+
+/// Tests that `--metadata-dir` reads and creates metadata outside the corpus
+/// root's `metadata/`.
+#[test]
+fn test_metadata_dir() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    fs::rename(fixture.path("metadata"), fixture.path("corpus-metadata")).unwrap();
+
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("--metadata-dir"),
+        "{output:?}"
+    );
+    let output = fixture.run(&["download", "--metadata-dir", "corpus-metadata"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(vec!["hello"], file_names(&fixture, "program_pairs"));
+    assert!(!fixture.path("metadata").exists());
+
+    let output = fixture.run(&["init", "--metadata-dir", "other-metadata"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["demo", "individual", "metadata.schema.json", "project"],
+        file_names(&fixture, "other-metadata")
+    );
+    assert!(!fixture.path("metadata").exists());
+}
+
+/// Tests that a cached clone missing files from its working directory is
+/// reported by `cache fsck` and cloned again by the next download.
+#[test]
+fn test_download_reclones_unhealthy_cache() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    assert!(fixture.run(&["download"]).status.success());
+    assert!(fixture.run(&["cache", "fsck"]).status.success());
+
+    let cached_file = WalkDir::new(fixture.path("repository_clones"))
+        .into_iter()
+        .map(|entry| entry.unwrap().into_path())
+        .find(|path| path.ends_with("hello.c"))
+        .unwrap();
+    fs::remove_file(cached_file).unwrap();
+    fs::remove_dir_all(fixture.path("program_pairs")).unwrap();
+    assert_eq!(Some(1), fixture.run(&["cache", "fsck"]).status.code());
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("is unhealthy"),
+        "{output:?}"
+    );
+    assert!(
+        fixture
+            .path("program_pairs/hello/c-program/hello.c")
+            .is_file()
+    );
+    assert!(fixture.run(&["cache", "fsck"]).status.success());
+}
+
+/// Tests that `cache size` reports every clone, that `cache prune` removes
+/// the least recently used clones first, and that `cache clear` rejects a
+/// name that several clones share.
+#[test]
+fn test_cache_size_prune_and_clear() {
+    let fixture = Fixture::new();
+    // Four clones of 1000 bytes each, and an index recording when three of
+    // them were last used; `unused` was not used since the index was written.
+    let clones = [
+        ("github.com/one/tool", Some(300)),
+        ("github.com/two/tool", Some(100)),
+        ("gitlab.com/three/lib", Some(200)),
+        ("gitlab.com/four/unused", None),
+    ];
+    let mut last_used = serde_json::Map::new();
+    for (clone, used) in clones {
+        let directory = fixture.path("repository_clones").join(clone);
+        fs::create_dir_all(directory.join(".git")).unwrap();
+        fs::write(directory.join("data"), [0; 1000]).unwrap();
+        if let Some(used) = used {
+            last_used.insert(clone.to_string(), json!(used));
+        }
+    }
+    fs::write(
+        fixture.path("repository_clones/cache-index.json"),
+        json!({ "last_used": last_used }).to_string(),
+    )
+    .unwrap();
+    let exists = |clone: &str| fixture.path("repository_clones").join(clone).exists();
+
+    let output = fixture.run(&["cache", "size"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("in 4 clones"), "{stdout}");
+    for (clone, _) in clones {
+        assert!(stdout.contains(clone), "{stdout}");
+    }
+    let line = |clone: &str| stdout.lines().find(|line| line.ends_with(clone)).unwrap();
+    assert!(line("four/unused").contains("never"), "{stdout}");
+    assert!(line("one/tool").contains("d ago"), "{stdout}");
+
+    let output = fixture.run(&["cache", "clear", "tool"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("matches several cached clones")
+            && stderr.contains("github.com/one/tool")
+            && stderr.contains("github.com/two/tool"),
+        "{stderr}"
+    );
+    assert!(exists("github.com/one/tool") && exists("github.com/two/tool"));
+
+    let output = fixture.run(&["cache", "prune", "--max-size", "huge"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+    let output = fixture.run(&["cache", "prune", "--max-size", "2000"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!exists("gitlab.com/four/unused") && !exists("github.com/two/tool"));
+    assert!(exists("gitlab.com/three/lib") && exists("github.com/one/tool"));
+    let output = fixture.run(&["cache", "prune", "--max-size", "1K"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!exists("gitlab.com/three/lib") && exists("github.com/one/tool"));
+
+    // Once only one clone has the name, it is cleared, along with the
+    // directories it leaves empty and its entry in the index.
+    let output = fixture.run(&["cache", "clear", "tool"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!fixture.path("repository_clones/github.com").exists());
+    let index = fs::read_to_string(fixture.path("repository_clones/cache-index.json")).unwrap();
+    assert!(!index.contains("tool"), "{index}");
+    let output = fixture.run(&["cache", "clear", "tool"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+}
+
+/// Tests that concurrent downloads sharing a clone both succeed, and that a
+/// clone whose download was killed is restored before its files are copied.
+#[test]
+fn test_download_locks_clones() {
+    let fixture = Fixture::new();
+    let repository = fixture.repository(
+        "hello",
+        &[
+            ("hello.c", "int main(void) { return 0; }\n"),
+            ("src/main.rs", "fn main() {}\n"),
+        ],
+    );
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&repository, &["hello.c"]),
+            (&repository, &["src"]),
+        )],
+    );
+    let outputs: Vec<_> = thread::scope(|scope| {
+        let downloads: Vec<_> = (0..2)
+            .map(|_| scope.spawn(|| fixture.run(&["download"])))
+            .collect();
+        downloads
+            .into_iter()
+            .map(|download| download.join().unwrap())
+            .collect()
+    });
+    for output in outputs {
+        assert!(output.status.success(), "{output:?}");
+    }
+    assert!(fixture.run(&["cache", "fsck"]).status.success());
+
+    // A killed download leaves its process ID in the lock file, and may
+    // leave the clone's files half updated.
+    let files: Vec<_> = WalkDir::new(fixture.path("repository_clones"))
+        .into_iter()
+        .map(|entry| entry.unwrap().into_path())
+        .collect();
+    let lock_file = files
+        .iter()
+        .find(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "lock")
+                && !path.to_string_lossy().ends_with(".json.lock")
+        })
+        .unwrap();
+    assert_eq!("", fs::read_to_string(lock_file).unwrap());
+    fs::write(lock_file, "4194304").unwrap();
+    let cached_file = files.iter().find(|path| path.ends_with("hello.c")).unwrap();
+    fs::write(cached_file, "int main(void) { return 1; }\n").unwrap();
+    fs::remove_dir_all(fixture.path("program_pairs")).unwrap();
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("was interrupted"),
+        "{output:?}"
+    );
+    assert_eq!(
+        "int main(void) { return 0; }\n",
+        fs::read_to_string(fixture.path("program_pairs/hello/c-program/hello.c")).unwrap()
+    );
+    assert_eq!("", fs::read_to_string(lock_file).unwrap());
+}
+
+/// Tests that clone durations are recorded, and that a later download of the
+/// same repositories estimates how long cloning them will take.
+#[test]
+fn test_download_estimates_duration() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        !String::from_utf8_lossy(&output.stderr).contains("expected to take"),
+        "{output:?}"
+    );
+    let timings_file = fixture.path("repository_clones/download-timings.json");
+    let timings = fs::read_to_string(&timings_file).unwrap();
+    let recorded: serde_json::Value = serde_json::from_str(&timings).unwrap();
+    assert_eq!(2, recorded["repositories"].as_object().unwrap().len());
+
+    // Cached repositories are not cloned again, so only uncached ones count.
+    fs::remove_dir_all(fixture.path("repository_clones")).unwrap();
+    fs::remove_dir_all(fixture.path("program_pairs")).unwrap();
+    fs::create_dir(fixture.path("repository_clones")).unwrap();
+    fs::write(&timings_file, timings).unwrap();
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Cloning is expected to take about"),
+        "{output:?}"
+    );
+}
+
+/// Tests that the post-download hook runs in each pair's directory, and that
+/// a failing hook fails its pair with the hook's output.
+#[test]
+fn test_download_runs_hook() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    let output = fixture.run(&["download", "--hook", "echo ran> hook.txt"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(fixture.path("program_pairs/hello/hook.txt").is_file());
+
+    let output = fixture.run(&["download", "--hook", "echo broken && exit 3"]);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Post-download hook 'echo broken && exit 3' failed")
+            && stderr.contains("    broken"),
+        "{output:?}"
+    );
+}
+
+/// Tests that `--link` links files into the pair and records it in the
+/// manifest, and that a later download without it copies them again.
+#[test]
+fn test_download_links_files() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    let materialization = || {
+        let manifest: Value = serde_json::from_str(
+            &fs::read_to_string(fixture.path("program_pairs/hello/manifest.json")).unwrap(),
+        )
+        .unwrap();
+        manifest["c_program"]["materialization"].clone()
+    };
+
+    let output = fixture.run(&["download", "--link"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!("hardlink", materialization());
+
+    if cfg!(unix) {
+        let output = fixture.run(&["download", "--link", "symbolic"]);
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!("symlink", materialization());
+        let link = fs::symlink_metadata(fixture.path("program_pairs/hello/c-program/hello.c"));
+        assert!(link.unwrap().file_type().is_symlink());
+    }
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!("copy", materialization());
+    let copy = fs::symlink_metadata(fixture.path("program_pairs/hello/c-program/hello.c"));
+    assert!(copy.unwrap().file_type().is_file());
+}
+
+/// Tests that `--link store` links identical files of different pairs to one
+/// copy in the store, and that `stats --dedup` reports the space saved.
+#[test]
+fn test_download_store_deduplicates_files() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let pairs: Vec<Value> = ["hello", "world"]
+        .iter()
+        .map(|name| {
+            pair(
+                name,
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            )
+        })
+        .collect();
+    fixture.metadata("pairs", &pairs);
+
+    let output = fixture.run(&["download", "--link", "store"]);
+    assert!(output.status.success(), "{output:?}");
+    let manifest: Value = serde_json::from_str(
+        &fs::read_to_string(fixture.path("program_pairs/world/manifest.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!("store", manifest["c_program"]["materialization"]);
+    // One object per distinct file: the C and Rust sources.
+    assert_eq!(
+        2,
+        fs::read_dir(fixture.path("store/objects")).unwrap().count()
+    );
+
+    if cfg!(unix) {
+        let output = fixture.run(&["stats", "--dedup"]);
+        assert!(output.status.success(), "{output:?}");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let saved = "fn main() {}\n".len() + "int main(void) { return 0; }\n".len();
+        assert!(
+            stdout.contains(&format!("Saved by links: {saved} B")),
+            "{stdout}"
+        );
+    }
+}
+
+/// Tests that pairs whose metadata, locked commits, and options are unchanged
+/// are not downloaded again unless forced.
+#[test]
+fn test_download_skips_up_to_date_pairs() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("hello", std::slice::from_ref(&hello));
+    let up_to_date = |output: &std::process::Output| {
+        String::from_utf8_lossy(&output.stderr).contains("'hello' is up to date")
+    };
+
+    let output = fixture.run(&["download"]);
+    assert!(
+        output.status.success() && !up_to_date(&output),
+        "{output:?}"
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success() && up_to_date(&output), "{output:?}");
+    let output = fixture.run(&["download", "--force"]);
+    assert!(
+        output.status.success() && !up_to_date(&output),
+        "{output:?}"
+    );
+
+    hello["program_description"] = json!("Says hello.");
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["download"]);
+    assert!(
+        output.status.success() && !up_to_date(&output),
+        "{output:?}"
+    );
+}
+
+/// Tests that metadata and `--layout` presets choose the program
+/// directories and whether subdirectories are kept, and that switching
+/// layouts removes the old directories.
+#[test]
+fn test_download_layout() {
+    let fixture = Fixture::new();
+    let (c_repository, _) = repositories(&fixture);
+    let rust_repository = fixture.repository(
+        "tree",
+        &[
+            ("src/main.rs", "fn main() {}\n"),
+            ("src/bin/tool.rs", "fn main() {}\n"),
+        ],
+    );
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    hello["rust_program"]["output_directory"] = json!("rs");
+    hello["rust_program"]["flatten"] = json!(false);
+    fixture.metadata("hello", &[hello]);
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        fixture
+            .path("program_pairs/hello/c-program/hello.c")
+            .is_file()
+    );
+    assert!(fixture.path("program_pairs/hello/rs/bin/tool.rs").is_file());
+    assert!(fixture.path("program_pairs/hello/rs/main.rs").is_file());
+
+    let output = fixture.run(&["download", "--layout", "by-language"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["c", "manifest.json", "provenance.json", "rust"],
+        file_names(&fixture, "program_pairs/hello")
+    );
+    assert_eq!(
+        vec!["bin-tool.rs", "main.rs"],
+        file_names(&fixture, "program_pairs/hello/rust")
+    );
+
+    let output = fixture.run(&["stats"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("hello"),
+        "{output:?}"
+    );
+}
+
+/// Tests that a pair whose provenance names files outside of its directory,
+/// through `..` or an absolute path, is not downloaded over them.
+#[test]
+fn test_download_rejects_escaping_provenance() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let outside = fixture.path("outside.txt");
+    fs::write(&outside, "outside\n").unwrap();
+    let provenance_path = fixture.path("program_pairs/hello/provenance.json");
+    let provenance = fs::read_to_string(&provenance_path).unwrap();
+    for path in ["../../outside.txt", outside.to_str().unwrap()] {
+        let mut escaping: Value = serde_json::from_str(&provenance).unwrap();
+        escaping["files"][0]["path"] = json!(path);
+        fs::write(&provenance_path, escaping.to_string()).unwrap();
+
+        let output = fixture.run(&["download", "--force", "--on-conflict", "backup"]);
+        assert!(!output.status.success(), "{output:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("escapes"),
+            "{output:?}"
+        );
+        assert_eq!("outside\n", fs::read_to_string(&outside).unwrap());
+        assert!(!fixture.path("outside.txt.orig").exists());
+    }
+}
+
+/// Tests that the build files in the metadata are copied, and the closest
+/// `Makefile` and `Cargo.toml` too with `--include-build-files`.
+#[test]
+fn test_download_build_files() {
+    let fixture = Fixture::new();
+    let (_, rust_repository) = repositories(&fixture);
+    let c_repository = fixture.repository(
+        "make",
+        &[
+            ("Makefile", "all:\n"),
+            ("config/config.h", "#define VERSION 1\n"),
+            ("src/hello.c", "int main(void) { return 0; }\n"),
+        ],
+    );
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["src/hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    hello["c_program"]["build_files"] = json!(["config/config.h"]);
+    fixture.metadata("hello", &[hello]);
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["config.h", "hello.c"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+    assert_eq!(
+        vec!["main.rs"],
+        file_names(&fixture, "program_pairs/hello/rust-program")
+    );
+
+    let output = fixture.run(&["download", "--include-build-files"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["Makefile", "config.h", "hello.c"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+    assert_eq!(
+        vec!["Cargo.toml", "main.rs"],
+        file_names(&fixture, "program_pairs/hello/rust-program")
+    );
+    let manifest: Value = serde_json::from_str(
+        &fs::read_to_string(fixture.path("program_pairs/hello/manifest.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        json!(["Cargo.toml"]),
+        manifest["rust_program"]["build_files"]
+    );
+}
+
+/// Tests that patches from the metadata are applied to the copied files, are
+/// applied again once edited, and fail the pair if they do not apply.
+#[test]
+fn test_download_patches() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    hello["c_program"]["patches"] = json!(["patches/hello.patch"]);
+    fixture.metadata("hello", &[hello]);
+    let patch = fixture.path("patches/hello.patch");
+    fs::create_dir_all(patch.parent().unwrap()).unwrap();
+    fs::write(
+        &patch,
+        "--- a/hello.c\n+++ b/hello.c\n@@ -1 +1 @@\n\
+         -int main(void) { return 0; }\n+int main(void) { return 1; }\n",
+    )
+    .unwrap();
+
+    let output = fixture.run(&["download", "--link", "hard"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        "int main(void) { return 1; }\n",
+        fs::read_to_string(fixture.path("program_pairs/hello/c-program/hello.c")).unwrap()
+    );
+    let manifest: Value = serde_json::from_str(
+        &fs::read_to_string(fixture.path("program_pairs/hello/manifest.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        json!(["patches/hello.patch"]),
+        manifest["c_program"]["patches"]
+    );
+    assert_eq!(json!("copy"), manifest["c_program"]["materialization"]);
+    // The patched file was replaced rather than changed through its link.
+    let cached_file = WalkDir::new(fixture.path("repository_clones"))
+        .into_iter()
+        .map(|entry| entry.unwrap().into_path())
+        .find(|path| path.ends_with("hello.c"))
+        .unwrap();
+    assert_eq!(
+        "int main(void) { return 0; }\n",
+        fs::read_to_string(cached_file).unwrap()
+    );
+
+    fs::write(
+        &patch,
+        "--- a/hello.c\n+++ b/hello.c\n@@ -1 +1 @@\n\
+         -int main(void) { return 2; }\n+int main(void) { return 1; }\n",
+    )
+    .unwrap();
+    let output = fixture.run(&["download"]);
+    assert!(!output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Failed to apply patch 'patches/hello.patch'"),
+        "{output:?}"
+    );
+}
+
+/// Tests that `--transform` rewrites the placed sources, leaves other files
+/// and the clone alone, and records the transforms in the provenance.
+#[test]
+fn test_download_transforms_sources() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository(
+        "c",
+        &[
+            (
+                "hello.c",
+                "/* Says hello. */\r\nint main(void) {\r\n\treturn 0; // done\r\n}\r\n",
+            ),
+            ("Makefile", "hello: hello.c # build\r\n"),
+        ],
+    );
+    let rust_repository =
+        fixture.repository("rust", &[("src/main.rs", "fn main() {} // \"//\"\n")]);
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    hello["c_program"]["build_files"] = json!(["Makefile"]);
+    fixture.metadata("hello", &[hello]);
+
+    let output = fixture.run(&[
+        "download",
+        "--link",
+        "hard",
+        "--transform",
+        "strip-comments,normalize-line-endings,expand-tabs",
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    let read = |path: &str| fs::read_to_string(fixture.path(path)).unwrap();
+    assert_eq!(
+        " \nint main(void) {\n        return 0;\n}\n",
+        read("program_pairs/hello/c-program/hello.c")
+    );
+    assert_eq!(
+        "fn main() {}\n",
+        read("program_pairs/hello/rust-program/main.rs")
+    );
+    assert_eq!(
+        "hello: hello.c # build\r\n",
+        read("program_pairs/hello/c-program/Makefile")
+    );
+
+    let provenance: Value =
+        serde_json::from_str(&read("program_pairs/hello/provenance.json")).unwrap();
+    let transforms = |path: &str| {
+        provenance["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|file| file["path"] == path)
+            .map(|file| file["transforms"].clone())
+            .unwrap()
+    };
+    assert_eq!(
+        json!(["strip-comments", "normalize-line-endings", "expand-tabs"]),
+        transforms("c-program/hello.c")
+    );
+    assert_eq!(Value::Null, transforms("c-program/Makefile"));
+    let manifest: Value = serde_json::from_str(&read("program_pairs/hello/manifest.json")).unwrap();
+    assert_eq!(json!("copy"), manifest["c_program"]["materialization"]);
+
+    // The transformed file was replaced rather than changed through its link.
+    let cached_file = WalkDir::new(fixture.path("repository_clones"))
+        .into_iter()
+        .map(|entry| entry.unwrap().into_path())
+        .find(|path| path.ends_with("hello.c"))
+        .unwrap();
+    assert!(
+        fs::read_to_string(cached_file)
+            .unwrap()
+            .starts_with("/* Says hello. */")
+    );
+}
+
+/// Tests that binary files in `source_paths` directories are skipped and
+/// reported unless `--allow-binary` is passed.
+#[test]
+fn test_download_skips_binary_files() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository("c", &[("hello.c", "int main(void) { return 0; }\n")]);
+    let rust_repository = fixture.repository(
+        "rust",
+        &[
+            ("src/main.rs", "fn main() {}\n"),
+            ("src/hello.o", "\u{7f}ELF\u{2}\u{1}\u{1}\u{0}"),
+        ],
+    );
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    let output = fixture.run(&["download", "--include-extension", "o"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Skipped binary file 'src/hello.o' (pass --allow-binary to copy it)"),
+        "{output:?}"
+    );
+    assert_eq!(
+        vec!["main.rs"],
+        file_names(&fixture, "program_pairs/hello/rust-program")
+    );
+
+    let output = fixture.run(&["download", "--include-extension", "o", "--allow-binary"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["hello.o", "main.rs"],
+        file_names(&fixture, "program_pairs/hello/rust-program")
+    );
+}
+
+/// Tests that pairs are downloaded sorted by name across metadata files, and
+/// that `--shuffle` orders them the same way for the same seed.
+#[test]
+fn test_download_orders_pairs() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let named = |name: &str| {
+        pair(
+            name,
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )
+    };
+    fixture.metadata("a", &[named("zeta")]);
+    fixture.metadata("b", &[named("mid"), named("alpha")]);
+    let downloaded = |arguments: &[&str]| {
+        let output = fixture.run(arguments);
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .filter_map(|line| line.strip_prefix("Downloaded '"))
+            .map(|name| name.trim_end_matches('\'').to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec!["alpha", "mid", "zeta"], downloaded(&["download"]));
+    let shuffled = downloaded(&["download", "--force", "--shuffle", "--seed", "1"]);
+    assert_eq!(vec!["zeta", "mid", "alpha"], shuffled);
+    assert_eq!(
+        shuffled,
+        downloaded(&["download", "--force", "--shuffle", "--seed", "1"])
+    );
+    assert!(!fixture.run(&["download", "--seed", "1"]).status.success());
+}
+
+/// Tests that `--profile` downloads the pairs of the metadata files and the
+/// pair names a profile lists, and rejects unknown profiles and pairs and
+/// metadata files outside the metadata directories.
+#[test]
+fn test_download_profile() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let named = |name: &str| {
+        pair(
+            name,
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )
+    };
+    fixture.metadata("a", &[named("alpha"), named("beta")]);
+    fixture.metadata("b", &[named("gamma"), named("delta")]);
+    let profiles = json!({
+        "profiles": {
+            "tiny": {
+                "metadata_files": ["./metadata/individual/a.json"],
+                "pairs": ["delta"]
+            },
+            "typo": { "pairs": ["epsilon"] },
+            "stray": { "metadata_files": ["metadata/a.json"] }
+        }
+    });
+    fs::copy(
+        fixture.path("metadata/individual/a.json"),
+        fixture.path("metadata/a.json"),
+    )
+    .unwrap();
+    fs::write(fixture.path("metadata/profiles.json"), profiles.to_string()).unwrap();
+
+    let output = fixture.run(&["download", "--profile", "tiny"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["alpha", "beta", "delta"],
+        file_names(&fixture, "program_pairs")
+    );
+
+    let output = fixture.run(&["download", "--profile", "huge"]);
+    assert!(!output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("available profiles: demo, full, stray, tiny, typo"),
+        "{output:?}"
+    );
+    let output = fixture.run(&["download", "--profile", "typo"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("names unknown pairs: epsilon"),
+        "{output:?}"
+    );
+    let output = fixture.run(&["download", "--profile", "stray"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("lists 'metadata/a.json', which is not in"),
+        "{output:?}"
+    );
+}
+
+/// Tests that pairs in the skip list are reported as skipped rather than
+/// downloaded or counted as failed.
+#[test]
+fn test_download_skips_skiplist_pairs() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "pairs",
+        &[
+            pair(
+                "hello",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            ),
+            pair(
+                "missing",
+                (&c_repository, &["missing.c"]),
+                (&rust_repository, &["src"]),
+            ),
+        ],
+    );
+    fs::write(
+        fixture.path("metadata/skiplist.json"),
+        r#"{"pairs": [{"program_name": "missing", "reason": "upstream_deleted", "note": "gone"}]}"#,
+    )
+    .unwrap();
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Skipped 'missing' (upstream deleted: gone)"),
+        "{output:?}"
+    );
+    assert!(fixture.path("program_pairs/hello/manifest.json").is_file());
+    assert!(!fixture.path("program_pairs/missing").exists());
+
+    fs::write(
+        fixture.path("metadata/skiplist.json"),
+        r#"{"pairs": [{"program_name": "missing", "reason": "bored"}]}"#,
+    )
+    .unwrap();
+    assert_eq!(Some(3), fixture.run(&["download"]).status.code());
+}
+
+/// Tests that metadata violating the schema is reported through the exit
+/// code, after the valid pairs are downloaded, or before anything is
+/// downloaded with `--strict`.
+#[test]
+fn test_download_rejects_invalid_metadata() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let mut invalid = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    invalid["feature_relationship"] = Value::from("identical");
+    fixture.metadata("invalid", &[invalid]);
+    fixture.metadata(
+        "valid",
+        &[pair(
+            "valid",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    let output = fixture.run(&["download", "--strict"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("invalid.json"), "{output:?}");
+    assert!(stderr.contains("feature_relationship"), "{output:?}");
+    assert!(!fixture.path("program_pairs/valid").exists());
+
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("feature_relationship"),
+        "{output:?}"
+    );
+    assert!(fixture.path("program_pairs/valid/manifest.json").is_file());
+}
+
+/// Tests that a download estimated to need more disk space than is available
+/// is aborted before cloning, unless `--skip-space-check` is passed.
+#[test]
+fn test_download_checks_disk_space() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    // Enriched metadata is keyed by normalized URL, which drops the scheme.
+    let key = c_repository.url.trim_start_matches("file://");
+    let enriched = json!({
+        "repositories": { key: { "stars": 0, "size": u64::MAX / 4 } },
+    });
+    fs::create_dir_all(fixture.path("metadata/enriched")).unwrap();
+    fs::write(
+        fixture.path("metadata/enriched/repositories.json"),
+        enriched.to_string(),
+    )
+    .unwrap();
+
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(5), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("--skip-space-check"),
+        "{output:?}"
+    );
+    assert!(!fixture.path("repository_clones").exists());
+
+    let output = fixture.run(&["download", "--skip-space-check"]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+/// Tests that `doctor` passes in a working environment, warns when the
+/// schema file differs from the built-in schema, and suggests a fix when the
+/// schema given with `--schema` is missing.
+#[test]
+fn test_doctor() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    let output = fixture.run(&["doctor"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("[ok] Connectivity"),
+        "{output:?}"
+    );
+    let output = fixture.run(&["doctor", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let findings = result["findings"].as_array().unwrap();
+    assert!(
+        findings.iter().all(|finding| finding["status"] != "failed"),
+        "{result}"
+    );
+    assert!(
+        findings
+            .iter()
+            .any(|finding| finding["check"] == "Connectivity"),
+        "{result}"
+    );
+
+    fs::write(fixture.path("metadata/metadata.schema.json"), "{}").unwrap();
+    let output = fixture.run(&["doctor"]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("differs from the schema built into"),
+        "{output:?}"
+    );
+
+    let output = fixture.run(&["doctor", "--schema", "missing.schema.json"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("fix: Pass a valid schema"),
+        "{output:?}"
+    );
+}
+
+/// Tests that metadata is validated against the built-in schema when the
+/// schema file is missing, and against the file given with `--schema`.
+#[test]
+fn test_download_schema() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    fs::remove_file(fixture.path("metadata/metadata.schema.json")).unwrap();
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    // A schema that rejects everything fails the metadata.
+    fs::write(fixture.path("strict.schema.json"), r#"{"not": {}}"#).unwrap();
+    let output = fixture.run(&["lint", "--schema", "strict.schema.json"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+}
+
+/// Tests that `corpus.toml` sets defaults for the download options, and that
+/// unknown keys are reported.
+#[test]
+fn test_download_reads_config_file() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    fs::write(
+        fixture.path("corpus.toml"),
+        "[download]\nhook = \"echo ran> hook.txt\"\n",
+    )
+    .unwrap();
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(fixture.path("program_pairs/hello/hook.txt").is_file());
+
+    fs::write(
+        fixture.path("corpus.toml"),
+        "[download]\nhooks = \"true\"\n",
+    )
+    .unwrap();
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("unknown field `hooks`"),
+        "{output:?}"
+    );
+}
+
+/// Tests that flags given as `=false` turn off options `corpus.toml` turns
+/// on, and that `corpus.toml` is read from the root given with `--root`.
+#[test]
+fn test_download_overrides_config_file() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let mut invalid = pair(
+        "invalid",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    invalid["feature_relationship"] = Value::from("identical");
+    fixture.metadata("invalid", &[invalid]);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    fs::write(
+        fixture.path("corpus.toml"),
+        "[download]\nstrict = true\nhook = \"echo ran> hook.txt\"\n",
+    )
+    .unwrap();
+
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert!(!fixture.path("program_pairs/hello").exists());
+
+    fs::create_dir(fixture.path("elsewhere")).unwrap();
+    let output = fixture.run_in("elsewhere", &["--root", "..", "download", "--strict=false"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert!(fixture.path("program_pairs/hello/hook.txt").is_file());
+}
+
+/// Tests that `--tag` downloads and lists only the pairs with one of the
+/// tags, and that the tags are recorded in the manifest.
+#[test]
+fn test_download_filters_by_tag() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    hello["tags"] = json!(["cli-tool", "compression"]);
+    let mut util = pair(
+        "util",
+        (&c_repository, &["lib"]),
+        (&rust_repository, &["src"]),
+    );
+    util["tags"] = json!(["network"]);
+    fixture.metadata("pairs", &[hello, util]);
+
+    let output = fixture.run(&["list", "--tag", "compression"]);
+    assert!(output.status.success(), "{output:?}");
+    let listing = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        listing.contains("[tags: cli-tool, compression]"),
+        "{output:?}"
+    );
+    assert!(!listing.contains("util"), "{output:?}");
+
+    let output = fixture.run(&["download", "--tag", "cli-tool,compression"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!fixture.path("program_pairs/util").exists());
+    let manifest: Value = serde_json::from_str(
+        &fs::read_to_string(fixture.path("program_pairs/hello/manifest.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(json!(["cli-tool", "compression"]), manifest["tags"]);
+}
+
+/// Tests that `report` renders an index page linking to a page per pair
+/// with its files and their escaped source text.
+#[test]
+fn test_report_html() {
+    let fixture = Fixture::new();
+    let c_repository =
+        fixture.repository("c", &[("hello.c", "int main(void) { return 1 < 2; }\n")]);
+    let rust_repository = fixture.repository("rust", &[("src/main.rs", "fn main() {}\n")]);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "c++hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let output = fixture.run(&["report", "--format", "html"]);
+    assert!(output.status.success(), "{output:?}");
+    let index = fs::read_to_string(fixture.path("site/index.html")).unwrap();
+    assert!(
+        index.contains("href=\"pairs/c%2B%2Bhello.html\""),
+        "{index}"
+    );
+    assert!(index.contains("The c++hello program."), "{index}");
+    let page = fs::read_to_string(fixture.path("site/pairs/c++hello.html")).unwrap();
+    assert!(page.contains("<code>hello.c</code>"), "{page}");
+    assert!(page.contains("<code>main.rs</code>"), "{page}");
+    assert!(page.contains("return 1 &lt; 2;"), "{page}");
+    assert!(fixture.path("site/style.css").is_file());
+
+    // The pages of an earlier report are replaced, but files it did not
+    // write are never removed.
+    let output = fixture.run(&["report"]);
+    assert!(output.status.success(), "{output:?}");
+    fs::write(fixture.path("site/pairs/notes.txt"), "notes\n").unwrap();
+    let output = fixture.run(&["report"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+    assert!(fixture.path("site/pairs/notes.txt").is_file());
+    assert!(fixture.path("site/pairs/c++hello.html").is_file());
+}
+
+/// Tests that `show` prints a C file next to the Rust file defining the
+/// same functions, or one after the other with `--layout unified`.
+#[test]
+fn test_show() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository(
+        "c",
+        &[
+            ("hello.c", "int main(void) {\n\treturn greet();\n}\n"),
+            ("greet.c", "int greet(void) { return 0; }\n"),
+        ],
+    );
+    let rust_repository = fixture.repository(
+        "rust",
+        &[
+            ("src/main.rs", "fn main() {}\n"),
+            ("src/util.rs", "pub fn greet() -> i32 {\n    0\n}\n"),
+        ],
+    );
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c", "greet.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let output = fixture.run(&["show", "hello", "greet.c", "--width", "80"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].contains("c-program/greet.c"), "{stdout}");
+    assert!(lines[0].contains("rust-program/util.rs"), "{stdout}");
+    assert!(
+        lines[1].starts_with("1 int greet(void) { return 0; }"),
+        "{stdout}"
+    );
+    assert!(
+        lines[1].ends_with("│ 1 pub fn greet() -> i32 {"),
+        "{stdout}"
+    );
+    assert!(
+        lines.iter().all(|line| line.chars().count() <= 80),
+        "{stdout}"
+    );
+
+    let output = fixture.run(&["show", "hello", "--layout", "unified"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout
+            .starts_with("==> c-program/hello.c <==\n1 int main(void) {\n2     return greet();\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("==> rust-program/main.rs <==\n1 fn main() {}\n"),
+        "{stdout}"
+    );
+
+    let output = fixture.run(&["show", "hello", "missing.c"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+}
+
+/// Tests that `analyze --translation-origin` records c2rust output as
+/// machine-translated, and that `export` includes the classification.
+#[test]
+fn test_analyze_translation_origin() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository("c", &[("hello.c", "int main(void) { return 0; }\n")]);
+    let rust_repository = fixture.repository(
+        "rust",
+        &[(
+            "src/main.rs",
+            "#![allow(mutable_transmutes)]\n\
+             pub unsafe extern \"C\" fn main_0() -> libc::c_int {\n    return 0;\n}\n",
+        )],
+    );
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let output = fixture.run(&["analyze", "--translation-origin"]);
+    assert!(output.status.success(), "{output:?}");
+    let analysis = fs::read_to_string(fixture.path("program_pairs/analysis.json")).unwrap();
+    let analysis: serde_json::Value = serde_json::from_str(&analysis).unwrap();
+    assert_eq!(
+        "machine_translated",
+        analysis["hello"]["translation_origin"]["origin"]
+    );
+
+    let output = fixture.run(&["export"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("\"translation_origin\":\"machine_translated\""),
+        "{stdout}"
+    );
+
+    // Only the pairs of the requested origins are exported.
+    let output = fixture.run(&["export", "--origin", "machine-translated"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"program_name\":\"hello\""));
+    let output = fixture.run(&["export", "--origin", "hand-written", "--origin", "mixed"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stdout.is_empty(), "{output:?}");
+
+    let output = fixture.run(&["analyze", "missing", "--translation-origin"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+
+    // Unreadable analyses fail the export rather than being left out.
+    fs::write(fixture.path("program_pairs/analysis.json"), "{").unwrap();
+    let output = fixture.run(&["export"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("recorded analyses"),
+        "{output:?}"
+    );
+}
+
+/// Tests that `export --format alignment` aligns each C file with the Rust
+/// file most alike in name and functions.
+#[test]
+fn test_export_alignment() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository(
+        "c",
+        &[
+            (
+                "src/cat.c",
+                "static int cat_file(void) { return 0; }\nint main(void) { return cat_file(); }\n",
+            ),
+            ("src/util.c", "void *xmalloc(int size) { return 0; }\n"),
+            ("src/zzz.c", "int zzz(void) { return 0; }\n"),
+        ],
+    );
+    let rust_repository = fixture.repository(
+        "rust",
+        &[
+            (
+                "src/uu/cat/src/main.rs",
+                "fn cat_file() {}\npub fn uumain() { cat_file() }\n",
+            ),
+            ("src/util.rs", "pub fn xmalloc(size: usize) {}\n"),
+            ("src/extra.rs", "pub fn extra() {}\n"),
+        ],
+    );
+    fixture.metadata(
+        "cat",
+        &[pair(
+            "cat",
+            (&c_repository, &["src"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let output = fixture.run(&["export", "--format", "alignment"]);
+    assert!(output.status.success(), "{output:?}");
+    let record: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!("cat", record["program_name"]);
+    assert_eq!(
+        json!([
+            {
+                "c_file": "src/cat.c",
+                "rust_file": "src/uu/cat/src/main.rs",
+                "confidence": 0.667,
+                "name_similarity": 1.0,
+                "function_similarity": 0.333,
+            },
+            {
+                "c_file": "src/util.c",
+                "rust_file": "src/util.rs",
+                "confidence": 1.0,
+                "name_similarity": 1.0,
+                "function_similarity": 1.0,
+            },
+            {
+                "c_file": "src/zzz.c",
+                "rust_file": null,
+                "confidence": 0.0,
+                "name_similarity": 0.0,
+                "function_similarity": null,
+            },
+        ]),
+        record["alignments"]
+    );
+    assert_eq!(json!(["src/extra.rs"]), record["unaligned_rust_files"]);
+}
+
+/// Tests that `index` loads the pairs of the metadata and the files of the
+/// downloaded pairs into a database, and replaces it when run again.
+#[cfg(feature = "index")]
+#[test]
+fn test_index() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    let mut later = hello.clone();
+    later["program_name"] = Value::from("later");
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    let output = fixture.run(&["index", "--db", "corpus.db"]);
+    assert!(output.status.success(), "{output:?}");
+
+    fixture.metadata("later", &[later]);
+    let output = fixture.run(&["index", "--db", "corpus.db"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!fixture.path(".corpus.db.tmp").exists());
+
+    let connection = rusqlite::Connection::open(fixture.path("corpus.db")).unwrap();
+    let mut statement = connection
+        .prepare("SELECT program_name, downloaded FROM pairs ORDER BY program_name")
+        .unwrap();
+    let pairs: Vec<(String, bool)> = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        vec![("hello".to_string(), true), ("later".to_string(), false)],
+        pairs
+    );
+    let (path, sha): (String, String) = connection
+        .query_row(
+            "SELECT files.path, commits.sha FROM files \
+             JOIN programs ON programs.id = files.program_id \
+             JOIN commits ON commits.id = files.commit_id \
+             WHERE programs.program_name = 'hello' AND programs.language = 'c'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!("c-program/hello.c", path);
+    assert_eq!(40, sha.len());
+}
+
+/// Tests that `analyze --classify` records each pair's domain and tags the
+/// pair with it.
+#[test]
+fn test_analyze_classify() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository(
+        "c",
+        &[(
+            "hello.c",
+            "int deflate_block(void);\nint main(void) { return deflate_block(); }\n",
+        )],
+    );
+    let rust_repository = fixture.repository(
+        "rust",
+        &[("src/main.rs", "fn main() {\n    gzip::inflate();\n}\n")],
+    );
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let output = fixture.run(&["analyze", "--classify"]);
+    assert!(output.status.success(), "{output:?}");
+    let analysis = fs::read_to_string(fixture.path("program_pairs/analysis.json")).unwrap();
+    let analysis: Value = serde_json::from_str(&analysis).unwrap();
+    assert_eq!("compression", analysis["hello"]["domain"]["domain"]);
+    let manifest = fs::read_to_string(fixture.path("program_pairs/hello/manifest.json")).unwrap();
+    let manifest: Value = serde_json::from_str(&manifest).unwrap();
+    assert_eq!(json!(["compression"]), manifest["tags"]);
+
+    // The domain tag does not make the pair out of date.
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("'hello' is up to date"),
+        "{output:?}"
+    );
+    let output = fixture.run(&["download", "--tag", "compression"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("'hello' is up to date"),
+        "{output:?}"
+    );
+
+    let output = fixture.run(&["list", "--tag", "compression"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("hello"), "{stdout}");
+    let output = fixture.run(&["list", "--tag", "networking"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("hello"), "{stdout}");
+
+    let output = fixture.run(&["analyze"]);
+    assert!(!output.status.success(), "{output:?}");
+}
+
+/// Tests that `audit` passes reachable links and reports dead repositories,
+/// branches, and documentation pages.
+#[test]
+fn test_audit() {
+    use std::io::{BufRead, BufReader, Write};
+
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+
+    // Serve documentation under `/docs/` and nothing else.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request_line = String::new();
+            BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            let status = if request_line.contains(" /docs/") {
+                "200 OK"
+            } else {
+                "404 Not Found"
+            };
+            let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n");
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    let documented = |mut pair: Value, path: &str| {
+        for program in ["c_program", "rust_program"] {
+            pair[program]["documentation_url"] = json!(format!("http://127.0.0.1:{port}{path}"));
+        }
+        pair
+    };
+    let hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("hello", &[documented(hello, "/docs/hello")]);
+    let output = fixture.run(&["audit"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let missing = FixtureRepository {
+        url: format!("{}-missing", c_repository.url),
+        head: String::new(),
+    };
+    let mut gone = pair(
+        "gone",
+        (&missing, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    gone["rust_program"]["branch"] = json!("gone");
+    fixture.metadata("gone", &[documented(gone, "/gone")]);
+    let output = fixture.run(&["audit"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(&format!("'{}' is dead", missing.url)),
+        "{stdout}"
+    );
+    assert!(stdout.contains("branch 'gone' not found"), "{stdout}");
+    assert!(
+        stdout.contains("/gone' is dead (404 Not Found)"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("3 dead, 0 renamed, 0 archived"), "{stdout}");
+
+    let output = fixture.run(&["audit", "--json"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let problems = result["problems"].as_array().unwrap();
+    assert_eq!(3, problems.len(), "{result}");
+    assert!(
+        problems
+            .iter()
+            .all(|problem| problem["problem"] == "dead" && problem["pairs"].is_array()),
+        "{result}"
+    );
+}
+
+/// Tests that `outdated` lists the pairs whose upstream moved past the
+/// commit they were copied from, with the number of commits in between.
+#[test]
+fn test_outdated() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let output = fixture.run(&["outdated"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Checked 1 program pairs: 0 outdated"),
+        "{stdout}"
+    );
+
+    fixture.commit("c", &[("hello.c", "int main(void) { return 1; }\n")]);
+    let head = fixture.commit("c", &[("hello.c", "int main(void) { return 2; }\n")]);
+    let output = fixture.run(&["outdated"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("(an unknown number of commits behind)"),
+        "{stdout}"
+    );
+    // The check does not fetch the new head into any cached clone.
+    let head_oid = git2::Oid::from_str(&head).unwrap();
+    let mut clones = 0;
+    for entry in WalkDir::new(fixture.path("repository_clones")) {
+        if let Ok(clone) = git2::Repository::open(entry.unwrap().path()) {
+            assert!(clone.find_commit(head_oid).is_err());
+            clones += 1;
+        }
+    }
+    assert!(clones > 0);
+
+    // Once the clone has fetched the head, the commits behind are counted.
+    let output = fixture.run(&["update", "--refresh-lock"]);
+    assert!(output.status.success(), "{output:?}");
+    let output = fixture.run(&["outdated"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(&format!(
+            "'hello' is outdated: '{}' moved from {} to {} (2 commits behind)",
+            c_repository.url,
+            &c_repository.head[..7],
+            &head[..7]
+        )),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("Checked 1 program pairs: 1 outdated"),
+        "{stdout}"
+    );
+}
+
+/// Tests that `discover` revalidates the cached response of a repeated
+/// search with its `ETag`, and that the token is not sent over plain HTTP.
+#[test]
+fn test_discover_revalidates_cached_responses() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        sync::{
+            Arc,
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+        },
+    };
+
+    let fixture = Fixture::new();
+
+    // Serve one search result, answering requests for the cached version with
+    // `304 Not Modified`.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let revalidated = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&revalidated);
+    let authorized = Arc::new(AtomicBool::new(false));
+    let saw_token = Arc::clone(&authorized);
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = Vec::new();
+            let mut reader = BufReader::new(&stream);
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+                request.push(line.to_lowercase());
+            }
+            if request
+                .iter()
+                .any(|line| line.starts_with("authorization:"))
+            {
+                saw_token.store(true, Ordering::SeqCst);
+            }
+            let response = if !request[0].starts_with("get /search/repositories?") {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string()
+            } else if request
+                .iter()
+                .any(|line| line == "if-none-match: \"v1\"\r\n")
+            {
+                counter.fetch_add(1, Ordering::SeqCst);
+                "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\n\r\n".to_string()
+            } else {
+                let body = json!({ "items": [{
+                    "full_name": "someone/hello-rs",
+                    "html_url": "https://github.com/someone/hello-rs",
+                    "description": "A Rust rewrite of hello",
+                    "default_branch": "main",
+                    "fork": false,
+                    "archived": false,
+                }]})
+                .to_string();
+                format!(
+                    "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\n\r\n{body}",
+                    body.len()
+                )
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    let api_url = format!("http://127.0.0.1:{port}");
+    let environment = [
+        ("GITHUB_API_URL", api_url.as_str()),
+        ("GITHUB_TOKEN", "secret"),
+    ];
+    for revalidations in [0, 1] {
+        let output = fixture.run_with_env(&["discover", "--seed", "hello"], &environment);
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(revalidations, revalidated.load(Ordering::SeqCst));
+        let drafts = fs::read_to_string(fixture.path("metadata/drafts/discovered.json")).unwrap();
+        assert_eq!(
+            1,
+            drafts
+                .matches("https://github.com/someone/hello-rs\"")
+                .count(),
+            "{drafts}"
+        );
+    }
+    assert!(!authorized.load(Ordering::SeqCst));
+
+    // Drafts edited since are kept, and the candidate is not added again.
+    let drafts_path = fixture.path("metadata/drafts/discovered.json");
+    let edited = fs::read_to_string(&drafts_path)
+        .unwrap()
+        .replace("A Rust rewrite of hello", "Checked");
+    fs::write(&drafts_path, &edited).unwrap();
+    let output = fixture.run_with_env(&["discover", "--seed", "hello"], &environment);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(edited, fs::read_to_string(&drafts_path).unwrap());
+
+    // A file that is not a drafts file is not overwritten.
+    fs::write(&drafts_path, "[]").unwrap();
+    let output = fixture.run_with_env(&["discover", "--seed", "hello"], &environment);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    assert_eq!("[]", fs::read_to_string(&drafts_path).unwrap());
+}
+
+/// Tests that `enrich` records the stars, license, and last commit of each
+/// repository, reports the ones it cannot query, and that `list --sort
+/// stars` orders pairs by their combined stars.
+#[test]
+fn test_enrich_and_list_by_stars() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let mut pairs = Vec::new();
+    for (name, c_url, rust_url) in [
+        (
+            "alpha",
+            "https://github.com/gnu/alpha",
+            "https://github.com/someone/alpha-rs",
+        ),
+        (
+            "beta",
+            "https://github.com/gnu/beta",
+            "https://github.com/someone/beta-rs",
+        ),
+        (
+            "gamma",
+            "https://github.com/gnu/gamma",
+            "https://github.com/someone/missing",
+        ),
+    ] {
+        let mut entry = pair(
+            name,
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src/main.rs"]),
+        );
+        entry["c_program"]["repository_url"] = json!(c_url);
+        entry["rust_program"]["repository_url"] = json!(rust_url);
+        pairs.push(entry);
+    }
+    fixture.metadata("pairs", &pairs);
+
+    let repository = |stars: u64| {
+        json!({
+            "stargazers_count": stars,
+            "size": 10,
+            "default_branch": "main",
+            "language": "C",
+            "license": { "spdx_id": "GPL-3.0" },
+        })
+    };
+    let commits = json!([{ "commit": { "committer": { "date": "2024-01-02T03:04:05Z" } } }]);
+    let api_url = api_server(&[
+        ("/repos/gnu/alpha", repository(5)),
+        ("/repos/gnu/alpha/commits", commits.clone()),
+        ("/repos/someone/alpha-rs", repository(1)),
+        ("/repos/someone/alpha-rs/commits", commits.clone()),
+        ("/repos/gnu/beta", repository(2)),
+        ("/repos/gnu/beta/commits", commits.clone()),
+        ("/repos/someone/beta-rs", repository(40)),
+        ("/repos/someone/beta-rs/commits", commits.clone()),
+        ("/repos/gnu/gamma", repository(3)),
+        ("/repos/gnu/gamma/commits", commits),
+    ]);
+    let environment = [("GITHUB_API_URL", api_url.as_str())];
+
+    // The missing repository fails, but the rest are recorded.
+    let output = fixture.run_with_env(&["enrich"], &environment);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+    let enriched: Value = serde_json::from_str(
+        &fs::read_to_string(fixture.path("metadata/enriched/repositories.json")).unwrap(),
+    )
+    .unwrap();
+    let repositories = enriched["repositories"].as_object().unwrap();
+    assert_eq!(5, repositories.len(), "{enriched}");
+    let beta = &repositories["github.com/someone/beta-rs"];
+    assert_eq!(40, beta["stars"]);
+    assert_eq!("GPL-3.0", beta["license"]);
+    assert_eq!("2024-01-02T03:04:05Z", beta["last_commit_date"]);
+    assert!(!repositories.contains_key("github.com/someone/missing"));
+
+    let output = fixture.run(&["list", "--sort", "stars", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    let listed: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let listed = listed["pairs"].as_array().unwrap();
+    let names: Vec<&str> = listed
+        .iter()
+        .map(|pair| pair["program_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(vec!["beta", "alpha", "gamma"], names);
+    assert_eq!(2, listed[0]["c_stars"]);
+    assert_eq!(40, listed[0]["rust_stars"]);
+    assert_eq!(3, listed[2]["c_stars"]);
+    assert_eq!(Value::Null, listed[2]["rust_stars"]);
+}
+
+/// Tests that `lint --semantic` flags a pair whose Rust repository binds the
+/// C program rather than rewriting it, and passes a rewrite.
+#[test]
+fn test_lint_semantic() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository(
+        "c",
+        &[(
+            "hello.c",
+            "/* Usage: hello [--loud] [--quiet] [--name NAME] */\nint main(void) { return 0; }\n",
+        )],
+    );
+    let rewrite = fixture.repository(
+        "rewrite",
+        &[
+            ("Cargo.toml", "[package]\nname = \"hello\"\n"),
+            (
+                "src/main.rs",
+                "// Usage: hello [--loud] [--quiet] [--name NAME]\nfn main() {}\n",
+            ),
+        ],
+    );
+    let wrapper = fixture.repository(
+        "wrapper",
+        &[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"greeting-sys\"\nlinks = \"greeting\"\n",
+            ),
+            ("src/lib.rs", "pub fn greet() {}\n"),
+        ],
+    );
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rewrite, &["src"]),
+        )],
+    );
+    assert!(fixture.run(&["download"]).status.success());
+    let output = fixture.run(&["lint", "--semantic"]);
+    assert!(output.status.success(), "{output:?}");
+
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&wrapper, &["src"]),
+        )],
+    );
+    fs::remove_dir_all(fixture.path("program_pairs/hello")).unwrap();
+    assert!(fixture.run(&["download"]).status.success());
+    let output = fixture.run(&["lint", "--semantic"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("(hello): suspicious pair"), "{stdout}");
+    assert!(stdout.contains("native library 'greeting'"), "{stdout}");
+    assert!(stdout.contains("never mention 'hello'"), "{stdout}");
+    assert!(stdout.contains("only 0 of the C program's 3"), "{stdout}");
+}
+
+/// Tests that `lint` reports every malformed value the schema cannot
+/// express, of every pair, in one run.
+#[test]
+fn test_lint_reports_every_invalid_value() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[
+            pair(
+                "hello",
+                (&c_repository, &["../hello.c"]),
+                (&rust_repository, &["/src"]),
+            ),
+            pair(
+                "valid",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            ),
+            pair(
+                "escape",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src/../.."]),
+            ),
+        ],
+    );
+
+    let output = fixture.run(&["lint"]);
+    assert_eq!(Some(3), output.status.code(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("3 invalid values"), "{stdout}");
+    for path in ["../hello.c", "/src", "src/../.."] {
+        assert!(
+            stdout.contains(&format!("Invalid source path '{path}'")),
+            "{stdout}"
+        );
+    }
+
+    let output = fixture.run(&["lint", "--json"]);
+    let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let invalid_values = &result["invalid_files"][0]["invalid_values"];
+    assert_eq!(3, invalid_values.as_array().unwrap().len(), "{result}");
+    assert_eq!("hello", invalid_values[0]["program_name"]);
+    assert_eq!("escape", invalid_values[2]["program_name"]);
+}
+
+/// Tests that `snapshot` records the commits of downloaded pairs, refuses to
+/// overwrite a release, and that `changelog` lists the pairs added since.
+#[test]
+fn test_snapshot_and_changelog() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    assert!(fixture.run(&["download"]).status.success());
+    let output = fixture.run(&["snapshot", "1.0"]);
+    assert!(output.status.success(), "{output:?}");
+    let snapshot = fs::read_to_string(fixture.path("releases/corpus-v1.0.json")).unwrap();
+    let snapshot: serde_json::Value = serde_json::from_str(&snapshot).unwrap();
+    assert_eq!(
+        rust_repository.head,
+        snapshot["pairs"]["hello"]["rust_commit"]
+    );
+    assert_eq!(Some(1), fixture.run(&["snapshot", "1.0"]).status.code());
+    assert_eq!(Some(1), fixture.run(&["snapshot", "one"]).status.code());
+
+    fixture.metadata(
+        "goodbye",
+        &[pair(
+            "goodbye",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    assert!(fixture.run(&["snapshot", "1.1"]).status.success());
+    let output = fixture.run(&["changelog", "1.0", "v1.1"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("# Corpus v1.1\n"), "{stdout}");
+    assert!(
+        stdout.contains("1 added, 0 removed, and 0 changed since v1.0"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("- `goodbye`: The goodbye program."),
+        "{stdout}"
+    );
+}
+
+/// Tests that `publish` pushes one snapshot of the downloaded pairs per
+/// change, building on the remote's history from a fresh checkout.
+#[test]
+fn test_publish() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    let output = fixture.run(&["publish", "--remote", "unused"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+    assert!(fixture.run(&["download"]).status.success());
+
+    let remote = git2::Repository::init_bare(fixture.path("remote.git")).unwrap();
+    let path = fixture
+        .path("remote.git")
+        .to_string_lossy()
+        .replace('\\', "/");
+    let url = format!("file:///{}", path.trim_start_matches('/'));
+    let output = fixture.run(&["publish", "--remote", &url]);
+    assert!(output.status.success(), "{output:?}");
+    let head = remote.find_reference("refs/heads/main").unwrap();
+    let tree = head.peel_to_tree().unwrap();
+    assert!(tree.get_path(Path::new("hello/manifest.json")).is_ok());
+
+    // Another checkout of the corpus publishes on top of the remote.
+    fs::remove_dir_all(fixture.path("publish.git")).unwrap();
+    let output = fixture.run(&["publish", "--remote", &url]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("did not change"),
+        "{output:?}"
+    );
+
+    fixture.metadata(
+        "goodbye",
+        &[pair(
+            "goodbye",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    assert!(fixture.run(&["download"]).status.success());
+    let output = fixture.run(&["publish", "--remote", &url, "--message", "Add goodbye"]);
+    assert!(output.status.success(), "{output:?}");
+    let head = remote
+        .find_reference("refs/heads/main")
+        .unwrap()
+        .peel_to_commit()
+        .unwrap();
+    assert_eq!(Some("Add goodbye"), head.message());
+    assert_eq!(1, head.parent_count());
+    assert!(head.tree().unwrap().get_name("goodbye").is_some());
+
+    // Files linked into the clone cache are published with their content.
+    if cfg!(unix) {
+        let output = fixture.run(&["download", "--force", "--link", "symbolic"]);
+        assert!(output.status.success(), "{output:?}");
+        let output = fixture.run(&["publish", "--remote", &url]);
+        assert!(output.status.success(), "{output:?}");
+        let tree = remote
+            .find_reference("refs/heads/main")
+            .unwrap()
+            .peel_to_tree()
+            .unwrap();
+        let entry = tree.get_path(Path::new("hello/c-program/hello.c")).unwrap();
+        assert_eq!(0o100_644, entry.filemode());
+        let blob = remote.find_blob(entry.id()).unwrap();
+        assert_eq!(
+            fs::read(fixture.path("program_pairs/hello/c-program/hello.c")).unwrap(),
+            blob.content()
+        );
+    }
+}
+
+/// Tests that `download` locks repositories to the commits they resolved to,
+/// keeps checking those out as the repositories move on, and that
+/// `update --refresh-lock` advances them.
+#[test]
+fn test_lock_pins_commits() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+    assert!(fixture.run(&["download"]).status.success());
+    let lock = fs::read_to_string(fixture.path("corpus.lock")).unwrap();
+    assert!(lock.contains(&c_repository.head), "{lock}");
+    assert!(lock.contains(&rust_repository.head), "{lock}");
+
+    let latest = fixture.commit("rust", &[("src/main.rs", "fn main() { greet(); }\n")]);
+    for directory in ["program_pairs", "repository_clones"] {
+        fs::remove_dir_all(fixture.path(directory)).unwrap();
+    }
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        "fn main() {}\n",
+        fs::read_to_string(fixture.path("program_pairs/hello/rust-program/main.rs")).unwrap()
+    );
+
+    let output = fixture.run(&["update", "--refresh-lock"]);
+    assert!(output.status.success(), "{output:?}");
+    let lock = fs::read_to_string(fixture.path("corpus.lock")).unwrap();
+    assert!(lock.contains(&latest), "{lock}");
+    assert!(!lock.contains(&rust_repository.head), "{lock}");
+    fs::remove_dir_all(fixture.path("program_pairs")).unwrap();
+    assert!(fixture.run(&["download"]).status.success());
+    assert_eq!(
+        "fn main() { greet(); }\n",
+        fs::read_to_string(fixture.path("program_pairs/hello/rust-program/main.rs")).unwrap()
+    );
+}
+
+/// Tests that `rename` renames a pair in its metadata, the skip list, and
+/// `program_pairs/`, so the renamed pair and the others of its metadata file
+/// stay up to date.
+#[test]
+fn test_rename() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[
+            pair(
+                "hello",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            ),
+            pair(
+                "other",
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            ),
+        ],
+    );
+    assert!(fixture.run(&["download"]).status.success());
+
+    let output = fixture.run(&["rename", "hello", "other"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+    let output = fixture.run(&["rename", "missing", "greet"]);
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+
+    fs::write(
+        fixture.path("metadata/skiplist.json"),
+        r#"{"pairs": [{"program_name": "hello", "reason": "bored"}]}"#,
+    )
+    .unwrap();
+    let output = fixture.run(&["rename", "hello", "greet"]);
+    assert!(output.status.success(), "{output:?}");
+    let metadata = fs::read_to_string(fixture.path("metadata/individual/hello.json")).unwrap();
+    assert!(
+        metadata.contains(r#""program_name": "greet""#),
+        "{metadata}"
+    );
+    assert!(!metadata.contains(r#""hello""#), "{metadata}");
+    let skiplist = fs::read_to_string(fixture.path("metadata/skiplist.json")).unwrap();
+    assert!(skiplist.contains(r#""greet""#), "{skiplist}");
+    assert!(!fixture.path("program_pairs/hello").exists());
+    let manifest: Value = serde_json::from_str(
+        &fs::read_to_string(fixture.path("program_pairs/greet/manifest.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!("greet", manifest["program_name"]);
+
+    fs::remove_file(fixture.path("metadata/skiplist.json")).unwrap();
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("'greet' is up to date"),
+        "{output:?}"
+    );
+    // The rewritten file reads back as the same pairs.
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("'other' is up to date"),
+        "{output:?}"
+    );
+}
+
+/// Tests that `rename` only changes the pair's name in a project metadata
+/// file, which keeps its `project_information` and the order of its keys.
+#[test]
+fn test_rename_project_metadata() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let program = |repository: &FixtureRepository| {
+        json!({
+            "documentation_url": "https://example.com",
+            "repository_url": repository.url,
+        })
+    };
+    let project = |name: &str| {
+        json!({
+            "project_information": {
+                "program_name": "greetings",
+                "translation_tools": ["manual"],
+                "feature_relationship": "rust_equivalent_to_c",
+                "c_program": program(&c_repository),
+                "rust_program": program(&rust_repository),
+            },
+            "pairs": [{
+                "program_name": name,
+                "program_description": "The hello program.",
+                "c_program": { "source_paths": ["hello.c"] },
+                "rust_program": { "source_paths": ["src"] },
+            }],
+        })
+    };
+    fs::create_dir_all(fixture.path("metadata/project")).unwrap();
+    let path = fixture.path("metadata/project/greetings.json");
+    fs::write(
+        &path,
+        serde_json::to_string_pretty(&project("hello")).unwrap(),
+    )
+    .unwrap();
+
+    let output = fixture.run(&["rename", "hello", "greet"]);
+    assert!(output.status.success(), "{output:?}");
+    let metadata = fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        project("greet"),
+        serde_json::from_str::<Value>(&metadata).unwrap()
+    );
+    assert!(
+        metadata.find("project_information") < metadata.find("pairs"),
+        "{metadata}"
+    );
+}
+
+/// Tests that a program with a `branch` is downloaded from that branch with
+/// either git backend, even from a clone shared with the default branch, and
+/// that the branch is locked and refreshed separately and recorded in the
+/// provenance.
+#[test]
+fn test_download_branch() {
+    for backend in ["libgit2", "cli"] {
+        let fixture = Fixture::new();
+        let (c_repository, rust_repository) = repositories(&fixture);
+        let rewrite = fixture.branch_commit(
+            "rust",
+            "rust-rewrite",
+            &[("src/main.rs", "fn main() { rewrite(); }\n")],
+        );
+        let hello = pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        );
+        let mut rewritten = pair(
+            "rewritten",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        );
+        rewritten["rust_program"]["branch"] = json!("rust-rewrite");
+        fixture.metadata("hello", &[hello, rewritten]);
+
+        let output = fixture.run(&["download", "--git-backend", backend]);
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            "fn main() {}\n",
+            fs::read_to_string(fixture.path("program_pairs/hello/rust-program/main.rs")).unwrap()
+        );
+        assert_eq!(
+            "fn main() { rewrite(); }\n",
+            fs::read_to_string(fixture.path("program_pairs/rewritten/rust-program/main.rs"))
+                .unwrap()
+        );
+        let lock = fs::read_to_string(fixture.path("corpus.lock")).unwrap();
+        assert!(lock.contains("branch = \"rust-rewrite\""), "{lock}");
+        assert!(lock.contains(&rewrite), "{lock}");
+        assert!(lock.contains(&rust_repository.head), "{lock}");
+        let provenance: Value = serde_json::from_str(
+            &fs::read_to_string(fixture.path("program_pairs/rewritten/provenance.json")).unwrap(),
+        )
+        .unwrap();
+        let main = provenance["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|file| file["path"] == "rust-program/main.rs")
+            .unwrap();
+        assert_eq!(json!("rust-rewrite"), main["branch"]);
+        assert_eq!(json!(rewrite), main["commit"]);
+
+        let latest = fixture.branch_commit(
+            "rust",
+            "rust-rewrite",
+            &[("src/main.rs", "fn main() { rewrite_again(); }\n")],
+        );
+        let output = fixture.run(&["update", "--refresh-lock"]);
+        assert!(output.status.success(), "{output:?}");
+        let lock = fs::read_to_string(fixture.path("corpus.lock")).unwrap();
+        assert!(lock.contains(&latest), "{lock}");
+        assert!(lock.contains(&rust_repository.head), "{lock}");
+    }
+}
+
+/// Tests that tarball and file sources are fetched, verified against their
+/// checksums, and copied like repositories, and that a source whose checksum
+/// does not match is rejected.
+#[test]
+fn test_download_tarball_and_file_sources() {
+    use flate2::{Compression, write::GzEncoder};
+    use sha2::{Digest, Sha256};
+
+    let fixture = Fixture::new();
+    fs::create_dir_all(fixture.path("sources")).unwrap();
+    let source = |name: &str, bytes: &[u8]| {
+        let path = fixture.path("sources").join(name);
+        fs::write(&path, bytes).unwrap();
+        let path = path.to_string_lossy().replace('\\', "/");
+        let url = format!("file:///{}", path.trim_start_matches('/'));
+        (url, format!("{:x}", Sha256::digest(bytes)))
+    };
+
+    // A release tarball with every file in a top-level directory.
+    let mut tarball = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let contents = b"int main(void) { return 0; }\n";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    tarball
+        .append_data(&mut header, "hello-1.0/src/hello.c", &contents[..])
+        .unwrap();
+    let (tarball_url, tarball_checksum) = source(
+        "hello-1.0.tar.gz",
+        &tarball.into_inner().unwrap().finish().unwrap(),
+    );
+    let (file_url, file_checksum) = source("main.rs", b"fn main() {}\n");
+
+    let tarball = FixtureRepository {
+        url: tarball_url,
+        head: String::new(),
+    };
+    let file = FixtureRepository {
+        url: file_url,
+        head: String::new(),
+    };
+    let mut hello = pair("hello", (&tarball, &["src"]), (&file, &["main.rs"]));
+    hello["c_program"]["source_type"] = json!("tarball");
+    hello["c_program"]["checksum"] = json!(tarball_checksum);
+    hello["rust_program"]["source_type"] = json!("http_file");
+    hello["rust_program"]["checksum"] = json!(file_checksum.to_uppercase());
+    fixture.metadata("hello", &[hello.clone()]);
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["hello.c"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+    assert_eq!(
+        vec!["main.rs"],
+        file_names(&fixture, "program_pairs/hello/rust-program")
+    );
+    let provenance =
+        fs::read_to_string(fixture.path("program_pairs/hello/provenance.json")).unwrap();
+    assert!(provenance.contains(&tarball_checksum), "{provenance}");
+
+    // Cached sources are managed like clones: they are kept while the
+    // metadata references them, and removed once it no longer does.
+    let output = fixture.run(&["cache", "size"]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("in 2 clones"),
+        "{output:?}"
+    );
+    assert!(fixture.run(&["delete", "--unused"]).status.success());
+    assert!(fixture.run(&["download", "--offline"]).status.success());
+    let metadata_file = fixture.path("metadata/individual/hello.json");
+    let metadata = fs::read_to_string(&metadata_file).unwrap();
+    fs::remove_file(&metadata_file).unwrap();
+    let output = fixture.run(&["delete", "--unused"]);
+    assert!(output.status.success(), "{output:?}");
+    let output = fixture.run(&["cache", "size"]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("in 0 clones"),
+        "{output:?}"
+    );
+    fs::write(&metadata_file, metadata).unwrap();
+
+    // A source must have a checksum, and the checksum must match.
+    let mut unverified = hello.clone();
+    unverified["rust_program"]
+        .as_object_mut()
+        .unwrap()
+        .remove("checksum");
+    fixture.metadata("hello", &[unverified]);
+    assert_eq!(Some(3), fixture.run(&["download"]).status.code());
+
+    let mut tampered = hello;
+    tampered["program_name"] = json!("tampered");
+    tampered["c_program"]["checksum"] = json!("0".repeat(64));
+    fixture.metadata("hello", &[tampered]);
+    fs::remove_dir_all(fixture.path("repository_clones")).unwrap();
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Checksum mismatch"),
+        "{output:?}"
+    );
+}
+
+/// Tests that a program with `release_tag` and `asset` is downloaded from the
+/// release asset's URL in its repository and verified by checksum.
+#[test]
+fn test_download_release_asset() {
+    use flate2::{Compression, write::GzEncoder};
+    use sha2::{Digest, Sha256};
+
+    let fixture = Fixture::new();
+    let (_, rust_repository) = repositories(&fixture);
+    let mut tarball = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let contents = b"int main(void) { return 0; }\n";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    tarball
+        .append_data(&mut header, "hello-1.0/src/hello.c", &contents[..])
+        .unwrap();
+    let bytes = tarball.into_inner().unwrap().finish().unwrap();
+    let releases = fixture.path("hello/releases/download/v1.0");
+    fs::create_dir_all(&releases).unwrap();
+    fs::write(releases.join("hello-1.0.tar.gz"), &bytes).unwrap();
+
+    // Release assets are only named in GitHub repositories, so the asset is
+    // found in a local mirror once the repository fails.
+    let path = fixture.path("hello").to_string_lossy().replace('\\', "/");
+    let release = FixtureRepository {
+        url: "https://github.com/c-rust-program-pairs-missing/hello".to_string(),
+        head: String::new(),
+    };
+    let mut hello = pair("hello", (&release, &["src"]), (&rust_repository, &["src"]));
+    hello["c_program"]["mirror_urls"] =
+        json!([format!("file:///{}.git", path.trim_start_matches('/'))]);
+    hello["c_program"]["release_tag"] = json!("v1.0");
+    hello["c_program"]["asset"] = json!("hello-1.0.tar.gz");
+    hello["c_program"]["checksum"] = json!(format!("{:x}", Sha256::digest(&bytes)));
+    fixture.metadata("hello", &[hello.clone()]);
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["hello.c"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+
+    // A release asset needs both fields and cannot be a git source.
+    let mut incomplete = hello.clone();
+    incomplete["c_program"]
+        .as_object_mut()
+        .unwrap()
+        .remove("release_tag");
+    fixture.metadata("hello", &[incomplete]);
+    assert_eq!(Some(3), fixture.run(&["download"]).status.code());
+    let mut elsewhere = hello.clone();
+    elsewhere["c_program"]["repository_url"] = json!("https://gitlab.com/gnu/hello");
+    fixture.metadata("hello", &[elsewhere]);
+    assert_eq!(Some(3), fixture.run(&["download"]).status.code());
+    hello["c_program"]["source_type"] = json!("git");
+    fixture.metadata("hello", &[hello]);
+    assert_eq!(Some(3), fixture.run(&["download"]).status.code());
+}
+
+/// Tests that a pair translated to Go is placed in `go-program` with Go's
+/// extensions, left out by `--languages c,rust`, and rejected if a language
+/// is on the wrong side of the pair.
+#[test]
+fn test_download_languages() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let go_repository = fixture.repository(
+        "go",
+        &[
+            ("src/main.go", "package main\n\nfunc main() {}\n"),
+            ("src/go.mod", "module hello\n"),
+            ("src/main.rs", "fn main() {}\n"),
+        ],
+    );
+    let rust = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    let mut go = pair(
+        "hello-go",
+        (&c_repository, &["hello.c"]),
+        (&go_repository, &["src"]),
+    );
+    go["rust_program"]["language"] = json!("go");
+    fixture.metadata("hello", &[rust, go.clone()]);
+
+    let output = fixture.run(&["download", "--languages", "c,rust"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(fixture.path("program_pairs/hello/rust-program").is_dir());
+    assert!(!fixture.path("program_pairs/hello-go").exists());
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["go.mod", "main.go"],
+        file_names(&fixture, "program_pairs/hello-go/go-program")
+    );
+    let manifest =
+        fs::read_to_string(fixture.path("program_pairs/hello-go/manifest.json")).unwrap();
+    assert!(
+        manifest.contains("\"directory\": \"go-program\""),
+        "{manifest}"
+    );
+
+    go["c_program"]["language"] = json!("rust");
+    fixture.metadata("hello", &[go]);
+    assert_eq!(Some(3), fixture.run(&["download"]).status.code());
+}
+
+/// Tests that the Rust variants of a pair are placed in
+/// `rust-program-<label>`, listed, counted by `stats`, and removed once they
+/// leave the metadata.
+#[test]
+fn test_download_rust_variants() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let variant_repository = fixture.repository("variant", &[("src/lib.rs", "pub fn run() {}\n")]);
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    let variant = pair(
+        "hello",
+        (&c_repository, &[]),
+        (&variant_repository, &["src"]),
+    );
+    hello["rust_variants"] = json!([{"label": "hello-rs", "program": variant["rust_program"]}]);
+    fixture.metadata("hello", &[hello.clone()]);
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["lib.rs"],
+        file_names(&fixture, "program_pairs/hello/rust-program-hello-rs")
+    );
+    let output = fixture.run(&["list"]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("[variants: hello-rs]"),
+        "{output:?}"
+    );
+    let output = fixture.run(&["--json", "stats"]);
+    let stats: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        json!("rust-program-hello-rs"),
+        stats["pairs"][0]["rust_variants"]["hello-rs"]["directory"]
+    );
+
+    let mut repeated = hello.clone();
+    let variants = repeated["rust_variants"].as_array_mut().unwrap();
+    variants.push(variants[0].clone());
+    fixture.metadata("hello", &[repeated]);
+    assert_eq!(Some(3), fixture.run(&["download"]).status.code());
+
+    // A variant may not share a directory with the pair's other programs,
+    // whether it is named or the default one.
+    let mut colliding = hello.clone();
+    colliding["rust_variants"][0]["program"]["output_directory"] = json!("rust-program");
+    fixture.metadata("hello", &[colliding]);
+    assert_eq!(Some(3), fixture.run(&["download"]).status.code());
+    let mut colliding = hello.clone();
+    colliding["c_program"]["output_directory"] = json!("rust-program-hello-rs");
+    fixture.metadata("hello", &[colliding]);
+    assert_eq!(Some(3), fixture.run(&["download"]).status.code());
+
+    hello.as_object_mut().unwrap().remove("rust_variants");
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        !fixture
+            .path("program_pairs/hello/rust-program-hello-rs")
+            .exists()
+    );
+}
+
+/// Tests that files changed since their pair was downloaded are only
+/// overwritten with `--force`, and are kept or backed up by the other
+/// conflict policies.
+#[test]
+fn test_download_conflict_policy() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("hello", std::slice::from_ref(&hello));
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let source = fixture.path("program_pairs/hello/c-program/hello.c");
+    let original = fs::read_to_string(&source).unwrap();
+    let changed = "int main(void) { return 1; }\n";
+    fs::write(&source, changed).unwrap();
+
+    // The metadata changed, so the pair is downloaded again, but not over
+    // the changed file without --force.
+    hello["program_description"] = json!("Says hello.");
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["download"]);
+    assert!(!output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("c-program/hello.c"),
+        "{output:?}"
+    );
+    assert_eq!(changed, fs::read_to_string(&source).unwrap());
+    let output = fixture.run(&["download", "--force", "--on-conflict", "error"]);
+    assert!(!output.status.success(), "{output:?}");
+
+    let output = fixture.run(&["download", "--on-conflict", "skip"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(changed, fs::read_to_string(&source).unwrap());
+
+    let output = fixture.run(&["download", "--force", "--on-conflict", "backup"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(original, fs::read_to_string(&source).unwrap());
+    assert_eq!(
+        changed,
+        fs::read_to_string(source.with_extension("c.orig")).unwrap()
+    );
+
+    fs::write(&source, changed).unwrap();
+    let output = fixture.run(&["download", "--force"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(original, fs::read_to_string(&source).unwrap());
+}
+
+/// Tests that a changed file that cannot be backed up is still in place
+/// after the download fails, since conflicts are resolved before the staged
+/// pair replaces the previous download.
+#[test]
+fn test_download_conflict_keeps_changes_on_failure() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("hello", std::slice::from_ref(&hello));
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let source = fixture.path("program_pairs/hello/c-program/hello.c");
+    let changed = "int main(void) { return 1; }\n";
+    fs::write(&source, changed).unwrap();
+    // A directory where the backup goes makes writing it fail.
+    let blocker = source.with_extension("c.orig");
+    fs::create_dir(&blocker).unwrap();
+    fs::write(blocker.join("notes.txt"), "Notes\n").unwrap();
+
+    hello["program_description"] = json!("Says hello.");
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["download", "--force", "--on-conflict", "backup"]);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+    assert_eq!(changed, fs::read_to_string(&source).unwrap());
+    assert!(blocker.join("notes.txt").is_file());
+    assert!(!fixture.path("program_pairs/.staging").exists());
+}
+
+/// Tests that a program name leading out of `program_pairs/` is rejected
+/// as invalid metadata, leaving the directory it names alone.
+#[test]
+fn test_download_rejects_escaping_program_names() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let victim = fixture.path("victim");
+    fs::create_dir(&victim).unwrap();
+    fs::write(victim.join("keep.txt"), "Keep me\n").unwrap();
+
+    for program_name in ["../victim", &victim.to_string_lossy()] {
+        fixture.metadata(
+            "evil",
+            &[pair(
+                program_name,
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            )],
+        );
+        let output = fixture.run(&["download"]);
+        assert_eq!(Some(3), output.status.code(), "{output:?}");
+        assert_eq!(vec!["keep.txt"], file_names(&fixture, "victim"));
+    }
+}
+
+/// Tests that a pair that fails to download leaves neither a half-populated
+/// directory nor its staging directory behind, and keeps its previous
+/// download.
+#[test]
+fn test_download_stages_pairs() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    let broken = pair(
+        "broken",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["missing.rs"]),
+    );
+    fixture.metadata("hello", &[hello.clone(), broken]);
+
+    let output = fixture.run(&["download"]);
+    assert_eq!(Some(2), output.status.code(), "{output:?}");
+    assert_eq!(vec!["hello"], file_names(&fixture, "program_pairs"));
+
+    let mut renamed = hello;
+    renamed["rust_program"]["source_paths"] = json!(["missing.rs"]);
+    fixture.metadata("hello", &[renamed]);
+    let output = fixture.run(&["download"]);
+    assert!(!output.status.success(), "{output:?}");
+    assert_eq!(
+        vec!["main.rs"],
+        file_names(&fixture, "program_pairs/hello/rust-program")
+    );
+    assert!(!fixture.path("program_pairs/.staging").exists());
+}
+
+/// Tests that downloading a pair again keeps the files in its directory
+/// that no download placed, such as notes and backups of changed files.
+#[test]
+fn test_download_keeps_untracked_files() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+
+    fs::write(fixture.path("program_pairs/hello/NOTES.md"), "notes\n").unwrap();
+    fs::write(
+        fixture.path("program_pairs/hello/c-program/hello.c.orig"),
+        "old\n",
+    )
+    .unwrap();
+    let output = fixture.run(&["download", "--force"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        "notes\n",
+        fs::read_to_string(fixture.path("program_pairs/hello/NOTES.md")).unwrap()
+    );
+    assert_eq!(
+        vec!["hello.c", "hello.c.orig"],
+        file_names(&fixture, "program_pairs/hello/c-program")
+    );
+    assert_eq!(vec!["hello"], file_names(&fixture, "program_pairs"));
+}
+
+/// Tests that every download records its metrics, counting clones made on
+/// the first download and cache hits on the next.
+#[test]
+fn test_download_records_run_metrics() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let hello = pair(
+        "hello",
+        (&c_repository, &["hello.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("hello", &[hello]);
+    let metrics = || -> Value {
+        serde_json::from_str(&fs::read_to_string(fixture.path("run-metrics.json")).unwrap())
+            .unwrap()
+    };
+
+    let output = fixture.run(&["download"]);
+    assert!(output.status.success(), "{output:?}");
+    let first = metrics();
+    assert_eq!(json!(1), first["pairs_succeeded"]);
+    assert_eq!(json!(0), first["pairs_failed"]);
+    assert_eq!(json!(2), first["cache_misses"]);
+    assert_eq!(json!(0.0), first["cache_hit_rate"]);
+    assert!(first["bytes_downloaded"].as_u64().unwrap() > 0);
+
+    let output = fixture.run(&["download", "--force"]);
+    assert!(output.status.success(), "{output:?}");
+    let second = metrics();
+    assert_eq!(json!(2), second["cache_hits"]);
+    assert_eq!(json!(0), second["bytes_downloaded"]);
+    assert_eq!(json!(1.0), second["cache_hit_rate"]);
+
+    // The clones are only written by the first download, while both copy
+    // the same files into the pair.
+    let copied = second["bytes_written"].as_u64().unwrap();
+    assert!(copied > 0);
+    assert_eq!(
+        first["bytes_downloaded"].as_u64().unwrap() + copied,
+        first["bytes_written"].as_u64().unwrap()
+    );
+}
+
+/// Tests that `metadata rust` prints a `rust_program` section for a crate,
+/// and fails for a crate that is not found or has no repository.
+#[test]
+fn test_metadata_rust() {
+    let fixture = Fixture::new();
+    let api_url = api_server(&[
+        (
+            "/api/v1/crates/hello-rs",
+            json!({ "crate": {
+                "name": "hello-rs",
+                "description": "  A Rust rewrite of hello\n",
+                "repository": "https://github.com/someone/hello-rs",
+                "documentation": null,
+                "max_stable_version": "1.2.0",
+                "max_version": "2.0.0-beta.1",
+            }}),
+        ),
+        (
+            "/api/v1/crates/unpublished",
+            json!({ "crate": {
+                "name": "unpublished",
+                "description": null,
+                "repository": null,
+                "documentation": null,
+                "max_stable_version": null,
+                "max_version": "0.1.0",
+            }}),
+        ),
+    ]);
+    let environment = [("CRATES_IO_URL", api_url.as_str())];
+
+    let output = fixture.run_with_env(&["metadata", "rust", "hello-rs"], &environment);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let section: Value =
+        serde_json::from_str(stdout.strip_prefix("\"rust_program\": ").unwrap()).unwrap();
+    assert_eq!(
+        json!({
+            "documentation_url": "https://docs.rs/hello-rs",
+            "repository_url": "https://github.com/someone/hello-rs",
+            "source_paths": ["src/"],
+        }),
+        section
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("hello-rs 1.2.0: A Rust rewrite of hello"),
+        "{output:?}"
+    );
+
+    for crate_name in ["missing", "unpublished"] {
+        let output = fixture.run_with_env(&["metadata", "rust", crate_name], &environment);
+        assert_eq!(Some(1), output.status.code(), "{output:?}");
+        assert!(output.stdout.is_empty(), "{output:?}");
+    }
+}
+
+/// Tests that `metadata discover` prints the sources of a C program, and
+/// clones a Rust repository to write its sources to the program's pair.
+#[test]
+fn test_metadata_discover() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.commit(
+        "rust",
+        &[
+            ("src/main.rs", "mod cli;\nfn main() {}\n"),
+            ("src/cli.rs", "pub fn run() {}\n"),
+        ],
+    );
+    fixture.commit(
+        "c",
+        &[(
+            "Makefile.am",
+            "bin_PROGRAMS = hello\nhello_SOURCES = hello.c lib/util.c\n",
+        )],
+    );
+    let c_checkout = fixture.path("repositories/c");
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    let output = fixture.run(&[
+        "metadata",
+        "discover",
+        "hello",
+        "--repo",
+        c_checkout.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        "\"source_paths\": [\n    \"hello.c\",\n    \"lib/util.c\"\n]\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // `--prune` drops the sources `main` never calls into, and `--crate-dir`
+    // finds a Rust crate within the repository.
+    let discover_in_checkout = |arguments: &[&str]| {
+        let mut command = vec!["metadata", "discover"];
+        command.extend(arguments);
+        command.extend(["--repo", c_checkout.to_str().unwrap()]);
+        let output = fixture.run(&command);
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    assert_eq!(
+        "\"source_paths\": [\n    \"hello.c\"\n]\n",
+        discover_in_checkout(&["hello", "--prune"])
+    );
+    fixture.commit(
+        "c",
+        &[
+            ("tools/hi/Cargo.toml", "[package]\nname = \"hi\"\n"),
+            ("tools/hi/src/main.rs", "fn main() {}\n"),
+        ],
+    );
+    assert_eq!(
+        "\"source_paths\": [\n    \"tools/hi/src/main.rs\"\n]\n",
+        discover_in_checkout(&["hi", "--crate-dir", "tools/hi"])
+    );
+
+    let metadata_file = "metadata/individual/hello.json";
+    let discover = |program_name: &str| {
+        fixture.run(&[
+            "metadata",
+            "discover",
+            program_name,
+            "--repo",
+            &rust_repository.url,
+            "--write",
+            metadata_file,
+        ])
+    };
+    let output = discover("hello");
+    assert!(output.status.success(), "{output:?}");
+    assert!(fixture.path("repository_clones").is_dir());
+    let metadata: Value =
+        serde_json::from_str(&fs::read_to_string(fixture.path(metadata_file)).unwrap()).unwrap();
+    assert_eq!(
+        json!(["src/cli.rs", "src/main.rs"]),
+        metadata["pairs"][0]["rust_program"]["source_paths"]
+    );
+    assert_eq!(
+        json!(["hello.c"]),
+        metadata["pairs"][0]["c_program"]["source_paths"]
+    );
+
+    let output = discover("world");
+    assert_eq!(Some(1), output.status.code(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no pair named 'world'"));
+
+    // A project file only gains the sources, keeping its structure.
+    let program = |repository: &FixtureRepository| {
+        json!({
+            "documentation_url": "https://example.com",
+            "repository_url": repository.url,
+        })
+    };
+    let project = |c_sources: Value| {
+        json!({
+            "project_information": {
+                "program_name": "greetings",
+                "translation_tools": ["manual"],
+                "feature_relationship": "rust_equivalent_to_c",
+                "c_program": program(&c_repository),
+                "rust_program": program(&rust_repository),
+            },
+            "pairs": [{
+                "program_name": "hello",
+                "program_description": "The hello program.",
+                "c_program": { "source_paths": c_sources },
+                "rust_program": { "source_paths": ["src"] },
+            }],
+        })
+    };
+    let project_file = fixture.path("metadata/project/greetings.json");
+    fs::create_dir_all(project_file.parent().unwrap()).unwrap();
+    fs::write(&project_file, project(json!(["hello.c"])).to_string()).unwrap();
+    let output = fixture.run(&[
+        "metadata",
+        "discover",
+        "hello",
+        "--repo",
+        c_checkout.to_str().unwrap(),
+        "--write",
+        project_file.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    let contents = fs::read_to_string(&project_file).unwrap();
+    assert_eq!(
+        project(json!(["hello.c", "lib/util.c"])),
+        serde_json::from_str::<Value>(&contents).unwrap()
+    );
+    assert!(
+        contents.find("project_information") < contents.find("pairs"),
+        "{contents}"
+    );
+}
+
+/// Tests that `functions --suggest` prints the C and Rust functions whose
+/// names match as `function_mappings` with their repository paths, which
+/// the pair's metadata then accepts.
+#[test]
+fn test_functions_suggest() {
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.commit(
+        "c",
+        &[(
+            "lib/util.c",
+            "void util(void) {}\n\nint parse_args(void) {\n    return 0;\n}\n",
+        )],
+    );
+    fixture.commit(
+        "rust",
+        &[(
+            "src/main.rs",
+            "fn main() {}\n\nfn parseArgs() {}\n\nfn helper() {}\n",
+        )],
+    );
+    let mut hello = pair(
+        "hello",
+        (&c_repository, &["hello.c", "lib/util.c"]),
+        (&rust_repository, &["src"]),
+    );
+    fixture.metadata("hello", &[hello.clone()]);
+    assert!(fixture.run(&["download"]).status.success());
+
+    let output = fixture.run(&["functions", "hello", "--suggest"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("2 of 3 C functions matched"),
+        "{output:?}"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mappings: Value = serde_json::from_str(
+        stdout
+            .strip_prefix("\"function_mappings\": ")
+            .unwrap_or_else(|| panic!("{stdout}")),
+    )
+    .unwrap();
+    assert_eq!(
+        json!([
+            {
+                "c": { "file": "hello.c", "function": "main", "start_line": 1, "end_line": 1 },
+                "rust": {
+                    "file": "src/main.rs",
+                    "function": "main",
+                    "start_line": 1,
+                    "end_line": 1,
+                },
+            },
+            {
+                "c": {
+                    "file": "lib/util.c",
+                    "function": "parse_args",
+                    "start_line": 3,
+                    "end_line": 5,
+                },
+                "rust": {
+                    "file": "src/main.rs",
+                    "function": "parseArgs",
+                    "start_line": 3,
+                    "end_line": 3,
+                },
+            },
+        ]),
+        mappings
+    );
+
+    hello["function_mappings"] = mappings;
+    fixture.metadata("hello", &[hello]);
+    let output = fixture.run(&["lint"]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+/// Tests that the server lists pairs, downloads them on request, and streams
+/// downloaded pairs as archives.
+#[test]
+fn test_serve() {
+    use std::{io::Read, time::Duration};
+
+    use flate2::read::GzDecoder;
+
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&c_repository, &["hello.c"]),
+            (&rust_repository, &["src"]),
+        )],
+    );
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let address = format!("127.0.0.1:{port}");
+    let mut server = fixture.spawn(&["serve", "--address", &address]);
+    let client = reqwest::blocking::Client::new();
+    let url = |path: &str| format!("http://{address}{path}");
+    let get = |path: &str| {
+        for _ in 0..100 {
+            if let Ok(response) = client.get(url(path)).send() {
+                return response;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        panic!("The server did not start");
+    };
+
+    let pairs: Value = get("/pairs").json().unwrap();
+    assert_eq!(json!("hello"), pairs[0]["program_name"]);
+    assert_eq!(json!(false), pairs[0]["downloaded"]);
+    assert_eq!(404, get("/pairs/hello/archive").status().as_u16());
+    assert_eq!(404, get("/pairs/missing").status().as_u16());
+    let metadata: Value = get("/pairs/hello").json().unwrap();
+    assert_eq!(
+        json!(c_repository.url),
+        metadata["c_program"]["repository_url"]
+    );
+
+    let response = client
+        .post(url("/downloads"))
+        .body(r#"{"pairs": ["hello"]}"#)
+        .send()
+        .unwrap();
+    assert_eq!(202, response.status().as_u16());
+    let mut status: Value = get("/downloads").json().unwrap();
+    for _ in 0..100 {
+        if status["running"] == json!(false) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+        status = get("/downloads").json().unwrap();
+    }
+    assert_eq!(json!(null), status["error"], "{status}");
+
+    let response = get("/pairs/hello/archive");
+    assert_eq!(200, response.status().as_u16());
+    let mut archive = tar::Archive::new(GzDecoder::new(response));
+    let mut main = String::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        if entry.path().unwrap().ends_with("rust-program/main.rs") {
+            entry.read_to_string(&mut main).unwrap();
+        }
+    }
+    assert_eq!("fn main() {}\n", main);
+
+    server.kill().unwrap();
+    server.wait().unwrap();
+}
+
+/// Tests that the async downloader downloads pairs sharing repositories
+/// concurrently, sending an event as each pair finishes.
+#[cfg(feature = "async")]
+#[test]
+fn test_download_async() {
+    use std::sync::mpsc;
+
+    use c_rust_program_pairs::{DownloadEvent, DownloadOptions, download_program_pairs_async};
+
+    let fixture = Fixture::new();
+    let (c_repository, rust_repository) = repositories(&fixture);
+    let pairs: Vec<Value> = ["hello", "world"]
+        .iter()
+        .map(|name| {
+            pair(
+                name,
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            )
+        })
+        .collect();
+    fixture.metadata("pairs", &pairs);
+
+    let mut options = DownloadOptions::default();
+    options.root = fixture.path("");
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let (sender, receiver) = mpsc::channel();
+    runtime
+        .block_on(download_program_pairs_async(
+            false,
+            options,
+            2,
+            Some(sender),
+        ))
+        .unwrap();
+
+    let mut finished: Vec<String> = receiver
+        .iter()
+        .filter_map(|event| match event {
+            DownloadEvent::PairFinished { program_name } => Some(program_name),
+            _ => None,
+        })
+        .collect();
+    finished.sort();
+    assert_eq!(vec!["hello", "world"], finished);
+    for name in ["hello", "world"] {
+        assert_eq!(
+            vec!["hello.c"],
+            file_names(&fixture, &format!("program_pairs/{name}/c-program"))
+        );
+    }
+    assert!(fixture.path("corpus.lock").exists());
+}
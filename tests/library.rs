@@ -0,0 +1,186 @@
+//! Tests of the library API, which run in this process rather than the
+//! binary.
+
+// Each test binary uses only some of the fixtures.
+#[allow(dead_code)]
+mod common;
+
+use std::{env, fs, sync::mpsc};
+
+use c_rust_program_pairs::{
+    Classify, DownloadEvent, Downloader, ErrorKind, WriterError, analysis::ast, parse,
+    schema::Language, write, write_document,
+};
+use common::{Fixture, pair};
+use serde_json::json;
+
+/// Tests that written metadata parses back into the metadata it was written
+/// from, and that invalid metadata is not written.
+#[test]
+fn test_write_round_trip() {
+    let fixture = Fixture::new();
+    let repository = fixture.repository("c", &[("hello.c", "int main(void) { return 0; }\n")]);
+    let mut hello = pair(
+        "hello",
+        (&repository, &["hello.c"]),
+        (&repository, &["src"]),
+    );
+    hello["tags"] = json!(["cli-tool"]);
+    hello["c_program"]["mirror_urls"] = json!(["https://example.com/mirror"]);
+    hello["c_program"]["clone_depth"] = json!(10);
+    hello["rust_program"]["include_extensions"] = json!(["rs", "in"]);
+    hello["rust_program"]["output_directory"] = json!("rust");
+    fixture.metadata("hello", &[hello]);
+
+    let metadata = parse(&fixture.path("metadata/individual/hello.json")).unwrap();
+    let written = fixture.path("written.json");
+    write(&written, &metadata).unwrap();
+    let contents = fs::read_to_string(&written).unwrap();
+    assert!(contents.contains("\"include_extensions\""));
+    assert!(!contents.contains("\"exclude_paths\""));
+    assert_eq!(
+        serde_json::to_value(&metadata).unwrap(),
+        serde_json::to_value(parse(&written).unwrap()).unwrap()
+    );
+
+    let mut invalid = metadata;
+    invalid.pairs[0].program_name.clear();
+    let error = write(&written, &invalid).unwrap_err();
+    assert!(matches!(error, WriterError::Invalid { .. }), "{error}");
+    assert_eq!(ErrorKind::InvalidMetadata, error.kind());
+    assert_eq!(contents, fs::read_to_string(&written).unwrap());
+}
+
+/// Tests that written pairs keep the field order of the repository's
+/// metadata files, and that project metadata is only written back as its
+/// own JSON document, keeping its `project_information`.
+#[test]
+fn test_write_project_metadata() {
+    let fixture = Fixture::new();
+    let repository = fixture.repository("c", &[("hello.c", "int main(void) { return 0; }\n")]);
+    fixture.metadata(
+        "hello",
+        &[pair(
+            "hello",
+            (&repository, &["hello.c"]),
+            (&repository, &["src"]),
+        )],
+    );
+    let metadata = parse(&fixture.path("metadata/individual/hello.json")).unwrap();
+    let written = fixture.path("written.json");
+    write(&written, &metadata).unwrap();
+    let contents = fs::read_to_string(&written).unwrap();
+    let positions: Vec<usize> = [
+        "program_name",
+        "program_description",
+        "translation_tools",
+        "feature_relationship",
+        "c_program",
+        "rust_program",
+    ]
+    .iter()
+    .map(|field| contents.find(&format!("\"{field}\"")).unwrap())
+    .collect();
+    assert!(positions.is_sorted(), "{contents}");
+
+    let program = json!({
+        "documentation_url": "https://example.com",
+        "repository_url": repository.url,
+    });
+    let mut project = json!({
+        "project_information": {
+            "program_name": "greetings",
+            "translation_tools": ["manual"],
+            "feature_relationship": "rust_equivalent_to_c",
+            "c_program": program,
+            "rust_program": program,
+        },
+        "pairs": [{
+            "program_name": "hello",
+            "program_description": "The hello program.",
+            "c_program": { "source_paths": ["hello.c"] },
+            "rust_program": { "source_paths": ["src"] },
+        }],
+    });
+    let project_file = fixture.path("project.json");
+    write_document(&project_file, &project).unwrap();
+    let error = write(&project_file, &parse(&project_file).unwrap()).unwrap_err();
+    assert!(matches!(error, WriterError::ProjectFile { .. }), "{error}");
+
+    project["pairs"][0]["tags"] = json!(["cli-tool"]);
+    write_document(&project_file, &project).unwrap();
+    let contents = fs::read_to_string(&project_file).unwrap();
+    assert_eq!(
+        project,
+        serde_json::from_str::<serde_json::Value>(&contents).unwrap()
+    );
+    assert!(
+        contents.find("project_information") < contents.find("pairs"),
+        "{contents}"
+    );
+    assert_eq!(
+        vec!["cli-tool"],
+        parse(&project_file).unwrap().pairs[0].tags
+    );
+}
+
+/// Tests that a downloader resolves its paths against its root without
+/// changing the working directory, and downloads only the pairs its filter
+/// selects.
+#[test]
+fn test_downloader() {
+    let fixture = Fixture::new();
+    let c_repository = fixture.repository("c", &[("hello.c", "int main(void) { return 0; }\n")]);
+    let rust_repository = fixture.repository("rust", &[("src/main.rs", "fn main() {}\n")]);
+    let pairs: Vec<_> = ["hello", "world"]
+        .iter()
+        .map(|name| {
+            pair(
+                name,
+                (&c_repository, &["hello.c"]),
+                (&rust_repository, &["src"]),
+            )
+        })
+        .collect();
+    fixture.metadata("pairs", &pairs);
+
+    let previous = env::current_dir().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    Downloader::new()
+        .root(fixture.path(""))
+        .jobs(2)
+        .filter(|pair| pair.program_name == "hello")
+        .events(sender)
+        .download()
+        .unwrap();
+    assert_eq!(previous, env::current_dir().unwrap());
+
+    let finished: Vec<String> = receiver
+        .iter()
+        .filter_map(|event| match event {
+            DownloadEvent::PairFinished { program_name } => Some(program_name),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vec!["hello"], finished);
+    assert!(
+        fixture
+            .path("program_pairs/hello/c-program/hello.c")
+            .is_file()
+    );
+    assert!(!fixture.path("program_pairs/world").exists());
+}
+
+/// Tests that library users can extract the functions of a source file
+/// through `analysis::ast`.
+#[test]
+fn test_analysis_ast() {
+    let functions = ast::extract(
+        "static int add(int a, int b)\n{\n    return a + b;\n}\n",
+        &Language::C,
+    );
+    assert_eq!(1, functions.len());
+    assert_eq!("add", functions[0].name);
+    assert_eq!("static int add(int a, int b)", functions[0].signature);
+    assert_eq!((1, 4), (functions[0].line, functions[0].end_line));
+}